@@ -0,0 +1,86 @@
+//! Counts the allocations of parsing, cloning and evaluating scripts, which the timings of
+//! `bench` do not show. Run with `cargo bench --bench alloc`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use aiscript_v0::{ast::Node, Interpreter, Parser};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Runs `f` and prints the number and the total size of the allocations it made.
+fn count<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+    let result = f();
+    println!(
+        "{name:<32} {:>10} allocations {:>12} bytes",
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        BYTES.load(Ordering::Relaxed) - bytes,
+    );
+    result
+}
+
+fn interpreter() -> Interpreter {
+    Interpreter::new(
+        [],
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        None,
+    )
+}
+
+fn parse(script: &str) -> Vec<Node> {
+    Parser::default().parse(script).unwrap()
+}
+
+/// A script creating a closure with a body of `lines` statements 1000 times.
+fn closures(lines: usize) -> String {
+    let body = "let a = [x, x + 1].map(@(v) { v * 2 })\n".repeat(lines);
+    format!("for let i, 1000 {{\n@f(x) {{\n{body}x\n}}\n}}\n")
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let script: String = (0..1000)
+        .map(|i| format!("@f{i}(x, y) {{\nlet a = [x, y, {i}].map(@(v) {{ v * 2 }})\n}}\n"))
+        .collect();
+    let ast = count("parse (1000 fns)", || parse(&script));
+    count("clone (1000 fns)", || ast.clone());
+    // The bodies of functions are shared, so creating a closure costs the same however large
+    // its body is.
+    for lines in [1, 100] {
+        let ast = parse(&closures(lines));
+        let interpreter = interpreter();
+        count(&format!("create closures ({lines} lines)"), || {
+            runtime.block_on(interpreter.exec(ast)).unwrap()
+        });
+    }
+}
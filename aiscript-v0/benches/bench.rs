@@ -0,0 +1,182 @@
+use aiscript_v0::{ast::Node, Interpreter, Parser};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn interpreter() -> Interpreter {
+    Interpreter::new(
+        [],
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        None,
+    )
+}
+
+fn parse(script: &str) -> Vec<Node> {
+    Parser::default().parse(script).unwrap()
+}
+
+fn large_script(lines: usize) -> String {
+    (0..lines)
+        .map(|i| {
+            format!(
+                "@f{i}(x, y) {{\n\
+                    let a = [x, y, {i}].map(@(v) {{ v * 2 }})\n\
+                    if (a[0] > y) {{ `{{x}} > {{y}}` }} else {{ {{ a: a, b: Core:add(x, y) }} }}\n\
+                }}\n"
+            )
+        })
+        .collect()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for lines in [10, 100, 1000] {
+        let script = large_script(lines);
+        group.bench_with_input(BenchmarkId::from_parameter(lines), &script, |b, script| {
+            b.iter(|| Parser::default().parse(black_box(script)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_exec(c: &mut Criterion, name: &str, script: &str) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let script = parse(script);
+    c.bench_function(name, |b| {
+        b.to_async(&runtime)
+            .iter(|| async { interpreter().exec(black_box(script.clone())).await.unwrap() })
+    });
+}
+
+fn bench_fib(c: &mut Criterion) {
+    bench_exec(
+        c,
+        "fib",
+        r#"
+        @fib(n) {
+            if (n < 2) { return n }
+            fib(n - 1) + fib(n - 2)
+        }
+        fib(15)
+        "#,
+    );
+}
+
+fn bench_fib_sync(c: &mut Criterion) {
+    let script = parse(
+        r#"
+        @fib(n) {
+            if (n < 2) { return n }
+            fib(n - 1) + fib(n - 2)
+        }
+        fib(15)
+        "#,
+    );
+    c.bench_function("fib_sync", |b| {
+        b.iter(|| interpreter().exec_sync(black_box(script.clone())).unwrap())
+    });
+}
+
+fn bench_ackermann(c: &mut Criterion) {
+    bench_exec(
+        c,
+        "ackermann",
+        r#"
+        @ack(m, n) {
+            if (m == 0) { return n + 1 }
+            if (n == 0) { return ack(m - 1, 1) }
+            ack(m - 1, ack(m, n - 1))
+        }
+        ack(2, 3)
+        "#,
+    );
+}
+
+fn bench_arr(c: &mut Criterion) {
+    bench_exec(
+        c,
+        "arr",
+        r#"
+        let arr = Arr:create(1000, 0).map(@(v, i) { (i * 7919) % 1000 })
+        let even = arr.filter(@(v) { v % 2 == 0 })
+        let sorted = even.map(@(v) { v * 2 }).sort(@(a, b) { a - b })
+        sorted.reduce(@(acc, v) { acc + v }, 0)
+        "#,
+    );
+}
+
+fn bench_chain(c: &mut Criterion) {
+    bench_exec(
+        c,
+        "chain",
+        r#"
+        let obj = { a: { b: { c: { d: { e: [[[[1]]]] } } } } }
+        let f = @() { @() { @() { @() { obj } } } }
+        var sum = 0
+        for 500 {
+            sum += f()()()().a.b.c.d.e[0][0][0][0]
+        }
+        sum
+        "#,
+    );
+}
+
+fn bench_namespace(c: &mut Criterion) {
+    let members: String = (0..200)
+        .map(|i| format!("@f{i}(x) {{ if (x > 0) {{ Ns:f{i}(x - 1) }} else {{ [{i}] }} }}\n"))
+        .collect();
+    bench_exec(
+        c,
+        "namespace",
+        &format!(":: Ns {{\n{members}}}\nNs:f199(20)"),
+    );
+}
+
+fn bench_str(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("str");
+    group.sample_size(10);
+    let tmpl = parse(&format!("let x = 'ai'\n`{}`", "{x}script".repeat(50_000)));
+    group.bench_function("template", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { interpreter().exec(black_box(tmpl.clone())).await.unwrap() })
+    });
+    let parts = "let parts = Arr:create(100000, 'aiscript')";
+    let join = parse(&format!("{parts}\nparts.join(',')"));
+    group.bench_function("join", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { interpreter().exec(black_box(join.clone())).await.unwrap() })
+    });
+    let concat_all = parse(&format!("{parts}\nStr:concat_all(parts)"));
+    group.bench_function("concat_all", |b| {
+        b.to_async(&runtime).iter(|| async {
+            interpreter()
+                .exec(black_box(concat_all.clone()))
+                .await
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_instantiate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instantiate");
+    group.bench_function("new", |b| b.iter(interpreter));
+    let base = interpreter();
+    group.bench_function("fork", |b| b.iter(|| black_box(&base).fork()));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_fib,
+    bench_fib_sync,
+    bench_ackermann,
+    bench_arr,
+    bench_chain,
+    bench_namespace,
+    bench_str,
+    bench_instantiate
+);
+criterion_main!(benches);
@@ -55,7 +55,7 @@ async fn main() -> Result<()> {
                         input.clear();
                         let result = aiscript.exec(script).await.unwrap();
                         if let Some(Value { value, .. }) = result {
-                            if *value != V::Null {
+                            if value != V::Null {
                                 println!("{}", value.repr_value());
                             }
                         }
@@ -0,0 +1,15 @@
+#![no_main]
+
+use aiscript_v0::{ast::ArbitraryScript, Interpreter};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|script: ArbitraryScript| {
+    let interpreter = Interpreter::new(
+        [],
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        Some(1000),
+    );
+    let _ = interpreter.exec_sync(script.0);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use aiscript_v0::Parser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|src: &str| {
+    let _ = Parser::default().parse(src);
+});
@@ -1,7 +1,18 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
 use peg::{error::ParseError, str::LineCol};
 use thiserror::Error;
 
+use crate::{
+    interpreter::{
+        permission::Capability,
+        value::{Value, V},
+    },
+    node::Loc,
+};
+
 #[derive(Error, Debug, PartialEq, Clone)]
+#[non_exhaustive]
 pub enum AiScriptError {
     #[error("Internal: {0}")]
     Internal(String),
@@ -12,7 +23,156 @@ pub enum AiScriptError {
     Runtime(#[from] AiScriptRuntimeError),
 }
 
+impl AiScriptError {
+    pub fn is_internal(&self) -> bool {
+        matches!(self, AiScriptError::Internal(_))
+    }
+
+    pub fn is_syntax(&self) -> bool {
+        matches!(self, AiScriptError::Syntax(_))
+    }
+
+    pub fn is_runtime(&self) -> bool {
+        matches!(self, AiScriptError::Runtime(_))
+    }
+
+    /// Returns the call stack captured while the error unwound, innermost frame first.
+    pub fn stack(&self) -> &[StackFrame] {
+        match self {
+            AiScriptError::Runtime(e) => e.stack(),
+            _ => &[],
+        }
+    }
+
+    /// Returns where the code which caused a runtime error came from according to the source map
+    /// set via [`Interpreter::with_source_map`](crate::Interpreter::with_source_map), if any.
+    pub fn origin(&self) -> Option<&str> {
+        match self {
+            AiScriptError::Runtime(e) => e.origin(),
+            _ => None,
+        }
+    }
+
+    /// Returns the stable code of the kind of the error, e.g. `type_mismatch`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AiScriptError::Internal(_) => "internal",
+            AiScriptError::Syntax(e) => e.code(),
+            AiScriptError::Runtime(e) => e.code(),
+        }
+    }
+
+    /// Returns the location in the source where the error occurred, if known.
+    ///
+    /// For a runtime error, this is the location of the innermost call, or of the call itself
+    /// if a value which is not a function was called.
+    pub fn loc(&self) -> Option<Loc> {
+        match self {
+            AiScriptError::Syntax(AiScriptSyntaxError::Parse(e)) => Some(Loc {
+                start: e.location.offset,
+                end: e.location.offset,
+            }),
+            AiScriptError::Syntax(AiScriptSyntaxError::ChainedComparison(loc)) => loc.clone(),
+            AiScriptError::Runtime(e) => match e.root() {
+                AiScriptRuntimeError::NotCallable { loc: Some(loc), .. } => Some(*loc.clone()),
+                _ => self.stack().first().and_then(|frame| frame.loc.clone()),
+            },
+            _ => self.stack().first().and_then(|frame| frame.loc.clone()),
+        }
+    }
+
+    /// Returns the line and the column, both starting from 1, where the error occurred in
+    /// `input`, the source the error came from, if known (see [`Self::loc`]). The column counts
+    /// characters.
+    pub fn position(&self, input: &str) -> Option<(usize, usize)> {
+        let start = self.loc()?.start;
+        let before = input.get(..start)?;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Some((
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        ))
+    }
+
+    /// Returns the name of the error value which [`Self::to_value`] converts the error into,
+    /// which is `internal`, `syntax`, `index_out_of_range`, `user`, `runtime`, or the name of an
+    /// error value raised by a native function.
+    pub fn label(&self) -> &str {
+        match self {
+            AiScriptError::Internal(_) => "internal",
+            AiScriptError::Syntax(_) => "syntax",
+            AiScriptError::Runtime(e) => match e.root() {
+                AiScriptRuntimeError::IndexOutOfRange(_, _) => "index_out_of_range",
+                AiScriptRuntimeError::User(_) => "user",
+                AiScriptRuntimeError::Thrown(Value {
+                    value: V::Error { value, .. },
+                    ..
+                }) => value,
+                _ => "runtime",
+            },
+        }
+    }
+
+    /// Returns the message of the error in `language`. The message in English is the same as
+    /// [`Display`](std::fmt::Display).
+    ///
+    /// Messages given as strings, e.g. by native functions, are not translated.
+    pub fn message(&self, language: Language) -> String {
+        match language {
+            Language::En => self.to_string(),
+            Language::Ja => match self {
+                AiScriptError::Internal(message) => format!("内部エラー: {message}"),
+                AiScriptError::Syntax(e) => format!("構文エラー: {}", e.message_ja()),
+                AiScriptError::Runtime(e) => e.message_ja(),
+            },
+        }
+    }
+
+    /// Returns the error displayed with the message in `catalog`.
+    pub fn localized<'a>(&'a self, catalog: &'a MessageCatalog) -> Localized<'a> {
+        Localized {
+            error: self,
+            catalog,
+        }
+    }
+
+    /// Converts the error into an AiScript error value.
+    ///
+    /// The `info` of the value is an object which has `message`, `loc` and `stack`,
+    /// where `loc` is the location of the innermost call, and `origin` if the error has one.
+    /// An error value raised by a native function is returned as it is.
+    pub fn to_value(&self) -> Value {
+        self.to_value_localized(&MessageCatalog::default())
+    }
+
+    /// Same as [`Self::to_value`] but the `message` is the one in `catalog`.
+    pub fn to_value_localized(&self, catalog: &MessageCatalog) -> Value {
+        if let AiScriptError::Runtime(e) = self {
+            if let AiScriptRuntimeError::Thrown(value) = e.root() {
+                return value.clone();
+            }
+        }
+        let stack = self.stack();
+        let mut info = vec![
+            ("message", Value::str(catalog.message(self))),
+            (
+                "loc",
+                stack
+                    .first()
+                    .and_then(|frame| frame.loc.as_ref())
+                    .map_or_else(Value::null, loc_to_value),
+            ),
+            ("stack", Value::arr(stack.iter().map(StackFrame::to_value))),
+        ];
+        if let Some(origin) = self.origin() {
+            info.push(("origin", Value::str(origin)));
+        }
+        Value::error(self.label(), Some(Value::obj(info)))
+    }
+}
+
 #[derive(Error, Debug, PartialEq, Clone)]
+#[non_exhaustive]
 pub enum AiScriptSyntaxError {
     #[error("Parsing error. (Line {}:{})", .0.location.line, .0.location.column)]
     Parse(#[from] ParseError<LineCol>),
@@ -22,14 +182,471 @@ pub enum AiScriptSyntaxError {
     ReservedWord(String),
     #[error("Unknown type: '{0}'")]
     UnknownType(String),
+    /// An ordering comparison of the result of another comparison, e.g. `1 < x < 10`.
+    #[error("Comparisons cannot be chained. Use && to combine them, e.g. `a < b && b < c`.")]
+    ChainedComparison(Option<Loc>),
+}
+
+impl AiScriptSyntaxError {
+    /// Returns the stable code of the kind of the error, e.g. `reserved_word`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AiScriptSyntaxError::Parse(_) => "parse",
+            AiScriptSyntaxError::Attribute => "invalid_attribute",
+            AiScriptSyntaxError::ReservedWord(_) => "reserved_word",
+            AiScriptSyntaxError::UnknownType(_) => "unknown_type",
+            AiScriptSyntaxError::ChainedComparison(_) => "chained_comparison",
+        }
+    }
+
+    fn message_ja(&self) -> String {
+        match self {
+            AiScriptSyntaxError::Parse(e) => format!(
+                "構文解析に失敗しました。(行 {}:{})",
+                e.location.line, e.location.column
+            ),
+            AiScriptSyntaxError::Attribute => "不正な属性です。".to_string(),
+            AiScriptSyntaxError::ReservedWord(word) => {
+                format!("予約語「{word}」は変数名に使用できません。")
+            }
+            AiScriptSyntaxError::UnknownType(name) => format!("不明な型です: '{name}'"),
+            AiScriptSyntaxError::ChainedComparison(_) => {
+                "比較演算子は連結できません。&& で組み合わせてください。例: `a < b && b < c`"
+                    .to_string()
+            }
+        }
+    }
 }
 
 #[derive(Error, Debug, PartialEq, Clone)]
+#[non_exhaustive]
 pub enum AiScriptRuntimeError {
     #[error("Runtime: {0}")]
     Runtime(String),
+    /// A value of an unexpected type, e.g. `expected` of `number` and `got` of `string`.
+    #[error("Runtime: Expect {expected}, but got {got}")]
+    Type { expected: String, got: String },
+    #[error("Runtime: No such variable '{name}' in scope '{scope}'")]
+    NoSuchVariable { name: String, scope: String },
+    #[error("Runtime: Variable '{name}' already exists in scope '{scope}'")]
+    VariableExists { name: String, scope: String },
+    #[error("Runtime: Cannot assign to an immutable variable {0}.")]
+    AssignToImmutable(String),
+    /// A definition which shadows a variable of the same function in strict mode.
+    #[error("Runtime: Variable '{0}' shadows another variable in the same function.")]
+    Shadowing(String),
+    /// An assignment from a namespace to a variable outside it in strict mode.
+    #[error("Runtime: Cannot assign to variable '{0}' defined outside the namespace.")]
+    AssignToOuter(String),
+    /// A string or an array longer than the limit named `limit`, e.g. `max_str_len`.
+    #[error("Runtime: {limit} exceeded: length {got} > {max}")]
+    LimitExceeded {
+        limit: String,
+        max: usize,
+        got: usize,
+    },
+    /// A variable used before its definition in strict mode.
+    #[error("Runtime: Variable '{0}' is used before its definition.")]
+    UsedBeforeDefinition(String),
+    /// The step limit was exceeded, in the innermost loop running at the time if any.
+    #[error("Runtime: max step exceeded{}", in_loop.as_ref().map_or_else(String::new, |l| format!(" {l}")))]
+    MaxStepExceeded { in_loop: Option<LoopInfo> },
+    /// A loop ran more iterations than the limit set via
+    /// [`Interpreter::with_max_loop_iterations`](crate::Interpreter::with_max_loop_iterations).
+    #[error("Runtime: max loop iterations exceeded {0}")]
+    MaxLoopIterationsExceeded(LoopInfo),
+    /// A variable captured by a function whose value cannot be serialized, returned by
+    /// [`VFn::serialize_captures`](crate::values::VFn::serialize_captures).
+    #[error("Runtime: Cannot serialize variable '{0}' captured by the function.")]
+    NotSerializable(String),
+    /// A call to a function tagged with a capability which is not allowed via
+    /// [`Interpreter::with_permissions`](crate::Interpreter::with_permissions).
+    #[error("Runtime: Permission denied: '{name}' requires the capability '{capability}'.")]
+    PermissionDenied {
+        name: String,
+        capability: Capability,
+    },
+    /// A call of a value which is not a function, e.g. `x()` where `x` is a number. `name` is the
+    /// name of the called variable or property, if any, `got` is the type of the value, and
+    /// `loc` is the location of the call.
+    #[error("Runtime: Cannot call {} of type {got}, which is not a function.", name.as_ref().map_or_else(|| "a value".to_string(), |name| format!("'{name}'")))]
+    NotCallable {
+        name: Option<String>,
+        got: String,
+        /// Boxed to keep the size of errors, which deeply recursive scripts pass around.
+        loc: Option<Box<Loc>>,
+    },
+    /// A call of a native function which did not complete within the timeout set via
+    /// [`Interpreter::with_native_timeout`](crate::Interpreter::with_native_timeout) and was
+    /// cancelled.
+    #[error("Runtime: '{name}' did not complete within {timeout:?}.")]
+    NativeTimeout { name: String, timeout: Duration },
     #[error("Runtime: Index out of range. index: {0} max: {1}")]
     IndexOutOfRange(f64, isize),
     #[error("{0}")]
     User(String),
+    /// An error value raised by a native function.
+    #[error("{}", .0.repr_value())]
+    Thrown(Value),
+    /// An error which unwound through one or more user-defined functions, or occurred in code
+    /// mapped by a source map.
+    #[error("{error}")]
+    Traced {
+        error: Box<AiScriptRuntimeError>,
+        stack: Vec<StackFrame>,
+        /// Origin of the innermost code where the error occurred in the source map.
+        origin: Option<String>,
+    },
+}
+
+impl AiScriptRuntimeError {
+    /// Returns the call stack captured while the error unwound, innermost frame first.
+    pub fn stack(&self) -> &[StackFrame] {
+        match self {
+            AiScriptRuntimeError::Traced { stack, .. } => stack,
+            _ => &[],
+        }
+    }
+
+    /// Returns where the code which caused the error came from according to the source map set
+    /// via [`Interpreter::with_source_map`](crate::Interpreter::with_source_map), if any.
+    pub fn origin(&self) -> Option<&str> {
+        match self {
+            AiScriptRuntimeError::Traced { origin, .. } => origin.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the original error without the call stack.
+    pub fn root(&self) -> &AiScriptRuntimeError {
+        match self {
+            AiScriptRuntimeError::Traced { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Returns the stable code of the kind of the error, e.g. `type_mismatch`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AiScriptRuntimeError::Runtime(_) => "runtime",
+            AiScriptRuntimeError::Type { .. } => "type_mismatch",
+            AiScriptRuntimeError::NoSuchVariable { .. } => "no_such_variable",
+            AiScriptRuntimeError::VariableExists { .. } => "variable_exists",
+            AiScriptRuntimeError::AssignToImmutable(_) => "assign_to_immutable",
+            AiScriptRuntimeError::Shadowing(_) => "shadowing",
+            AiScriptRuntimeError::AssignToOuter(_) => "assign_to_outer",
+            AiScriptRuntimeError::UsedBeforeDefinition(_) => "used_before_definition",
+            AiScriptRuntimeError::LimitExceeded { .. } => "limit_exceeded",
+            AiScriptRuntimeError::MaxStepExceeded { .. } => "max_step_exceeded",
+            AiScriptRuntimeError::MaxLoopIterationsExceeded(_) => "max_loop_iterations_exceeded",
+            AiScriptRuntimeError::NotSerializable(_) => "not_serializable",
+            AiScriptRuntimeError::PermissionDenied { .. } => "permission_denied",
+            AiScriptRuntimeError::NotCallable { .. } => "not_callable",
+            AiScriptRuntimeError::NativeTimeout { .. } => "native_timeout",
+            AiScriptRuntimeError::IndexOutOfRange(_, _) => "index_out_of_range",
+            AiScriptRuntimeError::User(_) => "user",
+            AiScriptRuntimeError::Thrown(_) => "thrown",
+            AiScriptRuntimeError::Traced { error, .. } => error.code(),
+        }
+    }
+
+    fn message_ja(&self) -> String {
+        match self {
+            AiScriptRuntimeError::Runtime(message) => format!("実行時エラー: {message}"),
+            AiScriptRuntimeError::Type { expected, got } => {
+                format!("実行時エラー: {expected} が必要ですが、{got} が渡されました。")
+            }
+            AiScriptRuntimeError::NoSuchVariable { name, scope } => {
+                format!("実行時エラー: 変数 '{name}' はスコープ '{scope}' に存在しません。")
+            }
+            AiScriptRuntimeError::VariableExists { name, scope } => {
+                format!("実行時エラー: 変数 '{name}' はスコープ '{scope}' に既に存在します。")
+            }
+            AiScriptRuntimeError::AssignToImmutable(name) => {
+                format!("実行時エラー: 変更できない変数 {name} には代入できません。")
+            }
+            AiScriptRuntimeError::Shadowing(name) => {
+                format!("実行時エラー: 変数 '{name}' は同じ関数内の別の変数を隠しています。")
+            }
+            AiScriptRuntimeError::AssignToOuter(name) => {
+                format!("実行時エラー: 名前空間の外で定義された変数 '{name}' には代入できません。")
+            }
+            AiScriptRuntimeError::LimitExceeded { limit, max, got } => {
+                format!("実行時エラー: {limit} を超えました: 長さ {got} > {max}")
+            }
+            AiScriptRuntimeError::UsedBeforeDefinition(name) => {
+                format!("実行時エラー: 変数 '{name}' が定義される前に使用されています。")
+            }
+            AiScriptRuntimeError::MaxStepExceeded { in_loop: None } => {
+                "実行時エラー: 最大ステップ数を超えました。".to_string()
+            }
+            AiScriptRuntimeError::MaxStepExceeded {
+                in_loop: Some(in_loop),
+            } => format!(
+                "実行時エラー: {}で最大ステップ数を超えました。",
+                in_loop.message_ja()
+            ),
+            AiScriptRuntimeError::MaxLoopIterationsExceeded(in_loop) => format!(
+                "実行時エラー: {}で最大反復回数を超えました。",
+                in_loop.message_ja()
+            ),
+            AiScriptRuntimeError::NotSerializable(name) => {
+                format!("実行時エラー: 関数が参照する変数 '{name}' はシリアライズできません。")
+            }
+            AiScriptRuntimeError::PermissionDenied { name, capability } => {
+                format!(
+                    "実行時エラー: 権限がありません: '{name}' には権限 '{capability}' が必要です。"
+                )
+            }
+            AiScriptRuntimeError::NotCallable { name, got, .. } => format!(
+                "実行時エラー: {} は {got} 型であり、関数ではないため呼び出せません。",
+                name.as_ref()
+                    .map_or_else(|| "値".to_string(), |name| format!("'{name}'"))
+            ),
+            AiScriptRuntimeError::NativeTimeout { name, timeout } => {
+                format!("実行時エラー: '{name}' が {timeout:?} 以内に完了しませんでした。")
+            }
+            AiScriptRuntimeError::IndexOutOfRange(index, max) => {
+                format!("実行時エラー: インデックスが範囲外です。index: {index} max: {max}")
+            }
+            AiScriptRuntimeError::User(_) | AiScriptRuntimeError::Thrown(_) => self.to_string(),
+            AiScriptRuntimeError::Traced { error, .. } => error.message_ja(),
+        }
+    }
+
+    pub(crate) fn with_frame(self, frame: StackFrame) -> Self {
+        match self {
+            AiScriptRuntimeError::Traced {
+                error,
+                mut stack,
+                origin,
+            } => {
+                stack.push(frame);
+                AiScriptRuntimeError::Traced {
+                    error,
+                    stack,
+                    origin,
+                }
+            }
+            error => AiScriptRuntimeError::Traced {
+                error: Box::new(error),
+                stack: vec![frame],
+                origin: None,
+            },
+        }
+    }
+
+    /// Records `in_loop` as the loop where the step limit was exceeded, unless an inner loop is
+    /// already recorded.
+    pub(crate) fn in_loop(self, in_loop: impl FnOnce() -> LoopInfo) -> Self {
+        match self.root() {
+            AiScriptRuntimeError::MaxStepExceeded { in_loop: None } => {
+                self.with_root(AiScriptRuntimeError::MaxStepExceeded {
+                    in_loop: Some(in_loop()),
+                })
+            }
+            _ => self,
+        }
+    }
+
+    /// Replaces the original error, keeping the call stack and the origin.
+    pub(crate) fn with_root(self, root: AiScriptRuntimeError) -> Self {
+        match self {
+            AiScriptRuntimeError::Traced { stack, origin, .. } => AiScriptRuntimeError::Traced {
+                error: Box::new(root),
+                stack,
+                origin,
+            },
+            _ => root,
+        }
+    }
+
+    pub(crate) fn with_origin(self, origin: String) -> Self {
+        match self {
+            AiScriptRuntimeError::Traced { error, stack, .. } => AiScriptRuntimeError::Traced {
+                error,
+                stack,
+                origin: Some(origin),
+            },
+            error => AiScriptRuntimeError::Traced {
+                error: Box::new(error),
+                stack: Vec::new(),
+                origin: Some(origin),
+            },
+        }
+    }
+}
+
+/// A non-fatal problem found during an execution, reported via
+/// [`Interpreter::with_warn`](crate::Interpreter::with_warn).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Warning {
+    /// Stable code of the kind of the warning, e.g. `deprecated`.
+    pub code: String,
+    pub message: String,
+    /// Location in the source which caused the warning, if known.
+    pub loc: Option<Loc>,
+}
+
+/// A language of the messages of errors.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Language {
+    #[default]
+    En,
+    Ja,
+}
+
+type MessageFn = dyn Fn(&AiScriptError) -> String + Send + Sync;
+
+/// Messages of errors keyed by [`AiScriptError::code`], e.g. to show errors to end users in their
+/// language. The errors whose codes have no messages in the catalog fall back to the built-in
+/// messages in the language of the catalog.
+///
+/// ```
+/// use aiscript_v0::errors::{Language, MessageCatalog};
+///
+/// let catalog = MessageCatalog::new(Language::En).with_message("no_such_variable", |e| {
+///     format!("Variable inconnue ({})", e.code())
+/// });
+/// ```
+#[derive(Default, Clone)]
+pub struct MessageCatalog {
+    language: Language,
+    messages: Arc<HashMap<String, Arc<MessageFn>>>,
+}
+
+impl MessageCatalog {
+    /// Creates an empty catalog falling back to the built-in messages in `language`.
+    pub fn new(language: Language) -> Self {
+        MessageCatalog {
+            language,
+            messages: Arc::default(),
+        }
+    }
+
+    /// Sets the function which returns the message of an error with `code`, replacing the one
+    /// already set.
+    pub fn with_message(
+        mut self,
+        code: impl Into<String>,
+        message: impl Fn(&AiScriptError) -> String + Send + Sync + 'static,
+    ) -> Self {
+        Arc::make_mut(&mut self.messages).insert(code.into(), Arc::new(message));
+        self
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Returns the message of `error`.
+    pub fn message(&self, error: &AiScriptError) -> String {
+        match self.messages.get(error.code()) {
+            Some(message) => message(error),
+            None => error.message(self.language),
+        }
+    }
+}
+
+impl std::fmt::Debug for MessageCatalog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageCatalog")
+            .field("language", &self.language)
+            .field("codes", &self.messages.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// An error displayed with the message in a [`MessageCatalog`], returned by
+/// [`AiScriptError::localized`].
+#[derive(Debug, Clone, Copy)]
+pub struct Localized<'a> {
+    error: &'a AiScriptError,
+    catalog: &'a MessageCatalog,
+}
+
+impl std::fmt::Display for Localized<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.catalog.message(self.error))
+    }
+}
+
+/// A loop where an error occurred.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoopInfo {
+    /// Location of the loop.
+    pub loc: Option<Loc>,
+    /// The number of iterations the loop had started.
+    pub iterations: usize,
+}
+
+impl LoopInfo {
+    fn message_ja(&self) -> String {
+        match &self.loc {
+            Some(Loc { start, end }) => {
+                format!("{start}..{end} のループの {} 回目", self.iterations)
+            }
+            None => format!("ループの {} 回目", self.iterations),
+        }
+    }
+}
+
+impl std::fmt::Display for LoopInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.loc {
+            Some(Loc { start, end }) => write!(f, "in the loop at {start}..{end}")?,
+            None => write!(f, "in a loop")?,
+        }
+        write!(f, " after {} iterations", self.iterations)
+    }
+}
+
+/// A function call which an error unwound through.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StackFrame {
+    /// Name of the called function. `None` if the function is anonymous.
+    pub name: Option<String>,
+    /// Location of the call.
+    pub loc: Option<Loc>,
+    /// Origin of the call in the source map, if any.
+    pub origin: Option<String>,
+}
+
+impl StackFrame {
+    fn to_value(&self) -> Value {
+        let mut frame = vec![
+            (
+                "name",
+                self.name.clone().map_or_else(Value::null, Value::str),
+            ),
+            (
+                "loc",
+                self.loc.as_ref().map_or_else(Value::null, loc_to_value),
+            ),
+        ];
+        if let Some(origin) = &self.origin {
+            frame.push(("origin", Value::str(origin)));
+        }
+        Value::obj(frame)
+    }
+}
+
+fn loc_to_value(loc: &Loc) -> Value {
+    Value::obj([
+        ("start", Value::num(loc.start as f64)),
+        ("end", Value::num(loc.end as f64)),
+    ])
+}
+
+impl std::fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at {}", self.name.as_deref().unwrap_or("<anonymous>"))?;
+        if let Some(Loc { start, end }) = &self.loc {
+            write!(f, " ({start}..{end})")?;
+        }
+        if let Some(origin) = &self.origin {
+            write!(f, " [{origin}]")?;
+        }
+        Ok(())
+    }
 }
@@ -1,39 +1,74 @@
 //! AiScript interpreter
 
 use std::{
-    collections::HashMap,
-    iter::{repeat, zip},
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    iter::{once, repeat, zip},
+    pin::pin,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use futures::{
-    future::{try_join_all, BoxFuture},
+    future::{self, try_join_all, BoxFuture, Either},
     Future, FutureExt,
 };
 use indexmap::IndexMap;
+use tokio_util::sync::CancellationToken;
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
 use value::VObj;
 
 use crate::{
-    error::{AiScriptError, AiScriptRuntimeError},
+    error::{AiScriptError, AiScriptRuntimeError, LoopInfo, MessageCatalog, StackFrame, Warning},
     node as ast,
+    parser::diagnostic::{Diagnostic, ScopeInfo},
 };
 
 use self::{
+    clock::{Clock, SystemClock},
+    cycle::{may_refer, CycleCollector},
+    fn_body::FnBodies,
+    generator::{Generator, Yielder},
+    input::{InputKind, InputRequest},
     lib::std::std,
-    primitive_props::get_prim_prop,
+    metrics::{Metrics, MetricsState},
+    native_type::NativeTypes,
+    permission::{address, Capability, Permissions},
+    primitive_props::{get_prim_prop, PrimProps},
+    profile::{ProfileEntry, Profiler},
     scope::Scope,
-    util::expect_any,
-    value::{unwrap_ret, Attr, VFn, Value, V},
+    step::{StepBudget, StepExecution},
+    time_travel::Timeline,
+    util::{const_value, expect_any, ReprValue},
+    value::{unwrap_ret, ArgMap, Attr, NativeObject, PrimPropFn, PrimType, VArr, VFn, Value, V},
     variable::Variable,
 };
 
+pub mod channel;
+pub mod clock;
+mod cycle;
+mod fn_body;
+#[cfg(feature = "fs")]
+pub mod fs;
+mod generator;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod input;
 mod lib;
+pub mod metrics;
+mod native_type;
+pub mod permission;
 mod primitive_props;
+pub mod profile;
 pub mod scope;
+pub mod step;
+pub mod storage;
+pub mod time_travel;
 pub mod util;
 pub mod value;
 mod variable;
@@ -41,14 +76,74 @@ mod variable;
 const IRQ_RATE: usize = 300;
 const IRQ_AT: usize = IRQ_RATE - 1;
 
+/// A set of behaviors which differ between this implementation and AiScript for JavaScript,
+/// chosen via [`Interpreter::with_compat_profile`].
+///
+/// | Behavior | `Native` | `Upstream` |
+/// | --- | --- | --- |
+/// | `==`, `match`, `arr.incl` and `arr.index_of` | compare arrays and objects by their contents | compare arrays and objects by reference |
+/// | Numbers in templates, `Core:to_str` and `num.to_str()` | `inf`, `1000000000000000000000`, `0.0000001`, `-0` | `Infinity`, `1e+21`, `1e-7`, `0` |
+///
+/// Both profiles behave the same in the rest, e.g. comparisons with NaN are false, and an index
+/// of an array which is not an integer in range is an error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum CompatProfile {
+    /// The behaviors of this implementation.
+    #[default]
+    Native,
+    /// The behaviors of AiScript for JavaScript, e.g. for hosts migrating from it.
+    Upstream,
+}
+
+type InputFn = dyn (Fn(InputRequest) -> BoxFuture<'static, Option<Value>>) + Sync + Send;
+
 #[derive(Clone, Default)]
 pub struct Interpreter {
     pub step_count: Arc<AtomicUsize>,
     stop: Arc<AtomicBool>,
     pub scope: Scope,
     abort_handlers: Arc<Mutex<tokio::task::JoinSet<Result<(), AiScriptError>>>>,
+    abort_notify: Arc<tokio::sync::Notify>,
+    event_handlers: Arc<Mutex<HashMap<String, Vec<VFn>>>>,
+    in_: Option<Arc<dyn (Fn(String) -> BoxFuture<'static, String>) + Sync + Send + 'static>>,
+    input: Option<Arc<InputFn>>,
+    out: Option<Arc<dyn (Fn(Value) -> BoxFuture<'static, ()>) + Sync + Send + 'static>>,
     err: Option<Arc<dyn (Fn(AiScriptError) -> BoxFuture<'static, ()>) + Sync + Send + 'static>>,
+    warn: Option<Arc<dyn (Fn(Warning) -> BoxFuture<'static, ()>) + Sync + Send + 'static>>,
     max_step: Option<usize>,
+    max_loop_iterations: Option<usize>,
+    max_str_len: Option<usize>,
+    max_arr_len: Option<usize>,
+    max_repr_depth: Option<usize>,
+    max_repr_len: Option<usize>,
+    context: Option<Arc<dyn Any + Sync + Send>>,
+    clock: Option<Arc<dyn Clock>>,
+    sync: bool,
+    strict_integers: bool,
+    reference_equality: bool,
+    compat_profile: CompatProfile,
+    native_timing: bool,
+    strict_variables: bool,
+    source_map: Option<Arc<ast::SourceMap>>,
+    prim_props: Arc<PrimProps>,
+    metrics: Arc<MetricsState>,
+    cycles: Arc<CycleCollector>,
+    fn_bodies: Arc<FnBodies>,
+    yielder: Option<Arc<Yielder>>,
+    timeline: Option<Arc<Timeline>>,
+    permissions: Arc<Permissions>,
+    native_types: Arc<NativeTypes>,
+    profiler: Option<Arc<Profiler>>,
+    step_budget: Option<Arc<StepBudget>>,
+    /// The timeout of calls of native functions, and whether to cancel them after it.
+    native_timeout: Option<(Duration, bool)>,
+    /// The addresses of the native functions of the standard library, which are not watched by
+    /// the native timeout like the built-in methods of values, since they may run the callbacks
+    /// of scripts, e.g. `Core:try`.
+    builtins: Arc<HashSet<usize>>,
+    messages: MessageCatalog,
+    #[cfg(feature = "tracing")]
+    script_name: Option<Arc<str>>,
 }
 
 impl std::fmt::Debug for Interpreter {
@@ -62,19 +157,38 @@ impl std::fmt::Debug for Interpreter {
     }
 }
 
+// Interpreters and values can be moved to and shared between threads, e.g. by `tokio::spawn`,
+// as long as native functions are (see `Value::fn_native`).
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Interpreter>();
+    assert_send_sync::<Scope>();
+    assert_send_sync::<Value>();
+    assert_send_sync::<VFn>();
+    assert_send_sync::<AiScriptError>();
+};
+
+#[allow(dead_code)]
+fn assert_send_futures(interpreter: &Interpreter, fn_: VFn) {
+    fn assert_send<T: Send>(_: T) {}
+    assert_send(interpreter.exec(Vec::new()));
+    assert_send(interpreter.exec_fn(fn_, Vec::new()));
+    assert_send(interpreter.dispatch_event("", Value::null()));
+}
+
 impl Interpreter {
     pub fn new(
         consts: impl IntoIterator<Item = (String, Value)>,
         in_: Option<impl Fn(String) -> BoxFuture<'static, String> + Sync + Send + Clone + 'static>,
-        out: Option<impl Fn(Value) -> BoxFuture<'static, ()> + Sync + Send + Clone + 'static>,
+        out: Option<impl Fn(Value) -> BoxFuture<'static, ()> + Sync + Send + 'static>,
         err: Option<impl Fn(AiScriptError) -> BoxFuture<'static, ()> + Sync + Send + 'static>,
         max_step: Option<usize>,
     ) -> Self {
         let io = [
             (
                 "print".to_string(),
-                Value::fn_native(move |args, _| {
-                    let out = out.clone();
+                Value::fn_native(|args, interpreter| {
+                    let out = interpreter.out.clone();
                     async move {
                         let mut args = args.into_iter();
                         let v = expect_any(args.next())?;
@@ -88,12 +202,22 @@ impl Interpreter {
             ),
             (
                 "readline".to_string(),
-                Value::fn_native(move |args, _| {
-                    let in_ = in_.clone();
+                Value::fn_native(|args, interpreter| {
+                    let interpreter = interpreter.clone();
                     async move {
                         let mut args = args.into_iter();
                         let q = String::try_from(args.next().unwrap_or_default())?;
-                        if let Some(in_) = in_ {
+                        if interpreter.input.is_some() {
+                            let a = interpreter
+                                .request_input(InputRequest {
+                                    prompt: q,
+                                    kind: InputKind::Text,
+                                    default: None,
+                                    choices: Vec::new(),
+                                })
+                                .await;
+                            Ok(a.unwrap_or_default())
+                        } else if let Some(in_) = &interpreter.in_ {
                             let a = in_(q).await;
                             Ok(Value::str(a))
                         } else {
@@ -105,8 +229,18 @@ impl Interpreter {
             ),
         ];
         let mut states = Vec::from_iter(consts);
-        states.extend(std());
-        states.extend(io);
+        let builtins = std().into_iter().chain(io).collect::<Vec<_>>();
+        let builtin_addresses = builtins
+            .iter()
+            .filter_map(|(_, value)| match &value.value {
+                V::Fn(fn_) => match fn_.as_ref() {
+                    VFn::FnNative(fn_) => Some(address(fn_)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        states.extend(builtins);
         let states = states
             .into_iter()
             .map(|(k, v)| (k, Variable::Const(v)))
@@ -116,21 +250,997 @@ impl Interpreter {
             stop: Arc::new(AtomicBool::new(false)),
             scope: Scope::new(states, None),
             abort_handlers: Arc::new(Mutex::new(tokio::task::JoinSet::new())),
+            abort_notify: Arc::new(tokio::sync::Notify::new()),
+            event_handlers: Arc::new(Mutex::new(HashMap::new())),
+            in_: match in_ {
+                Some(in_) => Some(Arc::new(in_)),
+                None => None,
+            },
+            input: None,
+            out: match out {
+                Some(out) => Some(Arc::new(out)),
+                None => None,
+            },
             err: match err {
                 Some(err) => Some(Arc::new(err)),
                 None => None,
             },
+            warn: None,
             max_step,
+            max_loop_iterations: None,
+            max_str_len: None,
+            max_arr_len: None,
+            max_repr_depth: None,
+            max_repr_len: None,
+            context: None,
+            clock: None,
+            sync: false,
+            strict_integers: false,
+            reference_equality: false,
+            compat_profile: CompatProfile::Native,
+            native_timing: false,
+            strict_variables: false,
+            source_map: None,
+            prim_props: Arc::new(PrimProps::default()),
+            metrics: Arc::new(MetricsState::default()),
+            cycles: Arc::new(CycleCollector::default()),
+            fn_bodies: Arc::default(),
+            yielder: None,
+            timeline: None,
+            permissions: Arc::new(Permissions::new()),
+            native_types: Arc::new(NativeTypes::default()),
+            profiler: None,
+            step_budget: None,
+            native_timeout: None,
+            builtins: Arc::new(builtin_addresses),
+            messages: MessageCatalog::default(),
+            #[cfg(feature = "tracing")]
+            script_name: None,
+        }
+    }
+
+    /// Defines mutable variables at the top level, which scripts can reassign unlike the
+    /// constants given to [`Self::new`], e.g. a `config` object the host reads back via
+    /// [`Scope::get`] after the execution. A constant of the same name is replaced.
+    pub fn with_vars(self, vars: impl IntoIterator<Item = (String, Value)>) -> Self {
+        for (name, value) in vars {
+            self.scope.set(name, Variable::Mut(value));
+        }
+        self
+    }
+
+    /// Makes integer arithmetic, `Num:parse_int` and `Json:parse` fail with a runtime error when
+    /// they result in an integer out of the range where numbers are exact, ±(2^53 - 1), instead
+    /// of silently losing the precision, e.g. of 64-bit IDs.
+    pub fn with_strict_integers(self, strict_integers: bool) -> Self {
+        Interpreter {
+            strict_integers,
+            ..self
+        }
+    }
+
+    /// Returns an error if the integer precision of `value` may be lost in strict integer mode
+    /// (see [`Self::with_strict_integers`]).
+    pub(crate) fn check_integer(&self, value: f64) -> Result<(), AiScriptError> {
+        const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+        if self.strict_integers && value.trunc() == value && value.abs() > MAX_SAFE_INTEGER {
+            Err(AiScriptRuntimeError::Runtime(format!(
+                "Integer precision lost: {value} is out of the safe range"
+            )))?
+        }
+        Ok(())
+    }
+
+    /// Makes `==`, `!=`, `match`, `arr.incl` and `arr.index_of` compare values in the same way as
+    /// AiScript for JavaScript does (see [`Value::ref_eq`]), where arrays and objects are equal
+    /// only if they are the same one, instead of by their contents (see [`Value::deep_eq`]).
+    pub fn with_reference_equality(self, reference_equality: bool) -> Self {
+        Interpreter {
+            reference_equality,
+            ..self
+        }
+    }
+
+    /// Switches the behaviors which differ from AiScript for JavaScript at once (see
+    /// [`CompatProfile`] for the list).
+    ///
+    /// The profile sets [`Self::with_reference_equality`], which can be overridden after this.
+    pub fn with_compat_profile(self, compat_profile: CompatProfile) -> Self {
+        Interpreter {
+            compat_profile,
+            reference_equality: compat_profile == CompatProfile::Upstream,
+            ..self
+        }
+    }
+
+    /// Converts a value into a string as templates and `Core:to_str` do in this interpreter (see
+    /// [`Self::with_compat_profile`] and [`Self::with_max_repr_depth`]).
+    pub(crate) fn repr_string(&self, value: &Value) -> String {
+        self.format_repr(value.repr_value(), self.max_repr_depth, self.max_repr_len)
+    }
+
+    /// Converts a value into a string with the given limits, which are unlimited if `None`.
+    pub(crate) fn format_repr(
+        &self,
+        repr: ReprValue,
+        max_depth: Option<usize>,
+        max_len: Option<usize>,
+    ) -> String {
+        let repr = repr
+            .with_js_numbers(self.compat_profile == CompatProfile::Upstream)
+            .with_max_depth(max_depth);
+        match max_len {
+            Some(max_len) => repr.to_string_limited(max_len),
+            None => repr.to_string(),
+        }
+    }
+
+    /// Limits how deep templates and `Core:to_str` show nested arrays and objects. Those nested
+    /// deeper than `max_repr_depth` levels are shown as `...`, as those in a cycle are.
+    pub fn with_max_repr_depth(self, max_repr_depth: usize) -> Self {
+        Interpreter {
+            max_repr_depth: Some(max_repr_depth),
+            ..self
+        }
+    }
+
+    /// Limits the strings converted from values by templates and `Core:to_str` to
+    /// `max_repr_len` characters followed by `...`, stopping the conversion there.
+    pub fn with_max_repr_len(self, max_repr_len: usize) -> Self {
+        Interpreter {
+            max_repr_len: Some(max_repr_len),
+            ..self
+        }
+    }
+
+    /// Returns the limits set via [`Self::with_max_repr_depth`] and [`Self::with_max_repr_len`].
+    pub(crate) fn repr_limits(&self) -> (Option<usize>, Option<usize>) {
+        (self.max_repr_depth, self.max_repr_len)
+    }
+
+    /// Enforces stricter rules on variables with runtime errors: a definition cannot shadow a
+    /// variable of the same function, including its arguments, code in a namespace cannot assign
+    /// to a variable outside the namespace, and a variable used before its definition is
+    /// reported as such instead of as an unknown variable.
+    pub fn with_strict_variables(self, strict_variables: bool) -> Self {
+        Interpreter {
+            strict_variables,
+            ..self
+        }
+    }
+
+    /// Limits the number of iterations of each run of a loop, `each`, `for` or `loop`, with a
+    /// runtime error that tells the location of the loop.
+    pub fn with_max_loop_iterations(self, max_loop_iterations: usize) -> Self {
+        Interpreter {
+            max_loop_iterations: Some(max_loop_iterations),
+            ..self
+        }
+    }
+
+    /// Returns an error if a loop at `loc` starting its `iterations`-th iteration exceeds the
+    /// limit set via [`Self::with_max_loop_iterations`].
+    fn check_loop_iterations(
+        &self,
+        loc: &Option<ast::Loc>,
+        iterations: usize,
+    ) -> Result<(), AiScriptError> {
+        match self.max_loop_iterations {
+            Some(max) if iterations > max => {
+                Err(AiScriptRuntimeError::MaxLoopIterationsExceeded(LoopInfo {
+                    loc: loc.clone(),
+                    iterations,
+                }))?
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports an unknown variable as used before its definition in strict mode if one of
+    /// `rest`, the failing statement and the ones after it, defines the variable.
+    fn explain_undefined<'a>(
+        &self,
+        error: AiScriptError,
+        mut rest: impl Iterator<Item = NodeRef<'a>>,
+    ) -> AiScriptError {
+        let (true, AiScriptError::Runtime(e)) = (self.strict_variables, &error) else {
+            return error;
+        };
+        let AiScriptRuntimeError::NoSuchVariable { name, .. } = e.root() else {
+            return error;
+        };
+        let defined = rest.any(|node| match node {
+            NodeRef::Node(ast::Node::Statement(ast::Statement::Definition(definition)))
+            | NodeRef::Statement(ast::Statement::Definition(definition)) => {
+                &definition.name == name
+            }
+            _ => false,
+        });
+        if defined {
+            let root = AiScriptRuntimeError::UsedBeforeDefinition(name.clone());
+            e.clone().with_root(root).into()
+        } else {
+            error
+        }
+    }
+
+    /// Sets the clock which `Date:now`, `Core:sleep`, `Async:timeout` and `Async:interval` use
+    /// instead of the real time, e.g. a [`ManualClock`](clock::ManualClock) to fast-forward
+    /// time in tests.
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Interpreter {
+            clock: Some(clock),
+            ..self
+        }
+    }
+
+    /// Returns the current time of the clock in milliseconds since the Unix epoch.
+    pub(crate) fn now(&self) -> f64 {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// Returns the current time for the native function `name` reading it in place of a missing
+    /// timestamp, e.g. `Date:year()`, which is denied unless [`Capability::Time`] is allowed.
+    pub(crate) fn now_for(&self, name: &str) -> Result<f64, AiScriptError> {
+        if !self.permissions.allows(Capability::Time) {
+            Err(AiScriptRuntimeError::PermissionDenied {
+                name: name.to_string(),
+                capability: Capability::Time,
+            })?
+        }
+        Ok(self.now())
+    }
+
+    /// Completes after `duration` elapses on the clock, or when the interpreter is aborted.
+    pub(crate) fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send + 'static {
+        let sleep = match &self.clock {
+            Some(clock) => clock.sleep(duration),
+            None => SystemClock.sleep(duration),
+        };
+        let interpreter = self.clone();
+        async move {
+            futures::future::select(sleep, interpreter.aborted().boxed()).await;
+        }
+    }
+
+    /// Limits the length of strings created by templates and native functions, including the
+    /// standard library, to `max_str_len` bytes. Exceeding it results in a runtime error.
+    pub fn with_max_str_len(self, max_str_len: usize) -> Self {
+        Interpreter {
+            max_str_len: Some(max_str_len),
+            ..self
+        }
+    }
+
+    /// Limits the number of items of arrays created or grown by native functions, including the
+    /// standard library, to `max_arr_len`. Exceeding it results in a runtime error.
+    pub fn with_max_arr_len(self, max_arr_len: usize) -> Self {
+        Interpreter {
+            max_arr_len: Some(max_arr_len),
+            ..self
+        }
+    }
+
+    /// Returns an error if a string of `len` bytes exceeds the limit set via
+    /// [`Self::with_max_str_len`].
+    pub(crate) fn check_str_len(&self, len: usize) -> Result<(), AiScriptError> {
+        match self.max_str_len {
+            Some(max) if len > max => Err(AiScriptRuntimeError::LimitExceeded {
+                limit: "max_str_len".to_string(),
+                max,
+                got: len,
+            })?,
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if an array of `len` items exceeds the limit set via
+    /// [`Self::with_max_arr_len`].
+    pub(crate) fn check_arr_len(&self, len: usize) -> Result<(), AiScriptError> {
+        match self.max_arr_len {
+            Some(max) if len > max => Err(AiScriptRuntimeError::LimitExceeded {
+                limit: "max_arr_len".to_string(),
+                max,
+                got: len,
+            })?,
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if `value` is a string or an array exceeding the limits.
+    fn check_len(&self, value: &Value) -> Result<(), AiScriptError> {
+        match &value.value {
+            V::Str(str) => self.check_str_len(str.len()),
+            V::Arr(arr) => self.check_arr_len(arr.read().unwrap().len()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the time spent in each native function called by name in
+    /// [`Metrics::native_time`], e.g. for billing the use of `Http:` functions.
+    pub fn with_native_timing(self, native_timing: bool) -> Self {
+        Interpreter {
+            native_timing,
+            ..self
+        }
+    }
+
+    /// Watches calls of native functions by name, e.g. `Http:get`, and reports a call which does
+    /// not complete within `timeout` on the clock via a warning with the code `native_timeout`.
+    /// If `cancel` is true, the call is also cancelled and fails with
+    /// [`AiScriptRuntimeError::NativeTimeout`](crate::errors::AiScriptRuntimeError::NativeTimeout),
+    /// unlike `max_step`, which cannot stop a native function.
+    ///
+    /// Calls are not watched in synchronous execution, where they cannot wait. The functions of
+    /// the standard library and the built-in methods of values are not watched either, since
+    /// some of them run the callbacks of scripts, e.g. `arr.map`, whose time is limited by
+    /// `max_step` instead.
+    pub fn with_native_timeout(self, timeout: Duration, cancel: bool) -> Self {
+        Interpreter {
+            native_timeout: Some((timeout, cancel)),
+            ..self
+        }
+    }
+
+    /// Sets the catalog of the messages of errors, which are the messages of the error values
+    /// scripts catch, e.g. by `Core:try`. Hosts can display errors with the same messages via
+    /// [`AiScriptError::localized`] with [`Self::messages`].
+    pub fn with_messages(self, messages: MessageCatalog) -> Self {
+        Interpreter { messages, ..self }
+    }
+
+    pub fn messages(&self) -> &MessageCatalog {
+        &self.messages
+    }
+
+    /// Awaits a call of a native function watched by [`Self::with_native_timeout`].
+    async fn watch_native(
+        &self,
+        call: impl Future<Output = Result<Value, AiScriptError>>,
+        name: Option<&str>,
+        loc: Option<&ast::Loc>,
+        (timeout, cancel): (Duration, bool),
+    ) -> Result<Value, AiScriptError> {
+        let timer = match &self.clock {
+            Some(clock) => clock.sleep(timeout),
+            None => SystemClock.sleep(timeout),
+        };
+        match future::select(pin!(call), timer).await {
+            Either::Left((result, _)) => result,
+            Either::Right(((), call)) => {
+                let name = name.unwrap_or("<anonymous>");
+                self.warn(Warning {
+                    code: "native_timeout".to_string(),
+                    message: format!("{name} has been running for more than {timeout:?}"),
+                    loc: loc.cloned(),
+                })
+                .await;
+                if cancel {
+                    Err(AiScriptRuntimeError::NativeTimeout {
+                        name: name.to_string(),
+                        timeout,
+                    })?
+                }
+                call.await
+            }
+        }
+    }
+
+    /// Records the time spent in each function for [`Self::profile`], e.g. to find out what is
+    /// slow in a script. The children created via [`Self::spawn_child`] record into the same
+    /// profile.
+    pub fn with_profiling(self, profiling: bool) -> Self {
+        Interpreter {
+            profiler: profiling.then(Arc::default),
+            ..self
+        }
+    }
+
+    /// Returns the time spent in the functions called so far by the name they are called with,
+    /// longest self time first, if enabled by [`Self::with_profiling`].
+    pub fn profile(&self) -> Vec<ProfileEntry> {
+        self.profiler
+            .as_ref()
+            .map(|profiler| profiler.report())
+            .unwrap_or_default()
+    }
+
+    /// Sets the source map of the scripts to execute, so that runtime errors report where the
+    /// failing code came from (see [`AiScriptError::origin`]), e.g. when the scripts are generated
+    /// by a visual editor.
+    pub fn with_source_map(self, source_map: ast::SourceMap) -> Self {
+        Interpreter {
+            source_map: Some(Arc::new(source_map)),
+            ..self
+        }
+    }
+
+    /// Returns the origin of `loc` in the source map, if any (see [`Self::with_source_map`]).
+    fn origin(&self, loc: Option<&ast::Loc>) -> Option<String> {
+        let source_map = self.source_map.as_ref()?;
+        source_map.origin(loc?).map(str::to_string)
+    }
+
+    /// Adds the method `name` to the values of `type_`, e.g. `"text".fancy()`, which calls
+    /// `method` with the value, the arguments and the interpreter.
+    ///
+    /// Built-in props, such as `len` of strings, cannot be overridden and result in an error.
+    /// Since props of objects are their entries, an entry of an object shadows the method of the
+    /// same name. Adding a method of the same type and name again replaces the method.
+    pub fn with_prim_prop(
+        mut self,
+        type_: PrimType,
+        name: impl Into<String>,
+        method: impl Fn(Value, Vec<Value>, &Interpreter) -> BoxFuture<'static, Result<Value, AiScriptError>>
+            + Sync
+            + Send
+            + 'static,
+    ) -> Result<Self, AiScriptError> {
+        let method: PrimPropFn = Arc::new(method);
+        Arc::make_mut(&mut self.prim_props).insert(type_, name.into(), method)?;
+        Ok(self)
+    }
+
+    /// Compares values as `==` does in this interpreter (see [`Self::with_reference_equality`]).
+    pub(crate) fn equals(&self, a: &Value, b: &Value) -> bool {
+        if self.reference_equality {
+            a.ref_eq(b)
+        } else {
+            a.deep_eq(b)
+        }
+    }
+
+    /// Sets the name of the script recorded in the tracing spans of the executions.
+    #[cfg(feature = "tracing")]
+    pub fn with_script_name(self, script_name: impl Into<Arc<str>>) -> Self {
+        Interpreter {
+            script_name: Some(script_name.into()),
+            ..self
+        }
+    }
+
+    /// Sets a callback which receives warnings, which do not stop the execution.
+    ///
+    /// The interpreter warns when a template interpolates null and when a function with the
+    /// `#[deprecated]` attribute, optionally with a message, is called. Native functions can
+    /// warn via [`Self::warn`].
+    pub fn with_warn(
+        self,
+        warn: impl Fn(Warning) -> BoxFuture<'static, ()> + Sync + Send + 'static,
+    ) -> Self {
+        Interpreter {
+            warn: Some(Arc::new(warn)),
+            ..self
+        }
+    }
+
+    /// Sets a callback which answers the requests for input of `readline`, `Ui:input`,
+    /// `Ui:confirm` and `Ui:select`, e.g. to render a prompt suited to the kind of the answer,
+    /// instead of the input callback of [`Self::new`], which only receives the question.
+    ///
+    /// The callback returns `None` if the user gives no answer, in which case the script gets the
+    /// default answer of the request if any, or null. An answer which is not of the requested
+    /// kind (see [`InputKind`]) is treated as no answer.
+    pub fn with_input(
+        self,
+        input: impl Fn(InputRequest) -> BoxFuture<'static, Option<Value>> + Sync + Send + 'static,
+    ) -> Self {
+        Interpreter {
+            input: Some(Arc::new(input)),
+            ..self
+        }
+    }
+
+    /// Asks the user for input via the callback set via [`Self::with_input`], or else the input
+    /// callback of [`Self::new`], and returns the answer or the default one.
+    pub(crate) async fn request_input(&self, request: InputRequest) -> Option<Value> {
+        let answer = if let Some(input) = &self.input {
+            input(request.clone())
+                .await
+                .and_then(|answer| request.accept(answer))
+        } else if let Some(in_) = &self.in_ {
+            request.parse(&in_(request.text_prompt()).await)
+        } else {
+            None
+        };
+        answer.or(request.default)
+    }
+
+    /// Reports a warning to the callback set via [`Self::with_warn`], if any.
+    pub async fn warn(&self, warning: Warning) {
+        if let Some(warn) = &self.warn {
+            warn(warning).await;
+        }
+    }
+
+    /// Tags the function `name`, e.g. one given to [`Self::new`], with the capability which it
+    /// needs, for [`Self::with_permissions`]. The functions of the standard library,
+    /// `FsCapability` and `HttpPolicy` are tagged already, e.g. `Math:rnd` with
+    /// [`Capability::Random`].
+    ///
+    /// A method of native objects is named by the type name and the method name, e.g.
+    /// `row.save` for the method `save` of [`NativeObject`](crate::values::NativeObject)s whose
+    /// type name is `row`.
+    pub fn with_capability(self, name: impl Into<String>, capability: Capability) -> Self {
+        let mut permissions = (*self.permissions).clone();
+        permissions.tag(name.into(), capability);
+        permissions.bind(&self.scope);
+        Interpreter {
+            permissions: Arc::new(permissions),
+            ..self
+        }
+    }
+
+    /// Declares the type of the native function `name`, e.g. one given to [`Self::new`], which
+    /// can be parsed via [`Parser::parse_type`](crate::Parser::parse_type). The arguments are
+    /// checked against the types of the parameters before the function is called, failing with
+    /// [`AiScriptRuntimeError::Type`](crate::errors::AiScriptRuntimeError::Type) otherwise,
+    /// where a missing argument is null.
+    ///
+    /// A method of native objects is named as in [`Self::with_capability`], e.g. `row.save`.
+    pub fn with_native_type(self, name: impl Into<String>, type_: ast::FnTypeSource) -> Self {
+        let mut native_types = (*self.native_types).clone();
+        native_types.declare(name.into(), type_);
+        native_types.bind(&self.scope);
+        Interpreter {
+            native_types: Arc::new(native_types),
+            ..self
+        }
+    }
+
+    /// Returns the type of the native function `name` declared via [`Self::with_native_type`],
+    /// e.g. for checking the types of scripts before running them.
+    pub fn native_type(&self, name: &str) -> Option<&ast::FnTypeSource> {
+        self.native_types.get(name)
+    }
+
+    /// Allows scripts to call only the functions tagged with the capabilities in `allow`,
+    /// besides the untagged ones. Calling another tagged function fails with
+    /// [`AiScriptRuntimeError::PermissionDenied`](crate::errors::AiScriptRuntimeError::PermissionDenied)
+    /// naming the capability, even if the function is stored in another variable.
+    ///
+    /// The children created via [`Self::spawn_child`], including by `Core:eval_sandbox`, have the
+    /// same permissions.
+    pub fn with_permissions(self, allow: impl IntoIterator<Item = Capability>) -> Self {
+        let mut permissions = (*self.permissions).clone();
+        permissions.allow(allow.into_iter().collect());
+        permissions.bind(&self.scope);
+        Interpreter {
+            permissions: Arc::new(permissions),
+            ..self
         }
     }
 
+    /// Creates an interpreter which runs scripts isolated from this one, such as code supplied by
+    /// users.
+    ///
+    /// The child has its own scope, which has the standard library and only the variables of
+    /// this interpreter named in `allow`. The child shares the step count with this interpreter,
+    /// so that its steps are charged to this one, and it can take at most `max_step` steps and
+    /// the steps this interpreter has left. The child prints via the output callback of this
+    /// interpreter and returns errors instead of passing them to the error callback, and it has
+    /// no input nor event handlers. The other options, the methods added to values and the
+    /// metrics are shared with this interpreter, and so is the abort state: aborting either of
+    /// them stops both.
+    pub fn spawn_child(
+        &self,
+        allow: impl IntoIterator<Item = impl AsRef<str>>,
+        max_step: Option<usize>,
+    ) -> Result<Interpreter, AiScriptError> {
+        let consts = allow
+            .into_iter()
+            .map(|name| {
+                let name = name.as_ref();
+                Ok((name.to_string(), self.scope.get(name)?))
+            })
+            .collect::<Result<Vec<_>, AiScriptError>>()?;
+        let step_count = self.step_count.load(Ordering::SeqCst);
+        let remaining = self
+            .max_step
+            .map(|max_step| max_step.saturating_sub(step_count));
+        let max_step = match (max_step, remaining) {
+            (Some(max_step), Some(remaining)) => Some(max_step.min(remaining)),
+            (max_step, remaining) => max_step.or(remaining),
+        };
+        let Interpreter {
+            scope, builtins, ..
+        } = Interpreter::new(
+            consts,
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            None,
+        );
+        let mut child = Interpreter {
+            scope,
+            builtins,
+            max_step: max_step.map(|max_step| step_count.saturating_add(max_step)),
+            in_: None,
+            input: None,
+            err: None,
+            event_handlers: Arc::new(Mutex::new(HashMap::new())),
+            source_map: None,
+            yielder: None,
+            timeline: None,
+            ..self.clone()
+        };
+        let mut permissions = (*self.permissions).clone();
+        permissions.bind(&child.scope);
+        child.permissions = Arc::new(permissions);
+        let mut native_types = (*self.native_types).clone();
+        native_types.bind(&child.scope);
+        child.native_types = Arc::new(native_types);
+        Ok(child)
+    }
+
+    /// Creates an interpreter that runs scripts in isolation from this one, sharing the standard
+    /// library, the constants and the options without building them again.
+    ///
+    /// Variables defined by the scripts run on the fork are not visible from this interpreter.
+    /// The mutable variables of this interpreter are copied to the fork, so reassigning them does
+    /// not affect this interpreter either, while arrays and objects in them are still shared.
+    /// The step count, the abort state, the event handlers and the metrics start fresh.
+    pub fn fork(&self) -> Interpreter {
+        Interpreter {
+            step_count: Arc::new(AtomicUsize::new(0)),
+            stop: Arc::new(AtomicBool::new(false)),
+            scope: self
+                .scope
+                .create_child_scope(self.scope.get_all_mut(), Some("<root>".to_string())),
+            abort_handlers: Arc::new(Mutex::new(tokio::task::JoinSet::new())),
+            abort_notify: Arc::new(tokio::sync::Notify::new()),
+            event_handlers: Arc::new(Mutex::new(HashMap::new())),
+            metrics: Arc::new(MetricsState::default()),
+            profiler: self.profiler.as_ref().map(|_| Arc::default()),
+            yielder: None,
+            timeline: None,
+            ..self.clone()
+        }
+    }
+
+    /// Frees arrays, objects and variables of functions which are referenced only from reference
+    /// cycles, e.g. an array pushed into itself or a function stored in a variable of the scope
+    /// it is defined in, and returns the number of them, which become empty.
+    ///
+    /// Such cycles are not freed by reference counting alone, so a long-lived interpreter, e.g.
+    /// one with event handlers, should call this periodically. It should be called while no
+    /// script is running on the interpreter on another thread, since references moved by the
+    /// script during the collection may be missed.
+    pub fn collect_cycles(&self) -> usize {
+        self.cycles.collect()
+    }
+
+    /// Records `arr` as a candidate of [`Self::collect_cycles`] if storing `value` in it may
+    /// create a cycle.
+    pub(crate) fn track_arr(&self, arr: &VArr, value: &Value) {
+        if may_refer(value) {
+            self.cycles.add_arr(arr);
+        }
+    }
+
+    /// Records `obj` as a candidate of [`Self::collect_cycles`] if storing `value` in it may
+    /// create a cycle.
+    pub(crate) fn track_obj(&self, obj: &VObj, value: &Value) {
+        if may_refer(value) {
+            self.cycles.add_obj(obj);
+        }
+    }
+
+    /// Returns the counters of the executions so far.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+            .snapshot(self.step_count.load(Ordering::SeqCst))
+    }
+
     pub async fn exec(&self, script: Vec<ast::Node>) -> Result<Option<Value>, AiScriptError> {
         self.stop.store(false, Ordering::SeqCst);
-        let script = self.collect_ns(script, self.scope.clone()).await?;
-        let result = self.run(script, &self.scope).await;
+        let collect_ns = self.collect_ns(&script, self.scope.clone());
+        #[cfg(feature = "tracing")]
+        let collect_ns = collect_ns.instrument(tracing::debug_span!(
+            "aiscript.collect_ns",
+            script = self.script_name.as_deref(),
+        ));
+        collect_ns.await?;
+        let result = self.run_top_level(&script).await;
         self.handle_error(result).await
     }
 
+    /// Re-executes the definitions of an updated script for live coding, and returns the names of
+    /// the mutable variables which were reset.
+    ///
+    /// The other top-level statements are not executed. A mutable variable keeps its value from
+    /// the previous execution if it was mutable and has the same type, while functions, constants
+    /// and namespaces are always redefined. Closures see the preserved values since they refer to
+    /// the same scope.
+    ///
+    /// If the updated script fails, the previous definitions are restored and the error is
+    /// returned, after being passed to the error callback if any.
+    pub async fn reload(&self, script: Vec<ast::Node>) -> Result<Vec<String>, AiScriptError> {
+        fn collect_ns_names(ns: &ast::Namespace, prefix: &str, names: &mut Vec<String>) {
+            let prefix = format!("{prefix}{}:", ns.name);
+            for member in &ns.members {
+                match member {
+                    ast::DefinitionOrNamespace::Definition(definition) => {
+                        names.push(format!("{prefix}{}", definition.name))
+                    }
+                    ast::DefinitionOrNamespace::Namespace(ns) => {
+                        collect_ns_names(ns, &prefix, names)
+                    }
+                }
+            }
+        }
+
+        let mut names = Vec::new();
+        for node in &script {
+            match node {
+                ast::Node::Statement(ast::Statement::Definition(definition)) => {
+                    names.push(definition.name.clone())
+                }
+                ast::Node::Namespace(ns) => collect_ns_names(ns, "", &mut names),
+                _ => {}
+            }
+        }
+        let old = names
+            .iter()
+            .filter_map(|name| {
+                let variable = self.scope.remove(name)?;
+                Some((name.clone(), variable))
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.stop.store(false, Ordering::SeqCst);
+        let result = async {
+            self.collect_ns(&script, self.scope.clone()).await?;
+            let mut reset = Vec::new();
+            for node in &script {
+                let ast::Node::Statement(ast::Statement::Definition(definition)) = node else {
+                    continue;
+                };
+                self.eval(node, &self.scope).await?;
+                if !definition.mut_ {
+                    continue;
+                }
+                let value = self.scope.get(&definition.name)?;
+                match old.get(&definition.name) {
+                    Some(Variable::Mut(old))
+                        if std::mem::discriminant(&old.value)
+                            == std::mem::discriminant(&value.value) =>
+                    {
+                        self.scope
+                            .set(definition.name.clone(), Variable::Mut(old.clone()));
+                    }
+                    _ => reset.push(definition.name.clone()),
+                }
+            }
+            Ok::<_, AiScriptError>(reset)
+        }
+        .await;
+        if let Err(e) = &result {
+            for name in &names {
+                self.scope.remove(name);
+            }
+            for (name, variable) in old {
+                self.scope.set(name, variable);
+            }
+            if let Some(err) = &self.err {
+                err(e.clone()).await;
+            }
+        }
+        result
+    }
+
+    /// Runs the top-level statements of a script, each in a tracing span if enabled.
+    async fn run_top_level(&self, script: &[ast::Node]) -> Result<Value, AiScriptError> {
+        let mut v = Value::null();
+        for (i, node) in script.iter().enumerate() {
+            let eval = self.eval(node, &self.scope);
+            #[cfg(feature = "tracing")]
+            let eval = eval.instrument(tracing::debug_span!(
+                "aiscript.statement",
+                script = self.script_name.as_deref(),
+                start = node.loc().map(|loc| loc.start),
+                end = node.loc().map(|loc| loc.end),
+            ));
+            v = match eval.await {
+                Ok(v) => v,
+                Err(e) => {
+                    return Err(self.explain_undefined(e, script[i..].iter().map(NodeRef::from)))
+                }
+            };
+            if let V::Return(_) | V::Break | V::Continue = v.value {
+                return Ok(v);
+            }
+        }
+        Ok(v)
+    }
+
+    /// Executes AiScript with output and error callbacks which replace the ones given to the constructor
+    /// only for this execution. `None` keeps the original callback.
+    pub async fn exec_with_io(
+        &self,
+        script: Vec<ast::Node>,
+        out: Option<impl Fn(Value) -> BoxFuture<'static, ()> + Sync + Send + 'static>,
+        err: Option<impl Fn(AiScriptError) -> BoxFuture<'static, ()> + Sync + Send + 'static>,
+    ) -> Result<Option<Value>, AiScriptError> {
+        let mut interpreter = self.clone();
+        if let Some(out) = out {
+            interpreter.out = Some(Arc::new(out));
+        }
+        if let Some(err) = err {
+            interpreter.err = Some(Arc::new(err));
+        }
+        interpreter.exec(script).await
+    }
+
+    /// Executes AiScript and returns the result with all values printed during the execution, in order.
+    ///
+    /// The printed values are not passed to the output callback given to the constructor.
+    pub async fn exec_collect(
+        &self,
+        script: Vec<ast::Node>,
+    ) -> Result<(Option<Value>, Vec<Value>), AiScriptError> {
+        let outputs = Arc::new(Mutex::new(Vec::new()));
+        let result = self
+            .exec_with_io(
+                script,
+                Some({
+                    let outputs = outputs.clone();
+                    move |value| {
+                        outputs.lock().unwrap().push(value);
+                        async move {}.boxed()
+                    }
+                }),
+                None::<fn(_) -> _>,
+            )
+            .await?;
+        let outputs = std::mem::take(&mut *outputs.lock().unwrap());
+        Ok((result, outputs))
+    }
+
+    /// Executes AiScript until it completes or `token` is cancelled.
+    ///
+    /// Cancelling the token during the execution calls [`Self::abort`], which stops the script
+    /// at the next step and cancels the timers it started. The token is no longer watched after
+    /// the execution completed, so cancelling it later does not affect other executions; call
+    /// [`Self::abort`] to cancel the timers left running.
+    pub async fn exec_with_cancel(
+        &self,
+        script: Vec<ast::Node>,
+        token: CancellationToken,
+    ) -> Result<Option<Value>, AiScriptError> {
+        let exec = self.exec(script);
+        let cancelled = token.cancelled();
+        futures::pin_mut!(exec, cancelled);
+        match future::select(exec, cancelled).await {
+            Either::Left((result, _)) => result,
+            Either::Right((_, exec)) => {
+                self.abort();
+                exec.await
+            }
+        }
+    }
+
+    /// Executes AiScript synchronously, without an async runtime.
+    ///
+    /// All native functions called by the script must complete without waiting.
+    /// If one waits, e.g. `Core:sleep` or `Async:timeout`, the execution fails.
+    pub fn exec_sync(&self, script: Vec<ast::Node>) -> Result<Option<Value>, AiScriptError> {
+        Interpreter {
+            sync: true,
+            ..self.clone()
+        }
+        .exec(script)
+        .now_or_never()
+        .unwrap_or_else(|| {
+            Err(AiScriptRuntimeError::Runtime(
+                "Cannot wait in synchronous execution".to_string(),
+            ))?
+        })
+    }
+
+    /// Starts executing AiScript by a number of steps at a time via
+    /// [`StepExecution::run_steps`], e.g. to run many scripts in turns on one thread without
+    /// spawning a task for each.
+    ///
+    /// Like [`Self::exec_sync`], no async runtime is needed and native functions which wait,
+    /// e.g. `Core:sleep`, fail.
+    pub fn start(&self, script: Vec<ast::Node>) -> StepExecution {
+        let budget = Arc::new(StepBudget::default());
+        let interpreter = Interpreter {
+            sync: true,
+            step_budget: Some(budget.clone()),
+            ..self.clone()
+        };
+        StepExecution::new(
+            budget,
+            async move { interpreter.exec(script).await }.boxed(),
+        )
+    }
+
+    /// Returns an error if the current execution is synchronous (see [`Self::exec_sync`]).
+    pub(crate) fn expect_async(&self, name: &str) -> Result<(), AiScriptError> {
+        if self.sync {
+            Err(AiScriptRuntimeError::Runtime(format!(
+                "{name} cannot be called in synchronous execution"
+            )))?
+        }
+        Ok(())
+    }
+
+    /// Executes AiScript with a context value which native functions can get via [`Self::context`].
+    ///
+    /// The context is visible only to this execution, so executions with different contexts can run concurrently.
+    pub async fn exec_with_context(
+        &self,
+        script: Vec<ast::Node>,
+        context: Arc<dyn Any + Sync + Send>,
+    ) -> Result<Option<Value>, AiScriptError> {
+        Interpreter {
+            context: Some(context),
+            ..self.clone()
+        }
+        .exec(script)
+        .await
+    }
+
+    /// Executes AiScript with variables which shadow those of this interpreter, e.g. `print` or
+    /// `Math:rnd` of the standard library, only for this execution.
+    ///
+    /// The script runs in a new scope which has the overrides as constants, so its definitions
+    /// are not added to this interpreter and the original variables are seen again after it
+    /// returns. Executions with different overrides can run concurrently. Functions defined
+    /// before, e.g. by [`Self::exec`], keep referring to the original variables.
+    pub async fn exec_with_overrides(
+        &self,
+        script: Vec<ast::Node>,
+        overrides: HashMap<String, Value>,
+    ) -> Result<Option<Value>, AiScriptError> {
+        let overrides = overrides
+            .into_iter()
+            .map(|(name, value)| (name, Variable::Const(value)))
+            .collect();
+        Interpreter {
+            scope: self
+                .scope
+                .create_child_scope(overrides, Some("<root>".to_string())),
+            ..self.clone()
+        }
+        .exec(script)
+        .await
+    }
+
+    /// Executes a library script and returns its top-level constants, functions and namespace
+    /// members, sorted by name, which can be given to [`Self::new`] of other interpreters as
+    /// constants.
+    ///
+    /// The script runs in a new scope, so its definitions are not added to this interpreter.
+    /// The exported functions keep referring to the scope of the library, so the mutable
+    /// variables of the library are shared by all interpreters calling them.
+    /// Unlike [`Self::exec`], an error is always returned instead of being passed to the error
+    /// callback, since the library would be incomplete.
+    pub async fn exec_as_library(
+        &self,
+        script: Vec<ast::Node>,
+    ) -> Result<Vec<(String, Value)>, AiScriptError> {
+        let scope = self.scope.create_child_scope(HashMap::new(), None);
+        let interpreter = Interpreter {
+            scope: scope.clone(),
+            ..self.clone()
+        };
+        interpreter.stop.store(false, Ordering::SeqCst);
+        interpreter.collect_ns(&script, scope.clone()).await?;
+        interpreter.run_top_level(&script).await?;
+        Ok(scope.get_own_consts())
+    }
+
+    /// Returns the context of the current execution if it is of type `T`.
+    pub fn context<T: Any>(&self) -> Option<&T> {
+        self.context.as_deref()?.downcast_ref()
+    }
+
     /// Executes AiScript Function.
     ///
     /// When it fails,
@@ -154,43 +1264,123 @@ impl Interpreter {
         fn_: VFn,
         args: impl IntoIterator<Item = Value>,
     ) -> Result<Value, AiScriptError> {
-        self.fn_(fn_, args).await
+        self.fn_(fn_, args).await
+    }
+
+    /// Executes AiScript Function with arguments given by position and by the names of the
+    /// parameters.
+    ///
+    /// Like [`Self::exec_fn_simple`], an error is always returned. Native functions only take
+    /// positional arguments, since the names of their parameters are not known.
+    pub async fn call(&self, fn_: VFn, args: ArgMap) -> Result<Value, AiScriptError> {
+        let args = args.resolve(&fn_)?;
+        self.fn_(fn_, args).await
+    }
+
+    /// Executes AiScript Function.
+    ///
+    /// Almost same as [`Self::exec_fn_simple`] but a runtime error is converted into an error value
+    /// (see [`AiScriptError::to_value`]) instead of being returned.
+    pub async fn exec_fn_catching(
+        &self,
+        fn_: VFn,
+        args: impl IntoIterator<Item = Value>,
+    ) -> Result<Value, AiScriptError> {
+        match self.fn_(fn_, args).await {
+            Err(e @ AiScriptError::Runtime(_)) => Ok(e.to_value_localized(&self.messages)),
+            result => result,
+        }
     }
 
-    pub fn collect_metadata(script: Vec<ast::Node>) -> IndexMap<Option<String>, Option<Value>> {
-        fn node_to_value(node: ast::Expression) -> Option<Value> {
-            match node {
-                ast::Expression::Arr(ast::Arr { value, .. }) => Some(Value::arr({
-                    let mut vec = Vec::new();
-                    for node in value {
-                        if let Some(value) = node_to_value(node) {
-                            vec.push(value);
-                        }
-                    }
-                    vec
-                })),
-                ast::Expression::Bool(ast::Bool { value, .. }) => Some(Value::bool(value)),
-                ast::Expression::Null(_) => Some(Value::null()),
-                ast::Expression::Num(ast::Num { value, .. }) => Some(Value::num(value)),
-                ast::Expression::Obj(ast::Obj { value, .. }) => Some(Value::obj({
-                    let mut obj = IndexMap::new();
-                    for (k, v) in value.into_iter() {
-                        if let Some(value) = node_to_value(v) {
-                            obj.insert(k, value);
-                        }
-                    }
-                    obj
-                })),
-                ast::Expression::Str(ast::Str { value, .. }) => Some(Value::str(value)),
-                _ => None,
+    /// Registers a handler which is called with a value each time the host dispatches the event `name`
+    /// via [`Self::dispatch_event`]. Scripts register handlers with `Event:on(name, fn)`.
+    ///
+    /// Handlers are kept, together with the scopes they capture, after the execution which registered
+    /// them returns, until [`Self::off_event`] is called. Clones of the interpreter share the handlers.
+    pub fn on_event(&self, name: impl Into<String>, handler: VFn) {
+        self.event_handlers
+            .lock()
+            .unwrap()
+            .entry(name.into())
+            .or_default()
+            .push(handler);
+    }
+
+    /// Removes all handlers of the event `name`.
+    pub fn off_event(&self, name: &str) {
+        self.event_handlers.lock().unwrap().remove(name);
+    }
+
+    /// Calls the handlers of the event `name` with `value` in the order of registration.
+    ///
+    /// If a handler fails and error callback is registered via constructor, the error is passed to the callback
+    /// and the remaining handlers are still called. Otherwise, the error is returned.
+    /// Unlike [`Self::exec_fn`], a failed handler does not abort the interpreter.
+    pub async fn dispatch_event(&self, name: &str, value: Value) -> Result<(), AiScriptError> {
+        let handlers = self
+            .event_handlers
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .unwrap_or_default();
+        for handler in handlers {
+            if let Err(e) = self.fn_(handler, [value.clone()]).await {
+                match &self.err {
+                    Some(err) => err(e).await,
+                    None => Err(e)?,
+                }
             }
         }
+        Ok(())
+    }
+
+    /// Returns the top-level functions with the attribute `attr`, such as `#[Event "note_created"]`,
+    /// as triples of the name, the attribute value and the function, sorted by name. A function
+    /// appears once for each such attribute.
+    pub fn definitions_with_attr(&self, attr: &str) -> Vec<(String, Value, VFn)> {
+        self.scope
+            .inspect()
+            .into_iter()
+            .filter_map(|binding| match binding.value.value {
+                V::Fn(fn_) => Some((binding.name, binding.attr, *fn_)),
+                _ => None,
+            })
+            .flat_map(|(name, attrs, fn_)| {
+                attrs
+                    .into_iter()
+                    .filter(|a| a.name == attr)
+                    .map(move |a| (name.clone(), a.value, fn_.clone()))
+            })
+            .collect()
+    }
 
+    /// Reports identifiers in the script which are declared neither in the script nor in the
+    /// scope of the interpreter, i.e. the standard library and the constants given to
+    /// [`Self::new`], without executing it.
+    ///
+    /// Identifiers checked by `exists` are not reported.
+    pub fn unknown_identifiers(&self, script: &[ast::Node]) -> Vec<Diagnostic> {
+        ScopeInfo::new(script)
+            .unresolved
+            .into_iter()
+            .filter(|reference| !self.scope.exists(&reference.name))
+            .map(|reference| Diagnostic {
+                message: format!("Unknown identifier: {}", reference.name),
+                loc: reference.loc,
+            })
+            .collect()
+    }
+
+    /// Collects the metadata of a script, given by `### name value`. Values other than literals,
+    /// which [`Parser::with_const_metadata`](crate::Parser::with_const_metadata) allows, are
+    /// evaluated only if constant, and are `None` otherwise.
+    pub fn collect_metadata(script: Vec<ast::Node>) -> IndexMap<Option<String>, Option<Value>> {
         let mut meta = IndexMap::new();
 
         for node in script {
             if let ast::Node::Meta(ast::Meta { name, value, .. }) = node {
-                meta.insert(name, node_to_value(value));
+                meta.insert(name, const_value(value));
             }
         }
 
@@ -216,52 +1406,44 @@ impl Interpreter {
         }
     }
 
-    async fn collect_ns(
-        &self,
-        script: impl IntoIterator<Item = impl Into<ast::Node>>,
-        scope: Scope,
-    ) -> Result<Vec<ast::Node>, AiScriptError> {
-        let mut nodes = Vec::new();
+    async fn collect_ns(&self, script: &[ast::Node], scope: Scope) -> Result<(), AiScriptError> {
         for node in script {
-            match node.into() {
-                ast::Node::Namespace(namespace) => {
-                    self.collect_ns_member(namespace.clone(), scope.clone())
-                        .await?;
-                    nodes.push(ast::Node::Namespace(namespace));
-                }
-                node => nodes.push(node),
+            if let ast::Node::Namespace(namespace) = node {
+                self.collect_ns_member(namespace, scope.clone()).await?;
             }
         }
-        Ok(nodes)
+        Ok(())
     }
 
-    fn collect_ns_member(
-        &self,
-        ns: ast::Namespace,
+    fn collect_ns_member<'a>(
+        &'a self,
+        ns: &'a ast::Namespace,
         scope: Scope,
-    ) -> BoxFuture<'_, Result<(), AiScriptError>> {
+    ) -> BoxFuture<'a, Result<(), AiScriptError>> {
         async move {
-            let ns_scope = scope.create_child_namespace_scope(ns.name, HashMap::new(), None);
+            let ns_scope =
+                scope.create_child_namespace_scope(ns.name.clone(), HashMap::new(), None);
             for node in &ns.members {
                 if let ast::DefinitionOrNamespace::Namespace(ns) = node {
-                    self.collect_ns_member(ns.clone(), ns_scope.clone()).await?;
+                    self.collect_ns_member(ns, ns_scope.clone()).await?;
                 }
             }
-            for node in ns.members {
+            for node in &ns.members {
                 if let ast::DefinitionOrNamespace::Definition(ast::Definition {
                     name,
                     expr,
                     mut_,
+                    loc,
                     ..
                 }) = node
                 {
-                    if mut_ {
+                    if *mut_ {
                         Err(AiScriptError::Internal(
                             "Namespaces cannot include mutable variable: {name}".to_string(),
                         ))?;
                     } else {
                         let variable = Variable::Const(self.eval(expr, &ns_scope).await?);
-                        ns_scope.add(name, variable)?;
+                        ns_scope.add_at(name.clone(), variable, loc.clone())?;
                     }
                 }
             }
@@ -270,11 +1452,97 @@ impl Interpreter {
         .boxed()
     }
 
+    /// Tests whether a value matches a pattern of `match`, collecting the variables which the
+    /// pattern binds.
+    fn match_pattern<'a>(
+        &'a self,
+        pattern: &'a ast::Pattern,
+        value: Value,
+        scope: &'a Scope,
+        bindings: &'a mut HashMap<String, Variable>,
+    ) -> BoxFuture<'a, Result<bool, AiScriptError>> {
+        async move {
+            match pattern {
+                ast::Pattern::Expr(expr) => {
+                    let q = self.eval(expr, scope).await?;
+                    Ok(self.equals(&value, &q))
+                }
+                ast::Pattern::Bind { name, .. } => {
+                    bindings.insert(name.clone(), Variable::Const(value));
+                    Ok(true)
+                }
+                ast::Pattern::Arr { items, .. } => {
+                    let V::Arr(arr) = value.value else {
+                        return Ok(false);
+                    };
+                    let arr = arr.read().unwrap().clone();
+                    if arr.len() != items.len() {
+                        return Ok(false);
+                    }
+                    for (item, value) in zip(items, arr) {
+                        if !self.match_pattern(item, value, scope, bindings).await? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+                ast::Pattern::Obj { entries, .. } => {
+                    let V::Obj(obj) = value.value else {
+                        return Ok(false);
+                    };
+                    let obj = obj.read().unwrap().clone();
+                    for (key, pattern) in entries {
+                        let Some(value) = obj.get(key) else {
+                            return Ok(false);
+                        };
+                        if !self
+                            .match_pattern(pattern, value.clone(), scope, bindings)
+                            .await?
+                        {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+            }
+        }
+        .boxed()
+    }
+
     fn fn_(
         &self,
         fn_: VFn,
         args: impl IntoIterator<Item = Value>,
     ) -> BoxFuture<'_, Result<Value, AiScriptError>> {
+        self.fn_named(None, fn_, args)
+    }
+
+    /// Calls a function like [`Self::fn_`], recording the call under `name` if profiling.
+    fn fn_named(
+        &self,
+        name: Option<&str>,
+        fn_: VFn,
+        args: impl IntoIterator<Item = Value>,
+    ) -> BoxFuture<'_, Result<Value, AiScriptError>> {
+        let Some(profiler) = &self.profiler else {
+            return self.fn_unprofiled(fn_, args);
+        };
+        let frame = profiler.enter(name, matches!(fn_, VFn::FnNative(_)));
+        let call = self.fn_unprofiled(fn_, args);
+        async move {
+            let result = call.await;
+            profiler.exit(frame);
+            result
+        }
+        .boxed()
+    }
+
+    fn fn_unprofiled(
+        &self,
+        fn_: VFn,
+        args: impl IntoIterator<Item = Value>,
+    ) -> BoxFuture<'_, Result<Value, AiScriptError>> {
+        self.metrics.count_call(matches!(fn_, VFn::FnNative(_)));
         match fn_ {
             VFn::Fn {
                 args: fn_args,
@@ -289,195 +1557,290 @@ impl Interpreter {
                 )
                 .collect();
                 async move {
-                    self.run(statements, &scope.create_child_scope(args, None))
+                    // `yield` in a function called by a generator does not belong to the generator.
+                    let interpreter;
+                    let this = if self.yielder.is_some() {
+                        interpreter = Interpreter {
+                            yielder: None,
+                            ..self.clone()
+                        };
+                        &interpreter
+                    } else {
+                        self
+                    };
+                    this.run(statements.iter(), &scope.create_fn_scope(args))
                         .map(|r| r.map(unwrap_ret))
                         .await
                 }
                 .boxed()
             }
-            VFn::FnNative(fn_) => fn_(args.into_iter().collect(), self),
+            VFn::FnNative(fn_) => match self.permissions.denied(&fn_) {
+                Some((name, capability)) => {
+                    let error = AiScriptRuntimeError::PermissionDenied {
+                        name: name.clone(),
+                        capability: *capability,
+                    };
+                    async move { Err(error)? }.boxed()
+                }
+                None => {
+                    let args = args.into_iter().collect::<Vec<_>>();
+                    match self.native_types.check(&fn_, &args) {
+                        Ok(()) => fn_(args, self),
+                        Err(error) => async move { Err(error)? }.boxed(),
+                    }
+                }
+            },
         }
     }
 
     fn eval<'a>(
         &'a self,
-        node: impl Into<ast::Node>,
+        node: impl Into<NodeRef<'a>>,
+        scope: &'a Scope,
+    ) -> impl Future<Output = Result<Value, AiScriptError>> + Send + 'a {
+        self.eval_chain(node, scope)
+            .map(|result| result.map(Option::unwrap_or_default))
+    }
+
+    /// Evaluates a node like [`Self::eval`], but returns `None` if the node is a chain of calls,
+    /// indexes and properties which is short-circuited by an optional access (`?.`) on null.
+    fn eval_chain<'a>(
+        &'a self,
+        node: impl Into<NodeRef<'a>>,
         scope: &'a Scope,
-    ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+    ) -> BoxFuture<'a, Result<Option<Value>, AiScriptError>> {
         if self.stop.load(Ordering::SeqCst) {
-            return async move { Ok(Value::null()) }.boxed();
+            return async move { Ok(Some(Value::null())) }.boxed();
         }
         let node = node.into();
         async move {
             let step_count = self.step_count.load(Ordering::SeqCst);
-            if step_count % IRQ_RATE == IRQ_AT {
+            if step_count % IRQ_RATE == IRQ_AT && !self.sync {
                 tokio::time::sleep(Duration::from_millis(5)).await;
             }
+            if let Some(budget) = &self.step_budget {
+                budget.take().await;
+            }
             let step_count = self.step_count.fetch_add(1, Ordering::SeqCst);
             if let Some(max_step) = self.max_step {
                 if step_count > max_step {
-                    Err(AiScriptRuntimeError::Runtime(
-                        "max step exceeded".to_string(),
-                    ))?
+                    Err(AiScriptRuntimeError::MaxStepExceeded { in_loop: None })?
                 }
             }
-            Ok(match node {
-                ast::Node::Namespace(_) | ast::Node::Meta(_) => Value::null(),
-                ast::Node::Statement(statement) => match statement {
-                    ast::Statement::Definition(ast::Definition {
-                        name,
-                        expr,
-                        mut_,
-                        attr,
-                        ..
-                    }) => {
-                        let value = self.eval(expr, scope).await?;
-                        let attr = match attr {
-                            Some(attr) => {
-                                let mut attrs = Vec::new();
-                                for n_attr in attr {
-                                    attrs.push(Attr {
-                                        name: n_attr.name,
-                                        value: self.eval(n_attr.value, scope).await?,
-                                    })
-                                }
-                                Some(attrs)
-                            }
-                            None => None,
-                        };
-                        scope.add(
+            if let Some(timeline) = &self.timeline {
+                timeline.step(step_count, &self.scope).await;
+            }
+            Ok(Some(match node {
+                NodeRef::Node(ast::Node::Namespace(_) | ast::Node::Meta(_)) => Value::null(),
+                NodeRef::Node(ast::Node::Statement(statement)) | NodeRef::Statement(statement) => {
+                    match statement {
+                        ast::Statement::Definition(ast::Definition {
                             name,
-                            if mut_ {
-                                Variable::Mut(Value { attr, ..value })
-                            } else {
-                                Variable::Const(Value { attr, ..value })
-                            },
-                        )?;
-                        Value::null()
-                    }
-                    ast::Statement::Return(ast::Return { expr, .. }) => {
-                        let val = self.eval(expr, scope).await?;
-                        Value::return_(val)
-                    }
-                    ast::Statement::Each(ast::Each {
-                        items, for_, var, ..
-                    }) => {
-                        let items = self.eval(items, scope).await?;
-                        let items = <Vec<Value>>::try_from(items)?;
-                        for item in items {
-                            let scope = scope.create_child_scope(
-                                HashMap::from_iter([(var.clone(), Variable::Const(item))]),
-                                None,
-                            );
-                            let v = self.eval(*for_.clone(), &scope).await?;
-                            match *v.value {
-                                V::Break => {
-                                    break;
-                                }
-                                V::Return(_) => {
-                                    return Ok(v);
-                                }
-                                _ => (),
+                            expr,
+                            mut_,
+                            attr,
+                            loc,
+                            ..
+                        }) => {
+                            if self.strict_variables && scope.shadows(name) {
+                                Err(AiScriptRuntimeError::Shadowing(name.clone()))?
                             }
-                        }
-                        Value::null()
-                    }
-                    ast::Statement::For(ast::For {
-                        times,
-                        from,
-                        var,
-                        to,
-                        for_,
-                        ..
-                    }) => {
-                        if let Some(times) = times {
-                            let times = self.eval(times, scope).await?;
-                            let times = f64::try_from(times)?;
-                            let mut i = 0.0;
-                            while i < times {
-                                let v = self.eval(*for_.clone(), scope).await?;
-                                match *v.value {
-                                    V::Break => {
-                                        break;
-                                    }
-                                    V::Return(_) => {
-                                        return Ok(v);
+                            let value = self.eval(expr, scope).await?;
+                            let attr = match attr {
+                                Some(attr) => {
+                                    let mut attrs = Vec::new();
+                                    for n_attr in attr {
+                                        attrs.push(Attr {
+                                            name: n_attr.name.clone(),
+                                            value: self.eval(&n_attr.value, scope).await?,
+                                        })
                                     }
-                                    _ => (),
+                                    Some(attrs)
                                 }
-                                i += 1.0;
+                                None => None,
+                            };
+                            scope.add_at(
+                                name.clone(),
+                                if *mut_ {
+                                    Variable::Mut(Value { attr, ..value })
+                                } else {
+                                    Variable::Const(Value { attr, ..value })
+                                },
+                                loc.clone(),
+                            )?;
+                            Value::null()
+                        }
+                        ast::Statement::Return(ast::Return { expr, .. }) => {
+                            let val = self.eval(expr, scope).await?;
+                            Value::return_(val)
+                        }
+                        ast::Statement::Yield(ast::Yield { expr, .. }) => {
+                            let val = self.eval(expr, scope).await?;
+                            let Some(yielder) = &self.yielder else {
+                                Err(AiScriptRuntimeError::Runtime(
+                                    "Cannot yield outside of a generator function.".to_string(),
+                                ))?
+                            };
+                            yielder.yield_(val).await;
+                            Value::null()
+                        }
+                        ast::Statement::Each(ast::Each {
+                            items,
+                            for_,
+                            var,
+                            loc,
+                        }) => {
+                            if self.strict_variables && scope.defined_in_fn(var) {
+                                Err(AiScriptRuntimeError::Shadowing(var.clone()))?
                             }
-                        } else if let (Some(from), Some(to), Some(var)) = (from, to, var) {
-                            let from = self.eval(from, scope).await?;
-                            let to = self.eval(to, scope).await?;
-                            let from = f64::try_from(from)?;
-                            let to = f64::try_from(to)?;
-                            let mut i = from;
-                            while i < from + to {
+                            let items = self.eval(items, scope).await?;
+                            let mut items = EachIter::try_from(items)?;
+                            let mut iterations = 0;
+                            while let Some(item) = items.next(self).await? {
+                                iterations += 1;
+                                self.check_loop_iterations(loc, iterations)?;
                                 let scope = scope.create_child_scope(
-                                    HashMap::from_iter([(
-                                        var.clone(),
-                                        Variable::Const(Value::num(i)),
-                                    )]),
+                                    HashMap::from_iter([(var.clone(), Variable::Const(item))]),
                                     None,
                                 );
-                                let v = self.eval(*for_.clone(), &scope).await?;
-                                match *v.value {
+                                let v = self
+                                    .eval(for_.as_ref(), &scope)
+                                    .await
+                                    .map_err(|e| in_loop(e, loc, iterations))?;
+                                match v.value {
                                     V::Break => {
                                         break;
                                     }
                                     V::Return(_) => {
-                                        return Ok(v);
+                                        return Ok(Some(v));
                                     }
+                                    _ if self.stop.load(Ordering::SeqCst) => break,
                                     _ => (),
                                 }
-                                i += 1.0;
                             }
+                            Value::null()
                         }
-                        Value::null()
-                    }
-                    ast::Statement::Loop(ast::Loop { statements, .. }) => loop {
-                        let v = self
-                            .run(
-                                statements.clone(),
-                                &scope.create_child_scope(HashMap::new(), None),
-                            )
-                            .await?;
-                        match *v.value {
-                            V::Break => {
-                                break Value::null();
+                        ast::Statement::For(ast::For {
+                            times,
+                            from,
+                            var,
+                            to,
+                            for_,
+                            loc,
+                        }) => {
+                            let mut iterations = 0;
+                            if let Some(times) = times {
+                                let times = self.eval(times, scope).await?;
+                                let times = f64::try_from(times)?;
+                                let mut i = 0.0;
+                                while i < times {
+                                    iterations += 1;
+                                    self.check_loop_iterations(loc, iterations)?;
+                                    let v = self
+                                        .eval(for_.as_ref(), scope)
+                                        .await
+                                        .map_err(|e| in_loop(e, loc, iterations))?;
+                                    match v.value {
+                                        V::Break => {
+                                            break;
+                                        }
+                                        V::Return(_) => {
+                                            return Ok(Some(v));
+                                        }
+                                        _ if self.stop.load(Ordering::SeqCst) => break,
+                                        _ => (),
+                                    }
+                                    i += 1.0;
+                                }
+                            } else if let (Some(from), Some(to), Some(var)) = (from, to, var) {
+                                if self.strict_variables && scope.defined_in_fn(var) {
+                                    Err(AiScriptRuntimeError::Shadowing(var.clone()))?
+                                }
+                                let from = self.eval(from, scope).await?;
+                                let to = self.eval(to, scope).await?;
+                                let from = f64::try_from(from)?;
+                                let to = f64::try_from(to)?;
+                                let mut i = from;
+                                while i < from + to {
+                                    iterations += 1;
+                                    self.check_loop_iterations(loc, iterations)?;
+                                    let scope = scope.create_child_scope(
+                                        HashMap::from_iter([(
+                                            var.clone(),
+                                            Variable::Const(Value::num(i)),
+                                        )]),
+                                        None,
+                                    );
+                                    let v = self
+                                        .eval(for_.as_ref(), &scope)
+                                        .await
+                                        .map_err(|e| in_loop(e, loc, iterations))?;
+                                    match v.value {
+                                        V::Break => {
+                                            break;
+                                        }
+                                        V::Return(_) => {
+                                            return Ok(Some(v));
+                                        }
+                                        _ if self.stop.load(Ordering::SeqCst) => break,
+                                        _ => (),
+                                    }
+                                    i += 1.0;
+                                }
                             }
-                            V::Return(_) => {
-                                break v;
+                            Value::null()
+                        }
+                        ast::Statement::Loop(ast::Loop { statements, loc }) => {
+                            let mut iterations = 0;
+                            loop {
+                                iterations += 1;
+                                self.check_loop_iterations(loc, iterations)?;
+                                let v = self
+                                    .run(
+                                        statements,
+                                        &scope.create_child_scope(HashMap::new(), None),
+                                    )
+                                    .await
+                                    .map_err(|e| in_loop(e, loc, iterations))?;
+                                match v.value {
+                                    V::Break => {
+                                        break Value::null();
+                                    }
+                                    V::Return(_) => {
+                                        break v;
+                                    }
+                                    _ if self.stop.load(Ordering::SeqCst) => break Value::null(),
+                                    _ => (),
+                                }
                             }
-                            _ => (),
                         }
-                    },
-                    ast::Statement::Break(_) => Value::break_(),
-                    ast::Statement::Continue(_) => Value::continue_(),
-                    ast::Statement::Assign(ast::Assign { expr, dest, .. }) => {
-                        let v = self.eval(expr, scope).await?;
-                        self.assign(scope, dest, v).await?;
-                        Value::null()
-                    }
-                    ast::Statement::AddAssign(ast::AddAssign { expr, dest, .. }) => {
-                        let target = self.eval(dest.clone(), scope).await?;
-                        let target = f64::try_from(target)?;
-                        let v = self.eval(expr, scope).await?;
-                        let v = f64::try_from(v)?;
-                        self.assign(scope, dest, Value::num(target + v)).await?;
-                        Value::null()
-                    }
-                    ast::Statement::SubAssign(ast::SubAssign { expr, dest, .. }) => {
-                        let target = self.eval(dest.clone(), scope).await?;
-                        let target = f64::try_from(target)?;
-                        let v = self.eval(expr, scope).await?;
-                        let v = f64::try_from(v)?;
-                        self.assign(scope, dest, Value::num(target - v)).await?;
-                        Value::null()
+                        ast::Statement::Break(_) => Value::break_(),
+                        ast::Statement::Continue(_) => Value::continue_(),
+                        ast::Statement::Assign(ast::Assign { expr, dest, .. }) => {
+                            let v = self.eval(expr, scope).await?;
+                            self.assign(scope, dest, v).await?;
+                            Value::null()
+                        }
+                        ast::Statement::AddAssign(ast::AddAssign { expr, dest, .. }) => {
+                            let target = self.eval(dest, scope).await?;
+                            let target = f64::try_from(target)?;
+                            let v = self.eval(expr, scope).await?;
+                            let v = f64::try_from(v)?;
+                            self.assign(scope, dest, Value::num(target + v)).await?;
+                            Value::null()
+                        }
+                        ast::Statement::SubAssign(ast::SubAssign { expr, dest, .. }) => {
+                            let target = self.eval(dest, scope).await?;
+                            let target = f64::try_from(target)?;
+                            let v = self.eval(expr, scope).await?;
+                            let v = f64::try_from(v)?;
+                            self.assign(scope, dest, Value::num(target - v)).await?;
+                            Value::null()
+                        }
                     }
-                },
-                ast::Node::Expression(expression) => match expression {
+                }
+                NodeRef::Node(ast::Node::Expression(expression))
+                | NodeRef::Expression(expression) => match expression {
                     ast::Expression::If(ast::If {
                         cond,
                         then,
@@ -485,42 +1848,79 @@ impl Interpreter {
                         else_,
                         ..
                     }) => {
-                        let cond = self.eval(*cond, scope).await?;
+                        let cond = self.eval(cond.as_ref(), scope).await?;
                         let cond = bool::try_from(cond)?;
                         if cond {
-                            self.eval(*then, scope).await?
+                            self.eval(then.as_ref(), scope).await?
                         } else {
                             for ast::Elseif { cond, then } in elseif {
                                 let cond = self.eval(cond, scope).await?;
                                 let cond = bool::try_from(cond)?;
                                 if cond {
-                                    return self.eval(then, scope).await;
+                                    return self.eval(then, scope).await.map(Some);
                                 }
                             }
                             if let Some(else_) = else_ {
-                                self.eval(*else_, scope).await?
+                                self.eval(else_.as_ref(), scope).await?
                             } else {
                                 Value::null()
                             }
                         }
                     }
-                    ast::Expression::Fn(ast::Fn { args, children, .. }) => Value::fn_(
-                        args.into_iter().map(|arg| arg.name),
-                        children,
-                        scope.clone(),
-                    ),
+                    ast::Expression::Fn(
+                        fn_ @ ast::Fn {
+                            args,
+                            generator: false,
+                            ..
+                        },
+                    ) => {
+                        self.cycles.add_scope(scope);
+                        Value::new(V::Fn(Box::new(VFn::Fn {
+                            args: args.iter().map(|arg| arg.name.clone()).collect(),
+                            statements: self.fn_bodies.get(fn_),
+                            scope: scope.clone(),
+                        })))
+                    }
+                    ast::Expression::Fn(
+                        fn_ @ ast::Fn {
+                            args,
+                            generator: true,
+                            ..
+                        },
+                    ) => {
+                        let fn_args = args.iter().map(|arg| arg.name.clone()).collect::<Vec<_>>();
+                        let statements = self.fn_bodies.get(fn_);
+                        let scope = scope.clone();
+                        Value::fn_native(move |args, interpreter| {
+                            let generator = Generator::new(
+                                interpreter,
+                                &fn_args,
+                                statements.clone(),
+                                &scope,
+                                args,
+                            );
+                            async move { Ok(Value::native_object(generator)) }.boxed()
+                        })
+                    }
                     ast::Expression::Match(ast::Match {
                         about, qs, default, ..
                     }) => {
-                        let about = self.eval(*about, scope).await?;
+                        let about = self.eval(about.as_ref(), scope).await?;
                         for ast::QA { q, a } in qs {
-                            let q = self.eval(q, scope).await?;
-                            if about == q {
-                                return self.eval(a, scope).await;
+                            let mut bindings = HashMap::new();
+                            if self
+                                .match_pattern(q, about.clone(), scope, &mut bindings)
+                                .await?
+                            {
+                                if bindings.is_empty() {
+                                    return self.eval(a, scope).await.map(Some);
+                                }
+                                let scope = scope.create_child_scope(bindings, None);
+                                return self.eval(a, &scope).await.map(Some);
                             }
                         }
                         if let Some(default) = default {
-                            self.eval(*default, scope).await?
+                            self.eval(default.as_ref(), scope).await?
                         } else {
                             Value::null()
                         }
@@ -533,34 +1933,64 @@ impl Interpreter {
                         Value::bool(scope.exists(&identifier.name))
                     }
                     ast::Expression::Tmpl(ast::Tmpl { tmpl, .. }) => {
-                        let mut str = Vec::new();
+                        let mut str = String::with_capacity(
+                            tmpl.iter()
+                                .map(|x| match x {
+                                    ast::StringOrExpression::String(x) => x.len(),
+                                    ast::StringOrExpression::Expression(_) => 0,
+                                })
+                                .sum(),
+                        );
                         for x in tmpl {
                             match x {
-                                ast::StringOrExpression::String(x) => str.push(x),
+                                ast::StringOrExpression::String(x) => str.push_str(x),
                                 ast::StringOrExpression::Expression(x) => {
                                     let v = self.eval(x, scope).await?;
-                                    str.push(v.value.repr_value().to_string())
+                                    if let V::Null = v.value {
+                                        self.warn(Warning {
+                                            code: "null_in_template".to_string(),
+                                            message: "null is interpolated into a template"
+                                                .to_string(),
+                                            loc: x.loc().cloned(),
+                                        })
+                                        .await;
+                                    }
+                                    str.push_str(&self.repr_string(&v));
                                 }
                             }
                         }
-                        Value::str(str.concat())
+                        self.check_str_len(str.len())?;
+                        self.metrics.count_allocation(str.len());
+                        Value::str(str)
                     }
-                    ast::Expression::Str(ast::Str { value, .. }) => Value::str(value),
-                    ast::Expression::Num(ast::Num { value, .. }) => Value::num(value),
-                    ast::Expression::Bool(ast::Bool { value, .. }) => Value::bool(value),
+                    ast::Expression::Str(ast::Str { value, .. }) => {
+                        self.metrics.count_allocation(value.len());
+                        Value::str(value.clone())
+                    }
+                    ast::Expression::Num(ast::Num { value, .. }) => Value::num(*value),
+                    ast::Expression::Bool(ast::Bool { value, .. }) => Value::bool(*value),
                     ast::Expression::Null(_) => Value::null(),
                     ast::Expression::Obj(ast::Obj { value, .. }) => {
                         let mut obj = IndexMap::new();
                         for (k, v) in value {
-                            obj.insert(k, self.eval(v, scope).await?);
+                            obj.insert(k.clone(), self.eval(v, scope).await?);
                         }
+                        self.metrics.count_allocation(
+                            obj.keys()
+                                .map(|k| k.len() + size_of::<(String, Value)>())
+                                .sum(),
+                        );
                         Value::obj(obj)
                     }
-                    ast::Expression::Arr(ast::Arr { value, .. }) => Value::arr(
-                        try_join_all(value.into_iter().map(|node| self.eval(node, scope))).await?,
-                    ),
+                    ast::Expression::Arr(ast::Arr { value, .. }) => {
+                        self.metrics
+                            .count_allocation(value.len() * size_of::<Value>());
+                        Value::arr(
+                            try_join_all(value.iter().map(|node| self.eval(node, scope))).await?,
+                        )
+                    }
                     ast::Expression::Not(ast::Not { expr, .. }) => {
-                        let v = self.eval(*expr, scope).await?;
+                        let v = self.eval(expr.as_ref(), scope).await?;
                         let bool = bool::try_from(v)?;
                         Value::bool(!bool)
                     }
@@ -568,22 +1998,24 @@ impl Interpreter {
                         let Value {
                             value: left_value,
                             attr,
-                        } = self.eval(*left, scope).await?;
-                        let left_value = bool::try_from(*left_value)?;
+                            ..
+                        } = self.eval(left.as_ref(), scope).await?;
+                        let left_value = bool::try_from(left_value)?;
                         if !left_value {
                             Value {
-                                value: Box::new(V::Bool(left_value)),
                                 attr,
+                                ..Value::bool(left_value)
                             }
                         } else {
                             let Value {
                                 value: right_value,
                                 attr,
-                            } = self.eval(*right, scope).await?;
-                            let right_value = bool::try_from(*right_value)?;
+                                ..
+                            } = self.eval(right.as_ref(), scope).await?;
+                            let right_value = bool::try_from(right_value)?;
                             Value {
-                                value: Box::new(V::Bool(right_value)),
                                 attr,
+                                ..Value::bool(right_value)
                             }
                         }
                     }
@@ -591,43 +2023,172 @@ impl Interpreter {
                         let Value {
                             value: left_value,
                             attr,
-                        } = self.eval(*left, scope).await?;
-                        let left_value = bool::try_from(*left_value)?;
+                            ..
+                        } = self.eval(left.as_ref(), scope).await?;
+                        let left_value = bool::try_from(left_value)?;
                         if left_value {
                             Value {
-                                value: Box::new(V::Bool(left_value)),
                                 attr,
+                                ..Value::bool(left_value)
                             }
                         } else {
                             let Value {
                                 value: right_value,
                                 attr,
-                            } = self.eval(*right, scope).await?;
-                            let right_value = bool::try_from(*right_value)?;
+                                ..
+                            } = self.eval(right.as_ref(), scope).await?;
+                            let right_value = bool::try_from(right_value)?;
                             Value {
-                                value: Box::new(V::Bool(right_value)),
                                 attr,
+                                ..Value::bool(right_value)
                             }
                         }
                     }
-                    ast::Expression::Identifier(ast::Identifier { name, .. }) => {
-                        scope.get(&name)?
+                    ast::Expression::Coalesce(ast::Coalesce { left, right, .. }) => {
+                        let left = self.eval(left.as_ref(), scope).await?;
+                        if let V::Null = left.value {
+                            self.eval(right.as_ref(), scope).await?
+                        } else {
+                            left
+                        }
                     }
-                    ast::Expression::Call(ast::Call { target, args, .. }) => {
-                        let callee = self.eval(*target, scope).await?;
-                        let callee = VFn::try_from(callee)?;
+                    ast::Expression::Identifier(ast::Identifier { name, .. }) => scope.get(name)?,
+                    ast::Expression::Call(ast::Call {
+                        target,
+                        args,
+                        optional,
+                        loc,
+                    }) => {
+                        let name = match target.as_ref() {
+                            ast::Expression::Identifier(ast::Identifier { name, .. })
+                            | ast::Expression::Prop(ast::Prop { name, .. }) => Some(name.clone()),
+                            _ => None,
+                        };
+                        let mut native_method = None;
+                        let mut builtin_method = false;
+                        let callee = if let ast::Expression::Prop(ast::Prop {
+                            target,
+                            name,
+                            optional,
+                            ..
+                        }) = target.as_ref()
+                        {
+                            let target = self.eval_chain(target.as_ref(), scope).await?;
+                            let Some(value) = chain_target(target, *optional) else {
+                                return Ok(None);
+                            };
+                            let (method, name, builtin) = self.get_method(value, name)?;
+                            native_method = name;
+                            builtin_method = builtin;
+                            Some(method)
+                        } else {
+                            self.eval_chain(target.as_ref(), scope).await?
+                        };
+                        let Some(callee) = chain_target(callee, *optional) else {
+                            return Ok(None);
+                        };
+                        let name = native_method.or(name);
+                        if let Some(Attr { value, .. }) = callee
+                            .attr
+                            .iter()
+                            .flatten()
+                            .find(|attr| attr.name == "deprecated")
+                        {
+                            let mut message = format!(
+                                "{} is deprecated",
+                                name.as_deref().unwrap_or("<anonymous>")
+                            );
+                            if let V::Str(reason) = &value.value {
+                                write!(message, ": {reason}").unwrap();
+                            }
+                            self.warn(Warning {
+                                code: "deprecated".to_string(),
+                                message,
+                                loc: loc.clone(),
+                            })
+                            .await;
+                        }
+                        let callee = match callee.value {
+                            V::Fn(callee) => *callee,
+                            value => Err(AiScriptRuntimeError::NotCallable {
+                                name: name.clone(),
+                                got: value.display_type().to_string(),
+                                loc: loc.clone().map(Box::new),
+                            })?,
+                        };
                         let args =
-                            try_join_all(args.into_iter().map(|node| self.eval(node, scope)))
-                                .await?;
-                        self.fn_(callee, args).await?
-                    }
-                    ast::Expression::Index(ast::Index { target, index, .. }) => {
-                        let target = self.eval(*target, scope).await?;
-                        let i = self.eval(*index, scope).await?;
-                        match *target.value {
+                            try_join_all(args.iter().map(|node| self.eval(node, scope))).await?;
+                        let is_native = matches!(callee, VFn::FnNative(_));
+                        let is_builtin = builtin_method
+                            || match &callee {
+                                VFn::FnNative(fn_) => self.builtins.contains(&address(fn_)),
+                                _ => false,
+                            };
+                        if let (true, Some(name)) = (is_native, &name) {
+                            self.metrics.count_namespace_call(name);
+                        }
+                        let call = self.fn_named(name.as_deref(), callee, args);
+                        #[cfg(feature = "tracing")]
+                        let call = call.instrument(if is_native {
+                            tracing::trace_span!(
+                                "aiscript.native_call",
+                                name = name.as_deref(),
+                                script = self.script_name.as_deref(),
+                                start = loc.as_ref().map(|loc| loc.start),
+                                end = loc.as_ref().map(|loc| loc.end),
+                            )
+                        } else {
+                            tracing::Span::none()
+                        });
+                        let started = (is_native && self.native_timing).then(Instant::now);
+                        let call = match self.native_timeout {
+                            Some(native_timeout) if is_native && !is_builtin && !self.sync => {
+                                Either::Left(
+                                    self.watch_native(
+                                        call,
+                                        name.as_deref(),
+                                        loc.as_ref(),
+                                        native_timeout,
+                                    )
+                                    .boxed(),
+                                )
+                            }
+                            _ => Either::Right(call),
+                        };
+                        let result = call.await;
+                        if let (Some(started), Some(name)) = (started, &name) {
+                            self.metrics.add_native_time(name, started.elapsed());
+                        }
+                        match result {
+                            Err(AiScriptError::Runtime(e)) if !is_native => {
+                                Err(e.with_frame(StackFrame {
+                                    name,
+                                    origin: self.origin(loc.as_ref()),
+                                    loc: loc.clone(),
+                                }))?
+                            }
+                            Ok(value) if is_native => {
+                                self.check_len(&value)?;
+                                value
+                            }
+                            result => result?,
+                        }
+                    }
+                    ast::Expression::Index(ast::Index {
+                        target,
+                        index,
+                        optional,
+                        ..
+                    }) => {
+                        let target = self.eval_chain(target.as_ref(), scope).await?;
+                        let Some(target) = chain_target(target, *optional) else {
+                            return Ok(None);
+                        };
+                        let i = self.eval(index.as_ref(), scope).await?;
+                        match target.value {
                             V::Arr(arr) => {
                                 let i = f64::try_from(i)?;
-                                let item = if i.trunc() == i {
+                                let item = if i.trunc() == i && i >= 0.0 {
                                     arr.read().unwrap().get(i as usize).cloned()
                                 } else {
                                     None
@@ -649,6 +2210,26 @@ impl Interpreter {
                                     Value::null()
                                 }
                             }
+                            V::Bin(bin) => {
+                                let i = f64::try_from(i)?;
+                                let item = if i.trunc() == i && i >= 0.0 {
+                                    bin.get(i as usize).copied()
+                                } else {
+                                    None
+                                };
+                                if let Some(item) = item {
+                                    Value::num(item)
+                                } else {
+                                    Err(AiScriptRuntimeError::IndexOutOfRange(
+                                        i,
+                                        bin.len() as isize - 1,
+                                    ))?
+                                }
+                            }
+                            V::NativeObject(object) => {
+                                let i = String::try_from(i)?;
+                                object.get_prop(&i, self).await?
+                            }
                             target => Err(AiScriptRuntimeError::Runtime(format!(
                                 "Cannot read prop ({}) of {}.",
                                 i.value.repr_value(),
@@ -656,33 +2237,107 @@ impl Interpreter {
                             )))?,
                         }
                     }
-                    ast::Expression::Prop(ast::Prop { target, name, .. }) => {
-                        let value = self.eval(*target.clone(), scope).await?;
-                        if let V::Obj(value) = *value.value {
-                            if let Some(value) = value.read().unwrap().get(&name) {
-                                value.clone()
-                            } else {
-                                Value::null()
-                            }
-                        } else {
-                            get_prim_prop(value, name)?
-                        }
+                    ast::Expression::Prop(ast::Prop {
+                        target,
+                        name,
+                        optional,
+                        ..
+                    }) => {
+                        let target = self.eval_chain(target.as_ref(), scope).await?;
+                        let Some(value) = chain_target(target, *optional) else {
+                            return Ok(None);
+                        };
+                        self.get_prop(value, name).await?
                     }
                 },
-            })
+            }))
         }
+        .map(move |result| match result {
+            // Records the origin of the innermost node which has one.
+            Err(AiScriptError::Runtime(e)) if e.origin().is_none() => match self.origin(node.loc())
+            {
+                Some(origin) => Err(e.with_origin(origin).into()),
+                None => Err(e.into()),
+            },
+            result => result,
+        })
         .boxed()
     }
 
-    async fn run(
+    /// Returns the method `name` of `target` to call, along with the name of the method if it is
+    /// that of a native object and whether it is a built-in method of a primitive value.
+    fn get_method(
         &self,
-        program: impl IntoIterator<Item = impl Into<ast::Node>>,
-        scope: &Scope,
+        target: Value,
+        name: &str,
+    ) -> Result<(Value, Option<String>, bool), AiScriptError> {
+        match target.value {
+            V::NativeObject(object) => {
+                // A method is called like a native function named `type.method`, so that it is
+                // checked, watched and recorded in the same way.
+                let method = format!("{}.{name}", object.type_name());
+                let native_method = Some(method.clone());
+                let name = name.to_string();
+                let fn_ = Value::fn_native(move |args, interpreter| {
+                    let object = object.clone();
+                    let name = name.clone();
+                    let interpreter = interpreter.clone();
+                    if let Some(capability) = interpreter.permissions.denied_method(&method) {
+                        let error = AiScriptRuntimeError::PermissionDenied {
+                            name: method.clone(),
+                            capability,
+                        };
+                        return async move { Err(error)? }.boxed();
+                    }
+                    let checked = interpreter.native_types.check_method(&method, &args);
+                    async move {
+                        checked?;
+                        object.call_method(&name, args, &interpreter).await
+                    }
+                    .boxed()
+                });
+                Ok((fn_, native_method, false))
+            }
+            V::Obj(ref value) => {
+                let value = value.read().unwrap().get(name).cloned();
+                let value = value
+                    .or_else(|| self.prim_props.get(&target, name))
+                    .unwrap_or_default();
+                Ok((value, None, false))
+            }
+            _ => {
+                let (value, builtin) = get_prim_prop(target, name.to_string(), &self.prim_props)?;
+                Ok((value, None, builtin))
+            }
+        }
+    }
+
+    async fn get_prop(&self, target: Value, name: &str) -> Result<Value, AiScriptError> {
+        match &target.value {
+            V::Obj(value) => {
+                let value = value.read().unwrap().get(name).cloned();
+                Ok(value
+                    .or_else(|| self.prim_props.get(&target, name))
+                    .unwrap_or_default())
+            }
+            V::NativeObject(value) => value.get_prop(name, self).await,
+            _ => get_prim_prop(target, name.to_string(), &self.prim_props).map(|(value, _)| value),
+        }
+    }
+
+    async fn run<'a>(
+        &'a self,
+        program: impl IntoIterator<Item = impl Into<NodeRef<'a>>>,
+        scope: &'a Scope,
     ) -> Result<Value, AiScriptError> {
         let mut v = Value::null();
-        for node in program {
-            v = self.eval(node, scope).await?;
-            if let V::Return(_) | V::Break | V::Continue = *v.value {
+        let mut program = program.into_iter().map(Into::into);
+        while let Some(node) = program.next() {
+            v = match self.eval(node, scope).await {
+                Ok(v) => v,
+                Err(e) => return Err(self.explain_undefined(e, once(node).chain(program))),
+            };
+            if let V::Return(_) | V::Break | V::Continue = v.value {
                 return Ok(v);
             }
         }
@@ -699,26 +2354,45 @@ impl Interpreter {
     pub fn abort(&self) {
         self.stop.store(true, Ordering::SeqCst);
         self.abort_handlers.lock().unwrap().abort_all();
+        self.abort_notify.notify_waiters();
+    }
+
+    /// Completes when [`Self::abort`] is called, or immediately if already aborted.
+    pub(crate) async fn aborted(&self) {
+        let notified = self.abort_notify.notified();
+        futures::pin_mut!(notified);
+        notified.as_mut().enable();
+        if !self.stop.load(Ordering::SeqCst) {
+            notified.await;
+        }
     }
 
     fn assign<'a>(
         &'a self,
         scope: &'a Scope,
-        dest: ast::Expression,
+        dest: &'a ast::Expression,
         value: Value,
     ) -> BoxFuture<'a, Result<(), AiScriptError>> {
         async move {
             match dest {
                 ast::Expression::Identifier(ast::Identifier { name, .. }) => {
-                    scope.assign(name, value)?
+                    if self.strict_variables && scope.is_outside_namespace(name) {
+                        Err(AiScriptRuntimeError::AssignToOuter(name.clone()))?
+                    }
+                    scope.assign(name.clone(), value)?
                 }
                 ast::Expression::Index(ast::Index { target, index, .. }) => {
-                    let assignee = self.eval(*target.clone(), scope).await?;
-                    let i = self.eval(*index, scope).await?;
-                    match *assignee.value {
+                    let assignee = self.eval(target.as_ref(), scope).await?;
+                    let i = self.eval(index.as_ref(), scope).await?;
+                    assignee.expect_mutable()?;
+                    match assignee.value {
                         V::Arr(arr) => {
                             let i = f64::try_from(i)?;
-                            if i.trunc() == i && arr.read().unwrap().get(i as usize).is_some() {
+                            if i.trunc() == i
+                                && i >= 0.0
+                                && arr.read().unwrap().get(i as usize).is_some()
+                            {
+                                self.track_arr(&arr, &value);
                                 arr.write().unwrap()[i as usize] = value;
                             } else {
                                 Err(AiScriptRuntimeError::IndexOutOfRange(
@@ -729,8 +2403,13 @@ impl Interpreter {
                         }
                         V::Obj(obj) => {
                             let i = String::try_from(i)?;
+                            self.track_obj(&obj, &value);
                             obj.write().unwrap().insert(i, value);
                         }
+                        V::NativeObject(object) => {
+                            let i = String::try_from(i)?;
+                            object.set_prop(&i, value, self).await?;
+                        }
                         _ => Err(AiScriptRuntimeError::Runtime(format!(
                             "Cannot read prop ({}) of {}.",
                             i.value.repr_value(),
@@ -739,21 +2418,27 @@ impl Interpreter {
                     }
                 }
                 ast::Expression::Prop(ast::Prop { target, name, .. }) => {
-                    let assignee = self.eval(*target.clone(), scope).await?;
-                    let assignee = VObj::try_from(assignee)?;
-                    assignee.write().unwrap().insert(name, value);
+                    let assignee = self.eval(target.as_ref(), scope).await?;
+                    assignee.expect_mutable()?;
+                    if let V::NativeObject(object) = assignee.value {
+                        object.set_prop(name, value, self).await?;
+                    } else {
+                        let assignee = VObj::try_from(assignee)?;
+                        self.track_obj(&assignee, &value);
+                        assignee.write().unwrap().insert(name.clone(), value);
+                    }
                 }
                 ast::Expression::Arr(ast::Arr { value: target, .. }) => {
                     let value = <Vec<Value>>::try_from(value)?;
-                    try_join_all(target.into_iter().enumerate().map(|(index, item)| {
+                    try_join_all(target.iter().enumerate().map(|(index, item)| {
                         self.assign(scope, item, value.get(index).cloned().unwrap_or_default())
                     }))
                     .await?;
                 }
                 ast::Expression::Obj(ast::Obj { value: target, .. }) => {
                     let value = <IndexMap<String, Value>>::try_from(value)?;
-                    try_join_all(target.into_iter().map(|(key, item)| {
-                        self.assign(scope, item, value.get(&key).cloned().unwrap_or_default())
+                    try_join_all(target.iter().map(|(key, item)| {
+                        self.assign(scope, item, value.get(key).cloned().unwrap_or_default())
                     }))
                     .await?;
                 }
@@ -768,3 +2453,106 @@ impl Interpreter {
         .boxed()
     }
 }
+
+/// Source of the items of `each`.
+///
+/// Besides arrays, `each` accepts a function which returns `{ done, value }` on each call, and a
+/// [`NativeObject`] which yields items via [`NativeObject::next`].
+enum EachIter {
+    Arr(std::vec::IntoIter<Value>),
+    Fn(VFn),
+    NativeObject(Arc<dyn NativeObject>),
+}
+
+impl EachIter {
+    async fn next(&mut self, interpreter: &Interpreter) -> Result<Option<Value>, AiScriptError> {
+        match self {
+            EachIter::Arr(items) => Ok(items.next()),
+            EachIter::Fn(fn_) => {
+                let result = interpreter.fn_(fn_.clone(), []).await?;
+                let result = VObj::try_from(result)?;
+                let result = result.read().unwrap();
+                let done = match result.get("done") {
+                    Some(done) => bool::try_from(done.clone())?,
+                    None => false,
+                };
+                Ok((!done).then(|| result.get("value").cloned().unwrap_or_default()))
+            }
+            EachIter::NativeObject(object) => object.next(interpreter).await,
+        }
+    }
+}
+
+impl TryFrom<Value> for EachIter {
+    type Error = AiScriptError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.value {
+            V::Fn(fn_) => Ok(EachIter::Fn(*fn_)),
+            V::NativeObject(object) => Ok(EachIter::NativeObject(object)),
+            value => Ok(EachIter::Arr(<Vec<Value>>::try_from(value)?.into_iter())),
+        }
+    }
+}
+
+/// Returns the target of an access unless it short-circuits the chain, i.e. the target is
+/// short-circuited itself or the access is optional and the target is null.
+fn chain_target(target: Option<Value>, optional: bool) -> Option<Value> {
+    target.filter(|target| !(optional && matches!(target.value, V::Null)))
+}
+
+/// Records the loop at `loc` in its `iterations`-th iteration as where the step limit was
+/// exceeded, unless an inner loop is already recorded.
+fn in_loop(error: AiScriptError, loc: &Option<ast::Loc>, iterations: usize) -> AiScriptError {
+    match error {
+        AiScriptError::Runtime(e) => AiScriptError::Runtime(e.in_loop(|| LoopInfo {
+            loc: loc.clone(),
+            iterations,
+        })),
+        error => error,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NodeRef<'a> {
+    Node(&'a ast::Node),
+    Statement(&'a ast::Statement),
+    Expression(&'a ast::Expression),
+}
+
+impl<'a> NodeRef<'a> {
+    fn loc(self) -> Option<&'a ast::Loc> {
+        match self {
+            NodeRef::Node(node) => node.loc(),
+            NodeRef::Statement(statement) => statement.loc(),
+            NodeRef::Expression(expression) => expression.loc(),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::Node> for NodeRef<'a> {
+    fn from(value: &'a ast::Node) -> Self {
+        NodeRef::Node(value)
+    }
+}
+
+impl<'a> From<&'a ast::Statement> for NodeRef<'a> {
+    fn from(value: &'a ast::Statement) -> Self {
+        NodeRef::Statement(value)
+    }
+}
+
+impl<'a> From<&'a ast::Expression> for NodeRef<'a> {
+    fn from(value: &'a ast::Expression) -> Self {
+        NodeRef::Expression(value)
+    }
+}
+
+impl<'a> From<&'a ast::StatementOrExpression> for NodeRef<'a> {
+    fn from(value: &'a ast::StatementOrExpression) -> Self {
+        match value {
+            ast::StatementOrExpression::Statement(statement) => NodeRef::Statement(statement),
+            ast::StatementOrExpression::Expression(expression) => NodeRef::Expression(expression),
+        }
+    }
+}
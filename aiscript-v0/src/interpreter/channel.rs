@@ -0,0 +1,148 @@
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::error::{AiScriptError, AiScriptRuntimeError};
+
+use super::{
+    value::{NativeObject, Value, V},
+    Interpreter,
+};
+
+/// A bounded channel shared by a host and scripts.
+///
+/// Pass [`Channel::to_value`] to a script as a constant, then the script can use it via
+/// `Chan:send(ch, v)`, `Chan:recv(ch)` and `Chan:close(ch)`.
+/// Waiting on a channel in a script ends when the interpreter is aborted.
+///
+/// ```
+/// use aiscript_v0::{Channel, Interpreter, Parser};
+/// # use aiscript_v0::{errors::AiScriptError, values::Value};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), AiScriptError> {
+/// let events = Channel::bounded(16);
+/// let interpreter = Interpreter::new(
+///     [("events".to_string(), events.to_value())],
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None,
+/// );
+/// events.send(Value::str("note")).await?;
+/// events.close();
+/// let script = Parser::default().parse("Chan:recv(events)")?;
+/// assert_eq!(interpreter.exec(script).await?, Some(Value::str("note")));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Channel {
+    sender: async_channel::Sender<Value>,
+    receiver: async_channel::Receiver<Value>,
+}
+
+/// The largest capacity of a channel created by `Chan:create`.
+pub(crate) const MAX_CAPACITY: usize = 1 << 16;
+
+impl Channel {
+    /// Creates a channel which holds at most `capacity` values. `capacity` must be positive.
+    pub fn bounded(capacity: usize) -> Self {
+        let (sender, receiver) = async_channel::bounded(capacity);
+        Channel { sender, receiver }
+    }
+
+    /// Sends a value, waiting while the channel is full. Fails if the channel is closed.
+    pub async fn send(&self, value: Value) -> Result<(), AiScriptError> {
+        self.sender
+            .send(value)
+            .await
+            .map_err(|_| AiScriptRuntimeError::Runtime("Channel is closed".to_string()).into())
+    }
+
+    /// Receives a value, waiting while the channel is empty.
+    /// Returns `None` if the channel is closed and empty.
+    pub async fn recv(&self) -> Option<Value> {
+        self.receiver.recv().await.ok()
+    }
+
+    /// Closes the channel. Values already sent can still be received.
+    ///
+    /// Returns `true` if this call closed the channel.
+    pub fn close(&self) -> bool {
+        self.sender.close()
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Returns a value which scripts pass to the functions in `Chan:` namespace.
+    pub fn to_value(&self) -> Value {
+        Value::native_object(self.clone())
+    }
+}
+
+impl NativeObject for Channel {
+    fn type_name(&self) -> &str {
+        "channel"
+    }
+
+    fn get_prop<'a>(
+        &'a self,
+        _: &'a str,
+        _: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+        async move { Ok(Value::null()) }.boxed()
+    }
+
+    fn call_method<'a>(
+        &'a self,
+        name: &'a str,
+        args: Vec<Value>,
+        interpreter: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+        async move {
+            match name {
+                "send" => {
+                    let value = args.into_iter().next().unwrap_or_default();
+                    futures::select! {
+                        result = self.send(value).fuse() => result?,
+                        _ = interpreter.aborted().fuse() => (),
+                    }
+                    Ok(Value::null())
+                }
+                "recv" => Ok(futures::select! {
+                    value = self.recv().fuse() => value,
+                    _ = interpreter.aborted().fuse() => None,
+                }
+                .unwrap_or_default()),
+                "close" => {
+                    self.close();
+                    Ok(Value::null())
+                }
+                _ => Err(AiScriptRuntimeError::Runtime(format!(
+                    "No such method ({name}) of channel."
+                )))?,
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Calls the method `name` of a value created by [`Channel::to_value`].
+pub(crate) async fn call(
+    interpreter: &Interpreter,
+    name: &str,
+    args: Vec<Value>,
+) -> Result<Value, AiScriptError> {
+    let mut args = args.into_iter();
+    match args.next().unwrap_or_default().value {
+        V::NativeObject(object) if object.type_name() == "channel" => {
+            object.call_method(name, args.collect(), interpreter).await
+        }
+        value => Err(AiScriptRuntimeError::Type {
+            expected: "channel".to_string(),
+            got: value.display_type().to_string(),
+        })?,
+    }
+}
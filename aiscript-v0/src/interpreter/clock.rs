@@ -0,0 +1,89 @@
+//! Sources of the current time and timers of interpreters, which can be replaced, e.g. to test
+//! scripts that wait without actually waiting.
+
+use std::{sync::Mutex, time::Duration};
+
+use futures::{channel::oneshot, future::BoxFuture, FutureExt};
+
+/// The current time and timers used by `Date:now`, `Core:sleep`, `Async:timeout` and
+/// `Async:interval`, set via [`Interpreter::with_clock`](super::Interpreter::with_clock).
+pub trait Clock: Send + Sync {
+    /// Returns the current time in milliseconds since the Unix epoch.
+    fn now(&self) -> f64;
+
+    /// Completes after `duration` elapses.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The real time and timers of the Tokio runtime, which is used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        chrono::Utc::now().timestamp_millis() as f64
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+}
+
+/// A clock which advances only via [`ManualClock::advance`].
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    state: Mutex<ManualState>,
+}
+
+#[derive(Debug, Default)]
+struct ManualState {
+    now: f64,
+    timers: Vec<(f64, oneshot::Sender<()>)>,
+}
+
+impl ManualClock {
+    /// Creates a clock whose current time is `now` milliseconds since the Unix epoch.
+    pub fn new(now: f64) -> Self {
+        ManualClock {
+            state: Mutex::new(ManualState {
+                now,
+                timers: Vec::new(),
+            }),
+        }
+    }
+
+    /// Advances the time by `duration`, completing the sleeps which end by then.
+    ///
+    /// A sleep started after this call, e.g. the next tick of `Async:interval`, starts at the new
+    /// time even if it would have ended within `duration` in real time.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += duration.as_secs_f64() * 1000.0;
+        let now = state.now;
+        let (due, pending) = std::mem::take(&mut state.timers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(end, _)| *end <= now);
+        state.timers = pending;
+        drop(state);
+        for (_, timer) in due {
+            let _ = timer.send(());
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        if duration.is_zero() {
+            return async {}.boxed();
+        }
+        let (sender, receiver) = oneshot::channel();
+        let mut state = self.state.lock().unwrap();
+        let end = state.now + duration.as_secs_f64() * 1000.0;
+        state.timers.push((end, sender));
+        receiver.map(|_| ()).boxed()
+    }
+}
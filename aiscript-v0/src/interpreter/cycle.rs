@@ -0,0 +1,253 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock, Weak},
+};
+
+use super::{
+    scope::Scope,
+    value::{VArr, VFn, VObj, Value, V},
+    variable::Variable,
+};
+
+type States = Arc<RwLock<HashMap<String, Variable>>>;
+
+/// An array, an object or the variables of a scope, which can be part of a reference cycle.
+enum Container {
+    Arr(VArr),
+    Obj(VObj),
+    Scope(States),
+}
+
+impl Container {
+    fn id(&self) -> usize {
+        match self {
+            Container::Arr(arr) => Arc::as_ptr(arr) as *const () as usize,
+            Container::Obj(obj) => Arc::as_ptr(obj) as *const () as usize,
+            Container::Scope(states) => Arc::as_ptr(states) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Container::Arr(arr) => Arc::strong_count(arr),
+            Container::Obj(obj) => Arc::strong_count(obj),
+            Container::Scope(states) => Arc::strong_count(states),
+        }
+    }
+
+    fn downgrade(&self) -> WeakContainer {
+        match self {
+            Container::Arr(arr) => WeakContainer::Arr(Arc::downgrade(arr)),
+            Container::Obj(obj) => WeakContainer::Obj(Arc::downgrade(obj)),
+            Container::Scope(states) => WeakContainer::Scope(Arc::downgrade(states)),
+        }
+    }
+
+    /// Returns the containers which this one refers to directly, once for each reference.
+    fn children(&self) -> Vec<Container> {
+        let mut children = Vec::new();
+        match self {
+            Container::Arr(arr) => {
+                for value in arr.read().unwrap().iter() {
+                    value_children(value, &mut children);
+                }
+            }
+            Container::Obj(obj) => {
+                for value in obj.read().unwrap().values() {
+                    value_children(value, &mut children);
+                }
+            }
+            Container::Scope(states) => {
+                for Variable::Mut(value) | Variable::Const(value) in states.read().unwrap().values()
+                {
+                    value_children(value, &mut children);
+                }
+            }
+        }
+        children
+    }
+
+    /// Removes the contents, which are returned to be dropped after all locks are released.
+    fn clear(&self) -> Vec<Value> {
+        match self {
+            Container::Arr(arr) => std::mem::take(&mut *arr.write().unwrap()),
+            Container::Obj(obj) => std::mem::take(&mut *obj.write().unwrap())
+                .into_values()
+                .collect(),
+            Container::Scope(states) => std::mem::take(&mut *states.write().unwrap())
+                .into_values()
+                .map(|(Variable::Mut(value) | Variable::Const(value))| value)
+                .collect(),
+        }
+    }
+}
+
+fn value_children(value: &Value, children: &mut Vec<Container>) {
+    for attr in value.attr.iter().flatten() {
+        value_children(&attr.value, children);
+    }
+    match &value.value {
+        V::Arr(arr) => children.push(Container::Arr(arr.clone())),
+        V::Obj(obj) => children.push(Container::Obj(obj.clone())),
+        V::Fn(fn_) => {
+            if let VFn::Fn { scope, .. } = fn_.as_ref() {
+                children.extend(scope.states_chain().cloned().map(Container::Scope));
+            }
+        }
+        V::Return(value) => value_children(value, children),
+        V::Error {
+            info: Some(info), ..
+        } => value_children(info, children),
+        _ => (),
+    }
+}
+
+/// Returns whether storing `value` in an array, an object or a scope may create a cycle.
+pub(crate) fn may_refer(value: &Value) -> bool {
+    value.attr.is_some()
+        || matches!(
+            value.value,
+            V::Arr(_) | V::Obj(_) | V::Fn(_) | V::Return(_) | V::Error { info: Some(_), .. }
+        )
+}
+
+enum WeakContainer {
+    Arr(Weak<RwLock<Vec<Value>>>),
+    Obj(Weak<RwLock<indexmap::IndexMap<String, Value>>>),
+    Scope(Weak<RwLock<HashMap<String, Variable>>>),
+}
+
+impl WeakContainer {
+    fn upgrade(&self) -> Option<Container> {
+        match self {
+            WeakContainer::Arr(arr) => arr.upgrade().map(Container::Arr),
+            WeakContainer::Obj(obj) => obj.upgrade().map(Container::Obj),
+            WeakContainer::Scope(states) => states.upgrade().map(Container::Scope),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            WeakContainer::Arr(arr) => arr.strong_count() > 0,
+            WeakContainer::Obj(obj) => obj.strong_count() > 0,
+            WeakContainer::Scope(states) => states.strong_count() > 0,
+        }
+    }
+}
+
+/// Frees reference cycles of arrays, objects and scopes captured by functions, which are not
+/// freed by reference counting alone.
+///
+/// Since garbage is not reachable from anywhere, the interpreter records the containers where a
+/// cycle can be formed as candidates: those which an array, an object or a function is stored
+/// into, and the scopes captured by functions.
+#[derive(Default)]
+pub(crate) struct CycleCollector {
+    candidates: Mutex<Candidates>,
+}
+
+#[derive(Default)]
+struct Candidates {
+    containers: HashMap<usize, WeakContainer>,
+    /// The number of candidates alive after the last pruning.
+    alive: usize,
+}
+
+impl CycleCollector {
+    pub(crate) fn add_arr(&self, arr: &VArr) {
+        self.add(Container::Arr(arr.clone()));
+    }
+
+    pub(crate) fn add_obj(&self, obj: &VObj) {
+        self.add(Container::Obj(obj.clone()));
+    }
+
+    /// Adds the scopes captured by a function defined in `scope`.
+    pub(crate) fn add_scope(&self, scope: &Scope) {
+        for states in scope.states_chain() {
+            self.add(Container::Scope(states.clone()));
+        }
+    }
+
+    fn add(&self, container: Container) {
+        let mut candidates = self.candidates.lock().unwrap();
+        candidates
+            .containers
+            .insert(container.id(), container.downgrade());
+        // Forgets the freed candidates once in a while to keep the memory bounded.
+        if candidates.containers.len() > candidates.alive * 2 + 64 {
+            candidates.containers.retain(|_, weak| weak.is_alive());
+            candidates.alive = candidates.containers.len();
+        }
+    }
+
+    /// Frees the containers which are referenced only from reference cycles, by clearing them,
+    /// and returns the number of them.
+    pub(crate) fn collect(&self) -> usize {
+        let roots = {
+            let mut candidates = self.candidates.lock().unwrap();
+            candidates.containers.retain(|_, weak| weak.is_alive());
+            candidates.alive = candidates.containers.len();
+            candidates
+                .containers
+                .values()
+                .filter_map(WeakContainer::upgrade)
+                .collect::<Vec<_>>()
+        };
+
+        // Finds every container reachable from the candidates, holding one reference to each.
+        let mut nodes = HashMap::<usize, Container>::new();
+        let mut edges = HashMap::<usize, Vec<usize>>::new();
+        let mut stack = roots;
+        while let Some(container) = stack.pop() {
+            let id = container.id();
+            if nodes.contains_key(&id) {
+                continue;
+            }
+            let children = container.children();
+            edges.insert(id, children.iter().map(Container::id).collect());
+            nodes.insert(id, container);
+            stack.extend(
+                children
+                    .into_iter()
+                    .filter(|child| !nodes.contains_key(&child.id())),
+            );
+        }
+
+        // A container is referenced from outside if it has more references than those from the
+        // found containers and this collector, which keeps everything it refers to alive.
+        let mut internal = HashMap::<usize, usize>::new();
+        for id in edges.values().flatten() {
+            *internal.entry(*id).or_default() += 1;
+        }
+        let mut alive = nodes
+            .iter()
+            .filter(|(id, container)| {
+                container.strong_count() - 1 > internal.get(id).copied().unwrap_or(0)
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let mut reachable = alive.iter().copied().collect::<HashSet<_>>();
+        while let Some(id) = alive.pop() {
+            for child in &edges[&id] {
+                if reachable.insert(*child) {
+                    alive.push(*child);
+                }
+            }
+        }
+
+        let garbage = nodes
+            .iter()
+            .filter(|(id, _)| !reachable.contains(id))
+            .map(|(_, container)| container)
+            .collect::<Vec<_>>();
+        let count = garbage.len();
+        let contents = garbage
+            .into_iter()
+            .flat_map(Container::clear)
+            .collect::<Vec<_>>();
+        drop(nodes);
+        drop(contents);
+        count
+    }
+}
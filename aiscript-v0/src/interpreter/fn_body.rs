@@ -0,0 +1,33 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::node as ast;
+
+/// The bodies of function values, shared between the values created from the same function
+/// expression, so that creating a closure does not copy its body.
+#[derive(Default)]
+pub(crate) struct FnBodies(Mutex<HashMap<usize, Weak<[ast::StatementOrExpression]>>>);
+
+impl FnBodies {
+    /// Returns the body of the function values created from `fn_`.
+    pub(crate) fn get(&self, fn_: &ast::Fn) -> Arc<[ast::StatementOrExpression]> {
+        let key = fn_ as *const ast::Fn as usize;
+        let mut bodies = self.0.lock().unwrap();
+        // Another node may be at the same address after the node is dropped, so the body is
+        // shared only if it is the same.
+        if let Some(body) = bodies.get(&key).and_then(Weak::upgrade) {
+            if *body == *fn_.children {
+                return body;
+            }
+        }
+        if bodies.len() == bodies.capacity() {
+            // Frees the entries of the dropped bodies before growing.
+            bodies.retain(|_, body| body.strong_count() > 0);
+        }
+        let body = Arc::<[_]>::from(fn_.children.as_slice());
+        bodies.insert(key, Arc::downgrade(&body));
+        body
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::FutureExt;
+
+use crate::error::{AiScriptError, AiScriptRuntimeError};
+
+use super::value::Value;
+
+/// A grant for scripts to access files under a directory via the `Fs:` namespace.
+///
+/// The namespace is not a part of the standard library. Pass [`FsCapability::functions`] to an
+/// interpreter as constants to enable it.
+///
+/// ```
+/// use aiscript_v0::{FsCapability, Interpreter};
+///
+/// let capability = FsCapability::new("./data").writable(true);
+/// let interpreter = Interpreter::new(
+///     capability.functions(),
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None,
+/// );
+/// ```
+///
+/// Scripts call `Fs:read_text(path)`, `Fs:write_text(path, text)` and `Fs:list_dir(path?)` with
+/// paths relative to the root. An I/O failure is returned as an error value named `io_error`.
+/// An absolute path, a path out of the root, or writing without the permission fails with a
+/// runtime error.
+#[derive(Debug, Clone)]
+pub struct FsCapability {
+    root: PathBuf,
+    writable: bool,
+}
+
+impl FsCapability {
+    /// Creates a read-only grant for the files under `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsCapability {
+            root: root.into(),
+            writable: false,
+        }
+    }
+
+    /// Sets whether scripts can write files.
+    pub fn writable(self, writable: bool) -> Self {
+        FsCapability { writable, ..self }
+    }
+
+    /// Returns the functions of the `Fs:` namespace which follow this grant.
+    pub fn functions(self) -> Vec<(String, Value)> {
+        let capability = Arc::new(self);
+        vec![
+            ("Fs:read_text".to_string(), {
+                let capability = capability.clone();
+                Value::fn_native(move |args, interpreter| {
+                    let capability = capability.clone();
+                    let interpreter = interpreter.clone();
+                    async move {
+                        interpreter.expect_async("Fs:read_text")?;
+                        let mut args = args.into_iter();
+                        let path = String::try_from(args.next().unwrap_or_default())?;
+                        let path = capability.resolve(&path).await?;
+                        Ok(match tokio::fs::read_to_string(path).await {
+                            Ok(text) => Value::str(text),
+                            Err(e) => io_error(e),
+                        })
+                    }
+                    .boxed()
+                })
+            }),
+            ("Fs:write_text".to_string(), {
+                let capability = capability.clone();
+                Value::fn_native(move |args, interpreter| {
+                    let capability = capability.clone();
+                    let interpreter = interpreter.clone();
+                    async move {
+                        interpreter.expect_async("Fs:write_text")?;
+                        let mut args = args.into_iter();
+                        let path = String::try_from(args.next().unwrap_or_default())?;
+                        let text = String::try_from(args.next().unwrap_or_default())?;
+                        if !capability.writable {
+                            Err(AiScriptRuntimeError::Runtime(format!(
+                                "Writing {path} is not allowed"
+                            )))?
+                        }
+                        let path = capability.resolve(&path).await?;
+                        Ok(match tokio::fs::write(path, text).await {
+                            Ok(()) => Value::null(),
+                            Err(e) => io_error(e),
+                        })
+                    }
+                    .boxed()
+                })
+            }),
+            ("Fs:list_dir".to_string(), {
+                Value::fn_native(move |args, interpreter| {
+                    let capability = capability.clone();
+                    let interpreter = interpreter.clone();
+                    async move {
+                        interpreter.expect_async("Fs:list_dir")?;
+                        let mut args = args.into_iter();
+                        let path = match args.next() {
+                            Some(path) => String::try_from(path)?,
+                            None => ".".to_string(),
+                        };
+                        let path = capability.resolve(&path).await?;
+                        Ok(match list_dir(&path).await {
+                            Ok(names) => Value::arr(names.into_iter().map(Value::str)),
+                            Err(e) => io_error(e),
+                        })
+                    }
+                    .boxed()
+                })
+            }),
+        ]
+    }
+
+    /// Resolves `path` against the root, rejecting paths which point out of it, including via
+    /// symbolic links, and all paths if the root cannot be resolved.
+    async fn resolve(&self, path: &str) -> Result<PathBuf, AiScriptError> {
+        let out_of_root =
+            || AiScriptRuntimeError::Runtime(format!("Path {path} is out of the root directory"));
+        let relative = Path::new(path);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            Err(out_of_root())?
+        }
+        let resolved = self.root.join(relative);
+        let root = tokio::fs::canonicalize(&self.root).await.map_err(|e| {
+            AiScriptRuntimeError::Runtime(format!(
+                "Root directory {} is not accessible: {e}",
+                self.root.display()
+            ))
+        })?;
+        // The file may not exist yet when writing, so check the nearest existing ancestor. An
+        // entry which exists but cannot be resolved is a dangling symbolic link, which could
+        // create a file anywhere.
+        let mut existing = resolved.as_path();
+        let canonical = loop {
+            match tokio::fs::canonicalize(existing).await {
+                Ok(canonical) => break canonical,
+                Err(_) if tokio::fs::symlink_metadata(existing).await.is_ok() => {
+                    Err(out_of_root())?
+                }
+                Err(_) => match existing.parent() {
+                    Some(parent) => existing = parent,
+                    None => Err(out_of_root())?,
+                },
+            }
+        };
+        if canonical.starts_with(&root) {
+            Ok(resolved)
+        } else {
+            Err(out_of_root())?
+        }
+    }
+}
+
+async fn list_dir(path: &Path) -> std::io::Result<Vec<String>> {
+    let mut entries = tokio::fs::read_dir(path).await?;
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn io_error(e: std::io::Error) -> Value {
+    Value::error("io_error", Some(Value::str(e.to_string())))
+}
@@ -0,0 +1,137 @@
+use std::{
+    future::poll_fn,
+    sync::{Arc, Mutex},
+    task::Poll,
+};
+
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    node::StatementOrExpression,
+};
+
+use super::{
+    scope::Scope,
+    value::{NativeObject, Value},
+    variable::Variable,
+    Interpreter,
+};
+
+/// The place where the body of a generator puts the value of `yield`.
+#[derive(Debug, Default)]
+pub(crate) struct Yielder(Mutex<Option<Value>>);
+
+impl Yielder {
+    /// Passes `value` to the consumer of the generator and suspends the body until the next item
+    /// is requested.
+    pub(crate) async fn yield_(&self, value: Value) {
+        let mut value = Some(value);
+        poll_fn(|_| match value.take() {
+            Some(v) => {
+                // Another `yield` evaluated concurrently, e.g. in an array literal, goes first.
+                let mut slot = self.0.lock().unwrap();
+                if slot.is_none() {
+                    *slot = Some(v);
+                } else {
+                    value = Some(v);
+                }
+                Poll::Pending
+            }
+            None => Poll::Ready(()),
+        })
+        .await
+    }
+
+    fn take(&self) -> Option<Value> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// The value returned by a generator function (`@name*() { ... }` or `@*() { ... }`), which
+/// runs the body lazily each time `each` or `Arr:from` requests the next item, until the next
+/// `yield`.
+pub(crate) struct Generator {
+    body: futures::lock::Mutex<Option<BoxFuture<'static, Result<Value, AiScriptError>>>>,
+    yielder: Arc<Yielder>,
+}
+
+impl std::fmt::Debug for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Generator").finish_non_exhaustive()
+    }
+}
+
+impl Generator {
+    pub(crate) fn new(
+        interpreter: &Interpreter,
+        fn_args: &[String],
+        statements: Arc<[StatementOrExpression]>,
+        scope: &Scope,
+        args: Vec<Value>,
+    ) -> Self {
+        let yielder = Arc::new(Yielder::default());
+        let interpreter = Interpreter {
+            yielder: Some(yielder.clone()),
+            ..interpreter.clone()
+        };
+        let args = fn_args
+            .iter()
+            .cloned()
+            .zip(
+                args.into_iter()
+                    .chain(std::iter::repeat(Value::null()))
+                    .map(Variable::Mut),
+            )
+            .collect();
+        let scope = scope.create_fn_scope(args);
+        let body = async move { interpreter.run(statements.iter(), &scope).await }.boxed();
+        Generator {
+            body: futures::lock::Mutex::new(Some(body)),
+            yielder,
+        }
+    }
+}
+
+impl NativeObject for Generator {
+    fn type_name(&self) -> &str {
+        "generator"
+    }
+
+    fn get_prop<'a>(
+        &'a self,
+        _: &'a str,
+        _: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+        async move { Ok(Value::null()) }.boxed()
+    }
+
+    fn next<'a>(
+        &'a self,
+        _: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Option<Value>, AiScriptError>> {
+        async move {
+            let Some(mut body) = self.body.try_lock() else {
+                Err(AiScriptRuntimeError::Runtime(
+                    "Generator is already running.".to_string(),
+                ))?
+            };
+            let Some(future) = body.as_mut() else {
+                return Ok(None);
+            };
+            let result = poll_fn(|cx| match future.poll_unpin(cx) {
+                Poll::Ready(result) => Poll::Ready(result.map(|_| None)),
+                Poll::Pending => match self.yielder.take() {
+                    Some(value) => Poll::Ready(Ok(Some(value))),
+                    None => Poll::Pending,
+                },
+            })
+            .await;
+            if !matches!(result, Ok(Some(_))) {
+                *body = None;
+            }
+            result
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,256 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::FutureExt;
+use indexmap::IndexMap;
+use reqwest::{header::HeaderMap, redirect, Client, Method, Url};
+
+use crate::error::{AiScriptError, AiScriptRuntimeError};
+
+use super::{
+    value::{Value, V},
+    Interpreter,
+};
+
+/// Limits on the requests of scripts via the `Http:` namespace.
+///
+/// The namespace is not a part of the standard library. Pass [`HttpPolicy::functions`] to an
+/// interpreter as constants to enable it.
+///
+/// ```
+/// use aiscript_v0::{HttpPolicy, Interpreter};
+///
+/// let policy = HttpPolicy::new(|url| url.host_str() == Some("example.com"))
+///     .max_body_size(64 * 1024);
+/// let interpreter = Interpreter::new(
+///     policy.functions().unwrap(),
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None,
+/// );
+/// ```
+///
+/// Scripts call `Http:get(url, options?)` and `Http:post(url, body, options?)`, where `body` is a
+/// string or a binary and `options` is `{ headers?: obj, timeout?: num }` with the timeout in
+/// milliseconds. They return `{ status: num, headers: obj, body: str }`, or an error value named
+/// `request_failed` or `body_too_large`. A request to a URL which the policy does not allow,
+/// including a redirect, fails with a runtime error.
+#[derive(Clone)]
+pub struct HttpPolicy {
+    allow: Arc<dyn Fn(&Url) -> bool + Sync + Send>,
+    max_body_size: usize,
+    timeout: Duration,
+}
+
+impl std::fmt::Debug for HttpPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpPolicy")
+            .field("max_body_size", &self.max_body_size)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl HttpPolicy {
+    /// Creates a policy which allows requests to the URLs for which `allow` returns `true`,
+    /// with a response body of at most 1 MiB and a timeout of 30 seconds.
+    pub fn new(allow: impl Fn(&Url) -> bool + Sync + Send + 'static) -> Self {
+        HttpPolicy {
+            allow: Arc::new(allow),
+            max_body_size: 1024 * 1024,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets the maximum size of a response body in bytes.
+    pub fn max_body_size(self, max_body_size: usize) -> Self {
+        HttpPolicy {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Sets the maximum timeout of a request. Scripts can only shorten it.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        HttpPolicy { timeout, ..self }
+    }
+
+    /// Returns the functions of the `Http:` namespace which follow this policy.
+    ///
+    /// Fails if the HTTP client cannot be built, e.g. when the TLS backend cannot be
+    /// initialized.
+    pub fn functions(self) -> Result<Vec<(String, Value)>, reqwest::Error> {
+        let allow = self.allow.clone();
+        let client = Client::builder()
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 10 {
+                    attempt.error("too many redirects")
+                } else if allow(attempt.url()) {
+                    attempt.follow()
+                } else {
+                    let url = attempt.url().to_string();
+                    attempt.error(NotAllowed(url))
+                }
+            }))
+            .build()?;
+        let policy = Arc::new((self, client));
+        Ok(vec![
+            ("Http:get".to_string(), {
+                let policy = policy.clone();
+                Value::fn_native(move |args, interpreter| {
+                    let policy = policy.clone();
+                    let interpreter = interpreter.clone();
+                    async move {
+                        let mut args = args.into_iter();
+                        let url = String::try_from(args.next().unwrap_or_default())?;
+                        let options = args.next().unwrap_or_default();
+                        let (policy, client) = policy.as_ref();
+                        policy
+                            .request(&interpreter, client, Method::GET, url, None, options)
+                            .await
+                    }
+                    .boxed()
+                })
+            }),
+            ("Http:post".to_string(), {
+                Value::fn_native(move |args, interpreter| {
+                    let policy = policy.clone();
+                    let interpreter = interpreter.clone();
+                    async move {
+                        let mut args = args.into_iter();
+                        let url = String::try_from(args.next().unwrap_or_default())?;
+                        let body = match args.next().unwrap_or_default().value {
+                            V::Str(body) => body.into_bytes(),
+                            V::Bin(body) => body,
+                            body => Err(AiScriptRuntimeError::Type {
+                                expected: "string or binary".to_string(),
+                                got: body.display_type().to_string(),
+                            })?,
+                        };
+                        let options = args.next().unwrap_or_default();
+                        let (policy, client) = policy.as_ref();
+                        policy
+                            .request(&interpreter, client, Method::POST, url, Some(body), options)
+                            .await
+                    }
+                    .boxed()
+                })
+            }),
+        ])
+    }
+
+    async fn request(
+        &self,
+        interpreter: &Interpreter,
+        client: &Client,
+        method: Method,
+        url: String,
+        body: Option<Vec<u8>>,
+        options: Value,
+    ) -> Result<Value, AiScriptError> {
+        interpreter.expect_async(&format!("Http:{}", method.as_str().to_lowercase()))?;
+        let url = Url::parse(&url)
+            .map_err(|e| AiScriptRuntimeError::Runtime(format!("Invalid URL {url}: {e}")))?;
+        if !(self.allow)(&url) {
+            Err(NotAllowed(url.to_string()))?
+        }
+        let mut timeout = self.timeout;
+        let mut request = client.request(method, url);
+        if let V::Obj(options) = options.value {
+            let options = options.read().unwrap().clone();
+            if let Some(headers) = options.get("headers") {
+                for (name, value) in <IndexMap<String, Value>>::try_from(headers.clone())? {
+                    request = request.header(name, String::try_from(value)?);
+                }
+            }
+            if let Some(ms) = options.get("timeout") {
+                let ms = f64::try_from(ms.clone())?;
+                // a negative, NaN or too large timeout keeps that of the policy
+                if let Ok(ms) = Duration::try_from_secs_f64(ms / 1000.0) {
+                    timeout = timeout.min(ms);
+                }
+            }
+        }
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        let request = request.timeout(timeout).send();
+        let response = futures::select! {
+            response = request.fuse() => response,
+            _ = interpreter.aborted().fuse() => return Ok(Value::null()),
+        };
+        let mut response = match response {
+            Ok(response) => response,
+            Err(e) => return request_failed(e),
+        };
+        let status = response.status().as_u16();
+        let headers = headers_to_value(response.headers());
+        let mut body = Vec::new();
+        loop {
+            let chunk = futures::select! {
+                chunk = response.chunk().fuse() => chunk,
+                _ = interpreter.aborted().fuse() => return Ok(Value::null()),
+            };
+            match chunk {
+                Ok(Some(chunk)) => {
+                    if body.len() + chunk.len() > self.max_body_size {
+                        return Ok(Value::error("body_too_large", None));
+                    }
+                    body.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(e) => return request_failed(e),
+            }
+        }
+        Ok(Value::obj([
+            ("status", Value::num(status)),
+            ("headers", headers),
+            ("body", Value::str(String::from_utf8_lossy(&body))),
+        ]))
+    }
+}
+
+#[derive(Debug)]
+struct NotAllowed(String);
+
+impl std::fmt::Display for NotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request to {} is not allowed", self.0)
+    }
+}
+
+impl std::error::Error for NotAllowed {}
+
+impl From<NotAllowed> for AiScriptError {
+    fn from(value: NotAllowed) -> Self {
+        AiScriptRuntimeError::Runtime(value.to_string()).into()
+    }
+}
+
+/// Converts an error of a request into an error value, or a runtime error if a redirect was not
+/// allowed by the policy.
+fn request_failed(e: reqwest::Error) -> Result<Value, AiScriptError> {
+    let mut source = std::error::Error::source(&e);
+    while let Some(error) = source {
+        if let Some(NotAllowed(url)) = error.downcast_ref() {
+            Err(NotAllowed(url.clone()))?
+        }
+        source = error.source();
+    }
+    Ok(Value::error(
+        "request_failed",
+        Some(Value::str(e.to_string())),
+    ))
+}
+
+fn headers_to_value(headers: &HeaderMap) -> Value {
+    Value::obj(headers.keys().map(|name| {
+        let value = headers
+            .get_all(name)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .collect::<Vec<_>>()
+            .join(", ");
+        (name.as_str(), Value::str(value))
+    }))
+}
@@ -0,0 +1,104 @@
+//! Requests for input from the user made by `readline` and the `Ui:` functions, which hosts can
+//! render as appropriate UI, e.g. a checkbox for a confirmation.
+
+use super::value::{Value, V};
+
+/// The kind of the answer which a script asks for.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InputKind {
+    /// A string, asked by `readline` and `Ui:input`.
+    Text,
+    /// A number, asked by `Ui:input` with `type: 'number'`.
+    Number,
+    /// A boolean, asked by `Ui:confirm`.
+    Confirm,
+    /// One of [`InputRequest::choices`], asked by `Ui:select`.
+    Select,
+}
+
+/// A request for input from the user, which is passed to the callback set via
+/// [`Interpreter::with_input`](super::Interpreter::with_input).
+#[derive(Debug, PartialEq, Clone)]
+pub struct InputRequest {
+    /// The question shown to the user.
+    pub prompt: String,
+    pub kind: InputKind,
+    /// The answer suggested to the user, which the script uses if the user gives none.
+    pub default: Option<Value>,
+    /// The strings which the user chooses from if the kind is [`InputKind::Select`].
+    pub choices: Vec<String>,
+}
+
+impl InputRequest {
+    /// Returns `answer` if it is of the requested kind, e.g. a number for
+    /// [`InputKind::Number`].
+    pub(crate) fn accept(&self, answer: Value) -> Option<Value> {
+        match (self.kind, &answer.value) {
+            (InputKind::Text, V::Str(_)) | (InputKind::Number, V::Num(_)) => Some(answer),
+            (InputKind::Confirm, V::Bool(_)) => Some(answer),
+            (InputKind::Select, V::Str(choice)) if self.choices.contains(choice) => Some(answer),
+            _ => None,
+        }
+    }
+
+    /// Interprets a line typed by the user, for hosts which only read strings (see
+    /// [`Interpreter::new`](super::Interpreter::new)).
+    ///
+    /// An empty line is no answer. A finite number is parsed, a confirmation is `y`, `yes` or
+    /// `true`, or `n`, `no` or `false`, and a choice is either itself or its position counted
+    /// from 1.
+    pub(crate) fn parse(&self, line: &str) -> Option<Value> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            return None;
+        }
+        match self.kind {
+            InputKind::Text => Some(Value::str(line)),
+            InputKind::Number => line
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .map(Value::num),
+            InputKind::Confirm => match line.trim().to_lowercase().as_str() {
+                "y" | "yes" | "true" => Some(Value::bool(true)),
+                "n" | "no" | "false" => Some(Value::bool(false)),
+                _ => None,
+            },
+            InputKind::Select => {
+                let line = line.trim();
+                self.choices
+                    .iter()
+                    .find(|choice| *choice == line)
+                    .or_else(|| {
+                        let position = line.parse::<usize>().ok()?;
+                        self.choices.get(position.checked_sub(1)?)
+                    })
+                    .map(Value::str)
+            }
+        }
+    }
+
+    /// Returns the question for hosts which only show strings, with the choices and the default
+    /// answer appended, e.g. `Color? (1: red, 2: blue) [red]`.
+    pub(crate) fn text_prompt(&self) -> String {
+        let mut prompt = self.prompt.clone();
+        match self.kind {
+            InputKind::Confirm => prompt.push_str(" (y/n)"),
+            InputKind::Select => {
+                let choices = self
+                    .choices
+                    .iter()
+                    .enumerate()
+                    .map(|(i, choice)| format!("{}: {choice}", i + 1))
+                    .collect::<Vec<_>>();
+                prompt.push_str(&format!(" ({})", choices.join(", ")));
+            }
+            InputKind::Text | InputKind::Number => (),
+        }
+        if let Some(default) = &self.default {
+            prompt.push_str(&format!(" [{}]", default.repr_value()));
+        }
+        prompt
+    }
+}
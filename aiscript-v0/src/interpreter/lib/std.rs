@@ -1,27 +1,62 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{atomic::Ordering, Arc, Mutex},
     time::Duration,
 };
 
+use base64::prelude::*;
 use chrono::{Datelike, TimeZone, Timelike};
-use futures::FutureExt;
+use futures::{stream, FutureExt, StreamExt, TryStreamExt};
 use indexmap::IndexMap;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
 use uri_encoding::{decode_uri, decode_uri_component, encode_uri, encode_uri_component};
 
 use crate::{
     constants::AISCRIPT_VERSION,
     error::{AiScriptError, AiScriptRuntimeError},
     interpreter::{
+        channel::{self, Channel},
         lib::std::seedrandom::seedrandom,
         util::expect_any,
         value::{Value, V},
+        EachIter, Interpreter,
     },
-    values::{VFn, VObj},
+    values::{VArr, VFn, VObj},
+    Parser,
 };
 
+mod arr;
+mod bit;
+mod cell;
+#[cfg(feature = "crypto")]
+mod crypto;
+mod date;
+mod json;
+pub(crate) mod number_format;
+mod schema;
 mod seedrandom;
+#[cfg(feature = "toml")]
+mod toml;
+mod ui;
 mod uri_encoding;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+/// Returns the date and time in UTC of a timestamp in milliseconds.
+fn utc_date(millis: f64) -> Result<chrono::DateTime<chrono::Utc>, AiScriptError> {
+    // `as i64` would turn NaN into 0 and saturate infinities and too large numbers.
+    (i64::MIN as f64..i64::MAX as f64)
+        .contains(&millis)
+        .then(|| chrono::Utc.timestamp_millis_opt(millis as i64).single())
+        .flatten()
+        .ok_or_else(|| AiScriptRuntimeError::Runtime(format!("invalid timestamp: {millis}")).into())
+}
+
+/// Returns the local date and time of a timestamp in milliseconds.
+fn local_date(millis: f64) -> Result<chrono::DateTime<chrono::Local>, AiScriptError> {
+    Ok(utc_date(millis)?.with_timezone(&chrono::Local))
+}
 
 pub fn std() -> HashMap<String, Value> {
     let mut std = HashMap::new();
@@ -47,14 +82,30 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    // `==`, which compares arrays and objects by their contents (see `Value::deep_eq`) unless
+    // `Interpreter::with_reference_equality` is set.
     std.insert(
         "Core:eq".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let a = expect_any(args.next())?;
+                let b = expect_any(args.next())?;
+                Ok(Value::bool(interpreter.equals(&a, &b)))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Core:deep_eq".to_string(),
         Value::fn_native(|args, _| {
             async move {
                 let mut args = args.into_iter();
                 let a = expect_any(args.next())?;
                 let b = expect_any(args.next())?;
-                Ok(Value::bool(a == b))
+                Ok(Value::bool(a.deep_eq(&b)))
             }
             .boxed()
         }),
@@ -62,12 +113,13 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:neq".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let a = expect_any(args.next())?;
                 let b = expect_any(args.next())?;
-                Ok(Value::bool(a != b))
+                Ok(Value::bool(!interpreter.equals(&a, &b)))
             }
             .boxed()
         }),
@@ -107,12 +159,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:add".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(a + b))
+                let res = a + b;
+                check_integer_op(&interpreter, a, b, res)?;
+                Ok(Value::num(res))
             }
             .boxed()
         }),
@@ -120,12 +175,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:sub".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(a - b))
+                let res = a - b;
+                check_integer_op(&interpreter, a, b, res)?;
+                Ok(Value::num(res))
             }
             .boxed()
         }),
@@ -133,12 +191,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:mul".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(a * b))
+                let res = a * b;
+                check_integer_op(&interpreter, a, b, res)?;
+                Ok(Value::num(res))
             }
             .boxed()
         }),
@@ -146,7 +207,8 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:pow".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
@@ -158,6 +220,7 @@ pub fn std() -> HashMap<String, Value> {
                         "Invalid operation.".to_string(),
                     ))?
                 } else {
+                    check_integer_op(&interpreter, a, b, res)?;
                     Ok(Value::num(res))
                 }
             }
@@ -264,11 +327,47 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:to_str".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let v = expect_any(args.next())?;
-                Ok(Value::str(v.repr_value().to_string()))
+                Ok(Value::str(interpreter.repr_string(&v)))
+            }
+            .boxed()
+        }),
+    );
+
+    // Converts a value into a string like `Core:to_str`, but quotes a string as in an array.
+    // `depth` and `max_len` of the options override the limits of the interpreter.
+    std.insert(
+        "Core:inspect".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let v = expect_any(args.next())?;
+                let (mut max_depth, mut max_len) = interpreter.repr_limits();
+                if let Some(options) = args.next() {
+                    let options = VObj::try_from(options)?;
+                    let options = options.read().unwrap();
+                    for (name, limit) in [("depth", &mut max_depth), ("max_len", &mut max_len)] {
+                        if let Some(value) = options.get(name) {
+                            let value = f64::try_from(value.clone())?;
+                            if value < 0.0 || value.trunc() != value {
+                                Err(AiScriptRuntimeError::Runtime(format!(
+                                    "Core:inspect expected non-negative integer {name}, got {value}"
+                                )))?
+                            }
+                            *limit = Some(value as usize);
+                        }
+                    }
+                }
+                Ok(Value::str(interpreter.format_repr(
+                    v.literal_like(),
+                    max_depth,
+                    max_len,
+                )))
             }
             .boxed()
         }),
@@ -276,11 +375,21 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:range".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
+                if !a.is_finite() || !b.is_finite() {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "range bounds must be finite: {a}, {b}"
+                    )))?
+                }
+                if interpreter.max_arr_len.is_some() {
+                    interpreter
+                        .check_arr_len(((a - b).abs().floor() as usize).saturating_add(1))?;
+                }
                 Ok(Value::arr(if a < b {
                     let length = (b - a).floor() + 1.0;
                     let mut i = 0.0;
@@ -309,11 +418,14 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Core:sleep".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let sync = interpreter.expect_async("Core:sleep");
+            let interpreter = interpreter.clone();
             async move {
+                sync?;
                 let mut args = args.into_iter();
                 let delay = f64::try_from(args.next().unwrap_or_default())?;
-                tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+                interpreter.sleep(Duration::from_millis(delay as u64)).await;
                 Ok(Value::null())
             }
             .boxed()
@@ -332,6 +444,67 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    std.insert(
+        "Core:eval_sandbox".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let code = String::try_from(args.next().unwrap_or_default())?;
+                let (max_step, allow) = expect_sandbox_options(args.next())?;
+                let script = match Parser::default().parse(&code) {
+                    Ok(script) => script,
+                    Err(e) => return Ok(e.to_value_localized(interpreter.messages())),
+                };
+                let child = interpreter.spawn_child(allow, max_step)?;
+                let result = futures::select! {
+                    result = child.exec(script).fuse() => result,
+                    _ = interpreter.aborted().fuse() => return Ok(Value::null()),
+                };
+                // The steps of the child are charged to this interpreter, whose limit must not be
+                // caught as an error value so that scripts cannot escape it.
+                if interpreter.max_step.is_some_and(|max_step| {
+                    interpreter.step_count.load(Ordering::SeqCst) > max_step
+                }) {
+                    Err(AiScriptRuntimeError::MaxStepExceeded { in_loop: None })?
+                }
+                Ok(match result {
+                    Ok(value) => value.unwrap_or_default(),
+                    Err(e) => e.to_value_localized(interpreter.messages()),
+                })
+            }
+            .boxed()
+        }),
+    );
+
+    // Calls `fn` and returns a runtime error in it as an error value, or passes the error value to
+    // `catch` if given. Exceeding the max step is not caught so that scripts cannot escape it.
+    std.insert(
+        "Core:try".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let fn_ = VFn::try_from(args.next().unwrap_or_default())?;
+                let catch = args.next().map(VFn::try_from).transpose()?;
+                match interpreter.exec_fn_simple(fn_, []).await {
+                    Err(AiScriptError::Runtime(e))
+                        if !matches!(e.root(), AiScriptRuntimeError::MaxStepExceeded { .. }) =>
+                    {
+                        let error =
+                            AiScriptError::Runtime(e).to_value_localized(interpreter.messages());
+                        match catch {
+                            Some(catch) => interpreter.exec_fn_simple(catch, [error]).await,
+                            None => Ok(error),
+                        }
+                    }
+                    result => result,
+                }
+            }
+            .boxed()
+        }),
+    );
+
     std.insert(
         "Util:uuid".to_string(),
         Value::fn_native(|_, _| async move { Ok(Value::str(uuid::Uuid::new_v4())) }.boxed()),
@@ -339,11 +512,24 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Json:stringify".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let v = expect_any(args.next())?;
-                serde_json::to_string(&v.value).map_or_else(
+                let indent = json::expect_indent(args.next())?;
+                let v = match json::expect_stringify_options(args.next())? {
+                    Some(options) => {
+                        let options = Arc::new(options);
+                        let key = Value::str("");
+                        match json::replace(interpreter, options, key, v, Vec::new()).await? {
+                            Some(v) => v,
+                            None => return Ok(Value::error("not_json", None)),
+                        }
+                    }
+                    None => v,
+                };
+                json::to_string(&v.value, &indent).map_or_else(
                     |err| {
                         if err.to_string() == "cyclic_reference" {
                             Err(AiScriptError::Internal("too much recursion".to_string()))
@@ -360,12 +546,24 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Json:parse".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let json = String::try_from(args.next().unwrap_or_default())?;
-                Ok(serde_json::from_str(&json)
-                    .map_or_else(|_| Value::error("not_json", None), Value::new))
+                let (relaxed, reviver) = expect_json_options(args.next())?;
+                let json = if relaxed { json::relax(&json) } else { json };
+                let value = match serde_json::from_str(&json) {
+                    Ok(value) => Value::new(value),
+                    Err(_) => return Ok(Value::error("not_json", None)),
+                };
+                check_integers_in(&interpreter, &value.value)?;
+                match reviver {
+                    Some(reviver) => {
+                        json::revive(interpreter, reviver, Value::str(""), value).await
+                    }
+                    None => Ok(value),
+                }
             }
             .boxed()
         }),
@@ -377,6 +575,8 @@ pub fn std() -> HashMap<String, Value> {
             async move {
                 let mut args = args.into_iter();
                 let json = String::try_from(args.next().unwrap_or_default())?;
+                let (relaxed, _) = expect_json_options(args.next())?;
+                let json = if relaxed { json::relax(&json) } else { json };
                 Ok(Value::bool(serde_json::from_str::<V>(&json).is_ok()))
             }
             .boxed()
@@ -385,28 +585,23 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:now".to_string(),
-        Value::fn_native(|_, _| {
-            async move { Ok(Value::num(chrono::Local::now().timestamp_millis() as f64)) }.boxed()
+        Value::fn_native(|_, interpreter| {
+            let now = interpreter.now();
+            async move { Ok(Value::num(now)) }.boxed()
         }),
     );
 
     std.insert(
         "Date:year".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:year");
             async move {
                 let mut args = args.into_iter();
-                let date =
-                    args.next()
-                        .map(f64::try_from)
-                        .map_or(Ok(None), |r| r.map(Some))?
-                        .map_or_else(
-                            || Ok(chrono::Local::now()),
-                            |v| {
-                                chrono::Local.timestamp_millis_opt(v as i64).single().ok_or(
-                                    AiScriptError::Internal(format!("invalid timestamp: {v}")),
-                                )
-                            },
-                        )?;
+                let date = args
+                    .next()
+                    .map(f64::try_from)
+                    .map_or(Ok(None), |r| r.map(Some))?
+                    .map_or_else(|| local_date(now?), local_date)?;
                 Ok(Value::num(date.year()))
             }
             .boxed()
@@ -415,21 +610,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:month".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:month");
             async move {
                 let mut args = args.into_iter();
-                let date =
-                    args.next()
-                        .map(f64::try_from)
-                        .map_or(Ok(None), |r| r.map(Some))?
-                        .map_or_else(
-                            || Ok(chrono::Local::now()),
-                            |v| {
-                                chrono::Local.timestamp_millis_opt(v as i64).single().ok_or(
-                                    AiScriptError::Internal(format!("invalid timestamp: {v}")),
-                                )
-                            },
-                        )?;
+                let date = args
+                    .next()
+                    .map(f64::try_from)
+                    .map_or(Ok(None), |r| r.map(Some))?
+                    .map_or_else(|| local_date(now?), local_date)?;
                 Ok(Value::num(date.month()))
             }
             .boxed()
@@ -438,21 +627,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:day".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:day");
             async move {
                 let mut args = args.into_iter();
-                let date =
-                    args.next()
-                        .map(f64::try_from)
-                        .map_or(Ok(None), |r| r.map(Some))?
-                        .map_or_else(
-                            || Ok(chrono::Local::now()),
-                            |v| {
-                                chrono::Local.timestamp_millis_opt(v as i64).single().ok_or(
-                                    AiScriptError::Internal(format!("invalid timestamp: {v}")),
-                                )
-                            },
-                        )?;
+                let date = args
+                    .next()
+                    .map(f64::try_from)
+                    .map_or(Ok(None), |r| r.map(Some))?
+                    .map_or_else(|| local_date(now?), local_date)?;
                 Ok(Value::num(date.day()))
             }
             .boxed()
@@ -461,21 +644,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:hour".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:hour");
             async move {
                 let mut args = args.into_iter();
-                let date =
-                    args.next()
-                        .map(f64::try_from)
-                        .map_or(Ok(None), |r| r.map(Some))?
-                        .map_or_else(
-                            || Ok(chrono::Local::now()),
-                            |v| {
-                                chrono::Local.timestamp_millis_opt(v as i64).single().ok_or(
-                                    AiScriptError::Internal(format!("invalid timestamp: {v}")),
-                                )
-                            },
-                        )?;
+                let date = args
+                    .next()
+                    .map(f64::try_from)
+                    .map_or(Ok(None), |r| r.map(Some))?
+                    .map_or_else(|| local_date(now?), local_date)?;
                 Ok(Value::num(date.hour()))
             }
             .boxed()
@@ -484,21 +661,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:minute".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:minute");
             async move {
                 let mut args = args.into_iter();
-                let date =
-                    args.next()
-                        .map(f64::try_from)
-                        .map_or(Ok(None), |r| r.map(Some))?
-                        .map_or_else(
-                            || Ok(chrono::Local::now()),
-                            |v| {
-                                chrono::Local.timestamp_millis_opt(v as i64).single().ok_or(
-                                    AiScriptError::Internal(format!("invalid timestamp: {v}")),
-                                )
-                            },
-                        )?;
+                let date = args
+                    .next()
+                    .map(f64::try_from)
+                    .map_or(Ok(None), |r| r.map(Some))?
+                    .map_or_else(|| local_date(now?), local_date)?;
                 Ok(Value::num(date.minute()))
             }
             .boxed()
@@ -507,21 +678,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:second".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:second");
             async move {
                 let mut args = args.into_iter();
-                let date =
-                    args.next()
-                        .map(f64::try_from)
-                        .map_or(Ok(None), |r| r.map(Some))?
-                        .map_or_else(
-                            || Ok(chrono::Local::now()),
-                            |v| {
-                                chrono::Local.timestamp_millis_opt(v as i64).single().ok_or(
-                                    AiScriptError::Internal(format!("invalid timestamp: {v}")),
-                                )
-                            },
-                        )?;
+                let date = args
+                    .next()
+                    .map(f64::try_from)
+                    .map_or(Ok(None), |r| r.map(Some))?
+                    .map_or_else(|| local_date(now?), local_date)?;
                 Ok(Value::num(date.second()))
             }
             .boxed()
@@ -530,14 +695,15 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:millisecond".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:millisecond");
             async move {
                 let mut args = args.into_iter();
                 let v = args
                     .next()
                     .map(f64::try_from)
                     .map_or(Ok(None), |r| r.map(Some))?
-                    .unwrap_or_else(|| chrono::Local::now().timestamp_millis() as f64);
+                    .map_or(now, Ok)?;
                 Ok(Value::num(v % 1000.0))
             }
             .boxed()
@@ -560,25 +726,32 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Date:to_iso_str".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:to_iso_str");
             async move {
                 let mut args = args.into_iter();
                 let mut date = args
                     .next()
                     .map(f64::try_from)
                     .map_or(Ok(None), |r| r.map(Some))?
-                    .map_or_else(chrono::Local::now, |v| {
-                        chrono::Local.timestamp_millis_opt(v as i64).unwrap()
-                    });
+                    .map_or_else(|| local_date(now?), local_date)?;
                 let local_offset =
                     chrono::Duration::seconds(date.offset().local_minus_utc() as i64);
-                let ofs = args
-                    .next()
-                    .map(f64::try_from)
-                    .map_or(Ok(None), |r| r.map(Some))?
-                    .map(|ofs| chrono::Duration::minutes(ofs as i64));
+                let ofs = match args.next() {
+                    Some(zone) => Some(chrono::Duration::seconds(
+                        date::Zone::expect(Some(zone))?
+                            .offset_at(&date.to_utc())
+                            .local_minus_utc() as i64,
+                    )),
+                    None => None,
+                };
                 if let Some(ofs) = ofs {
-                    date += -local_offset + ofs;
+                    date = date.checked_add_signed(ofs - local_offset).ok_or_else(|| {
+                        AiScriptRuntimeError::Runtime(format!(
+                            "invalid timestamp: {}",
+                            date.timestamp_millis()
+                        ))
+                    })?;
                 }
                 let ofs = ofs.unwrap_or(local_offset);
                 Ok(Value::str(format!(
@@ -756,13 +929,40 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    std.insert(
+        "Math:clamp".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = f64::try_from(args.next().unwrap_or_default())?;
+                let min = f64::try_from(args.next().unwrap_or_default())?;
+                let max = f64::try_from(args.next().unwrap_or_default())?;
+                if min > max {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "Math:clamp expected min <= max, got {min} > {max}"
+                    )))?
+                }
+                Ok(Value::num(if v.is_nan() || min.is_nan() || max.is_nan() {
+                    f64::NAN
+                } else if v < min {
+                    min
+                } else if v > max {
+                    max
+                } else {
+                    v
+                }))
+            }
+            .boxed()
+        }),
+    );
+
     std.insert(
         "Math:clz32".to_string(),
         Value::fn_native(|args, _| {
             async move {
                 let mut args = args.into_iter();
                 let v = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num((v as i32).leading_zeros()))
+                Ok(Value::num(to_uint32(v).leading_zeros()))
             }
             .boxed()
         }),
@@ -860,9 +1060,25 @@ pub fn std() -> HashMap<String, Value> {
                         let mut values = Vec::new();
                         for v in args {
                             let v = f64::try_from(v)?;
-                            values.push(v);
+                            values.push(v.abs());
+                        }
+                        if values.iter().any(|v| v.is_infinite()) {
+                            f64::INFINITY
+                        } else if values.iter().any(|v| v.is_nan()) {
+                            f64::NAN
+                        } else {
+                            // Scale to avoid overflow and underflow of the squares.
+                            let max = values.iter().fold(0.0, |acc: f64, v| acc.max(*v));
+                            if max == 0.0 {
+                                0.0
+                            } else {
+                                values
+                                    .iter()
+                                    .fold(0.0, |acc, v| acc + (v / max) * (v / max))
+                                    .sqrt()
+                                    * max
+                            }
                         }
-                        values.iter().fold(0.0, |acc, v| acc + v * v).sqrt()
                     }
                 }))
             }
@@ -877,7 +1093,7 @@ pub fn std() -> HashMap<String, Value> {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num((a as i32) * (b as i32)))
+                Ok(Value::num(to_int32(a).wrapping_mul(to_int32(b))))
             }
             .boxed()
         }),
@@ -938,7 +1154,18 @@ pub fn std() -> HashMap<String, Value> {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(a.max(b)))
+                Ok(Value::num(if a.is_nan() || b.is_nan() {
+                    f64::NAN
+                } else if a == b {
+                    // max(-0, 0) is 0
+                    if a.is_sign_negative() {
+                        b
+                    } else {
+                        a
+                    }
+                } else {
+                    a.max(b)
+                }))
             }
             .boxed()
         }),
@@ -951,7 +1178,18 @@ pub fn std() -> HashMap<String, Value> {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(a.min(b)))
+                Ok(Value::num(if a.is_nan() || b.is_nan() {
+                    f64::NAN
+                } else if a == b {
+                    // min(-0, 0) is -0
+                    if a.is_sign_negative() {
+                        a
+                    } else {
+                        b
+                    }
+                } else {
+                    a.min(b)
+                }))
             }
             .boxed()
         }),
@@ -964,7 +1202,13 @@ pub fn std() -> HashMap<String, Value> {
                 let mut args = args.into_iter();
                 let a = f64::try_from(args.next().unwrap_or_default())?;
                 let b = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(a.powf(b)))
+                Ok(Value::num(
+                    if b.is_nan() || (a.abs() == 1.0 && b.is_infinite()) {
+                        f64::NAN
+                    } else {
+                        a.powf(b)
+                    },
+                ))
             }
             .boxed()
         }),
@@ -976,7 +1220,10 @@ pub fn std() -> HashMap<String, Value> {
             async move {
                 let mut args = args.into_iter();
                 let v = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(v.round()))
+                // Halves are rounded up, e.g. -2.5 to -2.
+                let r = v.round();
+                let r = if v - r == 0.5 { r + 1.0 } else { r };
+                Ok(Value::num(if r == 0.0 { 0.0f64.copysign(v) } else { r }))
             }
             .boxed()
         }),
@@ -988,12 +1235,10 @@ pub fn std() -> HashMap<String, Value> {
             async move {
                 let mut args = args.into_iter();
                 let v = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::num(if v < 0.0 {
-                    -1.0
-                } else if v == 0.0 {
-                    0.0
+                Ok(Value::num(if v.is_nan() || v == 0.0 {
+                    v
                 } else {
-                    1.0
+                    v.signum()
                 }))
             }
             .boxed()
@@ -1097,7 +1342,7 @@ pub fn std() -> HashMap<String, Value> {
             async move {
                 let mut args = args.into_iter();
                 let seed = expect_any(args.next())?;
-                Ok(match *seed.value {
+                Ok(match seed.value {
                     V::Num(num) => Some(num.to_string()),
                     V::Str(str) => Some(str),
                     _ => None,
@@ -1132,7 +1377,76 @@ pub fn std() -> HashMap<String, Value> {
             async move {
                 let mut args = args.into_iter();
                 let v = f64::try_from(args.next().unwrap_or_default())?;
-                Ok(Value::str(format!("{:x}", v as i64)))
+                Ok(Value::str(number_format::to_radix_string(v, 16)))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Num:to_fixed".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = f64::try_from(args.next().unwrap_or_default())?;
+                let digits = match args.next() {
+                    Some(digits) => expect_digits(digits, 0, 100, "Num:to_fixed")?,
+                    None => 0,
+                };
+                Ok(Value::str(number_format::to_fixed(v, digits)))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Num:to_precision".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = f64::try_from(args.next().unwrap_or_default())?;
+                let precision =
+                    expect_digits(args.next().unwrap_or_default(), 1, 100, "Num:to_precision")?;
+                Ok(Value::str(number_format::to_precision(v, precision)))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Num:parse_int".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let s = String::try_from(args.next().unwrap_or_default())?;
+                let radix = match args.next() {
+                    Some(radix) => f64::try_from(radix)?.trunc(),
+                    None => 0.0,
+                };
+                // An invalid radix results in NaN, while NaN means the default.
+                let radix = if radix.is_nan() {
+                    0
+                } else if (0.0..=36.0).contains(&radix) {
+                    radix as u32
+                } else {
+                    1
+                };
+                let value = number_format::parse_int(&s, radix);
+                interpreter.check_integer(value)?;
+                Ok(Value::num(value))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Num:parse_float".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let s = String::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::num(number_format::parse_float(&s)))
             }
             .boxed()
         }),
@@ -1152,6 +1466,36 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    std.insert(
+        "Str:concat_all".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let arr = VArr::try_from(args.next().unwrap_or_default())?;
+                let arr = arr.read().unwrap();
+                let mut str = String::with_capacity(
+                    arr.iter()
+                        .map(|item| match &item.value {
+                            V::Str(value) => value.len(),
+                            _ => 0,
+                        })
+                        .sum(),
+                );
+                for item in arr.iter() {
+                    match &item.value {
+                        V::Str(value) => str.push_str(value),
+                        value => Err(AiScriptRuntimeError::Type {
+                            expected: "string".to_string(),
+                            got: value.display_type().to_string(),
+                        })?,
+                    }
+                }
+                Ok(Value::str(str))
+            }
+            .boxed()
+        }),
+    );
+
     std.insert("Str:lf".to_string(), Value::str("\n"));
 
     std.insert(
@@ -1249,6 +1593,67 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    std.insert(
+        "Str:to_utf8_bin".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = String::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::bin(v))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Str:normalize".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let s = String::try_from(args.next().unwrap_or_default())?;
+                let form = match args.next() {
+                    Some(form) => String::try_from(form)?,
+                    None => "NFC".to_string(),
+                };
+                Ok(Value::str(match form.as_str() {
+                    "NFC" => s.nfc().collect::<String>(),
+                    "NFD" => s.nfd().collect(),
+                    "NFKC" => s.nfkc().collect(),
+                    "NFKD" => s.nfkd().collect(),
+                    _ => Err(AiScriptRuntimeError::Runtime(format!(
+                        "Str:normalize expected 'NFC', 'NFD', 'NFKC' or 'NFKD', got '{form}'"
+                    )))?,
+                }))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Str:casefold".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let s = String::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::str(caseless::default_case_fold_str(&s)))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Str:levenshtein".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let a = String::try_from(args.next().unwrap_or_default())?;
+                let b = String::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::num(levenshtein(&a, &b) as f64))
+            }
+            .boxed()
+        }),
+    );
+
     std.insert(
         "Uri:encode_full".to_string(),
         Value::fn_native(|args, _| {
@@ -1302,8 +1707,103 @@ pub fn std() -> HashMap<String, Value> {
     );
 
     std.insert(
-        "Arr:create".to_string(),
+        "Bin:from_base64".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = String::try_from(args.next().unwrap_or_default())?;
+                let bin = BASE64_STANDARD.decode(v).map_err(|e| {
+                    AiScriptRuntimeError::Runtime(format!("Invalid base64 string: {e}"))
+                })?;
+                Ok(Value::bin(bin))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Bin:to_base64".to_string(),
         Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let bin = <Vec<u8>>::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::str(BASE64_STANDARD.encode(bin)))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Bin:from_hex".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = String::try_from(args.next().unwrap_or_default())?;
+                let bin = hex::decode(v).map_err(|e| {
+                    AiScriptRuntimeError::Runtime(format!("Invalid hex string: {e}"))
+                })?;
+                Ok(Value::bin(bin))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Bin:to_hex".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let bin = <Vec<u8>>::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::str(hex::encode(bin)))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Bin:from_arr".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let bytes = <Vec<Value>>::try_from(args.next().unwrap_or_default())?;
+                let bin = bytes
+                    .into_iter()
+                    .map(|byte| {
+                        let byte = f64::try_from(byte)?;
+                        if byte.trunc() == byte && (0.0..=255.0).contains(&byte) {
+                            Ok(byte as u8)
+                        } else {
+                            Err(AiScriptRuntimeError::Runtime(format!(
+                                "{byte} is not a byte"
+                            )))?
+                        }
+                    })
+                    .collect::<Result<Vec<u8>, AiScriptError>>()?;
+                Ok(Value::bin(bin))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Bin:to_str".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let bin = <Vec<u8>>::try_from(args.next().unwrap_or_default())?;
+                let s = String::from_utf8(bin).map_err(|e| {
+                    AiScriptRuntimeError::Runtime(format!("Invalid UTF-8 sequence: {e}"))
+                })?;
+                Ok(Value::str(s))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Arr:create".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
                 let length = f64::try_from(args.next().unwrap_or_default())?;
@@ -1317,6 +1817,7 @@ pub fn std() -> HashMap<String, Value> {
                         "arr.repeat expected integer, got non-integer".to_string(),
                     ))?
                 } else {
+                    interpreter.check_arr_len(length as usize)?;
                     let mut value = Vec::new();
                     for _ in 0..length as usize {
                         value.push(initial.clone())
@@ -1328,6 +1829,57 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    // Collects the items of anything `each` iterates, such as generators, into an array.
+    std.insert(
+        "Arr:from".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut items = EachIter::try_from(args.into_iter().next().unwrap_or_default())?;
+                let mut value = Vec::new();
+                while let Some(item) = items.next(&interpreter).await? {
+                    value.push(item);
+                }
+                Ok(Value::arr(value))
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Arr:parallel_map".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let arr = VArr::try_from(args.next().unwrap_or_default())?;
+                let fn_ = VFn::try_from(args.next().unwrap_or_default())?;
+                let concurrency = args
+                    .next()
+                    .map(f64::try_from)
+                    .map_or(Ok(None), |r| r.map(Some))?;
+                let items = arr.read().unwrap().clone();
+                let concurrency = match concurrency {
+                    Some(concurrency) if concurrency < 1.0 || concurrency.trunc() != concurrency => {
+                        Err(AiScriptRuntimeError::Runtime(format!(
+                            "Arr:parallel_map expected positive integer concurrency, got {concurrency}"
+                        )))?
+                    }
+                    Some(concurrency) => concurrency as usize,
+                    None => items.len().max(1),
+                };
+                let values = stream::iter(items.into_iter().enumerate().map(|(i, item)| {
+                    interpreter.exec_fn_simple(fn_.clone(), vec![item, Value::num(i as f64)])
+                }))
+                .buffered(concurrency)
+                .try_collect::<Vec<Value>>()
+                .await?;
+                Ok(Value::arr(values))
+            }
+            .boxed()
+        }),
+    );
+
     std.insert(
         "Obj:keys".to_string(),
         Value::fn_native(|args, _| {
@@ -1398,12 +1950,16 @@ pub fn std() -> HashMap<String, Value> {
 
     std.insert(
         "Obj:set".to_string(),
-        Value::fn_native(|args, _| {
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
             async move {
                 let mut args = args.into_iter();
-                let obj = VObj::try_from(args.next().unwrap_or_default())?;
+                let obj = args.next().unwrap_or_default();
+                obj.expect_mutable()?;
+                let obj = VObj::try_from(obj)?;
                 let key = String::try_from(args.next().unwrap_or_default())?;
                 let value = expect_any(args.next())?;
+                interpreter.track_obj(&obj, &value);
                 obj.write().unwrap().insert(key, value);
                 Ok(Value::null())
             }
@@ -1451,6 +2007,19 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    std.insert(
+        "Obj:sort_keys".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let mut obj = <IndexMap<String, Value>>::try_from(args.next().unwrap_or_default())?;
+                obj.sort_unstable_keys();
+                Ok(Value::obj(obj))
+            }
+            .boxed()
+        }),
+    );
+
     std.insert(
         "Error:create".to_string(),
         Value::fn_native(|args, _| {
@@ -1469,6 +2038,7 @@ pub fn std() -> HashMap<String, Value> {
         Value::fn_native(|args, interpreter| {
             let interpreter = interpreter.clone();
             async move {
+                interpreter.expect_async("Async:interval")?;
                 let mut args = args.into_iter();
                 let interval = f64::try_from(args.next().unwrap_or_default())?;
                 let callback = VFn::try_from(args.next().unwrap_or_default())?;
@@ -1479,13 +2049,12 @@ pub fn std() -> HashMap<String, Value> {
                 let abort_handler = interpreter.register_abort_handler({
                     let interpreter = interpreter.clone();
                     async move {
-                        let mut interval =
-                            tokio::time::interval(Duration::from_millis(interval as u64));
-                        if !immediate.unwrap_or(false) {
-                            interval.tick().await;
+                        let interval = Duration::from_millis(interval as u64);
+                        if immediate.unwrap_or(false) {
+                            interpreter.exec_fn(callback.clone(), Vec::new()).await?;
                         }
                         loop {
-                            interval.tick().await;
+                            interpreter.sleep(interval).await;
                             interpreter.exec_fn(callback.clone(), Vec::new()).await?;
                         }
                     }
@@ -1504,13 +2073,16 @@ pub fn std() -> HashMap<String, Value> {
         Value::fn_native(|args, interpreter| {
             let interpreter = interpreter.clone();
             async move {
+                interpreter.expect_async("Async:timeout")?;
                 let mut args = args.into_iter();
                 let interval = f64::try_from(args.next().unwrap_or_default())?;
                 let callback = VFn::try_from(args.next().unwrap_or_default())?;
                 let abort_handler = interpreter.register_abort_handler({
                     let interpreter = interpreter.clone();
                     async move {
-                        tokio::time::sleep(Duration::from_millis(interval as u64)).await;
+                        interpreter
+                            .sleep(Duration::from_millis(interval as u64))
+                            .await;
                         interpreter.exec_fn(callback.clone(), Vec::new()).await?;
                         Ok(())
                     }
@@ -1524,5 +2096,207 @@ pub fn std() -> HashMap<String, Value> {
         }),
     );
 
+    std.insert(
+        "Event:on".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let name = String::try_from(args.next().unwrap_or_default())?;
+                let handler = VFn::try_from(args.next().unwrap_or_default())?;
+                interpreter.on_event(name, handler);
+                Ok(Value::null())
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Chan:create".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let capacity = f64::try_from(args.next().unwrap_or_default())?;
+                if !(1.0..=channel::MAX_CAPACITY as f64).contains(&capacity)
+                    || capacity.trunc() != capacity
+                {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "Chan:create expected integer capacity from 1 to {}, got {capacity}",
+                        channel::MAX_CAPACITY
+                    )))?
+                }
+                let capacity = capacity as usize;
+                interpreter.check_arr_len(capacity)?;
+                Ok(Channel::bounded(capacity).to_value())
+            }
+            .boxed()
+        }),
+    );
+
+    std.insert(
+        "Chan:send".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move { channel::call(&interpreter, "send", args).await }.boxed()
+        }),
+    );
+
+    std.insert(
+        "Chan:recv".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move { channel::call(&interpreter, "recv", args).await }.boxed()
+        }),
+    );
+
+    std.insert(
+        "Chan:close".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move { channel::call(&interpreter, "close", args).await }.boxed()
+        }),
+    );
+
+    std.extend(arr::arr());
+
+    std.extend(bit::bit());
+
+    std.extend(cell::cell());
+
+    std.extend(date::date());
+
+    std.extend(schema::schema());
+
+    std.extend(ui::ui());
+
+    #[cfg(feature = "crypto")]
+    std.extend(crypto::crypto());
+
+    #[cfg(feature = "toml")]
+    std.extend(toml::toml());
+
+    #[cfg(feature = "yaml")]
+    std.extend(yaml::yaml());
+
     std
 }
+
+/// Returns the number of insertions, deletions and substitutions of graphemes needed to change
+/// `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a = a.graphemes(true).collect::<Vec<_>>();
+    let b = b.graphemes(true).collect::<Vec<_>>();
+    let mut distances = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.iter().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != b);
+            diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(diagonal + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+/// Converts a number into a 32-bit integer by wrapping around like `ToInt32` of JavaScript.
+fn to_int32(v: f64) -> i32 {
+    to_uint32(v) as i32
+}
+
+/// Converts a number into an unsigned 32-bit integer by wrapping around like `ToUint32` of
+/// JavaScript.
+fn to_uint32(v: f64) -> u32 {
+    if v.is_finite() {
+        v.trunc().rem_euclid(4294967296.0) as u32
+    } else {
+        0
+    }
+}
+
+/// Checks the result of an arithmetic operation on integers in strict integer mode.
+fn check_integer_op(
+    interpreter: &Interpreter,
+    a: f64,
+    b: f64,
+    res: f64,
+) -> Result<(), AiScriptError> {
+    if a.is_finite() && a.trunc() == a && b.is_finite() && b.trunc() == b {
+        interpreter.check_integer(res)?;
+    }
+    Ok(())
+}
+
+/// Checks the numbers in a parsed JSON value in strict integer mode.
+fn check_integers_in(interpreter: &Interpreter, value: &V) -> Result<(), AiScriptError> {
+    match value {
+        V::Num(value) => interpreter.check_integer(*value),
+        V::Arr(value) => value
+            .read()
+            .unwrap()
+            .iter()
+            .try_for_each(|value| check_integers_in(interpreter, &value.value)),
+        V::Obj(value) => value
+            .read()
+            .unwrap()
+            .values()
+            .try_for_each(|value| check_integers_in(interpreter, &value.value)),
+        _ => Ok(()),
+    }
+}
+
+/// Expects the options of `Core:eval_sandbox`, `{ max_step?: num, allow?: arr }`.
+fn expect_sandbox_options(
+    options: Option<Value>,
+) -> Result<(Option<usize>, Vec<String>), AiScriptError> {
+    let Some(options) = options else {
+        return Ok((None, Vec::new()));
+    };
+    let options = VObj::try_from(options)?;
+    let options = options.read().unwrap();
+    let max_step = options
+        .get("max_step")
+        .map(|max_step| f64::try_from(max_step.clone()))
+        .transpose()?
+        .map(|max_step| max_step.max(0.0) as usize);
+    let allow = match options.get("allow") {
+        Some(allow) => VArr::try_from(allow.clone())?
+            .read()
+            .unwrap()
+            .iter()
+            .map(|name| String::try_from(name.clone()))
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    Ok((max_step, allow))
+}
+
+/// Expects the options of `Json:parse`, `{ relaxed?: bool, reviver?: fn }`.
+fn expect_json_options(options: Option<Value>) -> Result<(bool, Option<VFn>), AiScriptError> {
+    let Some(options) = options else {
+        return Ok((false, None));
+    };
+    let options = VObj::try_from(options)?;
+    let options = options.read().unwrap();
+    let relaxed = options
+        .get("relaxed")
+        .map_or(Ok(false), |relaxed| bool::try_from(relaxed.clone()))?;
+    let reviver = options
+        .get("reviver")
+        .map(|reviver| VFn::try_from(reviver.clone()))
+        .transpose()?;
+    Ok((relaxed, reviver))
+}
+
+/// Expects an integer number of digits between `min` and `max`.
+fn expect_digits(value: Value, min: usize, max: usize, name: &str) -> Result<usize, AiScriptError> {
+    let digits = f64::try_from(value)?;
+    if digits.trunc() == digits && (min as f64..=max as f64).contains(&digits) {
+        Ok(digits as usize)
+    } else {
+        Err(AiScriptRuntimeError::Runtime(format!(
+            "{name} expected an integer between {min} and {max}, got {digits}"
+        )))?
+    }
+}
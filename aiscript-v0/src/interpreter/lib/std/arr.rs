@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use futures::FutureExt;
+use indexmap::IndexMap;
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    interpreter::{
+        util::sort_by_keys,
+        value::{VFn, Value},
+    },
+};
+
+fn expect_arr(value: Option<Value>) -> Result<Vec<Value>, AiScriptError> {
+    <Vec<Value>>::try_from(value.unwrap_or_default())
+}
+
+fn expect_nums(value: Option<Value>) -> Result<Vec<f64>, AiScriptError> {
+    expect_arr(value)?.into_iter().map(f64::try_from).collect()
+}
+
+/// Returns the items of arrays with the same index as arrays, up to the shortest array.
+fn transpose(arrs: Vec<Vec<Value>>) -> Vec<Value> {
+    let len = arrs.iter().map(Vec::len).min().unwrap_or(0);
+    (0..len)
+        .map(|i| Value::arr(arrs.iter().map(|arr| arr[i].clone())))
+        .collect()
+}
+
+pub fn arr() -> HashMap<String, Value> {
+    let mut arr = HashMap::new();
+
+    arr.insert(
+        "Arr:zip".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let arrs = args
+                    .into_iter()
+                    .map(|arg| expect_arr(Some(arg)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::arr(transpose(arrs)))
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:unzip".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let arrs = expect_arr(args.into_iter().next())?
+                    .into_iter()
+                    .map(|item| expect_arr(Some(item)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::arr(transpose(arrs)))
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:range_by".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let start = f64::try_from(args.next().unwrap_or_default())?;
+                let end = f64::try_from(args.next().unwrap_or_default())?;
+                let step = f64::try_from(args.next().unwrap_or_default())?;
+                if step == 0.0 || !step.is_finite() {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "Arr:range_by expected non-zero step, got {step}"
+                    )))?
+                }
+                if !start.is_finite() || !end.is_finite() {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "range bounds must be finite: {start}, {end}"
+                    )))?
+                }
+                let count = ((end - start) / step).floor() + 1.0;
+                // the same limit as the length of an array in JavaScript
+                if count > u32::MAX as f64 {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "Arr:range_by cannot create {count} items"
+                    )))?
+                }
+                if count >= 1.0 {
+                    interpreter.check_arr_len(count as usize)?;
+                }
+                Ok(Value::arr(if count >= 1.0 {
+                    (0..count as usize)
+                        .map(|i| Value::num(start + i as f64 * step))
+                        .collect()
+                } else {
+                    Vec::new()
+                }))
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:chunk".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let items = expect_arr(args.next())?;
+                let size = f64::try_from(args.next().unwrap_or_default())?;
+                if size < 1.0 || size.trunc() != size {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "Arr:chunk expected positive integer size, got {size}"
+                    )))?
+                }
+                Ok(Value::arr(
+                    items
+                        .chunks(size as usize)
+                        .map(|chunk| Value::arr(chunk.to_vec())),
+                ))
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:group_by".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let items = expect_arr(args.next())?;
+                let fn_ = VFn::try_from(args.next().unwrap_or_default())?;
+                let mut groups = IndexMap::<String, Vec<Value>>::new();
+                for (i, item) in items.into_iter().enumerate() {
+                    let key = interpreter
+                        .exec_fn_simple(fn_.clone(), [item.clone(), Value::num(i as f64)])
+                        .await?;
+                    groups.entry(String::try_from(key)?).or_default().push(item);
+                }
+                Ok(Value::obj(
+                    groups
+                        .into_iter()
+                        .map(|(key, items)| (key, Value::arr(items))),
+                ))
+            }
+            .boxed()
+        }),
+    );
+
+    // Arr:sort_by_keys(arr, key_fns, orders?) returns the items sorted stably by the keys of
+    // the functions, the later ones breaking ties of the earlier ones, in the orders "asc"
+    // (default) or "desc".
+    arr.insert(
+        "Arr:sort_by_keys".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let items = expect_arr(args.next())?;
+                let fns = expect_arr(args.next())?
+                    .into_iter()
+                    .map(VFn::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let descending = match args.next() {
+                    None => Vec::new(),
+                    orders => expect_arr(orders)?
+                        .into_iter()
+                        .map(|order| match String::try_from(order)?.as_str() {
+                            "asc" => Ok(false),
+                            "desc" => Ok(true),
+                            order => Err(AiScriptRuntimeError::Runtime(format!(
+                                "Arr:sort_by_keys expected \"asc\" or \"desc\", got {order}"
+                            )))?,
+                        })
+                        .collect::<Result<Vec<_>, AiScriptError>>()?,
+                };
+                let mut keys = Vec::with_capacity(fns.len());
+                for fn_ in fns {
+                    let mut fn_keys = Vec::with_capacity(items.len());
+                    for item in items.iter() {
+                        fn_keys.push(
+                            interpreter
+                                .exec_fn_simple(fn_.clone(), [item.clone()])
+                                .await?,
+                        );
+                    }
+                    keys.push(fn_keys);
+                }
+                Ok(Value::arr(sort_by_keys(items, &keys, &descending)?))
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:distinct".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut distinct = Vec::<Value>::new();
+                for item in expect_arr(args.into_iter().next())? {
+                    if !distinct
+                        .iter()
+                        .any(|other| interpreter.equals(other, &item))
+                    {
+                        distinct.push(item);
+                    }
+                }
+                Ok(Value::arr(distinct))
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:sum".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let nums = expect_nums(args.into_iter().next())?;
+                Ok(Value::num(nums.into_iter().sum::<f64>()))
+            }
+            .boxed()
+        }),
+    );
+
+    // The following functions return null for an empty array.
+
+    arr.insert(
+        "Arr:avg".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let nums = expect_nums(args.into_iter().next())?;
+                Ok(if nums.is_empty() {
+                    Value::null()
+                } else {
+                    Value::num(nums.iter().sum::<f64>() / nums.len() as f64)
+                })
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:min".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let nums = expect_nums(args.into_iter().next())?;
+                Ok(nums
+                    .into_iter()
+                    .reduce(f64::min)
+                    .map_or_else(Value::null, Value::num))
+            }
+            .boxed()
+        }),
+    );
+
+    arr.insert(
+        "Arr:max".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let nums = expect_nums(args.into_iter().next())?;
+                Ok(nums
+                    .into_iter()
+                    .reduce(f64::max)
+                    .map_or_else(Value::null, Value::num))
+            }
+            .boxed()
+        }),
+    );
+
+    arr
+}
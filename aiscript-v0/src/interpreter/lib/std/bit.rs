@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use futures::FutureExt;
+
+use crate::{error::AiScriptError, interpreter::value::Value};
+
+use super::{to_int32, to_uint32};
+
+// Bitwise operations work on 32-bit integers in the same way as the operators of JavaScript.
+// Numbers are truncated and wrapped around into 32 bits, where NaN and infinities are 0, and
+// shift counts are taken modulo 32.
+
+fn expect_num(value: Option<Value>) -> Result<f64, AiScriptError> {
+    f64::try_from(value.unwrap_or_default())
+}
+
+fn binary(name: &str, op: fn(f64, f64) -> f64) -> (String, Value) {
+    (
+        format!("Bit:{name}"),
+        Value::fn_native(move |args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let a = expect_num(args.next())?;
+                let b = expect_num(args.next())?;
+                Ok(Value::num(op(a, b)))
+            }
+            .boxed()
+        }),
+    )
+}
+
+pub fn bit() -> HashMap<String, Value> {
+    let mut bit = HashMap::from([
+        binary("and", |a, b| (to_int32(a) & to_int32(b)).into()),
+        binary("or", |a, b| (to_int32(a) | to_int32(b)).into()),
+        binary("xor", |a, b| (to_int32(a) ^ to_int32(b)).into()),
+        binary("shl", |a, n| to_int32(a).wrapping_shl(to_uint32(n)).into()),
+        binary("shr", |a, n| to_int32(a).wrapping_shr(to_uint32(n)).into()),
+        binary("ushr", |a, n| {
+            to_uint32(a).wrapping_shr(to_uint32(n)).into()
+        }),
+    ]);
+
+    bit.insert(
+        "Bit:not".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let a = expect_num(args.into_iter().next())?;
+                Ok(Value::num(!to_int32(a)))
+            }
+            .boxed()
+        }),
+    );
+
+    bit
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    interpreter::{
+        value::{NativeObject, Value, V},
+        Interpreter,
+    },
+};
+
+/// A mutable box which lets namespaces, whose members are constants, keep state.
+///
+/// ```aiscript
+/// :: Counter {
+///     let count = Cell:create(0)
+///     @inc() { Cell:set(count, Cell:get(count) + 1) }
+/// }
+/// ```
+///
+/// The value is also accessible as the property `value`.
+#[derive(Debug)]
+struct Cell(RwLock<Value>);
+
+impl NativeObject for Cell {
+    fn type_name(&self) -> &str {
+        "cell"
+    }
+
+    fn get_prop<'a>(
+        &'a self,
+        name: &'a str,
+        _: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+        async move {
+            Ok(match name {
+                "value" => self.0.read().unwrap().clone(),
+                _ => Value::null(),
+            })
+        }
+        .boxed()
+    }
+
+    fn set_prop<'a>(
+        &'a self,
+        name: &'a str,
+        value: Value,
+        _: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<(), AiScriptError>> {
+        async move {
+            match name {
+                "value" => *self.0.write().unwrap() = value,
+                _ => Err(AiScriptRuntimeError::Runtime(format!(
+                    "Cannot set prop ({name}) of cell."
+                )))?,
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+fn expect_cell(value: Option<Value>) -> Result<Arc<dyn NativeObject>, AiScriptError> {
+    match value.unwrap_or_default().value {
+        V::NativeObject(object) if object.type_name() == "cell" => Ok(object),
+        value => Err(AiScriptRuntimeError::Type {
+            expected: "cell".to_string(),
+            got: value.display_type().to_string(),
+        })?,
+    }
+}
+
+pub fn cell() -> HashMap<String, Value> {
+    let mut cell = HashMap::new();
+
+    cell.insert(
+        "Cell:create".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let value = args.into_iter().next().unwrap_or_default();
+                Ok(Value::native_object(Cell(RwLock::new(value))))
+            }
+            .boxed()
+        }),
+    );
+
+    cell.insert(
+        "Cell:get".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let cell = expect_cell(args.into_iter().next())?;
+                cell.get_prop("value", &interpreter).await
+            }
+            .boxed()
+        }),
+    );
+
+    cell.insert(
+        "Cell:set".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let cell = expect_cell(args.next())?;
+                let value = args.next().unwrap_or_default();
+                cell.set_prop("value", value, &interpreter).await?;
+                Ok(Value::null())
+            }
+            .boxed()
+        }),
+    );
+
+    cell
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use futures::FutureExt;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    interpreter::value::{Value, V},
+};
+
+fn expect_bytes(value: Option<Value>) -> Result<Vec<u8>, AiScriptError> {
+    match value.unwrap_or_default().value {
+        V::Str(value) => Ok(value.into_bytes()),
+        V::Bin(value) => Ok(value),
+        value => Err(AiScriptRuntimeError::Type {
+            expected: "string or binary".to_string(),
+            got: value.display_type().to_string(),
+        })?,
+    }
+}
+
+fn digest<D: Digest>(args: Vec<Value>) -> Result<Value, AiScriptError> {
+    let data = expect_bytes(args.into_iter().next())?;
+    Ok(Value::str(hex::encode(D::digest(data))))
+}
+
+pub fn crypto() -> HashMap<String, Value> {
+    let mut crypto = HashMap::new();
+
+    crypto.insert(
+        "Crypto:sha256".to_string(),
+        Value::fn_native(|args, _| async move { digest::<Sha256>(args) }.boxed()),
+    );
+
+    crypto.insert(
+        "Crypto:sha1".to_string(),
+        Value::fn_native(|args, _| async move { digest::<Sha1>(args) }.boxed()),
+    );
+
+    crypto.insert(
+        "Crypto:md5".to_string(),
+        Value::fn_native(|args, _| async move { digest::<Md5>(args) }.boxed()),
+    );
+
+    crypto.insert(
+        "Crypto:hmac_sha256".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let key = expect_bytes(args.next())?;
+                let message = expect_bytes(args.next())?;
+                let mut mac = Hmac::<Sha256>::new_from_slice(&key)
+                    .map_err(|e| AiScriptError::Internal(e.to_string()))?;
+                mac.update(&message);
+                Ok(Value::str(hex::encode(mac.finalize().into_bytes())))
+            }
+            .boxed()
+        }),
+    );
+
+    crypto
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use chrono::{format::Item, FixedOffset, Offset};
+use futures::FutureExt;
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    interpreter::value::{Value, V},
+};
+
+/// A timezone given to `Date:format` and `Date:to_iso_str`.
+pub(super) enum Zone {
+    Local,
+    Fixed(FixedOffset),
+    /// A timezone of the IANA database, e.g. `Asia/Tokyo`.
+    #[cfg(feature = "tz")]
+    Named(chrono_tz::Tz),
+}
+
+impl Zone {
+    /// Expects the local timezone if omitted or null, an offset from UTC in minutes, or a name,
+    /// which is `UTC`, an offset like `+09:00`, or a name of the IANA database if the `tz`
+    /// feature is enabled.
+    pub(super) fn expect(value: Option<Value>) -> Result<Self, AiScriptError> {
+        match value.map(|value| value.value) {
+            None | Some(V::Null) => Ok(Zone::Local),
+            Some(V::Num(minutes)) => FixedOffset::east_opt((minutes * 60.0) as i32)
+                .map(Zone::Fixed)
+                .ok_or_else(|| {
+                    AiScriptRuntimeError::Runtime(format!("invalid timezone offset: {minutes}"))
+                        .into()
+                }),
+            Some(V::Str(name)) => {
+                if name == "UTC" || name == "Z" {
+                    return Ok(Zone::Fixed(FixedOffset::east_opt(0).unwrap()));
+                }
+                if let Ok(offset) = name.parse::<FixedOffset>() {
+                    return Ok(Zone::Fixed(offset));
+                }
+                #[cfg(feature = "tz")]
+                if let Ok(tz) = name.parse::<chrono_tz::Tz>() {
+                    return Ok(Zone::Named(tz));
+                }
+                Err(AiScriptRuntimeError::Runtime(format!(
+                    "unknown timezone: {name}"
+                )))?
+            }
+            Some(value) => Err(AiScriptRuntimeError::Type {
+                expected: "number or string".to_string(),
+                got: value.display_type().to_string(),
+            })?,
+        }
+    }
+
+    /// Returns the offset from UTC at a date.
+    pub(super) fn offset_at(&self, date: &chrono::DateTime<chrono::Utc>) -> FixedOffset {
+        match self {
+            Zone::Local => date.with_timezone(&chrono::Local).offset().fix(),
+            Zone::Fixed(offset) => *offset,
+            #[cfg(feature = "tz")]
+            Zone::Named(tz) => date.with_timezone(tz).offset().fix(),
+        }
+    }
+}
+
+/// Formats a date as `Date:format` does, checking that all the specifiers are known.
+fn format_date(
+    date: chrono::DateTime<FixedOffset>,
+    fmt: &str,
+    locale: Option<&str>,
+) -> Result<String, AiScriptError> {
+    let check = |items: &[Item]| {
+        if items.iter().any(|item| matches!(item, Item::Error)) {
+            Err(AiScriptRuntimeError::Runtime(format!(
+                "invalid date format: {fmt}"
+            )))?
+        }
+        Ok::<_, AiScriptError>(())
+    };
+    match locale {
+        #[cfg(feature = "locale")]
+        Some(name) => {
+            let locale = chrono::Locale::try_from(name.replace('-', "_").as_str())
+                .map_err(|_| AiScriptRuntimeError::Runtime(format!("unknown locale: {name}")))?;
+            let items =
+                chrono::format::StrftimeItems::new_with_locale(fmt, locale).collect::<Vec<_>>();
+            check(&items)?;
+            Ok(date
+                .format_localized_with_items(items.iter(), locale)
+                .to_string())
+        }
+        #[cfg(not(feature = "locale"))]
+        Some(name) => Err(AiScriptRuntimeError::Runtime(format!(
+            "unknown locale: {name}"
+        )))?,
+        None => {
+            let items = chrono::format::StrftimeItems::new(fmt).collect::<Vec<_>>();
+            check(&items)?;
+            Ok(date.format_with_items(items.iter()).to_string())
+        }
+    }
+}
+
+pub fn date() -> HashMap<String, Value> {
+    let mut date = HashMap::new();
+
+    // Date:format(date, fmt, tz?, locale?) formats a timestamp with the specifiers of strftime,
+    // e.g. `%Y-%m-%d %H:%M`. The names of months and weekdays are in the locale, e.g. `ja_JP`,
+    // if the `locale` feature is enabled.
+    date.insert(
+        "Date:format".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let now = interpreter.now_for("Date:format");
+            async move {
+                let mut args = args.into_iter();
+                let millis = match args.next() {
+                    None | Some(Value { value: V::Null, .. }) => now?,
+                    Some(millis) => f64::try_from(millis)?,
+                };
+                let fmt = String::try_from(args.next().unwrap_or_default())?;
+                let date = super::utc_date(millis)?;
+                let offset = Zone::expect(args.next())?.offset_at(&date);
+                let locale = match args.next().map(|locale| locale.value) {
+                    None | Some(V::Null) => None,
+                    Some(V::Str(locale)) => Some(locale),
+                    Some(value) => Err(AiScriptRuntimeError::Type {
+                        expected: "string".to_string(),
+                        got: value.display_type().to_string(),
+                    })?,
+                };
+                let date = date.with_timezone(&offset);
+                Ok(Value::str(format_date(date, &fmt, locale.as_deref())?))
+            }
+            .boxed()
+        }),
+    );
+
+    date
+}
@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use futures::{future::BoxFuture, FutureExt};
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    interpreter::{
+        value::{Value, V},
+        Interpreter,
+    },
+    values::{VFn, VObj},
+};
+
+/// Serializes a value with `indent` for each level of nesting, or in one line if it is empty.
+pub fn to_string(value: &V, indent: &str) -> serde_json::Result<String> {
+    if indent.is_empty() {
+        return serde_json::to_string(value);
+    }
+    let mut writer = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut writer, PrettyFormatter::with_indent(indent.as_bytes()));
+    value.serialize(&mut serializer)?;
+    Ok(String::from_utf8(writer).unwrap())
+}
+
+/// Converts the indent argument of `Json:stringify` like `JSON.stringify` does, which is at most
+/// 10 spaces or the first 10 characters of a string.
+pub fn expect_indent(value: Option<Value>) -> Result<String, AiScriptError> {
+    match value.map(|value| value.value) {
+        None | Some(V::Null) => Ok(String::new()),
+        Some(V::Num(width)) => Ok(" ".repeat(width.clamp(0.0, 10.0) as usize)),
+        Some(V::Str(indent)) => Ok(indent.chars().take(10).collect()),
+        Some(value) => Err(AiScriptRuntimeError::Type {
+            expected: "number or string".to_string(),
+            got: value.display_type().to_string(),
+        })?,
+    }
+}
+
+/// The options of `Json:stringify`.
+pub struct StringifyOptions {
+    /// Called with each key and value from the root like the replacer of `JSON.stringify`,
+    /// returning the value to serialize instead.
+    replacer: Option<VFn>,
+    /// The keys of objects to serialize, if limited.
+    keys: Option<Vec<String>>,
+    /// Whether NaN, infinities and functions make the result `not_json` instead of being
+    /// serialized as null and `"<function>"`.
+    strict: bool,
+}
+
+/// Converts the options argument of `Json:stringify`, which is an object with `replacer`,
+/// `keys` and `strict`.
+pub fn expect_stringify_options(
+    value: Option<Value>,
+) -> Result<Option<StringifyOptions>, AiScriptError> {
+    let Some(options) = value else {
+        return Ok(None);
+    };
+    let options = VObj::try_from(options)?;
+    let options = options.read().unwrap();
+    let replacer = options
+        .get("replacer")
+        .map(|replacer| VFn::try_from(replacer.clone()))
+        .transpose()?;
+    let keys = options
+        .get("keys")
+        .map(|keys| {
+            <Vec<Value>>::try_from(keys.clone())?
+                .into_iter()
+                .map(String::try_from)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+    let strict = options
+        .get("strict")
+        .map_or(Ok(false), |strict| bool::try_from(strict.clone()))?;
+    Ok(Some(StringifyOptions {
+        replacer,
+        keys,
+        strict,
+    }))
+}
+
+/// Copies a value for `Json:stringify` with the options applied, from the root with the key of
+/// an empty string like [`revive`]. Returns `None` if the value is not JSON in the strict mode.
+pub fn replace(
+    interpreter: Interpreter,
+    options: Arc<StringifyOptions>,
+    key: Value,
+    value: Value,
+    // The arrays and objects from the root, to detect cycles.
+    path: Vec<usize>,
+) -> BoxFuture<'static, Result<Option<Value>, AiScriptError>> {
+    async move {
+        let value = match &options.replacer {
+            Some(replacer) => {
+                interpreter
+                    .exec_fn_simple(replacer.clone(), [key, value])
+                    .await?
+            }
+            None => value,
+        };
+        // Adds an array or an object to the path, failing if it is already there.
+        let enter = |address: usize| {
+            if path.contains(&address) {
+                Err(AiScriptError::Internal("too much recursion".to_string()))?
+            }
+            Ok::<_, AiScriptError>([path.clone(), vec![address]].concat())
+        };
+        match &value.value {
+            V::Num(num) if !num.is_finite() => Ok((!options.strict).then(Value::null)),
+            V::Fn(_) if options.strict => Ok(None),
+            V::Arr(arr) => {
+                let path = enter(Arc::as_ptr(arr) as *const () as usize)?;
+                let items = arr.read().unwrap().clone();
+                let mut replaced = Vec::with_capacity(items.len());
+                for (i, item) in items.into_iter().enumerate() {
+                    let key = Value::num(i as f64);
+                    let path = path.clone();
+                    match replace(interpreter.clone(), options.clone(), key, item, path).await? {
+                        Some(item) => replaced.push(item),
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(Value::arr(replaced)))
+            }
+            V::Obj(obj) => {
+                let path = enter(Arc::as_ptr(obj) as *const () as usize)?;
+                let entries = obj.read().unwrap().clone();
+                let mut replaced = IndexMap::with_capacity(entries.len());
+                for (k, v) in entries {
+                    if options.keys.as_ref().is_some_and(|keys| !keys.contains(&k)) {
+                        continue;
+                    }
+                    let key = Value::str(&k);
+                    let path = path.clone();
+                    match replace(interpreter.clone(), options.clone(), key, v, path).await? {
+                        Some(v) => {
+                            replaced.insert(k, v);
+                        }
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(Value::obj(replaced)))
+            }
+            _ => Ok(Some(value)),
+        }
+    }
+    .boxed()
+}
+
+/// Removes comments and trailing commas from relaxed JSON so that it can be parsed as JSON.
+///
+/// The other syntax errors are left for the JSON parser.
+pub fn relax(json: &str) -> String {
+    let mut relaxed = String::with_capacity(json.len());
+    let mut chars = json.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                relaxed.push(c);
+                while let Some(c) = chars.next() {
+                    relaxed.push(c);
+                    match c {
+                        '\\' => relaxed.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = None;
+                for c in chars.by_ref() {
+                    if prev == Some('*') && c == '/' {
+                        break;
+                    }
+                    prev = Some(c);
+                }
+                relaxed.push(' ');
+            }
+            ']' | '}' => {
+                let content = relaxed.trim_end();
+                if content.ends_with(',') {
+                    relaxed.truncate(content.len() - 1);
+                }
+                relaxed.push(c);
+            }
+            c => relaxed.push(c),
+        }
+    }
+    relaxed
+}
+
+/// Replaces each value of a parsed JSON from the innermost with the result of
+/// `reviver(key, value)` like the reviver of `JSON.parse`. The key of an array element is its
+/// index and that of the root is an empty string.
+pub fn revive(
+    interpreter: Interpreter,
+    reviver: VFn,
+    key: Value,
+    value: Value,
+) -> BoxFuture<'static, Result<Value, AiScriptError>> {
+    async move {
+        match &value.value {
+            V::Arr(arr) => {
+                let items = arr.read().unwrap().clone();
+                for (i, item) in items.into_iter().enumerate() {
+                    let item = revive(
+                        interpreter.clone(),
+                        reviver.clone(),
+                        Value::num(i as f64),
+                        item,
+                    )
+                    .await?;
+                    interpreter.track_arr(arr, &item);
+                    arr.write().unwrap()[i] = item;
+                }
+            }
+            V::Obj(obj) => {
+                let entries = obj.read().unwrap().clone();
+                for (k, v) in entries {
+                    let v = revive(interpreter.clone(), reviver.clone(), Value::str(&k), v).await?;
+                    interpreter.track_obj(obj, &v);
+                    obj.write().unwrap().insert(k, v);
+                }
+            }
+            _ => {}
+        }
+        interpreter.exec_fn_simple(reviver, [key, value]).await
+    }
+    .boxed()
+}
@@ -0,0 +1,264 @@
+// https://tc39.es/ecma262/multipage/numbers-and-dates.html#sec-properties-of-the-number-prototype-object
+
+use regex::Regex;
+
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Formats a number in `radix` like `Number.prototype.toString` of V8.
+pub fn to_radix_string(value: f64, radix: u32) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    } else if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if radix == 10 {
+        return to_string(value);
+    }
+    let radix_f = radix as f64;
+    let negative = value < 0.0;
+    let value = value.abs();
+    let mut integer = value.floor();
+    let mut fraction = value - integer;
+    // The half distance to the next number, below which digits are not significant.
+    let mut delta = (0.5 * (f64::from_bits(value.to_bits() + 1) - value)).max(f64::from_bits(1));
+    let mut fraction_digits = Vec::new();
+    if fraction >= delta {
+        loop {
+            fraction *= radix_f;
+            delta *= radix_f;
+            let digit = fraction as usize;
+            fraction_digits.push(digit);
+            fraction -= digit as f64;
+            if (fraction > 0.5 || (fraction == 0.5 && digit & 1 == 1)) && fraction + delta > 1.0 {
+                // Round up, propagating the carry.
+                loop {
+                    match fraction_digits.pop() {
+                        None => {
+                            integer += 1.0;
+                            break;
+                        }
+                        Some(digit) if digit + 1 < radix as usize => {
+                            fraction_digits.push(digit + 1);
+                            break;
+                        }
+                        Some(_) => {}
+                    }
+                }
+                break;
+            }
+            if fraction < delta {
+                break;
+            }
+        }
+    }
+    let mut integer_digits = Vec::new();
+    // Digits beyond the precision of the integer part are zeros.
+    while integer / radix_f >= 2f64.powi(53) {
+        integer /= radix_f;
+        integer_digits.push(b'0');
+    }
+    loop {
+        let remainder = integer % radix_f;
+        integer_digits.push(DIGITS[remainder as usize]);
+        integer = (integer - remainder) / radix_f;
+        if integer <= 0.0 {
+            break;
+        }
+    }
+    let mut s = String::new();
+    if negative {
+        s.push('-');
+    }
+    s.extend(integer_digits.iter().rev().map(|&digit| digit as char));
+    if !fraction_digits.is_empty() {
+        s.push('.');
+        s.extend(fraction_digits.iter().map(|&digit| DIGITS[digit] as char));
+    }
+    s
+}
+
+/// Formats a finite number in the shortest form like `Number.prototype.toString` without radix.
+fn to_string(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    // The shortest digits which round-trip, e.g. `1.2345e3`.
+    let s = format!("{:e}", value.abs());
+    let (mantissa, exponent) = s.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as isize;
+    let n = exponent.parse::<isize>().unwrap() + 1;
+    let mut s = String::new();
+    if value < 0.0 {
+        s.push('-');
+    }
+    if k <= n && n <= 21 {
+        s.push_str(&digits);
+        s.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        s.push_str(&digits[..n as usize]);
+        s.push('.');
+        s.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        s.push_str("0.");
+        s.push_str(&"0".repeat(-n as usize));
+        s.push_str(&digits);
+    } else {
+        s.push_str(&digits[..1]);
+        if k > 1 {
+            s.push('.');
+            s.push_str(&digits[1..]);
+        }
+        s.push('e');
+        s.push(if n - 1 < 0 { '-' } else { '+' });
+        s.push_str(&(n - 1).abs().to_string());
+    }
+    s
+}
+
+/// Returns the exact decimal digits of a finite non-negative number and the position of the
+/// decimal point in them.
+fn exact_digits(value: f64) -> (Vec<u8>, usize) {
+    // 1074 digits are enough to represent the smallest subnormal number exactly.
+    let s = format!("{value:.1074}");
+    let (integer, fraction) = s.split_once('.').unwrap_or((&s, ""));
+    let digits = integer.bytes().chain(fraction.bytes()).collect();
+    (digits, integer.len())
+}
+
+/// Keeps the first `len` digits, rounding half up. Returns `true` if the carry overflowed, in
+/// which case the digits are prefixed with `1`.
+fn round_half_up(digits: &mut Vec<u8>, len: usize) -> bool {
+    let round_up = digits.get(len).is_some_and(|&digit| digit >= b'5');
+    digits.truncate(len);
+    digits.resize(len, b'0');
+    if round_up {
+        for digit in digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                return false;
+            }
+        }
+        digits.insert(0, b'1');
+        return true;
+    }
+    false
+}
+
+/// Formats a number with `fraction_digits` digits after the decimal point like
+/// `Number.prototype.toFixed`.
+pub fn to_fixed(value: f64, fraction_digits: usize) -> String {
+    if !value.is_finite() || value.abs() >= 1e21 {
+        return to_radix_string(value, 10);
+    }
+    let (mut digits, mut point) = exact_digits(value.abs());
+    if round_half_up(&mut digits, point + fraction_digits) {
+        point += 1;
+    }
+    let mut s = String::new();
+    if value < 0.0 {
+        s.push('-');
+    }
+    let (integer, fraction) = digits.split_at(point);
+    let integer = std::str::from_utf8(integer)
+        .unwrap()
+        .trim_start_matches('0');
+    s.push_str(if integer.is_empty() { "0" } else { integer });
+    if fraction_digits > 0 {
+        s.push('.');
+        s.push_str(std::str::from_utf8(fraction).unwrap());
+    }
+    s
+}
+
+/// Formats a number with `precision` significant digits like `Number.prototype.toPrecision`.
+pub fn to_precision(value: f64, precision: usize) -> String {
+    if !value.is_finite() {
+        return to_radix_string(value, 10);
+    }
+    let mut s = String::new();
+    if value < 0.0 {
+        s.push('-');
+    }
+    let (digits, exponent) = if value == 0.0 {
+        (vec![b'0'; precision], 0)
+    } else {
+        let (digits, point) = exact_digits(value.abs());
+        let leading_zeros = digits.iter().take_while(|&&digit| digit == b'0').count();
+        let mut digits = digits[leading_zeros..].to_vec();
+        let mut exponent = point as isize - leading_zeros as isize - 1;
+        if round_half_up(&mut digits, precision) {
+            digits.pop();
+            exponent += 1;
+        }
+        (digits, exponent)
+    };
+    let digits = String::from_utf8(digits).unwrap();
+    if exponent < -6 || exponent >= precision as isize {
+        s.push_str(&digits[..1]);
+        if precision > 1 {
+            s.push('.');
+            s.push_str(&digits[1..]);
+        }
+        s.push('e');
+        s.push(if exponent < 0 { '-' } else { '+' });
+        s.push_str(&exponent.abs().to_string());
+    } else if exponent >= 0 {
+        let point = exponent as usize + 1;
+        s.push_str(&digits[..point]);
+        if precision > point {
+            s.push('.');
+            s.push_str(&digits[point..]);
+        }
+    } else {
+        s.push_str("0.");
+        s.push_str(&"0".repeat((-exponent - 1) as usize));
+        s.push_str(&digits);
+    }
+    s
+}
+
+/// Parses the leading integer of a string like `parseInt`. `radix` of 0 means 10, or 16 if the
+/// string starts with `0x`.
+pub fn parse_int(s: &str, radix: u32) -> f64 {
+    let s = s.trim_start();
+    let (negative, s) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let has_hex_prefix = s.starts_with("0x") || s.starts_with("0X");
+    let (radix, s) = match radix {
+        0 if has_hex_prefix => (16, &s[2..]),
+        0 => (10, s),
+        16 if has_hex_prefix => (16, &s[2..]),
+        2..=36 => (radix, s),
+        _ => return f64::NAN,
+    };
+    let mut value: Option<f64> = None;
+    for c in s.chars() {
+        let Some(digit) = c.to_digit(radix) else {
+            break;
+        };
+        value = Some(value.unwrap_or(0.0) * radix as f64 + digit as f64);
+    }
+    match value {
+        Some(value) if negative => -value,
+        Some(value) => value,
+        None => f64::NAN,
+    }
+}
+
+/// Parses the leading decimal number of a string like `parseFloat`.
+pub fn parse_float(s: &str) -> f64 {
+    let re = Regex::new(r"^[+-]?(Infinity|([0-9]+\.?[0-9]*|\.[0-9]+)([eE][+-]?[0-9]+)?)").unwrap();
+    match re.find(s.trim_start()) {
+        Some(m) => {
+            let m = m.as_str();
+            m.replace("Infinity", "inf").parse().unwrap_or(f64::NAN)
+        }
+        None => f64::NAN,
+    }
+}
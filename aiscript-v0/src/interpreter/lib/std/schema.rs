@@ -0,0 +1,311 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::Arc,
+};
+
+use futures::FutureExt;
+use indexmap::IndexMap;
+use regex::Regex;
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    interpreter::{
+        value::{VObj, Value, V},
+        Interpreter,
+    },
+};
+
+/// The keys of a schema, which is an object like
+///
+/// ```aiscript
+/// {
+///     type: 'obj'
+///     props: {
+///         name: { type: 'str', min_len: 1 }
+///         age: { type: 'int', min: 0 }
+///         tags: { type: 'arr', items: { type: 'str' } }
+///     }
+///     required: ['name']
+/// }
+/// ```
+const SCHEMA_KEYS: [&str; 12] = [
+    "type",
+    "enum",
+    "min",
+    "max",
+    "min_len",
+    "max_len",
+    "pattern",
+    "items",
+    "props",
+    "required",
+    "additional",
+    "nullable",
+];
+
+fn invalid_schema(message: String) -> AiScriptError {
+    AiScriptRuntimeError::Runtime(format!("invalid schema: {message}")).into()
+}
+
+fn push_error(errors: &mut Vec<Value>, path: &[Value], message: String) {
+    errors.push(Value::obj([
+        ("path", Value::arr(path.to_vec())),
+        ("message", Value::str(message)),
+    ]));
+}
+
+/// The state shared by the whole validation of a value.
+#[derive(Default)]
+struct Visited {
+    /// The pairs of the addresses of a schema and an array or an object already checked against
+    /// it, so that cyclic schemas and values terminate.
+    pairs: HashSet<(usize, usize)>,
+    /// The compiled `pattern` of each schema, keyed by the address of the schema.
+    patterns: HashMap<usize, Regex>,
+}
+
+/// Checks `value` against `schema`, pushing an error object `{ path, message }` for each
+/// violation, where `path` is the array of the keys and the indexes from the root.
+fn validate(
+    interpreter: &Interpreter,
+    schema: &VObj,
+    value: &Value,
+    path: &mut Vec<Value>,
+    errors: &mut Vec<Value>,
+    visited: &mut Visited,
+) -> Result<(), AiScriptError> {
+    let schema_ptr = Arc::as_ptr(schema) as usize;
+    let value_ptr = match &value.value {
+        V::Arr(arr) => Some(Arc::as_ptr(arr) as usize),
+        V::Obj(obj) => Some(Arc::as_ptr(obj) as usize),
+        _ => None,
+    };
+    if let Some(value_ptr) = value_ptr {
+        if !visited.pairs.insert((schema_ptr, value_ptr)) {
+            return Ok(());
+        }
+    }
+    let schema = schema.read().unwrap().clone();
+    if let Some(key) = schema
+        .keys()
+        .find(|key| !SCHEMA_KEYS.contains(&key.as_str()))
+    {
+        Err(invalid_schema(format!("unknown key '{key}'")))?
+    }
+    let nullable = match schema.get("nullable") {
+        Some(nullable) => bool::try_from(nullable.clone())?,
+        None => false,
+    };
+    if nullable && matches!(value.value, V::Null) {
+        return Ok(());
+    }
+    let got = value.value.display_type().to_string();
+    if let Some(types) = schema.get("type") {
+        let types = match &types.value {
+            V::Str(type_) => vec![type_.clone()],
+            V::Arr(types) => types
+                .read()
+                .unwrap()
+                .iter()
+                .map(|type_| String::try_from(type_.clone()))
+                .collect::<Result<_, _>>()?,
+            _ => Err(invalid_schema(
+                "type must be a string or an array".to_string(),
+            ))?,
+        };
+        let matches = types.iter().any(|type_| match type_.as_str() {
+            "any" => true,
+            "int" => matches!(value.value, V::Num(num) if num.trunc() == num && num.is_finite()),
+            type_ => type_ == got,
+        });
+        if !matches {
+            push_error(
+                errors,
+                path,
+                format!("expected {}, but got {got}", types.join(" or ")),
+            );
+            return Ok(());
+        }
+    }
+    if let Some(allowed) = schema.get("enum") {
+        let allowed = <Vec<Value>>::try_from(allowed.clone())?;
+        if !allowed.iter().any(|item| interpreter.equals(item, value)) {
+            push_error(
+                errors,
+                path,
+                format!(
+                    "expected one of {}",
+                    allowed
+                        .iter()
+                        .map(|item| item.repr_value().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+    }
+    if let V::Num(num) = value.value {
+        if let Some(min) = schema.get("min") {
+            let min = f64::try_from(min.clone())?;
+            if num < min {
+                push_error(
+                    errors,
+                    path,
+                    format!("expected at least {min}, but got {num}"),
+                );
+            }
+        }
+        if let Some(max) = schema.get("max") {
+            let max = f64::try_from(max.clone())?;
+            if num > max {
+                push_error(
+                    errors,
+                    path,
+                    format!("expected at most {max}, but got {num}"),
+                );
+            }
+        }
+    }
+    let len = match &value.value {
+        V::Str(str) => Some(str.chars().count()),
+        V::Arr(arr) => Some(arr.read().unwrap().len()),
+        _ => None,
+    };
+    if let Some(len) = len {
+        if let Some(min_len) = schema.get("min_len") {
+            let min_len = f64::try_from(min_len.clone())?;
+            if (len as f64) < min_len {
+                push_error(
+                    errors,
+                    path,
+                    format!("expected length at least {min_len}, but got {len}"),
+                );
+            }
+        }
+        if let Some(max_len) = schema.get("max_len") {
+            let max_len = f64::try_from(max_len.clone())?;
+            if len as f64 > max_len {
+                push_error(
+                    errors,
+                    path,
+                    format!("expected length at most {max_len}, but got {len}"),
+                );
+            }
+        }
+    }
+    if let (V::Str(str), Some(pattern)) = (&value.value, schema.get("pattern")) {
+        let pattern = String::try_from(pattern.clone())?;
+        let regex = match visited.patterns.entry(schema_ptr) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(
+                Regex::new(&pattern)
+                    .map_err(|_| invalid_schema(format!("invalid pattern '{pattern}'")))?,
+            ),
+        };
+        if !regex.is_match(str) {
+            push_error(errors, path, format!("expected to match /{pattern}/"));
+        }
+    }
+    match &value.value {
+        V::Arr(arr) => {
+            if let Some(items) = schema.get("items") {
+                let items_schema = VObj::try_from(items.clone())?;
+                let items = arr.read().unwrap().clone();
+                for (i, item) in items.iter().enumerate() {
+                    path.push(Value::num(i as f64));
+                    validate(interpreter, &items_schema, item, path, errors, visited)?;
+                    path.pop();
+                }
+            }
+        }
+        V::Obj(obj) => {
+            let entries = obj.read().unwrap().clone();
+            if let Some(required) = schema.get("required") {
+                for key in <Vec<Value>>::try_from(required.clone())? {
+                    let key = String::try_from(key)?;
+                    if !entries.contains_key(&key) {
+                        push_error(errors, path, format!("missing required key '{key}'"));
+                    }
+                }
+            }
+            let props = match schema.get("props") {
+                Some(props) => VObj::try_from(props.clone())?.read().unwrap().clone(),
+                None => IndexMap::new(),
+            };
+            let additional = match schema.get("additional") {
+                Some(additional) => bool::try_from(additional.clone())?,
+                None => true,
+            };
+            for (key, value) in entries.iter() {
+                match props.get(key) {
+                    Some(prop) => {
+                        let prop = VObj::try_from(prop.clone())?;
+                        path.push(Value::str(key));
+                        validate(interpreter, &prop, value, path, errors, visited)?;
+                        path.pop();
+                    }
+                    None if !additional => {
+                        push_error(errors, path, format!("unexpected key '{key}'"))
+                    }
+                    None => (),
+                }
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+pub fn schema() -> HashMap<String, Value> {
+    let mut schema = HashMap::new();
+
+    // Schema:validate(schema, value) returns the errors of `value` against `schema`, which is
+    // empty if the value is valid.
+    schema.insert(
+        "Schema:validate".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let schema = VObj::try_from(args.next().unwrap_or_default())?;
+                let value = args.next().unwrap_or_default();
+                let mut errors = Vec::new();
+                validate(
+                    &interpreter,
+                    &schema,
+                    &value,
+                    &mut Vec::new(),
+                    &mut errors,
+                    &mut Visited::default(),
+                )?;
+                Ok(Value::arr(errors))
+            }
+            .boxed()
+        }),
+    );
+
+    schema.insert(
+        "Schema:is_valid".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let schema = VObj::try_from(args.next().unwrap_or_default())?;
+                let value = args.next().unwrap_or_default();
+                let mut errors = Vec::new();
+                validate(
+                    &interpreter,
+                    &schema,
+                    &value,
+                    &mut Vec::new(),
+                    &mut errors,
+                    &mut Visited::default(),
+                )?;
+                Ok(Value::bool(errors.is_empty()))
+            }
+            .boxed()
+        }),
+    );
+
+    schema
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use futures::FutureExt;
+
+use crate::{
+    error::AiScriptError,
+    interpreter::{util::expect_any, value::Value},
+};
+
+use super::check_integers_in;
+
+/// Converts a TOML value, in which a date-time becomes its string representation.
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(value) => Value::str(value),
+        toml::Value::Integer(value) => Value::num(value as f64),
+        toml::Value::Float(value) => Value::num(value),
+        toml::Value::Boolean(value) => Value::bool(value),
+        toml::Value::Datetime(value) => Value::str(value.to_string()),
+        toml::Value::Array(value) => Value::arr(value.into_iter().map(from_toml)),
+        toml::Value::Table(value) => Value::obj(
+            value
+                .into_iter()
+                .map(|(key, value)| (key, from_toml(value))),
+        ),
+    }
+}
+
+pub fn toml() -> HashMap<String, Value> {
+    let mut toml = HashMap::new();
+
+    toml.insert(
+        "Toml:stringify".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = expect_any(args.next())?;
+                toml::to_string(&v.value).map_or_else(
+                    |err| {
+                        if err.to_string() == "cyclic_reference" {
+                            Err(AiScriptError::Internal("too much recursion".to_string()))
+                        } else {
+                            Ok(Value::error("not_toml", None))
+                        }
+                    },
+                    |value| Ok(Value::str(value)),
+                )
+            }
+            .boxed()
+        }),
+    );
+
+    toml.insert(
+        "Toml:parse".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let toml = String::try_from(args.next().unwrap_or_default())?;
+                let value = toml::from_str::<toml::Table>(&toml).map_or_else(
+                    |_| Value::error("not_toml", None),
+                    |table| from_toml(toml::Value::Table(table)),
+                );
+                check_integers_in(&interpreter, &value.value)?;
+                Ok(value)
+            }
+            .boxed()
+        }),
+    );
+
+    toml.insert(
+        "Toml:parsable".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let toml = String::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::bool(toml::from_str::<toml::Table>(&toml).is_ok()))
+            }
+            .boxed()
+        }),
+    );
+
+    toml
+}
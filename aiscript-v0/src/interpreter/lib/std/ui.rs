@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use futures::FutureExt;
+use indexmap::IndexMap;
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    interpreter::{
+        input::{InputKind, InputRequest},
+        value::{Value, V},
+    },
+};
+
+fn expect_prompt(value: Option<Value>) -> Result<String, AiScriptError> {
+    String::try_from(value.unwrap_or_default())
+}
+
+/// Returns the default answer unless it is null or omitted.
+fn default_answer(value: Option<Value>) -> Option<Value> {
+    value.filter(|value| !matches!(value.value, V::Null))
+}
+
+pub fn ui() -> HashMap<String, Value> {
+    let mut ui = HashMap::new();
+
+    ui.insert(
+        "Ui:input".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let prompt = expect_prompt(args.next())?;
+                let options = match args.next() {
+                    Some(options) => <IndexMap<String, Value>>::try_from(options)?,
+                    None => IndexMap::new(),
+                };
+                let kind = match options.get("type").map(|type_| &type_.value) {
+                    None | Some(V::Null) => InputKind::Text,
+                    Some(V::Str(type_)) if type_ == "text" => InputKind::Text,
+                    Some(V::Str(type_)) if type_ == "number" => InputKind::Number,
+                    Some(type_) => Err(AiScriptRuntimeError::Runtime(format!(
+                        "Ui:input expected type 'text' or 'number', got {}",
+                        type_.repr_value()
+                    )))?,
+                };
+                let default = default_answer(options.get("default").cloned());
+                if let Some(default) = &default {
+                    match (kind, &default.value) {
+                        (InputKind::Text, V::Str(_)) | (InputKind::Number, V::Num(_)) => (),
+                        (_, value) => Err(AiScriptRuntimeError::Type {
+                            expected: if kind == InputKind::Text {
+                                "string"
+                            } else {
+                                "number"
+                            }
+                            .to_string(),
+                            got: value.display_type().to_string(),
+                        })?,
+                    }
+                }
+                let request = InputRequest {
+                    prompt,
+                    kind,
+                    default,
+                    choices: Vec::new(),
+                };
+                Ok(interpreter.request_input(request).await.unwrap_or_default())
+            }
+            .boxed()
+        }),
+    );
+
+    ui.insert(
+        "Ui:confirm".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let prompt = expect_prompt(args.next())?;
+                let default = default_answer(args.next())
+                    .map(|default| bool::try_from(default).map(Value::bool))
+                    .transpose()?;
+                let request = InputRequest {
+                    prompt,
+                    kind: InputKind::Confirm,
+                    default,
+                    choices: Vec::new(),
+                };
+                Ok(interpreter.request_input(request).await.unwrap_or_default())
+            }
+            .boxed()
+        }),
+    );
+
+    ui.insert(
+        "Ui:select".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let prompt = expect_prompt(args.next())?;
+                let choices = <Vec<Value>>::try_from(args.next().unwrap_or_default())?
+                    .into_iter()
+                    .map(String::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let default = default_answer(args.next())
+                    .map(String::try_from)
+                    .transpose()?;
+                if let Some(default) = default.as_ref().filter(|d| !choices.contains(d)) {
+                    Err(AiScriptRuntimeError::Runtime(format!(
+                        "Ui:select expected the default to be one of the choices, got \"{default}\""
+                    )))?
+                }
+                let request = InputRequest {
+                    prompt,
+                    kind: InputKind::Select,
+                    default: default.map(Value::str),
+                    choices,
+                };
+                Ok(interpreter.request_input(request).await.unwrap_or_default())
+            }
+            .boxed()
+        }),
+    );
+
+    ui
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use futures::FutureExt;
+
+use crate::{
+    error::AiScriptError,
+    interpreter::{
+        util::expect_any,
+        value::{Value, V},
+    },
+};
+
+use super::check_integers_in;
+
+pub fn yaml() -> HashMap<String, Value> {
+    let mut yaml = HashMap::new();
+
+    yaml.insert(
+        "Yaml:stringify".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let v = expect_any(args.next())?;
+                serde_yaml::to_string(&v.value).map_or_else(
+                    |err| {
+                        if err.to_string() == "cyclic_reference" {
+                            Err(AiScriptError::Internal("too much recursion".to_string()))
+                        } else {
+                            Ok(Value::error("not_yaml", None))
+                        }
+                    },
+                    |value| Ok(Value::str(value)),
+                )
+            }
+            .boxed()
+        }),
+    );
+
+    yaml.insert(
+        "Yaml:parse".to_string(),
+        Value::fn_native(|args, interpreter| {
+            let interpreter = interpreter.clone();
+            async move {
+                let mut args = args.into_iter();
+                let yaml = String::try_from(args.next().unwrap_or_default())?;
+                let value = serde_yaml::from_str(&yaml)
+                    .map_or_else(|_| Value::error("not_yaml", None), Value::new);
+                check_integers_in(&interpreter, &value.value)?;
+                Ok(value)
+            }
+            .boxed()
+        }),
+    );
+
+    yaml.insert(
+        "Yaml:parsable".to_string(),
+        Value::fn_native(|args, _| {
+            async move {
+                let mut args = args.into_iter();
+                let yaml = String::try_from(args.next().unwrap_or_default())?;
+                Ok(Value::bool(serde_yaml::from_str::<V>(&yaml).is_ok()))
+            }
+            .boxed()
+        }),
+    );
+
+    yaml
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// Counters of the executions of an interpreter, retrieved via
+/// [`Interpreter::metrics`](super::Interpreter::metrics).
+///
+/// The counters accumulate over all executions of the interpreter and its clones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of evaluated nodes, which is limited by `max_step`.
+    pub steps: usize,
+
+    /// The number of calls of functions defined in scripts.
+    pub fn_calls: usize,
+
+    /// The number of calls of native functions, including the standard library.
+    pub native_calls: usize,
+
+    /// The number of calls of native functions by namespace, e.g. `Core` for `Core:add`, which
+    /// includes operators.
+    pub namespace_calls: BTreeMap<String, usize>,
+
+    /// The total time spent in native functions by the name they are called with, e.g.
+    /// `Http:get`, including the time of the script functions they call back.
+    ///
+    /// This is recorded only if enabled by
+    /// [`Interpreter::with_native_timing`](super::Interpreter::with_native_timing).
+    pub native_time: BTreeMap<String, Duration>,
+
+    /// The number of arrays, objects and strings created by expressions.
+    pub allocations: usize,
+
+    /// An estimate of the bytes of arrays, objects and strings created by expressions.
+    ///
+    /// Values are not tracked after they are created, so this is an upper bound of the peak
+    /// memory used by values.
+    pub allocated_bytes: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct MetricsState {
+    fn_calls: AtomicUsize,
+    native_calls: AtomicUsize,
+    namespace_calls: Mutex<BTreeMap<String, usize>>,
+    native_time: Mutex<BTreeMap<String, Duration>>,
+    allocations: AtomicUsize,
+    allocated_bytes: AtomicUsize,
+}
+
+impl MetricsState {
+    pub fn count_call(&self, native: bool) {
+        if native {
+            self.native_calls.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.fn_calls.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn count_namespace_call(&self, name: &str) {
+        if let Some((namespace, _)) = name.rsplit_once(':') {
+            *self
+                .namespace_calls
+                .lock()
+                .unwrap()
+                .entry(namespace.to_string())
+                .or_default() += 1;
+        }
+    }
+
+    pub fn add_native_time(&self, name: &str, time: Duration) {
+        let mut native_time = self.native_time.lock().unwrap();
+        match native_time.get_mut(name) {
+            Some(total) => *total += time,
+            None => {
+                native_time.insert(name.to_string(), time);
+            }
+        }
+    }
+
+    pub fn count_allocation(&self, bytes: usize) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.allocated_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, steps: usize) -> Metrics {
+        Metrics {
+            steps,
+            fn_calls: self.fn_calls.load(Ordering::Relaxed),
+            native_calls: self.native_calls.load(Ordering::Relaxed),
+            namespace_calls: self.namespace_calls.lock().unwrap().clone(),
+            native_time: self.native_time.lock().unwrap().clone(),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
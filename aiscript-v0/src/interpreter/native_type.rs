@@ -0,0 +1,93 @@
+//! Types of native functions declared via
+//! [`Interpreter::with_native_type`](super::Interpreter::with_native_type), whose arguments are
+//! checked before the functions are called.
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{error::AiScriptRuntimeError, node as ast, r#type::matches};
+
+use super::{
+    permission::address,
+    scope::Scope,
+    value::{VFn, VFnNative, Value, V},
+};
+
+/// The declared types of native functions, held by an interpreter.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NativeTypes {
+    types: HashMap<String, Arc<ast::FnTypeSource>>,
+    /// The declared types by the addresses of the functions, so that the arguments are checked
+    /// under any name the function is passed as.
+    bound: HashMap<usize, Arc<ast::FnTypeSource>>,
+}
+
+impl NativeTypes {
+    pub(crate) fn declare(&mut self, name: String, type_: ast::FnTypeSource) {
+        self.types.insert(name, Arc::new(type_));
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&ast::FnTypeSource> {
+        self.types.get(name).map(Arc::as_ref)
+    }
+
+    /// Finds the functions with declared types among the variables of `scope`.
+    pub(crate) fn bind(&mut self, scope: &Scope) {
+        self.bound = self
+            .types
+            .iter()
+            .filter_map(|(name, type_)| {
+                let value = scope.get(name).ok()?;
+                let V::Fn(fn_) = value.value else {
+                    return None;
+                };
+                let VFn::FnNative(fn_) = *fn_ else {
+                    return None;
+                };
+                Some((address(&fn_), type_.clone()))
+            })
+            .collect();
+    }
+
+    /// Checks the arguments of `fn_` against its declared type, if any.
+    pub(crate) fn check(
+        &self,
+        fn_: &VFnNative,
+        args: &[Value],
+    ) -> Result<(), AiScriptRuntimeError> {
+        if self.bound.is_empty() {
+            return Ok(());
+        }
+        match self.bound.get(&address(fn_)) {
+            Some(type_) => check_args(type_, args),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the arguments of the method `name` of native objects, e.g. `row.save`, against its
+    /// declared type, if any.
+    pub(crate) fn check_method(
+        &self,
+        name: &str,
+        args: &[Value],
+    ) -> Result<(), AiScriptRuntimeError> {
+        match self.types.get(name) {
+            Some(type_) => check_args(type_, args),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Checks the arguments against a declared type. A missing argument is null.
+fn check_args(type_: &ast::FnTypeSource, args: &[Value]) -> Result<(), AiScriptRuntimeError> {
+    let null = Value::null();
+    for (i, param) in type_.args.iter().enumerate() {
+        let arg = args.get(i).unwrap_or(&null);
+        if !matches(param, arg) {
+            return Err(AiScriptRuntimeError::Type {
+                expected: param.to_string(),
+                got: arg.value.display_type().to_string(),
+            });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Capabilities which functions need to access the outside of the interpreter, and the
+//! capabilities allowed to scripts via [`Interpreter::with_permissions`](super::Interpreter::with_permissions).
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use super::{
+    scope::Scope,
+    value::{VFn, VFnNative, V},
+};
+
+/// A kind of access to the outside of the interpreter, which a function is tagged with.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Capability {
+    /// Network access, e.g. `Http:get`.
+    Net,
+    /// File system access, e.g. `Fs:read_text`.
+    Fs,
+    /// The current time and timers, e.g. `Date:now` and `Core:sleep`.
+    Time,
+    /// Random numbers which cannot be reproduced, e.g. `Math:rnd` and `Util:uuid`.
+    Random,
+}
+
+impl Capability {
+    /// Returns the name of the capability, e.g. `net`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Net => "net",
+            Capability::Fs => "fs",
+            Capability::Time => "time",
+            Capability::Random => "random",
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.name().fmt(f)
+    }
+}
+
+/// Returns the capabilities of the functions of the standard library, `FsCapability` and
+/// `HttpPolicy`.
+///
+/// `Date:year` and the like read the current time only when called without a timestamp, and
+/// are not tagged but denied only then, while `Math:gen_rng` is not tagged since its numbers are
+/// reproduced from the seed.
+fn default_tags() -> HashMap<String, Capability> {
+    [
+        ("Date:now", Capability::Time),
+        ("Core:sleep", Capability::Time),
+        ("Async:timeout", Capability::Time),
+        ("Async:interval", Capability::Time),
+        ("Math:rnd", Capability::Random),
+        ("Util:uuid", Capability::Random),
+        ("Fs:read_text", Capability::Fs),
+        ("Fs:write_text", Capability::Fs),
+        ("Fs:list_dir", Capability::Fs),
+        ("Http:get", Capability::Net),
+        ("Http:post", Capability::Net),
+    ]
+    .into_iter()
+    .map(|(name, capability)| (name.to_string(), capability))
+    .collect()
+}
+
+/// The capabilities of the functions and those allowed, held by an interpreter.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Permissions {
+    tags: HashMap<String, Capability>,
+    /// The allowed capabilities, or `None` if everything is allowed.
+    allow: Option<HashSet<Capability>>,
+    /// The names and the capabilities of the denied native functions, by their addresses, so
+    /// that a function is denied under any name it is passed as.
+    denied: HashMap<usize, (String, Capability)>,
+}
+
+impl Permissions {
+    pub(crate) fn new() -> Self {
+        Permissions {
+            tags: default_tags(),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn tag(&mut self, name: String, capability: Capability) {
+        self.tags.insert(name, capability);
+    }
+
+    pub(crate) fn allow(&mut self, allow: HashSet<Capability>) {
+        self.allow = Some(allow);
+    }
+
+    /// Finds the denied functions among the variables of `scope`.
+    pub(crate) fn bind(&mut self, scope: &Scope) {
+        let Some(allow) = &self.allow else {
+            self.denied.clear();
+            return;
+        };
+        self.denied = self
+            .tags
+            .iter()
+            .filter(|(_, capability)| !allow.contains(capability))
+            .filter_map(|(name, capability)| {
+                let value = scope.get(name).ok()?;
+                let V::Fn(fn_) = value.value else {
+                    return None;
+                };
+                let VFn::FnNative(fn_) = *fn_ else {
+                    return None;
+                };
+                Some((address(&fn_), (name.clone(), *capability)))
+            })
+            .collect();
+    }
+
+    /// Returns `true` if `capability` is allowed.
+    pub(crate) fn allows(&self, capability: Capability) -> bool {
+        self.allow
+            .as_ref()
+            .is_none_or(|allow| allow.contains(&capability))
+    }
+
+    /// Returns the name and the capability of `fn_` if it is denied.
+    pub(crate) fn denied(&self, fn_: &VFnNative) -> Option<&(String, Capability)> {
+        if self.denied.is_empty() {
+            return None;
+        }
+        self.denied.get(&address(fn_))
+    }
+
+    /// Returns the capability of the method `name` of native objects, e.g. `row.save`, if it is
+    /// denied.
+    pub(crate) fn denied_method(&self, name: &str) -> Option<Capability> {
+        let allow = self.allow.as_ref()?;
+        self.tags
+            .get(name)
+            .filter(|capability| !allow.contains(capability))
+            .copied()
+    }
+}
+
+pub(crate) fn address(fn_: &VFnNative) -> usize {
+    Arc::as_ptr(fn_) as *const () as usize
+}
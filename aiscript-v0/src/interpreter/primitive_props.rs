@@ -1,24 +1,125 @@
+use std::collections::HashMap;
+
 use futures::{
     future::{try_join_all, BoxFuture},
-    try_join, FutureExt,
+    FutureExt,
 };
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
     error::{AiScriptError, AiScriptRuntimeError},
-    Interpreter,
+    CompatProfile, Interpreter,
 };
 
 use super::{
-    util::expect_any,
-    value::{VFn, Value, V},
+    lib::std::number_format::to_radix_string,
+    util::{expect_any, sort_by_keys},
+    value::{PrimPropFn, PrimType, VFn, Value, V},
 };
 
-pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError> {
-    Ok(match *target.value {
+/// Methods added to primitive values by the host via [`Interpreter::with_prim_prop`].
+#[derive(Clone, Default)]
+pub(crate) struct PrimProps(HashMap<(PrimType, String), PrimPropFn>);
+
+impl PrimProps {
+    /// Adds a method, replacing the one of the same type and name if any. Fails if the type has a
+    /// built-in prop of the name.
+    pub(crate) fn insert(
+        &mut self,
+        type_: PrimType,
+        name: String,
+        method: PrimPropFn,
+    ) -> Result<(), AiScriptError> {
+        let sample = match type_ {
+            PrimType::Num => Some(Value::num(0)),
+            PrimType::Str => Some(Value::str("")),
+            PrimType::Arr => Some(Value::arr([])),
+            // objects have no built-in props
+            PrimType::Obj => None,
+            PrimType::Error => Some(Value::error("", None)),
+        };
+        if let Some(sample) = sample {
+            let type_name = sample.display_type().to_string();
+            if get_builtin_prim_prop(sample, name.clone()).is_ok() {
+                Err(AiScriptError::Internal(format!(
+                    "Cannot override the built-in prop ({name}) of {type_name}."
+                )))?
+            }
+        }
+        self.0.insert((type_, name), method);
+        Ok(())
+    }
+
+    /// Returns the method `name` added to the type of `target`, bound to `target`.
+    pub(crate) fn get(&self, target: &Value, name: &str) -> Option<Value> {
+        let type_ = match target.value {
+            V::Num(_) => PrimType::Num,
+            V::Str(_) => PrimType::Str,
+            V::Arr(_) => PrimType::Arr,
+            V::Obj(_) => PrimType::Obj,
+            V::Error { .. } => PrimType::Error,
+            _ => return None,
+        };
+        let method = self.0.get(&(type_, name.to_string()))?.clone();
+        let target = target.clone();
+        Some(Value::fn_native(move |args, interpreter| {
+            method(target.clone(), args, interpreter)
+        }))
+    }
+}
+
+/// Returns the prop `name` of a value other than objects, looking up the built-in props first and
+/// then the ones added by the host. Also returns whether the prop is a built-in one.
+pub fn get_prim_prop(
+    target: Value,
+    name: String,
+    props: &PrimProps,
+) -> Result<(Value, bool), AiScriptError> {
+    match get_builtin_prim_prop(target.clone(), name.clone()) {
+        Ok(value) => Ok((value, true)),
+        Err(e) => props
+            .get(&target, &name)
+            .map(|value| (value, false))
+            .ok_or(e),
+    }
+}
+
+/// Methods of arrays which mutate the array, and thus fail on a frozen array.
+const ARR_MUTATING_METHODS: [&str; 11] = [
+    "push", "unshift", "pop", "shift", "reverse", "sort", "sort_by", "fill", "splice", "insert",
+    "remove",
+];
+
+fn get_builtin_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError> {
+    if let (V::Arr(_), Err(e)) = (&target.value, target.expect_mutable()) {
+        if ARR_MUTATING_METHODS.contains(&name.as_str()) {
+            return Ok(Value::fn_native(move |_, _| {
+                let e = e.clone();
+                async move { Err(e) }.boxed()
+            }));
+        }
+    }
+    Ok(match target.value {
         V::Num(target) => match name.as_str() {
-            "to_str" => Value::fn_native(move |_, _| {
-                async move { Ok(Value::str(target.to_string())) }.boxed()
+            "to_str" => Value::fn_native(move |args, interpreter| {
+                let js_numbers = interpreter.compat_profile == CompatProfile::Upstream;
+                async move {
+                    let Some(radix) = args.into_iter().next() else {
+                        return Ok(Value::str(if js_numbers {
+                            to_radix_string(target, 10)
+                        } else {
+                            target.to_string()
+                        }));
+                    };
+                    let radix = f64::try_from(radix)?;
+                    if radix.trunc() != radix || !(2.0..=36.0).contains(&radix) {
+                        Err(AiScriptRuntimeError::Runtime(format!(
+                            "to_str expected radix between 2 and 36, got {radix}"
+                        )))?
+                    }
+                    Ok(Value::str(to_radix_string(target, radix as u32)))
+                }
+                .boxed()
             }),
             _ => Err(AiScriptRuntimeError::Runtime(format!(
                 "No such prop ({name}) in number."
@@ -297,8 +398,9 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 }
                 .boxed()
             }),
-            "pad_start" => Value::fn_native(move |args, _| {
+            "pad_start" => Value::fn_native(move |args, interpreter| {
                 let target = target.clone();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let width = f64::try_from(args.next().unwrap_or_default())?;
@@ -313,6 +415,8 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                         target
                     } else {
                         let width = width as usize - target_len;
+                        // Each grapheme of the padding has at least one byte.
+                        interpreter.check_str_len(target.len().saturating_add(width))?;
                         let mut s = pad.repeat(width / pad_len);
                         s += &pad[..pad.grapheme_indices(true).nth(width % pad_len).unwrap().0];
                         s += &target;
@@ -321,8 +425,9 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 }
                 .boxed()
             }),
-            "pad_end" => Value::fn_native(move |args, _| {
+            "pad_end" => Value::fn_native(move |args, interpreter| {
                 let target = target.clone();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let width = f64::try_from(args.next().unwrap_or_default())?;
@@ -337,6 +442,8 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                         target
                     } else {
                         let width = width as usize - target_len;
+                        // Each grapheme of the padding has at least one byte.
+                        interpreter.check_str_len(target.len().saturating_add(width))?;
                         let mut s = target;
                         s += &pad.repeat(width / pad_len);
                         s += &pad[..pad.grapheme_indices(true).nth(width % pad_len).unwrap().0];
@@ -351,21 +458,27 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
         },
         V::Arr(target) => match name.as_str() {
             "len" => Value::num(target.read().unwrap().len() as f64),
-            "push" => Value::fn_native(move |args, _| {
+            "push" => Value::fn_native(move |args, interpreter| {
                 let target = target.clone();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let val = expect_any(args.next())?;
+                    interpreter.check_arr_len(target.read().unwrap().len() + 1)?;
+                    interpreter.track_arr(&target, &val);
                     target.write().unwrap().push(val);
                     Ok(Value::new(V::Arr(target)))
                 }
                 .boxed()
             }),
-            "unshift" => Value::fn_native(move |args, _| {
+            "unshift" => Value::fn_native(move |args, interpreter| {
                 let target = target.clone();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let val = expect_any(args.next())?;
+                    interpreter.check_arr_len(target.read().unwrap().len() + 1)?;
+                    interpreter.track_arr(&target, &val);
                     target.write().unwrap().insert(0, val);
                     Ok(Value::new(V::Arr(target)))
                 }
@@ -428,7 +541,7 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 .boxed()
             }),
             "join" => Value::fn_native(move |args, _| {
-                let target = target.read().unwrap().clone();
+                let target = target.clone();
                 async move {
                     let mut args = args.into_iter();
                     let joiner = args
@@ -436,19 +549,25 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                         .map(String::try_from)
                         .map_or(Ok(None), |r| r.map(Some))?
                         .unwrap_or_else(String::new);
-                    Ok(Value::str(
-                        target
-                            .iter()
-                            .map(|i| {
-                                if let V::Str(value) = &*i.value {
-                                    value
-                                } else {
-                                    ""
-                                }
-                            })
-                            .collect::<Vec<&str>>()
-                            .join(&joiner),
-                    ))
+                    let target = target.read().unwrap();
+                    let items = target.iter().map(|i| {
+                        if let V::Str(value) = &i.value {
+                            value.as_str()
+                        } else {
+                            ""
+                        }
+                    });
+                    let mut str = String::with_capacity(
+                        items.clone().map(str::len).sum::<usize>()
+                            + joiner.len() * target.len().saturating_sub(1),
+                    );
+                    for (i, item) in items.enumerate() {
+                        if i > 0 {
+                            str.push_str(&joiner);
+                        }
+                        str.push_str(item);
+                    }
+                    Ok(Value::str(str))
                 }
                 .boxed()
             }),
@@ -537,17 +656,21 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 }
                 .boxed()
             }),
-            "incl" => Value::fn_native(move |args, _| {
+            "incl" => Value::fn_native(move |args, interpreter| {
                 let target = target.read().unwrap().clone();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let val = expect_any(args.next())?;
-                    Ok(Value::bool(target.into_iter().any(|item| val == item)))
+                    Ok(Value::bool(
+                        target.iter().any(|item| interpreter.equals(&val, item)),
+                    ))
                 }
                 .boxed()
             }),
-            "index_of" => Value::fn_native(move |args, _| {
+            "index_of" => Value::fn_native(move |args, interpreter| {
                 let target = target.read().unwrap().clone();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let val = expect_any(args.next())?;
@@ -560,7 +683,7 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                     Ok(Value::num(
                         target[from_i..]
                             .iter()
-                            .position(|item| item == &val)
+                            .position(|item| interpreter.equals(&val, item))
                             .map_or(-1.0, |result| (result + from_i) as f64),
                     ))
                 }
@@ -575,6 +698,8 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 async move { Ok(Value::arr(target)) }.boxed()
             }),
             "sort" => Value::fn_native({
+                // A stable merge sort which calls the comparator one by one in a deterministic
+                // order, since it may be an AiScript function with side effects.
                 fn merge_sort(
                     arr: Vec<Value>,
                     comp: VFn,
@@ -589,10 +714,8 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                     let right = left.split_off(mid);
                     let interpreter = interpreter.clone();
                     async move {
-                        let (left, right) = try_join!(
-                            merge_sort(left, comp.clone(), &interpreter),
-                            merge_sort(right, comp.clone(), &interpreter)
-                        )?;
+                        let left = merge_sort(left, comp.clone(), &interpreter).await?;
+                        let right = merge_sort(right, comp.clone(), &interpreter).await?;
                         merge(left, right, comp, &interpreter).await
                     }
                     .boxed()
@@ -613,7 +736,8 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                             .exec_fn_simple(comp.clone(), vec![l.clone(), r.clone()])
                             .await?;
                         let comp_value = f64::try_from(comp_value)?;
-                        if comp_value < 0.0 {
+                        // Equal items keep their order.
+                        if comp_value <= 0.0 {
                             result.push(l);
                             left_index += 1;
                         } else {
@@ -640,12 +764,37 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                     .boxed()
                 }
             }),
-            "fill" => Value::fn_native(move |args, _| {
+            // Sorts by the keys returned by the function for each item, which is called once per
+            // item, unlike the comparator of `sort`.
+            "sort_by" => Value::fn_native(move |args, interpreter| {
+                let interpreter = interpreter.clone();
+                let target = target.clone();
+                async move {
+                    let mut args = args.into_iter();
+                    let fn_ = VFn::try_from(args.next().unwrap_or_default())?;
+                    let arr = target.read().unwrap().clone();
+                    let mut keys = Vec::with_capacity(arr.len());
+                    for item in arr.iter() {
+                        keys.push(
+                            interpreter
+                                .exec_fn_simple(fn_.clone(), [item.clone()])
+                                .await?,
+                        );
+                    }
+                    let sorted = sort_by_keys(arr, &[keys], &[])?;
+                    target.write().unwrap().splice(.., sorted);
+                    Ok(Value::new(V::Arr(target)))
+                }
+                .boxed()
+            }),
+            "fill" => Value::fn_native(move |args, interpreter| {
                 let target = target.clone();
                 let target_len = target.read().unwrap().len();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let val = args.next().unwrap_or_default();
+                    interpreter.track_arr(&target, &val);
                     let start = args
                         .next()
                         .map(f64::try_from)
@@ -677,8 +826,9 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 }
                 .boxed()
             }),
-            "repeat" => Value::fn_native(move |args, _| {
+            "repeat" => Value::fn_native(move |args, interpreter| {
                 let target = target.read().unwrap().clone();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let times = f64::try_from(args.next().unwrap_or_default())?;
@@ -691,6 +841,7 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                             "arr.repeat expected integer, got non-integer".to_string(),
                         ))?
                     } else {
+                        interpreter.check_arr_len(target.len().saturating_mul(times as usize))?;
                         let mut value = Vec::new();
                         let target = &target[..];
                         for _ in 0..times as usize {
@@ -701,9 +852,10 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 }
                 .boxed()
             }),
-            "splice" => Value::fn_native(move |args, _| {
+            "splice" => Value::fn_native(move |args, interpreter| {
                 let target = target.clone();
                 let target_len = target.read().unwrap().len();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let idx = f64::try_from(args.next().unwrap_or_default())?;
@@ -726,6 +878,10 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                         .map(<Vec<Value>>::try_from)
                         .map_or(Ok(None), |r| r.map(Some))?
                         .unwrap_or_default();
+                    interpreter.check_arr_len(target_len - remove_count + items.len())?;
+                    for item in &items {
+                        interpreter.track_arr(&target, item);
+                    }
                     let result = target
                         .write()
                         .unwrap()
@@ -759,7 +915,7 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                                 return;
                             }
                             for v in arr {
-                                if let V::Arr(value) = *v.value {
+                                if let V::Arr(value) = v.value {
                                     flat(value.read().unwrap().clone(), depth - 1, result);
                                 } else {
                                     result.push(v);
@@ -787,7 +943,7 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                         .await?;
                     let mut result = Vec::new();
                     for value in mapped_vals {
-                        if let V::Arr(value) = *value.value {
+                        if let V::Arr(value) = value.value {
                             result.extend(value.read().unwrap().clone())
                         } else {
                             result.push(value)
@@ -835,9 +991,10 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 }
                 .boxed()
             }),
-            "insert" => Value::fn_native(move |args, _| {
+            "insert" => Value::fn_native(move |args, interpreter| {
                 let target = target.clone();
                 let target_len = target.read().unwrap().len();
+                let interpreter = interpreter.clone();
                 async move {
                     let mut args = args.into_iter();
                     let idx = f64::try_from(args.next().unwrap_or_default())?;
@@ -848,6 +1005,8 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                     }
                     .clamp(0.0, target_len as f64) as usize;
                     let item = expect_any(args.next())?;
+                    interpreter.check_arr_len(target_len + 1)?;
+                    interpreter.track_arr(&target, &item);
                     target.write().unwrap().insert(index, item);
                     Ok(Value::null())
                 }
@@ -902,6 +1061,16 @@ pub fn get_prim_prop(target: Value, name: String) -> Result<Value, AiScriptError
                 "No such prop ({name}) in string."
             )))?,
         },
+        V::Bin(target) => match name.as_str() {
+            "len" => Value::num(target.len() as f64),
+            "to_arr" => Value::fn_native(move |_, _| {
+                let target = target.clone();
+                async move { Ok(Value::arr(target.into_iter().map(Value::num))) }.boxed()
+            }),
+            _ => Err(AiScriptRuntimeError::Runtime(format!(
+                "No such prop ({name}) in binary."
+            )))?,
+        },
         V::Error { value, info } => match name.as_str() {
             "name" => Value::str(value),
             "info" => info.map_or_else(Value::null, |info| *info),
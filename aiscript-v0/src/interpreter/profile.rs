@@ -0,0 +1,120 @@
+//! Profiling of the time spent in each function, enabled by
+//! [`Interpreter::with_profiling`](super::Interpreter::with_profiling).
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The time spent in the functions called by a name, returned by
+/// [`Interpreter::profile`](super::Interpreter::profile).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// The name the function is called with, e.g. `greet` or `Core:add`, or `<anonymous>` for a
+    /// function called without a name, e.g. a callback of `Arr:map`.
+    pub name: String,
+
+    /// Whether the function is a native function, including the standard library.
+    pub native: bool,
+
+    pub calls: usize,
+
+    /// The time spent in the function itself, excluding the functions it calls.
+    pub self_time: Duration,
+
+    /// The time spent in the function, including the functions it calls. The time of a
+    /// recursive call is counted only once.
+    pub total_time: Duration,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    state: Mutex<ProfilerState>,
+}
+
+#[derive(Debug, Default)]
+struct ProfilerState {
+    next_id: usize,
+    /// The functions being called, the innermost last.
+    stack: Vec<Frame>,
+    entries: HashMap<(String, bool), ProfileEntry>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    id: usize,
+    key: (String, bool),
+    started: Instant,
+    /// The total time of the functions called by this one.
+    children: Duration,
+}
+
+impl Profiler {
+    /// Starts a call and returns the id to pass to [`Self::exit`].
+    pub fn enter(&self, name: Option<&str>, native: bool) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        let key = (name.unwrap_or("<anonymous>").to_string(), native);
+        state
+            .entries
+            .entry(key.clone())
+            .or_insert_with(|| ProfileEntry {
+                name: key.0.clone(),
+                native,
+                calls: 0,
+                self_time: Duration::ZERO,
+                total_time: Duration::ZERO,
+            })
+            .calls += 1;
+        state.stack.push(Frame {
+            id,
+            key,
+            started: Instant::now(),
+            children: Duration::ZERO,
+        });
+        id
+    }
+
+    /// Ends a call started by [`Self::enter`].
+    ///
+    /// Calls running concurrently, e.g. via `Async:` functions, may end in another order than
+    /// they started, in which case the time is attributed to the caller approximately.
+    pub fn exit(&self, id: usize) {
+        let mut state = self.state.lock().unwrap();
+        let Some(i) = state.stack.iter().rposition(|frame| frame.id == id) else {
+            return;
+        };
+        let frame = state.stack.remove(i);
+        let elapsed = frame.started.elapsed();
+        let recursive = state.stack.iter().any(|caller| caller.key == frame.key);
+        if let Some(caller) = i.checked_sub(1).map(|i| &mut state.stack[i]) {
+            caller.children += elapsed;
+        }
+        if let Some(entry) = state.entries.get_mut(&frame.key) {
+            entry.self_time += elapsed.saturating_sub(frame.children);
+            if !recursive {
+                entry.total_time += elapsed;
+            }
+        }
+    }
+
+    /// Returns the entries sorted by the self time, longest first.
+    pub fn report(&self) -> Vec<ProfileEntry> {
+        let mut entries = self
+            .state
+            .lock()
+            .unwrap()
+            .entries
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            b.self_time
+                .cmp(&a.self_time)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        entries
+    }
+}
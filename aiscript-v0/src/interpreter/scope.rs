@@ -3,16 +3,37 @@ use std::{
     sync::{Arc, RwLock},
 };
 
-use crate::error::{AiScriptError, AiScriptRuntimeError};
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    node::Loc,
+};
+
+use super::{
+    value::{Attr, Value},
+    variable::Variable,
+};
 
-use super::{value::Value, variable::Variable};
+/// A variable visible from a scope, returned by [`Scope::inspect`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub value: Value,
+    pub mutable: bool,
+    /// The attributes given by `#[...]` on the definition.
+    pub attr: Vec<Attr>,
+    /// The location of the definition, which is `None` for variables given by the host.
+    pub loc: Option<Loc>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Scope {
     parent: Option<Box<Scope>>,
     states: Arc<RwLock<HashMap<String, Variable>>>,
+    locs: Arc<RwLock<HashMap<String, Loc>>>,
     name: String,
     ns_name: Option<String>,
+    /// Whether this is the scope of the arguments of a function call.
+    fn_: bool,
 }
 
 impl Default for Scope {
@@ -20,8 +41,10 @@ impl Default for Scope {
         Self {
             parent: Default::default(),
             states: Default::default(),
+            locs: Default::default(),
             name: "<root>".to_string(),
             ns_name: Default::default(),
+            fn_: false,
         }
     }
 }
@@ -31,8 +54,10 @@ impl Scope {
         Scope {
             parent: None,
             states: Arc::new(RwLock::new(states)),
+            locs: Default::default(),
             name: name.unwrap_or_else(|| "<root>".to_string()),
             ns_name: None,
+            fn_: false,
         }
     }
 
@@ -44,8 +69,18 @@ impl Scope {
         Scope {
             parent: Some(self.clone().into()),
             states: Arc::new(RwLock::new(states)),
+            locs: Default::default(),
             name: name.unwrap_or_else(|| "<anonymous>".to_string()),
             ns_name: None,
+            fn_: false,
+        }
+    }
+
+    /// Creates the scope of a function call, which has the arguments.
+    pub(crate) fn create_fn_scope(&self, args: HashMap<String, Variable>) -> Self {
+        Scope {
+            fn_: true,
+            ..self.create_child_scope(args, None)
         }
     }
 
@@ -58,8 +93,10 @@ impl Scope {
         Scope {
             parent: Some(self.clone().into()),
             states: Arc::new(RwLock::new(states)),
+            locs: Default::default(),
             name: name.unwrap_or_else(|| "<anonymous>".to_string()),
             ns_name: Some(ns_name),
+            fn_: false,
         }
     }
 
@@ -75,9 +112,10 @@ impl Scope {
         } else if let Some(parent) = &self.parent {
             parent.get_(name, scope_name)
         } else {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "No such variable '{name}' in scope '{scope_name}'",
-            )))?
+            Err(AiScriptRuntimeError::NoSuchVariable {
+                name: name.to_string(),
+                scope: scope_name.to_string(),
+            })?
         }
     }
 
@@ -91,6 +129,85 @@ impl Scope {
         }
     }
 
+    /// Returns the maps of the variables of this scope and its parents, innermost first.
+    pub(crate) fn states_chain(
+        &self,
+    ) -> impl Iterator<Item = &Arc<RwLock<HashMap<String, Variable>>>> {
+        std::iter::successors(Some(self), |scope| scope.parent.as_deref())
+            .map(|scope| &scope.states)
+    }
+
+    /// Returns whether a parent of this scope in the same function has a variable `name`, which
+    /// a definition in this scope would shadow.
+    pub(crate) fn shadows(&self, name: &str) -> bool {
+        match &self.parent {
+            Some(parent) if !self.fn_ => parent.defined_in_fn(name),
+            _ => false,
+        }
+    }
+
+    /// Returns whether this scope or a parent of it in the same function has a variable `name`.
+    pub(crate) fn defined_in_fn(&self, name: &str) -> bool {
+        let mut scope = self;
+        loop {
+            if scope.states.read().unwrap().contains_key(name) {
+                return true;
+            }
+            match &scope.parent {
+                Some(parent) if !scope.fn_ => scope = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Returns whether the variable `name` visible from this scope is defined outside the
+    /// innermost namespace which this scope is in.
+    pub(crate) fn is_outside_namespace(&self, name: &str) -> bool {
+        let mut in_namespace = false;
+        let mut scope = Some(self);
+        while let Some(s) = scope {
+            if s.states.read().unwrap().contains_key(name) {
+                return in_namespace;
+            }
+            in_namespace |= s.ns_name.is_some();
+            scope = s.parent.as_deref();
+        }
+        false
+    }
+
+    /// Returns the variables visible from this scope with their attributes and locations, sorted
+    /// by name. Variables of inner scopes shadow those of outer ones.
+    pub fn inspect(&self) -> Vec<Binding> {
+        let mut bindings = HashMap::new();
+        self.inspect_(&mut bindings);
+        let mut bindings = bindings.into_values().collect::<Vec<_>>();
+        bindings.sort_by(|a, b| a.name.cmp(&b.name));
+        bindings
+    }
+
+    fn inspect_(&self, bindings: &mut HashMap<String, Binding>) {
+        if let Some(parent) = &self.parent {
+            parent.inspect_(bindings);
+        }
+        let locs = self.locs.read().unwrap();
+        for (name, variable) in self.states.read().unwrap().iter() {
+            let (value, mutable) = match variable {
+                Variable::Mut(value) => (value, true),
+                Variable::Const(value) => (value, false),
+            };
+            bindings.insert(
+                name.clone(),
+                Binding {
+                    name: name.clone(),
+                    value: value.clone(),
+                    mutable,
+                    attr: value.attr.clone().unwrap_or_default(),
+                    loc: locs.get(name).cloned(),
+                },
+            );
+        }
+    }
+
     pub fn get_all(&self) -> HashMap<String, Variable> {
         if let Some(parent) = &self.parent {
             let mut states = parent.get_all();
@@ -101,20 +218,97 @@ impl Scope {
         }
     }
 
+    /// Returns the mutable variables visible from this scope, which are not shadowed by
+    /// immutable ones of inner scopes.
+    pub(crate) fn get_all_mut(&self) -> HashMap<String, Variable> {
+        let mut states = match &self.parent {
+            Some(parent) => parent.get_all_mut(),
+            None => HashMap::new(),
+        };
+        for (name, variable) in self.states.read().unwrap().iter() {
+            match variable {
+                Variable::Mut(_) => {
+                    states.insert(name.clone(), variable.clone());
+                }
+                Variable::Const(_) => {
+                    states.remove(name);
+                }
+            }
+        }
+        states
+    }
+
+    /// Returns the immutable variables of this scope without looking up the parents, sorted by
+    /// name.
+    pub(crate) fn get_own_consts(&self) -> Vec<(String, Value)> {
+        let mut consts = self
+            .states
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, variable)| match variable {
+                Variable::Const(value) => Some((name.clone(), value.clone())),
+                Variable::Mut(_) => None,
+            })
+            .collect::<Vec<_>>();
+        consts.sort_by(|(a, _), (b, _)| a.cmp(b));
+        consts
+    }
+
+    /// Returns the values of the variables of this scope without looking up the parents, sorted
+    /// by name.
+    pub(crate) fn get_own(&self) -> Vec<(String, Value)> {
+        let mut states = self
+            .states
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, Variable::Mut(value) | Variable::Const(value))| {
+                (name.clone(), value.clone())
+            })
+            .collect::<Vec<_>>();
+        states.sort_by(|(a, _), (b, _)| a.cmp(b));
+        states
+    }
+
+    /// Removes a variable from this scope without looking up the parents.
+    pub(crate) fn remove(&self, name: &str) -> Option<Variable> {
+        self.locs.write().unwrap().remove(name);
+        self.states.write().unwrap().remove(name)
+    }
+
+    /// Sets a variable in this scope, replacing the existing one regardless of its mutability.
+    pub(crate) fn set(&self, name: String, variable: Variable) {
+        self.states.write().unwrap().insert(name, variable);
+    }
+
     pub fn add(&self, name: String, variable: Variable) -> Result<(), AiScriptError> {
+        self.add_at(name, variable, None)
+    }
+
+    /// Adds a variable defined at `loc` in a script.
+    pub(crate) fn add_at(
+        &self,
+        name: String,
+        variable: Variable,
+        loc: Option<Loc>,
+    ) -> Result<(), AiScriptError> {
         if self.states.read().unwrap().contains_key(&name) {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "Variable '{name}' already exists in scope '{}'",
-                self.name
-            )))?
+            Err(AiScriptRuntimeError::VariableExists {
+                name,
+                scope: self.name.clone(),
+            })?
         } else {
             self.states
                 .write()
                 .unwrap()
                 .insert(name.clone(), variable.clone());
+            if let Some(loc) = &loc {
+                self.locs.write().unwrap().insert(name.clone(), loc.clone());
+            }
             if let Some(parent) = &self.parent {
                 if let Some(ns_name) = &self.ns_name {
-                    parent.add(format!("{ns_name}:{name}"), variable)?;
+                    parent.add_at(format!("{ns_name}:{name}"), variable, loc)?;
                 }
             }
             Ok(())
@@ -140,16 +334,15 @@ impl Scope {
                     .insert(name, Variable::Mut(val));
                 Ok(())
             }
-            Some(false) => Err(AiScriptRuntimeError::Runtime(format!(
-                "Cannot assign to an immutable variable {name}."
-            )))?,
+            Some(false) => Err(AiScriptRuntimeError::AssignToImmutable(name))?,
             None => {
                 if let Some(parent) = &self.parent {
                     parent.assign_(name, val, scope_name)
                 } else {
-                    Err(AiScriptRuntimeError::Runtime(format!(
-                        "No such variable '{name}' in scope '{scope_name}"
-                    )))?
+                    Err(AiScriptRuntimeError::NoSuchVariable {
+                        name,
+                        scope: scope_name.to_string(),
+                    })?
                 }
             }
         }
@@ -0,0 +1,100 @@
+//! Execution of a script by a number of steps at a time, started by
+//! [`Interpreter::start`](super::Interpreter::start).
+
+use std::{
+    future::poll_fn,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use futures::{future::BoxFuture, task::noop_waker_ref, FutureExt};
+
+use crate::error::AiScriptError;
+
+use super::value::Value;
+
+/// The result of [`StepExecution::run_steps`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    /// The script has not completed yet, since it used up the steps or a native function it
+    /// called is waiting.
+    Pending,
+    /// The script has completed with the value of the last statement.
+    Complete(Option<Value>),
+}
+
+/// An execution of a script started by [`Interpreter::start`](super::Interpreter::start), which
+/// runs only when [`Self::run_steps`] is called, e.g. by a scheduler running many scripts in
+/// turns on one thread.
+pub struct StepExecution {
+    budget: std::sync::Arc<StepBudget>,
+    exec: Option<BoxFuture<'static, Result<Option<Value>, AiScriptError>>>,
+}
+
+impl std::fmt::Debug for StepExecution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StepExecution")
+            .field("completed", &self.exec.is_none())
+            .finish_non_exhaustive()
+    }
+}
+
+impl StepExecution {
+    pub(crate) fn new(
+        budget: std::sync::Arc<StepBudget>,
+        exec: BoxFuture<'static, Result<Option<Value>, AiScriptError>>,
+    ) -> Self {
+        StepExecution {
+            budget,
+            exec: Some(exec),
+        }
+    }
+
+    /// Runs the script for at most `steps` steps, the same steps as limited by `max_step`.
+    ///
+    /// Calling this again after the script has completed or failed returns an error.
+    pub fn run_steps(&mut self, steps: usize) -> Result<StepResult, AiScriptError> {
+        let Some(exec) = &mut self.exec else {
+            Err(AiScriptError::Internal(
+                "the execution has already completed".to_string(),
+            ))?
+        };
+        self.budget.remaining.store(steps, Ordering::SeqCst);
+        match exec.poll_unpin(&mut Context::from_waker(noop_waker_ref())) {
+            Poll::Ready(result) => {
+                self.exec = None;
+                Ok(StepResult::Complete(result?))
+            }
+            Poll::Pending => Ok(StepResult::Pending),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.exec.is_none()
+    }
+}
+
+/// The number of steps an execution started by
+/// [`Interpreter::start`](super::Interpreter::start) can evaluate before pausing.
+#[derive(Debug, Default)]
+pub(crate) struct StepBudget {
+    remaining: AtomicUsize,
+}
+
+impl StepBudget {
+    /// Takes a step from the budget, or pauses until [`StepExecution::run_steps`] is called
+    /// again if it is used up.
+    pub(crate) async fn take(&self) {
+        poll_fn(|_| {
+            match self
+                .remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| {
+                    remaining.checked_sub(1)
+                }) {
+                Ok(_) => Poll::Ready(()),
+                Err(_) => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
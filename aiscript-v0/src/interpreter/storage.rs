@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::error::{AiScriptError, AiScriptRuntimeError};
+
+use super::value::{Value, V};
+
+/// A key-value store which keeps the state of scripts, e.g. the settings of a plugin.
+///
+/// Pass [`Storage::functions`] to an interpreter as constants to enable `Storage:get(key)`,
+/// `Storage:set(key, value)`, `Storage:delete(key)` and `Storage:list()` in scripts.
+/// `Storage:get` returns null for a missing key.
+///
+/// ```
+/// use aiscript_v0::{Interpreter, MemoryStorage, Parser, Storage};
+/// # use aiscript_v0::{errors::AiScriptError, values::Value};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), AiScriptError> {
+/// let storage = MemoryStorage::new();
+/// let interpreter = Interpreter::new(
+///     storage.clone().functions(),
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None::<fn(_) -> _>,
+///     None,
+/// );
+/// let script = Parser::default().parse("Storage:set('count', 1)")?;
+/// interpreter.exec(script).await?;
+/// assert_eq!(storage.get("count").await?, Some(Value::num(1)));
+/// # Ok(())
+/// # }
+/// ```
+pub trait Storage: Send + Sync {
+    /// Returns the value of `key`, or `None` if it is not set.
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Value>, AiScriptError>>;
+
+    /// Sets the value of `key`.
+    fn set<'a>(&'a self, key: &'a str, value: Value) -> BoxFuture<'a, Result<(), AiScriptError>>;
+
+    /// Deletes `key`. Deleting a missing key is not an error.
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), AiScriptError>>;
+
+    /// Returns all keys.
+    fn list(&self) -> BoxFuture<'_, Result<Vec<String>, AiScriptError>>;
+
+    /// Returns the functions of the `Storage:` namespace backed by this storage.
+    fn functions(self) -> Vec<(String, Value)>
+    where
+        Self: Sized + 'static,
+    {
+        functions(Arc::new(self))
+    }
+}
+
+fn functions(storage: Arc<dyn Storage>) -> Vec<(String, Value)> {
+    vec![
+        ("Storage:get".to_string(), {
+            let storage = storage.clone();
+            Value::fn_native(move |args, _| {
+                let storage = storage.clone();
+                async move {
+                    let mut args = args.into_iter();
+                    let key = String::try_from(args.next().unwrap_or_default())?;
+                    Ok(storage.get(&key).await?.unwrap_or_default())
+                }
+                .boxed()
+            })
+        }),
+        ("Storage:set".to_string(), {
+            let storage = storage.clone();
+            Value::fn_native(move |args, _| {
+                let storage = storage.clone();
+                async move {
+                    let mut args = args.into_iter();
+                    let key = String::try_from(args.next().unwrap_or_default())?;
+                    let value = args.next().unwrap_or_default();
+                    storage.set(&key, value).await?;
+                    Ok(Value::null())
+                }
+                .boxed()
+            })
+        }),
+        ("Storage:delete".to_string(), {
+            let storage = storage.clone();
+            Value::fn_native(move |args, _| {
+                let storage = storage.clone();
+                async move {
+                    let mut args = args.into_iter();
+                    let key = String::try_from(args.next().unwrap_or_default())?;
+                    storage.delete(&key).await?;
+                    Ok(Value::null())
+                }
+                .boxed()
+            })
+        }),
+        (
+            "Storage:list".to_string(),
+            Value::fn_native(move |_, _| {
+                let storage = storage.clone();
+                async move {
+                    let keys = storage.list().await?;
+                    Ok(Value::arr(keys.into_iter().map(Value::str)))
+                }
+                .boxed()
+            }),
+        ),
+    ]
+}
+
+/// A [`Storage`] in memory, which keeps values as JSON like persistent storages do.
+///
+/// Clones share the same values.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    values: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Value>, AiScriptError>> {
+        async move {
+            let values = self.values.lock().unwrap();
+            match values.get(key) {
+                Some(json) => Ok(Some(Value::new(
+                    serde_json::from_str::<V>(json)
+                        .map_err(|e| AiScriptError::Internal(e.to_string()))?,
+                ))),
+                None => Ok(None),
+            }
+        }
+        .boxed()
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Value) -> BoxFuture<'a, Result<(), AiScriptError>> {
+        async move {
+            let json = serde_json::to_string(&value.value).map_err(|e| {
+                AiScriptRuntimeError::Runtime(format!("Cannot store the value of {key}: {e}"))
+            })?;
+            self.values.lock().unwrap().insert(key.to_string(), json);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), AiScriptError>> {
+        async move {
+            self.values.lock().unwrap().remove(key);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<String>, AiScriptError>> {
+        async move { Ok(self.values.lock().unwrap().keys().cloned().collect()) }.boxed()
+    }
+}
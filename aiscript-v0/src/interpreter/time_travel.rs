@@ -0,0 +1,352 @@
+//! Time-travel debugging of deterministic scripts, which records snapshots of the top-level
+//! variables while running a script and rewinds to one of them by running the script again.
+
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    sync::{Arc, Mutex},
+    task::Poll,
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use indexmap::IndexMap;
+
+use crate::{error::AiScriptError, node as ast};
+
+use super::{
+    scope::Scope,
+    value::{VFn, Value, V},
+    Interpreter,
+};
+
+/// The changes of the top-level variables of a script, captured every `interval` steps by
+/// [`Interpreter::record`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// The number of steps evaluated before the snapshot.
+    pub step: usize,
+    /// The variables defined or changed since the previous snapshot, sorted by name, with copies
+    /// of their values at the time.
+    pub changes: IndexMap<String, Value>,
+}
+
+/// An execution recorded by [`Interpreter::record`], which can be rewound to its snapshots.
+pub struct Recording {
+    base: Interpreter,
+    script: Vec<ast::Node>,
+    interval: usize,
+    snapshots: Vec<Snapshot>,
+    result: Result<Option<Value>, AiScriptError>,
+}
+
+impl std::fmt::Debug for Recording {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recording")
+            .field("interval", &self.interval)
+            .field("snapshots", &self.snapshots)
+            .field("result", &self.result)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Recording {
+    /// Returns the result of the recorded execution.
+    pub fn result(&self) -> &Result<Option<Value>, AiScriptError> {
+        &self.result
+    }
+
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    /// Returns all the top-level variables at the snapshot `index`, by applying the changes of
+    /// the snapshots up to it.
+    pub fn state_at(&self, index: usize) -> IndexMap<String, Value> {
+        let mut state = IndexMap::new();
+        for snapshot in self.snapshots.iter().take(index + 1) {
+            state.extend(snapshot.changes.clone());
+        }
+        state.sort_unstable_keys();
+        state
+    }
+
+    /// Runs the script again on a new fork of the interpreter, and pauses it at the snapshot
+    /// `index`.
+    ///
+    /// The script must be deterministic: it must take the time from a
+    /// [`ManualClock`](super::clock::ManualClock), random numbers from `Math:gen_rng` with a
+    /// fixed seed, and input from the same answers. An error is returned if the variables differ
+    /// from the recorded ones at any snapshot up to `index`.
+    pub async fn rewind(&self, index: usize) -> Result<Replay, AiScriptError> {
+        let Some(target) = self.snapshots.get(index) else {
+            Err(AiScriptError::Internal(format!(
+                "no snapshot at index {index}"
+            )))?
+        };
+        let timeline = Arc::new(Timeline::new(self.interval, Some(target.step)));
+        let interpreter = Interpreter {
+            timeline: Some(timeline.clone()),
+            ..self.base.fork()
+        };
+        let mut exec = {
+            let interpreter = interpreter.clone();
+            let script = self.script.clone();
+            async move { interpreter.exec(script).await }.boxed()
+        };
+        let finished = poll_fn(|cx| match exec.poll_unpin(cx) {
+            Poll::Ready(result) => Poll::Ready(Some(result)),
+            Poll::Pending if timeline.paused() => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        })
+        .await;
+        if let Some(result) = finished {
+            result?;
+            Err(AiScriptError::Internal(format!(
+                "the replay finished before step {}",
+                target.step
+            )))?
+        }
+        let replayed = timeline.snapshots.lock().unwrap().clone();
+        for (recorded, replayed) in self.snapshots.iter().zip(replayed).take(index + 1) {
+            if !same_snapshot(recorded, &replayed) {
+                Err(AiScriptError::Internal(format!(
+                    "the replay diverged from the recording at step {}",
+                    recorded.step
+                )))?
+            }
+        }
+        Ok(Replay {
+            interpreter,
+            step: target.step,
+            timeline,
+            exec,
+        })
+    }
+}
+
+/// A replay of a [`Recording`] paused at a snapshot.
+pub struct Replay {
+    interpreter: Interpreter,
+    step: usize,
+    timeline: Arc<Timeline>,
+    exec: BoxFuture<'static, Result<Option<Value>, AiScriptError>>,
+}
+
+impl std::fmt::Debug for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Replay")
+            .field("interpreter", &self.interpreter)
+            .field("step", &self.step)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Replay {
+    /// Returns the number of steps evaluated before the pause.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Returns the interpreter running the replay, e.g. to inspect its scope while paused.
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// Continues the replay to the end of the script.
+    pub async fn resume(self) -> Result<Option<Value>, AiScriptError> {
+        self.timeline.resume();
+        self.exec.await
+    }
+}
+
+/// The snapshots being recorded by an interpreter, set via [`Interpreter::record`] and
+/// [`Recording::rewind`].
+pub(crate) struct Timeline {
+    interval: usize,
+    snapshots: Mutex<Vec<Snapshot>>,
+    /// The copies of the variables as of the last snapshot.
+    latest: Mutex<HashMap<String, Value>>,
+    /// The step to pause at, if replaying.
+    pause_at: Option<usize>,
+    pause: Mutex<PauseState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PauseState {
+    Running,
+    Paused,
+    Resumed,
+}
+
+impl Timeline {
+    fn new(interval: usize, pause_at: Option<usize>) -> Self {
+        Timeline {
+            interval: interval.max(1),
+            snapshots: Mutex::new(Vec::new()),
+            latest: Mutex::new(HashMap::new()),
+            pause_at,
+            pause: Mutex::new(PauseState::Running),
+        }
+    }
+
+    /// Captures a snapshot of the variables of `scope` if `step` steps have been evaluated at
+    /// the end of an interval, and waits there until resumed if it is the step to pause at.
+    pub(crate) async fn step(&self, step: usize, scope: &Scope) {
+        if step == 0 || !step.is_multiple_of(self.interval) {
+            return;
+        }
+        self.capture(step, scope);
+        if self.pause_at == Some(step) {
+            poll_fn(|_| {
+                let mut pause = self.pause.lock().unwrap();
+                match *pause {
+                    PauseState::Running | PauseState::Paused => {
+                        *pause = PauseState::Paused;
+                        Poll::Pending
+                    }
+                    PauseState::Resumed => Poll::Ready(()),
+                }
+            })
+            .await
+        }
+    }
+
+    fn capture(&self, step: usize, scope: &Scope) {
+        let mut latest = self.latest.lock().unwrap();
+        let mut copies = HashMap::new();
+        let changes = scope
+            .get_own()
+            .into_iter()
+            .map(|(name, value)| (name, detach(&value, &mut copies)))
+            .filter(|(name, value)| !latest.get(name).is_some_and(|latest| same(latest, value)))
+            .collect::<IndexMap<_, _>>();
+        latest.extend(changes.clone());
+        self.snapshots
+            .lock()
+            .unwrap()
+            .push(Snapshot { step, changes });
+    }
+
+    fn paused(&self) -> bool {
+        *self.pause.lock().unwrap() == PauseState::Paused
+    }
+
+    fn resume(&self) {
+        *self.pause.lock().unwrap() = PauseState::Resumed;
+    }
+}
+
+impl Interpreter {
+    /// Runs a script on a new fork of this interpreter while capturing a [`Snapshot`] of the
+    /// changes of the top-level variables every `interval` steps, for rewinding the execution
+    /// via [`Recording::rewind`].
+    pub async fn record(&self, script: Vec<ast::Node>, interval: usize) -> Recording {
+        let interpreter = Interpreter {
+            timeline: Some(Arc::new(Timeline::new(interval, None))),
+            ..self.fork()
+        };
+        let result = interpreter.exec(script.clone()).await;
+        let snapshots = interpreter
+            .timeline
+            .as_ref()
+            .map(|timeline| timeline.snapshots.lock().unwrap().clone())
+            .unwrap_or_default();
+        Recording {
+            base: self.clone(),
+            script,
+            interval: interval.max(1),
+            snapshots,
+            result,
+        }
+    }
+}
+
+/// Copies arrays and objects in `value` recursively, so that later changes to them are not
+/// reflected. `copies` keeps the copies of the containers already copied, to keep cycles and
+/// sharing.
+fn detach(value: &Value, copies: &mut HashMap<usize, V>) -> Value {
+    let detached = match &value.value {
+        V::Arr(arr) => {
+            let id = Arc::as_ptr(arr) as *const () as usize;
+            match copies.get(&id) {
+                Some(copy) => copy.clone(),
+                None => {
+                    let copy = super::value::VArr::default();
+                    copies.insert(id, V::Arr(copy.clone()));
+                    let items = arr
+                        .read()
+                        .unwrap()
+                        .iter()
+                        .map(|item| detach(item, copies))
+                        .collect();
+                    *copy.write().unwrap() = items;
+                    V::Arr(copy)
+                }
+            }
+        }
+        V::Obj(obj) => {
+            let id = Arc::as_ptr(obj) as *const () as usize;
+            match copies.get(&id) {
+                Some(copy) => copy.clone(),
+                None => {
+                    let copy = super::value::VObj::default();
+                    copies.insert(id, V::Obj(copy.clone()));
+                    let entries = obj
+                        .read()
+                        .unwrap()
+                        .iter()
+                        .map(|(key, value)| (key.clone(), detach(value, copies)))
+                        .collect();
+                    *copy.write().unwrap() = entries;
+                    V::Obj(copy)
+                }
+            }
+        }
+        V::Error {
+            value,
+            info: Some(info),
+        } => V::Error {
+            value: value.clone(),
+            info: Some(Box::new(detach(info, copies))),
+        },
+        value => value.clone(),
+    };
+    Value {
+        value: detached,
+        ..value.clone()
+    }
+}
+
+fn same_snapshot(a: &Snapshot, b: &Snapshot) -> bool {
+    a.step == b.step
+        && a.changes.len() == b.changes.len()
+        && a.changes
+            .iter()
+            .all(|(name, a)| b.changes.get(name).is_some_and(|b| same(a, b)))
+}
+
+/// Compares values by their contents like [`Value::deep_eq`], except that functions are
+/// equal to those of a replay: functions defined by scripts are equal if they have the same
+/// arguments and body, and native functions are always equal since those created while running,
+/// e.g. by `Math:gen_rng`, are different in each run.
+fn same(a: &Value, b: &Value) -> bool {
+    a.deep_eq_by(b, |a, b| match (a, b) {
+        (
+            VFn::Fn {
+                args: a_args,
+                statements: a_statements,
+                ..
+            },
+            VFn::Fn {
+                args: b_args,
+                statements: b_statements,
+                ..
+            },
+        ) => {
+            a_args == b_args
+                && (Arc::ptr_eq(a_statements, b_statements) || a_statements == b_statements)
+        }
+        (VFn::FnNative(_), VFn::FnNative(_)) => true,
+        _ => false,
+    })
+}
@@ -1,8 +1,11 @@
 use std::{
-    rc::Rc,
+    cmp::Ordering,
+    collections::HashSet,
+    iter::zip,
     sync::{Arc, RwLock},
 };
 
+use base64::prelude::*;
 use indexmap::IndexMap;
 use regex::Regex;
 use serde::{
@@ -11,13 +14,20 @@ use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use crate::error::{AiScriptError, AiScriptRuntimeError};
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    node as ast,
+};
 
-use super::value::{VArr, VFn, VObj, Value, V};
+use super::{
+    lib::std::number_format::to_radix_string,
+    value::{VArr, VFn, VObj, Value, V},
+};
 
 pub fn expect_any(val: Option<Value>) -> Result<Value, AiScriptError> {
-    Ok(val.ok_or_else(|| {
-        AiScriptRuntimeError::Runtime("Expect anything, but got nothing.".to_string())
+    Ok(val.ok_or_else(|| AiScriptRuntimeError::Type {
+        expected: "anything".to_string(),
+        got: "nothing".to_string(),
     })?)
 }
 
@@ -28,10 +38,10 @@ impl TryFrom<V> for bool {
         if let V::Bool(value) = value {
             Ok(value)
         } else {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "Expect boolean, but got {}",
-                value.display_type(),
-            )))?
+            Err(AiScriptRuntimeError::Type {
+                expected: "boolean".to_string(),
+                got: value.display_type().to_string(),
+            })?
         }
     }
 }
@@ -40,7 +50,7 @@ impl TryFrom<Value> for bool {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
     }
 }
 
@@ -49,12 +59,12 @@ impl TryFrom<V> for VFn {
 
     fn try_from(value: V) -> Result<Self, Self::Error> {
         if let V::Fn(value) = value {
-            Ok(value)
+            Ok(*value)
         } else {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "Expect function, but got {}",
-                value.display_type(),
-            )))?
+            Err(AiScriptRuntimeError::Type {
+                expected: "function".to_string(),
+                got: value.display_type().to_string(),
+            })?
         }
     }
 }
@@ -63,7 +73,7 @@ impl TryFrom<Value> for VFn {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
     }
 }
 
@@ -74,10 +84,10 @@ impl TryFrom<V> for String {
         if let V::Str(value) = value {
             Ok(value)
         } else {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "Expect string, but got {}",
-                value.display_type(),
-            )))?
+            Err(AiScriptRuntimeError::Type {
+                expected: "string".to_string(),
+                got: value.display_type().to_string(),
+            })?
         }
     }
 }
@@ -86,7 +96,7 @@ impl TryFrom<Value> for String {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
     }
 }
 
@@ -97,10 +107,10 @@ impl TryFrom<V> for f64 {
         if let V::Num(value) = value {
             Ok(value)
         } else {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "Expect number, but got {}",
-                value.display_type(),
-            )))?
+            Err(AiScriptRuntimeError::Type {
+                expected: "number".to_string(),
+                got: value.display_type().to_string(),
+            })?
         }
     }
 }
@@ -109,7 +119,7 @@ impl TryFrom<Value> for f64 {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
     }
 }
 
@@ -120,10 +130,10 @@ impl TryFrom<V> for VObj {
         if let V::Obj(value) = value {
             Ok(value)
         } else {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "Expect object, but got {}",
-                value.display_type(),
-            )))?
+            Err(AiScriptRuntimeError::Type {
+                expected: "object".to_string(),
+                got: value.display_type().to_string(),
+            })?
         }
     }
 }
@@ -132,7 +142,7 @@ impl TryFrom<Value> for VObj {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
     }
 }
 
@@ -148,7 +158,7 @@ impl TryFrom<Value> for IndexMap<String, Value> {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
     }
 }
 
@@ -159,10 +169,10 @@ impl TryFrom<V> for VArr {
         if let V::Arr(value) = value {
             Ok(value)
         } else {
-            Err(AiScriptRuntimeError::Runtime(format!(
-                "Expect array, but got {}",
-                value.display_type(),
-            )))?
+            Err(AiScriptRuntimeError::Type {
+                expected: "array".to_string(),
+                got: value.display_type().to_string(),
+            })?
         }
     }
 }
@@ -171,7 +181,30 @@ impl TryFrom<Value> for VArr {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
+    }
+}
+
+impl TryFrom<V> for Vec<u8> {
+    type Error = AiScriptError;
+
+    fn try_from(value: V) -> Result<Self, Self::Error> {
+        if let V::Bin(value) = value {
+            Ok(value)
+        } else {
+            Err(AiScriptRuntimeError::Type {
+                expected: "binary".to_string(),
+                got: value.display_type().to_string(),
+            })?
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<u8> {
+    type Error = AiScriptError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.value.try_into()
     }
 }
 
@@ -187,7 +220,7 @@ impl TryFrom<Value> for Vec<Value> {
     type Error = AiScriptError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        (*value.value).try_into()
+        value.value.try_into()
     }
 }
 
@@ -197,6 +230,93 @@ impl PartialEq for Value {
     }
 }
 
+impl Value {
+    /// Compares values by their contents, which is how `==` compares by default.
+    ///
+    /// Arrays and objects are equal if they have equal items, regardless of the order of the keys
+    /// of objects, functions are never equal and errors are equal if they have the same name and
+    /// equal info. Unlike [`PartialEq`], this terminates for cyclic values.
+    pub fn deep_eq(&self, other: &Value) -> bool {
+        self.deep_eq_by(other, |_, _| false)
+    }
+
+    /// Compares values like [`Self::deep_eq`], except that functions are compared by `fn_eq`.
+    pub(crate) fn deep_eq_by(&self, other: &Value, fn_eq: impl Fn(&VFn, &VFn) -> bool) -> bool {
+        deep_eq(&self.value, &other.value, &fn_eq, &mut HashSet::new())
+    }
+
+    /// Compares values in the same way as `==` of AiScript for JavaScript, which is how `==`
+    /// compares with [`Interpreter::with_reference_equality`](crate::Interpreter::with_reference_equality).
+    ///
+    /// Arrays, objects and functions are equal only if they are the same one, and errors are
+    /// equal if they have the same name.
+    pub fn ref_eq(&self, other: &Value) -> bool {
+        match (&self.value, &other.value) {
+            (V::Arr(a), V::Arr(b)) => Arc::ptr_eq(a, b),
+            (V::Obj(a), V::Obj(b)) => Arc::ptr_eq(a, b),
+            (V::Fn(a), V::Fn(b)) => match (a.as_ref(), b.as_ref()) {
+                (VFn::Fn { statements: a, .. }, VFn::Fn { statements: b, .. }) => Arc::ptr_eq(a, b),
+                (VFn::FnNative(a), VFn::FnNative(b)) => Arc::ptr_eq(a, b),
+                _ => false,
+            },
+            (V::Error { value: a, .. }, V::Error { value: b, .. }) => a == b,
+            (a, b) => a == b,
+        }
+    }
+}
+
+/// `visited` keeps the pairs of containers being compared, which are assumed to be equal when
+/// they are compared again in a cycle.
+fn deep_eq(
+    a: &V,
+    b: &V,
+    fn_eq: &dyn Fn(&VFn, &VFn) -> bool,
+    visited: &mut HashSet<(usize, usize)>,
+) -> bool {
+    match (a, b) {
+        (V::Arr(a), V::Arr(b)) => {
+            if !visited.insert((Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize)) {
+                return true;
+            }
+            let a = a.read().unwrap().clone();
+            let b = b.read().unwrap().clone();
+            a.len() == b.len()
+                && zip(a, b).all(|(a, b)| deep_eq(&a.value, &b.value, fn_eq, visited))
+        }
+        (V::Obj(a), V::Obj(b)) => {
+            if !visited.insert((Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize)) {
+                return true;
+            }
+            let a = a.read().unwrap().clone();
+            let b = b.read().unwrap().clone();
+            a.len() == b.len()
+                && a.into_iter().all(|(key, a)| {
+                    b.get(&key)
+                        .is_some_and(|b| deep_eq(&a.value, &b.value, fn_eq, visited))
+                })
+        }
+        (V::Fn(a), V::Fn(b)) => fn_eq(a, b),
+        (V::Return(a), V::Return(b)) => deep_eq(&a.value, &b.value, fn_eq, visited),
+        (
+            V::Error {
+                value: a_value,
+                info: a_info,
+            },
+            V::Error {
+                value: b_value,
+                info: b_info,
+            },
+        ) => {
+            a_value == b_value
+                && match (a_info, b_info) {
+                    (Some(a), Some(b)) => deep_eq(&a.value, &b.value, fn_eq, visited),
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+        }
+        (a, b) => a == b,
+    }
+}
+
 impl std::fmt::Display for V {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.display_type().fmt(f)?;
@@ -205,7 +325,7 @@ impl std::fmt::Display for V {
             V::Bool(value) => write!(f, "<{}>", value),
             V::Str(value) => write!(f, "<\"{}\">", value),
             V::Fn { .. } => write!(f, "<...>"),
-            V::Obj(_) => write!(f, "<..>"),
+            V::Obj(_) | V::Bin(_) | V::NativeObject(_) => write!(f, "<..>"),
             V::Null => write!(f, "<>"),
             _ => write!(f, "<null>"),
         }
@@ -253,7 +373,9 @@ impl std::fmt::Display for DisplayType<'_> {
                 V::Str(_) => "str",
                 V::Arr(_) => "arr",
                 V::Obj(_) => "obj",
+                V::Bin(_) => "bin",
                 V::Fn { .. } => "fn",
+                V::NativeObject(value) => value.type_name(),
                 V::Return(_) => "return",
                 V::Break => "break",
                 V::Continue => "continue",
@@ -299,16 +421,16 @@ impl Serialize for V {
 
 struct VWithMemo {
     pub value: V,
-    pub processed_arrays: Rc<Vec<VArr>>,
-    pub processed_objects: Rc<Vec<VObj>>,
+    pub processed_arrays: Arc<Vec<VArr>>,
+    pub processed_objects: Arc<Vec<VObj>>,
 }
 
 impl VWithMemo {
     pub fn new(value: V) -> Self {
         VWithMemo {
             value,
-            processed_arrays: Rc::new(Vec::new()),
-            processed_objects: Rc::new(Vec::new()),
+            processed_arrays: Arc::new(Vec::new()),
+            processed_objects: Arc::new(Vec::new()),
         }
     }
 }
@@ -322,7 +444,8 @@ impl Serialize for VWithMemo {
             V::Null => serializer.serialize_unit(),
             V::Bool(value) => serializer.serialize_bool(*value),
             V::Num(value) => {
-                if value.trunc() == *value {
+                // Larger numbers, including infinities, would be saturated by `as i64`.
+                if value.trunc() == *value && value.abs() < i64::MAX as f64 {
                     serializer.serialize_i64(*value as i64)
                 } else {
                     serializer.serialize_f64(*value)
@@ -335,12 +458,12 @@ impl Serialize for VWithMemo {
                 } else {
                     let mut processed_arrays = (*self.processed_arrays).clone();
                     processed_arrays.push(value.clone());
-                    let processed_arrays = Rc::new(processed_arrays);
+                    let processed_arrays = Arc::new(processed_arrays);
                     let value = value.read().unwrap();
                     let mut seq = serializer.serialize_seq(Some(value.len()))?;
                     for e in value.iter() {
                         seq.serialize_element(&VWithMemo {
-                            value: *e.value.clone(),
+                            value: e.value.clone(),
                             processed_arrays: processed_arrays.clone(),
                             processed_objects: self.processed_objects.clone(),
                         })?;
@@ -354,14 +477,14 @@ impl Serialize for VWithMemo {
                 } else {
                     let mut processed_objects = (*self.processed_objects).clone();
                     processed_objects.push(value.clone());
-                    let processed_objects = Rc::new(processed_objects);
+                    let processed_objects = Arc::new(processed_objects);
                     let value = value.read().unwrap();
                     let mut map = serializer.serialize_map(Some(value.len()))?;
                     for (k, v) in value.iter() {
                         map.serialize_entry(
                             k,
                             &VWithMemo {
-                                value: *v.value.clone(),
+                                value: v.value.clone(),
                                 processed_arrays: self.processed_arrays.clone(),
                                 processed_objects: processed_objects.clone(),
                             },
@@ -370,6 +493,7 @@ impl Serialize for VWithMemo {
                     map.end()
                 }
             }
+            V::Bin(value) => serializer.serialize_str(&BASE64_STANDARD.encode(value)),
             V::Fn(_) => serializer.serialize_str("<function>"),
             value => Err(ser::Error::custom(format!(
                 "Unrecognized value type: {}",
@@ -462,6 +586,185 @@ impl<'de> Visitor<'de> for VVisitor {
     }
 }
 
+/// Compares sort keys, which are numbers, strings, or arrays of them compared item by item.
+fn compare_keys(a: &Value, b: &Value) -> Result<Ordering, AiScriptRuntimeError> {
+    match (&a.value, &b.value) {
+        (V::Num(a), V::Num(b)) => Ok(a.total_cmp(b)),
+        (V::Str(a), V::Str(b)) => Ok(a.cmp(b)),
+        (V::Arr(a), V::Arr(b)) => {
+            let (a, b) = (a.read().unwrap(), b.read().unwrap());
+            for (a, b) in zip(a.iter(), b.iter()) {
+                match compare_keys(a, b)? {
+                    Ordering::Equal => (),
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(a.len().cmp(&b.len()))
+        }
+        (a, b) => Err(AiScriptRuntimeError::Runtime(format!(
+            "cannot compare sort keys of type {} and {}",
+            a.display_type(),
+            b.display_type()
+        ))),
+    }
+}
+
+/// Sorts items by their keys stably, where `keys[i]` has a key of each item for the `i`th
+/// criterion, which is in the descending order if `descending[i]`.
+pub(crate) fn sort_by_keys(
+    items: Vec<Value>,
+    keys: &[Vec<Value>],
+    descending: &[bool],
+) -> Result<Vec<Value>, AiScriptError> {
+    let mut error = None;
+    let mut indices = (0..items.len()).collect::<Vec<_>>();
+    indices.sort_by(|&a, &b| {
+        for (i, keys) in keys.iter().enumerate() {
+            match compare_keys(&keys[a], &keys[b]) {
+                Ok(Ordering::Equal) => (),
+                Ok(ordering) if descending.get(i) == Some(&true) => return ordering.reverse(),
+                Ok(ordering) => return ordering,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    return Ordering::Equal;
+                }
+            }
+        }
+        Ordering::Equal
+    });
+    if let Some(error) = error {
+        Err(error)?
+    }
+    Ok(indices.into_iter().map(|i| items[i].clone()).collect())
+}
+
+/// Converts a literal which consists only of literals, e.g. the value of a metadata or an
+/// attribute, into a value. The items which are not literals are left out.
+pub(crate) fn static_value(node: ast::Expression) -> Option<Value> {
+    match node {
+        ast::Expression::Arr(ast::Arr { value, .. }) => Some(Value::arr({
+            let mut vec = Vec::new();
+            for node in value {
+                if let Some(value) = static_value(node) {
+                    vec.push(value);
+                }
+            }
+            vec
+        })),
+        ast::Expression::Bool(ast::Bool { value, .. }) => Some(Value::bool(value)),
+        ast::Expression::Null(_) => Some(Value::null()),
+        ast::Expression::Num(ast::Num { value, .. }) => Some(Value::num(value)),
+        ast::Expression::Obj(ast::Obj { value, .. }) => Some(Value::obj({
+            let mut obj = IndexMap::new();
+            for (k, v) in value.into_iter() {
+                if let Some(value) = static_value(v) {
+                    obj.insert(k, value);
+                }
+            }
+            obj
+        })),
+        ast::Expression::Str(ast::Str { value, .. }) => Some(Value::str(value)),
+        _ => None,
+    }
+}
+
+/// Evaluates an expression of constants without running a script: literals, arrays, objects and
+/// templates of constants, `!`, `&&`, `||`, `??`, and the arithmetic and comparison operators.
+/// Returns `None` if the expression is not constant, e.g. calls a function, or if evaluating it
+/// would fail.
+pub(crate) fn const_value(node: ast::Expression) -> Option<Value> {
+    match node {
+        ast::Expression::Arr(ast::Arr { value, .. }) => Some(Value::arr(
+            value
+                .into_iter()
+                .map(const_value)
+                .collect::<Option<Vec<_>>>()?,
+        )),
+        ast::Expression::Obj(ast::Obj { value, .. }) => Some(Value::obj(
+            value
+                .into_iter()
+                .map(|(k, v)| Some((k, const_value(v)?)))
+                .collect::<Option<IndexMap<_, _>>>()?,
+        )),
+        ast::Expression::Tmpl(ast::Tmpl { tmpl, .. }) => {
+            let mut str = String::new();
+            for x in tmpl {
+                match x {
+                    ast::StringOrExpression::String(x) => str.push_str(&x),
+                    ast::StringOrExpression::Expression(x) => {
+                        str.push_str(&const_value(x)?.repr_value().to_string())
+                    }
+                }
+            }
+            Some(Value::str(str))
+        }
+        ast::Expression::Not(ast::Not { expr, .. }) => match const_value(*expr)?.value {
+            V::Bool(value) => Some(Value::bool(!value)),
+            _ => None,
+        },
+        ast::Expression::And(ast::And { left, right, .. }) => match const_value(*left)?.value {
+            V::Bool(false) => Some(Value::bool(false)),
+            V::Bool(true) => match const_value(*right)?.value {
+                V::Bool(value) => Some(Value::bool(value)),
+                _ => None,
+            },
+            _ => None,
+        },
+        ast::Expression::Or(ast::Or { left, right, .. }) => match const_value(*left)?.value {
+            V::Bool(true) => Some(Value::bool(true)),
+            V::Bool(false) => match const_value(*right)?.value {
+                V::Bool(value) => Some(Value::bool(value)),
+                _ => None,
+            },
+            _ => None,
+        },
+        ast::Expression::Coalesce(ast::Coalesce { left, right, .. }) => match const_value(*left)? {
+            Value { value: V::Null, .. } => const_value(*right),
+            left => Some(left),
+        },
+        ast::Expression::Call(ast::Call { target, args, .. }) => {
+            let ast::Expression::Identifier(ast::Identifier { name, .. }) = *target else {
+                return None;
+            };
+            let [a, b] = <[ast::Expression; 2]>::try_from(args).ok()?;
+            let (a, b) = (const_value(a)?, const_value(b)?);
+            match name.as_str() {
+                "Core:eq" | "Core:neq" => {
+                    let eq = match (&a.value, &b.value) {
+                        (V::Num(a), V::Num(b)) => a == b,
+                        (V::Str(a), V::Str(b)) => a == b,
+                        (V::Bool(a), V::Bool(b)) => a == b,
+                        (V::Null, V::Null) => true,
+                        (V::Num(_) | V::Str(_) | V::Bool(_) | V::Null, _) => false,
+                        _ => return None,
+                    };
+                    Some(Value::bool(eq == (name == "Core:eq")))
+                }
+                _ => {
+                    let (V::Num(a), V::Num(b)) = (a.value, b.value) else {
+                        return None;
+                    };
+                    match name.as_str() {
+                        "Core:add" => Some(Value::num(a + b)),
+                        "Core:sub" => Some(Value::num(a - b)),
+                        "Core:mul" => Some(Value::num(a * b)),
+                        // NaN is an error at runtime
+                        "Core:div" => Some(a / b).filter(|res| !res.is_nan()).map(Value::num),
+                        "Core:pow" => Some(a.powf(b)).filter(|res| !res.is_nan()).map(Value::num),
+                        "Core:mod" => Some(Value::num(a % b)),
+                        "Core:lt" => Some(Value::bool(a < b)),
+                        "Core:lteq" => Some(Value::bool(a <= b)),
+                        "Core:gt" => Some(Value::bool(a > b)),
+                        "Core:gteq" => Some(Value::bool(a >= b)),
+                        _ => None,
+                    }
+                }
+            }
+        }
+        node => static_value(node),
+    }
+}
+
 pub fn get_lang_version(input: &str) -> Option<String> {
     let re = Regex::new(r"^\s*///\s*@\s*([a-zA-Z0-9_.-]+)(?:[\r\n][\s\S]*)?$").unwrap();
     re.captures(input).map(|captures| captures[1].to_string())
@@ -472,8 +775,10 @@ impl V {
         ReprValue {
             value: self,
             literal_like: false,
-            processed_arrays: Rc::new(Vec::new()),
-            processed_objects: Rc::new(Vec::new()),
+            js_numbers: false,
+            depth: None,
+            processed_arrays: Arc::new(Vec::new()),
+            processed_objects: Arc::new(Vec::new()),
         }
     }
 
@@ -481,8 +786,10 @@ impl V {
         ReprValue {
             value: self,
             literal_like: true,
-            processed_arrays: Rc::new(Vec::new()),
-            processed_objects: Rc::new(Vec::new()),
+            js_numbers: false,
+            depth: None,
+            processed_arrays: Arc::new(Vec::new()),
+            processed_objects: Arc::new(Vec::new()),
         }
     }
 }
@@ -500,8 +807,61 @@ impl Value {
 pub struct ReprValue<'a> {
     value: &'a V,
     literal_like: bool,
-    processed_arrays: Rc<Vec<&'a VArr>>,
-    processed_objects: Rc<Vec<&'a VObj>>,
+    js_numbers: bool,
+    /// The number of levels of arrays and objects left to show, or `None` if unlimited.
+    depth: Option<usize>,
+    processed_arrays: Arc<Vec<&'a VArr>>,
+    processed_objects: Arc<Vec<&'a VObj>>,
+}
+
+impl ReprValue<'_> {
+    /// Formats numbers like `Number.prototype.toString` of JavaScript, e.g. `1e+21` instead of
+    /// `1000000000000000000000`.
+    pub fn with_js_numbers(self, js_numbers: bool) -> Self {
+        ReprValue { js_numbers, ..self }
+    }
+
+    /// Shows arrays and objects nested deeper than `depth` levels as `...`, as well as those in
+    /// a cycle.
+    pub fn with_max_depth(self, depth: Option<usize>) -> Self {
+        ReprValue { depth, ..self }
+    }
+
+    /// Returns the first `max_len` characters of the string followed by `...` if it is longer,
+    /// without formatting the rest.
+    pub fn to_string_limited(&self, max_len: usize) -> String {
+        let mut writer = LimitedWriter {
+            buf: String::new(),
+            remaining: max_len,
+        };
+        match std::fmt::write(&mut writer, format_args!("{self}")) {
+            Ok(()) => writer.buf,
+            Err(_) => writer.buf + "...",
+        }
+    }
+}
+
+/// A writer which fails after `remaining` characters to stop formatting.
+struct LimitedWriter {
+    buf: String,
+    remaining: usize,
+}
+
+impl std::fmt::Write for LimitedWriter {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        match s.char_indices().nth(self.remaining) {
+            Some((end, _)) => {
+                self.buf.push_str(&s[..end]);
+                self.remaining = 0;
+                Err(std::fmt::Error)
+            }
+            None => {
+                self.buf.push_str(s);
+                self.remaining -= s.chars().count();
+                Ok(())
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for ReprValue<'_> {
@@ -521,31 +881,33 @@ impl std::fmt::Display for ReprValue<'_> {
                     write!(f, "{}", value)
                 }
             }
+            V::Num(value) if self.js_numbers => write!(f, "{}", to_radix_string(*value, 10)),
             V::Num(value) => write!(f, "{}", value),
+            V::Arr(_) | V::Obj(_) if self.depth == Some(0) => write!(f, "..."),
             V::Arr(value) => {
                 if self.processed_arrays.iter().any(|v| Arc::ptr_eq(v, value)) {
                     write!(f, "...")
                 } else {
                     let mut processed_arrays = (*self.processed_arrays).clone();
                     processed_arrays.push(value);
-                    let processed_arrays = Rc::new(processed_arrays);
-                    write!(
-                        f,
-                        "[ {} ]",
-                        value
-                            .read()
-                            .unwrap()
-                            .iter()
-                            .map(|value| ReprValue {
-                                value: &value.value,
-                                literal_like: true,
-                                processed_arrays: processed_arrays.clone(),
-                                processed_objects: self.processed_objects.clone(),
-                            }
-                            .to_string())
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                    )
+                    let processed_arrays = Arc::new(processed_arrays);
+                    let items = value.read().unwrap().clone();
+                    write!(f, "[ ")?;
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        ReprValue {
+                            value: &item.value,
+                            literal_like: true,
+                            js_numbers: self.js_numbers,
+                            depth: self.depth.map(|depth| depth - 1),
+                            processed_arrays: processed_arrays.clone(),
+                            processed_objects: self.processed_objects.clone(),
+                        }
+                        .fmt(f)?;
+                    }
+                    write!(f, " ]")
                 }
             }
             V::Obj(value) => {
@@ -554,26 +916,25 @@ impl std::fmt::Display for ReprValue<'_> {
                 } else {
                     let mut processed_objects = (*self.processed_objects).clone();
                     processed_objects.push(value);
-                    let processed_objects = Rc::new(processed_objects);
-                    write!(
-                        f,
-                        "{{ {} }}",
-                        value
-                            .read()
-                            .unwrap()
-                            .iter()
-                            .map(|(key, val)| format!(
-                                "{key}: {}",
-                                ReprValue {
-                                    value: &val.value,
-                                    literal_like: true,
-                                    processed_arrays: self.processed_arrays.clone(),
-                                    processed_objects: processed_objects.clone(),
-                                }
-                            ))
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                    )
+                    let processed_objects = Arc::new(processed_objects);
+                    let entries = value.read().unwrap().clone();
+                    write!(f, "{{ ")?;
+                    for (i, (key, val)) in entries.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{key}: ")?;
+                        ReprValue {
+                            value: &val.value,
+                            literal_like: true,
+                            js_numbers: self.js_numbers,
+                            depth: self.depth.map(|depth| depth - 1),
+                            processed_arrays: self.processed_arrays.clone(),
+                            processed_objects: processed_objects.clone(),
+                        }
+                        .fmt(f)?;
+                    }
+                    write!(f, " }}")
                 }
             }
             V::Bool(value) => write!(f, "{}", value),
@@ -581,12 +942,14 @@ impl std::fmt::Display for ReprValue<'_> {
             V::Fn(value) => write!(
                 f,
                 "@( {} ) {{ ... }}",
-                if let VFn::Fn { args, .. } = value {
+                if let VFn::Fn { args, .. } = value.as_ref() {
                     args.join(", ")
                 } else {
                     String::new()
                 }
             ),
+            V::Bin(value) => write!(f, "Bin:from_hex(\"{}\")", hex::encode(value)),
+            V::NativeObject(value) => write!(f, "<{}>", value.type_name()),
             _ => write!(f, "?"),
         }
     }
@@ -1,12 +1,24 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
 
-use futures::future::BoxFuture;
+use futures::{future::BoxFuture, FutureExt};
 use indexmap::IndexMap;
 
-use crate::{error::AiScriptError, node::StatementOrExpression};
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    node::StatementOrExpression,
+};
 
 use super::{scope::Scope, Interpreter};
 
+mod capture;
+mod diff;
+
+pub use capture::Capture;
+pub use diff::{diff, Difference, ValueDiff};
+
 #[derive(Clone, Debug, Default)]
 pub enum V {
     #[default]
@@ -16,7 +28,9 @@ pub enum V {
     Str(String),
     Arr(VArr),
     Obj(VObj),
-    Fn(VFn),
+    Bin(Vec<u8>),
+    Fn(Box<VFn>),
+    NativeObject(Arc<dyn NativeObject>),
     Return(Box<Value>),
     Break,
     Continue,
@@ -28,6 +42,13 @@ pub enum V {
 
 pub type VArr = Arc<RwLock<Vec<Value>>>;
 
+/// The entries of an object, which are kept in the order of insertion.
+///
+/// The order is part of the behavior of objects: `Obj:keys`, `Obj:vals`, `Obj:kvs`,
+/// `Core:to_str` and `Json:stringify` list the entries in this order. Setting an existing
+/// key keeps its position, a new key is added to the end, `Obj:merge` adds the keys of the
+/// second object which are not in the first to the end, and `Json:parse` keeps the order in the
+/// source. Only `Obj:sort_keys` reorders the entries, into a new object.
 pub type VObj = Arc<RwLock<IndexMap<String, Value>>>;
 
 impl PartialEq for V {
@@ -42,7 +63,9 @@ impl PartialEq for V {
             (Self::Obj(l0), Self::Obj(r0)) => {
                 l0.read().unwrap().clone() == r0.read().unwrap().clone()
             }
+            (Self::Bin(l0), Self::Bin(r0)) => l0 == r0,
             (Self::Fn(_), Self::Fn(_)) => false,
+            (Self::NativeObject(l0), Self::NativeObject(r0)) => Arc::ptr_eq(l0, r0),
             (Self::Return(l0), Self::Return(r0)) => l0 == r0,
             (
                 Self::Error {
@@ -63,7 +86,7 @@ impl PartialEq for V {
 pub enum VFn {
     Fn {
         args: Vec<String>,
-        statements: Vec<StatementOrExpression>,
+        statements: Arc<[StatementOrExpression]>,
         scope: Scope,
     },
     FnNative(VFnNative),
@@ -93,6 +116,165 @@ impl std::fmt::Debug for VFn {
     }
 }
 
+impl VFn {
+    /// Returns the names of the parameters of a function defined by a script, or `None` for a
+    /// native function, whose parameters are not known.
+    pub fn params(&self) -> Option<&[String]> {
+        match self {
+            VFn::Fn { args, .. } => Some(args),
+            VFn::FnNative(_) => None,
+        }
+    }
+
+    /// Returns the number of the parameters of a function defined by a script, or `None` for a
+    /// native function.
+    pub fn arity(&self) -> Option<usize> {
+        self.params().map(<[String]>::len)
+    }
+}
+
+/// The arguments of [`Interpreter::call`], given by position and by the names of the parameters.
+///
+/// Parameters given neither by position nor by name are null, as when a script calls a function
+/// with fewer arguments than its parameters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArgMap {
+    positional: Vec<Value>,
+    named: IndexMap<String, Value>,
+}
+
+impl ArgMap {
+    pub fn new() -> Self {
+        ArgMap::default()
+    }
+
+    /// Adds an argument after the positional ones added so far.
+    pub fn arg(mut self, value: Value) -> Self {
+        self.positional.push(value);
+        self
+    }
+
+    /// Adds an argument for the parameter `name`.
+    pub fn named(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.named.insert(name.into(), value);
+        self
+    }
+
+    /// Arranges the arguments in the order of the parameters of `fn_`.
+    pub(crate) fn resolve(self, fn_: &VFn) -> Result<Vec<Value>, AiScriptError> {
+        let Some(params) = fn_.params() else {
+            if let Some(name) = self.named.keys().next() {
+                Err(AiScriptRuntimeError::Runtime(format!(
+                    "Cannot pass argument '{name}' by name to a native function."
+                )))?
+            }
+            return Ok(self.positional);
+        };
+        let given = self.positional.len();
+        let mut args = self.positional;
+        if args.len() < params.len() {
+            args.resize(params.len(), Value::null());
+        }
+        for (name, value) in self.named {
+            match params.iter().position(|param| *param == name) {
+                Some(i) if i < given => Err(AiScriptRuntimeError::Runtime(format!(
+                    "Argument '{name}' is given both by position and by name."
+                )))?,
+                Some(i) => args[i] = value,
+                None => Err(AiScriptRuntimeError::Runtime(format!(
+                    "No such parameter '{name}'."
+                )))?,
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// An opaque value owned by the host, such as a handle to a database row.
+///
+/// Scripts access it with the usual property syntax: `obj.name` calls [`Self::get_prop`],
+/// `obj.name = value` calls [`Self::set_prop`] and `obj.name(args)` calls [`Self::call_method`].
+/// Index access with a string (`obj['name']`) behaves the same as property access.
+pub trait NativeObject: std::fmt::Debug + Send + Sync {
+    /// Returns the type name of the object, which is also the result of `Core:type`.
+    fn type_name(&self) -> &str {
+        "native"
+    }
+
+    /// Returns the property `name`. Missing properties should be null like those of objects.
+    fn get_prop<'a>(
+        &'a self,
+        name: &'a str,
+        interpreter: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Value, AiScriptError>>;
+
+    /// Sets the property `name`. The object is read-only by default.
+    fn set_prop<'a>(
+        &'a self,
+        name: &'a str,
+        value: Value,
+        interpreter: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<(), AiScriptError>> {
+        let _ = (value, interpreter);
+        async move {
+            Err(AiScriptRuntimeError::Runtime(format!(
+                "Cannot set prop ({name}) of {}.",
+                self.type_name()
+            )))?
+        }
+        .boxed()
+    }
+
+    /// Returns the next item when the object is iterated by `each`, or `None` at the end.
+    /// The object is not iterable by default.
+    fn next<'a>(
+        &'a self,
+        interpreter: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Option<Value>, AiScriptError>> {
+        let _ = interpreter;
+        async move {
+            Err(AiScriptRuntimeError::Type {
+                expected: "array".to_string(),
+                got: self.type_name().to_string(),
+            })?
+        }
+        .boxed()
+    }
+
+    /// Calls the method `name`. By default, this calls the function returned by
+    /// [`Self::get_prop`].
+    fn call_method<'a>(
+        &'a self,
+        name: &'a str,
+        args: Vec<Value>,
+        interpreter: &'a Interpreter,
+    ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+        async move {
+            let method = VFn::try_from(self.get_prop(name, interpreter).await?)?;
+            interpreter.exec_fn_simple(method, args).await
+        }
+        .boxed()
+    }
+}
+
+/// Type of values which can have methods added by [`Interpreter::with_prim_prop`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrimType {
+    Num,
+    Str,
+    Arr,
+    Obj,
+    Error,
+}
+
+/// Method added by [`Interpreter::with_prim_prop`], which is called with the value whose prop
+/// it is, the arguments and the interpreter.
+pub type PrimPropFn = Arc<
+    dyn Fn(Value, Vec<Value>, &Interpreter) -> BoxFuture<'static, Result<Value, AiScriptError>>
+        + Sync
+        + Send,
+>;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Attr {
     pub name: String,
@@ -101,18 +283,47 @@ pub struct Attr {
 
 #[derive(Clone, Debug, Default)]
 pub struct Value {
-    pub value: Box<V>,
+    pub value: V,
     pub attr: Option<Vec<Attr>>,
+    pub(crate) frozen: bool,
 }
 
 impl Value {
     pub fn new(value: V) -> Self {
         Value {
-            value: value.into(),
+            value,
             attr: None,
+            frozen: false,
         }
     }
 
+    /// Freezes `value` deeply so that scripts cannot mutate it, e.g. a configuration shared
+    /// between scripts. Assigning to an element or a property of a frozen array or object, and
+    /// calling mutating methods such as `arr.push` or `Obj:set` on it, result in a runtime error.
+    ///
+    /// Values read from a frozen value are frozen as well, while copies such as `arr.copy()` are
+    /// not. The values nested in `value` are frozen in place.
+    pub fn frozen(mut value: Value) -> Self {
+        freeze(&mut value, &mut HashSet::new());
+        value
+    }
+
+    /// Returns `true` if the value is frozen by [`Self::frozen`].
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Returns an error if the value is frozen by [`Self::frozen`].
+    pub(crate) fn expect_mutable(&self) -> Result<(), AiScriptError> {
+        if self.frozen {
+            Err(AiScriptRuntimeError::Runtime(format!(
+                "Cannot mutate a frozen {}.",
+                self.display_type()
+            )))?
+        }
+        Ok(())
+    }
+
     pub fn null() -> Self {
         Value::new(V::Null)
     }
@@ -142,25 +353,75 @@ impl Value {
         ))))
     }
 
+    pub fn bin(value: impl Into<Vec<u8>>) -> Self {
+        Value::new(V::Bin(value.into()))
+    }
+
     pub fn fn_(
         args: impl IntoIterator<Item = impl Into<String>>,
         statements: impl IntoIterator<Item = StatementOrExpression>,
         scope: Scope,
     ) -> Self {
-        Value::new(V::Fn(VFn::Fn {
+        Value::new(V::Fn(Box::new(VFn::Fn {
             args: args.into_iter().map(Into::into).collect(),
             statements: statements.into_iter().collect(),
             scope,
-        }))
+        })))
     }
 
+    /// Creates a function implemented by the host.
+    ///
+    /// The closure and the future it returns must be `Send`, so that the interpreter can run on
+    /// any thread of a multi-threaded runtime. This means the closure can not capture `Rc` or
+    /// `RefCell`, and the future can not hold a guard of `std::sync::Mutex` or `RwLock` across an
+    /// `.await`; use `Arc` and an async-aware lock such as `tokio::sync::Mutex` instead.
+    ///
+    /// The closure is given the interpreter calling it, which can call back functions passed
+    /// as arguments, e.g. via [`Interpreter::exec_fn_simple`], so that hosts can implement
+    /// higher-order functions like `arr.map`. Clone the interpreter to use it in the future.
+    ///
+    /// ```
+    /// use aiscript_v0::{values::{VFn, Value}, Interpreter, Parser};
+    /// # use aiscript_v0::errors::AiScriptError;
+    /// use futures::FutureExt;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), AiScriptError> {
+    /// // Host:twice(f, x) returns f(f(x)).
+    /// let twice = Value::fn_native(|args, interpreter| {
+    ///     let interpreter = interpreter.clone();
+    ///     async move {
+    ///         let mut args = args.into_iter();
+    ///         let f = VFn::try_from(args.next().unwrap_or_default())?;
+    ///         let x = args.next().unwrap_or_default();
+    ///         let x = interpreter.exec_fn_simple(f.clone(), [x]).await?;
+    ///         interpreter.exec_fn_simple(f, [x]).await
+    ///     }
+    ///     .boxed()
+    /// });
+    /// let interpreter = Interpreter::new(
+    ///     [("Host:twice".to_string(), twice)],
+    ///     None::<fn(_) -> _>,
+    ///     None::<fn(_) -> _>,
+    ///     None::<fn(_) -> _>,
+    ///     None,
+    /// );
+    /// let script = Parser::default().parse("Host:twice(@(x) { x * 3 }, 2)")?;
+    /// assert_eq!(interpreter.exec(script).await?, Some(Value::num(18)));
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn fn_native(
         value: impl Fn(Vec<Value>, &Interpreter) -> BoxFuture<'static, Result<Value, AiScriptError>>
             + Sync
             + Send
             + 'static,
     ) -> Self {
-        Value::new(V::Fn(VFn::FnNative(Arc::new(value))))
+        Value::new(V::Fn(Box::new(VFn::FnNative(Arc::new(value)))))
+    }
+
+    pub fn native_object(value: impl NativeObject + 'static) -> Self {
+        Value::new(V::NativeObject(Arc::new(value)))
     }
 
     pub fn return_(value: Value) -> Self {
@@ -184,9 +445,28 @@ impl Value {
 }
 
 pub fn unwrap_ret(v: Value) -> Value {
-    if let V::Return(value) = *v.value {
+    if let V::Return(value) = v.value {
         *value
     } else {
         v
     }
 }
+
+/// Marks `value` and the values nested in it as frozen. `visited` keeps the containers already
+/// visited so that cyclic values terminate without locking a container twice.
+fn freeze(value: &mut Value, visited: &mut HashSet<usize>) {
+    value.frozen = true;
+    match &value.value {
+        V::Arr(arr) if visited.insert(Arc::as_ptr(arr) as usize) => {
+            for item in arr.write().unwrap().iter_mut() {
+                freeze(item, visited);
+            }
+        }
+        V::Obj(obj) if visited.insert(Arc::as_ptr(obj) as usize) => {
+            for item in obj.write().unwrap().values_mut() {
+                freeze(item, visited);
+            }
+        }
+        _ => {}
+    }
+}
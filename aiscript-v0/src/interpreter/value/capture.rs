@@ -0,0 +1,102 @@
+use std::{collections::HashSet, sync::Arc};
+
+use indexmap::IndexMap;
+
+use crate::{
+    error::{AiScriptError, AiScriptRuntimeError},
+    node as ast,
+    parser::diagnostic::ScopeInfo,
+};
+
+use super::{VFn, Value, V};
+
+/// A variable of an outer scope which a function refers to, returned by [`VFn::captures`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub name: String,
+    /// The current value of the variable, or `None` if it is not serializable.
+    pub value: Option<Value>,
+}
+
+impl VFn {
+    /// Returns the variables of outer scopes which a function defined by a script refers to, in
+    /// the order of their first appearance in the body.
+    ///
+    /// Only the values which can be serialized are exposed: null, booleans, numbers, strings,
+    /// and arrays and objects of them without cycles. Native functions, e.g. of the standard
+    /// library, are not listed, and neither are any variables of native functions.
+    pub fn captures(&self) -> Vec<Capture> {
+        let VFn::Fn {
+            args,
+            statements,
+            scope,
+        } = self
+        else {
+            return Vec::new();
+        };
+        let body = statements
+            .iter()
+            .cloned()
+            .map(ast::Node::from)
+            .collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        ScopeInfo::new(&body)
+            .unresolved
+            .into_iter()
+            .filter(|reference| !args.contains(&reference.name))
+            .filter(|reference| seen.insert(reference.name.clone()))
+            .filter_map(|reference| {
+                let value = scope.get(&reference.name).ok()?;
+                if let V::Fn(fn_) = &value.value {
+                    if let VFn::FnNative(_) = **fn_ {
+                        return None;
+                    }
+                }
+                Some(Capture {
+                    value: is_serializable(&value, &mut Vec::new()).then_some(value),
+                    name: reference.name,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the values of the variables captured by a function (see [`Self::captures`]), e.g.
+    /// to persist the state of a closure.
+    ///
+    /// Fails with [`AiScriptRuntimeError::NotSerializable`] naming the first captured variable
+    /// whose value is not serializable, such as another function defined by a script.
+    pub fn serialize_captures(&self) -> Result<IndexMap<String, Value>, AiScriptError> {
+        self.captures()
+            .into_iter()
+            .map(|capture| match capture.value {
+                Some(value) => Ok((capture.name, value)),
+                None => Err(AiScriptRuntimeError::NotSerializable(capture.name))?,
+            })
+            .collect()
+    }
+}
+
+/// `ancestors` keeps the arrays and objects containing `value`, to detect cycles.
+fn is_serializable(value: &Value, ancestors: &mut Vec<usize>) -> bool {
+    let (id, children) = match &value.value {
+        V::Null | V::Bool(_) | V::Num(_) | V::Str(_) => return true,
+        V::Arr(arr) => (
+            Arc::as_ptr(arr) as *const () as usize,
+            arr.read().unwrap().clone(),
+        ),
+        V::Obj(obj) => (
+            Arc::as_ptr(obj) as *const () as usize,
+            obj.read().unwrap().values().cloned().collect(),
+        ),
+        _ => return false,
+    };
+    if ancestors.contains(&id) {
+        return false;
+    }
+    ancestors.push(id);
+    let serializable = children
+        .iter()
+        .all(|child| is_serializable(child, ancestors));
+    ancestors.pop();
+    serializable
+}
@@ -0,0 +1,152 @@
+use std::{collections::HashSet, sync::Arc};
+
+use super::{Value, V};
+
+/// A difference between two values found by [`diff`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    /// Path to the differing value from the compared values, e.g. `[1].name`, which is empty if
+    /// the compared values differ themselves.
+    pub path: String,
+    /// The value in the expected value, or `None` if the actual value has an extra item or key.
+    pub expected: Option<Value>,
+    /// The value in the actual value, or `None` if an item or a key is missing from it.
+    pub actual: Option<Value>,
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "at the root: ")?;
+        } else {
+            write!(f, "at {}: ", self.path)?;
+        }
+        match (&self.expected, &self.actual) {
+            (Some(expected), Some(actual)) => write!(
+                f,
+                "expected {}, got {}",
+                expected.display_simple(),
+                actual.display_simple()
+            ),
+            (Some(expected), None) => write!(f, "missing {}", expected.display_simple()),
+            (None, Some(actual)) => write!(f, "unexpected {}", actual.display_simple()),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The differences between two values, one per line when displayed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValueDiff(pub Vec<Difference>);
+
+impl ValueDiff {
+    /// Returns whether the values are equal.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Display for ValueDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, difference) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            difference.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds where `actual` differs from `expected` in nested arrays and objects, e.g. to report a
+/// failed assertion in tests.
+///
+/// Values are compared in the same way as [`Value::deep_eq`], which is true if and only if the
+/// diff is empty.
+pub fn diff(expected: &Value, actual: &Value) -> ValueDiff {
+    let mut differences = Vec::new();
+    diff_at(
+        String::new(),
+        expected,
+        actual,
+        &mut differences,
+        &mut HashSet::new(),
+    );
+    ValueDiff(differences)
+}
+
+/// `visited` keeps the pairs of containers being compared, which are assumed to be equal when
+/// they are compared again in a cycle, as in [`Value::deep_eq`].
+fn diff_at(
+    path: String,
+    expected: &Value,
+    actual: &Value,
+    differences: &mut Vec<Difference>,
+    visited: &mut HashSet<(usize, usize)>,
+) {
+    match (&expected.value, &actual.value) {
+        (V::Arr(a), V::Arr(b)) => {
+            if !visited.insert((Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize)) {
+                return;
+            }
+            let a = a.read().unwrap().clone();
+            let b = b.read().unwrap().clone();
+            for i in 0..a.len().max(b.len()) {
+                let path = format!("{path}[{i}]");
+                match (a.get(i), b.get(i)) {
+                    (Some(a), Some(b)) => diff_at(path, a, b, differences, visited),
+                    (a, b) => differences.push(Difference {
+                        path,
+                        expected: a.cloned(),
+                        actual: b.cloned(),
+                    }),
+                }
+            }
+        }
+        (V::Obj(a), V::Obj(b)) => {
+            if !visited.insert((Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize)) {
+                return;
+            }
+            let a = a.read().unwrap().clone();
+            let b = b.read().unwrap().clone();
+            for (key, a) in &a {
+                let path = format!("{path}{}", key_path(key));
+                match b.get(key) {
+                    Some(b) => diff_at(path, a, b, differences, visited),
+                    None => differences.push(Difference {
+                        path,
+                        expected: Some(a.clone()),
+                        actual: None,
+                    }),
+                }
+            }
+            for (key, b) in b.into_iter().filter(|(key, _)| !a.contains_key(key)) {
+                differences.push(Difference {
+                    path: format!("{path}{}", key_path(&key)),
+                    expected: None,
+                    actual: Some(b),
+                });
+            }
+        }
+        _ if expected.deep_eq(actual) => (),
+        _ => differences.push(Difference {
+            path,
+            expected: Some(expected.clone()),
+            actual: Some(actual.clone()),
+        }),
+    }
+}
+
+/// Returns the part of a path for `key`, as a property if it can be written as one.
+fn key_path(key: &str) -> String {
+    let mut chars = key.chars();
+    if chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        format!(".{key}")
+    } else {
+        format!("[{key:?}]")
+    }
+}
@@ -38,6 +38,15 @@ pub mod ast {
 
 pub mod cst {
     pub use crate::parser::node::*;
+    pub use crate::parser::visit::Visitor;
+}
+
+pub mod diagnostics {
+    pub use crate::parser::diagnostic::*;
+}
+
+pub mod docs {
+    pub use crate::parser::docs::*;
 }
 
 pub mod errors {
@@ -48,11 +57,35 @@ pub mod utils {
     pub use crate::interpreter::util::*;
 }
 
+pub mod tokens {
+    pub use crate::parser::token::*;
+}
+
 pub mod values {
     pub use crate::interpreter::value::*;
 }
 
 pub use constants::AISCRIPT_VERSION;
-pub use interpreter::scope::Scope;
-pub use interpreter::Interpreter;
-pub use parser::{Parser, ParserPlugin, PluginType};
+pub use interpreter::channel::Channel;
+pub use interpreter::clock::{Clock, ManualClock, SystemClock};
+#[cfg(feature = "fs")]
+pub use interpreter::fs::FsCapability;
+#[cfg(feature = "http")]
+pub use interpreter::http::HttpPolicy;
+pub use interpreter::input::{InputKind, InputRequest};
+pub use interpreter::metrics::Metrics;
+pub use interpreter::permission::Capability;
+pub use interpreter::profile::ProfileEntry;
+pub use interpreter::scope::{Binding, Scope};
+pub use interpreter::step::{StepExecution, StepResult};
+pub use interpreter::storage::{MemoryStorage, Storage};
+pub use interpreter::time_travel::{Recording, Replay, Snapshot};
+pub use interpreter::{CompatProfile, Interpreter};
+pub use parser::{DiagnosticPlugin, Parser, ParserPlugin, PluginType};
+
+/// Checks whether a script definitely fails without executing it, using the default parser.
+///
+/// See [`Parser::validate`] for the problems reported.
+pub fn validate(input: &str) -> Vec<diagnostics::Diagnostic> {
+    Parser::default().validate(input)
+}
@@ -4,6 +4,18 @@
 
 use indexmap::IndexMap;
 
+#[cfg(feature = "arbitrary")]
+pub use generate::ArbitraryScript;
+pub use print::to_source;
+pub(crate) use shift::Shift;
+pub use source_map::SourceMap;
+
+#[cfg(feature = "arbitrary")]
+mod generate;
+mod print;
+mod shift;
+mod source_map;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Loc {
     pub start: usize,
@@ -18,6 +30,17 @@ pub enum Node {
     Expression(Expression),
 }
 
+impl Node {
+    /// Returns the location in the source, if any.
+    pub fn loc(&self) -> Option<&Loc> {
+        match self {
+            Node::Namespace(Namespace { loc, .. }) | Node::Meta(Meta { loc, .. }) => loc.as_ref(),
+            Node::Statement(statement) => statement.loc(),
+            Node::Expression(expression) => expression.loc(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum DefinitionOrNamespace {
     Definition(Definition),
@@ -41,6 +64,16 @@ pub enum StatementOrExpression {
     Expression(Expression),
 }
 
+impl StatementOrExpression {
+    /// Returns the location in the source, if any.
+    pub fn loc(&self) -> Option<&Loc> {
+        match self {
+            StatementOrExpression::Statement(statement) => statement.loc(),
+            StatementOrExpression::Expression(expression) => expression.loc(),
+        }
+    }
+}
+
 impl From<StatementOrExpression> for Node {
     fn from(val: StatementOrExpression) -> Self {
         match val {
@@ -60,6 +93,7 @@ pub enum StringOrExpression {
 pub enum Statement {
     Definition(Definition),
     Return(Return),
+    Yield(Yield),
     Each(Each),
     For(For),
     Loop(Loop),
@@ -70,6 +104,25 @@ pub enum Statement {
     SubAssign(SubAssign),
 }
 
+impl Statement {
+    /// Returns the location in the source, if any.
+    pub fn loc(&self) -> Option<&Loc> {
+        match self {
+            Statement::Definition(Definition { loc, .. })
+            | Statement::Return(Return { loc, .. })
+            | Statement::Yield(Yield { loc, .. })
+            | Statement::Each(Each { loc, .. })
+            | Statement::For(For { loc, .. })
+            | Statement::Loop(Loop { loc, .. })
+            | Statement::Break(Break { loc })
+            | Statement::Continue(Continue { loc })
+            | Statement::Assign(Assign { loc, .. })
+            | Statement::AddAssign(AddAssign { loc, .. })
+            | Statement::SubAssign(SubAssign { loc, .. }) => loc.as_ref(),
+        }
+    }
+}
+
 impl From<Statement> for Node {
     fn from(val: Statement) -> Self {
         Node::Statement(val)
@@ -93,12 +146,41 @@ pub enum Expression {
     Not(Not),
     And(And),
     Or(Or),
+    Coalesce(Coalesce),
     Identifier(Identifier),
     Call(Call),
     Index(Index),
     Prop(Prop),
 }
 
+impl Expression {
+    /// Returns the location in the source, if any.
+    pub fn loc(&self) -> Option<&Loc> {
+        match self {
+            Expression::If(If { loc, .. })
+            | Expression::Fn(Fn { loc, .. })
+            | Expression::Match(Match { loc, .. })
+            | Expression::Block(Block { loc, .. })
+            | Expression::Exists(Exists { loc, .. })
+            | Expression::Tmpl(Tmpl { loc, .. })
+            | Expression::Str(Str { loc, .. })
+            | Expression::Num(Num { loc, .. })
+            | Expression::Bool(Bool { loc, .. })
+            | Expression::Null(Null { loc })
+            | Expression::Obj(Obj { loc, .. })
+            | Expression::Arr(Arr { loc, .. })
+            | Expression::Not(Not { loc, .. })
+            | Expression::And(And { loc, .. })
+            | Expression::Or(Or { loc, .. })
+            | Expression::Coalesce(Coalesce { loc, .. })
+            | Expression::Identifier(Identifier { loc, .. })
+            | Expression::Call(Call { loc, .. })
+            | Expression::Index(Index { loc, .. })
+            | Expression::Prop(Prop { loc, .. }) => loc.as_ref(),
+        }
+    }
+}
+
 impl From<Expression> for Node {
     fn from(val: Expression) -> Self {
         Node::Expression(val)
@@ -147,6 +229,13 @@ pub struct Return {
     pub loc: Option<Loc>,
 }
 
+// yield文
+#[derive(Debug, PartialEq, Clone)]
+pub struct Yield {
+    pub expr: Expression, // 式
+    pub loc: Option<Loc>,
+}
+
 // each文
 #[derive(Debug, PartialEq, Clone)]
 pub struct Each {
@@ -233,6 +322,15 @@ pub struct Or {
     pub loc: Option<Loc>,
 }
 
+// null合体演算子 (`??`)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Coalesce {
+    pub left: Box<Expression>,
+    pub right: Box<Expression>,
+    pub operator_loc: Loc,
+    pub loc: Option<Loc>,
+}
+
 // if式
 #[derive(Debug, PartialEq, Clone)]
 pub struct If {
@@ -255,6 +353,7 @@ pub struct Fn {
     pub args: Vec<Arg>,
     pub ret_type: Option<TypeSource>,         // 戻り値の型
     pub children: Vec<StatementOrExpression>, // 本体処理
+    pub generator: bool,                      // ジェネレータ関数か否か
     pub loc: Option<Loc>,
 }
 
@@ -275,10 +374,29 @@ pub struct Match {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct QA {
-    pub q: Expression,            // 条件
+    pub q: Pattern,               // 条件
     pub a: StatementOrExpression, // 結果
 }
 
+// パターン
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    /// Matches a value equal to the expression.
+    Expr(Expression),
+    /// Matches any value and binds it to the name.
+    Bind { name: String, loc: Option<Loc> },
+    /// Matches an array of the same length whose items match the patterns.
+    Arr {
+        items: Vec<Pattern>,
+        loc: Option<Loc>,
+    },
+    /// Matches an object which has all the keys and whose values match the patterns.
+    Obj {
+        entries: IndexMap<String, Pattern>,
+        loc: Option<Loc>,
+    },
+}
+
 // ブロックまたはeval式
 #[derive(Debug, PartialEq, Clone)]
 pub struct Block {
@@ -353,6 +471,7 @@ pub struct Identifier {
 pub struct Call {
     pub target: Box<Expression>, // 対象
     pub args: Vec<Expression>,   // 引数
+    pub optional: bool,          // `?.()` か否か
     pub loc: Option<Loc>,
 }
 
@@ -361,6 +480,7 @@ pub struct Call {
 pub struct Index {
     pub target: Box<Expression>, // 対象
     pub index: Box<Expression>,  // インデックス
+    pub optional: bool,          // `?.[]` か否か
     pub loc: Option<Loc>,
 }
 
@@ -369,6 +489,7 @@ pub struct Index {
 pub struct Prop {
     pub target: Box<Expression>, // 対象
     pub name: String,            // プロパティ名
+    pub optional: bool,          // `?.` か否か
     pub loc: Option<Loc>,
 }
 
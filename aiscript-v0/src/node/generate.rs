@@ -0,0 +1,455 @@
+//! 構文を考慮したASTの生成
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use indexmap::IndexMap;
+
+use super::*;
+
+const NAMES: [&str; 6] = ["a", "b", "c", "f", "x", "y"];
+const GLOBAL_NAMES: [&str; 5] = ["print", "Core:add", "Core:eq", "Core:not", "Ns:a"];
+const NAMESPACES: [&str; 2] = ["Ns", "Sub"];
+const PROPS: [&str; 3] = ["x", "y", "len"];
+const CHARS: [char; 12] = [
+    'a', 'Z', '0', ' ', '\n', '\'', '"', '`', '{', '}', '/', 'あ',
+];
+
+/// A script generated according to the grammar, for fuzzing and property tests.
+///
+/// [`to_source`](super::to_source) of the script is always accepted by [`Parser`](crate::Parser)
+/// and is parsed into the same script except for locations.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArbitraryScript(pub Vec<Node>);
+
+impl<'a> Arbitrary<'a> for ArbitraryScript {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut nodes = Vec::new();
+        for _ in 0..u.int_in_range(0..=6)? {
+            nodes.push(match u.int_in_range(0..=9)? {
+                0 => Node::Namespace(namespace(u, 2)?),
+                1 => {
+                    let name = if u.arbitrary()? { Some(name(u)?) } else { None };
+                    let value = loop {
+                        let value = static_literal(u, 2)?;
+                        // `### null` is parsed as a name followed by the value on the next line
+                        if name.is_some()
+                            || !matches!(value, Expression::Bool(_) | Expression::Null(_))
+                        {
+                            break value;
+                        }
+                    };
+                    Node::Meta(Meta {
+                        name,
+                        value,
+                        loc: None,
+                    })
+                }
+                2 => Node::Statement(Statement::Definition(definition(u, 3, true)?)),
+                _ => statement_or_expression(u, 3)?.into(),
+            });
+        }
+        Ok(ArbitraryScript(nodes))
+    }
+}
+
+fn name(u: &mut Unstructured) -> Result<String> {
+    Ok(u.choose(&NAMES)?.to_string())
+}
+
+fn string(u: &mut Unstructured, min_len: usize) -> Result<String> {
+    let len = u.int_in_range(min_len..=min_len + 4)?;
+    (0..len).map(|_| u.choose(&CHARS).copied()).collect()
+}
+
+fn namespace(u: &mut Unstructured, depth: usize) -> Result<Namespace> {
+    let mut members = Vec::new();
+    for _ in 0..u.int_in_range(0..=3)? {
+        members.push(if depth > 0 && u.ratio(1, 4)? {
+            DefinitionOrNamespace::Namespace(namespace(u, depth - 1)?)
+        } else {
+            DefinitionOrNamespace::Definition(Definition {
+                mut_: false,
+                ..definition(u, depth, false)?
+            })
+        });
+    }
+    Ok(Namespace {
+        name: u.choose(&NAMESPACES)?.to_string(),
+        members,
+        loc: None,
+    })
+}
+
+fn definition(u: &mut Unstructured, depth: usize, attr: bool) -> Result<Definition> {
+    let attr = if attr {
+        let mut attrs = Vec::new();
+        for _ in 0..u.int_in_range(0..=2)? {
+            attrs.push(Attribute {
+                name: name(u)?,
+                value: static_literal(u, 1)?,
+                loc: None,
+            });
+        }
+        Some(attrs)
+    } else {
+        None
+    };
+    Ok(Definition {
+        name: name(u)?,
+        expr: expression(u, depth)?,
+        var_type: if u.ratio(1, 4)? {
+            Some(type_source(u, 2)?)
+        } else {
+            None
+        },
+        mut_: u.arbitrary()?,
+        attr,
+        loc: None,
+    })
+}
+
+fn type_source(u: &mut Unstructured, depth: usize) -> Result<TypeSource> {
+    if depth > 0 && u.ratio(1, 4)? {
+        let mut args = Vec::new();
+        for _ in 0..u.int_in_range(0..=2)? {
+            args.push(type_source(u, depth - 1)?);
+        }
+        return Ok(TypeSource::FnTypeSource(FnTypeSource {
+            args,
+            result: type_source(u, depth - 1)?.into(),
+            loc: None,
+        }));
+    }
+    let name = *u.choose(&["null", "bool", "num", "str", "any", "void", "arr", "obj"])?;
+    Ok(TypeSource::NamedTypeSource(NamedTypeSource {
+        name: name.to_string(),
+        inner: if depth > 0 && matches!(name, "arr" | "obj") && u.arbitrary()? {
+            Some(type_source(u, depth - 1)?.into())
+        } else {
+            None
+        },
+        loc: None,
+    }))
+}
+
+fn statements(
+    u: &mut Unstructured,
+    depth: usize,
+    min_len: usize,
+) -> Result<Vec<StatementOrExpression>> {
+    let mut statements = Vec::new();
+    for _ in 0..u.int_in_range(min_len..=min_len + 2)? {
+        statements.push(statement_or_expression(u, depth)?);
+    }
+    Ok(statements)
+}
+
+/// A block which is the body of `if`, `each`, `for` and `match`.
+fn block(u: &mut Unstructured, depth: usize) -> Result<StatementOrExpression> {
+    Ok(StatementOrExpression::Expression(Expression::Block(
+        Block {
+            statements: statements(u, depth, 0)?,
+            loc: None,
+        },
+    )))
+}
+
+fn statement_or_expression(u: &mut Unstructured, depth: usize) -> Result<StatementOrExpression> {
+    Ok(if u.arbitrary()? {
+        StatementOrExpression::Statement(statement(u, depth)?)
+    } else {
+        StatementOrExpression::Expression(expression(u, depth)?)
+    })
+}
+
+fn dest(u: &mut Unstructured, depth: usize) -> Result<Expression> {
+    let identifier = Expression::Identifier(Identifier {
+        name: name(u)?,
+        loc: None,
+    });
+    Ok(match u.int_in_range(0..=2)? {
+        0 => Expression::Index(Index {
+            target: identifier.into(),
+            index: expression(u, depth)?.into(),
+            optional: false,
+            loc: None,
+        }),
+        1 => Expression::Prop(Prop {
+            target: identifier.into(),
+            name: u.choose(&PROPS)?.to_string(),
+            optional: false,
+            loc: None,
+        }),
+        _ => identifier,
+    })
+}
+
+fn statement(u: &mut Unstructured, depth: usize) -> Result<Statement> {
+    let depth = depth.saturating_sub(1);
+    Ok(match u.int_in_range(0..=11)? {
+        0 => Statement::Return(Return {
+            expr: expression(u, depth)?,
+            loc: None,
+        }),
+        11 => Statement::Yield(Yield {
+            expr: expression(u, depth)?,
+            loc: None,
+        }),
+        1 => Statement::Each(Each {
+            var: name(u)?,
+            items: expression(u, depth)?,
+            for_: block(u, depth)?.into(),
+            loc: None,
+        }),
+        2 => Statement::For(For {
+            var: Some(name(u)?),
+            from: Some(expression(u, depth)?),
+            to: Some(expression(u, depth)?),
+            times: None,
+            for_: block(u, depth)?.into(),
+            loc: None,
+        }),
+        3 => Statement::For(For {
+            var: None,
+            from: None,
+            to: None,
+            times: Some(expression(u, depth)?),
+            for_: block(u, depth)?.into(),
+            loc: None,
+        }),
+        4 => Statement::Loop(Loop {
+            statements: statements(u, depth, 1)?,
+            loc: None,
+        }),
+        5 => Statement::Break(Break { loc: None }),
+        6 => Statement::Continue(Continue { loc: None }),
+        7 => Statement::Assign(Assign {
+            dest: dest(u, depth)?,
+            expr: expression(u, depth)?,
+            loc: None,
+        }),
+        8 => Statement::AddAssign(AddAssign {
+            dest: dest(u, depth)?,
+            expr: expression(u, depth)?,
+            loc: None,
+        }),
+        9 => Statement::SubAssign(SubAssign {
+            dest: dest(u, depth)?,
+            expr: expression(u, depth)?,
+            loc: None,
+        }),
+        _ => Statement::Definition(definition(u, depth, false)?),
+    })
+}
+
+fn num(u: &mut Unstructured) -> Result<f64> {
+    Ok(if u.arbitrary()? {
+        u.int_in_range(0..=100)? as f64
+    } else {
+        u.int_in_range(0..=10000)? as f64 / 100.0
+    })
+}
+
+fn static_literal(u: &mut Unstructured, depth: usize) -> Result<Expression> {
+    let max = if depth > 0 { 5 } else { 3 };
+    Ok(match u.int_in_range(0..=max)? {
+        0 => Expression::Num(Num {
+            value: num(u)?,
+            loc: None,
+        }),
+        1 => Expression::Str(Str {
+            value: string(u, 0)?,
+            loc: None,
+        }),
+        2 => Expression::Bool(Bool {
+            value: u.arbitrary()?,
+            loc: None,
+        }),
+        3 => Expression::Null(Null { loc: None }),
+        4 => {
+            let mut value = Vec::new();
+            for _ in 0..u.int_in_range(0..=3)? {
+                value.push(static_literal(u, depth - 1)?);
+            }
+            Expression::Arr(Arr { value, loc: None })
+        }
+        _ => {
+            let mut value = IndexMap::new();
+            for _ in 0..u.int_in_range(0..=3)? {
+                value.insert(name(u)?, static_literal(u, depth - 1)?);
+            }
+            Expression::Obj(Obj { value, loc: None })
+        }
+    })
+}
+
+fn expression(u: &mut Unstructured, depth: usize) -> Result<Expression> {
+    if depth == 0 || u.ratio(1, 3)? {
+        return Ok(match u.int_in_range(0..=2)? {
+            0 => Expression::Identifier(Identifier {
+                name: if u.arbitrary()? {
+                    name(u)?
+                } else {
+                    u.choose(&GLOBAL_NAMES)?.to_string()
+                },
+                loc: None,
+            }),
+            1 => Expression::Exists(Exists {
+                identifier: Identifier {
+                    name: name(u)?,
+                    loc: None,
+                },
+                loc: None,
+            }),
+            _ => static_literal(u, 0)?,
+        });
+    }
+    let depth = depth - 1;
+    Ok(match u.int_in_range(0..=15)? {
+        0 => {
+            let mut elseif = Vec::new();
+            for _ in 0..u.int_in_range(0..=1)? {
+                elseif.push(Elseif {
+                    cond: expression(u, depth)?,
+                    then: block(u, depth)?,
+                });
+            }
+            Expression::If(If {
+                cond: expression(u, depth)?.into(),
+                then: block(u, depth)?.into(),
+                elseif,
+                else_: if u.arbitrary()? {
+                    Some(block(u, depth)?.into())
+                } else {
+                    None
+                },
+                loc: None,
+            })
+        }
+        1 => {
+            let mut args = Vec::new();
+            for _ in 0..u.int_in_range(0..=2)? {
+                args.push(Arg {
+                    name: name(u)?,
+                    arg_type: if u.ratio(1, 4)? {
+                        Some(type_source(u, 1)?)
+                    } else {
+                        None
+                    },
+                });
+            }
+            Expression::Fn(Fn {
+                args,
+                ret_type: if u.ratio(1, 4)? {
+                    Some(type_source(u, 1)?)
+                } else {
+                    None
+                },
+                children: statements(u, depth, 0)?,
+                generator: u.arbitrary()?,
+                loc: None,
+            })
+        }
+        2 => {
+            let mut qs = Vec::new();
+            for _ in 0..u.int_in_range(1..=2)? {
+                qs.push(QA {
+                    q: Pattern::Expr(expression(u, depth)?),
+                    a: if u.arbitrary()? {
+                        block(u, depth)?
+                    } else {
+                        StatementOrExpression::Expression(expression(u, depth)?)
+                    },
+                });
+            }
+            Expression::Match(Match {
+                about: expression(u, depth)?.into(),
+                qs,
+                default: if u.arbitrary()? {
+                    Some(block(u, depth)?.into())
+                } else {
+                    None
+                },
+                loc: None,
+            })
+        }
+        3 => Expression::Block(Block {
+            statements: statements(u, depth, 1)?,
+            loc: None,
+        }),
+        4 => {
+            let mut tmpl = Vec::new();
+            for _ in 0..u.int_in_range(0..=3)? {
+                // adjacent strings are parsed as one string
+                let is_string =
+                    !matches!(tmpl.last(), Some(StringOrExpression::String(_))) && u.arbitrary()?;
+                tmpl.push(if is_string {
+                    StringOrExpression::String(string(u, 1)?)
+                } else {
+                    StringOrExpression::Expression(expression(u, depth)?)
+                });
+            }
+            Expression::Tmpl(Tmpl { tmpl, loc: None })
+        }
+        5 => {
+            let mut value = IndexMap::new();
+            for _ in 0..u.int_in_range(0..=3)? {
+                value.insert(name(u)?, expression(u, depth)?);
+            }
+            Expression::Obj(Obj { value, loc: None })
+        }
+        6 => {
+            let mut value = Vec::new();
+            for _ in 0..u.int_in_range(0..=3)? {
+                value.push(expression(u, depth)?);
+            }
+            Expression::Arr(Arr { value, loc: None })
+        }
+        7 => Expression::Not(Not {
+            expr: expression(u, depth)?.into(),
+            loc: None,
+        }),
+        8 => Expression::And(And {
+            left: expression(u, depth)?.into(),
+            right: expression(u, depth)?.into(),
+            operator_loc: Loc { start: 0, end: 0 },
+            loc: None,
+        }),
+        9 => Expression::Or(Or {
+            left: expression(u, depth)?.into(),
+            right: expression(u, depth)?.into(),
+            operator_loc: Loc { start: 0, end: 0 },
+            loc: None,
+        }),
+        10 | 11 => {
+            let mut args = Vec::new();
+            for _ in 0..u.int_in_range(0..=2)? {
+                args.push(expression(u, depth)?);
+            }
+            Expression::Call(Call {
+                target: expression(u, depth)?.into(),
+                args,
+                optional: u.arbitrary()?,
+                loc: None,
+            })
+        }
+        12 => Expression::Index(Index {
+            target: expression(u, depth)?.into(),
+            index: expression(u, depth)?.into(),
+            optional: u.arbitrary()?,
+            loc: None,
+        }),
+        13 => Expression::Prop(Prop {
+            target: expression(u, depth)?.into(),
+            name: u.choose(&PROPS)?.to_string(),
+            optional: u.arbitrary()?,
+            loc: None,
+        }),
+        14 => Expression::Coalesce(Coalesce {
+            left: expression(u, depth)?.into(),
+            right: expression(u, depth)?.into(),
+            operator_loc: Loc { start: 0, end: 0 },
+            loc: None,
+        }),
+        _ => static_literal(u, depth)?,
+    })
+}
@@ -0,0 +1,464 @@
+//! ASTからソースコードへの変換
+
+use std::fmt::{Display, Formatter, Result, Write};
+
+use super::*;
+
+/// Converts a script into source code which is parsed into the same script, except for locations.
+///
+/// Some scripts cannot be represented in the syntax and are parsed differently:
+/// strings which contain a backslash, and metadata without a name whose value is `true`, `false` or `null`.
+pub fn to_source(script: &[Node]) -> String {
+    let mut source = String::new();
+    for node in script {
+        writeln!(source, "{node}").unwrap();
+    }
+    source
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        Printer::new(f).node(self)
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        Printer::new(f).statement(self)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        Printer::new(f).expression(self)
+    }
+}
+
+struct Printer<'a, 'b> {
+    f: &'a mut Formatter<'b>,
+    indent: usize,
+}
+
+impl<'a, 'b> Printer<'a, 'b> {
+    fn new(f: &'a mut Formatter<'b>) -> Self {
+        Printer { f, indent: 0 }
+    }
+
+    fn newline(&mut self) -> Result {
+        writeln!(self.f)?;
+        for _ in 0..self.indent {
+            write!(self.f, "\t")?;
+        }
+        Ok(())
+    }
+
+    /// Writes `{`, the items on separate lines and `}`.
+    fn lines<T>(&mut self, items: &[T], mut item: impl FnMut(&mut Self, &T) -> Result) -> Result {
+        write!(self.f, "{{")?;
+        if !items.is_empty() {
+            self.indent += 1;
+            for x in items {
+                self.newline()?;
+                item(self, x)?;
+            }
+            self.indent -= 1;
+            self.newline()?;
+        }
+        write!(self.f, "}}")
+    }
+
+    fn list<T>(&mut self, items: &[T], mut item: impl FnMut(&mut Self, &T) -> Result) -> Result {
+        for (i, x) in items.iter().enumerate() {
+            if i > 0 {
+                write!(self.f, ", ")?;
+            }
+            item(self, x)?;
+        }
+        Ok(())
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> Result {
+        match pattern {
+            Pattern::Expr(expression) => self.expression(expression),
+            Pattern::Bind { name, .. } => write!(self.f, "{name}"),
+            Pattern::Arr { items, .. } => {
+                write!(self.f, "[")?;
+                self.list(items, |p, item| p.pattern(item))?;
+                write!(self.f, "]")
+            }
+            Pattern::Obj { entries, .. } => {
+                if entries.is_empty() {
+                    return write!(self.f, "{{}}");
+                }
+                write!(self.f, "{{ ")?;
+                let entries = entries.iter().collect::<Vec<_>>();
+                self.list(&entries, |p, (key, value)| {
+                    write!(p.f, "{key}: ")?;
+                    p.pattern(value)
+                })?;
+                write!(self.f, " }}")
+            }
+        }
+    }
+
+    fn node(&mut self, node: &Node) -> Result {
+        match node {
+            Node::Namespace(namespace) => self.namespace(namespace),
+            Node::Meta(Meta { name, value, .. }) => {
+                write!(self.f, "###")?;
+                if let Some(name) = name {
+                    write!(self.f, " {name}")?;
+                }
+                write!(self.f, " ")?;
+                self.expression(value)
+            }
+            Node::Statement(statement) => self.statement(statement),
+            Node::Expression(expression) => self.expression(expression),
+        }
+    }
+
+    fn namespace(&mut self, namespace: &Namespace) -> Result {
+        write!(self.f, ":: {} ", namespace.name)?;
+        self.lines(&namespace.members, |p, member| match member {
+            DefinitionOrNamespace::Definition(definition) => p.definition(definition),
+            DefinitionOrNamespace::Namespace(namespace) => p.namespace(namespace),
+        })
+    }
+
+    fn definition(&mut self, definition: &Definition) -> Result {
+        for Attribute { name, value, .. } in definition.attr.iter().flatten() {
+            write!(self.f, "#[{name} ")?;
+            self.expression(value)?;
+            write!(self.f, "]")?;
+            self.newline()?;
+        }
+        write!(
+            self.f,
+            "{} {}",
+            if definition.mut_ { "var" } else { "let" },
+            definition.name
+        )?;
+        if let Some(var_type) = &definition.var_type {
+            write!(self.f, ": ")?;
+            self.type_source(var_type)?;
+        }
+        write!(self.f, " = ")?;
+        self.expression(&definition.expr)
+    }
+
+    fn type_source(&mut self, type_source: &TypeSource) -> Result {
+        match type_source {
+            TypeSource::NamedTypeSource(NamedTypeSource { name, inner, .. }) => {
+                write!(self.f, "{name}")?;
+                if let Some(inner) = inner {
+                    write!(self.f, "<")?;
+                    self.type_source(inner)?;
+                    write!(self.f, ">")?;
+                }
+                Ok(())
+            }
+            TypeSource::FnTypeSource(FnTypeSource { args, result, .. }) => {
+                write!(self.f, "@(")?;
+                self.list(args, |p, arg| p.type_source(arg))?;
+                write!(self.f, ") => ")?;
+                self.type_source(result)
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &[StatementOrExpression]) -> Result {
+        self.lines(statements, |p, statement| {
+            p.statement_or_expression(statement)
+        })
+    }
+
+    fn statement_or_expression(&mut self, node: &StatementOrExpression) -> Result {
+        match node {
+            StatementOrExpression::Statement(statement) => self.statement(statement),
+            StatementOrExpression::Expression(expression) => self.expression(expression),
+        }
+    }
+
+    /// Writes the body of `if`, `each`, `for` and `match`.
+    fn block_or_statement(&mut self, node: &StatementOrExpression) -> Result {
+        match node {
+            StatementOrExpression::Expression(Expression::Block(Block { statements, .. })) => {
+                self.statements(statements)
+            }
+            // `{` here starts a block
+            StatementOrExpression::Expression(expression @ Expression::Obj(_)) => {
+                self.parenthesized(expression)
+            }
+            node => self.statement_or_expression(node),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result {
+        match statement {
+            Statement::Definition(definition) => self.definition(definition),
+            Statement::Return(Return { expr, .. }) => {
+                write!(self.f, "return ")?;
+                self.expression(expr)
+            }
+            Statement::Yield(Yield { expr, .. }) => {
+                write!(self.f, "yield ")?;
+                self.expression(expr)
+            }
+            Statement::Each(Each {
+                var, items, for_, ..
+            }) => {
+                write!(self.f, "each (let {var}, ")?;
+                self.expression(items)?;
+                write!(self.f, ") ")?;
+                self.block_or_statement(for_)
+            }
+            Statement::For(For {
+                var,
+                from,
+                to,
+                times,
+                for_,
+                ..
+            }) => {
+                write!(self.f, "for (")?;
+                if let Some(var) = var {
+                    write!(self.f, "let {var}")?;
+                    if let Some(from) = from {
+                        write!(self.f, " = ")?;
+                        self.expression(from)?;
+                    }
+                    write!(self.f, ", ")?;
+                }
+                if let Some(times) = to.as_ref().or(times.as_ref()) {
+                    self.expression(times)?;
+                }
+                write!(self.f, ") ")?;
+                self.block_or_statement(for_)
+            }
+            Statement::Loop(Loop { statements, .. }) => {
+                write!(self.f, "loop ")?;
+                self.statements(statements)
+            }
+            Statement::Break(_) => write!(self.f, "break"),
+            Statement::Continue(_) => write!(self.f, "continue"),
+            Statement::Assign(Assign { dest, expr, .. }) => self.assign(dest, "=", expr),
+            Statement::AddAssign(AddAssign { dest, expr, .. }) => self.assign(dest, "+=", expr),
+            Statement::SubAssign(SubAssign { dest, expr, .. }) => self.assign(dest, "-=", expr),
+        }
+    }
+
+    fn assign(&mut self, dest: &Expression, op: &str, expr: &Expression) -> Result {
+        self.expression(dest)?;
+        write!(self.f, " {op} ")?;
+        self.expression(expr)
+    }
+
+    fn parenthesized(&mut self, expression: &Expression) -> Result {
+        write!(self.f, "(")?;
+        self.expression(expression)?;
+        write!(self.f, ")")
+    }
+
+    /// Writes an operand of `!`, `&&` and `||` or a target of a call, an index or a property,
+    /// parenthesized if it would be parsed differently otherwise.
+    fn operand(&mut self, expression: &Expression) -> Result {
+        match expression {
+            Expression::Tmpl(_)
+            | Expression::Str(_)
+            | Expression::Bool(_)
+            | Expression::Null(_)
+            | Expression::Obj(_)
+            | Expression::Arr(_)
+            | Expression::And(_)
+            | Expression::Or(_)
+            | Expression::Coalesce(_)
+            | Expression::Identifier(_)
+            | Expression::Call(_)
+            | Expression::Index(_)
+            | Expression::Prop(_) => self.expression(expression),
+            _ => self.parenthesized(expression),
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Result {
+        match expression {
+            Expression::If(If {
+                cond,
+                then,
+                elseif,
+                else_,
+                ..
+            }) => {
+                write!(self.f, "if ")?;
+                self.expression(cond)?;
+                write!(self.f, " ")?;
+                self.block_or_statement(then)?;
+                for Elseif { cond, then } in elseif {
+                    write!(self.f, " elif ")?;
+                    self.expression(cond)?;
+                    write!(self.f, " ")?;
+                    self.block_or_statement(then)?;
+                }
+                if let Some(else_) = else_ {
+                    write!(self.f, " else ")?;
+                    self.block_or_statement(else_)?;
+                }
+                Ok(())
+            }
+            Expression::Fn(Fn {
+                args,
+                ret_type,
+                children,
+                generator,
+                ..
+            }) => {
+                write!(self.f, "@")?;
+                if *generator {
+                    write!(self.f, "*")?;
+                }
+                write!(self.f, "(")?;
+                self.list(args, |p, Arg { name, arg_type }| {
+                    write!(p.f, "{name}")?;
+                    if let Some(arg_type) = arg_type {
+                        write!(p.f, ": ")?;
+                        p.type_source(arg_type)?;
+                    }
+                    Ok(())
+                })?;
+                write!(self.f, ")")?;
+                if let Some(ret_type) = ret_type {
+                    write!(self.f, ": ")?;
+                    self.type_source(ret_type)?;
+                }
+                write!(self.f, " ")?;
+                self.statements(children)
+            }
+            Expression::Match(Match {
+                about, qs, default, ..
+            }) => {
+                write!(self.f, "match ")?;
+                self.expression(about)?;
+                write!(self.f, " ")?;
+                let arms = qs
+                    .iter()
+                    .map(|QA { q, a }| (Some(q), a))
+                    .chain(default.iter().map(|default| (None, default.as_ref())))
+                    .collect::<Vec<_>>();
+                self.lines(&arms, |p, (q, a)| {
+                    match q {
+                        Some(Pattern::Expr(q)) => p.expression(q)?,
+                        Some(q) => {
+                            write!(p.f, "case ")?;
+                            p.pattern(q)?
+                        }
+                        None => write!(p.f, "*")?,
+                    }
+                    write!(p.f, " => ")?;
+                    p.block_or_statement(a)
+                })
+            }
+            Expression::Block(Block { statements, .. }) => {
+                write!(self.f, "eval ")?;
+                self.statements(statements)
+            }
+            Expression::Exists(Exists { identifier, .. }) => {
+                write!(self.f, "exists {}", identifier.name)
+            }
+            Expression::Tmpl(Tmpl { tmpl, .. }) => {
+                write!(self.f, "`")?;
+                for x in tmpl {
+                    match x {
+                        StringOrExpression::String(s) => {
+                            for c in s.chars() {
+                                if matches!(c, '`' | '{' | '}') {
+                                    write!(self.f, "\\")?;
+                                }
+                                write!(self.f, "{c}")?;
+                            }
+                        }
+                        StringOrExpression::Expression(x) => {
+                            write!(self.f, "{{")?;
+                            self.expression(x)?;
+                            write!(self.f, "}}")?;
+                        }
+                    }
+                }
+                write!(self.f, "`")
+            }
+            Expression::Str(Str { value, .. }) => {
+                if value.contains('"') && !value.contains('\'') {
+                    write!(self.f, "'{value}'")
+                } else {
+                    write!(self.f, "\"{}\"", value.replace('"', "\\\""))
+                }
+            }
+            Expression::Num(Num { value, .. }) => write!(self.f, "{value}"),
+            Expression::Bool(Bool { value, .. }) => write!(self.f, "{value}"),
+            Expression::Null(_) => write!(self.f, "null"),
+            Expression::Obj(Obj { value, .. }) => {
+                if value.is_empty() {
+                    return write!(self.f, "{{}}");
+                }
+                write!(self.f, "{{ ")?;
+                let value = value.iter().collect::<Vec<_>>();
+                self.list(&value, |p, (key, value)| {
+                    write!(p.f, "{key}: ")?;
+                    p.expression(value)
+                })?;
+                write!(self.f, " }}")
+            }
+            Expression::Arr(Arr { value, .. }) => {
+                write!(self.f, "[")?;
+                self.list(value, |p, item| p.expression(item))?;
+                write!(self.f, "]")
+            }
+            Expression::Not(Not { expr, .. }) => {
+                write!(self.f, "!")?;
+                self.operand(expr)
+            }
+            Expression::And(And { left, right, .. }) => self.binary(left, "&&", right),
+            Expression::Or(Or { left, right, .. }) => self.binary(left, "||", right),
+            Expression::Coalesce(Coalesce { left, right, .. }) => self.binary(left, "??", right),
+            Expression::Identifier(Identifier { name, .. }) => write!(self.f, "{name}"),
+            Expression::Call(Call {
+                target,
+                args,
+                optional,
+                ..
+            }) => {
+                self.operand(target)?;
+                write!(self.f, "{}(", if *optional { "?." } else { "" })?;
+                self.list(args, |p, arg| p.expression(arg))?;
+                write!(self.f, ")")
+            }
+            Expression::Index(Index {
+                target,
+                index,
+                optional,
+                ..
+            }) => {
+                self.operand(target)?;
+                write!(self.f, "{}[", if *optional { "?." } else { "" })?;
+                self.expression(index)?;
+                write!(self.f, "]")
+            }
+            Expression::Prop(Prop {
+                target,
+                name,
+                optional,
+                ..
+            }) => {
+                self.operand(target)?;
+                write!(self.f, "{}.{name}", if *optional { "?" } else { "" })
+            }
+        }
+    }
+
+    fn binary(&mut self, left: &Expression, op: &str, right: &Expression) -> Result {
+        write!(self.f, "(")?;
+        self.operand(left)?;
+        write!(self.f, " {op} ")?;
+        self.operand(right)?;
+        write!(self.f, ")")
+    }
+}
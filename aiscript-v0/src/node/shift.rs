@@ -0,0 +1,295 @@
+//! ASTの位置情報の移動
+
+use super::*;
+
+/// Moves locations of nodes, e.g. after an edit before them.
+pub(crate) trait Shift {
+    /// Moves all locations in the node from `from` to `to`, where `from` is not after any of them.
+    fn shift(&mut self, from: usize, to: usize);
+}
+
+impl Shift for Loc {
+    fn shift(&mut self, from: usize, to: usize) {
+        // The end of an empty location is right before `from` if it starts at `from`.
+        self.start = self.start.wrapping_sub(from).wrapping_add(to);
+        self.end = self.end.wrapping_sub(from).wrapping_add(to);
+    }
+}
+
+impl<T: Shift> Shift for Option<T> {
+    fn shift(&mut self, from: usize, to: usize) {
+        if let Some(value) = self {
+            value.shift(from, to);
+        }
+    }
+}
+
+impl<T: Shift> Shift for Box<T> {
+    fn shift(&mut self, from: usize, to: usize) {
+        self.as_mut().shift(from, to);
+    }
+}
+
+impl<T: Shift> Shift for Vec<T> {
+    fn shift(&mut self, from: usize, to: usize) {
+        for value in self {
+            value.shift(from, to);
+        }
+    }
+}
+
+impl Shift for Node {
+    fn shift(&mut self, from: usize, to: usize) {
+        match self {
+            Node::Namespace(namespace) => namespace.shift(from, to),
+            Node::Meta(Meta { value, loc, .. }) => {
+                value.shift(from, to);
+                loc.shift(from, to);
+            }
+            Node::Statement(statement) => statement.shift(from, to),
+            Node::Expression(expression) => expression.shift(from, to),
+        }
+    }
+}
+
+impl Shift for Namespace {
+    fn shift(&mut self, from: usize, to: usize) {
+        for member in &mut self.members {
+            match member {
+                DefinitionOrNamespace::Definition(definition) => definition.shift(from, to),
+                DefinitionOrNamespace::Namespace(namespace) => namespace.shift(from, to),
+            }
+        }
+        self.loc.shift(from, to);
+    }
+}
+
+impl Shift for Definition {
+    fn shift(&mut self, from: usize, to: usize) {
+        self.expr.shift(from, to);
+        self.var_type.shift(from, to);
+        for attr in self.attr.iter_mut().flatten() {
+            attr.value.shift(from, to);
+            attr.loc.shift(from, to);
+        }
+        self.loc.shift(from, to);
+    }
+}
+
+impl Shift for StatementOrExpression {
+    fn shift(&mut self, from: usize, to: usize) {
+        match self {
+            StatementOrExpression::Statement(statement) => statement.shift(from, to),
+            StatementOrExpression::Expression(expression) => expression.shift(from, to),
+        }
+    }
+}
+
+impl Shift for Statement {
+    fn shift(&mut self, from: usize, to: usize) {
+        match self {
+            Statement::Definition(definition) => definition.shift(from, to),
+            Statement::Return(Return { expr, loc }) | Statement::Yield(Yield { expr, loc }) => {
+                expr.shift(from, to);
+                loc.shift(from, to);
+            }
+            Statement::Each(Each {
+                items, for_, loc, ..
+            }) => {
+                items.shift(from, to);
+                for_.shift(from, to);
+                loc.shift(from, to);
+            }
+            Statement::For(For {
+                from: from_,
+                to: to_,
+                times,
+                for_,
+                loc,
+                ..
+            }) => {
+                from_.shift(from, to);
+                to_.shift(from, to);
+                times.shift(from, to);
+                for_.shift(from, to);
+                loc.shift(from, to);
+            }
+            Statement::Loop(Loop { statements, loc }) => {
+                statements.shift(from, to);
+                loc.shift(from, to);
+            }
+            Statement::Break(Break { loc }) | Statement::Continue(Continue { loc }) => {
+                loc.shift(from, to)
+            }
+            Statement::Assign(Assign { dest, expr, loc })
+            | Statement::AddAssign(AddAssign { dest, expr, loc })
+            | Statement::SubAssign(SubAssign { dest, expr, loc }) => {
+                dest.shift(from, to);
+                expr.shift(from, to);
+                loc.shift(from, to);
+            }
+        }
+    }
+}
+
+impl Shift for Expression {
+    fn shift(&mut self, from: usize, to: usize) {
+        match self {
+            Expression::If(If {
+                cond,
+                then,
+                elseif,
+                else_,
+                loc,
+            }) => {
+                cond.shift(from, to);
+                then.shift(from, to);
+                for Elseif { cond, then } in elseif {
+                    cond.shift(from, to);
+                    then.shift(from, to);
+                }
+                else_.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Fn(Fn {
+                args,
+                ret_type,
+                children,
+                loc,
+                ..
+            }) => {
+                for arg in args {
+                    arg.arg_type.shift(from, to);
+                }
+                ret_type.shift(from, to);
+                children.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Match(Match {
+                about,
+                qs,
+                default,
+                loc,
+            }) => {
+                about.shift(from, to);
+                for QA { q, a } in qs {
+                    q.shift(from, to);
+                    a.shift(from, to);
+                }
+                default.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Block(Block { statements, loc }) => {
+                statements.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Exists(Exists { identifier, loc }) => {
+                identifier.loc.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Tmpl(Tmpl { tmpl, loc }) => {
+                for x in tmpl {
+                    if let StringOrExpression::Expression(x) = x {
+                        x.shift(from, to);
+                    }
+                }
+                loc.shift(from, to);
+            }
+            Expression::Str(Str { loc, .. })
+            | Expression::Num(Num { loc, .. })
+            | Expression::Bool(Bool { loc, .. })
+            | Expression::Null(Null { loc })
+            | Expression::Identifier(Identifier { loc, .. }) => loc.shift(from, to),
+            Expression::Obj(Obj { value, loc }) => {
+                for v in value.values_mut() {
+                    v.shift(from, to);
+                }
+                loc.shift(from, to);
+            }
+            Expression::Arr(Arr { value, loc }) => {
+                value.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Not(Not { expr, loc }) => {
+                expr.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::And(And {
+                left,
+                right,
+                operator_loc,
+                loc,
+            })
+            | Expression::Or(Or {
+                left,
+                right,
+                operator_loc,
+                loc,
+            })
+            | Expression::Coalesce(Coalesce {
+                left,
+                right,
+                operator_loc,
+                loc,
+            }) => {
+                left.shift(from, to);
+                right.shift(from, to);
+                operator_loc.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Call(Call {
+                target, args, loc, ..
+            }) => {
+                target.shift(from, to);
+                args.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Index(Index {
+                target, index, loc, ..
+            }) => {
+                target.shift(from, to);
+                index.shift(from, to);
+                loc.shift(from, to);
+            }
+            Expression::Prop(Prop { target, loc, .. }) => {
+                target.shift(from, to);
+                loc.shift(from, to);
+            }
+        }
+    }
+}
+
+impl Shift for Pattern {
+    fn shift(&mut self, from: usize, to: usize) {
+        match self {
+            Pattern::Expr(expression) => expression.shift(from, to),
+            Pattern::Bind { loc, .. } => loc.shift(from, to),
+            Pattern::Arr { items, loc } => {
+                items.shift(from, to);
+                loc.shift(from, to);
+            }
+            Pattern::Obj { entries, loc } => {
+                for pattern in entries.values_mut() {
+                    pattern.shift(from, to);
+                }
+                loc.shift(from, to);
+            }
+        }
+    }
+}
+
+impl Shift for TypeSource {
+    fn shift(&mut self, from: usize, to: usize) {
+        match self {
+            TypeSource::NamedTypeSource(NamedTypeSource { inner, loc, .. }) => {
+                inner.shift(from, to);
+                loc.shift(from, to);
+            }
+            TypeSource::FnTypeSource(FnTypeSource { args, result, loc }) => {
+                args.shift(from, to);
+                result.shift(from, to);
+                loc.shift(from, to);
+            }
+        }
+    }
+}
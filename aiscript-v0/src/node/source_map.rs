@@ -0,0 +1,36 @@
+//! 生成されたスクリプトの位置から元の位置への対応
+
+use std::ops::Range;
+
+use super::Loc;
+
+/// Maps ranges of a generated script back to where they came from, e.g. the blocks of a visual
+/// editor, so that runtime errors can report the original location.
+///
+/// See [`Interpreter::with_source_map`](crate::Interpreter::with_source_map).
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SourceMap {
+    mappings: Vec<(Range<usize>, String)>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// Records that the bytes `range` of the generated script came from `origin`.
+    ///
+    /// Ranges may be nested, in which case the innermost one takes precedence.
+    pub fn add(&mut self, range: Range<usize>, origin: impl Into<String>) {
+        self.mappings.push((range, origin.into()));
+    }
+
+    /// Returns the origin of the innermost range which contains `loc`, if any.
+    pub fn origin(&self, loc: &Loc) -> Option<&str> {
+        self.mappings
+            .iter()
+            .filter(|(range, _)| range.start <= loc.start && loc.end < range.end)
+            .min_by_key(|(range, _)| range.len())
+            .map(|(_, origin)| origin.as_str())
+    }
+}
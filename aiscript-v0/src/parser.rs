@@ -1,40 +1,67 @@
+use std::{collections::VecDeque, ops::Range};
+
 use crate::{
     error::{AiScriptError, AiScriptSyntaxError},
-    node as ast,
+    node::{self as ast, Shift},
+    r#type::Type,
 };
 
 use self::{
+    diagnostic::{Diagnostic, JumpKind, ScopeInfo},
     node as cst,
-    parser::parser::{main, preprocess},
+    parser::parser::{global_statement_at, main, preprocess, type_source},
     plugins::{
-        set_attribute::set_attribute, transform_chain::transform_chain,
-        validate_keyword::validate_keyword, validate_type::validate_type,
+        set_attribute::set_attribute,
+        transform_chain::transform_chain,
+        validate_comparison::validate_comparison,
+        validate_keyword::{validate_generator_keyword, validate_keyword},
+        validate_type::validate_type,
     },
 };
 
+pub mod diagnostic;
+pub mod docs;
 pub mod node;
-#[allow(clippy::module_inception)]
+#[allow(clippy::module_inception, clippy::too_many_arguments)]
 mod parser;
 mod plugins;
-mod visit;
+pub mod token;
+pub(crate) mod visit;
 
+/// A function which receives the CST nodes of a whole script and returns (possibly rewritten) nodes.
 pub type ParserPlugin = fn(Vec<cst::Node>) -> Result<Vec<cst::Node>, AiScriptError>;
 
+/// A function which receives the AST nodes of a whole script with its declarations and reports problems.
+pub type DiagnosticPlugin = fn(&[ast::Node], &ScopeInfo) -> Vec<Diagnostic>;
+
+/// Kind of a [`ParserPlugin`].
+///
+/// All validate plugins run before any transform plugin.
+/// Within each kind, plugins run in the order of registration.
 pub enum PluginType {
+    /// Checks the nodes right after parsing.
     Validate(ParserPlugin),
+    /// Rewrites the nodes after validation.
+    /// Plugins added by [`Parser::add_plugin`] receive the nodes transformed by the built-in plugins,
+    /// so attributes are already attached and chains are already expanded.
     Transform(ParserPlugin),
+    /// Reports diagnostics after all transform plugins.
+    /// Only [`Parser::parse_with_diagnostics`] runs these plugins.
+    Diagnostic(DiagnosticPlugin),
 }
 
 struct Plugins {
     pub validate: Vec<ParserPlugin>,
     pub transform: Vec<ParserPlugin>,
+    pub diagnostic: Vec<DiagnosticPlugin>,
 }
 
 impl Default for Plugins {
     fn default() -> Self {
         Self {
-            validate: vec![validate_keyword, validate_type],
+            validate: vec![validate_keyword, validate_type, validate_comparison],
             transform: vec![set_attribute, transform_chain],
+            diagnostic: Vec::new(),
         }
     }
 }
@@ -42,26 +69,226 @@ impl Default for Plugins {
 #[derive(Default)]
 pub struct Parser {
     plugins: Plugins,
+    while_loops: bool,
+    const_metadata: bool,
+    generators: bool,
 }
 
 impl Parser {
+    /// Creates a parser which runs only the given plugins.
+    ///
+    /// Use [`Parser::default`] to get a parser with the built-in plugins.
     pub fn new(validate: Vec<ParserPlugin>, transform: Vec<ParserPlugin>) -> Self {
         Parser {
             plugins: Plugins {
                 validate,
                 transform,
+                diagnostic: Vec::new(),
             },
+            while_loops: false,
+            const_metadata: false,
+            generators: false,
+        }
+    }
+
+    /// Enables `while cond { ... }` and `do { ... } while cond` of AiScript v1, which are parsed
+    /// into `loop` with `if !cond break` before or after the body respectively.
+    pub fn with_while_loops(self, while_loops: bool) -> Self {
+        Parser {
+            while_loops,
+            ..self
+        }
+    }
+
+    /// Enables generator functions, `@*() { ... }` and `@name*() { ... }`, and the `yield`
+    /// statement in them. `yield` is a reserved word only while they are enabled, so that
+    /// existing scripts can keep using it as a name.
+    pub fn with_generators(self, generators: bool) -> Self {
+        Parser { generators, ..self }
+    }
+
+    /// Allows any expression as the value of metadata, e.g. `### { size: 2 * 1024 }`, instead
+    /// of literals only. [`Interpreter::collect_metadata`](crate::Interpreter::collect_metadata)
+    /// evaluates values made of literals, arithmetic, comparisons and templates without running
+    /// the script, and gives `None` for the others, e.g. those calling functions.
+    pub fn with_const_metadata(self, const_metadata: bool) -> Self {
+        Parser {
+            const_metadata,
+            ..self
         }
     }
 
+    /// Parses a type, e.g. `arr<str>` or `@(num) => str`, such as those of the parameters of
+    /// native functions declared via
+    /// [`Interpreter::with_native_type`](crate::Interpreter::with_native_type).
+    pub fn parse_type(&self, input: &str) -> Result<ast::TypeSource, AiScriptError> {
+        let type_ = type_source(
+            input,
+            self.while_loops,
+            self.const_metadata,
+            self.generators,
+        )
+        .map_err(AiScriptSyntaxError::Parse)?;
+        Type::try_from(type_.clone())?;
+        Ok(type_)
+    }
+
     pub fn parse(&self, input: &str) -> Result<Vec<ast::Node>, AiScriptError> {
-        let code = preprocess(input).map_err(AiScriptSyntaxError::Parse)?;
-        let nodes: Vec<node::Node> = main(&code).map_err(AiScriptSyntaxError::Parse)?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("aiscript.parse", len = input.len()).entered();
+        let code = preprocess(
+            input,
+            self.while_loops,
+            self.const_metadata,
+            self.generators,
+        )
+        .map_err(AiScriptSyntaxError::Parse)?;
+        let nodes: Vec<node::Node> = main(
+            &code,
+            self.while_loops,
+            self.const_metadata,
+            self.generators,
+        )
+        .map_err(AiScriptSyntaxError::Parse)?;
+        self.run_plugins(nodes)
+    }
+
+    /// Parses the input lazily, returning the top-level nodes one at a time.
+    ///
+    /// Each node is parsed when it is requested, so a large script can be processed before it is
+    /// parsed entirely, without keeping all nodes in memory. The validate and transform plugins
+    /// run on each top-level node with the attributes right before it, instead of the whole
+    /// script. The iteration ends after the first error.
+    pub fn parse_iter<'a>(
+        &'a self,
+        input: &str,
+    ) -> impl Iterator<Item = Result<ast::Node, AiScriptError>> + 'a {
+        let code = preprocess(
+            input,
+            self.while_loops,
+            self.const_metadata,
+            self.generators,
+        )
+        .map_err(|e| AiScriptSyntaxError::Parse(e).into());
+        ParseIter {
+            parser: self,
+            code,
+            pos: 0,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Parses the input after an edit, reusing the top-level nodes of the previous result which
+    /// the edit does not affect, e.g. for an editor which parses a script on every keystroke.
+    ///
+    /// `old` is the result of parsing the script before the edit, and `input` is the script
+    /// after the bytes in `edit` were replaced with `new_text`. The nodes before the edit are
+    /// reused except for the last one, which may continue after it, e.g. with `else`, and the
+    /// nodes after the edit are reused with their locations moved once the parser reaches one of
+    /// them. As with [`Self::parse_iter`], the plugins run on each parsed node separately.
+    /// The input is parsed entirely if it does not match the edit, or if it has an error to
+    /// report the same error as [`Self::parse`].
+    pub fn reparse(
+        &self,
+        old: &[ast::Node],
+        edit: Range<usize>,
+        new_text: &str,
+        input: &str,
+    ) -> Result<Vec<ast::Node>, AiScriptError> {
+        /// Returns the location of a node including the attributes before it.
+        fn extent(node: &ast::Node) -> Option<ast::Loc> {
+            let loc = node.loc()?;
+            let start = match node {
+                ast::Node::Statement(ast::Statement::Definition(ast::Definition {
+                    attr: Some(attr),
+                    ..
+                })) => attr
+                    .iter()
+                    .filter_map(|attr| attr.loc.as_ref())
+                    .map(|loc| loc.start)
+                    .fold(loc.start, usize::min),
+                _ => loc.start,
+            };
+            Some(ast::Loc {
+                start,
+                end: loc.end,
+            })
+        }
+
+        let new_end = edit.start + new_text.len();
+        let locs = old.iter().map(extent).collect::<Option<Vec<_>>>();
+        let (Some(locs), true) = (
+            locs,
+            edit.start <= edit.end && input.get(edit.start..new_end) == Some(new_text),
+        ) else {
+            return self.parse(input);
+        };
+        let keep = locs
+            .iter()
+            .take_while(|loc| loc.end + 1 < edit.start)
+            .count()
+            .saturating_sub(1);
+        let mut nodes = old[..keep].to_vec();
+        let mut iter = ParseIter {
+            parser: self,
+            code: preprocess(
+                input,
+                self.while_loops,
+                self.const_metadata,
+                self.generators,
+            )
+            .map_err(|e| AiScriptSyntaxError::Parse(e).into()),
+            pos: keep.checked_sub(1).map_or(0, |last| locs[last].end + 1),
+            pending: VecDeque::new(),
+            done: false,
+        };
+        let Ok(code) = iter.code.clone() else {
+            return self.parse(input);
+        };
+        let mut reusable = locs
+            .iter()
+            .position(|loc| loc.start >= edit.end)
+            .unwrap_or(old.len());
+        while !iter.done {
+            while reusable < old.len() && locs[reusable].start - edit.end + new_end < iter.pos {
+                reusable += 1;
+            }
+            if let Some(loc) = locs.get(reusable) {
+                let start = loc.start - edit.end + new_end;
+                // The node is reused only if it starts a statement, which is not commented out.
+                let gap = code.get(iter.pos..start).filter(|gap| {
+                    gap.trim_start_matches([' ', '\t', '\r', '\n']).is_empty()
+                        && (iter.pos == 0 || gap.contains('\n'))
+                });
+                if gap.is_some() && code.as_bytes().get(start) == input.as_bytes().get(start) {
+                    nodes.extend(old[reusable..].iter().map(|node| {
+                        let mut node = node.clone();
+                        node.shift(edit.end, new_end);
+                        node
+                    }));
+                    return Ok(nodes);
+                }
+            }
+            match iter.next_nodes() {
+                Ok(parsed) => nodes.extend(parsed),
+                Err(_) => return self.parse(input),
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn run_plugins(&self, nodes: Vec<cst::Node>) -> Result<Vec<ast::Node>, AiScriptError> {
         let nodes = self
             .plugins
             .validate
             .iter()
             .try_fold(nodes, |nodes, plugin| plugin(nodes))?;
+        let nodes = if self.generators {
+            validate_generator_keyword(nodes)?
+        } else {
+            nodes
+        };
         let nodes = self
             .plugins
             .transform
@@ -70,10 +297,151 @@ impl Parser {
         Ok(nodes.into_iter().map(Into::into).collect())
     }
 
+    /// Parses the input and runs the diagnostic plugins on the result.
+    ///
+    /// Diagnostics of all plugins are returned in the order of registration.
+    pub fn parse_with_diagnostics(
+        &self,
+        input: &str,
+    ) -> Result<(Vec<ast::Node>, Vec<Diagnostic>), AiScriptError> {
+        let nodes = self.parse(input)?;
+        let diagnostics = if self.plugins.diagnostic.is_empty() {
+            Vec::new()
+        } else {
+            let scope_info = ScopeInfo::new(&nodes);
+            self.plugins
+                .diagnostic
+                .iter()
+                .flat_map(|plugin| plugin(&nodes, &scope_info))
+                .collect()
+        };
+        Ok((nodes, diagnostics))
+    }
+
+    /// Checks whether the script definitely fails without executing it, and returns the problems.
+    ///
+    /// In addition to the errors of [`Self::parse`], such as syntax errors and reserved words
+    /// used as names, this reports the problems which are otherwise found only by executing the
+    /// script: mutable variables in namespaces and variables defined twice in the same scope.
+    /// `break` and `continue` outside loops, which end the function or the script instead, are
+    /// also reported. Diagnostic plugins are not run.
+    pub fn validate(&self, input: &str) -> Vec<Diagnostic> {
+        fn validate_namespace(namespace: &ast::Namespace, diagnostics: &mut Vec<Diagnostic>) {
+            for member in &namespace.members {
+                match member {
+                    ast::DefinitionOrNamespace::Definition(definition) => {
+                        if definition.mut_ {
+                            diagnostics.push(Diagnostic {
+                                message: format!(
+                                    "Namespaces cannot include mutable variable: {}",
+                                    definition.name
+                                ),
+                                loc: definition.loc.clone(),
+                            });
+                        }
+                    }
+                    ast::DefinitionOrNamespace::Namespace(namespace) => {
+                        validate_namespace(namespace, diagnostics)
+                    }
+                }
+            }
+        }
+
+        let nodes = match self.parse(input) {
+            Ok(nodes) => nodes,
+            Err(e) => {
+                return vec![Diagnostic {
+                    message: e.to_string(),
+                    loc: e.loc(),
+                }]
+            }
+        };
+        let mut diagnostics = Vec::new();
+        for node in &nodes {
+            if let ast::Node::Namespace(namespace) = node {
+                validate_namespace(namespace, &mut diagnostics);
+            }
+        }
+        let scope_info = ScopeInfo::new(&nodes);
+        diagnostics.extend(scope_info.redeclared().map(|(declaration, _)| Diagnostic {
+            message: format!("Variable '{}' already exists", declaration.name),
+            loc: declaration.loc.clone(),
+        }));
+        diagnostics.extend(scope_info.invalid_jumps().map(|jump| Diagnostic {
+            message: match jump.kind {
+                JumpKind::Break => "break must be inside a loop".to_string(),
+                JumpKind::Continue => "continue must be inside a loop".to_string(),
+            },
+            loc: jump.loc.clone(),
+        }));
+        diagnostics.sort_by_key(|diagnostic| diagnostic.loc.as_ref().map(|loc| loc.start));
+        diagnostics
+    }
+
+    /// Appends a plugin which runs after the plugins of the same kind already registered.
     pub fn add_plugin(&mut self, plugin: PluginType) {
         match plugin {
             PluginType::Validate(plugin) => self.plugins.validate.push(plugin),
             PluginType::Transform(plugin) => self.plugins.transform.push(plugin),
+            PluginType::Diagnostic(plugin) => self.plugins.diagnostic.push(plugin),
+        }
+    }
+}
+
+/// The iterator returned by [`Parser::parse_iter`].
+struct ParseIter<'a> {
+    parser: &'a Parser,
+    code: Result<String, AiScriptError>,
+    pos: usize,
+    pending: VecDeque<ast::Node>,
+    done: bool,
+}
+
+impl ParseIter<'_> {
+    /// Parses the next top-level node with the attributes before it.
+    fn next_nodes(&mut self) -> Result<Vec<ast::Node>, AiScriptError> {
+        let code = self.code.as_ref().map_err(Clone::clone)?;
+        let mut nodes = Vec::new();
+        while let Some((node, end)) = global_statement_at(
+            code,
+            self.parser.while_loops,
+            self.parser.const_metadata,
+            self.parser.generators,
+            self.pos,
+            self.pos == 0,
+        )
+        .map_err(AiScriptSyntaxError::Parse)?
+        {
+            self.pos = end;
+            let is_attribute = matches!(node, cst::Node::Statement(cst::Statement::Attribute(_)));
+            nodes.push(node);
+            if !is_attribute {
+                return self.parser.run_plugins(nodes);
+            }
+        }
+        self.done = true;
+        self.parser.run_plugins(nodes)
+    }
+}
+
+impl Iterator for ParseIter<'_> {
+    type Item = Result<ast::Node, AiScriptError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(node) = self.pending.pop_front() {
+                return Some(Ok(node));
+            }
+            if self.done {
+                return None;
+            }
+            match self.next_nodes() {
+                Ok(nodes) => self.pending.extend(nodes),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
         }
     }
 }
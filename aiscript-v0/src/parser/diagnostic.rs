@@ -0,0 +1,568 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::node::{self as ast, Loc};
+
+/// A problem in a script reported by a diagnostic plugin.
+///
+/// Unlike errors, diagnostics do not stop parsing.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub loc: Option<Loc>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DeclarationKind {
+    /// `let`, `var` or `@name()`
+    Variable,
+    /// Argument of a function
+    Argument,
+    /// Variable of `each` or `for`
+    Iterator,
+    /// Variable bound by a pattern of `match`
+    Pattern,
+}
+
+/// A name declared in a script.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Declaration {
+    /// Name without namespace.
+    pub name: String,
+    pub kind: DeclarationKind,
+    pub mut_: bool,
+    /// Location of the definition, or of the function or loop which declares the name.
+    pub loc: Option<Loc>,
+    /// Nesting depth of the scope. Top-level is `0`.
+    pub depth: usize,
+    /// Index of the declaration in an enclosing scope which has the same name.
+    pub shadows: Option<usize>,
+    /// Index of the declaration in the same scope which has the same name.
+    pub redeclares: Option<usize>,
+    /// Number of identifiers which refer to the declaration.
+    pub references: usize,
+}
+
+/// A reference to a name which is not declared in a script.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnresolvedReference {
+    pub name: String,
+    /// Location of the identifier.
+    pub loc: Option<Loc>,
+}
+
+/// An attribute given to a definition in a script.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AttributeUse {
+    pub name: String,
+    /// Location of the attribute.
+    pub loc: Option<Loc>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JumpKind {
+    Break,
+    Continue,
+}
+
+/// A `break` or `continue` in a script.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Jump {
+    pub kind: JumpKind,
+    pub loc: Option<Loc>,
+    /// Location of the innermost `each`, `for` or `loop` in the same function, which the jump
+    /// exits or continues. `None` if the jump is outside loops.
+    pub target: Option<Loc>,
+    /// Location of the statement right after the jump in the same block, which is never run.
+    pub unreachable: Option<Loc>,
+}
+
+/// Declarations of a script in the order of appearance.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScopeInfo {
+    pub declarations: Vec<Declaration>,
+    /// References and assignments to names which are not declared in the script, such as the
+    /// standard library, in the order of appearance. Names checked by `exists` anywhere in the
+    /// script are excluded.
+    pub unresolved: Vec<UnresolvedReference>,
+    /// Jump statements in the order of appearance.
+    pub jumps: Vec<Jump>,
+    /// Attributes of definitions in the order of appearance.
+    pub attributes: Vec<AttributeUse>,
+}
+
+/// The names which a script needs from the host, returned by [`ScopeInfo::dependencies`], e.g.
+/// to ask the user for permissions before running a plugin.
+///
+/// Names of the standard library are included, and operators are referred to as the functions
+/// of `Core:`, e.g. `Core:add`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Dependencies {
+    /// Names with namespaces which the script refers to but does not define, e.g. `Http:get`.
+    pub functions: BTreeSet<String>,
+    /// Namespaces of [`Self::functions`], e.g. `Http`, or `Mk:ui` for `Mk:ui:text`.
+    pub namespaces: BTreeSet<String>,
+    /// Names without namespaces which the script refers to or assigns to but does not define,
+    /// e.g. `print` or a constant given by the host.
+    pub globals: BTreeSet<String>,
+    /// Names of the attributes given to definitions, e.g. `deprecated`.
+    pub attributes: BTreeSet<String>,
+}
+
+impl ScopeInfo {
+    pub fn new(script: &[ast::Node]) -> Self {
+        let mut analyzer = Analyzer::default();
+        let root = analyzer.new_scope(None, None);
+        for node in script {
+            analyzer.node(node, root);
+        }
+        let unresolved = analyzer.resolve();
+        ScopeInfo {
+            declarations: analyzer.declarations,
+            unresolved,
+            jumps: analyzer.jumps,
+            attributes: analyzer.attributes,
+        }
+    }
+
+    /// Returns the names which the script needs from the host. Names checked by `exists` are
+    /// excluded, since the script runs without them.
+    pub fn dependencies(&self) -> Dependencies {
+        let mut dependencies = Dependencies::default();
+        for reference in &self.unresolved {
+            match reference.name.rsplit_once(':') {
+                Some((namespace, _)) => {
+                    dependencies.functions.insert(reference.name.clone());
+                    dependencies.namespaces.insert(namespace.to_string());
+                }
+                None => {
+                    dependencies.globals.insert(reference.name.clone());
+                }
+            }
+        }
+        dependencies.attributes = self
+            .attributes
+            .iter()
+            .map(|attribute| attribute.name.clone())
+            .collect();
+        dependencies
+    }
+
+    /// Returns jumps outside loops, which end the function or the script instead of a loop.
+    pub fn invalid_jumps(&self) -> impl Iterator<Item = &Jump> {
+        self.jumps.iter().filter(|jump| jump.target.is_none())
+    }
+
+    /// Returns pairs of a declaration and the declaration it shadows.
+    pub fn shadowed(&self) -> impl Iterator<Item = (&Declaration, &Declaration)> {
+        self.declarations.iter().filter_map(|declaration| {
+            declaration
+                .shadows
+                .map(|shadows| (declaration, &self.declarations[shadows]))
+        })
+    }
+
+    /// Returns pairs of a variable and the declaration in the same scope with the same name,
+    /// which makes the definition of the variable fail at runtime.
+    pub fn redeclared(&self) -> impl Iterator<Item = (&Declaration, &Declaration)> {
+        self.declarations.iter().filter_map(|declaration| {
+            declaration
+                .redeclares
+                .filter(|_| declaration.kind == DeclarationKind::Variable)
+                .map(|redeclares| (declaration, &self.declarations[redeclares]))
+        })
+    }
+
+    /// Returns declarations which are never referred to.
+    pub fn unused(&self) -> impl Iterator<Item = &Declaration> {
+        self.declarations
+            .iter()
+            .filter(|declaration| declaration.references == 0)
+    }
+}
+
+#[derive(Default)]
+struct ScopeData {
+    parent: Option<usize>,
+    ns_name: Option<String>,
+    depth: usize,
+    names: HashMap<String, usize>,
+}
+
+#[derive(PartialEq)]
+enum ReferenceKind {
+    Read,
+    Exists,
+    Assign,
+}
+
+struct Reference {
+    name: String,
+    scope: usize,
+    kind: ReferenceKind,
+    loc: Option<Loc>,
+}
+
+#[derive(Default)]
+struct Analyzer {
+    declarations: Vec<Declaration>,
+    scopes: Vec<ScopeData>,
+    references: Vec<Reference>,
+    jumps: Vec<Jump>,
+    attributes: Vec<AttributeUse>,
+    /// Locations of the loops enclosing the current node in the current function.
+    loops: Vec<Option<Loc>>,
+}
+
+impl Analyzer {
+    fn new_scope(&mut self, parent: Option<usize>, ns_name: Option<String>) -> usize {
+        let depth = parent.map_or(0, |parent| self.scopes[parent].depth + 1);
+        self.scopes.push(ScopeData {
+            parent,
+            ns_name,
+            depth,
+            names: HashMap::new(),
+        });
+        self.scopes.len() - 1
+    }
+
+    fn lookup(&self, scope: usize, name: &str) -> Option<usize> {
+        let scope = &self.scopes[scope];
+        match scope.names.get(name) {
+            Some(declaration) => Some(*declaration),
+            None => scope.parent.and_then(|parent| self.lookup(parent, name)),
+        }
+    }
+
+    fn declare(
+        &mut self,
+        scope: usize,
+        name: &str,
+        kind: DeclarationKind,
+        mut_: bool,
+        loc: &Option<Loc>,
+    ) {
+        let shadows = self.scopes[scope]
+            .parent
+            .and_then(|parent| self.lookup(parent, name));
+        let redeclares = self.scopes[scope].names.get(name).copied();
+        self.declarations.push(Declaration {
+            name: name.to_string(),
+            kind,
+            mut_,
+            loc: loc.clone(),
+            depth: self.scopes[scope].depth,
+            shadows,
+            redeclares,
+            references: 0,
+        });
+        let declaration = self.declarations.len() - 1;
+        let mut scope = scope;
+        let mut name = name.to_string();
+        loop {
+            self.scopes[scope].names.insert(name.clone(), declaration);
+            match (&self.scopes[scope].ns_name, self.scopes[scope].parent) {
+                (Some(ns_name), Some(parent)) => {
+                    name = format!("{ns_name}:{name}");
+                    scope = parent;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn refer(&mut self, scope: usize, identifier: &ast::Identifier, kind: ReferenceKind) {
+        self.references.push(Reference {
+            name: identifier.name.clone(),
+            scope,
+            kind,
+            loc: identifier.loc.clone(),
+        });
+    }
+
+    /// Counts the references of the declarations and returns the unresolved references.
+    fn resolve(&mut self) -> Vec<UnresolvedReference> {
+        let checked = self
+            .references
+            .iter()
+            .filter(|reference| reference.kind == ReferenceKind::Exists)
+            .map(|reference| reference.name.clone())
+            .collect::<HashSet<_>>();
+        let mut unresolved = Vec::new();
+        for reference in std::mem::take(&mut self.references) {
+            match self.lookup(reference.scope, &reference.name) {
+                Some(declaration) => {
+                    if reference.kind != ReferenceKind::Assign {
+                        self.declarations[declaration].references += 1;
+                    }
+                }
+                None => {
+                    if !checked.contains(&reference.name) {
+                        unresolved.push(UnresolvedReference {
+                            name: reference.name,
+                            loc: reference.loc,
+                        });
+                    }
+                }
+            }
+        }
+        unresolved
+    }
+
+    fn node(&mut self, node: &ast::Node, scope: usize) {
+        match node {
+            ast::Node::Namespace(namespace) => self.namespace(namespace, scope),
+            ast::Node::Meta(ast::Meta { value, .. }) => self.expression(value, scope),
+            ast::Node::Statement(statement) => self.statement(statement, scope),
+            ast::Node::Expression(expression) => self.expression(expression, scope),
+        }
+    }
+
+    fn namespace(&mut self, namespace: &ast::Namespace, scope: usize) {
+        let scope = self.new_scope(Some(scope), Some(namespace.name.clone()));
+        for member in &namespace.members {
+            match member {
+                ast::DefinitionOrNamespace::Definition(definition) => {
+                    self.definition(definition, scope)
+                }
+                ast::DefinitionOrNamespace::Namespace(namespace) => {
+                    self.namespace(namespace, scope)
+                }
+            }
+        }
+    }
+
+    fn definition(&mut self, definition: &ast::Definition, scope: usize) {
+        self.expression(&definition.expr, scope);
+        for attr in definition.attr.iter().flatten() {
+            self.attributes.push(AttributeUse {
+                name: attr.name.clone(),
+                loc: attr.loc.clone(),
+            });
+            self.expression(&attr.value, scope);
+        }
+        self.declare(
+            scope,
+            &definition.name,
+            DeclarationKind::Variable,
+            definition.mut_,
+            &definition.loc,
+        );
+    }
+
+    fn statements(&mut self, statements: &[ast::StatementOrExpression], scope: usize) {
+        let mut jump: Option<usize> = None;
+        for statement in statements {
+            if let Some(jump) = jump.take() {
+                self.jumps[jump].unreachable = statement.loc().cloned();
+            }
+            self.statement_or_expression(statement, scope);
+            if let ast::StatementOrExpression::Statement(
+                ast::Statement::Break(_) | ast::Statement::Continue(_),
+            ) = statement
+            {
+                jump = Some(self.jumps.len() - 1);
+            }
+        }
+    }
+
+    fn loop_body(&mut self, body: &ast::StatementOrExpression, loc: &Option<Loc>, scope: usize) {
+        self.loops.push(loc.clone());
+        self.statement_or_expression(body, scope);
+        self.loops.pop();
+    }
+
+    fn jump(&mut self, kind: JumpKind, loc: &Option<Loc>) {
+        self.jumps.push(Jump {
+            kind,
+            loc: loc.clone(),
+            target: self.loops.last().cloned().flatten(),
+            unreachable: None,
+        });
+    }
+
+    fn statement_or_expression(&mut self, node: &ast::StatementOrExpression, scope: usize) {
+        match node {
+            ast::StatementOrExpression::Statement(statement) => self.statement(statement, scope),
+            ast::StatementOrExpression::Expression(expression) => {
+                self.expression(expression, scope)
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &ast::Statement, scope: usize) {
+        match statement {
+            ast::Statement::Definition(definition) => self.definition(definition, scope),
+            ast::Statement::Return(ast::Return { expr, .. })
+            | ast::Statement::Yield(ast::Yield { expr, .. }) => self.expression(expr, scope),
+            ast::Statement::Each(ast::Each {
+                var,
+                items,
+                for_,
+                loc,
+            }) => {
+                self.expression(items, scope);
+                let scope = self.new_scope(Some(scope), None);
+                self.declare(scope, var, DeclarationKind::Iterator, false, loc);
+                self.loop_body(for_, loc, scope);
+            }
+            ast::Statement::For(ast::For {
+                var,
+                from,
+                to,
+                times,
+                for_,
+                loc,
+            }) => {
+                for expression in [from, to, times].into_iter().flatten() {
+                    self.expression(expression, scope);
+                }
+                if let Some(var) = var {
+                    let scope = self.new_scope(Some(scope), None);
+                    self.declare(scope, var, DeclarationKind::Iterator, false, loc);
+                    self.loop_body(for_, loc, scope);
+                } else {
+                    self.loop_body(for_, loc, scope);
+                }
+            }
+            ast::Statement::Loop(ast::Loop { statements, loc }) => {
+                let scope = self.new_scope(Some(scope), None);
+                self.loops.push(loc.clone());
+                self.statements(statements, scope);
+                self.loops.pop();
+            }
+            ast::Statement::Break(ast::Break { loc }) => self.jump(JumpKind::Break, loc),
+            ast::Statement::Continue(ast::Continue { loc }) => self.jump(JumpKind::Continue, loc),
+            ast::Statement::Assign(ast::Assign { dest, expr, .. }) => {
+                self.expression(expr, scope);
+                match dest {
+                    ast::Expression::Identifier(identifier) => {
+                        self.refer(scope, identifier, ReferenceKind::Assign)
+                    }
+                    dest => self.expression(dest, scope),
+                }
+            }
+            ast::Statement::AddAssign(ast::AddAssign { dest, expr, .. })
+            | ast::Statement::SubAssign(ast::SubAssign { dest, expr, .. }) => {
+                self.expression(expr, scope);
+                self.expression(dest, scope);
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &ast::Pattern, scope: usize) {
+        match pattern {
+            ast::Pattern::Expr(expression) => self.expression(expression, scope),
+            ast::Pattern::Bind { name, loc } => {
+                self.declare(scope, name, DeclarationKind::Pattern, false, loc)
+            }
+            ast::Pattern::Arr { items, .. } => {
+                for item in items {
+                    self.pattern(item, scope);
+                }
+            }
+            ast::Pattern::Obj { entries, .. } => {
+                for value in entries.values() {
+                    self.pattern(value, scope);
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &ast::Expression, scope: usize) {
+        match expression {
+            ast::Expression::If(ast::If {
+                cond,
+                then,
+                elseif,
+                else_,
+                ..
+            }) => {
+                self.expression(cond, scope);
+                self.statement_or_expression(then, scope);
+                for ast::Elseif { cond, then } in elseif {
+                    self.expression(cond, scope);
+                    self.statement_or_expression(then, scope);
+                }
+                if let Some(else_) = else_ {
+                    self.statement_or_expression(else_, scope);
+                }
+            }
+            ast::Expression::Fn(ast::Fn {
+                args,
+                children,
+                loc,
+                ..
+            }) => {
+                let scope = self.new_scope(Some(scope), None);
+                for arg in args {
+                    self.declare(scope, &arg.name, DeclarationKind::Argument, true, loc);
+                }
+                let loops = std::mem::take(&mut self.loops);
+                self.statements(children, scope);
+                self.loops = loops;
+            }
+            ast::Expression::Match(ast::Match {
+                about, qs, default, ..
+            }) => {
+                self.expression(about, scope);
+                for ast::QA { q, a } in qs {
+                    let scope = self.new_scope(Some(scope), None);
+                    self.pattern(q, scope);
+                    self.statement_or_expression(a, scope);
+                }
+                if let Some(default) = default {
+                    self.statement_or_expression(default, scope);
+                }
+            }
+            ast::Expression::Block(ast::Block { statements, .. }) => {
+                let scope = self.new_scope(Some(scope), None);
+                self.statements(statements, scope);
+            }
+            ast::Expression::Exists(ast::Exists { identifier, .. }) => {
+                self.refer(scope, identifier, ReferenceKind::Exists)
+            }
+            ast::Expression::Tmpl(ast::Tmpl { tmpl, .. }) => {
+                for x in tmpl {
+                    if let ast::StringOrExpression::Expression(x) = x {
+                        self.expression(x, scope);
+                    }
+                }
+            }
+            ast::Expression::Str(_)
+            | ast::Expression::Num(_)
+            | ast::Expression::Bool(_)
+            | ast::Expression::Null(_) => (),
+            ast::Expression::Obj(ast::Obj { value, .. }) => {
+                for v in value.values() {
+                    self.expression(v, scope);
+                }
+            }
+            ast::Expression::Arr(ast::Arr { value, .. }) => {
+                for v in value {
+                    self.expression(v, scope);
+                }
+            }
+            ast::Expression::Not(ast::Not { expr, .. }) => self.expression(expr, scope),
+            ast::Expression::And(ast::And { left, right, .. })
+            | ast::Expression::Or(ast::Or { left, right, .. })
+            | ast::Expression::Coalesce(ast::Coalesce { left, right, .. }) => {
+                self.expression(left, scope);
+                self.expression(right, scope);
+            }
+            ast::Expression::Identifier(identifier) => {
+                self.refer(scope, identifier, ReferenceKind::Read)
+            }
+            ast::Expression::Call(ast::Call { target, args, .. }) => {
+                self.expression(target, scope);
+                for arg in args {
+                    self.expression(arg, scope);
+                }
+            }
+            ast::Expression::Index(ast::Index { target, index, .. }) => {
+                self.expression(target, scope);
+                self.expression(index, scope);
+            }
+            ast::Expression::Prop(ast::Prop { target, .. }) => self.expression(target, scope),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Documentation of the definitions of a script, e.g. to generate the reference of a library.
+
+use crate::{
+    error::AiScriptError,
+    interpreter::{util::static_value, value::Attr},
+    node::{self as ast, Loc},
+};
+
+use super::Parser;
+
+/// The documentation of a definition at the top level or in a namespace, returned by
+/// [`Parser::extract_docs`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct DocItem {
+    /// The name with the namespaces, e.g. `Lib:greet`.
+    pub name: String,
+    pub kind: DocKind,
+    /// The parameters of a function, which is empty for a variable.
+    pub params: Vec<DocParam>,
+    /// The declared type of a variable, or the declared return type of a function.
+    pub type_: Option<String>,
+    /// The text of the doc comment right before the definition and its attributes, without the
+    /// comment markers.
+    pub doc: Option<String>,
+    /// The attributes given by `#[...]`, with their values.
+    pub attr: Vec<Attr>,
+    pub loc: Option<Loc>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DocKind {
+    /// A function defined by `@name() { ... }`, or a variable initialized with a function.
+    Fn,
+    Const,
+    Var,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DocParam {
+    pub name: String,
+    /// The declared type of the parameter.
+    pub type_: Option<String>,
+}
+
+impl Parser {
+    /// Parses the input and returns the documentation of the definitions at the top level and in
+    /// namespaces, in the order of appearance.
+    ///
+    /// Doc comments are line comments starting with `///` or block comments starting with
+    /// `/**`, right before the definition or the attributes of it.
+    pub fn extract_docs(&self, input: &str) -> Result<Vec<DocItem>, AiScriptError> {
+        let mut docs = Vec::new();
+        for node in self.parse(input)? {
+            match node {
+                ast::Node::Statement(ast::Statement::Definition(definition)) => {
+                    docs.push(doc_item(input, "", definition));
+                }
+                ast::Node::Namespace(namespace) => {
+                    namespace_docs(input, "", namespace, &mut docs);
+                }
+                _ => (),
+            }
+        }
+        Ok(docs)
+    }
+}
+
+fn namespace_docs(input: &str, prefix: &str, namespace: ast::Namespace, docs: &mut Vec<DocItem>) {
+    let prefix = format!("{prefix}{}:", namespace.name);
+    for member in namespace.members {
+        match member {
+            ast::DefinitionOrNamespace::Definition(definition) => {
+                docs.push(doc_item(input, &prefix, definition));
+            }
+            ast::DefinitionOrNamespace::Namespace(namespace) => {
+                namespace_docs(input, &prefix, namespace, docs);
+            }
+        }
+    }
+}
+
+fn doc_item(input: &str, prefix: &str, definition: ast::Definition) -> DocItem {
+    let (kind, params, type_) = match definition.expr {
+        ast::Expression::Fn(ast::Fn { args, ret_type, .. }) => (
+            DocKind::Fn,
+            args.into_iter()
+                .map(|arg| DocParam {
+                    name: arg.name,
+                    type_: arg.arg_type.map(|type_| type_.to_string()),
+                })
+                .collect(),
+            ret_type,
+        ),
+        _ if definition.mut_ => (DocKind::Var, Vec::new(), definition.var_type),
+        _ => (DocKind::Const, Vec::new(), definition.var_type),
+    };
+    DocItem {
+        name: format!("{prefix}{}", definition.name),
+        kind,
+        params,
+        type_: type_.map(|type_| type_.to_string()),
+        doc: definition
+            .loc
+            .as_ref()
+            .and_then(|loc| doc_comment(input, loc.start)),
+        attr: definition
+            .attr
+            .unwrap_or_default()
+            .into_iter()
+            .map(|attr| Attr {
+                name: attr.name,
+                value: static_value(attr.value).unwrap_or_default(),
+            })
+            .collect(),
+        loc: definition.loc,
+    }
+}
+
+/// Returns the doc comment before the position `start` of a definition, skipping the lines of
+/// attributes.
+fn doc_comment(input: &str, start: usize) -> Option<String> {
+    let mut lines = input.get(..start)?.split('\n').rev();
+    if !lines.next()?.trim().is_empty() {
+        return None;
+    }
+    let mut lines = lines
+        .map(str::trim)
+        .skip_while(|line| line.starts_with("#["))
+        .peekable();
+    let mut doc = Vec::new();
+    if lines.peek()?.ends_with("*/") {
+        let mut opened = false;
+        for line in lines {
+            let line = line.trim_end_matches("*/").trim_end();
+            if let Some(line) = line.strip_prefix("/**") {
+                doc.push(line.trim());
+                opened = true;
+                break;
+            }
+            if line.starts_with("/*") {
+                return None;
+            }
+            doc.push(line.strip_prefix('*').map_or(line, str::trim));
+        }
+        if !opened {
+            return None;
+        }
+        while doc.first().is_some_and(|line| line.is_empty()) {
+            doc.remove(0);
+        }
+        while doc.last().is_some_and(|line| line.is_empty()) {
+            doc.pop();
+        }
+    } else {
+        doc.extend(lines.map_while(|line| {
+            let line = line.strip_prefix("///")?;
+            Some(line.strip_prefix(' ').unwrap_or(line))
+        }));
+    }
+    if doc.is_empty() {
+        return None;
+    }
+    doc.reverse();
+    Some(doc.join("\n"))
+}
@@ -33,6 +33,7 @@ impl From<Node> for ast::Node {
 pub enum Statement {
     Definition(Definition),
     Return(Return),
+    Yield(Yield),
     Attribute(Attribute), // AST
     Each(Each),
     For(For),
@@ -49,6 +50,7 @@ impl From<Statement> for ast::Statement {
         match val {
             Statement::Definition(definition) => ast::Statement::Definition(definition.into()),
             Statement::Return(return_) => ast::Statement::Return(return_.into()),
+            Statement::Yield(yield_) => ast::Statement::Yield(yield_.into()),
             Statement::Attribute(_) => panic!(),
             Statement::Each(each) => ast::Statement::Each(each.into()),
             Statement::For(for_) => ast::Statement::For(for_.into()),
@@ -67,6 +69,7 @@ pub enum Expression {
     Not(Not),
     And(And),
     Or(Or),
+    Coalesce(Coalesce),
     If(If),
     Fn(Fn_),
     Match(Match),
@@ -91,6 +94,7 @@ impl From<Expression> for ast::Expression {
             Expression::Not(not) => ast::Expression::Not(not.into()),
             Expression::And(and) => ast::Expression::And(and.into()),
             Expression::Or(or) => ast::Expression::Or(or.into()),
+            Expression::Coalesce(coalesce) => ast::Expression::Coalesce(coalesce.into()),
             Expression::If(if_) => ast::Expression::If(if_.into()),
             Expression::Fn(fn_) => ast::Expression::Fn(fn_.into()),
             Expression::Match(match_) => ast::Expression::Match(match_.into()),
@@ -202,6 +206,21 @@ impl From<Return> for ast::Return {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct Yield {
+    pub expr: Expression,
+    pub loc: Option<Loc>,
+}
+
+impl From<Yield> for ast::Yield {
+    fn from(val: Yield) -> Self {
+        ast::Yield {
+            expr: val.expr.into(),
+            loc: val.loc,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Each {
     pub var: String,
@@ -366,6 +385,26 @@ impl From<Or> for ast::Or {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct Coalesce {
+    pub left: Box<Expression>,
+    pub right: Box<Expression>,
+    pub operator_loc: Loc,
+    pub chain: Option<Vec<ChainMember>>,
+    pub loc: Option<Loc>,
+}
+
+impl From<Coalesce> for ast::Coalesce {
+    fn from(val: Coalesce) -> Self {
+        ast::Coalesce {
+            left: Box::new((*val.left).into()),
+            right: Box::new((*val.right).into()),
+            operator_loc: val.operator_loc,
+            loc: val.loc,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct If {
     pub cond: Box<Expression>,
@@ -408,6 +447,7 @@ pub struct Fn_ {
     pub args: Vec<Arg>,
     pub ret_type: Option<TypeSource>,
     pub children: Vec<StatementOrExpression>,
+    pub generator: bool,
     pub chain: Option<Vec<ChainMember>>,
     pub loc: Option<Loc>,
 }
@@ -418,6 +458,7 @@ impl From<Fn_> for ast::Fn {
             args: val.args,
             ret_type: val.ret_type,
             children: val.children.into_iter().map(Into::into).collect(),
+            generator: val.generator,
             loc: val.loc,
         }
     }
@@ -445,10 +486,44 @@ impl From<Match> for ast::Match {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct QA {
-    pub q: Expression,
+    pub q: Pattern,
     pub a: StatementOrExpression,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Expr(Expression),
+    Bind {
+        name: String,
+        loc: Option<Loc>,
+    },
+    Arr {
+        items: Vec<Pattern>,
+        loc: Option<Loc>,
+    },
+    Obj {
+        entries: IndexMap<String, Pattern>,
+        loc: Option<Loc>,
+    },
+}
+
+impl From<Pattern> for ast::Pattern {
+    fn from(val: Pattern) -> Self {
+        match val {
+            Pattern::Expr(expr) => ast::Pattern::Expr(expr.into()),
+            Pattern::Bind { name, loc } => ast::Pattern::Bind { name, loc },
+            Pattern::Arr { items, loc } => ast::Pattern::Arr {
+                items: items.into_iter().map(Into::into).collect(),
+                loc,
+            },
+            Pattern::Obj { entries, loc } => ast::Pattern::Obj {
+                entries: entries.into_iter().map(|(k, v)| (k, v.into())).collect(),
+                loc,
+            },
+        }
+    }
+}
+
 impl From<QA> for ast::QA {
     fn from(val: QA) -> Self {
         ast::QA {
@@ -630,6 +705,7 @@ pub enum ChainMember {
 #[derive(Debug, PartialEq, Clone)]
 pub struct CallChain {
     pub args: Vec<Expression>,
+    pub optional: bool,
     pub loc: Option<Loc>,
 }
 
@@ -637,6 +713,7 @@ pub struct CallChain {
 #[derive(Debug, PartialEq, Clone)]
 pub struct IndexChain {
     pub index: Expression,
+    pub optional: bool,
     pub loc: Option<Loc>,
 }
 
@@ -644,6 +721,7 @@ pub struct IndexChain {
 #[derive(Debug, PartialEq, Clone)]
 pub struct PropChain {
     pub name: String,
+    pub optional: bool,
     pub loc: Option<Loc>,
 }
 
@@ -652,6 +730,7 @@ pub struct PropChain {
 pub struct Call {
     pub target: Box<Expression>,
     pub args: Vec<Expression>,
+    pub optional: bool,
     pub loc: Option<Loc>,
 }
 
@@ -660,6 +739,7 @@ impl From<Call> for ast::Call {
         ast::Call {
             target: Box::new((*val.target).into()),
             args: val.args.into_iter().map(Into::into).collect(),
+            optional: val.optional,
             loc: val.loc,
         }
     }
@@ -670,6 +750,7 @@ impl From<Call> for ast::Call {
 pub struct Index {
     pub target: Box<Expression>,
     pub index: Box<Expression>,
+    pub optional: bool,
     pub loc: Option<Loc>,
 }
 
@@ -678,6 +759,7 @@ impl From<Index> for ast::Index {
         Self {
             target: Box::new((*val.target).into()),
             index: Box::new((*val.index).into()),
+            optional: val.optional,
             loc: val.loc,
         }
     }
@@ -688,6 +770,7 @@ impl From<Index> for ast::Index {
 pub struct Prop {
     pub target: Box<Expression>,
     pub name: String,
+    pub optional: bool,
     pub loc: Option<Loc>,
 }
 
@@ -696,6 +779,7 @@ impl From<Prop> for ast::Prop {
         Self {
             target: Box::new((*val.target).into()),
             name: val.name,
+            optional: val.optional,
             loc: val.loc,
         }
     }
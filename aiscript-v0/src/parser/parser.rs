@@ -5,7 +5,7 @@ use crate::node::Loc;
 use super::node::*;
 
 peg::parser! {
-    pub grammar parser() for str {
+    pub grammar parser(while_loops: bool, const_metadata: bool, generators: bool) for str {
         //
         // preprocessor
         //
@@ -21,7 +21,9 @@ peg::parser! {
 
         rule comment() -> String
             = text:$("//" (!eol() [_])*) { " ".repeat(text.len()) }
-            / text:$("/*" (!"*/" [_])* "*/") { text.replace( |c| c != '\n', " ") }
+            / text:$("/*" (!"*/" [_])* "*/") {
+                text.chars().map(|c| if c == '\n' { "\n".to_string() } else { " ".repeat(c.len_utf8()) }).collect()
+            }
 
         //
         // main parser
@@ -30,6 +32,24 @@ peg::parser! {
         pub rule main() -> Vec<Node>
             = _* content:global_statements()? _* { content.unwrap_or_default() }
 
+        /// Parses the global statement after the position `start`, ignoring the rest of the
+        /// input, and returns it with the position right after it, or `None` at the end of the
+        /// input. Unless `first`, the statement must be on a new line as in `main`.
+        pub rule global_statement_at(start: usize, first: bool) -> Option<(Node, usize)>
+            = seek(start) _* ![_] { None }
+            / seek(start) (is(first) / __* lf()) _* node:global_statement() end:position!() seek_end() {
+                Some((node, end))
+            }
+
+        rule seek(pos: usize)
+            = #{|_, _| peg::RuleResult::Matched(pos, ())}
+
+        rule seek_end()
+            = #{|input: &str, _| peg::RuleResult::Matched(input.len(), ())}
+
+        rule is(cond: bool)
+            = #{|_, pos| if cond { peg::RuleResult::Matched(pos, ()) } else { peg::RuleResult::Failed }}
+
         rule global_statements() -> Vec<Node>
             = global_statement() ++ (__* lf() _*)
 
@@ -60,10 +80,13 @@ peg::parser! {
             / fn_def:fn_def() { StatementOrExpression::Statement(Statement::Definition(fn_def)) }            // "@"
             / out:out() { StatementOrExpression::Expression(Expression::Identifier(out)) }                   // "<:"
             / return_:return() { StatementOrExpression::Statement(Statement::Return(return_)) }              // "return"
+            / yield_:yield() { StatementOrExpression::Statement(Statement::Yield(yield_)) }                  // "yield"
             / attr:attr() { StatementOrExpression::Statement(Statement::Attribute(attr)) }                   // "+"
             / each:each() { StatementOrExpression::Statement(Statement::Each(each)) }                        // "each"
             / for_:for() { StatementOrExpression::Statement(Statement::For(for_)) }                          // "for"
             / loop_:loop() { StatementOrExpression::Statement(Statement::Loop(loop_)) }                      // "loop"
+            / loop_:while() { StatementOrExpression::Statement(Statement::Loop(loop_)) }                     // "while"
+            / loop_:do_while() { StatementOrExpression::Statement(Statement::Loop(loop_)) }                  // "do"
             / break_:break() { StatementOrExpression::Statement(Statement::Break(break_)) }                  // "break"
             / continue_:continue() { StatementOrExpression::Statement(Statement::Continue(continue_)) }      // "continue"
             / add_assign:add_assign() { StatementOrExpression::Statement(Statement::AddAssign(add_assign)) } // Expr "+="
@@ -76,6 +99,21 @@ peg::parser! {
         #[cache]
         rule expr() -> Expression
             = start:position!() expression:(precedence! {
+                left:(@) infix_sp()* start:position!() "??" end:position!() infix_sp()* right:@ {
+                    (
+                        Expression::Coalesce(
+                            Coalesce {
+                                left: left.0.into(),
+                                right: right.0.into(),
+                                operator_loc: Loc{ start, end: end - 1 },
+                                chain: None,
+                                loc: None,
+                            }
+                        ),
+                        true,
+                    )
+                }
+                --
                 left:(@) infix_sp()* start:position!() "&&" end:position!() infix_sp()* right:@ {
                     (
                         Expression::And(
@@ -113,6 +151,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -130,6 +169,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -148,6 +188,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -165,6 +206,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -182,6 +224,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -199,6 +242,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -217,6 +261,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -234,6 +279,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -252,6 +298,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -269,6 +316,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -286,6 +334,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -303,6 +352,7 @@ peg::parser! {
                                 chain: Some(
                                     vec![ChainMember::CallChain(CallChain {
                                         args: vec![left.0, right.0],
+                                        optional: false,
                                         loc: None,
                                     })]
                                 ),
@@ -322,8 +372,8 @@ peg::parser! {
                                 if let Some(ChainMember::CallChain(call_chain)) = chain.first() {
                                     chain[0] = ChainMember::CallChain(
                                         CallChain {
-                                            args: call_chain.args.clone(),
                                             loc: call_chain.loc.clone().or(Some(Loc { start, end })),
+                                            ..call_chain.clone()
                                         }
                                     );
                                 }
@@ -344,6 +394,12 @@ peg::parser! {
                             ..or
                         }
                     ),
+                    (Expression::Coalesce(coalesce), true) => Expression::Coalesce(
+                        Coalesce {
+                            loc: coalesce.loc.clone().or(Some(Loc { start, end })),
+                            ..coalesce
+                        }
+                    ),
                     (expression, _) => expression,
                 }
             }
@@ -397,14 +453,14 @@ peg::parser! {
         // meta statement
 
         rule meta() -> Meta
-            = start:position!() "###" __* name:name() _* value:static_literal() end:position!() {
+            = start:position!() "###" __* name:name() _* value:meta_value() end:position!() {
                 Meta {
                     name: Some(name),
                     value,
                     loc: Some(Loc{ start, end: end - 1 }),
                 }
             }
-            / start:position!() "###" __* value:static_literal() end:position!() {
+            / start:position!() "###" __* value:meta_value() end:position!() {
                 Meta {
                     name: None,
                     value,
@@ -412,6 +468,16 @@ peg::parser! {
                 }
             }
 
+        // any expression if constant metadata is enabled, which is evaluated by
+        // `Interpreter::collect_metadata` only if constant
+
+        rule meta_value() -> Expression
+            = const_metadata_enabled() e:expr() { e }
+            / static_literal()
+
+        rule const_metadata_enabled()
+            = {? if const_metadata { Ok(()) } else { Err("constant metadata to be enabled") } }
+
         //
         // statements ----------------------------------------------------------------------------
         //
@@ -450,7 +516,7 @@ peg::parser! {
                     chain: Some(
                         vec![
                             ChainMember::CallChain(
-                                CallChain { args: vec![expr], loc: Some(Loc{ start, end: end - 1 }) },
+                                CallChain { args: vec![expr], optional: false, loc: Some(Loc{ start, end: end - 1 }) },
                             ),
                         ],
                     ),
@@ -542,6 +608,19 @@ peg::parser! {
                 }
             }
 
+        // Generator functions and `yield` are enabled by `Parser::with_generators`.
+
+        rule generators_enabled()
+            = {? if generators { Ok(()) } else { Err("generators to be enabled") } }
+
+        rule yield() -> Yield
+            = generators_enabled() start:position!() "yield" !['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | ':'] _* expr:expr() end:position!() {
+                Yield {
+                    expr,
+                    loc: Some(Loc{ start, end: end - 1 }),
+                }
+            }
+
         rule loop() -> Loop
             = start:position!() "loop" _* "{" _* s:statements() _* "}" end:position!() {
                 Loop {
@@ -550,6 +629,28 @@ peg::parser! {
                 }
             }
 
+        // `while` and `do-while` are enabled by `Parser::with_while_loops` and desugared into `loop`
+        // like AiScript v1 does.
+
+        rule while_loops_enabled()
+            = {? if while_loops { Ok(()) } else { Err("while loops to be enabled") } }
+
+        rule while() -> Loop
+            = while_loops_enabled() start:position!() "while" _+ cond:expr() _+ body:block_or_statement() end:position!() {
+                Loop {
+                    statements: vec![break_unless(cond), body],
+                    loc: Some(Loc{ start, end: end - 1 }),
+                }
+            }
+
+        rule do_while() -> Loop
+            = while_loops_enabled() start:position!() "do" _* body:block_or_statement() _+ "while" _+ cond:expr() end:position!() {
+                Loop {
+                    statements: vec![body, break_unless(cond)],
+                    loc: Some(Loc{ start, end: end - 1 }),
+                }
+            }
+
         rule break() -> Break
             = start:position!() "break" !['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | ':'] end:position!() {
                 Break {
@@ -639,6 +740,14 @@ peg::parser! {
                             ..or
                         })
                     },
+                    Expression::Coalesce(coalesce) => {
+                        let mut c = coalesce.chain.unwrap_or_default();
+                        c.extend(chain);
+                        Expression::Coalesce(Coalesce {
+                            chain: Some(c),
+                            ..coalesce
+                        })
+                    },
                     Expression::If(if_) => {
                         let mut c = if_.chain.unwrap_or_default();
                         c.extend(chain);
@@ -755,9 +864,10 @@ peg::parser! {
             / prop_chain:prop_chain() { ChainMember::PropChain(prop_chain) }
 
         rule call_chain() -> CallChain
-            = start:position!() "(" _* args:call_args()? _* ")" end:position!() {
+            = start:position!() optional:optional() "(" _* args:call_args()? _* ")" end:position!() {
                 CallChain {
                     args: args.unwrap_or_default(),
+                    optional,
                     loc: Some(Loc{ start, end: end - 1 }),
                 }
             }
@@ -766,21 +876,28 @@ peg::parser! {
             = expr() ++ sep()
 
         rule index_chain() -> IndexChain
-            = start:position!() "[" _* index:expr() _* "]" end:position!() {
+            = start:position!() optional:optional() "[" _* index:expr() _* "]" end:position!() {
                 IndexChain {
                     index,
+                    optional,
                     loc: Some(Loc{ start, end: end - 1 }),
                 }
             }
 
         rule prop_chain() -> PropChain
-            = start:position!() "." name:name() end:position!() {
+            = start:position!() optional:"?"? "." name:name() end:position!() {
                 PropChain {
                     name,
+                    optional: optional.is_some(),
                     loc: Some(Loc{ start, end: end - 1 }),
                 }
             }
 
+        // `?.` before `(` or `[`, which makes the access short-circuit on null
+        rule optional() -> bool
+            = "?." { true }
+            / "" { false }
+
         // if statement
 
         rule if() -> If
@@ -818,7 +935,7 @@ peg::parser! {
             = start:position!() "match" !['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | ':'] _*
             about:expr() _*
             "{" _*
-            qs:(q:expr() _* "=>" _* a:block_or_statement() _* { QA{ q, a } })+
+            qs:(q:case() _* "=>" _* a:block_or_statement() _* { QA{ q, a } })+
             x:("*" _* "=>" _* x:block_or_statement() { x })? _*
             "}" end:position!() {
                 Match {
@@ -830,6 +947,34 @@ peg::parser! {
                 }
             }
 
+        rule case() -> Pattern
+            = "case" _+ p:pattern() { p }
+            / q:expr() { Pattern::Expr(q) }
+
+        rule pattern() -> Pattern
+            = start:position!() "[" _* items:(item:pattern() _* ","? _* { item })* _* "]" end:position!() {
+                Pattern::Arr {
+                    items,
+                    loc: Some(Loc{ start, end: end - 1 }),
+                }
+            }
+            / start:position!() "{" _* kvs:(k:name() _* ":" _+ v:pattern() _* ("," / ";")? _* { (k, v) })* _* "}" end:position!() {
+                Pattern::Obj {
+                    entries: IndexMap::from_iter(kvs),
+                    loc: Some(Loc{ start, end: end - 1 }),
+                }
+            }
+            / str:str() { Pattern::Expr(Expression::Str(str)) }
+            / num:num() { Pattern::Expr(Expression::Num(num)) }
+            / bool:bool() { Pattern::Expr(Expression::Bool(bool)) }
+            / null:null() { Pattern::Expr(Expression::Null(null)) }
+            / start:position!() name:name() end:position!() {
+                Pattern::Bind {
+                    name,
+                    loc: Some(Loc{ start, end: end - 1 }),
+                }
+            }
+
         // eval expression
 
         rule eval() -> Block
@@ -916,41 +1061,47 @@ peg::parser! {
         // number literal
 
         rule num() -> Num
-            = float()
-            / int()
+            = radix_int()
+            / decimal()
 
-        rule float() -> Num
-            = start:position!() n:$(['+' | '-']? ['1'..='9'] ['0'..='9']+ "." ['0'..='9']+) end:position!() {
-                Num {
-                    value: n.parse().unwrap(),
-                    chain: None,
-                    loc: Some(Loc{ start, end: end - 1 }),
-                }
-            }
-            / start:position!() n:$(['+' | '-']? ['0'..='9'] "." ['0'..='9']+) end:position!() {
-                Num {
-                    value: n.parse().unwrap(),
-                    chain: None,
-                    loc: Some(Loc{ start, end: end - 1 }),
-                }
-            }
+        // digits may be separated by single underscores, e.g. `1_000_000`
+
+        rule digits()
+            = ['0'..='9'] ("_"? ['0'..='9'])*
 
-        rule int() -> Num
-            = start:position!() n:$(['+' | '-']? ['1'..='9'] ['0'..='9']+) end:position!() {
+        rule decimal() -> Num
+            = start:position!() n:$(
+                ['+' | '-']? (['1'..='9'] ("_"? ['0'..='9'])* / "0")
+                ("." digits())?
+                (['e' | 'E'] ['+' | '-']? digits())?
+            ) end:position!() {
                 Num {
-                    value: n.parse().unwrap(),
+                    value: n.replace('_', "").parse().unwrap(),
                     chain: None,
                     loc: Some(Loc{ start, end: end - 1 }),
                 }
             }
-            / start:position!() n:$(['+' | '-']? ['0'..='9']) end:position!() {
-                Num {
-                    value: n.parse().unwrap(),
+
+        // hexadecimal (`0xff`) or binary (`0b1010`) integer
+
+        rule radix_int() -> Num
+            = start:position!() sign:$(['+' | '-']?) "0" digits:(
+                ['x' | 'X'] n:$(hex_digit() ("_"? hex_digit())*) { (n, 16) }
+                / ['b' | 'B'] n:$(['0' | '1'] ("_"? ['0' | '1'])*) { (n, 2) }
+            ) end:position!() {?
+                let (n, radix) = digits;
+                let value = u128::from_str_radix(&n.replace('_', ""), radix)
+                    .or(Err("number literal in range"))? as f64;
+                Ok(Num {
+                    value: if sign == "-" { -value } else { value },
                     chain: None,
                     loc: Some(Loc{ start, end: end - 1 }),
-                }
+                })
             }
 
+        rule hex_digit()
+            = ['0'..='9' | 'a'..='f' | 'A'..='F']
+
         // boolean literal
 
         rule bool() -> Bool
@@ -1026,6 +1177,7 @@ peg::parser! {
             "@"
             (quiet!{ !(__+) } / expected!("Cannot use spaces before or after the function name."))
             name:name()
+            generator:(generators_enabled() "*")?
             (quiet!{ !(__+) } / expected!("Cannot use spaces before or after the function name."))
             "(" _*
             args:args()? _*
@@ -1042,6 +1194,7 @@ peg::parser! {
                             args: args.unwrap_or_default(),
                             ret_type: ret,
                             children: content.unwrap_or_default(),
+                            generator: generator.is_some(),
                             chain: None,
                             loc: Some(Loc{ start, end: end - 1 }),
                         },
@@ -1057,7 +1210,7 @@ peg::parser! {
 
         rule fn() -> Fn_
             = start:position!()
-            "@(" _* args:args()? _* ")"
+            "@" generator:(generators_enabled() "*")? "(" _* args:args()? _* ")"
             ret:(_* ":" _* type_:type_() { type_ })? _*
             "{" _* content:statements()? _* "}"
             end:position!() {
@@ -1065,6 +1218,7 @@ peg::parser! {
                     args: args.unwrap_or_default(),
                     ret_type: ret,
                     children: content.unwrap_or_default(),
+                    generator: generator.is_some(),
                     chain: None,
                     loc: Some(Loc{ start, end: end - 1 }),
                 }
@@ -1100,6 +1254,9 @@ peg::parser! {
         // type ----------------------------------------------------------------------------------
         //
 
+        pub rule type_source() -> TypeSource
+            = _* type_:type_() _* { type_ }
+
         rule type_() -> TypeSource
             = fn_type:fn_type() { TypeSource::FnTypeSource(fn_type) }
             / named_type:named_type() { TypeSource::NamedTypeSource(named_type) }
@@ -1177,3 +1334,21 @@ peg::parser! {
             = [' ' | '\t']
     }
 }
+
+/// Creates `if !cond break`, which ends a desugared `while` loop.
+fn break_unless(cond: Expression) -> StatementOrExpression {
+    StatementOrExpression::Expression(Expression::If(If {
+        cond: Box::new(Expression::Not(Not {
+            expr: Box::new(cond),
+            chain: None,
+            loc: None,
+        })),
+        then: Box::new(StatementOrExpression::Statement(Statement::Break(Break {
+            loc: None,
+        }))),
+        elseif: Vec::new(),
+        else_: None,
+        chain: None,
+        loc: None,
+    }))
+}
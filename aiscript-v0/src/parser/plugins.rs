@@ -1,4 +1,5 @@
 pub mod set_attribute;
 pub mod transform_chain;
+pub mod validate_comparison;
 pub mod validate_keyword;
 pub mod validate_type;
@@ -22,6 +22,9 @@ impl Visitor for ChainTransformer {
             | cst::Expression::Or(cst::Or {
                 chain: Some(chain), ..
             })
+            | cst::Expression::Coalesce(cst::Coalesce {
+                chain: Some(chain), ..
+            })
             | cst::Expression::If(cst::If {
                 chain: Some(chain), ..
             })
@@ -74,6 +77,12 @@ impl Visitor for ChainTransformer {
                         chain: None,
                         ..or.clone()
                     }),
+                    cst::Expression::Coalesce(coalesce) => {
+                        cst::Expression::Coalesce(cst::Coalesce {
+                            chain: None,
+                            ..coalesce.clone()
+                        })
+                    }
                     cst::Expression::If(if_) => cst::Expression::If(cst::If {
                         chain: None,
                         ..if_.clone()
@@ -136,18 +145,21 @@ impl Visitor for ChainTransformer {
                     cst::ChainMember::CallChain(call_chain) => cst::Expression::Call(cst::Call {
                         target: parent.into(),
                         args: call_chain.args.clone(),
+                        optional: call_chain.optional,
                         loc: call_chain.loc.clone(),
                     }),
                     cst::ChainMember::IndexChain(index_chain) => {
                         cst::Expression::Index(cst::Index {
                             target: parent.into(),
                             index: index_chain.index.clone().into(),
+                            optional: index_chain.optional,
                             loc: index_chain.loc.clone(),
                         })
                     }
                     cst::ChainMember::PropChain(prop_chain) => cst::Expression::Prop(cst::Prop {
                         target: parent.into(),
                         name: prop_chain.name.clone(),
+                        optional: prop_chain.optional,
                         loc: prop_chain.loc.clone(),
                     }),
                 },
@@ -0,0 +1,68 @@
+use crate::{
+    error::{AiScriptError, AiScriptSyntaxError},
+    node::Loc,
+    parser::{node as cst, visit::Visitor},
+};
+
+/// Functions which `<`, `>`, `<=` and `>=` are parsed into, which take numbers.
+const ORDERING: [&str; 4] = ["Core:lt", "Core:gt", "Core:lteq", "Core:gteq"];
+
+/// Functions which the comparison operators are parsed into, which return booleans.
+const COMPARISON: [&str; 6] = [
+    "Core:lt",
+    "Core:gt",
+    "Core:lteq",
+    "Core:gteq",
+    "Core:eq",
+    "Core:neq",
+];
+
+/// Returns the arguments and the location of the operator if `expression` is a call of one of
+/// `names`.
+fn comparison_args<'a>(
+    expression: &'a cst::Expression,
+    names: &[&str],
+) -> Option<(&'a [cst::Expression], &'a Option<Loc>)> {
+    match expression {
+        cst::Expression::Identifier(cst::Identifier {
+            name,
+            chain: Some(chain),
+            loc,
+        }) if names.contains(&name.as_str()) => match chain.as_slice() {
+            [cst::ChainMember::CallChain(cst::CallChain { args, .. })] => Some((args, loc)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct ComparisonValidator;
+
+impl Visitor for ComparisonValidator {
+    fn callback_expression(
+        &self,
+        expression: cst::Expression,
+    ) -> Result<cst::Expression, AiScriptError> {
+        // `1 < x < 10` is parsed into `(1 < x) < 10`, which always fails since a boolean is
+        // compared with a number.
+        if let Some((args, loc)) = comparison_args(&expression, &ORDERING) {
+            if args
+                .iter()
+                .any(|arg| comparison_args(arg, &COMPARISON).is_some())
+            {
+                Err(AiScriptSyntaxError::ChainedComparison(loc.clone()))?
+            }
+        }
+        Ok(expression)
+    }
+}
+
+pub fn validate_comparison(
+    nodes: impl IntoIterator<Item = cst::Node>,
+) -> Result<Vec<cst::Node>, AiScriptError> {
+    nodes
+        .into_iter()
+        .map(|node| ComparisonValidator.visit_node(node))
+        .collect()
+}
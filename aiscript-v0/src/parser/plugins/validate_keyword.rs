@@ -42,15 +42,21 @@ const RESERVED_WORD: [&str; 29] = [
     // "out",
 ];
 
+/// The words reserved only while generators are enabled via
+/// [`Parser::with_generators`](crate::Parser::with_generators).
+const GENERATOR_RESERVED_WORD: [&str; 1] = ["yield"];
+
 #[derive(Debug, PartialEq, Clone)]
-struct KeywordValidator;
+struct KeywordValidator {
+    reserved: &'static [&'static str],
+}
 
 impl Visitor for KeywordValidator {
     fn callback_namespace(
         &self,
         namespace: cst::Namespace,
     ) -> Result<cst::Namespace, AiScriptError> {
-        if RESERVED_WORD.contains(&namespace.name.as_str()) {
+        if self.reserved.contains(&namespace.name.as_str()) {
             Err(AiScriptSyntaxError::ReservedWord(namespace.name))?
         } else {
             Ok(namespace)
@@ -61,7 +67,7 @@ impl Visitor for KeywordValidator {
         match meta {
             cst::Meta {
                 name: Some(name), ..
-            } if RESERVED_WORD.contains(&name.as_str()) => {
+            } if self.reserved.contains(&name.as_str()) => {
                 Err(AiScriptSyntaxError::ReservedWord(name.to_string()))?
             }
             _ => Ok(meta),
@@ -75,7 +81,7 @@ impl Visitor for KeywordValidator {
         match &statement {
             cst::Statement::Definition(cst::Definition { name, .. })
             | cst::Statement::Attribute(cst::Attribute { name, .. }) => {
-                if RESERVED_WORD.contains(&name.as_str()) {
+                if self.reserved.contains(&name.as_str()) {
                     Err(AiScriptSyntaxError::ReservedWord(name.to_string()))?
                 } else {
                     Ok(statement)
@@ -91,7 +97,7 @@ impl Visitor for KeywordValidator {
     ) -> Result<cst::Expression, AiScriptError> {
         match &expression {
             cst::Expression::Identifier(cst::Identifier { name, .. }) => {
-                if RESERVED_WORD.contains(&name.as_str()) {
+                if self.reserved.contains(&name.as_str()) {
                     Err(AiScriptSyntaxError::ReservedWord(name.to_string()))?
                 } else {
                     Ok(expression)
@@ -99,7 +105,7 @@ impl Visitor for KeywordValidator {
             }
             cst::Expression::Fn(cst::Fn_ { args, .. }) => {
                 for arg in args {
-                    if RESERVED_WORD.contains(&arg.name.as_str()) {
+                    if self.reserved.contains(&arg.name.as_str()) {
                         Err(AiScriptSyntaxError::ReservedWord(arg.name.to_string()))?
                     }
                 }
@@ -115,7 +121,7 @@ impl Visitor for KeywordValidator {
     ) -> Result<cst::ChainMember, AiScriptError> {
         match &chain_member {
             cst::ChainMember::PropChain(cst::PropChain { name, .. }) => {
-                if RESERVED_WORD.contains(&name.as_str()) {
+                if self.reserved.contains(&name.as_str()) {
                     Err(AiScriptSyntaxError::ReservedWord(name.to_string()))?
                 } else {
                     Ok(chain_member)
@@ -124,6 +130,15 @@ impl Visitor for KeywordValidator {
             _ => Ok(chain_member),
         }
     }
+
+    fn callback_pattern(&self, pattern: cst::Pattern) -> Result<cst::Pattern, AiScriptError> {
+        match &pattern {
+            cst::Pattern::Bind { name, .. } if self.reserved.contains(&name.as_str()) => {
+                Err(AiScriptSyntaxError::ReservedWord(name.to_string()))?
+            }
+            _ => Ok(pattern),
+        }
+    }
 }
 
 pub fn validate_keyword(
@@ -131,6 +146,27 @@ pub fn validate_keyword(
 ) -> Result<Vec<cst::Node>, AiScriptError> {
     nodes
         .into_iter()
-        .map(|node| KeywordValidator.visit_node(node))
+        .map(|node| {
+            KeywordValidator {
+                reserved: &RESERVED_WORD,
+            }
+            .visit_node(node)
+        })
+        .collect()
+}
+
+/// Rejects the words reserved for generators as names, regardless of the plugins, since
+/// `yield` would be ambiguous with the `yield` statement otherwise.
+pub(crate) fn validate_generator_keyword(
+    nodes: Vec<cst::Node>,
+) -> Result<Vec<cst::Node>, AiScriptError> {
+    nodes
+        .into_iter()
+        .map(|node| {
+            KeywordValidator {
+                reserved: &GENERATOR_RESERVED_WORD,
+            }
+            .visit_node(node)
+        })
         .collect()
 }
@@ -0,0 +1,284 @@
+//! Tokens of a script, e.g. for syntax highlighting without parsing the whole script.
+
+use crate::node::Loc;
+
+use super::Parser;
+
+/// A token returned by [`Parser::tokenize`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// The source text of the token.
+    pub text: String,
+    /// The byte positions of the first and last characters of the token.
+    pub loc: Loc,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TokenKind {
+    /// A reserved word, including `true`, `false` and `null`.
+    Keyword,
+    /// A name, possibly with namespaces, e.g. `Core:to_str`.
+    Identifier,
+    Num,
+    /// A string literal with its quotes.
+    Str,
+    /// A part of a template literal other than the embedded expressions, e.g. `` `Hello, `` and
+    /// `` ! ` `` of `` `Hello, {name}!` ``. The braces around an expression are operators.
+    Tmpl,
+    Comment,
+    /// An operator or a punctuation, e.g. `+`, `<:` or `{`.
+    Operator,
+    /// Spaces and tabs.
+    Whitespace,
+    /// `\n`, `\r\n` or `\r`, which separates statements.
+    LineBreak,
+    /// A character which does not start any token.
+    Unknown,
+}
+
+const KEYWORDS: [&str; 32] = [
+    "null",
+    "true",
+    "false",
+    "each",
+    "for",
+    "loop",
+    "do",
+    "while",
+    "break",
+    "continue",
+    "match",
+    "case",
+    "if",
+    "elif",
+    "else",
+    "return",
+    "eval",
+    "var",
+    "let",
+    "exists",
+    "yield",
+    "fn",
+    "namespace",
+    "meta",
+    "attr",
+    "attribute",
+    "static",
+    "class",
+    "struct",
+    "module",
+    "import",
+    "export",
+];
+
+/// The operators, longer ones first.
+const OPERATORS: [&str; 39] = [
+    "###", "#[", "<:", "::", "=>", "==", "!=", "<=", ">=", "&&", "||", "??", "?.", "+=", "-=", "{",
+    "}", "(", ")", "[", "]", ",", ";", ":", ".", "+", "-", "*", "/", "%", "^", "!", "<", ">", "=",
+    "@", "?", "~", "#",
+];
+
+impl Parser {
+    /// Splits the input into tokens without parsing it.
+    ///
+    /// The tokens cover the whole input, including whitespace and comments, so that joining
+    /// their texts gives back the input. An unterminated string, template or comment continues
+    /// to the end of the input.
+    pub fn tokenize(&self, input: &str) -> Vec<Token> {
+        let mut tokenizer = Tokenizer {
+            input,
+            pos: 0,
+            tokens: Vec::new(),
+            embeds: Vec::new(),
+        };
+        tokenizer.run();
+        tokenizer.tokens
+    }
+}
+
+struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+    tokens: Vec<Token>,
+    /// The depths of braces in the expressions embedded in templates, innermost last.
+    embeds: Vec<usize>,
+}
+
+impl Tokenizer<'_> {
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn push(&mut self, kind: TokenKind, len: usize) {
+        let start = self.pos;
+        self.pos += len;
+        self.tokens.push(Token {
+            kind,
+            text: self.input[start..self.pos].to_string(),
+            loc: Loc {
+                start,
+                end: self.pos - 1,
+            },
+        });
+    }
+
+    fn run(&mut self) {
+        while let Some(c) = self.rest().chars().next() {
+            let rest = self.rest();
+            match c {
+                ' ' | '\t' => {
+                    let len = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+                    self.push(TokenKind::Whitespace, len);
+                }
+                '\r' if rest.starts_with("\r\n") => self.push(TokenKind::LineBreak, 2),
+                '\r' | '\n' => self.push(TokenKind::LineBreak, 1),
+                '/' if rest.starts_with("//") => {
+                    let len = rest.find(['\r', '\n']).unwrap_or(rest.len());
+                    self.push(TokenKind::Comment, len);
+                }
+                '/' if rest.starts_with("/*") => {
+                    let len = rest[2..].find("*/").map_or(rest.len(), |end| end + 4);
+                    self.push(TokenKind::Comment, len);
+                }
+                '"' | '\'' => {
+                    let len = quoted_len(rest, c);
+                    self.push(TokenKind::Str, len);
+                }
+                '`' => self.template(1),
+                '{' if !self.embeds.is_empty() => {
+                    *self.embeds.last_mut().unwrap() += 1;
+                    self.push(TokenKind::Operator, 1);
+                }
+                '}' if self.embeds.last() == Some(&0) => {
+                    self.embeds.pop();
+                    self.push(TokenKind::Operator, 1);
+                    self.template(0);
+                }
+                '}' if !self.embeds.is_empty() => {
+                    *self.embeds.last_mut().unwrap() -= 1;
+                    self.push(TokenKind::Operator, 1);
+                }
+                '0'..='9' => {
+                    let len = num_len(rest);
+                    self.push(TokenKind::Num, len);
+                }
+                'a'..='z' | 'A'..='Z' | '_' => {
+                    // The name of a variable has no namespaces, e.g. `x` of `let x:num = 0`.
+                    let defined = self
+                        .tokens
+                        .iter()
+                        .rev()
+                        .find(|token| token.kind != TokenKind::Whitespace)
+                        .is_some_and(|token| token.text == "let" || token.text == "var");
+                    let len = name_len(rest, !defined);
+                    let kind = if KEYWORDS.contains(&&rest[..len]) {
+                        TokenKind::Keyword
+                    } else {
+                        TokenKind::Identifier
+                    };
+                    self.push(kind, len);
+                }
+                _ => match OPERATORS
+                    .iter()
+                    .find(|operator| rest.starts_with(*operator))
+                {
+                    Some(operator) => self.push(TokenKind::Operator, operator.len()),
+                    None => self.push(TokenKind::Unknown, c.len_utf8()),
+                },
+            }
+        }
+    }
+
+    /// Pushes a part of a template starting `skip` bytes after the current position, up to the
+    /// closing backquote or the opening brace of an embedded expression.
+    fn template(&mut self, skip: usize) {
+        let rest = self.rest();
+        let mut chars = rest.char_indices().skip(skip);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' if rest[i + 1..].starts_with(['{', '}', '`']) => {
+                    chars.next();
+                }
+                '`' => {
+                    self.push(TokenKind::Tmpl, i + 1);
+                    return;
+                }
+                '{' => {
+                    if i > 0 {
+                        self.push(TokenKind::Tmpl, i);
+                    }
+                    self.push(TokenKind::Operator, 1);
+                    self.embeds.push(0);
+                    return;
+                }
+                _ => (),
+            }
+        }
+        if !rest.is_empty() {
+            self.push(TokenKind::Tmpl, rest.len());
+        }
+    }
+}
+
+/// Returns the length of a string literal quoted with `quote`, where only the quote can be
+/// escaped.
+fn quoted_len(input: &str, quote: char) -> usize {
+    let mut chars = input.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' && input[i + 1..].starts_with(quote) {
+            chars.next();
+        } else if c == quote {
+            return i + 1;
+        }
+    }
+    input.len()
+}
+
+/// Returns the length of a number literal, e.g. `0xff`, `1_000` or `1.5e-3`.
+fn num_len(input: &str) -> usize {
+    let bytes = input.as_bytes();
+    let digits = |start: usize, is_digit: fn(&u8) -> bool| {
+        let mut end = start;
+        while end < bytes.len() && (is_digit(&bytes[end]) || bytes[end] == b'_') {
+            end += 1;
+        }
+        end
+    };
+    if bytes.len() > 2 && bytes[0] == b'0' {
+        match bytes[1] {
+            b'x' | b'X' if bytes[2].is_ascii_hexdigit() => return digits(2, u8::is_ascii_hexdigit),
+            b'b' | b'B' if matches!(bytes[2], b'0' | b'1') => {
+                return digits(2, |b| matches!(b, b'0' | b'1'))
+            }
+            _ => (),
+        }
+    }
+    let mut end = digits(0, u8::is_ascii_digit);
+    if bytes.get(end) == Some(&b'.') && bytes.get(end + 1).is_some_and(u8::is_ascii_digit) {
+        end = digits(end + 1, u8::is_ascii_digit);
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        if bytes.get(end + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            end = digits(end + 1 + sign, u8::is_ascii_digit);
+        }
+    }
+    end
+}
+
+/// Returns the length of a name, with namespaces if `namespaced`, e.g. `Core:to_str`.
+fn name_len(input: &str, namespaced: bool) -> usize {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut end = input.find(|c| !is_name_char(c)).unwrap_or(input.len());
+    while namespaced
+        && input[end..].starts_with(':')
+        && input[end + 1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+    {
+        end += 1;
+        end += input[end..]
+            .find(|c| !is_name_char(c))
+            .unwrap_or(input.len() - end);
+    }
+    end
+}
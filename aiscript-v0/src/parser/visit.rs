@@ -2,6 +2,9 @@ use indexmap::IndexMap;
 
 use crate::{error::AiScriptError, parser::node as cst};
 
+/// Walks CST nodes and rebuilds them.
+///
+/// Each `callback_*` method is called on a node before its children are visited.
 pub trait Visitor {
     fn visit_node(&self, node: cst::Node) -> Result<cst::Node, AiScriptError> {
         match node {
@@ -45,7 +48,10 @@ pub trait Visitor {
 
     fn visit_meta(&self, meta: cst::Meta) -> Result<cst::Meta, AiScriptError> {
         let meta = self.callback_meta(meta)?;
-        Ok(meta)
+        Ok(cst::Meta {
+            value: self.visit_expression(meta.value)?,
+            ..meta
+        })
     }
 
     fn visit_statement(&self, statement: cst::Statement) -> Result<cst::Statement, AiScriptError> {
@@ -59,6 +65,10 @@ pub trait Visitor {
                 expr: self.visit_expression(return_.expr)?,
                 ..return_
             }),
+            cst::Statement::Yield(yield_) => cst::Statement::Yield(cst::Yield {
+                expr: self.visit_expression(yield_.expr)?,
+                ..yield_
+            }),
             cst::Statement::Attribute(_) => statement,
             cst::Statement::Each(each) => cst::Statement::Each(cst::Each {
                 items: self.visit_expression(each.items)?,
@@ -152,6 +162,11 @@ pub trait Visitor {
                 right: self.visit_expression(*or.right)?.into(),
                 ..or
             }),
+            cst::Expression::Coalesce(coalesce) => cst::Expression::Coalesce(cst::Coalesce {
+                left: self.visit_expression(*coalesce.left)?.into(),
+                right: self.visit_expression(*coalesce.right)?.into(),
+                ..coalesce
+            }),
             cst::Expression::If(if_) => cst::Expression::If(cst::If {
                 cond: self.visit_expression(*if_.cond)?.into(),
                 then: match *if_.then {
@@ -225,7 +240,7 @@ pub trait Visitor {
                     .into_iter()
                     .map(|cst::QA { q, a }| {
                         Ok(cst::QA {
-                            q: self.visit_expression(q)?,
+                            q: self.visit_pattern(q)?,
                             a: match a {
                                 cst::StatementOrExpression::Statement(statement) => self
                                     .visit_statement(statement)
@@ -444,6 +459,30 @@ pub trait Visitor {
         })
     }
 
+    fn visit_pattern(&self, pattern: cst::Pattern) -> Result<cst::Pattern, AiScriptError> {
+        let pattern = self.callback_pattern(pattern)?;
+        Ok(match pattern {
+            cst::Pattern::Expr(expression) => {
+                cst::Pattern::Expr(self.visit_expression(expression)?)
+            }
+            cst::Pattern::Bind { .. } => pattern,
+            cst::Pattern::Arr { items, loc } => cst::Pattern::Arr {
+                items: items
+                    .into_iter()
+                    .map(|item| self.visit_pattern(item))
+                    .collect::<Result<Vec<cst::Pattern>, AiScriptError>>()?,
+                loc,
+            },
+            cst::Pattern::Obj { entries, loc } => cst::Pattern::Obj {
+                entries: entries
+                    .into_iter()
+                    .map(|(k, v)| Ok((k, self.visit_pattern(v)?)))
+                    .collect::<Result<IndexMap<String, cst::Pattern>, AiScriptError>>()?,
+                loc,
+            },
+        })
+    }
+
     fn visit_chain_member(
         &self,
         chain_member: cst::ChainMember,
@@ -501,4 +540,8 @@ pub trait Visitor {
     ) -> Result<cst::ChainMember, AiScriptError> {
         Ok(chain_member)
     }
+
+    fn callback_pattern(&self, pattern: cst::Pattern) -> Result<cst::Pattern, AiScriptError> {
+        Ok(pattern)
+    }
 }
@@ -1,6 +1,10 @@
 use std::fmt::Display;
 
-use crate::{error::AiScriptSyntaxError, node as ast};
+use crate::{
+    error::AiScriptSyntaxError,
+    interpreter::value::{Value, V},
+    node as ast,
+};
 
 pub enum Type {
     Simple,
@@ -67,3 +71,27 @@ impl TryFrom<ast::TypeSource> for Type {
         }
     }
 }
+
+/// Checks whether a value is of a type. Only the arguments of a function type are not checked,
+/// since they are not known until the function is called.
+pub(crate) fn matches(type_: &ast::TypeSource, value: &Value) -> bool {
+    match type_ {
+        ast::TypeSource::NamedTypeSource(ast::NamedTypeSource { name, inner, .. }) => {
+            match (name.as_str(), &value.value) {
+                ("any", _) | ("null" | "void", V::Null) | ("bool", V::Bool(_)) => true,
+                ("num", V::Num(_)) | ("str", V::Str(_)) => true,
+                ("arr", V::Arr(arr)) => inner.as_ref().is_none_or(|inner| {
+                    arr.read().unwrap().iter().all(|item| matches(inner, item))
+                }),
+                ("obj", V::Obj(obj)) => inner.as_ref().is_none_or(|inner| {
+                    obj.read()
+                        .unwrap()
+                        .values()
+                        .all(|value| matches(inner, value))
+                }),
+                _ => false,
+            }
+        }
+        ast::TypeSource::FnTypeSource(_) => matches!(value.value, V::Fn(_)),
+    }
+}
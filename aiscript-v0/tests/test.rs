@@ -1,9 +1,13 @@
+use ::std::sync::Arc;
 use aiscript_v0::{
     ast::*,
-    errors::{AiScriptError, AiScriptRuntimeError},
+    errors::{
+        AiScriptError, AiScriptRuntimeError, AiScriptSyntaxError, Language, LoopInfo,
+        MessageCatalog, Warning,
+    },
     utils,
-    values::Value,
-    Interpreter, Parser,
+    values::{VFn, Value, V},
+    Interpreter, Parser, ProfileEntry, StepExecution, StepResult,
 };
 use futures::FutureExt;
 use indexmap::IndexMap;
@@ -23,6 +27,18 @@ async fn test(program: &str, test: fn(Value)) -> Result<Value, AiScriptError> {
     aiscript.exec(ast).await.map(|value| value.unwrap())
 }
 
+/// Creates an interpreter with `consts` and without the callbacks and the step limit, which tests
+/// configure further with the builder methods.
+fn new_interpreter(consts: impl IntoIterator<Item = (String, Value)>) -> Interpreter {
+    Interpreter::new(
+        consts,
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        None::<fn(_) -> _>,
+        None,
+    )
+}
+
 fn get_meta(program: &str) -> Result<IndexMap<Option<String>, Option<Value>>, AiScriptError> {
     let ast = Parser::default().parse(program)?;
     let metadata = Interpreter::collect_metadata(ast);
@@ -108,2478 +124,4053 @@ mod interpreter {
             assert_eq!(vars.get("x"), None);
             assert_eq!(vars.get("y"), None);
         }
-    }
-}
 
-mod ops {
-    use super::*;
-
-    #[tokio::test]
-    async fn eq() {
-        test("<: (1 == 1)", |res| assert_eq!(res, bool(true)))
+        #[tokio::test]
+        async fn inspect() {
+            let aiscript = new_interpreter([]);
+            aiscript
+                .exec(
+                    Parser::default()
+                        .parse(
+                            "#[handler 'click']\n@on_click() {}\nvar count = 0\n:: Ns { let k = 1 }",
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let bindings = aiscript.scope.inspect();
+            let binding = |name| bindings.iter().find(|b| b.name == name).unwrap();
+            let on_click = binding("on_click");
+            assert!(!on_click.mutable);
+            assert_eq!(on_click.attr.len(), 1);
+            assert_eq!(on_click.attr[0].name, "handler");
+            assert_eq!(on_click.attr[0].value, str("click"));
+            assert_eq!(on_click.loc, Some(Loc { start: 19, end: 32 }));
+            let count = binding("count");
+            assert!(count.mutable);
+            assert!(count.attr.is_empty());
+            assert_eq!(count.loc, Some(Loc { start: 34, end: 46 }));
+            assert!(binding("Ns:k").loc.is_some());
+            assert_eq!(binding("Core:add").loc, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn exec_with_context() {
+        let aiscript = new_interpreter([(
+            "whoami".to_string(),
+            Value::fn_native(|_, interpreter| {
+                let user = interpreter.context::<String>().cloned();
+                async move { Ok(user.map_or_else(Value::null, Value::str)) }.boxed()
+            }),
+        )]);
+        let script = Parser::default().parse("Core:sleep(10)\nwhoami()").unwrap();
+        let (a, b) = futures::try_join!(
+            aiscript.exec_with_context(script.clone(), Arc::new("alice".to_string())),
+            aiscript.exec_with_context(script.clone(), Arc::new("bob".to_string())),
+        )
+        .unwrap();
+        assert_eq!(a, Some(str("alice")));
+        assert_eq!(b, Some(str("bob")));
+        assert_eq!(aiscript.exec(script).await.unwrap(), Some(null()));
+    }
+
+    #[tokio::test]
+    async fn exec_with_io() {
+        let default_out = Arc::new(::std::sync::Mutex::new(Vec::new()));
+        let aiscript = Interpreter::new(
+            [],
+            None::<fn(_) -> _>,
+            Some({
+                let default_out = default_out.clone();
+                move |value| {
+                    default_out.lock().unwrap().push(value);
+                    async move {}.boxed()
+                }
+            }),
+            None::<fn(_) -> _>,
+            None,
+        );
+        let out = Arc::new(::std::sync::Mutex::new(Vec::new()));
+        let err = Arc::new(::std::sync::Mutex::new(Vec::new()));
+        let res = aiscript
+            .exec_with_io(
+                Parser::default().parse("<: 1\nCore:abort('hoge')").unwrap(),
+                Some({
+                    let out = out.clone();
+                    move |value| {
+                        out.lock().unwrap().push(value);
+                        async move {}.boxed()
+                    }
+                }),
+                Some({
+                    let err = err.clone();
+                    move |e| {
+                        err.lock().unwrap().push(e);
+                        async move {}.boxed()
+                    }
+                }),
+            )
             .await
             .unwrap();
+        assert_eq!(res, None);
+        assert_eq!(*out.lock().unwrap(), vec![num(1)]);
+        assert_eq!(
+            *err.lock().unwrap(),
+            vec![AiScriptError::Runtime(AiScriptRuntimeError::User(
+                "hoge".to_string()
+            ))]
+        );
 
-        test("<: (1 == 2)", |res| assert_eq!(res, bool(false)))
+        aiscript
+            .exec(Parser::default().parse("<: 2").unwrap())
             .await
             .unwrap();
+        assert_eq!(*default_out.lock().unwrap(), vec![num(2)]);
+        assert_eq!(*out.lock().unwrap(), vec![num(1)]);
     }
 
     #[tokio::test]
-    async fn neq() {
-        test("<: (1 != 2)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
-
-        test("<: (1 != 1)", |res| assert_eq!(res, bool(false)))
+    async fn exec_collect() {
+        let aiscript = Interpreter::new(
+            [],
+            None::<fn(_) -> _>,
+            Some(|_| panic!()),
+            None::<fn(_) -> _>,
+            None,
+        );
+        let res = aiscript
+            .exec_collect(
+                Parser::default()
+                    .parse(
+                        r#"
+                        <: 1
+                        each let x, ["a", "b"] { <: x }
+                        3
+                        "#,
+                    )
+                    .unwrap(),
+            )
             .await
             .unwrap();
+        assert_eq!(res, (Some(num(3)), vec![num(1), str("a"), str("b")]));
     }
 
-    #[tokio::test]
-    async fn and() {
-        test("<: (true && true)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+    mod exec_fn_catching {
+        use super::*;
 
-        test("<: (true && false)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        async fn get_fn(aiscript: &Interpreter, program: &str, name: &str) -> VFn {
+            aiscript
+                .exec(Parser::default().parse(program).unwrap())
+                .await
+                .unwrap();
+            VFn::try_from(aiscript.scope.get(name).unwrap()).unwrap()
+        }
 
-        test("<: (false && true)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn returns_value() {
+            let aiscript = Interpreter::default();
+            let f = get_fn(&aiscript, "@f(x) { x }", "f").await;
+            let res = aiscript.exec_fn_catching(f, [num(1)]).await.unwrap();
+            assert_eq!(res, num(1));
+        }
 
-        test("<: (false && false)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn converts_runtime_error() {
+            let aiscript = Interpreter::default();
+            let f = get_fn(&aiscript, "@f(x) { [1][x] }", "f").await;
+            let res = aiscript.exec_fn_catching(f, [num(5)]).await.unwrap();
+            assert_eq!(
+                res,
+                error(
+                    "index_out_of_range",
+                    Some(obj([
+                        (
+                            "message",
+                            str("Runtime: Index out of range. index: 5 max: 0")
+                        ),
+                        ("loc", null()),
+                        ("stack", arr([])),
+                    ]))
+                )
+            );
+        }
 
-        test("<: (false && null)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn native_error_value() {
+            let aiscript = new_interpreter([(
+                "raise".to_string(),
+                Value::fn_native(|_, _| {
+                    async move {
+                        Err(AiScriptRuntimeError::Thrown(error(
+                            "my_error",
+                            Some(str("info")),
+                        )))?
+                    }
+                    .boxed()
+                }),
+            )]);
+            let f = get_fn(&aiscript, "@g() { raise() }\n@f() { g() }", "f").await;
+            let res = aiscript.exec_fn_catching(f, []).await.unwrap();
+            assert_eq!(res, error("my_error", Some(str("info"))));
+        }
+    }
 
-        let err = test("<: (true && null)", |_| {}).await.unwrap_err();
-        assert!(matches!(err, AiScriptError::Runtime(_)));
+    mod exec_sync {
+        use super::*;
 
-        test(
-            r#"
-            var tmp = null
+        fn interpreter() -> Interpreter {
+            new_interpreter([(
+                "wait".to_string(),
+                Value::fn_native(|_, _| futures::future::pending().boxed()),
+            )])
+        }
 
-            @func() {
-                tmp = true
-                return true
-            }
+        fn exec_sync(program: &str) -> Result<Option<Value>, AiScriptError> {
+            interpreter().exec_sync(Parser::default().parse(program).unwrap())
+        }
 
-            false && func()
+        #[test]
+        fn returns_value() {
+            let res = exec_sync(
+                r#"
+                var sum = 0
+                for (let i, 1000) {
+                    sum += i
+                }
+                sum
+                "#,
+            )
+            .unwrap();
+            assert_eq!(res, Some(num(499500)));
+        }
 
-            <: tmp
-            "#,
-            |res| assert_eq!(res, null()),
-        )
-        .await
-        .unwrap();
+        #[test]
+        fn async_std() {
+            exec_sync("Core:sleep(0)").unwrap_err();
+            exec_sync("Async:timeout(0, @() {})").unwrap_err();
+        }
 
-        test(
-            r#"
-            var tmp = null
+        #[test]
+        fn waiting_native() {
+            exec_sync("wait()").unwrap_err();
+        }
+    }
 
-            @func() {
-                tmp = true
-                return true
-            }
+    mod metrics {
+        use super::*;
 
-            true && func()
+        #[tokio::test]
+        async fn counts() {
+            let aiscript = new_interpreter([]);
+            assert_eq!(aiscript.metrics(), Default::default());
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    @f(x) { x + 1 }
+                    let a = [f(1), f(2)]
+                    Arr:create(2, 'ab')
+                    "#,
+                )
+                .unwrap();
+            aiscript.exec(script).await.unwrap();
+            let metrics = aiscript.metrics();
+            assert_eq!(metrics.fn_calls, 2);
+            assert_eq!(metrics.native_calls, 3);
+            assert_eq!(
+                metrics.namespace_calls,
+                [("Arr".to_string(), 1), ("Core".to_string(), 2)].into()
+            );
+            assert_eq!(metrics.allocations, 2);
+            assert!(metrics.allocated_bytes >= 2);
+            assert_eq!(
+                metrics.steps,
+                aiscript
+                    .step_count
+                    .load(::std::sync::atomic::Ordering::SeqCst)
+            );
+            assert!(metrics.native_time.is_empty());
+        }
 
-            <: tmp
-            "#,
-            |res| assert_eq!(res, bool(true)),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn native_time() {
+            let aiscript = new_interpreter([]).with_native_timing(true);
+            let script = Parser::default()
+                .parse("Core:sleep(20)\nCore:sleep(10)\nMath:abs(-1)\n[1].map(@(v) { v })")
+                .unwrap();
+            aiscript.exec(script).await.unwrap();
+            let native_time = aiscript.metrics().native_time;
+            assert_eq!(
+                native_time.keys().collect::<Vec<_>>(),
+                ["Core:sleep", "Math:abs", "map"]
+            );
+            assert!(native_time["Core:sleep"] >= ::std::time::Duration::from_millis(30));
+        }
     }
 
-    #[tokio::test]
-    async fn or() {
-        test("<: (true || true)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
-
-        test("<: (true || false)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+    #[cfg(feature = "tracing")]
+    mod tracing_spans {
+        use ::std::{
+            fmt::Write,
+            sync::{
+                atomic::{AtomicU64, Ordering},
+                Mutex,
+            },
+        };
 
-        test("<: (false || true)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        use ::tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
 
-        test("<: (false || false)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        use super::*;
 
-        test("<: (true || null)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        /// Records the names and the fields of new spans.
+        #[derive(Default)]
+        struct Recorder {
+            spans: Arc<Mutex<Vec<String>>>,
+            next_id: AtomicU64,
+        }
 
-        let err = test("<: (false || null)", |_| {}).await.unwrap_err();
-        assert!(matches!(err, AiScriptError::Runtime(_)));
+        struct Fields(String);
 
-        test(
-            r#"
-            var tmp = null
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn ::std::fmt::Debug) {
+                write!(self.0, " {}={:?}", field.name(), value).unwrap();
+            }
+        }
 
-            @func() {
-                tmp = true
-                return true
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
             }
 
-            true || func()
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                let mut fields = Fields(span.metadata().name().to_string());
+                span.record(&mut fields);
+                self.spans.lock().unwrap().push(fields.0);
+                span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+            }
 
-            <: tmp
-            "#,
-            |res| assert_eq!(res, null()),
-        )
-        .await
-        .unwrap();
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
 
-        test(
-            r#"
-            var tmp = null
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
 
-            @func() {
-                tmp = true
-                return true
-            }
+            fn event(&self, _: &Event<'_>) {}
 
-            false || func()
+            fn enter(&self, _: &span::Id) {}
 
-            <: tmp
-            "#,
-            |res| assert_eq!(res, bool(true)),
-        )
-        .await
-        .unwrap();
-    }
+            fn exit(&self, _: &span::Id) {}
+        }
 
-    #[tokio::test]
-    async fn add() {
-        test("<: (1 + 1)", |res| assert_eq!(res, num(2)))
-            .await
-            .unwrap();
+        #[test]
+        fn spans() {
+            let recorder = Recorder::default();
+            let spans = recorder.spans.clone();
+            ::tracing::subscriber::with_default(recorder, || {
+                let script = Parser::default()
+                    .parse("let a = 1\nCore:add(a, 2)")
+                    .unwrap();
+                new_interpreter([])
+                    .with_script_name("main")
+                    .exec_sync(script)
+                    .unwrap();
+            });
+            assert_eq!(
+                *spans.lock().unwrap(),
+                [
+                    "aiscript.parse len=24",
+                    r#"aiscript.collect_ns script="main""#,
+                    r#"aiscript.statement script="main" start=0 end=8"#,
+                    r#"aiscript.statement script="main" start=18 end=23"#,
+                    r#"aiscript.native_call name="Core:add" script="main" start=18 end=23"#,
+                ]
+            );
+        }
     }
 
-    #[tokio::test]
-    async fn sub() {
-        test("<: (1 - 1)", |res| assert_eq!(res, num(0)))
-            .await
-            .unwrap();
-    }
+    mod event {
+        use ::std::sync::Mutex;
 
-    #[tokio::test]
-    async fn mul() {
-        test("<: (1 * 1)", |res| assert_eq!(res, num(1)))
-            .await
-            .unwrap();
-    }
+        use super::*;
 
-    #[tokio::test]
-    async fn pow() {
-        test("<: (1 ^ 1)", |res| assert_eq!(res, num(1)))
-            .await
-            .unwrap();
-    }
+        #[tokio::test]
+        async fn dispatch() {
+            let printed = Arc::new(Mutex::new(Vec::new()));
+            let aiscript = Interpreter::new(
+                [],
+                None::<fn(_) -> _>,
+                Some({
+                    let printed = printed.clone();
+                    move |value| {
+                        printed.lock().unwrap().push(value);
+                        async move {}.boxed()
+                    }
+                }),
+                None::<fn(_) -> _>,
+                None,
+            );
+            aiscript
+                .exec(
+                    Parser::default()
+                        .parse(
+                            r#"
+                            var count = 0
+                            Event:on("note", @(note) {
+                                count += 1
+                                <: `{count}: {note}`
+                            })
+                            Event:on("note", @(note) { <: note })
+                            "#,
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            aiscript.dispatch_event("note", str("a")).await.unwrap();
+            aiscript.dispatch_event("note", str("b")).await.unwrap();
+            aiscript.dispatch_event("other", str("c")).await.unwrap();
+            assert_eq!(
+                *printed.lock().unwrap(),
+                vec![str("1: a"), str("a"), str("2: b"), str("b")]
+            );
+        }
 
-    #[tokio::test]
-    async fn div() {
-        test("<: (1 / 1)", |res| assert_eq!(res, num(1)))
-            .await
-            .unwrap();
-    }
+        #[tokio::test]
+        async fn from_host() {
+            let aiscript = Interpreter::default();
+            let handler = VFn::FnNative(Arc::new(|args, _| {
+                async move {
+                    assert_eq!(args, vec![num(1)]);
+                    Ok(Value::null())
+                }
+                .boxed()
+            }));
+            aiscript.on_event("e", handler);
+            aiscript.dispatch_event("e", num(1)).await.unwrap();
+            aiscript.off_event("e");
+            aiscript.dispatch_event("e", num(2)).await.unwrap();
+        }
 
-    #[tokio::test]
-    async fn mod_() {
-        test("<: (1 % 1)", |res| assert_eq!(res, num(0)))
-            .await
-            .unwrap();
-    }
-
-    #[tokio::test]
-    async fn gt() {
-        test("<: (2 > 1)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
-
-        test("<: (1 > 1)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn handler_error() {
+            let errors = Arc::new(Mutex::new(Vec::new()));
+            let aiscript = Interpreter::new(
+                [],
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                Some({
+                    let errors = errors.clone();
+                    move |e: AiScriptError| {
+                        errors.lock().unwrap().push(e.to_string());
+                        async move {}.boxed()
+                    }
+                }),
+                None,
+            );
+            aiscript
+                .exec(
+                    Parser::default()
+                        .parse(
+                            r#"
+                            var count = 0
+                            Event:on("e", @() { [][1] })
+                            Event:on("e", @() { count += 1 })
+                            "#,
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            aiscript.dispatch_event("e", null()).await.unwrap();
+            aiscript.dispatch_event("e", null()).await.unwrap();
+            assert_eq!(errors.lock().unwrap().len(), 2);
+            assert_eq!(aiscript.scope.get("count").unwrap(), num(2));
 
-        test("<: (0 > 1)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+            let aiscript = new_interpreter([]);
+            aiscript
+                .exec(
+                    Parser::default()
+                        .parse(r#"Event:on("e", @() { [][1] })"#)
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            aiscript.dispatch_event("e", null()).await.unwrap_err();
+        }
     }
 
-    #[tokio::test]
-    async fn lt() {
-        test("<: (2 < 1)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+    mod warn {
+        use super::*;
 
-        test("<: (1 < 1)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        async fn warnings(src: &str) -> Vec<Warning> {
+            let warnings = Arc::new(::std::sync::Mutex::new(Vec::new()));
+            let aiscript = Interpreter::default().with_warn({
+                let warnings = warnings.clone();
+                move |warning| {
+                    warnings.lock().unwrap().push(warning);
+                    async move {}.boxed()
+                }
+            });
+            aiscript
+                .exec(Parser::default().parse(src).unwrap())
+                .await
+                .unwrap();
+            let warnings = warnings.lock().unwrap().clone();
+            warnings
+        }
 
-        test("<: (0 < 1)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn null_in_template() {
+            assert_eq!(
+                warnings("let a = null\n`a: {a}`").await,
+                [Warning {
+                    code: "null_in_template".to_string(),
+                    message: "null is interpolated into a template".to_string(),
+                    loc: Some(Loc { start: 18, end: 18 }),
+                }]
+            );
+            assert_eq!(warnings("`a: {1}`").await, []);
+        }
+
+        #[tokio::test]
+        async fn deprecated() {
+            let warnings = warnings(
+                r#"
+                #[deprecated "use g instead"]
+                @f() {}
+                #[deprecated]
+                @g() {}
+                f()
+                g()
+                "#,
+            )
+            .await;
+            assert_eq!(
+                warnings
+                    .iter()
+                    .map(|warning| (warning.code.as_str(), warning.message.as_str()))
+                    .collect::<Vec<_>>(),
+                [
+                    ("deprecated", "f is deprecated: use g instead"),
+                    ("deprecated", "g is deprecated"),
+                ]
+            );
+            assert!(warnings.iter().all(|warning| warning.loc.is_some()));
+        }
     }
 
-    #[tokio::test]
-    async fn gteq() {
-        test("<: (2 >= 1)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+    mod strict_integers {
+        use super::*;
 
-        test("<: (1 >= 1)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        async fn exec(strict: bool, src: &str) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter([]).with_strict_integers(strict);
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
 
-        test("<: (0 >= 1)", |res| assert_eq!(res, bool(false)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn arithmetic() {
+            for src in [
+                "9007199254740991 + 1",
+                "0 - 9007199254740991 - 1",
+                "4294967296 * 4294967296",
+                "2 ^ 53",
+                "Num:parse_int('9007199254740993')",
+            ] {
+                exec(false, src).await.unwrap();
+                exec(true, src).await.unwrap_err();
+            }
+            for (src, expected) in [
+                ("9007199254740990 + 1", num(9007199254740991i64 as f64)),
+                ("2 ^ 52", num(4503599627370496i64 as f64)),
+                ("Math:Infinity + 1", num(f64::INFINITY)),
+                ("1.5 * 9007199254740992", num(13510798882111488i64 as f64)),
+            ] {
+                assert_eq!(exec(true, src).await.unwrap(), expected);
+            }
+        }
+
+        #[tokio::test]
+        async fn json() {
+            let src = r#"Json:parse('{"id": [9007199254740993]}')"#;
+            exec(false, src).await.unwrap();
+            exec(true, src).await.unwrap_err();
+            assert_eq!(
+                exec(
+                    true,
+                    r#"Json:parse('{"id": "9007199254740993", "n": 1.5}')"#
+                )
+                .await
+                .unwrap(),
+                obj([("id", str("9007199254740993")), ("n", num(1.5))])
+            );
+        }
     }
 
     #[tokio::test]
-    async fn lteq() {
-        test("<: (2 <= 1)", |res| assert_eq!(res, bool(false)))
+    async fn definitions_with_attr() {
+        let aiscript = Interpreter::default();
+        aiscript
+            .exec(
+                Parser::default()
+                    .parse(
+                        r#"
+                        #[Event "note_created"]
+                        @on_note(note) { `note: {note}` }
+                        #[Event "followed"]
+                        #[Event "unfollowed"]
+                        @on_follow(user) { `follow: {user}` }
+                        #[Event "ignored"]
+                        let not_fn = 1
+                        #[Other]
+                        @other() {}
+                        "#,
+                    )
+                    .unwrap(),
+            )
             .await
             .unwrap();
+        let definitions = aiscript.definitions_with_attr("Event");
+        assert_eq!(
+            definitions
+                .iter()
+                .map(|(name, value, _)| (name.as_str(), value.clone()))
+                .collect::<Vec<_>>(),
+            [
+                ("on_follow", str("followed")),
+                ("on_follow", str("unfollowed")),
+                ("on_note", str("note_created")),
+            ]
+        );
+        let (_, _, on_note) = definitions.last().unwrap().clone();
+        assert_eq!(
+            aiscript.exec_fn(on_note, [str("hi")]).await.unwrap(),
+            str("note: hi")
+        );
+    }
 
-        test("<: (1 <= 1)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+    mod strict_variables {
+        use super::*;
 
-        test("<: (0 <= 1)", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
-    }
+        async fn exec(strict: bool, src: &str) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter([]).with_strict_variables(strict);
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
 
-    #[tokio::test]
-    async fn precedence() {
-        test("<: 1 + 2 * 3 + 4", |res| assert_eq!(res, num(11)))
-            .await
-            .unwrap();
+        fn root(result: Result<Value, AiScriptError>) -> AiScriptRuntimeError {
+            match result.unwrap_err() {
+                AiScriptError::Runtime(e) => e.root().clone(),
+                e => panic!("runtime error expected: {e}"),
+            }
+        }
 
-        test("<: 1 + 4 / 4 + 1", |res| assert_eq!(res, num(3)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn shadowing() {
+            let src = r#"
+            @f(x) {
+                let y = 1
+                if true {
+                    let y = 2
+                    y
+                }
+            }
+            f(0)
+            "#;
+            assert_eq!(exec(false, src).await.unwrap(), num(2));
+            assert_eq!(
+                root(exec(true, src).await),
+                AiScriptRuntimeError::Shadowing("y".to_string())
+            );
+            let src = "@f(x) { each let x, [1] {} }\nf(0)";
+            assert_eq!(
+                root(exec(true, src).await),
+                AiScriptRuntimeError::Shadowing("x".to_string())
+            );
+        }
 
-        test("<: 1 + 1 == 2 && 2 * 2 == 4", |res| {
-            assert_eq!(res, bool(true))
-        })
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn shadowing_in_another_function() {
+            let src = r#"
+            let x = 1
+            @f() {
+                let x = 2
+                x
+            }
+            f() + x
+            "#;
+            assert_eq!(exec(true, src).await.unwrap(), num(3));
+        }
 
-        test("<: (1 + 1) * 2", |res| assert_eq!(res, num(4)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn assign_to_outer() {
+            let src = r#"
+            var count = 0
+            :: Counter {
+                @inc() {
+                    count += 1
+                }
+            }
+            @inc() {
+                count += 1
+            }
+            inc()
+            Counter:inc()
+            count
+            "#;
+            assert_eq!(exec(false, src).await.unwrap(), num(2));
+            let err = exec(true, src).await.unwrap_err();
+            assert_eq!(err.code(), "assign_to_outer");
+            assert_eq!(
+                err.to_string(),
+                "Runtime: Cannot assign to variable 'count' defined outside the namespace."
+            );
+        }
+
+        #[tokio::test]
+        async fn used_before_definition() {
+            for src in ["let y = x\nlet x = 1", "@f() { x }\nf()\nlet x = 1"] {
+                assert_eq!(root(exec(false, src).await).code(), "no_such_variable");
+                assert_eq!(
+                    root(exec(true, src).await),
+                    AiScriptRuntimeError::UsedBeforeDefinition("x".to_string())
+                );
+            }
+            let src = "if true { <: x\nlet x = 1 }";
+            assert_eq!(
+                root(exec(true, src).await),
+                AiScriptRuntimeError::UsedBeforeDefinition("x".to_string())
+            );
+            assert_eq!(root(exec(true, "<: x").await).code(), "no_such_variable");
+        }
     }
 
-    #[tokio::test]
-    async fn negative_numbers() {
-        test("<: 1+-1", |res| assert_eq!(res, num(0)))
-            .await
-            .unwrap();
+    mod cycles {
+        use ::std::sync::Weak;
 
-        test("<: 1--1", |res| assert_eq!(res, num(2)))
-            .await
-            .unwrap();
+        use super::*;
 
-        test("<: -1*-1", |res| assert_eq!(res, num(1)))
-            .await
-            .unwrap();
+        async fn make(aiscript: &Interpreter, program: &str) -> Value {
+            aiscript
+                .exec(Parser::default().parse(program).unwrap())
+                .await
+                .unwrap();
+            let make = VFn::try_from(aiscript.scope.get("make").unwrap()).unwrap();
+            aiscript.exec_fn_simple(make, []).await.unwrap()
+        }
 
-        test("<: -1==-1", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn array_in_itself() {
+            let aiscript = Interpreter::default();
+            let value = make(&aiscript, "@make() { let a = [1]\na.push(a)\na }").await;
+            let V::Arr(arr) = value.value else {
+                panic!("array expected")
+            };
+            let weak = Arc::downgrade(&arr);
+            drop(arr);
+            assert!(weak.upgrade().is_some());
+            assert_eq!(aiscript.collect_cycles(), 1);
+            assert!(weak.upgrade().is_none());
+        }
 
-        test("<: 1>-1", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn closure_in_its_scope() {
+            let aiscript = Interpreter::default();
+            let value = make(
+                &aiscript,
+                r#"
+                @make() {
+                    let state = { n: 0 }
+                    @inc() { state.n += 1 }
+                    state.inc = inc
+                    state
+                }
+                "#,
+            )
+            .await;
+            let V::Obj(obj) = value.value else {
+                panic!("object expected")
+            };
+            let weak: Weak<_> = Arc::downgrade(&obj);
+            drop(obj);
+            assert!(weak.upgrade().is_some());
+            assert!(aiscript.collect_cycles() >= 2);
+            assert!(weak.upgrade().is_none());
+        }
 
-        test("<: -1<1", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn keeps_reachable() {
+            let aiscript = Interpreter::default();
+            let value = make(
+                &aiscript,
+                "let a = []\na.push(a)\n@make() { let b = [a]\nb.push(b)\nb }",
+            )
+            .await;
+            assert_eq!(aiscript.collect_cycles(), 0);
+            let V::Arr(b) = &value.value else {
+                panic!("array expected")
+            };
+            assert_eq!(b.read().unwrap().len(), 2);
+            let script = Parser::default().parse("a.len").unwrap();
+            assert_eq!(aiscript.exec(script).await.unwrap(), Some(num(1)));
+        }
     }
-}
 
-mod infix_expression {
-    use super::*;
+    mod size_limits {
+        use super::*;
 
-    #[tokio::test]
-    async fn simple_infix_expression() {
-        test("<: 0 < 1", |res| assert_eq!(res, bool(true)))
-            .await
-            .unwrap();
+        async fn exec(src: &str) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter([]).with_max_str_len(10).with_max_arr_len(5);
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
 
-        test("<: 1 + 1", |res| assert_eq!(res, num(2)))
-            .await
-            .unwrap();
-    }
+        #[tokio::test]
+        async fn within_limits() {
+            assert_eq!(
+                exec("let a = [1, 2, 3, 4]\na.push(5)\n`{a.len}{'x'.pad_start(9)}`")
+                    .await
+                    .unwrap(),
+                str("5        x")
+            );
+        }
 
-    #[tokio::test]
-    async fn combination() {
-        test("<: 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10", |res| {
-            assert_eq!(res, num(55))
-        })
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn arr_len() {
+            for src in [
+                "[1, 2, 3, 4, 5].push(6)",
+                "[1, 2, 3, 4, 5].unshift(6)",
+                "[1, 2, 3, 4, 5].insert(0, 6)",
+                "[1, 2, 3].splice(0, 0, [4, 5, 6])",
+                "[1, 2].repeat(3)",
+                "[1, 2, 3].concat([4, 5, 6])",
+                "Arr:create(1000000000000, 0)",
+                "Core:range(1, 6)",
+                "Core:range(0, Math:pow(10, 300))",
+                "Arr:range_by(0, 10, 2)",
+            ] {
+                let err = exec(src).await.unwrap_err();
+                assert_eq!(err.code(), "limit_exceeded", "{src}");
+            }
+            let err = exec("let a = [1, 2, 3, 4, 5]\na.push(6)")
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Runtime: max_arr_len exceeded: length 6 > 5"
+            );
+        }
 
-        test("<: Core:add(1, 3) * Core:mul(2, 5)", |res| {
-            assert_eq!(res, num(40))
-        })
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn str_len() {
+            for src in [
+                "`{'hello'}{'world'}!`",
+                "'x'.pad_end(1000000000000)",
+                "'あ'.pad_start(4, 'あ')",
+                "['hello', 'world'].join(' ')",
+                "Core:to_str([1, 2, 3, 4])",
+            ] {
+                let err = exec(src).await.unwrap_err();
+                assert_eq!(err.code(), "limit_exceeded", "{src}");
+            }
+            assert_eq!(
+                exec("`{'hello'}{'world'}!`").await.unwrap_err().to_string(),
+                "Runtime: max_str_len exceeded: length 11 > 10"
+            );
+        }
 
-    #[tokio::test]
-    async fn use_parentheses_to_distinguish_expr() {
-        test("<: (1 + 10) * (2 + 5)", |res| assert_eq!(res, num(77)))
-            .await
-            .unwrap();
+        #[tokio::test]
+        async fn array_not_grown_over_limit() {
+            let aiscript = new_interpreter([]).with_max_arr_len(2);
+            let script = Parser::default()
+                .parse("let a = [1, 2]\na.push(3)")
+                .unwrap();
+            assert!(aiscript.exec(script).await.is_err());
+            let script = Parser::default().parse("a.len").unwrap();
+            assert_eq!(aiscript.exec(script).await.unwrap(), Some(num(2)));
+        }
     }
 
-    #[tokio::test]
-    async fn syntax_symbols_vs_infix_operators() {
-        test(
-            r#"
-            <: match true {
-                1 == 1 => "true"
-                1 < 1 => "false"
-            }
-            "#,
-            |res| assert_eq!(res, str("true")),
-        )
-        .await
-        .unwrap();
-    }
+    mod clock {
+        use ::std::{sync::Mutex, time::Duration};
 
-    #[tokio::test]
-    async fn number_if_expression() {
-        test("<: 1 + if true 1 else 2", |res| assert_eq!(res, num(2)))
-            .await
-            .unwrap();
-    }
+        use aiscript_v0::{Clock, ManualClock};
 
-    #[tokio::test]
-    async fn number_match_expression() {
-        test(
-            r#"
-            <: 1 + match 2 == 2 {
-                true => 3
-                false => 4
+        use super::*;
+
+        fn interpreter(clock: Arc<ManualClock>, out: Arc<Mutex<Vec<Value>>>) -> Interpreter {
+            Interpreter::new(
+                [],
+                None::<fn(_) -> _>,
+                Some(move |v| {
+                    out.lock().unwrap().push(v);
+                    async {}.boxed()
+                }),
+                None::<fn(_) -> _>,
+                None,
+            )
+            .with_clock(clock)
+        }
+
+        #[tokio::test]
+        async fn sleep() {
+            let clock = Arc::new(ManualClock::new(1000.0));
+            let out = Arc::new(Mutex::new(Vec::new()));
+            let aiscript = interpreter(clock.clone(), out.clone());
+            let script = Parser::default()
+                .parse("<: Date:now()\nCore:sleep(60000)\n<: Date:now()")
+                .unwrap();
+            let exec = tokio::spawn(async move { aiscript.exec(script).await });
+            tokio::task::yield_now().await;
+            clock.advance(Duration::from_secs(59));
+            tokio::task::yield_now().await;
+            assert!(!exec.is_finished());
+            clock.advance(Duration::from_secs(1));
+            exec.await.unwrap().unwrap();
+            assert_eq!(*out.lock().unwrap(), vec![num(1000), num(61000)]);
+            assert_eq!(clock.now(), 61000.0);
+        }
+
+        #[tokio::test]
+        async fn timers() {
+            let clock = Arc::new(ManualClock::new(0.0));
+            let out = Arc::new(Mutex::new(Vec::new()));
+            let aiscript = interpreter(clock.clone(), out.clone());
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    Async:timeout(500, @() { <: 'timeout' })
+                    Async:interval(200, @() { <: Date:now() })
+                    "#,
+                )
+                .unwrap();
+            aiscript.exec(script).await.unwrap();
+            // Lets the timers start.
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            for _ in 0..3 {
+                clock.advance(Duration::from_millis(200));
+                tokio::time::sleep(Duration::from_millis(10)).await;
             }
-            "#,
-            |res| assert_eq!(res, num(4)),
-        )
-        .await
-        .unwrap();
-    }
+            assert_eq!(
+                *out.lock().unwrap(),
+                vec![num(200), num(400), str("timeout"), num(600)]
+            );
+            aiscript.abort();
+        }
 
-    #[tokio::test]
-    async fn eval_eval() {
-        test("<: eval { 1 } + eval { 1 }", |res| assert_eq!(res, num(2)))
-            .await
-            .unwrap();
-    }
+        #[tokio::test]
+        async fn abort_cancels_sleep() {
+            let clock = Arc::new(ManualClock::new(0.0));
+            let out = Arc::new(Mutex::new(Vec::new()));
+            let aiscript = interpreter(clock, out.clone());
+            let script = Parser::default()
+                .parse("Core:sleep(1000)\n<: 'woke'")
+                .unwrap();
+            let exec = tokio::spawn({
+                let aiscript = aiscript.clone();
+                async move { aiscript.exec(script).await }
+            });
+            tokio::task::yield_now().await;
+            aiscript.abort();
+            exec.await.unwrap().unwrap();
+            assert!(out.lock().unwrap().is_empty());
+        }
 
-    #[tokio::test]
-    async fn disallow_line_break() {
-        test(
-            r#"
-            <: 1 +
-            1 + 1
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
+        #[tokio::test]
+        async fn abort_stops_child() {
+            let clock = Arc::new(ManualClock::new(0.0));
+            let out = Arc::new(Mutex::new(Vec::new()));
+            let aiscript = interpreter(clock, out.clone());
+            let child = aiscript.spawn_child([] as [&str; 0], None).unwrap();
+            let script = Parser::default()
+                .parse("Core:sleep(1000)\n<: 'woke'")
+                .unwrap();
+            let exec = tokio::spawn(async move { child.exec(script).await });
+            tokio::task::yield_now().await;
+            aiscript.abort();
+            exec.await.unwrap().unwrap();
+            assert!(out.lock().unwrap().is_empty());
+        }
     }
 
-    #[tokio::test]
-    async fn escaped_line_break() {
-        test(
-            r#"
-            <: 1 + \
-            1 + 1
-            "#,
-            |res| assert_eq!(res, num(3)),
-        )
-        .await
-        .unwrap();
-    }
+    mod loop_guard {
+        use super::*;
 
-    #[tokio::test]
-    async fn infix_to_fncall_on_namespace() {
-        test(
-            r#"
-            :: Hoge {
-                @add(x, y) {
-                    x + y
-                }
+        async fn exec(
+            src: &str,
+            max_step: Option<usize>,
+            max_loop_iterations: Option<usize>,
+        ) -> Result<Value, AiScriptError> {
+            let mut aiscript = Interpreter::new(
+                [],
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                max_step,
+            );
+            if let Some(max_loop_iterations) = max_loop_iterations {
+                aiscript = aiscript.with_max_loop_iterations(max_loop_iterations);
             }
-            <: Hoge:add(1, 2)
-            "#,
-            |res| assert_eq!(res, num(3)),
-        )
-        .await
-        .unwrap();
-    }
-}
-
-mod comment {
-    use super::*;
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
+
+        fn in_loop(err: &AiScriptError) -> Option<LoopInfo> {
+            let AiScriptError::Runtime(e) = err else {
+                panic!("{err:?}");
+            };
+            match e.root() {
+                AiScriptRuntimeError::MaxStepExceeded { in_loop } => in_loop.clone(),
+                AiScriptRuntimeError::MaxLoopIterationsExceeded(in_loop) => Some(in_loop.clone()),
+                e => panic!("{e:?}"),
+            }
+        }
 
-    #[tokio::test]
-    async fn single_line_comment() {
-        test(
-            r#"
-            // let a = ...
-            let a = 42
-            <: a
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn max_step_in_loop() {
+            let src = "var a = 1\nloop { a += 1 }";
+            let err = exec(src, Some(100), None).await.unwrap_err();
+            assert_eq!(err.code(), "max_step_exceeded");
+            let in_loop = in_loop(&err).unwrap();
+            let loc = in_loop.loc.unwrap();
+            assert!(src[loc.start..].starts_with("loop {"));
+            assert!(in_loop.iterations > 1);
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Runtime: max step exceeded in the loop at {}..{} after {} iterations",
+                    loc.start, loc.end, in_loop.iterations
+                )
+            );
+        }
 
-    #[tokio::test]
-    async fn multi_line_comment() {
-        test(
-            r#"
-            /* variable declaration here...
-                let a = ...
-            */
-            let a = 42
-            <: a
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn max_step_in_innermost_loop() {
+            let src = "each let x, [1, 2] {\n\tfor 10000 { x }\n}";
+            let err = exec(src, Some(100), None).await.unwrap_err();
+            let in_loop = in_loop(&err).unwrap();
+            assert!(src[in_loop.loc.unwrap().start..].starts_with("for 10000"));
+        }
 
-    #[tokio::test]
-    async fn multi_line_comment_2() {
-        test(
-            r#"
-            /* variable declaration here...
-                let a = ...
-            */
-            let a = 42
-            /*
-                another comment here
-            */
-            <: a
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
-    }
-}
+        #[tokio::test]
+        async fn max_step_in_loop_of_called_fn() {
+            let src = "@f() { for let i, 10000 { i } }\nfor 2 { f() }";
+            let err = exec(src, Some(100), None).await.unwrap_err();
+            let in_loop = in_loop(&err).unwrap();
+            assert!(src[in_loop.loc.unwrap().start..].starts_with("for let i"));
+        }
 
-#[tokio::test]
-async fn expression_containing_collon_is_not_object() {
-    test(
-        r#"
-        <: eval {
-            Core:eq("ai", "ai")
+        #[tokio::test]
+        async fn max_step_outside_loop() {
+            let src = "1\n".repeat(200);
+            let err = exec(&src, Some(100), None).await.unwrap_err();
+            assert_eq!(in_loop(&err), None);
+            assert_eq!(err.to_string(), "Runtime: max step exceeded");
         }
-        "#,
-        |res| assert_eq!(res, bool(true)),
-    )
-    .await
-    .unwrap();
-}
 
-#[tokio::test]
-async fn inc() {
-    test(
-        r#"
-        var a = 0
-        a += 1
-        a += 2
-        a += 3
-        <: a
-        "#,
-        |res| assert_eq!(res, num(6)),
-    )
-    .await
-    .unwrap();
-}
+        #[tokio::test]
+        async fn max_loop_iterations() {
+            assert_eq!(
+                exec(
+                    "var a = 0\nfor 10 { a += 1 }\neach let x, [1, 2, 3] { a += x }\na",
+                    None,
+                    Some(10),
+                )
+                .await
+                .unwrap(),
+                num(16)
+            );
+            for src in [
+                "for 11 { 1 }",
+                "for let i, 11 { i }",
+                "each let x, Core:range(1, 11) { x }",
+                "var i = 0\nloop { i += 1\nif i > 11 break }",
+            ] {
+                let err = exec(src, None, Some(10)).await.unwrap_err();
+                assert_eq!(err.code(), "max_loop_iterations_exceeded", "{src}");
+                let in_loop = in_loop(&err).unwrap();
+                assert_eq!(in_loop.iterations, 11, "{src}");
+                assert!(in_loop.loc.is_some(), "{src}");
+            }
+        }
 
-#[tokio::test]
-async fn dec() {
-    test(
-        r#"
-        var a = 0
-        a -= 1
-        a -= 2
-        a -= 3
-        <: a
-        "#,
-        |res| assert_eq!(res, num(-6)),
-    )
-    .await
-    .unwrap();
-}
+        #[tokio::test]
+        async fn max_loop_iterations_per_run() {
+            assert_eq!(
+                exec("var a = 0\nfor 3 { for 10 { a += 1 } }\na", None, Some(10))
+                    .await
+                    .unwrap(),
+                num(30)
+            );
+        }
+    }
 
-#[tokio::test]
-async fn reference_is_not_chained() {
-    test(
-        r#"
-        var f = @() { "a" }
-        var g = f
-        f = @() { "b" }
+    mod compat_profile {
+        use super::*;
+        use aiscript_v0::CompatProfile;
+
+        async fn exec(src: &str, profile: CompatProfile) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter([]).with_compat_profile(profile);
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
+
+        /// Sources and their results in the native and upstream profiles.
+        const MATRIX: [(&str, &str, &str); 12] = [
+            ("[1] == [1]", "true", "false"),
+            ("[[1]].index_of([1])", "0", "-1"),
+            ("[[1]].incl([1])", "true", "false"),
+            ("`{1e21}`", "1000000000000000000000", "1e+21"),
+            ("`{1e-7}`", "0.0000001", "1e-7"),
+            ("`{[Math:Infinity, -0]}`", "[ inf, -0 ]", "[ Infinity, 0 ]"),
+            (
+                "Core:to_str({ a: 1e30 })",
+                "{ a: 1000000000000000000000000000000 }",
+                "{ a: 1e+30 }",
+            ),
+            ("(1e21).to_str()", "1000000000000000000000", "1e+21"),
+            (
+                "`{0.1 + 0.2}`",
+                "0.30000000000000004",
+                "0.30000000000000004",
+            ),
+            ("`{Math:sqrt(-1)}`", "NaN", "NaN"),
+            ("Math:sqrt(-1) == Math:sqrt(-1)", "false", "false"),
+            ("let a = [1]\na == a", "true", "true"),
+        ];
 
-        <: g()
-        "#,
-        |res| assert_eq!(res, str("a")),
-    )
-    .await
-    .unwrap();
-}
+        #[tokio::test]
+        async fn matrix() {
+            for (src, native, upstream) in MATRIX {
+                for (profile, expected) in [
+                    (CompatProfile::Native, native),
+                    (CompatProfile::Upstream, upstream),
+                ] {
+                    let res = exec(&format!("Core:to_str(eval {{ {src} }})"), profile)
+                        .await
+                        .unwrap();
+                    assert_eq!(res, str(expected), "{src} in {profile:?}");
+                }
+            }
+        }
 
-mod cannot_put_multiple_statements_in_a_line {
-    use super::*;
+        #[tokio::test]
+        async fn index() {
+            for profile in [CompatProfile::Native, CompatProfile::Upstream] {
+                for src in [
+                    "[1, 2][-1]",
+                    "[1, 2][0.5]",
+                    "[1, 2][2]",
+                    "let a = [1]\na[-1] = 2",
+                ] {
+                    let err = exec(src, profile).await.unwrap_err();
+                    assert_eq!(err.code(), "index_out_of_range", "{src} in {profile:?}");
+                }
+            }
+        }
 
-    #[tokio::test]
-    async fn var_def() {
-        test(
-            r#"
-            let a = 42 let b = 11
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
+        #[tokio::test]
+        async fn override_option() {
+            let aiscript = new_interpreter([])
+                .with_compat_profile(CompatProfile::Upstream)
+                .with_reference_equality(false);
+            let script = Parser::default().parse("[[1] == [1], `{1e21}`]").unwrap();
+            assert_eq!(
+                aiscript.exec(script).await.unwrap().unwrap(),
+                arr([bool(true), str("1e+21")])
+            );
+        }
     }
 
-    #[tokio::test]
-    async fn var_def_op() {
-        test(
-            r#"
-            let a = 13 + 75 let b = 24 + 146
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-    }
-}
+    mod input {
+        use super::*;
+        use ::std::sync::Mutex;
+        use aiscript_v0::{InputKind, InputRequest};
 
-#[tokio::test]
-async fn empty_function() {
-    test(
-        r#"
-        @hoge() { }
-        <: hoge()
-        "#,
-        |res| assert_eq!(res, null()),
-    )
-    .await
-    .unwrap();
-}
+        async fn exec(
+            src: &str,
+            in_: Option<fn(String) -> futures::future::BoxFuture<'static, String>>,
+        ) -> Result<Value, AiScriptError> {
+            let aiscript = Interpreter::new([], in_, None::<fn(_) -> _>, None::<fn(_) -> _>, None);
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
 
-#[tokio::test]
-async fn empty_lambda() {
-    test(
-        r#"
-        let hoge = @() { }
-        <: hoge()
-        "#,
-        |res| assert_eq!(res, null()),
-    )
-    .await
-    .unwrap();
-}
+        #[tokio::test]
+        async fn structured_requests() {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let aiscript = new_interpreter([]).with_input({
+                let requests = requests.clone();
+                move |request: InputRequest| {
+                    let answer = match request.kind {
+                        InputKind::Text => Some(Value::str("Alice")),
+                        InputKind::Number => Some(Value::num(42)),
+                        InputKind::Confirm => Some(Value::bool(false)),
+                        InputKind::Select => None,
+                    };
+                    requests.lock().unwrap().push(request);
+                    async move { answer }.boxed()
+                }
+            });
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    [
+                        readline('Name?')
+                        Ui:input('Age?', { type: 'number', default: 20 })
+                        Ui:confirm('OK?', true)
+                        Ui:select('Color?', ['red', 'blue'], 'blue')
+                        Ui:select('Size?', ['S', 'M'])
+                    ]
+                    "#,
+                )
+                .unwrap();
+            assert_eq!(
+                aiscript.exec(script).await.unwrap().unwrap(),
+                arr([str("Alice"), num(42), bool(false), str("blue"), null()])
+            );
+            let requests = requests.lock().unwrap();
+            assert_eq!(
+                requests[1],
+                InputRequest {
+                    prompt: "Age?".to_string(),
+                    kind: InputKind::Number,
+                    default: Some(num(20)),
+                    choices: Vec::new(),
+                }
+            );
+            assert_eq!(
+                requests[3],
+                InputRequest {
+                    prompt: "Color?".to_string(),
+                    kind: InputKind::Select,
+                    default: Some(str("blue")),
+                    choices: vec!["red".to_string(), "blue".to_string()],
+                }
+            );
+        }
 
-#[tokio::test]
-async fn lambda_that_returns_an_object() {
-    test(
-        r#"
-        let hoge = @() {{}}
-        <: hoge()
-        "#,
-        |res| assert_eq!(res, obj([] as [(String, Value); 0])),
-    )
-    .await
-    .unwrap();
-}
+        #[tokio::test]
+        async fn answer_of_wrong_kind() {
+            let aiscript = new_interpreter([])
+                .with_input(|_| async move { Some(Value::str("green")) }.boxed());
+            let script = Parser::default()
+                .parse("[Ui:confirm('OK?', true), Ui:select('Color?', ['red']), Ui:input('?')]")
+                .unwrap();
+            assert_eq!(
+                aiscript.exec(script).await.unwrap().unwrap(),
+                arr([bool(true), null(), str("green")])
+            );
+        }
 
-#[tokio::test]
-async fn closure() {
-    test(
-        r#"
-        @store(v) {
-            let state = v
-            @() {
-                state
+        #[tokio::test]
+        async fn text_input() {
+            fn echo(prompt: String) -> futures::future::BoxFuture<'static, String> {
+                async move {
+                    match prompt.as_str() {
+                        "Color? (1: red, 2: blue) [red]" => "2",
+                        "OK? (y/n)" => "Yes",
+                        "Age? [20]" => "",
+                        "Name?" => "",
+                        _ => "?",
+                    }
+                    .to_string()
+                }
+                .boxed()
             }
+            assert_eq!(
+                exec(
+                    r#"
+                    [
+                        Ui:select('Color?', ['red', 'blue'], 'red')
+                        Ui:confirm('OK?')
+                        Ui:input('Age?', { type: 'number', default: 20 })
+                        readline('Name?')
+                        Ui:input('Name?')
+                    ]
+                    "#,
+                    Some(echo),
+                )
+                .await
+                .unwrap(),
+                arr([str("blue"), bool(true), num(20), str(""), null()])
+            );
         }
-        let s = store("ai")
-        <: s()
-        "#,
-        |res| assert_eq!(res, str("ai")),
-    )
-    .await
-    .unwrap();
-}
 
-#[tokio::test]
-async fn closure_counter() {
-    test(
-        r#"
-        @create_counter() {
-            var count = 0
-            {
-                get_count: @() { count };
-                count: @() { count = (count + 1) };
-            }
+        #[tokio::test]
+        async fn no_input() {
+            assert_eq!(
+                exec(
+                    "[readline('?'), Ui:input('?', { default: 'a' }), Ui:confirm('?')]",
+                    None
+                )
+                .await
+                .unwrap(),
+                arr([null(), str("a"), null()])
+            );
         }
 
-        let counter = create_counter()
-        let get_count = counter.get_count
-        let count = counter.count
+        #[tokio::test]
+        async fn invalid_arguments() {
+            for src in [
+                "Ui:input('?', { type: 'date' })",
+                "Ui:input('?', { type: 'number', default: '1' })",
+                "Ui:confirm('?', 1)",
+                "Ui:select('?', ['a'], 'b')",
+                "Ui:select('?', [1])",
+            ] {
+                assert!(exec(src, None).await.is_err(), "{src}");
+            }
+        }
+    }
 
-        count()
-        count()
-        count()
+    mod time_travel {
+        use super::*;
 
-        <: get_count()
-        "#,
-        |res| assert_eq!(res, num(3)),
-    )
-    .await
-    .unwrap();
-}
+        const SCRIPT: &str = r#"
+            var i = 0
+            let log = []
+            for 10 {
+                i += 1
+                log.push(i)
+            }
+            i
+        "#;
 
-#[tokio::test]
-async fn recursion() {
-    test(
-        r#"
-        @fact(n) {
-            if (n == 0) { 1 } else { (fact((n - 1)) * n) }
+        #[tokio::test]
+        async fn record() {
+            let aiscript = new_interpreter([]);
+            let script = Parser::default().parse(SCRIPT).unwrap();
+            let recording = aiscript.record(script, 10).await;
+            assert_eq!(recording.result(), &Ok(Some(num(10))));
+            let snapshots = recording.snapshots();
+            assert!(snapshots.len() > 2);
+            assert!(snapshots
+                .iter()
+                .enumerate()
+                .all(|(i, snapshot)| snapshot.step == (i + 1) * 10));
+            let last = recording.state_at(snapshots.len() - 1);
+            assert_eq!(last.keys().collect::<Vec<_>>(), ["i", "log"]);
+            assert_eq!(
+                <Vec<Value>>::try_from(last["log"].clone()).unwrap().len() as f64,
+                f64::try_from(last["i"].clone()).unwrap()
+            );
+            // The changes hold the values at the time.
+            let first = recording.state_at(0);
+            assert!(!first["log"].deep_eq(&last["log"]));
+            assert!(aiscript.scope.get("i").is_err());
         }
 
-        <: fact(5)
-        "#,
-        |res| assert_eq!(res, num(120)),
-    )
-    .await
-    .unwrap();
-}
+        #[tokio::test]
+        async fn rewind() {
+            let aiscript = new_interpreter([]);
+            let script = Parser::default().parse(SCRIPT).unwrap();
+            let recording = aiscript.record(script, 10).await;
+            let replay = recording.rewind(2).await.unwrap();
+            assert_eq!(replay.step(), 30);
+            let state = recording.state_at(2);
+            assert_eq!(replay.interpreter().scope.get("i").unwrap(), state["i"]);
+            assert_eq!(replay.interpreter().scope.get("log").unwrap(), state["log"]);
+            assert_eq!(replay.resume().await, Ok(Some(num(10))));
+        }
 
-mod var_name_starts_with_reserved_word {
-    use super::*;
+        #[tokio::test]
+        async fn rewind_seeded_rng() {
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    let rng = Math:gen_rng('seed')
+                    var x = 0
+                    for 20 {
+                        x = rng()
+                    }
+                    "#,
+                )
+                .unwrap();
+            let recording = new_interpreter([]).record(script, 10).await;
+            let replay = recording.rewind(3).await.unwrap();
+            assert_eq!(
+                replay.interpreter().scope.get("x").unwrap(),
+                recording.state_at(3)["x"]
+            );
+        }
 
-    #[tokio::test]
-    async fn let_() {
-        test(
-            r#"
-            @f() {
-                let letcat = "ai"
-                letcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn diverged() {
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    var x = 0
+                    for 20 {
+                        x = Math:rnd()
+                    }
+                    "#,
+                )
+                .unwrap();
+            let recording = new_interpreter([]).record(script, 10).await;
+            assert!(matches!(
+                recording.rewind(3).await,
+                Err(AiScriptError::Internal(message)) if message.contains("diverged")
+            ));
+            assert!(recording.rewind(recording.snapshots().len()).await.is_err());
+        }
     }
 
-    #[tokio::test]
-    async fn var() {
-        test(
-            r#"
-            @f() {
-                let varcat = "ai"
-                varcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+    mod call {
+        use super::*;
+        use aiscript_v0::values::ArgMap;
 
-    #[tokio::test]
-    async fn return_() {
-        test(
-            r#"
-            @f() {
-                let returncat = "ai"
-                returncat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
+        async fn get_fn(aiscript: &Interpreter, program: &str, name: &str) -> VFn {
+            aiscript
+                .exec(Parser::default().parse(program).unwrap())
+                .await
+                .unwrap();
+            VFn::try_from(aiscript.scope.get(name).unwrap()).unwrap()
+        }
+
+        #[tokio::test]
+        async fn params() {
+            let aiscript = Interpreter::default();
+            let f = get_fn(&aiscript, "@f(a, b, c) {}", "f").await;
+            assert_eq!(
+                f.params(),
+                Some(["a".to_string(), "b".to_string(), "c".to_string()].as_slice())
+            );
+            assert_eq!(f.arity(), Some(3));
+            let native = VFn::FnNative(Arc::new(|_, _| async { Ok(Value::null()) }.boxed()));
+            assert_eq!(native.params(), None);
+            assert_eq!(native.arity(), None);
+        }
+
+        #[tokio::test]
+        async fn named_args() {
+            let aiscript = Interpreter::default();
+            let f = get_fn(&aiscript, "@f(a, b, c) { [a, b, c] }", "f").await;
+            let res = aiscript
+                .call(f.clone(), ArgMap::new().arg(num(1)).named("c", num(3)))
+                .await
+                .unwrap();
+            assert_eq!(res, arr([num(1), null(), num(3)]));
+            let res = aiscript
+                .call(f, ArgMap::new().named("b", num(2)).named("a", num(1)))
+                .await
+                .unwrap();
+            assert_eq!(res, arr([num(1), num(2), null()]));
+        }
+
+        #[tokio::test]
+        async fn invalid_args() {
+            let aiscript = Interpreter::default();
+            let f = get_fn(&aiscript, "@f(a, b) { a }", "f").await;
+            assert!(aiscript
+                .call(f.clone(), ArgMap::new().named("x", num(1)))
+                .await
+                .is_err());
+            assert!(aiscript
+                .call(f, ArgMap::new().arg(num(1)).named("a", num(1)))
+                .await
+                .is_err());
+            let native = VFn::FnNative(Arc::new(|args, _| {
+                async move { Ok(Value::arr(args)) }.boxed()
+            }));
+            assert_eq!(
+                aiscript
+                    .call(native.clone(), ArgMap::new().arg(num(1)))
+                    .await
+                    .unwrap(),
+                arr([num(1)])
+            );
+            assert!(aiscript
+                .call(native, ArgMap::new().named("a", num(1)))
+                .await
+                .is_err());
+        }
     }
 
-    #[tokio::test]
-    async fn each() {
-        test(
-            r#"
-            @f() {
-                let eachcat = "ai"
-                eachcat
+    mod captures {
+        use super::*;
+        use aiscript_v0::values::Capture;
+
+        async fn get_fn(program: &str, name: &str) -> VFn {
+            let aiscript = new_interpreter([]);
+            aiscript
+                .exec(Parser::default().parse(program).unwrap())
+                .await
+                .unwrap();
+            VFn::try_from(aiscript.scope.get(name).unwrap()).unwrap()
+        }
+
+        const PROGRAM: &str = r#"
+            @make() {
+                var n = 1
+                let tags = ['a']
+                let helper = @() { 1 }
+                @(x) {
+                    n += x
+                    print(tags)
+                    helper()
+                    n
+                }
             }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
+            let f = make()
+            let g = @() { Obj:kvs({ n: f(1) }) }
+            let cyclic = []
+            cyclic.push(cyclic)
+            let h = @() { cyclic }
+        "#;
+
+        #[tokio::test]
+        async fn captures() {
+            let f = get_fn(PROGRAM, "f").await;
+            assert_eq!(
+                f.captures(),
+                vec![
+                    Capture {
+                        name: "n".to_string(),
+                        value: Some(num(1)),
+                    },
+                    Capture {
+                        name: "tags".to_string(),
+                        value: Some(arr([str("a")])),
+                    },
+                    Capture {
+                        name: "helper".to_string(),
+                        value: None,
+                    },
+                ]
+            );
+            let h = get_fn(PROGRAM, "h").await;
+            assert_eq!(
+                h.captures(),
+                vec![Capture {
+                    name: "cyclic".to_string(),
+                    value: None,
+                }]
+            );
+            let native = get_fn(PROGRAM, "Core:type").await;
+            assert_eq!(native.captures(), Vec::new());
+        }
+
+        #[tokio::test]
+        async fn serialize() {
+            let make = get_fn(PROGRAM, "make").await;
+            assert_eq!(make.serialize_captures(), Ok(IndexMap::new()));
+            let f = get_fn(PROGRAM, "f").await;
+            let err = f.serialize_captures().unwrap_err();
+            assert_eq!(err.code(), "not_serializable");
+            assert!(err.to_string().contains("'helper'"));
+            let g = get_fn(PROGRAM, "g").await;
+            assert_eq!(
+                g.serialize_captures().unwrap_err().code(),
+                "not_serializable"
+            );
+        }
     }
 
-    #[tokio::test]
-    async fn for_() {
-        test(
-            r#"
-            @f() {
-                let forcat = "ai"
-                forcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+    mod permissions {
+        use super::*;
+        use aiscript_v0::{errors::AiScriptRuntimeError, Capability};
 
-    #[tokio::test]
-    async fn loop_() {
-        test(
-            r#"
-            @f() {
-                let loopcat = "ai"
-                loopcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+        fn interpreter() -> Interpreter {
+            new_interpreter([(
+                "Bot:post".to_string(),
+                Value::fn_native(|_, _| async { Ok(Value::bool(true)) }.boxed()),
+            )])
+            .with_capability("Bot:post", Capability::Net)
+        }
 
-    #[tokio::test]
-    async fn break_() {
-        test(
-            r#"
-            @f() {
-                let breakcat = "ai"
-                breakcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+        async fn exec(aiscript: &Interpreter, src: &str) -> Result<Value, AiScriptError> {
+            let script = Parser::default().parse(src).unwrap();
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
 
-    #[tokio::test]
-    async fn continue_() {
-        test(
-            r#"
-            @f() {
-                let continuecat = "ai"
-                continuecat
+        fn denied(result: Result<Value, AiScriptError>) -> Option<(String, Capability)> {
+            match result {
+                Err(AiScriptError::Runtime(e)) => match e.root() {
+                    AiScriptRuntimeError::PermissionDenied { name, capability } => {
+                        Some((name.clone(), *capability))
+                    }
+                    _ => None,
+                },
+                _ => None,
             }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+        }
 
-    #[tokio::test]
-    async fn if_() {
-        test(
-            r#"
-            @f() {
-                let ifcat = "ai"
-                ifcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn allowed_by_default() {
+            let aiscript = interpreter();
+            assert_eq!(exec(&aiscript, "Bot:post()").await.unwrap(), bool(true));
+            assert!(exec(&aiscript, "Math:rnd() < 1").await.is_ok());
+        }
 
-    #[tokio::test]
-    async fn match_() {
-        test(
-            r#"
-            @f() {
-                let matchcat = "ai"
-                matchcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn denied_capabilities() {
+            let aiscript = interpreter().with_permissions([Capability::Time]);
+            assert_eq!(
+                denied(exec(&aiscript, "Math:rnd()").await),
+                Some(("Math:rnd".to_string(), Capability::Random))
+            );
+            assert_eq!(
+                denied(exec(&aiscript, "Bot:post()").await),
+                Some(("Bot:post".to_string(), Capability::Net))
+            );
+            // Under another name.
+            assert_eq!(
+                denied(exec(&aiscript, "let f = Util:uuid\n@g(h) { h() }\ng(f)").await),
+                Some(("Util:uuid".to_string(), Capability::Random))
+            );
+            assert!(exec(&aiscript, "Date:now()").await.is_ok());
+            assert!(exec(&aiscript, "Math:gen_rng(1)()").await.is_ok());
+            let err = exec(&aiscript, "Math:rnd()").await.unwrap_err();
+            assert_eq!(err.code(), "permission_denied");
+            assert!(err.to_string().contains("'random'"));
+        }
 
-    #[tokio::test]
-    async fn true_() {
-        test(
-            r#"
-            @f() {
-                let truecat = "ai"
-                truecat
+        #[tokio::test]
+        async fn current_time() {
+            let aiscript = interpreter().with_permissions([]);
+            for name in [
+                "Date:year",
+                "Date:second",
+                "Date:millisecond",
+                "Date:to_iso_str",
+            ] {
+                assert_eq!(
+                    denied(exec(&aiscript, &format!("{name}()")).await),
+                    Some((name.to_string(), Capability::Time))
+                );
+                assert!(exec(&aiscript, &format!("{name}(0)")).await.is_ok());
             }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+            assert_eq!(
+                denied(exec(&aiscript, "Date:format(null, '%Y')").await),
+                Some(("Date:format".to_string(), Capability::Time))
+            );
+            assert!(exec(&aiscript, "Date:format(0, '%Y')").await.is_ok());
+        }
 
-    #[tokio::test]
-    async fn false_() {
-        test(
-            r#"
-            @f() {
-                let falsecat = "ai"
-                falsecat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn child() {
+            let aiscript = interpreter().with_permissions([]);
+            let child = aiscript.spawn_child(["Bot:post"], None).unwrap();
+            assert_eq!(
+                denied(exec(&child, "Bot:post()").await),
+                Some(("Bot:post".to_string(), Capability::Net))
+            );
+            let res = exec(&aiscript, "Core:eval_sandbox('Core:sleep(0)').info.message")
+                .await
+                .unwrap();
+            assert!(String::try_from(res).unwrap().contains("'Core:sleep'"));
+        }
     }
 
-    #[tokio::test]
-    async fn null() {
-        test(
-            r#"
-            @f() {
-                let nullcat = "ai"
-                nullcat
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
-}
+    mod overrides {
+        use super::*;
+        use ::std::collections::HashMap;
 
-mod name_validation_of_reserved_word {
-    use super::*;
+        #[tokio::test]
+        async fn shadow_std() {
+            let aiscript = new_interpreter([]);
+            let script = Parser::default()
+                .parse("let x = Math:rnd()\n[x, Math:PI, Core:type(x)]")
+                .unwrap();
+            let overrides = HashMap::from([(
+                "Math:rnd".to_string(),
+                Value::fn_native(|_, _| async { Ok(Value::num(0.5)) }.boxed()),
+            )]);
+            assert_eq!(
+                aiscript
+                    .exec_with_overrides(script.clone(), overrides)
+                    .await
+                    .unwrap(),
+                Some(arr([num(0.5), num(::std::f64::consts::PI), str("num")]))
+            );
+            // The definitions and the overrides do not remain.
+            assert!(aiscript.scope.get("x").is_err());
+            let script = Parser::default().parse("Math:rnd() != 0.5").unwrap();
+            assert_eq!(aiscript.exec(script).await.unwrap(), Some(bool(true)));
+        }
 
-    #[tokio::test]
-    async fn def() {
-        test(
-            r#"
-            let let = 1
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
+        #[tokio::test]
+        async fn concurrent() {
+            let aiscript = new_interpreter([]);
+            let printed = Arc::new(::std::sync::Mutex::new(Vec::new()));
+            let tenant = |name: &'static str| {
+                let printed = printed.clone();
+                HashMap::from([(
+                    "print".to_string(),
+                    Value::fn_native(move |args, _| {
+                        let value = args.into_iter().next().unwrap_or_default();
+                        printed.lock().unwrap().push((name, value));
+                        async { Ok(Value::null()) }.boxed()
+                    }),
+                )])
+            };
+            let script = Parser::default().parse("Core:sleep(10)\nprint(1)").unwrap();
+            let (a, b) = futures::join!(
+                aiscript.exec_with_overrides(script.clone(), tenant("a")),
+                aiscript.exec_with_overrides(script, tenant("b")),
+            );
+            a.unwrap();
+            b.unwrap();
+            let mut printed = printed.lock().unwrap().clone();
+            printed.sort_by_key(|(name, _)| *name);
+            assert_eq!(printed, [("a", num(1)), ("b", num(1))]);
+        }
     }
 
-    #[tokio::test]
-    async fn attr() {
-        test(
-            r#"
-            #[let 1]
-            @f() { 1 }
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-    }
+    mod repr_limits {
+        use super::*;
 
-    #[tokio::test]
-    async fn ns() {
-        test(
-            r#"
-            :: let {
-                @f() { 1 }
-            }
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-    }
+        #[tokio::test]
+        async fn depth() {
+            let aiscript = new_interpreter([]).with_max_repr_depth(1);
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    let a = [1, [2, [3]], { b: {} }]
+                    [Core:to_str(a), `{a}`, Core:inspect(a, { depth: 3 })]
+                    "#,
+                )
+                .unwrap();
+            assert_eq!(
+                aiscript.exec(script).await.unwrap(),
+                Some(arr([
+                    str("[ 1, ..., ... ]"),
+                    str("[ 1, ..., ... ]"),
+                    str("[ 1, [ 2, [ 3 ] ], { b: {  } } ]"),
+                ]))
+            );
+        }
 
-    #[tokio::test]
-    async fn var() {
-        test(
-            r#"
-            let
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
+        #[tokio::test]
+        async fn len() {
+            let aiscript = new_interpreter([]).with_max_repr_len(10);
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    let a = Core:range(1, 1000)
+                    a.push(a)
+                    [Core:to_str(a), `{a}`, Core:to_str("short"), Core:to_str("0123456789")]
+                    "#,
+                )
+                .unwrap();
+            assert_eq!(
+                aiscript.exec(script).await.unwrap(),
+                Some(arr([
+                    str("[ 1, 2, 3,..."),
+                    str("[ 1, 2, 3,..."),
+                    str("short"),
+                    str("0123456789"),
+                ]))
+            );
+        }
     }
 
-    #[tokio::test]
-    async fn prop() {
-        test(
-            r#"
-            let x = { let: 1 }
-            x.let
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-    }
+    mod not_callable {
+        use super::*;
 
-    #[tokio::test]
-    async fn meta() {
-        test(
-            r#"
-            ### let 1
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-    }
+        async fn call_error(src: &str) -> AiScriptError {
+            let aiscript = new_interpreter([]);
+            let script = Parser::default().parse(src).unwrap();
+            aiscript.exec(script).await.unwrap_err()
+        }
 
-    #[tokio::test]
-    async fn fn_() {
-        test(
-            r#"
-            @let() { 1 }
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-    }
-}
+        #[tokio::test]
+        async fn variable() {
+            let err = call_error("let x = 1\nx(2)").await;
+            assert_eq!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::NotCallable {
+                    name: Some("x".to_string()),
+                    got: "num".to_string(),
+                    loc: Some(Box::new(Loc { start: 11, end: 13 })),
+                })
+            );
+            assert_eq!(err.code(), "not_callable");
+            assert_eq!(err.loc(), Some(Loc { start: 11, end: 13 }));
+            assert_eq!(
+                err.to_string(),
+                "Runtime: Cannot call 'x' of type num, which is not a function."
+            );
+        }
 
-mod object {
-    use super::*;
+        #[tokio::test]
+        async fn prop_and_anonymous() {
+            let err = call_error("let o = { f: 'str' }\no.f()").await;
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::NotCallable { name: Some(name), got, .. })
+                    if name == "f" && got == "str"
+            ));
 
-    #[tokio::test]
-    async fn property_access() {
-        test(
-            r#"
-            let obj = {
-                a: {
-                    b: {
-                        c: 42;
-                    };
-                };
-            }
+            let err = call_error("[null][0]()").await;
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::NotCallable { name: None, got, .. })
+                    if got == "null"
+            ));
+        }
 
-            <: obj.a.b.c
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn in_function() {
+            let err = call_error("@f(g) { g() }\nf(1)").await;
+            assert_eq!(err.code(), "not_callable");
+            // The location is of the call of the value, not of the call of the function.
+            assert_eq!(err.loc(), Some(Loc { start: 9, end: 10 }));
+            assert_eq!(err.stack().len(), 1);
+        }
     }
 
-    #[tokio::test]
-    async fn property_access_fn_call() {
-        test(
-            r#"
-            @f() { 42 }
+    mod native_types {
+        use super::*;
 
-            let obj = {
-                a: {
-                    b: {
-                        c: f;
-                    };
-                };
-            }
+        fn interpreter() -> Interpreter {
+            let type_ = match Parser::default()
+                .parse_type("@(str, arr<num>) => str")
+                .unwrap()
+            {
+                TypeSource::FnTypeSource(type_) => type_,
+                type_ => panic!("not a function type: {type_:?}"),
+            };
+            new_interpreter([(
+                "Host:repeat".to_string(),
+                Value::fn_native(|args, _| {
+                    async move {
+                        let mut args = args.into_iter();
+                        let s = String::try_from(args.next().unwrap())?;
+                        let n = Vec::<Value>::try_from(args.next().unwrap())?.len();
+                        Ok(Value::str(s.repeat(n)))
+                    }
+                    .boxed()
+                }),
+            )])
+            .with_native_type("Host:repeat", type_)
+        }
 
-            <: obj.a.b.c()
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
-    }
+        async fn exec(src: &str) -> Result<Option<Value>, AiScriptError> {
+            let script = Parser::default().parse(src).unwrap();
+            interpreter().exec(script).await
+        }
 
-    #[tokio::test]
-    async fn property_assign() {
-        test(
-            r#"
-            let obj = {
-                a: 1
-                b: {
-                    c: 2
-                    d: {
-                        e: 3
-                    }
-                }
-            }
+        #[tokio::test]
+        async fn valid() {
+            let res = exec("Host:repeat('ab', [1, 2])").await.unwrap();
+            assert_eq!(res, Some(str("abab")));
+        }
 
-            obj.a = 24
-            obj.b.d.e = 42
+        #[tokio::test]
+        async fn invalid() {
+            let err = exec("Host:repeat('ab', [1, 'x'])").await.unwrap_err();
+            assert_eq!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Type {
+                    expected: "arr<num>".to_string(),
+                    got: "arr".to_string(),
+                })
+            );
+            let err = exec("let f = Host:repeat\nf(1, [])").await.unwrap_err();
+            assert_eq!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Type {
+                    expected: "str".to_string(),
+                    got: "num".to_string(),
+                })
+            );
+        }
 
-            <: obj
-            "#,
-            |res| {
-                assert_eq!(
-                    res,
-                    obj([
-                        ("a", num(24)),
-                        ("b", obj([("c", num(2)), ("d", obj([("e", num(42))]))]))
-                    ])
-                )
-            },
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn missing() {
+            let err = exec("Host:repeat('ab')").await.unwrap_err();
+            assert_eq!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Type {
+                    expected: "arr<num>".to_string(),
+                    got: "null".to_string(),
+                })
+            );
+        }
+
+        #[test]
+        fn lookup() {
+            let type_ = interpreter().native_type("Host:repeat").unwrap().clone();
+            assert_eq!(
+                TypeSource::FnTypeSource(type_).to_string(),
+                "@(str, arr<num>) { str }"
+            );
+            assert!(interpreter().native_type("Host:other").is_none());
+            assert!(Parser::default().parse_type("arr<unknown>").is_err());
+        }
     }
-}
 
-mod array {
+    mod profile {
+        use super::*;
 
-    use super::*;
+        async fn profile(src: &str) -> Vec<ProfileEntry> {
+            let aiscript = new_interpreter([]).with_profiling(true);
+            let script = Parser::default().parse(src).unwrap();
+            aiscript.exec(script).await.unwrap();
+            aiscript.profile()
+        }
 
-    #[tokio::test]
-    async fn array_item_access() {
-        test(
-            r#"
-            let arr = ["ai", "chan", "kawaii"]
+        fn entry<'a>(profile: &'a [ProfileEntry], name: &str) -> &'a ProfileEntry {
+            profile.iter().find(|entry| entry.name == name).unwrap()
+        }
 
-            <: arr[1]
-            "#,
-            |res| assert_eq!(res, str("chan")),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn calls() {
+            let profile = profile(
+                r#"
+                @inner(n) { Arr:create(n, 0).map(@(x) { x + 1 }) }
+                @outer() { inner(100) }
+                outer()
+                outer()
+                "#,
+            )
+            .await;
+            let outer = entry(&profile, "outer");
+            let inner = entry(&profile, "inner");
+            assert_eq!((outer.calls, outer.native), (2, false));
+            assert_eq!((inner.calls, inner.native), (2, false));
+            assert!(outer.total_time >= inner.total_time);
+            assert!(outer.self_time <= outer.total_time);
+            assert!(entry(&profile, "Arr:create").native);
+            assert_eq!(entry(&profile, "<anonymous>").calls, 200);
+            assert!(profile
+                .windows(2)
+                .all(|entries| entries[0].self_time >= entries[1].self_time));
+        }
+
+        #[tokio::test]
+        async fn recursive() {
+            let profile = profile(
+                r#"
+                @fact(n) { if n <= 1 1 else n * fact(n - 1) }
+                fact(5)
+                "#,
+            )
+            .await;
+            let fact = entry(&profile, "fact");
+            assert_eq!(fact.calls, 5);
+            assert!(fact.self_time <= fact.total_time);
+        }
+
+        #[tokio::test]
+        async fn disabled() {
+            let aiscript = new_interpreter([]);
+            let script = Parser::default().parse("@f() {}\nf()").unwrap();
+            aiscript.exec(script).await.unwrap();
+            assert!(aiscript.profile().is_empty());
+        }
     }
 
-    #[tokio::test]
-    async fn array_item_assign() {
-        test(
-            r#"
-            let arr = ["ai", "chan", "kawaii"]
+    mod vars {
+        use super::*;
 
-            arr[1] = "taso"
+        fn interpreter() -> Interpreter {
+            new_interpreter([("limit".to_string(), num(10))]).with_vars([
+                ("count".to_string(), num(0)),
+                ("config".to_string(), obj([("theme", str("light"))])),
+            ])
+        }
 
-            <: arr
-            "#,
-            |res| assert_eq!(res, arr([str("ai"), str("taso"), str("kawaii"),])),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn read_back() {
+            let aiscript = interpreter();
+            let script = Parser::default()
+                .parse("count += limit\nconfig.theme = 'dark'")
+                .unwrap();
+            aiscript.exec(script).await.unwrap();
+            assert_eq!(aiscript.scope.get("count").unwrap(), num(10));
+            assert_eq!(
+                aiscript.scope.get("config").unwrap(),
+                obj([("theme", str("dark"))])
+            );
+        }
 
-    #[tokio::test]
-    async fn assign_array_item_to_out_of_range() {
-        let err = test(
-            r#"
-            let arr = [1, 2, 3]
+        #[tokio::test]
+        async fn reassign() {
+            let aiscript = interpreter();
+            let script = Parser::default()
+                .parse("config = { theme: 'auto' }")
+                .unwrap();
+            aiscript.exec(script).await.unwrap();
+            assert_eq!(
+                aiscript.scope.get("config").unwrap(),
+                obj([("theme", str("auto"))])
+            );
+            let script = Parser::default().parse("limit = 20").unwrap();
+            assert!(aiscript.exec(script).await.is_err());
+        }
+    }
 
-            arr[3] = 4
+    mod run_steps {
+        use super::*;
 
-            <: null
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-        assert!(matches!(
-            err,
-            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
-        ));
+        fn start(src: &str) -> StepExecution {
+            let aiscript = new_interpreter([]);
+            aiscript.start(Parser::default().parse(src).unwrap())
+        }
 
-        let err = test(
-            r#"
-            let arr = [1, 2, 3]
+        #[test]
+        fn interleave() {
+            let mut a = start("var x = 0\nfor 100 { x += 1 }\nx");
+            let mut b = start("var s = ''\nfor 3 { s = `{s}a` }\ns");
+            let mut turns = 0;
+            let (mut a_result, mut b_result) = (None, None);
+            while a_result.is_none() || b_result.is_none() {
+                turns += 1;
+                for (execution, result) in [(&mut a, &mut a_result), (&mut b, &mut b_result)] {
+                    if result.is_none() {
+                        if let StepResult::Complete(value) = execution.run_steps(10).unwrap() {
+                            *result = Some(value);
+                        }
+                    }
+                }
+            }
+            assert!(turns > 10);
+            assert_eq!(a_result, Some(Some(num(100))));
+            assert_eq!(b_result, Some(Some(str("aaa"))));
+            assert!(a.is_complete());
+            assert!(a.run_steps(10).is_err());
+        }
 
-            arr[9] = 10
+        #[test]
+        fn zero_steps() {
+            let mut execution = start("1 + 1");
+            assert_eq!(execution.run_steps(0).unwrap(), StepResult::Pending);
+            assert_eq!(
+                execution.run_steps(100).unwrap(),
+                StepResult::Complete(Some(num(2)))
+            );
+        }
 
-            <: null
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-        assert!(matches!(
-            err,
-            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
-        ));
+        #[test]
+        fn error() {
+            let mut execution = start("Core:sleep(1)");
+            assert!(execution.run_steps(100).is_err());
+            assert!(execution.is_complete());
+        }
     }
 
-    #[tokio::test]
-    async fn index_out_of_range_error() {
-        let err = test(
-            r#"
-            <: [42][1]
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-        assert!(matches!(
-            err,
-            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
-        ));
-    }
+    mod native_timeout {
+        use ::std::time::Duration;
 
-    #[tokio::test]
-    async fn index_out_of_range_on_assignment() {
-        let err = test(
-            r#"
-            var a = []
-            a[2] = 'hoge'
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-        assert!(matches!(
-            err,
-            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
-        ));
-    }
+        use super::*;
 
-    #[tokio::test]
-    async fn non_integer_indexed_assignment() {
-        let err = test(
-            r#"
-            var a = []
-            a[6.21] = 'hoge'
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-        assert!(matches!(
-            err,
-            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
-        ));
-    }
-}
+        async fn exec(
+            src: &str,
+            cancel: bool,
+        ) -> (Result<Option<Value>, AiScriptError>, Vec<Warning>) {
+            let warnings = Arc::new(::std::sync::Mutex::new(Vec::new()));
+            let aiscript = new_interpreter([(
+                "Host:slow".to_string(),
+                Value::fn_native(|args, _| {
+                    async move {
+                        let millis = f64::try_from(args.into_iter().next().unwrap())?;
+                        tokio::time::sleep(Duration::from_millis(millis as u64)).await;
+                        Ok(Value::str("done"))
+                    }
+                    .boxed()
+                }),
+            )])
+            .with_warn({
+                let warnings = warnings.clone();
+                move |warning| {
+                    warnings.lock().unwrap().push(warning);
+                    async move {}.boxed()
+                }
+            })
+            .with_native_timeout(Duration::from_millis(20), cancel);
+            let result = aiscript.exec(Parser::default().parse(src).unwrap()).await;
+            let warnings = warnings.lock().unwrap().clone();
+            (result, warnings)
+        }
 
-mod chain {
-    use super::*;
+        #[tokio::test]
+        async fn warn() {
+            let (result, warnings) = exec("Host:slow(100)", false).await;
+            assert_eq!(result.unwrap(), Some(str("done")));
+            assert_eq!(
+                warnings,
+                [Warning {
+                    code: "native_timeout".to_string(),
+                    message: "Host:slow has been running for more than 20ms".to_string(),
+                    loc: Some(Loc { start: 9, end: 13 }),
+                }]
+            );
 
-    #[tokio::test]
-    async fn chain_access_prop_index_call() {
-        test(
-            r#"
-            let obj = {
-                a: {
-                    b: [@(name) { name }, @(str) { "chan" }, @() { "kawaii" }];
-                };
-            }
+            let (result, warnings) = exec("Host:slow(0)", false).await;
+            assert_eq!(result.unwrap(), Some(str("done")));
+            assert_eq!(warnings, []);
+        }
 
-            <: obj.a.b[0]("ai")
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn cancel() {
+            let (result, warnings) = exec("Host:slow(10000)", true).await;
+            let err = result.unwrap_err();
+            assert_eq!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::NativeTimeout {
+                    name: "Host:slow".to_string(),
+                    timeout: Duration::from_millis(20),
+                })
+            );
+            assert_eq!(err.code(), "native_timeout");
+            assert_eq!(
+                err.to_string(),
+                "Runtime: 'Host:slow' did not complete within 20ms."
+            );
+            assert_eq!(warnings.len(), 1);
+        }
 
-    #[tokio::test]
-    async fn chained_assign_left_side_prop_index() {
-        test(
-            r#"
-            let obj = {
-                a: {
-                    b: ["ai", "chan", "kawaii"];
-                };
-            }
+        #[tokio::test]
+        async fn builtin() {
+            let (result, warnings) = exec("[100].map(@(x) { Core:sleep(x) })", true).await;
+            assert_eq!(result.unwrap(), Some(arr([null()])));
+            assert_eq!(warnings, []);
 
-            obj.a.b[1] = "taso"
+            let (result, warnings) = exec("Core:try(@() { Core:sleep(100) })", true).await;
+            assert_eq!(result.unwrap(), Some(null()));
+            assert_eq!(warnings, []);
 
-            <: obj
-            "#,
-            |res| {
-                assert_eq!(
-                    res,
-                    obj([(
-                        "a",
-                        obj([("b", arr([str("ai"), str("taso"), str("kawaii"),]))])
-                    )])
-                )
-            },
-        )
-        .await
-        .unwrap();
+            let (result, warnings) = exec("[10000].map(@(x) { Host:slow(x) })", true).await;
+            assert_eq!(
+                result.unwrap_err(),
+                AiScriptError::Runtime(AiScriptRuntimeError::NativeTimeout {
+                    name: "Host:slow".to_string(),
+                    timeout: Duration::from_millis(20),
+                })
+            );
+            assert_eq!(warnings.len(), 1);
+        }
     }
 
-    #[tokio::test]
-    async fn chained_assign_right_side_prop_index_call() {
-        test(
-            r#"
-            let obj = {
-                a: {
-                    b: ["ai", "chan", "kawaii"];
-                };
-            }
+    mod reload {
+        use ::std::sync::atomic::{AtomicUsize, Ordering};
 
-            var x = null
-            x = obj.a.b[1]
+        use super::*;
 
-            <: x
-            "#,
-            |res| assert_eq!(res, str("chan"),),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn preserves_state() {
+            let aiscript = Interpreter::default();
+            aiscript
+                .exec(
+                    Parser::default()
+                        .parse(
+                            r#"
+                            var count = 0
+                            var name = 'a'
+                            @inc() { count += 1 }
+                            :: Ns { let k = 1 }
+                            inc()
+                            inc()
+                            "#,
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let reset = aiscript
+                .reload(
+                    Parser::default()
+                        .parse(
+                            r#"
+                            var count = 0
+                            var name = 1
+                            var added = true
+                            @inc() { count += 10 }
+                            :: Ns { let k = 2 }
+                            inc()
+                            "#,
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(reset, ["name", "added"]);
+            let res = aiscript
+                .exec(
+                    Parser::default()
+                        .parse("inc()\n`{count} {name} {added} {Ns:k}`")
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res, Some(str("12 1 true 2")));
+        }
+
+        #[tokio::test]
+        async fn restores_on_error() {
+            let errors = Arc::new(AtomicUsize::new(0));
+            let aiscript = Interpreter::new(
+                [],
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                Some({
+                    let errors = errors.clone();
+                    move |_| {
+                        errors.fetch_add(1, Ordering::SeqCst);
+                        async move {}.boxed()
+                    }
+                }),
+                None,
+            );
+            aiscript
+                .exec(
+                    Parser::default()
+                        .parse("var count = 1\n@f() { 'old' }\n:: Ns { let k = 1 }")
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let err = aiscript
+                .reload(
+                    Parser::default()
+                        .parse(
+                            r#"
+                            var count = 2
+                            @f() { 'new' }
+                            :: Ns { let k = 2 }
+                            let added = 1
+                            let broken = Core:abort('oops')
+                            "#,
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), "user");
+            assert_eq!(errors.load(Ordering::SeqCst), 1);
+            let res = aiscript
+                .exec(
+                    Parser::default()
+                        .parse("`{count} {f()} {Ns:k} {exists added}`")
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res, Some(str("1 old 1 false")));
+        }
     }
 
-    #[tokio::test]
-    async fn chained_inc_dec_left_side_index_prop() {
-        test(
-            r#"
-            let arr = [
-                {
-                    a: 1;
-                    b: 2;
-                }
-            ]
+    mod frozen {
+        use super::*;
 
-            arr[0].a += 1
-            arr[0].b -= 1
+        async fn exec(src: &str) -> Result<Value, AiScriptError> {
+            let config = Value::frozen(obj([
+                ("list", arr([num(1), obj([("k", num(2))])])),
+                ("n", num(3)),
+            ]));
+            let aiscript = Interpreter::new(
+                [("config".to_string(), config)],
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                Some(9999),
+            );
+            aiscript
+                .exec(Parser::default().parse(src)?)
+                .await
+                .map(Option::unwrap_or_default)
+        }
 
-            <: arr
-            "#,
-            |res| assert_eq!(res, arr([obj([("a", num(2)), ("b", num(1)),])])),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn readable() {
+            let res = exec("config.list[0] + config.list[1].k + config.n")
+                .await
+                .unwrap();
+            assert_eq!(res, num(6));
+        }
 
-    #[tokio::test]
-    async fn chained_inc_dec_left_side_prop_index() {
-        test(
-            r#"
-            let obj = {
-                a: {
-                    b: [1, 2, 3];
-                };
+        #[tokio::test]
+        async fn mutation_fails() {
+            for src in [
+                "config.n = 1",
+                "config['n'] = 1",
+                "config.n += 1",
+                "config.list[0] = 1",
+                "config.list[1].k = 1",
+                "config.list.push(1)",
+                "config.list.sort(@(a, b) { 0 })",
+                "Obj:set(config, 'n', 1)",
+                "let list = config.list\nlist.pop()",
+                "each let item, config.list { item.k = 1 }",
+            ] {
+                let e = exec(src).await.unwrap_err();
+                assert!(e.to_string().contains("frozen"), "{src}: {e}");
             }
+        }
 
-            obj.a.b[1] += 1
-            obj.a.b[2] -= 1
-
-            <: obj
-            "#,
-            |res| {
-                assert_eq!(
-                    res,
-                    obj([("a", obj([("b", arr([num(1), num(3), num(2),]))]))])
-                )
-            },
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn copy_is_mutable() {
+            let res = exec(
+                r#"
+                let list = config.list.copy()
+                list.push(4)
+                list.len
+                "#,
+            )
+            .await
+            .unwrap();
+            assert_eq!(res, num(3));
+        }
 
-    #[tokio::test]
-    async fn prop_in_def() {
-        test(
-            r#"
-            let x = @() {
-                let obj = {
-                    a: 1
-                }
-                obj.a
+        #[test]
+        fn cyclic() {
+            let value = arr([]);
+            if let V::Arr(arr) = &value.value {
+                arr.write().unwrap().push(value.clone());
             }
-
-            <: x()
-            "#,
-            |res| assert_eq!(res, num(1)),
-        )
-        .await
-        .unwrap();
+            let value = Value::frozen(value);
+            assert!(value.is_frozen());
+        }
     }
 
-    #[tokio::test]
-    async fn prop_in_return() {
-        test(
-            r#"
-            let x = @() {
-                let obj = {
-                    a: 1
-                }
-                return obj.a
-                2
-            }
+    mod fork {
+        use super::*;
 
-            <: x()
-            "#,
-            |res| assert_eq!(res, num(1)),
-        )
-        .await
-        .unwrap();
-    }
+        fn interpreter() -> Interpreter {
+            Interpreter::new(
+                [("X".to_string(), Value::num(1.0))],
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                None::<fn(_) -> _>,
+                Some(9999),
+            )
+        }
 
-    #[tokio::test]
-    async fn prop_in_each() {
-        test(
-            r#"
-            let msgs = []
-            let x = { a: ["ai", "chan", "kawaii"] }
-            each let item, x.a {
-                let y = { a: item }
-                msgs.push([y.a, "!"].join())
-            }
-            <: msgs
-            "#,
-            |res| assert_eq!(res, arr([str("ai!"), str("chan!"), str("kawaii!"),])),
-        )
-        .await
-        .unwrap();
-    }
+        async fn exec(
+            interpreter: &Interpreter,
+            script: &str,
+        ) -> Result<Option<Value>, AiScriptError> {
+            interpreter
+                .exec(Parser::default().parse(script).unwrap())
+                .await
+        }
 
-    #[tokio::test]
-    async fn prop_in_for() {
-        test(
-            r#"
-            let x = { times: 10, count: 0 }
-            for (let i, x.times) {
-                x.count = (x.count + i)
-            }
-            <: x.count
-            "#,
-            |res| assert_eq!(res, num(45)),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn shares_std_and_consts() {
+            let fork = interpreter().fork();
+            let res = exec(&fork, "[X, Math:abs(-2), Core:type(print)]")
+                .await
+                .unwrap();
+            assert_eq!(res, Some(arr([num(1), num(2), str("fn")])));
+        }
 
-    #[tokio::test]
-    async fn object_with_index() {
-        test(
-            r#"
-            let ai = {a: {}}['a']
-            ai['chan'] = 'kawaii'
-            <: ai[{a: 'chan'}['a']]
-            "#,
-            |res| assert_eq!(res, str("kawaii")),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn isolates_definitions() {
+            let base = interpreter();
+            let fork = base.fork();
+            exec(&fork, "let a = 1").await.unwrap();
+            exec(&base, "a").await.unwrap_err();
+            exec(&fork.fork(), "let a = 2").await.unwrap();
+            assert_eq!(exec(&fork, "a").await.unwrap(), Some(num(1)));
+        }
+
+        #[tokio::test]
+        async fn isolates_assignments() {
+            let base = interpreter();
+            exec(&base, "var a = 1").await.unwrap();
+            let fork = base.fork();
+            exec(&fork, "a = 2").await.unwrap();
+            assert_eq!(exec(&fork, "a").await.unwrap(), Some(num(2)));
+            assert_eq!(exec(&base, "a").await.unwrap(), Some(num(1)));
+        }
+
+        #[tokio::test]
+        async fn fresh_step_count() {
+            let base = interpreter();
+            exec(&base, "for 100 {}").await.unwrap();
+            let fork = base.fork();
+            assert_eq!(
+                fork.step_count.load(::std::sync::atomic::Ordering::SeqCst),
+                0
+            );
+            exec(&fork, "for 9000 {}").await.unwrap();
+        }
     }
 
-    #[tokio::test]
-    async fn property_chain_with_parenthesis() {
-        let ast = Parser::default()
-            .parse(
-                r#"
-                (a.b).c
-                "#,
-            )
-            .unwrap();
-        let line = ast.first().unwrap().clone();
-        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
-            assert_eq!(name, "c".to_string());
-            if let Expression::Prop(Prop { target, name, .. }) = *target {
-                assert_eq!(name, "b".to_string());
-                if let Expression::Identifier(Identifier { name, .. }) = *target {
-                    assert_eq!(name, "a".to_string());
-                    return;
-                }
-            }
+    mod library {
+        use super::*;
+
+        #[tokio::test]
+        async fn exports() {
+            let library = new_interpreter(Vec::new())
+                .exec_as_library(
+                    Parser::default()
+                        .parse(
+                            r#"
+                            let prefix = "lib: "
+                            var count = 0
+                            @greet(name) { `{prefix}{name}` }
+                            :: Counter {
+                                @next() {
+                                    count += 1
+                                    count
+                                }
+                            }
+                            "#,
+                        )
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                library
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>(),
+                ["Counter:next", "greet", "prefix"]
+            );
+            let user = new_interpreter(library.clone());
+            let res = user
+                .exec(
+                    Parser::default()
+                        .parse("[greet('ai'), Counter:next(), Counter:next()]")
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res, Some(arr([str("lib: ai"), num(1), num(2)])));
+            let res = new_interpreter(library)
+                .exec(Parser::default().parse("Counter:next()").unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res, Some(num(3)));
+        }
+
+        #[tokio::test]
+        async fn isolated_from_host() {
+            let aiscript = new_interpreter(Vec::new());
+            aiscript
+                .exec_as_library(Parser::default().parse("let a = 1").unwrap())
+                .await
+                .unwrap();
+            aiscript
+                .exec(Parser::default().parse("a").unwrap())
+                .await
+                .unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn error() {
+            new_interpreter(Vec::new())
+                .exec_as_library(
+                    Parser::default()
+                        .parse("let a = 1\nCore:abort('x')")
+                        .unwrap(),
+                )
+                .await
+                .unwrap_err();
         }
-        panic!();
     }
 
-    #[tokio::test]
-    async fn index_chain_with_parenthesis() {
-        let ast = Parser::default()
-            .parse(
-                r#"
-                (a[42]).b
-                "#,
-            )
-            .unwrap();
-        let line = ast.first().unwrap().clone();
-        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
-            assert_eq!(name, "b".to_string());
-            if let Expression::Index(Index { target, index, .. }) = *target {
-                if let (
-                    Expression::Identifier(Identifier { name, .. }),
-                    Expression::Num(Num { value, .. }),
-                ) = (*target, *index)
-                {
-                    assert_eq!(name, "a".to_string());
-                    assert_eq!(value, 42.0);
-                    return;
+    mod cancel {
+        use ::std::{
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            time::Duration,
+        };
+
+        use tokio_util::sync::CancellationToken;
+
+        use super::*;
+
+        #[tokio::test]
+        async fn stops_script() {
+            let aiscript = new_interpreter([]);
+            let token = CancellationToken::new();
+            let handle = tokio::spawn({
+                let aiscript = aiscript.clone();
+                let token = token.clone();
+                async move {
+                    aiscript
+                        .exec_with_cancel(
+                            Parser::default()
+                                .parse("var i = 0\nloop { i += 1 }")
+                                .unwrap(),
+                            token,
+                        )
+                        .await
                 }
-            }
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token.cancel();
+            tokio::time::timeout(Duration::from_secs(1), handle)
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
         }
-        panic!();
-    }
 
-    #[tokio::test]
-    async fn call_chain_with_parenthesis() {
-        let ast = Parser::default()
-            .parse(
-                r#"
-                (foo(42, 57)).bar
-                "#,
-            )
-            .unwrap();
-        let line = ast.first().unwrap().clone();
-        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
-            assert_eq!(name, "bar".to_string());
-            if let Expression::Call(Call { target, args, .. }) = *target {
-                if let Expression::Identifier(Identifier { name, .. }) = *target {
-                    assert_eq!(name, "foo".to_string());
-                    if let [Expression::Num(Num { value: arg1, .. }), Expression::Num(Num { value: arg2, .. })] =
-                        args[..]
-                    {
-                        assert_eq!(arg1, 42.0);
-                        assert_eq!(arg2, 57.0);
-                        return;
+        #[tokio::test]
+        async fn cancel_after_completion() {
+            let count = Arc::new(AtomicUsize::new(0));
+            let aiscript = Interpreter::new(
+                [],
+                None::<fn(_) -> _>,
+                Some({
+                    let count = count.clone();
+                    move |_| {
+                        count.fetch_add(1, Ordering::SeqCst);
+                        async move {}.boxed()
                     }
-                }
-            }
+                }),
+                None::<fn(_) -> _>,
+                None,
+            );
+            let token = CancellationToken::new();
+            aiscript
+                .exec_with_cancel(Parser::default().parse("<: 1").unwrap(), token.clone())
+                .await
+                .unwrap();
+            token.cancel();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            aiscript
+                .exec(Parser::default().parse("<: 2").unwrap())
+                .await
+                .unwrap();
+            assert_eq!(count.load(Ordering::SeqCst), 2);
         }
-        panic!();
     }
 
-    #[tokio::test]
-    async fn longer_chain_with_parenthesis() {
-        let ast = Parser::default()
-            .parse(
-                r#"
-                (a.b.c).d.e
-                "#,
-            )
-            .unwrap();
-        let line = ast.first().unwrap().clone();
-        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
-            assert_eq!(name, "e".to_string());
-            if let Expression::Prop(Prop { target, name, .. }) = *target {
-                assert_eq!(name, "d".to_string());
-                if let Expression::Prop(Prop { target, name, .. }) = *target {
-                    assert_eq!(name, "c".to_string());
-                    if let Expression::Prop(Prop { target, name, .. }) = *target {
-                        assert_eq!(name, "b".to_string());
-                        if let Expression::Identifier(Identifier { name, .. }) = *target {
-                            assert_eq!(name, "a".to_string());
-                            return;
-                        }
-                    }
-                }
+    mod threads {
+        use super::*;
+
+        #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+        async fn exec_on_worker_threads() {
+            let base = new_interpreter([("shared".to_string(), Value::arr([]))]);
+            let handles = (0..8)
+                .map(|i| {
+                    let interpreter = base.fork();
+                    tokio::spawn(async move {
+                        let script = Parser::default()
+                            .parse(&format!("shared.push({i})\n{i} * 2"))
+                            .unwrap();
+                        interpreter.exec(script).await.unwrap()
+                    })
+                })
+                .collect::<Vec<_>>();
+            for (i, handle) in handles.into_iter().enumerate() {
+                assert_eq!(handle.await.unwrap(), Some(num(i as f64 * 2.0)));
             }
+            let shared = <Vec<Value>>::try_from(base.scope.get("shared").unwrap()).unwrap();
+            assert_eq!(shared.len(), 8);
         }
-        panic!();
     }
 
-    #[tokio::test]
-    async fn property_chain_with_if() {
-        let ast = Parser::default()
-            .parse(
-                r#"
-                (if a b else c).d
-                "#,
-            )
-            .unwrap();
-        let line = ast.first().unwrap().clone();
-        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line.clone() {
-            assert_eq!(name, "d".to_string());
-            if let Expression::If(If {
-                cond,
-                then,
-                else_: Some(else_),
-                ..
-            }) = *target
-            {
-                if let (
-                    Expression::Identifier(cond),
-                    StatementOrExpression::Expression(Expression::Identifier(then)),
-                    StatementOrExpression::Expression(Expression::Identifier(else_)),
-                ) = (*cond, *then, *else_)
-                {
-                    assert_eq!(cond.name, "a".to_string());
-                    assert_eq!(then.name, "b".to_string());
-                    assert_eq!(else_.name, "c".to_string());
-                    return;
-                }
+    mod source_map {
+        use super::*;
+
+        fn generate(blocks: &[(&str, &str)]) -> (String, SourceMap) {
+            let mut script = String::new();
+            let mut source_map = SourceMap::new();
+            for (id, code) in blocks {
+                let start = script.len();
+                script.push_str(code);
+                source_map.add(start..script.len(), *id);
             }
+            (script, source_map)
         }
-        panic!();
-    }
-}
 
-mod template_syntax {
-    use super::*;
+        #[tokio::test]
+        async fn error_has_origin() {
+            let (script, source_map) = generate(&[
+                ("block-1", "let a = 1\n"),
+                ("block-2", "@f(s) {\n  Core:add(a, s)\n}\n"),
+                ("block-3", "f('x')\n"),
+            ]);
+            let aiscript = new_interpreter([]).with_source_map(source_map);
+            let err = aiscript
+                .exec(Parser::default().parse(&script).unwrap())
+                .await
+                .unwrap_err();
+            assert_eq!(err.origin(), Some("block-2"));
+            assert_eq!(err.stack()[0].origin.as_deref(), Some("block-3"));
+            assert_eq!(err.stack()[0].to_string(), "at f (38..42) [block-3]");
+        }
 
-    #[tokio::test]
-    async fn basic() {
-        test(
-            r#"
-            let str = "kawaii"
-            <: `Ai is {str}!`
-            "#,
-            |res| assert_eq!(res, str("Ai is kawaii!")),
-        )
-        .await
-        .unwrap();
-    }
+        #[tokio::test]
+        async fn innermost_origin() {
+            let script = "let a = [1, 2]\n<: a[5]\n";
+            let mut source_map = SourceMap::new();
+            source_map.add(0..script.len(), "program");
+            source_map.add(15..script.len(), "print");
+            source_map.add(18..22, "index");
+            let aiscript = new_interpreter([]).with_source_map(source_map);
+            let err = aiscript
+                .exec(Parser::default().parse(script).unwrap())
+                .await
+                .unwrap_err();
+            assert_eq!(err.origin(), Some("index"));
+            let Value { value, .. } = err.to_value();
+            let V::Error { info, .. } = value else {
+                panic!("error value expected")
+            };
+            let V::Obj(info) = info.unwrap().value else {
+                panic!("object expected")
+            };
+            assert_eq!(info.read().unwrap().get("origin"), Some(&str("index")));
+        }
 
-    #[tokio::test]
-    async fn convert_to_str() {
-        test(
-            r#"
-            <: `1 + 1 = {(1 + 1)}`
-            "#,
-            |res| assert_eq!(res, str("1 + 1 = 2")),
-        )
-        .await
-        .unwrap();
+        #[tokio::test]
+        async fn no_source_map() {
+            let err = test("Core:add(1, 'a')", |_| {}).await.unwrap_err();
+            assert_eq!(err.origin(), None);
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Type { .. })
+            ));
+        }
     }
 
-    #[tokio::test]
-    async fn invalid() {
-        test(
-            r#"
-            <: `{hoge}`
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-    }
+    mod prim_prop {
+        use aiscript_v0::values::{PrimType, VObj};
 
-    #[tokio::test]
-    async fn escape() {
-        test(
-            r#"
-            let message = "Hello"
-            <: `\`a\{b\}c\``
-            "#,
-            |res| assert_eq!(res, str("`a{b}c`")),
-        )
-        .await
-        .unwrap();
+        use super::*;
+
+        fn interpreter() -> Result<Interpreter, AiScriptError> {
+            Interpreter::default()
+                .with_prim_prop(PrimType::Str, "fancy", |target, args, _| {
+                    async move {
+                        let target = String::try_from(target)?;
+                        let mark = match args.into_iter().next() {
+                            Some(mark) => String::try_from(mark)?,
+                            None => "*".to_string(),
+                        };
+                        Ok(Value::str(format!("{mark}{target}{mark}")))
+                    }
+                    .boxed()
+                })?
+                .with_prim_prop(PrimType::Obj, "size", |target, _, _| {
+                    async move {
+                        let len = VObj::try_from(target)?.read().unwrap().len();
+                        Ok(Value::num(len as f64))
+                    }
+                    .boxed()
+                })
+        }
+
+        #[tokio::test]
+        async fn call() {
+            let res = interpreter()
+                .unwrap()
+                .exec(
+                    Parser::default()
+                        .parse("[\"text\".fancy(), 'a'.fancy('_'), { a: 1, b: 2 }.size(), { size: 0 }.size]")
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res, Some(arr([str("*text*"), str("_a_"), num(2), num(0)])));
+        }
+
+        #[tokio::test]
+        async fn undefined_prop() {
+            interpreter()
+                .unwrap()
+                .exec(Parser::default().parse("1.fancy()").unwrap())
+                .await
+                .unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn child() {
+            let aiscript = interpreter().unwrap();
+            let child = aiscript.spawn_child([] as [&str; 0], None).unwrap();
+            let res = child
+                .exec(Parser::default().parse("'text'.fancy()").unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res, Some(str("*text*")));
+            assert_eq!(aiscript.metrics().native_calls, 1);
+        }
+
+        #[test]
+        fn cannot_override_builtin() {
+            Interpreter::default()
+                .with_prim_prop(PrimType::Str, "len", |_, _, _| {
+                    async move { Ok(Value::null()) }.boxed()
+                })
+                .unwrap_err();
+        }
     }
-}
 
-#[tokio::test]
-async fn throws_error_when_divided_by_zero() {
-    test(
-        r#"
-        <: (0 / 0)
-        "#,
-        |_| {},
-    )
-    .await
-    .unwrap_err();
+    mod native_object {
+        use ::std::sync::RwLock;
+
+        use aiscript_v0::{values::NativeObject, Capability};
+        use futures::future::BoxFuture;
+
+        use super::*;
+
+        #[derive(Debug)]
+        struct Row {
+            name: RwLock<String>,
+        }
+
+        impl NativeObject for Row {
+            fn type_name(&self) -> &str {
+                "row"
+            }
+
+            fn get_prop<'a>(
+                &'a self,
+                name: &'a str,
+                _: &'a Interpreter,
+            ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+                async move {
+                    Ok(match name {
+                        "name" => str(self.name.read().unwrap().clone()),
+                        "greet" => Value::fn_native(|args, _| {
+                            async move {
+                                let name = String::try_from(args[0].clone())?;
+                                Ok(str(format!("Hello, {name}")))
+                            }
+                            .boxed()
+                        }),
+                        _ => null(),
+                    })
+                }
+                .boxed()
+            }
+
+            fn set_prop<'a>(
+                &'a self,
+                name: &'a str,
+                value: Value,
+                _: &'a Interpreter,
+            ) -> BoxFuture<'a, Result<(), AiScriptError>> {
+                async move {
+                    match name {
+                        "name" => *self.name.write().unwrap() = String::try_from(value)?,
+                        _ => Err(AiScriptRuntimeError::Runtime(format!(
+                            "No such prop {name}"
+                        )))?,
+                    }
+                    Ok(())
+                }
+                .boxed()
+            }
+
+            fn call_method<'a>(
+                &'a self,
+                name: &'a str,
+                args: Vec<Value>,
+                interpreter: &'a Interpreter,
+            ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+                async move {
+                    match name {
+                        "rename" => {
+                            let [value] = <[Value; 1]>::try_from(args).unwrap();
+                            self.set_prop("name", value, interpreter).await?;
+                            Ok(null())
+                        }
+                        _ => {
+                            let method = VFn::try_from(self.get_prop(name, interpreter).await?)?;
+                            interpreter.exec_fn_simple(method, args).await
+                        }
+                    }
+                }
+                .boxed()
+            }
+        }
+
+        async fn exec(src: &str) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter([(
+                "row".to_string(),
+                Value::native_object(Row {
+                    name: RwLock::new("a".to_string()),
+                }),
+            )]);
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
+
+        #[tokio::test]
+        async fn get_prop() {
+            assert_eq!(exec("row.name").await.unwrap(), str("a"));
+            assert_eq!(exec("row['name']").await.unwrap(), str("a"));
+            assert_eq!(exec("row.age").await.unwrap(), null());
+            assert_eq!(exec("Core:type(row)").await.unwrap(), str("row"));
+        }
+
+        #[tokio::test]
+        async fn set_prop() {
+            assert_eq!(exec("row.name = 'b'\nrow.name").await.unwrap(), str("b"));
+            assert_eq!(exec("row['name'] = 'c'\nrow.name").await.unwrap(), str("c"));
+            exec("row.age = 1").await.unwrap_err();
+            exec("row.name = 1").await.unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn call_method() {
+            assert_eq!(exec("row.rename('b')\nrow.name").await.unwrap(), str("b"));
+            assert_eq!(exec("row.greet('b')").await.unwrap(), str("Hello, b"));
+            exec("row.age()").await.unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn checked_method() {
+            let TypeSource::FnTypeSource(type_) =
+                Parser::default().parse_type("@(str) => str").unwrap()
+            else {
+                panic!("not a function type")
+            };
+            let aiscript = new_interpreter([(
+                "row".to_string(),
+                Value::native_object(Row {
+                    name: RwLock::new("a".to_string()),
+                }),
+            )])
+            .with_capability("row.rename", Capability::Net)
+            .with_native_type("row.greet", type_)
+            .with_permissions([])
+            .with_profiling(true);
+            let exec = |src: &str| aiscript.exec(Parser::default().parse(src).unwrap());
+            let err = exec("row.rename('b')").await.unwrap_err();
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::PermissionDenied {
+                    name,
+                    capability: Capability::Net,
+                }) if name == "row.rename"
+            ));
+            assert_eq!(exec("row.name").await.unwrap(), Some(str("a")));
+            assert_eq!(exec("row.greet('b')").await.unwrap(), Some(str("Hello, b")));
+            assert_eq!(
+                exec("row.greet(1)").await.unwrap_err().code(),
+                "type_mismatch"
+            );
+            assert!(aiscript
+                .profile()
+                .iter()
+                .any(|entry| entry.name == "row.greet" && entry.native));
+        }
+
+        #[derive(Debug)]
+        struct Pages {
+            pages: RwLock<Vec<Vec<Value>>>,
+            items: RwLock<Vec<Value>>,
+        }
+
+        impl NativeObject for Pages {
+            fn get_prop<'a>(
+                &'a self,
+                _: &'a str,
+                _: &'a Interpreter,
+            ) -> BoxFuture<'a, Result<Value, AiScriptError>> {
+                async move { Ok(null()) }.boxed()
+            }
+
+            fn next<'a>(
+                &'a self,
+                _: &'a Interpreter,
+            ) -> BoxFuture<'a, Result<Option<Value>, AiScriptError>> {
+                async move {
+                    let mut items = self.items.write().unwrap();
+                    while items.is_empty() {
+                        let mut pages = self.pages.write().unwrap();
+                        if pages.is_empty() {
+                            return Ok(None);
+                        }
+                        *items = pages.remove(0);
+                    }
+                    Ok(Some(items.remove(0)))
+                }
+                .boxed()
+            }
+        }
+
+        #[tokio::test]
+        async fn each() {
+            let pages = Value::native_object(Pages {
+                pages: RwLock::new(vec![vec![num(1), num(2)], vec![], vec![num(3), num(4)]]),
+                items: RwLock::new(Vec::new()),
+            });
+            let aiscript = new_interpreter([("pages".to_string(), pages)]);
+            let script = Parser::default()
+                .parse(
+                    r#"
+                    let items = []
+                    each let item, pages {
+                        if item == 3 { break }
+                        items.push(item)
+                    }
+                    each let item, pages {
+                        items.push(item)
+                    }
+                    items
+                    "#,
+                )
+                .unwrap();
+            assert_eq!(
+                aiscript.exec(script).await.unwrap().unwrap(),
+                arr([num(1), num(2), num(4)])
+            );
+            exec("each let item, row {}").await.unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn identity() {
+            assert_eq!(exec("row == row").await.unwrap(), bool(true));
+            assert_eq!(
+                exec("let r = row\nr.name = 'b'\nrow.name").await.unwrap(),
+                str("b")
+            );
+        }
+    }
 }
 
-mod function_call {
+mod ops {
     use super::*;
 
     #[tokio::test]
-    async fn without_args() {
-        test(
-            r#"
-            @f() {
-                42
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
+    async fn eq() {
+        test("<: (1 == 1)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: (1 == 2)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn with_args() {
-        test(
-            r#"
-            @f(x) {
-                x
-            }
-            <: f(42)
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
+    async fn neq() {
+        test("<: (1 != 2)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: (1 != 1)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn with_args_separated_by_comma() {
+    async fn and() {
+        test("<: (true && true)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: (true && false)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+
+        test("<: (false && true)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+
+        test("<: (false && false)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+
+        test("<: (false && null)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+
+        let err = test("<: (true && null)", |_| {}).await.unwrap_err();
+        assert!(matches!(err, AiScriptError::Runtime(_)));
+
         test(
             r#"
-            @f(x, y) {
-                (x + y)
+            var tmp = null
+
+            @func() {
+                tmp = true
+                return true
             }
-            <: f(1, 1)
+
+            false && func()
+
+            <: tmp
             "#,
-            |res| assert_eq!(res, num(2)),
+            |res| assert_eq!(res, null()),
         )
         .await
         .unwrap();
-    }
 
-    #[tokio::test]
-    async fn with_args_separated_by_space() {
         test(
             r#"
-            @f(x y) {
-                (x + y)
+            var tmp = null
+
+            @func() {
+                tmp = true
+                return true
             }
-            <: f(1 1)
+
+            true && func()
+
+            <: tmp
             "#,
-            |res| assert_eq!(res, num(2)),
+            |res| assert_eq!(res, bool(true)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn std_throw_aiscript_error_when_required_arg_missing() {
-        let err = test(
-            r#"
-            <: Core:eq(1)
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-        assert!(matches!(err, AiScriptError::Runtime(_)));
-    }
+    async fn or() {
+        test("<: (true || true)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
 
-    #[tokio::test]
-    async fn omitted_args() {
-        test(
-            r#"
-            @f(x, y) {
-                [x, y]
-            }
-            <: f(1)
-            "#,
-            |res| assert_eq!(res, arr([num(1), null()])),
-        )
-        .await
-        .unwrap();
-    }
-}
+        test("<: (true || false)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
 
-mod return_ {
-    use super::*;
+        test("<: (false || true)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
 
-    #[tokio::test]
-    async fn early_return() {
-        test(
-            r#"
-            @f() {
-                if true {
-                    return "ai"
-                }
+        test("<: (false || false)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
 
-                "pope"
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+        test("<: (true || null)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        let err = test("<: (false || null)", |_| {}).await.unwrap_err();
+        assert!(matches!(err, AiScriptError::Runtime(_)));
 
-    #[tokio::test]
-    async fn early_return_nested() {
         test(
             r#"
-            @f() {
-                if true {
-                    if true {
-                        return "ai"
-                    }
-                }
+            var tmp = null
 
-                "pope"
+            @func() {
+                tmp = true
+                return true
             }
-            <: f()
+
+            true || func()
+
+            <: tmp
             "#,
-            |res| assert_eq!(res, str("ai")),
+            |res| assert_eq!(res, null()),
         )
         .await
         .unwrap();
-    }
 
-    #[tokio::test]
-    async fn early_return_nested_2() {
         test(
             r#"
-            @f() {
-                if true {
-                    return "ai"
-                }
+            var tmp = null
 
-                "pope"
+            @func() {
+                tmp = true
+                return true
             }
 
-            @g() {
-                if (f() == "ai") {
-                    return "kawaii"
-                }
-
-                "pope"
-            }
+            false || func()
 
-            <: g()
+            <: tmp
             "#,
-            |res| assert_eq!(res, str("kawaii")),
+            |res| assert_eq!(res, bool(true)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn early_return_without_block() {
-        test(
-            r#"
-            @f() {
-                if true return "ai"
-
-                "pope"
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
+    async fn add() {
+        test("<: (1 + 1)", |res| assert_eq!(res, num(2)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn return_inside_for() {
-        test(
-            r#"
-            @f() {
-                var count = 0
-                for (let i, 100) {
-                    count += 1
-                    if (i == 42) {
-                        return count
-                    }
-                }
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, num(43)),
-        )
-        .await
-        .unwrap();
+    async fn sub() {
+        test("<: (1 - 1)", |res| assert_eq!(res, num(0)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn return_inside_for_2() {
-        test(
-            r#"
-            @f() {
-                for (let i, 10) {
-                    return 1
-                }
-                2
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, num(1)),
-        )
-        .await
-        .unwrap();
+    async fn mul() {
+        test("<: (1 * 1)", |res| assert_eq!(res, num(1)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn return_inside_loop() {
-        test(
-            r#"
-            @f() {
-                var count = 0
-                loop {
-                    count += 1
-                    if (count == 42) {
-                        return count
-                    }
-                }
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, num(42)),
-        )
-        .await
-        .unwrap();
+    async fn pow() {
+        test("<: (1 ^ 1)", |res| assert_eq!(res, num(1)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn return_inside_loop_2() {
-        test(
-            r#"
-            @f() {
-                loop {
-                    return 1
-                }
-                2
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, num(1)),
-        )
-        .await
-        .unwrap();
+    async fn div() {
+        test("<: (1 / 1)", |res| assert_eq!(res, num(1)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn return_inside_each() {
-        test(
-            r#"
-            @f() {
-                var count = 0
-                each (let item, ["ai", "chan", "kawaii"]) {
-                    count += 1
-                    if (item == "chan") {
-                        return count
-                    }
-                }
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, num(2)),
-        )
-        .await
-        .unwrap();
+    async fn mod_() {
+        test("<: (1 % 1)", |res| assert_eq!(res, num(0)))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
-    async fn return_inside_each_2() {
-        test(
-            r#"
-            @f() {
-                each (let item, ["ai", "chan", "kawaii"]) {
-                    return 1
-                }
-                2
-            }
-            <: f()
-            "#,
-            |res| assert_eq!(res, num(1)),
-        )
-        .await
-        .unwrap();
-    }
-}
+    async fn gt() {
+        test("<: (2 > 1)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
 
-mod eval {
-    use super::*;
+        test("<: (1 > 1)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+
+        test("<: (0 > 1)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+    }
 
     #[tokio::test]
-    async fn returns_value() {
-        test(
-            r#"
-            let foo = eval {
-                let a = 1
-                let b = 2
-                (a + b)
-            }
+    async fn lt() {
+        test("<: (2 < 1)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
 
-            <: foo
-            "#,
-            |res| assert_eq!(res, num(3)),
-        )
+        test("<: (1 < 1)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+
+        test("<: (0 < 1)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn gteq() {
+        test("<: (2 >= 1)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: (1 >= 1)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: (0 >= 1)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn lteq() {
+        test("<: (2 <= 1)", |res| assert_eq!(res, bool(false)))
+            .await
+            .unwrap();
+
+        test("<: (1 <= 1)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: (0 <= 1)", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn precedence() {
+        test("<: 1 + 2 * 3 + 4", |res| assert_eq!(res, num(11)))
+            .await
+            .unwrap();
+
+        test("<: 1 + 4 / 4 + 1", |res| assert_eq!(res, num(3)))
+            .await
+            .unwrap();
+
+        test("<: 1 + 1 == 2 && 2 * 2 == 4", |res| {
+            assert_eq!(res, bool(true))
+        })
         .await
         .unwrap();
+
+        test("<: (1 + 1) * 2", |res| assert_eq!(res, num(4)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn negative_numbers() {
+        test("<: 1+-1", |res| assert_eq!(res, num(0)))
+            .await
+            .unwrap();
+
+        test("<: 1--1", |res| assert_eq!(res, num(2)))
+            .await
+            .unwrap();
+
+        test("<: -1*-1", |res| assert_eq!(res, num(1)))
+            .await
+            .unwrap();
+
+        test("<: -1==-1", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: 1>-1", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: -1<1", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
     }
 }
 
-mod exists {
+mod equality {
     use super::*;
 
+    async fn exec(src: &str, reference_equality: bool) -> Result<Value, AiScriptError> {
+        let ast = Parser::default().parse(src)?;
+        let aiscript = Interpreter::new(
+            [],
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            Some(9999),
+        )
+        .with_reference_equality(reference_equality);
+        aiscript.exec(ast).await.map(Option::unwrap)
+    }
+
+    const MATRIX: &str = r#"
+        let a = [1, { b: 2 }]
+        let o = { x: 1, y: 2 }
+        @f() { 1 }
+        [
+            1 == 1
+            1 == "1"
+            null == null
+            null == false
+            a == a
+            a == [1, { b: 2 }]
+            [1] == [2]
+            [1] == [1, 1]
+            o == { y: 2, x: 1 }
+            o == { x: 1 }
+            f == f
+            Error:create("e", 1) == Error:create("e", 2)
+            a != [1, { b: 2 }]
+            [a, o].incl([1, { b: 2 }])
+            [a, o].index_of({ x: 1, y: 2 })
+            match o { { x: 1, y: 2 } => true * => false }
+            Core:deep_eq(a, [1, { b: 2 }])
+        ]
+    "#;
+
+    #[tokio::test]
+    async fn by_contents() {
+        let res = exec(MATRIX, false).await.unwrap();
+        assert_eq!(
+            res,
+            arr([
+                bool(true),
+                bool(false),
+                bool(true),
+                bool(false),
+                bool(true),
+                bool(true),
+                bool(false),
+                bool(false),
+                bool(true),
+                bool(false),
+                bool(false),
+                bool(false),
+                bool(false),
+                bool(true),
+                num(1),
+                bool(true),
+                bool(true),
+            ])
+        );
+    }
+
     #[tokio::test]
-    async fn basic() {
-        test(
+    async fn by_reference() {
+        let res = exec(MATRIX, true).await.unwrap();
+        assert_eq!(
+            res,
+            arr([
+                bool(true),
+                bool(false),
+                bool(true),
+                bool(false),
+                bool(true),
+                bool(false),
+                bool(false),
+                bool(false),
+                bool(false),
+                bool(false),
+                bool(true),
+                bool(true),
+                bool(true),
+                bool(false),
+                num(-1),
+                bool(false),
+                bool(true),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn cyclic() {
+        let res = exec(
             r#"
-            let foo = null
-            <: [(exists foo) (exists bar)]
+            let a = []
+            a.push(a)
+            let b = []
+            b.push(b)
+            [a == b, Core:deep_eq(a, [a]), Core:deep_eq(a, [[1]])]
             "#,
-            |res| assert_eq!(res, arr([bool(true), bool(false)])),
+            false,
         )
         .await
         .unwrap();
+        assert_eq!(res, arr([bool(true), bool(true), bool(false)]));
     }
 }
 
-mod if_ {
+mod value_diff {
+    use super::*;
+    use aiscript_v0::values::{diff, Difference};
+
+    #[test]
+    fn equal() {
+        let a = arr([num(1), obj([("b", str("x"))])]);
+        let b = arr([num(1), obj([("b", str("x"))])]);
+        let d = diff(&a, &b);
+        assert!(d.is_empty());
+        assert_eq!(d.to_string(), "");
+    }
+
+    #[test]
+    fn nested() {
+        let expected = obj([
+            ("a", arr([num(1), num(2), num(3)])),
+            ("b", obj([("c", str("x")), ("d e", null())])),
+        ]);
+        let actual = obj([
+            ("a", arr([num(1), str("2")])),
+            ("b", obj([("c", str("y")), ("f", bool(true))])),
+        ]);
+        let d = diff(&expected, &actual);
+        assert_eq!(
+            d.0,
+            vec![
+                Difference {
+                    path: ".a[1]".to_string(),
+                    expected: Some(num(2)),
+                    actual: Some(str("2")),
+                },
+                Difference {
+                    path: ".a[2]".to_string(),
+                    expected: Some(num(3)),
+                    actual: None,
+                },
+                Difference {
+                    path: ".b.c".to_string(),
+                    expected: Some(str("x")),
+                    actual: Some(str("y")),
+                },
+                Difference {
+                    path: ".b[\"d e\"]".to_string(),
+                    expected: Some(null()),
+                    actual: None,
+                },
+                Difference {
+                    path: ".b.f".to_string(),
+                    expected: None,
+                    actual: Some(bool(true)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        let d = diff(
+            &arr([num(1), arr([bool(true)])]),
+            &arr([num(2), arr([bool(true), null()])]),
+        );
+        assert_eq!(
+            d.to_string(),
+            "at [0]: expected 1, got 2\nat [1][1]: unexpected (null)"
+        );
+        assert_eq!(
+            diff(&num(1), &str("1")).to_string(),
+            "at the root: expected 1, got \"1\""
+        );
+    }
+
+    #[test]
+    fn cyclic() {
+        let a = arr([num(1)]);
+        let b = arr([num(2)]);
+        if let (V::Arr(a_arr), V::Arr(b_arr)) = (&a.value, &b.value) {
+            a_arr.write().unwrap().push(a.clone());
+            b_arr.write().unwrap().push(b.clone());
+        }
+        assert_eq!(diff(&a, &b).to_string(), "at [0]: expected 1, got 2");
+        assert_eq!(diff(&a, &b).is_empty(), a.deep_eq(&b));
+    }
+}
+
+mod infix_expression {
     use super::*;
 
     #[tokio::test]
-    async fn if_() {
-        test(
-            r#"
-            var msg = "ai"
-            if true {
-                msg = "kawaii"
-            }
-            <: msg
-            "#,
-            |res| assert_eq!(res, str("kawaii")),
-        )
+    async fn simple_infix_expression() {
+        test("<: 0 < 1", |res| assert_eq!(res, bool(true)))
+            .await
+            .unwrap();
+
+        test("<: 1 + 1", |res| assert_eq!(res, num(2)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn combination() {
+        test("<: 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10", |res| {
+            assert_eq!(res, num(55))
+        })
+        .await
+        .unwrap();
+
+        test("<: Core:add(1, 3) * Core:mul(2, 5)", |res| {
+            assert_eq!(res, num(40))
+        })
         .await
         .unwrap();
+    }
+
+    #[tokio::test]
+    async fn use_parentheses_to_distinguish_expr() {
+        test("<: (1 + 10) * (2 + 5)", |res| assert_eq!(res, num(77)))
+            .await
+            .unwrap();
+    }
 
+    #[tokio::test]
+    async fn syntax_symbols_vs_infix_operators() {
         test(
             r#"
-            var msg = "ai"
-            if false {
-                msg = "kawaii"
+            <: match true {
+                1 == 1 => "true"
+                1 < 1 => "false"
             }
-            <: msg
             "#,
-            |res| assert_eq!(res, str("ai")),
+            |res| assert_eq!(res, str("true")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn else_() {
+    async fn number_if_expression() {
+        test("<: 1 + if true 1 else 2", |res| assert_eq!(res, num(2)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_match_expression() {
         test(
             r#"
-            var msg = null
-            if true {
-                msg = "ai"
-            } else {
-                msg = "kawaii"
+            <: 1 + match 2 == 2 {
+                true => 3
+                false => 4
             }
-            <: msg
             "#,
-            |res| assert_eq!(res, str("ai")),
+            |res| assert_eq!(res, num(4)),
         )
         .await
         .unwrap();
+    }
+
+    #[tokio::test]
+    async fn eval_eval() {
+        test("<: eval { 1 } + eval { 1 }", |res| assert_eq!(res, num(2)))
+            .await
+            .unwrap();
+    }
 
+    #[tokio::test]
+    async fn disallow_line_break() {
         test(
             r#"
-            var msg = null
-            if false {
-                msg = "ai"
-            } else {
-                msg = "kawaii"
-            }
-            <: msg
+            <: 1 +
+            1 + 1
             "#,
-            |res| assert_eq!(res, str("kawaii")),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
 
     #[tokio::test]
-    async fn elif() {
+    async fn escaped_line_break() {
         test(
             r#"
-            var msg = "bebeyo"
-            if false {
-                msg = "ai"
-            } elif true {
-                msg = "kawaii"
-            }
-            <: msg
+            <: 1 + \
+            1 + 1
             "#,
-            |res| assert_eq!(res, str("kawaii")),
+            |res| assert_eq!(res, num(3)),
         )
         .await
         .unwrap();
+    }
 
+    #[tokio::test]
+    async fn infix_to_fncall_on_namespace() {
         test(
             r#"
-            var msg = "bebeyo"
-            if false {
-                msg = "ai"
-            } elif false {
-                msg = "kawaii"
+            :: Hoge {
+                @add(x, y) {
+                    x + y
+                }
             }
-            <: msg
+            <: Hoge:add(1, 2)
             "#,
-            |res| assert_eq!(res, str("bebeyo")),
+            |res| assert_eq!(res, num(3)),
         )
         .await
         .unwrap();
     }
 
-    #[tokio::test]
-    async fn if_elif_else() {
-        test(
-            r#"
-            var msg = null
-            if false {
-                msg = "ai"
-            } elif true {
-                msg = "chan"
-            } else {
-                msg = "kawaii"
-            }
-            <: msg
-            "#,
-            |res| assert_eq!(res, str("chan")),
-        )
-        .await
-        .unwrap();
-
-        test(
-            r#"
-            var msg = null
-            if false {
-                msg = "ai"
-            } elif false {
-                msg = "chan"
-            } else {
-                msg = "kawaii"
-            }
-            <: msg
-            "#,
-            |res| assert_eq!(res, str("kawaii")),
-        )
-        .await
-        .unwrap();
+    #[test]
+    fn chained_comparison() {
+        for (src, operator) in [
+            ("let x = 5\n<: 1 < x < 10", "<"),
+            ("let x = 5\n<: 1 <= x <= 10", "<="),
+            ("let x = 5\n<: 10 > x >= 1", ">="),
+            ("let x = 5\n<: 1 == x < 10", "<"),
+            ("let x = 5\n<: 1 <= x < 10", "<="),
+            ("let x = 5\n<: (1 < x) > 10", ">"),
+            ("@f(x) { 0 < x < 1 }", "<"),
+        ] {
+            let err = Parser::default().parse(src).unwrap_err();
+            assert_eq!(err.code(), "chained_comparison", "{src}");
+            let loc = err.loc().unwrap();
+            assert_eq!(&src[loc.start..=loc.end], operator, "{src}");
+        }
+        let err = Parser::default().parse("<: 1 < 2 < 3").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Syntax: Comparisons cannot be chained. Use && to combine them, e.g. `a < b && b < c`."
+        );
     }
 
     #[tokio::test]
-    async fn expr() {
-        test(
-            r#"
-            <: if true "ai" else "kawaii"
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-
+    async fn comparison_of_comparisons() {
         test(
-            r#"
-            <: if false "ai" else "kawaii"
-            "#,
-            |res| assert_eq!(res, str("kawaii")),
+            "<: [(1 < 2) == (2 < 3), 1 == 1 != false, 1 < 2 && 2 < 3]",
+            |res| assert_eq!(res, arr([bool(true), bool(true), bool(true)])),
         )
         .await
         .unwrap();
     }
 }
 
-mod match_ {
+mod comment {
     use super::*;
 
     #[tokio::test]
-    async fn basic() {
+    async fn single_line_comment() {
         test(
             r#"
-            <: match 2 {
-                1 => "a"
-                2 => "b"
-                3 => "c"
-            }
+            // let a = ...
+            let a = 42
+            <: a
             "#,
-            |res| assert_eq!(res, str("b")),
+            |res| assert_eq!(res, num(42)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn when_default_not_provided_returns_null() {
+    async fn multi_line_comment() {
         test(
             r#"
-            <: match 42 {
-                1 => "a"
-                2 => "b"
-                3 => "c"
-            }
+            /* variable declaration here...
+                let a = ...
+            */
+            let a = 42
+            <: a
             "#,
-            |res| assert_eq!(res, null()),
+            |res| assert_eq!(res, num(42)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn with_default() {
+    async fn multi_line_comment_2() {
         test(
             r#"
-            <: match 42 {
-                1 => "a"
-                2 => "b"
-                3 => "c"
-                * => "d"
-            }
+            /* variable declaration here...
+                let a = ...
+            */
+            let a = 42
+            /*
+                another comment here
+            */
+            <: a
             "#,
-            |res| assert_eq!(res, str("d")),
+            |res| assert_eq!(res, num(42)),
         )
         .await
         .unwrap();
     }
+}
 
-    #[tokio::test]
-    async fn with_block() {
-        test(
-            r#"
-            <: match 2 {
-                1 => 1
-                2 => {
-                    let a = 1
-                    let b = 2
-                    (a + b)
-                }
-                3 => 3
-            }
-            "#,
-            |res| assert_eq!(res, num(3)),
-        )
-        .await
-        .unwrap();
-    }
+#[tokio::test]
+async fn expression_containing_collon_is_not_object() {
+    test(
+        r#"
+        <: eval {
+            Core:eq("ai", "ai")
+        }
+        "#,
+        |res| assert_eq!(res, bool(true)),
+    )
+    .await
+    .unwrap();
+}
 
-    #[tokio::test]
-    async fn with_return() {
-        test(
-            r#"
-            @f(x) {
-                match x {
-                    1 => {
-                        return "ai"
-                    }
-                }
-                "foo"
-            }
-            <: f(1)
-            "#,
-            |res| assert_eq!(res, str("ai")),
-        )
-        .await
-        .unwrap();
-    }
+#[tokio::test]
+async fn inc() {
+    test(
+        r#"
+        var a = 0
+        a += 1
+        a += 2
+        a += 3
+        <: a
+        "#,
+        |res| assert_eq!(res, num(6)),
+    )
+    .await
+    .unwrap();
 }
 
-mod loop_ {
+#[tokio::test]
+async fn dec() {
+    test(
+        r#"
+        var a = 0
+        a -= 1
+        a -= 2
+        a -= 3
+        <: a
+        "#,
+        |res| assert_eq!(res, num(-6)),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn reference_is_not_chained() {
+    test(
+        r#"
+        var f = @() { "a" }
+        var g = f
+        f = @() { "b" }
+
+        <: g()
+        "#,
+        |res| assert_eq!(res, str("a")),
+    )
+    .await
+    .unwrap();
+}
+
+mod cannot_put_multiple_statements_in_a_line {
     use super::*;
 
     #[tokio::test]
-    async fn basic() {
+    async fn var_def() {
         test(
             r#"
-            var count = 0
-            loop {
-                if (count == 10) break
-                count = (count + 1)
-            }
-            <: count
+            let a = 42 let b = 11
             "#,
-            |res| assert_eq!(res, num(10)),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
 
     #[tokio::test]
-    async fn with_continue() {
+    async fn var_def_op() {
         test(
             r#"
-            var a = ["ai" "chan" "kawaii" "yo" "!"]
-            var b = []
-            loop {
-                var x = a.shift()
-                if (x == "chan") continue
-                if (x == "yo") break
-                b.push(x)
-            }
-            <: b
+            let a = 13 + 75 let b = 24 + 146
             "#,
-            |res| assert_eq!(res, arr([str("ai"), str("kawaii")])),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
 }
 
-mod for_ {
-    use super::*;
+#[tokio::test]
+async fn empty_function() {
+    test(
+        r#"
+        @hoge() { }
+        <: hoge()
+        "#,
+        |res| assert_eq!(res, null()),
+    )
+    .await
+    .unwrap();
+}
 
-    #[tokio::test]
-    async fn basic() {
+#[tokio::test]
+async fn empty_lambda() {
+    test(
+        r#"
+        let hoge = @() { }
+        <: hoge()
+        "#,
+        |res| assert_eq!(res, null()),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn lambda_that_returns_an_object() {
+    test(
+        r#"
+        let hoge = @() {{}}
+        <: hoge()
+        "#,
+        |res| assert_eq!(res, obj([] as [(String, Value); 0])),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn closure() {
+    test(
+        r#"
+        @store(v) {
+            let state = v
+            @() {
+                state
+            }
+        }
+        let s = store("ai")
+        <: s()
+        "#,
+        |res| assert_eq!(res, str("ai")),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn closure_counter() {
+    test(
+        r#"
+        @create_counter() {
+            var count = 0
+            {
+                get_count: @() { count };
+                count: @() { count = (count + 1) };
+            }
+        }
+
+        let counter = create_counter()
+        let get_count = counter.get_count
+        let count = counter.count
+
+        count()
+        count()
+        count()
+
+        <: get_count()
+        "#,
+        |res| assert_eq!(res, num(3)),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn recursion() {
+    test(
+        r#"
+        @fact(n) {
+            if (n == 0) { 1 } else { (fact((n - 1)) * n) }
+        }
+
+        <: fact(5)
+        "#,
+        |res| assert_eq!(res, num(120)),
+    )
+    .await
+    .unwrap();
+}
+
+mod var_name_starts_with_reserved_word {
+    use super::*;
+
+    #[tokio::test]
+    async fn let_() {
         test(
             r#"
-            var count = 0
-            for (let i, 10) {
-                count += i + 1
+            @f() {
+                let letcat = "ai"
+                letcat
             }
-            <: count
+            <: f()
             "#,
-            |res| assert_eq!(res, num(55)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn initial_value() {
+    async fn var() {
         test(
             r#"
-            var count = 0
-            for (let i = 2, 10) {
-                count += i
+            @f() {
+                let varcat = "ai"
+                varcat
             }
-            <: count
+            <: f()
             "#,
-            |res| assert_eq!(res, num(65)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn without_iterator() {
+    async fn return_() {
         test(
             r#"
-            var count = 0
-            for (10) {
-                count = (count + 1)
+            @f() {
+                let returncat = "ai"
+                returncat
             }
-            <: count
+            <: f()
             "#,
-            |res| assert_eq!(res, num(10)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn without_brackets() {
+    async fn each() {
         test(
             r#"
-            var count = 0
-            for let i, 10 {
-                count = (count + i)
+            @f() {
+                let eachcat = "ai"
+                eachcat
             }
-            <: count
+            <: f()
             "#,
-            |res| assert_eq!(res, num(45)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn break_() {
+    async fn for_() {
         test(
             r#"
-            var count = 0
-            for (let i, 20) {
-                if (i == 11) break
-                count += i
+            @f() {
+                let forcat = "ai"
+                forcat
             }
-            <: count
+            <: f()
             "#,
-            |res| assert_eq!(res, num(55)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn continue_() {
+    async fn loop_() {
         test(
             r#"
-            var count = 0
-            for (let i, 10) {
-                if (i == 5) continue
-                count = (count + 1)
+            @f() {
+                let loopcat = "ai"
+                loopcat
             }
-            <: count
+            <: f()
             "#,
-            |res| assert_eq!(res, num(9)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn single_statement() {
+    async fn break_() {
         test(
             r#"
-            var count = 0
-            for 10 count += 1
-            <: count
+            @f() {
+                let breakcat = "ai"
+                breakcat
+            }
+            <: f()
             "#,
-            |res| assert_eq!(res, num(10)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn var_name_without_space() {
+    async fn continue_() {
         test(
             r#"
-            for (leti, 10) {
-                <: i
+            @f() {
+                let continuecat = "ai"
+                continuecat
             }
+            <: f()
             "#,
-            |_| {},
+            |res| assert_eq!(res, str("ai")),
         )
         .await
-        .unwrap_err();
+        .unwrap();
     }
-}
-
-mod for_of {
-    use super::*;
 
     #[tokio::test]
-    async fn standard() {
+    async fn if_() {
         test(
             r#"
-            let msgs = []
-            each let item, ["ai", "chan", "kawaii"] {
-                msgs.push([item, "!"].join())
+            @f() {
+                let ifcat = "ai"
+                ifcat
             }
-            <: msgs
+            <: f()
             "#,
-            |res| assert_eq!(res, arr([str("ai!"), str("chan!"), str("kawaii!")])),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn break_() {
+    async fn match_() {
         test(
             r#"
-            let msgs = []
-            each let item, ["ai", "chan", "kawaii" "yo"] {
-                if (item == "kawaii") break
-                msgs.push([item, "!"].join())
+            @f() {
+                let matchcat = "ai"
+                matchcat
             }
-            <: msgs
+            <: f()
             "#,
-            |res| assert_eq!(res, arr([str("ai!"), str("chan!")])),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn single_statement() {
+    async fn true_() {
         test(
             r#"
-            let msgs = []
-            each let item, ["ai", "chan", "kawaii"] msgs.push([item, "!"].join())
-            <: msgs
+            @f() {
+                let truecat = "ai"
+                truecat
+            }
+            <: f()
             "#,
-            |res| assert_eq!(res, arr([str("ai!"), str("chan!"), str("kawaii!")])),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn var_name_without_space() {
+    async fn false_() {
         test(
             r#"
-            each letitem, ["ai", "chan", "kawaii"] {
-                <: item
+            @f() {
+                let falsecat = "ai"
+                falsecat
             }
+            <: f()
             "#,
-            |_| {},
+            |res| assert_eq!(res, str("ai")),
         )
         .await
-        .unwrap_err();
+        .unwrap();
     }
-}
-
-mod not {
-    use super::*;
 
     #[tokio::test]
-    async fn basic() {
+    async fn null() {
         test(
             r#"
-            <: !true
+            @f() {
+                let nullcat = "ai"
+                nullcat
+            }
+            <: f()
             "#,
-            |res| assert_eq!(res, bool(false)),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 }
 
-mod namespace {
+mod name_validation_of_reserved_word {
     use super::*;
 
     #[tokio::test]
-    async fn standard() {
+    async fn def() {
         test(
             r#"
-            <: Foo:bar()
-
-            :: Foo {
-                @bar() { "ai" }
-            }
+            let let = 1
             "#,
-            |res| assert_eq!(res, str("ai")),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
 
     #[tokio::test]
-    async fn self_ref() {
+    async fn attr() {
         test(
             r#"
-            <: Foo:bar()
-
-            :: Foo {
-                let ai = "kawaii"
-                @bar() { ai }
-            }
+            #[let 1]
+            @f() { 1 }
             "#,
-            |res| assert_eq!(res, str("kawaii")),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
 
     #[tokio::test]
-    async fn cannot_declare_mutable_variable() {
+    async fn ns() {
         test(
             r#"
-            :: Foo {
-                var ai = "kawaii"
+            :: let {
+                @f() { 1 }
             }
             "#,
             |_| {},
@@ -2589,1131 +4180,5496 @@ mod namespace {
     }
 
     #[tokio::test]
-    async fn nested() {
+    async fn var() {
         test(
             r#"
-            <: Foo:Bar:baz()
-
-            :: Foo {
-                :: Bar {
-                    @baz() { "ai" }
-                }
-            }
+            let
             "#,
-            |res| assert_eq!(res, str("ai")),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
 
     #[tokio::test]
-    async fn nested_ref() {
+    async fn prop() {
         test(
             r#"
-            <: Foo:baz
-
-            :: Foo {
-                let baz = Bar:ai
-                :: Bar {
-                    let ai = "kawaii"
-                }
-            }
+            let x = { let: 1 }
+            x.let
             "#,
-            |res| assert_eq!(res, str("kawaii")),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
-}
-
-mod literal {
-    use super::*;
 
     #[tokio::test]
-    async fn string_single_quote() {
+    async fn meta() {
         test(
             r#"
-            <: 'foo'
+            ### let 1
             "#,
-            |res| assert_eq!(res, str("foo")),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
 
     #[tokio::test]
-    async fn string_double_quote() {
+    async fn fn_() {
         test(
             r#"
-            <: "foo"
+            @let() { 1 }
             "#,
-            |res| assert_eq!(res, str("foo")),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
     }
+}
+
+mod object {
+    use super::*;
 
     #[tokio::test]
-    async fn escaped_double_quote() {
+    async fn property_access() {
         test(
             r#"
-            <: "ai saw a note \"bebeyo\"."
+            let obj = {
+                a: {
+                    b: {
+                        c: 42;
+                    };
+                };
+            }
+
+            <: obj.a.b.c
             "#,
-            |res| assert_eq!(res, str(r#"ai saw a note "bebeyo"."#)),
+            |res| assert_eq!(res, num(42)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn escaped_single_quote() {
+    async fn property_access_fn_call() {
         test(
             r#"
-            <: 'ai saw a note \'bebeyo\'.'
+            @f() { 42 }
+
+            let obj = {
+                a: {
+                    b: {
+                        c: f;
+                    };
+                };
+            }
+
+            <: obj.a.b.c()
             "#,
-            |res| assert_eq!(res, str("ai saw a note 'bebeyo'.")),
+            |res| assert_eq!(res, num(42)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn bool_true() {
+    async fn property_assign() {
         test(
             r#"
-            <: true
+            let obj = {
+                a: 1
+                b: {
+                    c: 2
+                    d: {
+                        e: 3
+                    }
+                }
+            }
+
+            obj.a = 24
+            obj.b.d.e = 42
+
+            <: obj
             "#,
-            |res| assert_eq!(res, bool(true)),
+            |res| {
+                assert_eq!(
+                    res,
+                    obj([
+                        ("a", num(24)),
+                        ("b", obj([("c", num(2)), ("d", obj([("e", num(42))]))]))
+                    ])
+                )
+            },
         )
         .await
         .unwrap();
     }
+}
+
+mod array {
+
+    use super::*;
 
     #[tokio::test]
-    async fn bool_false() {
+    async fn array_item_access() {
         test(
             r#"
-            <: false
+            let arr = ["ai", "chan", "kawaii"]
+
+            <: arr[1]
             "#,
-            |res| assert_eq!(res, bool(false)),
+            |res| assert_eq!(res, str("chan")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn number_int() {
+    async fn array_item_assign() {
         test(
             r#"
-            <: 10
+            let arr = ["ai", "chan", "kawaii"]
+
+            arr[1] = "taso"
+
+            <: arr
             "#,
-            |res| assert_eq!(res, num(10)),
+            |res| assert_eq!(res, arr([str("ai"), str("taso"), str("kawaii"),])),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn number_float() {
-        test(
+    async fn assign_array_item_to_out_of_range() {
+        let err = test(
             r#"
-            <: 0.5
+            let arr = [1, 2, 3]
+
+            arr[3] = 4
+
+            <: null
             "#,
-            |res| assert_eq!(res, num(0.5)),
+            |_| {},
         )
         .await
-        .unwrap();
-    }
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
+        ));
 
-    #[tokio::test]
-    async fn arr_separated_by_comma() {
-        test(
+        let err = test(
             r#"
-            <: [1, 2, 3]
+            let arr = [1, 2, 3]
+
+            arr[9] = 10
+
+            <: null
             "#,
-            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
+        ));
     }
 
     #[tokio::test]
-    async fn arr_separated_by_comma_with_trailing_comma() {
-        test(
+    async fn index_out_of_range_error() {
+        let err = test(
             r#"
-            <: [1, 2, 3,]
+            <: [42][1]
             "#,
-            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
+        ));
     }
 
     #[tokio::test]
-    async fn arr_separated_by_line_break() {
-        test(
+    async fn index_out_of_range_on_assignment() {
+        let err = test(
             r#"
-            <: [
-                1
-                2
-                3
-            ]
+            var a = []
+            a[2] = 'hoge'
             "#,
-            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+            |_| {},
         )
         .await
-        .unwrap();
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
+        ));
     }
 
     #[tokio::test]
-    async fn arr_separated_by_line_break_and_comma() {
-        test(
+    async fn non_integer_indexed_assignment() {
+        let err = test(
             r#"
-            <: [
-                1,
-                2,
-                3
-            ]
+            var a = []
+            a[6.21] = 'hoge'
             "#,
-            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+            |_| {},
         )
         .await
-        .unwrap();
-    }
-
-    #[tokio::test]
-    async fn arr_separated_by_line_break_and_comma_with_trailing_comma() {
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            AiScriptError::Runtime(AiScriptRuntimeError::IndexOutOfRange(_, _))
+        ));
+    }
+}
+
+mod chain {
+    use super::*;
+
+    #[tokio::test]
+    async fn chain_access_prop_index_call() {
         test(
             r#"
-            <: [
-                1,
-                2,
-                3,
-            ]
+            let obj = {
+                a: {
+                    b: [@(name) { name }, @(str) { "chan" }, @() { "kawaii" }];
+                };
+            }
+
+            <: obj.a.b[0]("ai")
             "#,
-            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+            |res| assert_eq!(res, str("ai")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_separated_by_comma() {
+    async fn chained_assign_left_side_prop_index() {
         test(
             r#"
-            <: { a: 1, b: 2, c: 3 }
+            let obj = {
+                a: {
+                    b: ["ai", "chan", "kawaii"];
+                };
+            }
+
+            obj.a.b[1] = "taso"
+
+            <: obj
             "#,
-            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+            |res| {
+                assert_eq!(
+                    res,
+                    obj([(
+                        "a",
+                        obj([("b", arr([str("ai"), str("taso"), str("kawaii"),]))])
+                    )])
+                )
+            },
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_separated_by_comma_with_trailing_comma() {
+    async fn chained_assign_right_side_prop_index_call() {
         test(
             r#"
-            <: { a: 1, b: 2, c: 3, }
+            let obj = {
+                a: {
+                    b: ["ai", "chan", "kawaii"];
+                };
+            }
+
+            var x = null
+            x = obj.a.b[1]
+
+            <: x
             "#,
-            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+            |res| assert_eq!(res, str("chan"),),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_separated_by_semicolon() {
+    async fn chained_inc_dec_left_side_index_prop() {
         test(
             r#"
-            <: { a: 1; b: 2; c: 3 }
+            let arr = [
+                {
+                    a: 1;
+                    b: 2;
+                }
+            ]
+
+            arr[0].a += 1
+            arr[0].b -= 1
+
+            <: arr
             "#,
-            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+            |res| assert_eq!(res, arr([obj([("a", num(2)), ("b", num(1)),])])),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_separated_by_semicolon_with_trailing_semicolon() {
+    async fn chained_inc_dec_left_side_prop_index() {
         test(
             r#"
-            <: { a: 1; b: 2; c: 3; }
+            let obj = {
+                a: {
+                    b: [1, 2, 3];
+                };
+            }
+
+            obj.a.b[1] += 1
+            obj.a.b[2] -= 1
+
+            <: obj
             "#,
-            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+            |res| {
+                assert_eq!(
+                    res,
+                    obj([("a", obj([("b", arr([num(1), num(3), num(2),]))]))])
+                )
+            },
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_separated_by_line_break() {
+    async fn prop_in_def() {
         test(
             r#"
-            <: {
-                a: 1
-                b: 2
-                c: 3
+            let x = @() {
+                let obj = {
+                    a: 1
+                }
+                obj.a
             }
+
+            <: x()
             "#,
-            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+            |res| assert_eq!(res, num(1)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_separated_by_line_break_and_semicolon() {
+    async fn prop_in_return() {
         test(
             r#"
-            <: {
-                a: 1;
-                b: 2;
-                c: 3
+            let x = @() {
+                let obj = {
+                    a: 1
+                }
+                return obj.a
+                2
             }
+
+            <: x()
             "#,
-            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+            |res| assert_eq!(res, num(1)),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_separated_by_line_break_and_semicolon_with_trailing_semicolon() {
+    async fn prop_in_each() {
         test(
             r#"
-            <: {
-                a: 1;
-                b: 2;
-                c: 3;
+            let msgs = []
+            let x = { a: ["ai", "chan", "kawaii"] }
+            each let item, x.a {
+                let y = { a: item }
+                msgs.push([y.a, "!"].join())
             }
+            <: msgs
             "#,
-            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+            |res| assert_eq!(res, arr([str("ai!"), str("chan!"), str("kawaii!"),])),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn obj_and_arr_separated_by_line_break() {
+    async fn prop_in_for() {
         test(
             r#"
-            <: {
-                a: 1
-                b: [
-                    1
-                    2
-                    3
-                ]
-                c: 3
+            let x = { times: 10, count: 0 }
+            for (let i, x.times) {
+                x.count = (x.count + i)
             }
+            <: x.count
             "#,
-            |res| {
-                assert_eq!(
-                    res,
-                    obj([
-                        ("a", num(1)),
-                        ("b", arr([num(1), num(2), num(3)])),
-                        ("c", num(3))
-                    ])
-                )
-            },
+            |res| assert_eq!(res, num(45)),
         )
         .await
         .unwrap();
     }
-}
-
-mod type_declaration {
-    use super::*;
 
     #[tokio::test]
-    async fn def() {
+    async fn object_with_index() {
         test(
             r#"
-            let abc: num = 1
-            var xyz: str = "abc"
-            <: [abc xyz]
+            let ai = {a: {}}['a']
+            ai['chan'] = 'kawaii'
+            <: ai[{a: 'chan'}['a']]
             "#,
-            |res| assert_eq!(res, arr([num(1), str("abc")])),
+            |res| assert_eq!(res, str("kawaii")),
         )
         .await
         .unwrap();
     }
 
     #[tokio::test]
-    async fn fn_def() {
-        test(
-            r#"
-            @f(x: arr<num>, y: str, z: @(num) => bool): arr<num> {
-                x.push(0)
-                y = "abc"
-                var r: bool = z(x[0])
-                x.push(if r 5 else 10)
-                x
+    async fn property_chain_with_parenthesis() {
+        let ast = Parser::default()
+            .parse(
+                r#"
+                (a.b).c
+                "#,
+            )
+            .unwrap();
+        let line = ast.first().unwrap().clone();
+        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
+            assert_eq!(name, "c".to_string());
+            if let Expression::Prop(Prop { target, name, .. }) = *target {
+                assert_eq!(name, "b".to_string());
+                if let Expression::Identifier(Identifier { name, .. }) = *target {
+                    assert_eq!(name, "a".to_string());
+                    return;
+                }
             }
-
-            <: f([1, 2, 3], "a", @(n) { n == 1 })
-            "#,
-            |res| assert_eq!(res, arr([num(1), num(2), num(3), num(0), num(5)])),
-        )
-        .await
-        .unwrap();
+        }
+        panic!();
     }
-}
 
-mod meta {
-    use super::*;
+    #[tokio::test]
+    async fn index_chain_with_parenthesis() {
+        let ast = Parser::default()
+            .parse(
+                r#"
+                (a[42]).b
+                "#,
+            )
+            .unwrap();
+        let line = ast.first().unwrap().clone();
+        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
+            assert_eq!(name, "b".to_string());
+            if let Expression::Index(Index { target, index, .. }) = *target {
+                if let (
+                    Expression::Identifier(Identifier { name, .. }),
+                    Expression::Num(Num { value, .. }),
+                ) = (*target, *index)
+                {
+                    assert_eq!(name, "a".to_string());
+                    assert_eq!(value, 42.0);
+                    return;
+                }
+            }
+        }
+        panic!();
+    }
 
-    #[test]
-    fn default_meta() {
-        let res = get_meta(
-            r#"
-            ### { a: 1; b: 2; c: 3; }
-            "#,
-        )
+    #[tokio::test]
+    async fn call_chain_with_parenthesis() {
+        let ast = Parser::default()
+            .parse(
+                r#"
+                (foo(42, 57)).bar
+                "#,
+            )
+            .unwrap();
+        let line = ast.first().unwrap().clone();
+        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
+            assert_eq!(name, "bar".to_string());
+            if let Expression::Call(Call { target, args, .. }) = *target {
+                if let Expression::Identifier(Identifier { name, .. }) = *target {
+                    assert_eq!(name, "foo".to_string());
+                    if let [Expression::Num(Num { value: arg1, .. }), Expression::Num(Num { value: arg2, .. })] =
+                        args[..]
+                    {
+                        assert_eq!(arg1, 42.0);
+                        assert_eq!(arg2, 57.0);
+                        return;
+                    }
+                }
+            }
+        }
+        panic!();
+    }
+
+    #[tokio::test]
+    async fn longer_chain_with_parenthesis() {
+        let ast = Parser::default()
+            .parse(
+                r#"
+                (a.b.c).d.e
+                "#,
+            )
+            .unwrap();
+        let line = ast.first().unwrap().clone();
+        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line {
+            assert_eq!(name, "e".to_string());
+            if let Expression::Prop(Prop { target, name, .. }) = *target {
+                assert_eq!(name, "d".to_string());
+                if let Expression::Prop(Prop { target, name, .. }) = *target {
+                    assert_eq!(name, "c".to_string());
+                    if let Expression::Prop(Prop { target, name, .. }) = *target {
+                        assert_eq!(name, "b".to_string());
+                        if let Expression::Identifier(Identifier { name, .. }) = *target {
+                            assert_eq!(name, "a".to_string());
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        panic!();
+    }
+
+    #[tokio::test]
+    async fn property_chain_with_if() {
+        let ast = Parser::default()
+            .parse(
+                r#"
+                (if a b else c).d
+                "#,
+            )
+            .unwrap();
+        let line = ast.first().unwrap().clone();
+        if let Node::Expression(Expression::Prop(Prop { target, name, .. })) = line.clone() {
+            assert_eq!(name, "d".to_string());
+            if let Expression::If(If {
+                cond,
+                then,
+                else_: Some(else_),
+                ..
+            }) = *target
+            {
+                if let (
+                    Expression::Identifier(cond),
+                    StatementOrExpression::Expression(Expression::Identifier(then)),
+                    StatementOrExpression::Expression(Expression::Identifier(else_)),
+                ) = (*cond, *then, *else_)
+                {
+                    assert_eq!(cond.name, "a".to_string());
+                    assert_eq!(then.name, "b".to_string());
+                    assert_eq!(else_.name, "c".to_string());
+                    return;
+                }
+            }
+        }
+        panic!();
+    }
+}
+
+mod optional_chain {
+    use super::*;
+
+    #[tokio::test]
+    async fn prop() {
+        test(
+            r#"
+            let a = null
+            let b = { c: 1 }
+            <: [a?.c, b?.c]
+            "#,
+            |res| assert_eq!(res, arr([null(), num(1)])),
+        )
+        .await
         .unwrap();
-        assert_eq!(
-            res,
-            IndexMap::<Option<String>, Option<Value>>::from_iter([(
-                None,
-                Some(obj([("a", (num(1))), ("b", (num(2))), ("c", (num(3)))]))
-            )])
-        );
-        assert_eq!(
-            res.get(&None).cloned(),
-            Some(Some(obj([
-                ("a", (num(1))),
-                ("b", (num(2))),
-                ("c", (num(3)))
-            ])))
+    }
+
+    #[tokio::test]
+    async fn index() {
+        test(
+            r#"
+            let a = null
+            let b = [1, 2]
+            <: [a?.[0], b?.[1]]
+            "#,
+            |res| assert_eq!(res, arr([null(), num(2)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn call() {
+        test(
+            r#"
+            let f = null
+            let g = @(x) { x + 1 }
+            <: [f?.(1), g?.(1)]
+            "#,
+            |res| assert_eq!(res, arr([null(), num(2)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn short_circuits_rest_of_chain() {
+        test(
+            r#"
+            var count = 0
+            @f() { count += 1 }
+            let a = null
+            <: [a?.b.c, a?.b(f()), a?.[f()].d, count]
+            "#,
+            |res| assert_eq!(res, arr([null(), null(), null(), num(0)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn does_not_short_circuit_non_optional_access() {
+        let res = test(
+            r#"
+            let a = { b: null }
+            <: a?.b.c
+            "#,
+            |_| {},
+        )
+        .await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn coalesce() {
+        test(
+            r#"
+            let a = null
+            <: [a ?? 1, 2 ?? 3, false ?? true, a?.b ?? "c"]
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2), bool(false), str("c")])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn coalesce_is_lazy() {
+        test(
+            r#"
+            var count = 0
+            @f() { count += 1 }
+            let a = 1 ?? f()
+            <: [a, count]
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(0)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn coalesce_precedence() {
+        test(
+            r#"
+            <: [null ?? false || true, null ?? null ?? 1 + 1]
+            "#,
+            |res| assert_eq!(res, arr([bool(true), num(2)])),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod template_syntax {
+    use super::*;
+
+    #[tokio::test]
+    async fn basic() {
+        test(
+            r#"
+            let str = "kawaii"
+            <: `Ai is {str}!`
+            "#,
+            |res| assert_eq!(res, str("Ai is kawaii!")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn convert_to_str() {
+        test(
+            r#"
+            <: `1 + 1 = {(1 + 1)}`
+            "#,
+            |res| assert_eq!(res, str("1 + 1 = 2")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid() {
+        test(
+            r#"
+            <: `{hoge}`
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn escape() {
+        test(
+            r#"
+            let message = "Hello"
+            <: `\`a\{b\}c\``
+            "#,
+            |res| assert_eq!(res, str("`a{b}c`")),
         )
+        .await
+        .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn throws_error_when_divided_by_zero() {
+    test(
+        r#"
+        <: (0 / 0)
+        "#,
+        |_| {},
+    )
+    .await
+    .unwrap_err();
+}
+
+mod function_call {
+    use super::*;
+
+    #[tokio::test]
+    async fn without_args() {
+        test(
+            r#"
+            @f() {
+                42
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, num(42)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn with_args() {
+        test(
+            r#"
+            @f(x) {
+                x
+            }
+            <: f(42)
+            "#,
+            |res| assert_eq!(res, num(42)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn with_args_separated_by_comma() {
+        test(
+            r#"
+            @f(x, y) {
+                (x + y)
+            }
+            <: f(1, 1)
+            "#,
+            |res| assert_eq!(res, num(2)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn with_args_separated_by_space() {
+        test(
+            r#"
+            @f(x y) {
+                (x + y)
+            }
+            <: f(1 1)
+            "#,
+            |res| assert_eq!(res, num(2)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn std_throw_aiscript_error_when_required_arg_missing() {
+        let err = test(
+            r#"
+            <: Core:eq(1)
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, AiScriptError::Runtime(_)));
+    }
+
+    #[tokio::test]
+    async fn omitted_args() {
+        test(
+            r#"
+            @f(x, y) {
+                [x, y]
+            }
+            <: f(1)
+            "#,
+            |res| assert_eq!(res, arr([num(1), null()])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn error_has_stack() {
+        let err = test(
+            r#"
+            @f() {
+                Core:abort("hoge")
+            }
+            @g() { f() }
+            g()
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            AiScriptError::Runtime(e) if e.root() == &AiScriptRuntimeError::User("hoge".to_string())
+        ));
+        let stack = err
+            .stack()
+            .iter()
+            .map(|frame| frame.name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(stack, vec![Some("f"), Some("g")]);
+        assert_eq!(
+            err.stack()[1].loc,
+            Some(Loc {
+                start: 107,
+                end: 108
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn error_value_has_stack() {
+        let err = test(
+            r#"
+            let f = @() { [].reduce(@(){}) }
+            (@(){ f() })()
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        let Value { value, .. } = err.to_value();
+        let V::Error { value, info } = value else {
+            panic!("error value expected")
+        };
+        assert_eq!(value, "runtime");
+        let info = info.unwrap();
+        let V::Obj(info) = info.value else {
+            panic!("object expected")
+        };
+        let info = info.read().unwrap();
+        assert_eq!(info.get("message"), Some(&str(err.to_string())));
+        assert_eq!(
+            info.get("stack"),
+            Some(&arr([
+                obj([
+                    ("name", str("f")),
+                    ("loc", obj([("start", num(65)), ("end", num(66))])),
+                ]),
+                obj([
+                    ("name", null()),
+                    ("loc", obj([("start", num(70)), ("end", num(71))])),
+                ]),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn error_has_code() {
+        let err = test(
+            r#"
+            @f(x) { x + 1 }
+            f('a')
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.code(), "type_mismatch");
+        assert!(matches!(
+            &err,
+            AiScriptError::Runtime(e) if e.root() == &AiScriptRuntimeError::Type {
+                expected: "number".to_string(),
+                got: "str".to_string(),
+            }
+        ));
+        assert_eq!(err.loc(), Some(Loc { start: 42, end: 46 }));
+        assert_eq!(
+            err.message(Language::En),
+            "Runtime: Expect number, but got str"
+        );
+        assert_eq!(
+            err.message(Language::Ja),
+            "実行時エラー: number が必要ですが、str が渡されました。"
+        );
+
+        let err = test("hoge", |_| {}).await.unwrap_err();
+        assert_eq!(err.code(), "no_such_variable");
+        assert_eq!(
+            err.message(Language::Ja),
+            "実行時エラー: 変数 'hoge' はスコープ '<root>' に存在しません。"
+        );
+
+        let err = test("let a = 1\na = 2", |_| {}).await.unwrap_err();
+        assert_eq!(err.code(), "assign_to_immutable");
+
+        let err = Parser::default().parse("let a = ").unwrap_err();
+        assert_eq!(err.code(), "parse");
+        assert!(err.loc().is_some());
+    }
+
+    #[tokio::test]
+    async fn error_helpers() {
+        let src = "let a = 1\n@f(x) { x + 1 }\nf('a')";
+        let err = test(src, |_| {}).await.unwrap_err();
+        assert!(err.is_runtime());
+        assert!(!err.is_syntax() && !err.is_internal());
+        assert_eq!(err.label(), "runtime");
+        assert_eq!(err.position(src), Some((3, 2)));
+
+        let src = "let a = 1\nlet b = ";
+        let err = Parser::default().parse(src).unwrap_err();
+        assert!(err.is_syntax());
+        assert_eq!(err.label(), "syntax");
+        let AiScriptError::Syntax(AiScriptSyntaxError::Parse(e)) = &err else {
+            panic!("parse error expected")
+        };
+        assert_eq!(
+            err.position(src),
+            Some((e.location.line, e.location.column))
+        );
+
+        let err = test("[1][2]", |_| {}).await.unwrap_err();
+        assert_eq!(err.label(), "index_out_of_range");
+        assert_eq!(err.position("[1][2]"), None);
+        let Value { value, .. } = err.to_value();
+        assert!(matches!(value, V::Error { value, .. } if value == "index_out_of_range"));
+    }
+
+    #[tokio::test]
+    async fn localized_messages() {
+        let catalog = MessageCatalog::new(Language::Ja).with_message("no_such_variable", |e| {
+            let AiScriptError::Runtime(e) = e else {
+                unreachable!()
+            };
+            let AiScriptRuntimeError::NoSuchVariable { name, .. } = e.root() else {
+                unreachable!()
+            };
+            format!("Variable inconnue : {name}")
+        });
+
+        let err = test("hoge", |_| {}).await.unwrap_err();
+        assert_eq!(
+            err.localized(&catalog).to_string(),
+            "Variable inconnue : hoge"
+        );
+        let err = test("[1][2]", |_| {}).await.unwrap_err();
+        assert_eq!(
+            err.localized(&catalog).to_string(),
+            err.message(Language::Ja)
+        );
+        assert_eq!(
+            err.localized(&MessageCatalog::default()).to_string(),
+            err.to_string()
+        );
+
+        let aiscript = Interpreter::new(
+            [],
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            Some(9999),
+        )
+        .with_messages(catalog);
+        let ast = Parser::default()
+            .parse("Core:try(@() { fuga }).info.message")
+            .unwrap();
+        assert_eq!(
+            aiscript.exec(ast).await.unwrap(),
+            Some(str("Variable inconnue : fuga"))
+        );
+    }
+}
+
+mod return_ {
+    use super::*;
+
+    #[tokio::test]
+    async fn early_return() {
+        test(
+            r#"
+            @f() {
+                if true {
+                    return "ai"
+                }
+
+                "pope"
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn early_return_nested() {
+        test(
+            r#"
+            @f() {
+                if true {
+                    if true {
+                        return "ai"
+                    }
+                }
+
+                "pope"
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn early_return_nested_2() {
+        test(
+            r#"
+            @f() {
+                if true {
+                    return "ai"
+                }
+
+                "pope"
+            }
+
+            @g() {
+                if (f() == "ai") {
+                    return "kawaii"
+                }
+
+                "pope"
+            }
+
+            <: g()
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn early_return_without_block() {
+        test(
+            r#"
+            @f() {
+                if true return "ai"
+
+                "pope"
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn return_inside_for() {
+        test(
+            r#"
+            @f() {
+                var count = 0
+                for (let i, 100) {
+                    count += 1
+                    if (i == 42) {
+                        return count
+                    }
+                }
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, num(43)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn return_inside_for_2() {
+        test(
+            r#"
+            @f() {
+                for (let i, 10) {
+                    return 1
+                }
+                2
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, num(1)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn return_inside_loop() {
+        test(
+            r#"
+            @f() {
+                var count = 0
+                loop {
+                    count += 1
+                    if (count == 42) {
+                        return count
+                    }
+                }
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, num(42)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn return_inside_loop_2() {
+        test(
+            r#"
+            @f() {
+                loop {
+                    return 1
+                }
+                2
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, num(1)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn return_inside_each() {
+        test(
+            r#"
+            @f() {
+                var count = 0
+                each (let item, ["ai", "chan", "kawaii"]) {
+                    count += 1
+                    if (item == "chan") {
+                        return count
+                    }
+                }
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, num(2)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn return_inside_each_2() {
+        test(
+            r#"
+            @f() {
+                each (let item, ["ai", "chan", "kawaii"]) {
+                    return 1
+                }
+                2
+            }
+            <: f()
+            "#,
+            |res| assert_eq!(res, num(1)),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod eval {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_value() {
+        test(
+            r#"
+            let foo = eval {
+                let a = 1
+                let b = 2
+                (a + b)
+            }
+
+            <: foo
+            "#,
+            |res| assert_eq!(res, num(3)),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod exists {
+    use super::*;
+
+    #[tokio::test]
+    async fn basic() {
+        test(
+            r#"
+            let foo = null
+            <: [(exists foo) (exists bar)]
+            "#,
+            |res| assert_eq!(res, arr([bool(true), bool(false)])),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod if_ {
+    use super::*;
+
+    #[tokio::test]
+    async fn if_() {
+        test(
+            r#"
+            var msg = "ai"
+            if true {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+
+        test(
+            r#"
+            var msg = "ai"
+            if false {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn else_() {
+        test(
+            r#"
+            var msg = null
+            if true {
+                msg = "ai"
+            } else {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+
+        test(
+            r#"
+            var msg = null
+            if false {
+                msg = "ai"
+            } else {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn elif() {
+        test(
+            r#"
+            var msg = "bebeyo"
+            if false {
+                msg = "ai"
+            } elif true {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+
+        test(
+            r#"
+            var msg = "bebeyo"
+            if false {
+                msg = "ai"
+            } elif false {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("bebeyo")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn if_elif_else() {
+        test(
+            r#"
+            var msg = null
+            if false {
+                msg = "ai"
+            } elif true {
+                msg = "chan"
+            } else {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("chan")),
+        )
+        .await
+        .unwrap();
+
+        test(
+            r#"
+            var msg = null
+            if false {
+                msg = "ai"
+            } elif false {
+                msg = "chan"
+            } else {
+                msg = "kawaii"
+            }
+            <: msg
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn expr() {
+        test(
+            r#"
+            <: if true "ai" else "kawaii"
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+
+        test(
+            r#"
+            <: if false "ai" else "kawaii"
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod match_ {
+    use super::*;
+
+    #[tokio::test]
+    async fn basic() {
+        test(
+            r#"
+            <: match 2 {
+                1 => "a"
+                2 => "b"
+                3 => "c"
+            }
+            "#,
+            |res| assert_eq!(res, str("b")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn when_default_not_provided_returns_null() {
+        test(
+            r#"
+            <: match 42 {
+                1 => "a"
+                2 => "b"
+                3 => "c"
+            }
+            "#,
+            |res| assert_eq!(res, null()),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn with_default() {
+        test(
+            r#"
+            <: match 42 {
+                1 => "a"
+                2 => "b"
+                3 => "c"
+                * => "d"
+            }
+            "#,
+            |res| assert_eq!(res, str("d")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn with_block() {
+        test(
+            r#"
+            <: match 2 {
+                1 => 1
+                2 => {
+                    let a = 1
+                    let b = 2
+                    (a + b)
+                }
+                3 => 3
+            }
+            "#,
+            |res| assert_eq!(res, num(3)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn with_return() {
+        test(
+            r#"
+            @f(x) {
+                match x {
+                    1 => {
+                        return "ai"
+                    }
+                }
+                "foo"
+            }
+            <: f(1)
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn destructuring() {
+        test(
+            r#"
+            @f(x) {
+                match x {
+                    case [] => "empty"
+                    case [a, b] => `pair {a} {b}`
+                    case { type: "point", pos: [x, y] } => `point {x} {y}`
+                    case { type: "user", name: name } => `user {name}`
+                    case [1, [c]] => `nested {c}`
+                    * => "other"
+                }
+            }
+            <: [
+                f([])
+                f([1, "a"])
+                f({ type: "point", pos: [2, 3], extra: true })
+                f({ type: "user", name: "ai" })
+                f({ type: "user" })
+                f([1, [4], 5])
+                f(1)
+            ]
+            "#,
+            |res| {
+                assert_eq!(
+                    res,
+                    arr([
+                        str("empty"),
+                        str("pair 1 a"),
+                        str("point 2 3"),
+                        str("user ai"),
+                        str("other"),
+                        str("other"),
+                        str("other"),
+                    ])
+                )
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn destructuring_scope() {
+        test(
+            r#"
+            let a = "outer"
+            let r = match [1] {
+                case [a] => a
+            }
+            <: [r, a]
+            "#,
+            |res| assert_eq!(res, arr([num(1), str("outer")])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn destructuring_reserved_word() {
+        Parser::default()
+            .parse("match 1 { case [if] => 1 }")
+            .unwrap_err();
+    }
+}
+
+mod loop_ {
+    use super::*;
+
+    #[tokio::test]
+    async fn basic() {
+        test(
+            r#"
+            var count = 0
+            loop {
+                if (count == 10) break
+                count = (count + 1)
+            }
+            <: count
+            "#,
+            |res| assert_eq!(res, num(10)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn with_continue() {
+        test(
+            r#"
+            var a = ["ai" "chan" "kawaii" "yo" "!"]
+            var b = []
+            loop {
+                var x = a.shift()
+                if (x == "chan") continue
+                if (x == "yo") break
+                b.push(x)
+            }
+            <: b
+            "#,
+            |res| assert_eq!(res, arr([str("ai"), str("kawaii")])),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod while_ {
+    use super::*;
+
+    async fn exec(src: &str) -> Result<Value, AiScriptError> {
+        let ast = Parser::default().with_while_loops(true).parse(src)?;
+        let aiscript = Interpreter::new(
+            [],
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            None::<fn(_) -> _>,
+            Some(9999),
+        );
+        aiscript.exec(ast).await.map(Option::unwrap)
+    }
+
+    #[tokio::test]
+    async fn basic() {
+        let res = exec(
+            r#"
+            var count = 0
+            while count < 42 {
+                count += 1
+            }
+            count
+            "#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res, num(42));
+    }
+
+    #[tokio::test]
+    async fn with_continue() {
+        let res = exec(
+            r#"
+            var a = ["ai" "chan" "kawaii" "yo" "!"]
+            var b = []
+            while a.len != 0 {
+                var x = a.shift()
+                if (x == "chan") continue
+                if (x == "yo") break
+                b.push(x)
+            }
+            b
+            "#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res, arr([str("ai"), str("kawaii")]));
+    }
+
+    #[tokio::test]
+    async fn do_while() {
+        let res = exec(
+            r#"
+            var count = 0
+            do {
+                count += 1
+            } while false
+            var statement = 0
+            do statement += 1 while statement < 3
+            [count, statement]
+            "#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res, arr([num(1), num(3)]));
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        Parser::default()
+            .parse("var i = 0\nwhile i < 3 { i += 1 }")
+            .unwrap_err();
+        Parser::default()
+            .parse("var i = 0\ndo { i += 1 } while i < 3")
+            .unwrap_err();
+    }
+}
+
+mod for_ {
+    use super::*;
+
+    #[tokio::test]
+    async fn basic() {
+        test(
+            r#"
+            var count = 0
+            for (let i, 10) {
+                count += i + 1
+            }
+            <: count
+            "#,
+            |res| assert_eq!(res, num(55)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn initial_value() {
+        test(
+            r#"
+            var count = 0
+            for (let i = 2, 10) {
+                count += i
+            }
+            <: count
+            "#,
+            |res| assert_eq!(res, num(65)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn without_iterator() {
+        test(
+            r#"
+            var count = 0
+            for (10) {
+                count = (count + 1)
+            }
+            <: count
+            "#,
+            |res| assert_eq!(res, num(10)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn without_brackets() {
+        test(
+            r#"
+            var count = 0
+            for let i, 10 {
+                count = (count + i)
+            }
+            <: count
+            "#,
+            |res| assert_eq!(res, num(45)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn break_() {
+        test(
+            r#"
+            var count = 0
+            for (let i, 20) {
+                if (i == 11) break
+                count += i
+            }
+            <: count
+            "#,
+            |res| assert_eq!(res, num(55)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn continue_() {
+        test(
+            r#"
+            var count = 0
+            for (let i, 10) {
+                if (i == 5) continue
+                count = (count + 1)
+            }
+            <: count
+            "#,
+            |res| assert_eq!(res, num(9)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn single_statement() {
+        test(
+            r#"
+            var count = 0
+            for 10 count += 1
+            <: count
+            "#,
+            |res| assert_eq!(res, num(10)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn var_name_without_space() {
+        test(
+            r#"
+            for (leti, 10) {
+                <: i
+            }
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+    }
+}
+
+mod for_of {
+    use super::*;
+
+    #[tokio::test]
+    async fn standard() {
+        test(
+            r#"
+            let msgs = []
+            each let item, ["ai", "chan", "kawaii"] {
+                msgs.push([item, "!"].join())
+            }
+            <: msgs
+            "#,
+            |res| assert_eq!(res, arr([str("ai!"), str("chan!"), str("kawaii!")])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn break_() {
+        test(
+            r#"
+            let msgs = []
+            each let item, ["ai", "chan", "kawaii" "yo"] {
+                if (item == "kawaii") break
+                msgs.push([item, "!"].join())
+            }
+            <: msgs
+            "#,
+            |res| assert_eq!(res, arr([str("ai!"), str("chan!")])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn single_statement() {
+        test(
+            r#"
+            let msgs = []
+            each let item, ["ai", "chan", "kawaii"] msgs.push([item, "!"].join())
+            <: msgs
+            "#,
+            |res| assert_eq!(res, arr([str("ai!"), str("chan!"), str("kawaii!")])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn var_name_without_space() {
+        test(
+            r#"
+            each letitem, ["ai", "chan", "kawaii"] {
+                <: item
+            }
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn iterator_fn() {
+        test(
+            r#"
+            var i = 0
+            let next = @() {
+                i += 1
+                if i > 5 { return { done: true } }
+                { value: i }
+            }
+            let msgs = []
+            each let item, next {
+                if item == 2 { continue }
+                if item == 4 { break }
+                msgs.push(item)
+            }
+            <: msgs
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(3)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn not_iterable() {
+        test("each let item, 1 {}", |_| {}).await.unwrap_err();
+        test("each let item, @() { 1 } {}", |_| {})
+            .await
+            .unwrap_err();
+    }
+}
+
+mod generator {
+    use super::*;
+
+    async fn test(program: &str, test: fn(Value)) -> Result<Value, AiScriptError> {
+        let ast = Parser::default().with_generators(true).parse(program)?;
+        let aiscript = Interpreter::new(
+            [],
+            None::<fn(_) -> _>,
+            Some(move |value| {
+                test(value);
+                async move {}.boxed()
+            }),
+            None::<fn(_) -> _>,
+            Some(9999),
+        );
+        aiscript.exec(ast).await.map(|value| value.unwrap())
+    }
+
+    #[tokio::test]
+    async fn each() {
+        test(
+            r#"
+            @gen*() {
+                yield 1
+                yield 2
+            }
+            var result = []
+            each let x, gen() {
+                result.push(x)
+            }
+            <: result
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn arr_from() {
+        test(
+            r#"
+            @range*(n) {
+                for (let i, n) {
+                    yield i
+                }
+            }
+            <: Arr:from(range(3))
+            "#,
+            |res| assert_eq!(res, arr([num(0), num(1), num(2)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn is_lazy() {
+        test(
+            r#"
+            var count = 0
+            let naturals = @*() {
+                var i = 0
+                loop {
+                    count += 1
+                    yield i
+                    i += 1
+                }
+            }
+            var result = []
+            each let x, naturals() {
+                if x == 3 break
+                result.push(x)
+            }
+            <: [result, count]
+            "#,
+            |res| assert_eq!(res, arr([arr([num(0), num(1), num(2)]), num(4)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn return_ends_generator() {
+        test(
+            r#"
+            @gen*() {
+                yield 1
+                return 2
+                yield 3
+            }
+            <: Arr:from(gen())
+            "#,
+            |res| assert_eq!(res, arr([num(1)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn type_() {
+        test(
+            r#"
+            @gen*() { yield 1 }
+            <: [Core:type(gen), Core:type(gen())]
+            "#,
+            |res| assert_eq!(res, arr([str("fn"), str("generator")])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn yield_outside_generator() {
+        test(
+            r#"
+            @f() { yield 1 }
+            f()
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn yield_in_function_called_by_generator() {
+        test(
+            r#"
+            @f() { yield 1 }
+            @gen*() { f() }
+            Arr:from(gen())
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn yield_is_reserved_word() {
+        let err = test(
+            r#"
+            let yield = 1
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            AiScriptError::Syntax(AiScriptSyntaxError::ReservedWord(name)) if name == "yield"
+        ));
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        super::test(
+            r#"
+            @yield(x) { x + 1 }
+            let y = yield(1)
+            <: { yield: y }.yield
+            "#,
+            |res| assert_eq!(res, num(2)),
+        )
+        .await
+        .unwrap();
+        for src in ["@gen*() { 1 }", "let gen = @*() { 1 }", "@f() { yield 1 }"] {
+            let err = Parser::default().parse(src).unwrap_err();
+            assert!(err.is_syntax(), "{src}");
+        }
+    }
+}
+
+mod not {
+    use super::*;
+
+    #[tokio::test]
+    async fn basic() {
+        test(
+            r#"
+            <: !true
+            "#,
+            |res| assert_eq!(res, bool(false)),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod namespace {
+    use super::*;
+
+    #[tokio::test]
+    async fn standard() {
+        test(
+            r#"
+            <: Foo:bar()
+
+            :: Foo {
+                @bar() { "ai" }
+            }
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn self_ref() {
+        test(
+            r#"
+            <: Foo:bar()
+
+            :: Foo {
+                let ai = "kawaii"
+                @bar() { ai }
+            }
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cannot_declare_mutable_variable() {
+        test(
+            r#"
+            :: Foo {
+                var ai = "kawaii"
+            }
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        test(
+            r#"
+            <: Foo:Bar:baz()
+
+            :: Foo {
+                :: Bar {
+                    @baz() { "ai" }
+                }
+            }
+            "#,
+            |res| assert_eq!(res, str("ai")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn nested_ref() {
+        test(
+            r#"
+            <: Foo:baz
+
+            :: Foo {
+                let baz = Bar:ai
+                :: Bar {
+                    let ai = "kawaii"
+                }
+            }
+            "#,
+            |res| assert_eq!(res, str("kawaii")),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod literal {
+    use super::*;
+
+    #[tokio::test]
+    async fn string_single_quote() {
+        test(
+            r#"
+            <: 'foo'
+            "#,
+            |res| assert_eq!(res, str("foo")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn string_double_quote() {
+        test(
+            r#"
+            <: "foo"
+            "#,
+            |res| assert_eq!(res, str("foo")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn escaped_double_quote() {
+        test(
+            r#"
+            <: "ai saw a note \"bebeyo\"."
+            "#,
+            |res| assert_eq!(res, str(r#"ai saw a note "bebeyo"."#)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn escaped_single_quote() {
+        test(
+            r#"
+            <: 'ai saw a note \'bebeyo\'.'
+            "#,
+            |res| assert_eq!(res, str("ai saw a note 'bebeyo'.")),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn bool_true() {
+        test(
+            r#"
+            <: true
+            "#,
+            |res| assert_eq!(res, bool(true)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn bool_false() {
+        test(
+            r#"
+            <: false
+            "#,
+            |res| assert_eq!(res, bool(false)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_int() {
+        test(
+            r#"
+            <: 10
+            "#,
+            |res| assert_eq!(res, num(10)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_float() {
+        test(
+            r#"
+            <: 0.5
+            "#,
+            |res| assert_eq!(res, num(0.5)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_hex() {
+        test(
+            r#"
+            <: [0xff, 0XFF, 0x0, -0x10, 0xdead_beef, 0x1fffffffffffff]
+            "#,
+            |res| {
+                assert_eq!(
+                    res,
+                    arr([
+                        num(255),
+                        num(255),
+                        num(0),
+                        num(-16),
+                        num(3735928559u32),
+                        num(9007199254740991.0),
+                    ])
+                )
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_binary() {
+        test(
+            r#"
+            <: [0b1010, 0B1, -0b11, 0b1111_0000]
+            "#,
+            |res| assert_eq!(res, arr([num(10), num(1), num(-3), num(240)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_underscore_separator() {
+        test(
+            r#"
+            <: [1_000_000, 0.000_1, 1_0.5_5]
+            "#,
+            |res| assert_eq!(res, arr([num(1000000), num(0.0001), num(10.55)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_exponent() {
+        test(
+            r#"
+            <: [1e6, 2E3, 1.5e-3, -2.5e+2, 0e5, 1_0e1_0]
+            "#,
+            |res| {
+                assert_eq!(
+                    res,
+                    arr([
+                        num(1000000),
+                        num(2000),
+                        num(0.0015),
+                        num(-250),
+                        num(0),
+                        num(1e11),
+                    ])
+                )
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn number_rounded_to_nearest() {
+        test(
+            r#"
+            <: [0x20000000000001, 9007199254740993, 0.1e1]
+            "#,
+            |res| {
+                assert_eq!(
+                    res,
+                    arr([num(9007199254740992.0), num(9007199254740992.0), num(1)])
+                )
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_number_literals() {
+        for src in [
+            "<: 1__000",
+            "<: 1_",
+            "<: 0x",
+            "<: 0b12",
+            "<: 0x_f",
+            "<: 1e",
+            "<: 01",
+            "<: 0x1_0000_0000_0000_0000_0000_0000_0000_0000",
+        ] {
+            assert!(Parser::default().parse(src).is_err(), "{src}");
+        }
+    }
+
+    #[tokio::test]
+    async fn arr_separated_by_comma() {
+        test(
+            r#"
+            <: [1, 2, 3]
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn arr_separated_by_comma_with_trailing_comma() {
+        test(
+            r#"
+            <: [1, 2, 3,]
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn arr_separated_by_line_break() {
+        test(
+            r#"
+            <: [
+                1
+                2
+                3
+            ]
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn arr_separated_by_line_break_and_comma() {
+        test(
+            r#"
+            <: [
+                1,
+                2,
+                3
+            ]
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn arr_separated_by_line_break_and_comma_with_trailing_comma() {
+        test(
+            r#"
+            <: [
+                1,
+                2,
+                3,
+            ]
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2), num(3)])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_separated_by_comma() {
+        test(
+            r#"
+            <: { a: 1, b: 2, c: 3 }
+            "#,
+            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_separated_by_comma_with_trailing_comma() {
+        test(
+            r#"
+            <: { a: 1, b: 2, c: 3, }
+            "#,
+            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_separated_by_semicolon() {
+        test(
+            r#"
+            <: { a: 1; b: 2; c: 3 }
+            "#,
+            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_separated_by_semicolon_with_trailing_semicolon() {
+        test(
+            r#"
+            <: { a: 1; b: 2; c: 3; }
+            "#,
+            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_separated_by_line_break() {
+        test(
+            r#"
+            <: {
+                a: 1
+                b: 2
+                c: 3
+            }
+            "#,
+            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_separated_by_line_break_and_semicolon() {
+        test(
+            r#"
+            <: {
+                a: 1;
+                b: 2;
+                c: 3
+            }
+            "#,
+            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_separated_by_line_break_and_semicolon_with_trailing_semicolon() {
+        test(
+            r#"
+            <: {
+                a: 1;
+                b: 2;
+                c: 3;
+            }
+            "#,
+            |res| assert_eq!(res, obj([("a", num(1)), ("b", num(2)), ("c", num(3))])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn obj_and_arr_separated_by_line_break() {
+        test(
+            r#"
+            <: {
+                a: 1
+                b: [
+                    1
+                    2
+                    3
+                ]
+                c: 3
+            }
+            "#,
+            |res| {
+                assert_eq!(
+                    res,
+                    obj([
+                        ("a", num(1)),
+                        ("b", arr([num(1), num(2), num(3)])),
+                        ("c", num(3))
+                    ])
+                )
+            },
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod type_declaration {
+    use super::*;
+
+    #[tokio::test]
+    async fn def() {
+        test(
+            r#"
+            let abc: num = 1
+            var xyz: str = "abc"
+            <: [abc xyz]
+            "#,
+            |res| assert_eq!(res, arr([num(1), str("abc")])),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fn_def() {
+        test(
+            r#"
+            @f(x: arr<num>, y: str, z: @(num) => bool): arr<num> {
+                x.push(0)
+                y = "abc"
+                var r: bool = z(x[0])
+                x.push(if r 5 else 10)
+                x
+            }
+
+            <: f([1, 2, 3], "a", @(n) { n == 1 })
+            "#,
+            |res| assert_eq!(res, arr([num(1), num(2), num(3), num(0), num(5)])),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod meta {
+    use super::*;
+
+    #[test]
+    fn default_meta() {
+        let res = get_meta(
+            r#"
+            ### { a: 1; b: 2; c: 3; }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            IndexMap::<Option<String>, Option<Value>>::from_iter([(
+                None,
+                Some(obj([("a", (num(1))), ("b", (num(2))), ("c", (num(3)))]))
+            )])
+        );
+        assert_eq!(
+            res.get(&None).cloned(),
+            Some(Some(obj([
+                ("a", (num(1))),
+                ("b", (num(2))),
+                ("c", (num(3)))
+            ])))
+        )
+    }
+
+    mod string {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            let res = get_meta(
+                r#"
+                ### x "hoge"
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter([(
+                    Some("x".to_string()),
+                    Some(str("hoge"))
+                )])
+            );
+        }
+    }
+
+    mod number {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            let res = get_meta(
+                r#"
+                ### x 42
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter([(
+                    Some("x".to_string()),
+                    Some(num(42))
+                )])
+            );
+        }
+    }
+
+    mod boolean {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            let res = get_meta(
+                r#"
+                ### x true
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter([(
+                    Some("x".to_string()),
+                    Some(bool(true))
+                )])
+            );
+        }
+    }
+
+    mod null {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            let res = get_meta(
+                r#"
+                ### x null
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter([(
+                    Some("x".to_string()),
+                    Some(null())
+                )])
+            );
+        }
+    }
+
+    mod array {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            let res = get_meta(
+                r#"
+                ### x [1 2 3]
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter([(
+                    Some("x".to_string()),
+                    Some(arr([num(1), num(2), num(3)]))
+                )])
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            get_meta(
+                r#"
+                ### x [1 (2 + 2) 3]
+                "#,
+            )
+            .unwrap_err();
+        }
+    }
+
+    mod object {
+        use super::*;
+
+        #[test]
+        fn valid() {
+            let res = get_meta(
+                r#"
+                ### x { a: 1; b: 2; c: 3; }
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter([(
+                    Some("x".to_string()),
+                    Some(obj([("a", num(1)), ("b", num(2)), ("c", num(3))]))
+                )])
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            get_meta(
+                r#"
+                ### x { a: 1; b: (2 + 2); c: 3; }
+                "#,
+            )
+            .unwrap_err();
+        }
+    }
+
+    mod template {
+        use super::*;
+
+        #[test]
+        fn invalid() {
+            get_meta(
+                r#"
+                ### x `foo {bar} baz`
+                "#,
+            )
+            .unwrap_err();
+        }
+    }
+
+    mod expression {
+        use super::*;
+
+        #[test]
+        fn invalid() {
+            get_meta(
+                r#"
+                ### x (1 + 1)
+                "#,
+            )
+            .unwrap_err();
+        }
+    }
+
+    mod constant {
+        use super::*;
+
+        fn get_const_meta(
+            program: &str,
+        ) -> Result<IndexMap<Option<String>, Option<Value>>, AiScriptError> {
+            let ast = Parser::default().with_const_metadata(true).parse(program)?;
+            Ok(Interpreter::collect_metadata(ast))
+        }
+
+        #[test]
+        fn valid() {
+            let res = get_const_meta(
+                r#"
+                ### x (1 + 1)
+                ### y { size: 2 * 1024; label: `v{1 + 2}.{0}`; big: 2 ^ 10 >= 1000 && !false }
+                ### z [1, null ?? "default", 7 % 4, 1 == 1, "a" != 1]
+                ### { name: "literal" }
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter([
+                    (Some("x".to_string()), Some(num(2))),
+                    (
+                        Some("y".to_string()),
+                        Some(obj([
+                            ("size", num(2048)),
+                            ("label", str("v3.0")),
+                            ("big", bool(true)),
+                        ]))
+                    ),
+                    (
+                        Some("z".to_string()),
+                        Some(arr([
+                            num(1),
+                            str("default"),
+                            num(3),
+                            bool(true),
+                            bool(true),
+                        ]))
+                    ),
+                    (None, Some(obj([("name", str("literal"))]))),
+                ])
+            );
+        }
+
+        #[test]
+        fn not_constant() {
+            let res = get_const_meta(
+                r#"
+                ### a Core:v
+                ### b [1, Core:to_str(1)]
+                ### c 0 / 0
+                ### d 1 + "a"
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                res,
+                IndexMap::<Option<String>, Option<Value>>::from_iter(
+                    ["a", "b", "c", "d"].map(|name| (Some(name.to_string()), None))
+                )
+            );
+        }
+    }
+}
+
+mod lang_version {
+    use super::*;
+
+    #[test]
+    fn number() {
+        let res = utils::get_lang_version(
+            r#"
+            /// @2021
+            @f(x) {
+                x
+            }
+            "#,
+        );
+        assert_eq!(res, Some("2021".to_string()));
+    }
+
+    #[test]
+    fn chars() {
+        let res = utils::get_lang_version(
+            r#"
+            /// @ canary
+            const a = 1
+            @f(x) {
+                x
+            }
+            f(a)
+            "#,
+        );
+        assert_eq!(res, Some("canary".to_string()));
+    }
+
+    #[test]
+    fn complex() {
+        let res = utils::get_lang_version(
+            r#"
+            /// @ 2.0-Alpha
+            @f(x) {
+                x
+            }
+            "#,
+        );
+        assert_eq!(res, Some("2.0-Alpha".to_string()));
+    }
+
+    #[test]
+    fn no_specified() {
+        let res = utils::get_lang_version(
+            r#"
+            @f(x) {
+                x
+            }
+            "#,
+        );
+        assert_eq!(res, None);
+    }
+}
+
+mod attribute {
+    use super::*;
+
+    #[test]
+    fn single_attribute_with_function_str() {
+        let nodes = Parser::default()
+            .parse(
+                r#"
+                #[Event "Received"]
+                @onReceived(data) {
+                    data
+                }
+                "#,
+            )
+            .unwrap();
+        if let [Node::Statement(Statement::Definition(Definition { name, attr, .. }))] = &nodes[..]
+        {
+            assert_eq!(name, "onReceived");
+            if let Some(attr) = attr {
+                if let [Attribute {
+                    name,
+                    value: Expression::Str(Str { value, .. }),
+                    ..
+                }] = &attr[..]
+                {
+                    assert_eq!(name, "Event");
+                    assert_eq!(value, "Received");
+                    return;
+                }
+            }
+        }
+        panic!();
+    }
+
+    #[test]
+    fn multiple_attributes_with_function_obj_str_bool() {
+        let nodes = Parser::default()
+            .parse(
+                r#"
+                #[Endpoint { path: "/notes/create"; }]
+                #[Desc "Create a note."]
+                #[Cat true]
+                @createNote(text) {
+                    <: text
+                }
+                "#,
+            )
+            .unwrap();
+        if let [Node::Statement(Statement::Definition(Definition { name, attr, .. }))] = &nodes[..]
+        {
+            assert_eq!(name, "createNote");
+            if let Some(attr) = attr {
+                if let [Attribute {
+                    name: name1,
+                    value: Expression::Obj(Obj { value: value1, .. }),
+                    ..
+                }, Attribute {
+                    name: name2,
+                    value: Expression::Str(Str { value: value2, .. }),
+                    ..
+                }, Attribute {
+                    name: name3,
+                    value: Expression::Bool(Bool { value: true, .. }),
+                    ..
+                }] = &attr[..]
+                {
+                    assert_eq!(name1, "Endpoint");
+                    assert_eq!(name2, "Desc");
+                    assert_eq!(value2, "Create a note.");
+                    assert_eq!(name3, "Cat");
+                    if let [(key, Expression::Str(Str { value, .. }))] =
+                        value1.iter().collect::<Vec<(&String, &Expression)>>()[..]
+                    {
+                        assert_eq!(key, "path");
+                        assert_eq!(value, "/notes/create");
+                        return;
+                    }
+                }
+            }
+        }
+        panic!();
+    }
+
+    #[test]
+    fn single_attribute_no_value() {
+        let nodes = Parser::default()
+            .parse(
+                r#"
+                #[serializable]
+                let data = 1
+                "#,
+            )
+            .unwrap();
+        if let [Node::Statement(Statement::Definition(Definition { name, attr, .. }))] = &nodes[..]
+        {
+            assert_eq!(name, "data");
+            if let Some(attr) = attr {
+                if let [Attribute {
+                    name,
+                    value: Expression::Bool { .. },
+                    ..
+                }] = &attr[..]
+                {
+                    assert_eq!(name, "serializable");
+                    return;
+                }
+            }
+        }
+        panic!();
+    }
+}
+
+mod docs {
+    use super::*;
+    use aiscript_v0::{
+        docs::{DocItem, DocKind, DocParam},
+        values::Attr,
+    };
+
+    #[test]
+    fn extract() {
+        let docs = Parser::default()
+            .extract_docs(
+                r#"
+/// Greets someone.
+/// Returns the greeting.
+#[deprecated]
+#[since '1.0']
+@greet(name: str, times): str {
+    `Hello, {name}`
+}
+
+// Not a doc comment.
+let count: num = 0
+
+:: Lib {
+    /**
+     * The version.
+     */
+    let version = '1.0'
+
+    :: Util {
+        var state = null
+    }
+}
+
+greet('world')
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            docs.iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>(),
+            ["greet", "count", "Lib:version", "Lib:Util:state"]
+        );
+        assert_eq!(
+            docs[0],
+            DocItem {
+                name: "greet".to_string(),
+                kind: DocKind::Fn,
+                params: vec![
+                    DocParam {
+                        name: "name".to_string(),
+                        type_: Some("str".to_string()),
+                    },
+                    DocParam {
+                        name: "times".to_string(),
+                        type_: None,
+                    },
+                ],
+                type_: Some("str".to_string()),
+                doc: Some("Greets someone.\nReturns the greeting.".to_string()),
+                attr: vec![
+                    Attr {
+                        name: "deprecated".to_string(),
+                        value: bool(true),
+                    },
+                    Attr {
+                        name: "since".to_string(),
+                        value: str("1.0"),
+                    },
+                ],
+                loc: docs[0].loc.clone(),
+            }
+        );
+        assert_eq!(docs[1].kind, DocKind::Const);
+        assert_eq!(docs[1].type_, Some("num".to_string()));
+        assert_eq!(docs[1].doc, None);
+        assert_eq!(docs[2].doc, Some("The version.".to_string()));
+        assert_eq!(docs[3].kind, DocKind::Var);
+        assert_eq!(docs[3].doc, None);
+    }
+
+    #[test]
+    fn syntax_error() {
+        assert!(Parser::default().extract_docs("let x = ").is_err());
+    }
+}
+
+mod tokenize {
+    use super::*;
+    use aiscript_v0::tokens::TokenKind;
+
+    fn tokens(input: &str) -> Vec<(TokenKind, String)> {
+        Parser::default()
+            .tokenize(input)
+            .into_iter()
+            .filter(|token| token.kind != TokenKind::Whitespace)
+            .map(|token| (token.kind, token.text))
+            .collect()
+    }
+
+    #[test]
+    fn kinds() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens("let x:num = Core:to_str(0x1f + 1.5e3) // comment\n<: 'it\\'s' != null"),
+            [
+                (Keyword, "let"),
+                (Identifier, "x"),
+                (Operator, ":"),
+                (Identifier, "num"),
+                (Operator, "="),
+                (Identifier, "Core:to_str"),
+                (Operator, "("),
+                (Num, "0x1f"),
+                (Operator, "+"),
+                (Num, "1.5e3"),
+                (Operator, ")"),
+                (Comment, "// comment"),
+                (LineBreak, "\n"),
+                (Operator, "<:"),
+                (Str, "'it\\'s'"),
+                (Operator, "!="),
+                (Keyword, "null"),
+            ]
+            .map(|(kind, text)| (kind, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn template() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens("`a{ {b: `c{d}`}.b }\\{e`"),
+            [
+                (Tmpl, "`a"),
+                (Operator, "{"),
+                (Operator, "{"),
+                (Identifier, "b"),
+                (Operator, ":"),
+                (Tmpl, "`c"),
+                (Operator, "{"),
+                (Identifier, "d"),
+                (Operator, "}"),
+                (Tmpl, "`"),
+                (Operator, "}"),
+                (Operator, "."),
+                (Identifier, "b"),
+                (Operator, "}"),
+                (Tmpl, "\\{e`"),
+            ]
+            .map(|(kind, text)| (kind, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn lossless() {
+        let input =
+            "### { name: 'x' }\r\n#[attr]\n@f(a) {\n  /* block\n */ `{a}` ?? \"unterminated";
+        let tokens = Parser::default().tokenize(input);
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect::<String>(),
+            input
+        );
+        for token in tokens {
+            assert_eq!(&input[token.loc.start..=token.loc.end], token.text);
+        }
+    }
+}
+
+mod location {
+    use super::*;
+
+    #[test]
+    fn function() {
+        let nodes = Parser::default()
+            .parse(
+                r#"
+		@f(a) { a }
+                "#,
+            )
+            .unwrap();
+        if let [Node::Statement(Statement::Definition(Definition {
+            loc: Some(Loc { start, end }),
+            ..
+        }))] = &nodes[..]
+        {
+            assert_eq!(start.clone(), 3);
+            assert_eq!(end.clone(), 13);
+            return;
+        }
+        panic!();
+    }
+
+    #[test]
+    fn comment() {
+        let nodes = Parser::default()
+            .parse(
+                r#"
+		/*
+		*/
+		// hoge
+		@f(a) { a }
+                "#,
+            )
+            .unwrap();
+        if let [Node::Statement(Statement::Definition(Definition {
+            loc: Some(Loc { start, end }),
+            ..
+        }))] = &nodes[..]
+        {
+            assert_eq!(start.clone(), 23);
+            assert_eq!(end.clone(), 33);
+            return;
+        }
+        panic!();
+    }
+}
+
+mod to_source {
+    use super::*;
+
+    fn assert_roundtrip(script: &[Node]) {
+        let source = to_source(script);
+        let reparsed = Parser::default()
+            .with_generators(true)
+            .parse(&source)
+            .unwrap_or_else(|e| panic!("{e}\n{source}"));
+        assert_eq!(to_source(&reparsed), source);
+    }
+
+    #[test]
+    fn statements() {
+        let script = Parser::default()
+            .with_generators(true)
+            .parse(
+                r#"
+                ### Meta { name: "a", tags: ["b", 1.5] }
+                :: Ns {
+                    let x = 1
+                    @f(a: num): str { `{a}` }
+                }
+                #[since 1]
+                var a: arr<num> = [1, 2]
+                @g(x, y) {
+                    each (let v, x) { a += v }
+                    for (let i = 1, 3) { continue }
+                    for (2) { break }
+                    loop { return x && !y || false }
+                    a[0] = { b: null }.b
+                    a -= if exists x { 1 } elif y { 2 } else { 3 }
+                }
+                let t = `\{{match a { 1 => "x'y" * => ({}) }}\}`
+                let m = match a { case [x, { k: "v", n: null }] => x case {} => 0 }
+                <: (@() { eval { 1 } })()
+                <: a?.b?.[0]?.(1).c ?? null ?? 2
+                @gen*(x) { yield x }
+                "#,
+            )
+            .unwrap();
+        assert_roundtrip(&script);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn generated() {
+        use ::arbitrary::{Arbitrary, Unstructured};
+        use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let mut bytes = vec![0; 1024];
+            rng.fill_bytes(&mut bytes);
+            let script = ArbitraryScript::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            assert_roundtrip(&script.0);
+        }
+    }
+}
+
+mod plugin {
+    use aiscript_v0::{
+        cst,
+        diagnostics::{Diagnostic, JumpKind, ScopeInfo},
+        PluginType,
+    };
+
+    use super::*;
+
+    struct ReplaceAnswer;
+
+    impl cst::Visitor for ReplaceAnswer {
+        fn callback_expression(
+            &self,
+            expression: cst::Expression,
+        ) -> Result<cst::Expression, AiScriptError> {
+            Ok(match expression {
+                cst::Expression::Identifier(cst::Identifier {
+                    name,
+                    chain: None,
+                    loc,
+                }) if name == "ANSWER" => cst::Expression::Num(cst::Num {
+                    value: 42.0,
+                    chain: None,
+                    loc,
+                }),
+                expression => expression,
+            })
+        }
+    }
+
+    struct DoubleNum;
+
+    impl cst::Visitor for DoubleNum {
+        fn callback_expression(
+            &self,
+            expression: cst::Expression,
+        ) -> Result<cst::Expression, AiScriptError> {
+            Ok(match expression {
+                cst::Expression::Num(num) => cst::Expression::Num(cst::Num {
+                    value: num.value * 2.0,
+                    ..num
+                }),
+                expression => expression,
+            })
+        }
+    }
+
+    struct ForbidAnswer;
+
+    impl cst::Visitor for ForbidAnswer {
+        fn callback_expression(
+            &self,
+            expression: cst::Expression,
+        ) -> Result<cst::Expression, AiScriptError> {
+            match &expression {
+                cst::Expression::Identifier(cst::Identifier { name, .. }) if name == "ANSWER" => {
+                    Err(AiScriptError::Internal("ANSWER is forbidden".to_string()))
+                }
+                _ => Ok(expression),
+            }
+        }
+    }
+
+    fn replace_answer(nodes: Vec<cst::Node>) -> Result<Vec<cst::Node>, AiScriptError> {
+        nodes
+            .into_iter()
+            .map(|node| cst::Visitor::visit_node(&ReplaceAnswer, node))
+            .collect()
+    }
+
+    fn double_num(nodes: Vec<cst::Node>) -> Result<Vec<cst::Node>, AiScriptError> {
+        nodes
+            .into_iter()
+            .map(|node| cst::Visitor::visit_node(&DoubleNum, node))
+            .collect()
+    }
+
+    fn forbid_answer(nodes: Vec<cst::Node>) -> Result<Vec<cst::Node>, AiScriptError> {
+        nodes
+            .into_iter()
+            .map(|node| cst::Visitor::visit_node(&ForbidAnswer, node))
+            .collect()
+    }
+
+    async fn exec(parser: &Parser, program: &str) -> Result<Value, AiScriptError> {
+        let ast = parser.parse(program)?;
+        let aiscript = new_interpreter([]);
+        aiscript.exec(ast).await.map(Option::unwrap)
+    }
+
+    #[tokio::test]
+    async fn transform() {
+        let mut parser = Parser::default();
+        parser.add_plugin(PluginType::Transform(replace_answer));
+        // The plugin receives the nodes whose chains are already expanded.
+        let res = exec(&parser, "ANSWER + Core:add(ANSWER, 1)").await.unwrap();
+        assert_eq!(res, num(85));
+    }
+
+    #[tokio::test]
+    async fn transform_in_order_of_registration() {
+        let mut parser = Parser::default();
+        parser.add_plugin(PluginType::Transform(replace_answer));
+        parser.add_plugin(PluginType::Transform(double_num));
+        assert_eq!(exec(&parser, "ANSWER").await.unwrap(), num(84));
+
+        let mut parser = Parser::default();
+        parser.add_plugin(PluginType::Transform(double_num));
+        parser.add_plugin(PluginType::Transform(replace_answer));
+        assert_eq!(exec(&parser, "ANSWER").await.unwrap(), num(42));
+    }
+
+    #[tokio::test]
+    async fn validate_before_transform() {
+        let mut parser = Parser::default();
+        parser.add_plugin(PluginType::Transform(replace_answer));
+        parser.add_plugin(PluginType::Validate(forbid_answer));
+        let err = exec(&parser, "ANSWER").await.unwrap_err();
+        assert_eq!(
+            err,
+            AiScriptError::Internal("ANSWER is forbidden".to_string())
+        );
+    }
+
+    fn lint_shadowed(_: &[Node], scope_info: &ScopeInfo) -> Vec<Diagnostic> {
+        scope_info
+            .shadowed()
+            .map(|(declaration, _)| Diagnostic {
+                message: format!("'{}' shadows an outer variable", declaration.name),
+                loc: declaration.loc.clone(),
+            })
+            .collect()
+    }
+
+    fn lint_unused(_: &[Node], scope_info: &ScopeInfo) -> Vec<Diagnostic> {
+        scope_info
+            .unused()
+            .filter(|declaration| declaration.depth > 0)
+            .map(|declaration| Diagnostic {
+                message: format!("'{}' is never used", declaration.name),
+                loc: declaration.loc.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut parser = Parser::default();
+        parser.add_plugin(PluginType::Diagnostic(lint_shadowed));
+        parser.add_plugin(PluginType::Diagnostic(lint_unused));
+        let (nodes, diagnostics) = parser
+            .parse_with_diagnostics(
+                r#"
+let a = 1
+@f(x) {
+    let a = x
+    let b = 2
+    each let x, [a] { x }
+}
+"#,
+            )
+            .unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect::<Vec<_>>(),
+            vec![
+                "'a' shadows an outer variable",
+                "'x' shadows an outer variable",
+                "'b' is never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnostics_namespace() {
+        let scope_info = ScopeInfo::new(
+            &Parser::default()
+                .parse(
+                    r#"
+:: Foo {
+    let a = 1
+    @f() { a }
+}
+<: Foo:f()
+"#,
+                )
+                .unwrap(),
+        );
+        assert_eq!(
+            scope_info
+                .declarations
+                .iter()
+                .map(|declaration| (declaration.name.as_str(), declaration.references))
+                .collect::<Vec<_>>(),
+            vec![("a", 1), ("f", 1)]
+        );
+    }
+
+    #[test]
+    fn jumps() {
+        let scope_info = ScopeInfo::new(
+            &Parser::default()
+                .parse(
+                    r#"
+each let x, [1, 2] {
+    if x == 1 { continue }
+    @f() { break }
+    loop {
+        break
+        <: x
+    }
+}
+continue
+"#,
+                )
+                .unwrap(),
+        );
+        let each = Some(Loc { start: 1, end: 112 });
+        let loop_ = Some(Loc {
+            start: 72,
+            end: 110,
+        });
+        assert_eq!(
+            scope_info
+                .jumps
+                .iter()
+                .map(|jump| (jump.kind, jump.target.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (JumpKind::Continue, each),
+                (JumpKind::Break, None),
+                (JumpKind::Break, loop_),
+                (JumpKind::Continue, None),
+            ]
+        );
+        assert_eq!(
+            scope_info.jumps[2].unreachable,
+            Some(Loc {
+                start: 101,
+                end: 104
+            })
+        );
+        assert_eq!(scope_info.invalid_jumps().count(), 2);
+    }
+
+    #[test]
+    fn dependencies() {
+        let scope_info = ScopeInfo::new(
+            &Parser::default()
+                .parse(
+                    r#"
+:: Lib {
+    @get(url) { Http:get(url) }
+}
+#[deprecated "use Lib:get"]
+@fetch() { Lib:get(config.url) }
+if exists Storage:get { Storage:get('k') }
+Mk:ui:text('a')
+count = 1
+<: fetch()
+"#,
+                )
+                .unwrap(),
+        );
+        let dependencies = scope_info.dependencies();
+        assert_eq!(
+            dependencies.functions.into_iter().collect::<Vec<_>>(),
+            vec!["Http:get", "Mk:ui:text"]
+        );
+        assert_eq!(
+            dependencies.namespaces.into_iter().collect::<Vec<_>>(),
+            vec!["Http", "Mk:ui"]
+        );
+        assert_eq!(
+            dependencies.globals.into_iter().collect::<Vec<_>>(),
+            vec!["config", "count", "print"]
+        );
+        assert_eq!(
+            dependencies.attributes.into_iter().collect::<Vec<_>>(),
+            vec!["deprecated"]
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let diagnostics = aiscript_v0::validate(
+            r#"
+:: Foo {
+    var a = 1
+}
+let b = 1
+@f(b) {
+    let b = 2
+    break
+}
+each let c, [1] { let c = 2 }
+let b = 3
+"#,
+        );
+        assert_eq!(
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect::<Vec<_>>(),
+            vec![
+                "Namespaces cannot include mutable variable: a",
+                "Variable 'b' already exists",
+                "break must be inside a loop",
+                "Variable 'b' already exists",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_syntax_error() {
+        let diagnostics = aiscript_v0::validate("let if = 1");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("Syntax:"));
+        assert!(aiscript_v0::validate("let a = 1\n<: a").is_empty());
+    }
+
+    #[test]
+    fn parse_iter() {
+        let script = r#"
+/// @ 0.19.0
+### { name: "test" }
+:: Foo {
+    @f() { 1 } // comment
+}
+#[deprecated]
+let a = Foo:f()
+/*
+ * comment
+ */
+if a == 1 {
+    <: "one"
+} else {
+    <: "other"
+}
+
+@g(x) {
+    x + 1
+}
+Core:add(
+    g(a),
+    2
+).to_str()
+"#;
+        let parser = Parser::default();
+        let nodes = parser
+            .parse_iter(script)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(nodes, parser.parse(script).unwrap());
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(parser.parse_iter("  \n ").count(), 0);
+    }
+
+    #[test]
+    fn reparse() {
+        let script = r#"
+/* コメント */
+let a = 1
+@f(x) {
+    x + a
+}
+if a == 1 {
+    <: "one"
+}
+#[deprecated]
+let b = f(2)
+<: `{b}`
+"#;
+        let parser = Parser::default();
+        let old = parser.parse(script).unwrap();
+        for (from, to) in [
+            ("x + a", "x * a + 1"),
+            ("let a = 1", "let a = 10"),
+            ("}\n#[", "} else {\n    <: \"other\"\n}\n#["),
+            ("let b", "let c = 3\nlet b"),
+            ("<: `{b}`", "<: b /* \n */ <: b"),
+            ("/* コメント */", "/* コメント"),
+            ("#[deprecated]\n", ""),
+            ("\n<:", "<:"),
+            ("\n<:", "\n/*<:"),
+        ] {
+            let start = script.find(from).unwrap();
+            let edit = start..start + from.len();
+            let input = format!("{}{to}{}", &script[..start], &script[edit.end..]);
+            assert_eq!(
+                parser
+                    .reparse(&old, edit, to, &input)
+                    .map_err(|e| e.to_string()),
+                parser.parse(&input).map_err(|e| e.to_string()),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn reparse_reuses_nodes() {
+        let parser = Parser::default();
+        let mut old = parser.parse("let a = 1\nlet b = 2\nlet c = 3\n").unwrap();
+        // Nodes after the edit are not parsed again.
+        let Node::Statement(Statement::Definition(Definition {
+            expr: Expression::Num(num),
+            ..
+        })) = &mut old[2]
+        else {
+            panic!()
+        };
+        num.value = 4.0;
+        let nodes = parser
+            .reparse(&old, 18..19, "20", "let a = 1\nlet b = 20\nlet c = 3\n")
+            .unwrap();
+        assert_eq!(
+            nodes,
+            parser.parse("let a = 1\nlet b = 20\nlet c = 4\n").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_iter_error() {
+        let parser = Parser::default();
+        let mut nodes = parser.parse_iter("let a = 1\nlet b = 2 3\nlet c = 4");
+        assert!(nodes.next().unwrap().is_ok());
+        assert!(nodes.next().unwrap().is_ok());
+        let err = nodes.next().unwrap().unwrap_err();
+        assert_eq!(
+            err.loc(),
+            parser
+                .parse("let a = 1\nlet b = 2 3\nlet c = 4")
+                .unwrap_err()
+                .loc()
+        );
+        assert!(nodes.next().is_none());
+    }
+
+    #[test]
+    fn unknown_identifiers() {
+        let aiscript = new_interpreter([("HOST".to_string(), num(1))]);
+        let script = Parser::default()
+            .parse(
+                r#"
+:: Foo {
+    @f() { bar }
+}
+var a = HOST
+@g(x) { x + Foo:f() + Core:add(a, b) }
+c = 1
+if exists d { d }
+<: Foo:g()
+"#,
+            )
+            .unwrap();
+        let diagnostics = aiscript.unknown_identifiers(&script);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Unknown identifier: bar",
+                "Unknown identifier: b",
+                "Unknown identifier: c",
+                "Unknown identifier: Foo:g",
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.loc.is_some()));
+    }
+
+    #[test]
+    fn parse_does_not_run_diagnostic_plugins() {
+        let mut parser = Parser::default();
+        parser.add_plugin(PluginType::Diagnostic(|_, _| panic!()));
+        parser.parse("let a = 1").unwrap();
+    }
+
+    #[test]
+    fn without_builtin_plugins() {
+        let parser = Parser::new(Vec::new(), vec![replace_answer]);
+        let nodes = parser.parse("ANSWER").unwrap();
+        assert!(matches!(
+            &nodes[..],
+            [Node::Expression(Expression::Num(Num { value, .. }))] if *value == 42.0
+        ));
+    }
+}
+
+mod variable_declaration {
+    use super::*;
+
+    #[tokio::test]
+    async fn do_not_assign_to_let_issue_328() {
+        let err = test(
+            r#"
+            let hoge = 33
+            hoge = 4
+            "#,
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, AiScriptError::Runtime(_)));
+    }
+}
+
+mod variable_assignment {
+    use super::*;
+
+    #[tokio::test]
+    async fn simple() {
+        test(
+            r#"
+            var hoge = 25
+            hoge = 7
+            <: hoge
+            "#,
+            |res| assert_eq!(res, num(7)),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn destructuring_assingment() {
+        test(
+            r#"
+            var hoge = 'foo'
+            var fuga = { value: 'bar' }
+            [{ value: hoge }, fuga] = [fuga, hoge]
+            <: [hoge, fuga]
+            "#,
+            |res| assert_eq!(res, arr([str("bar"), str("foo")])),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+mod primitive_props {
+    use super::*;
+
+    mod num {
+        use super::*;
+
+        #[tokio::test]
+        async fn to_str() {
+            test(
+                r#"
+                let num = 123
+                <: num.to_str()
+                "#,
+                |res| assert_eq!(res, str("123")),
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    mod str {
+        use super::*;
+
+        #[tokio::test]
+        async fn len() {
+            test(
+                r#"
+                let str = "hello"
+                <: str.len
+                "#,
+                |res| assert_eq!(res, num(5)),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn to_num() {
+            test(
+                r#"
+                let str = "123"
+                <: str.to_num()
+                "#,
+                |res| assert_eq!(res, num(123)),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn upper() {
+            test(
+                r#"
+                let str = "hello"
+                <: str.upper()
+                "#,
+                |res| assert_eq!(res, str("HELLO")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn lower() {
+            test(
+                r#"
+                let str = "HELLO"
+                <: str.lower()
+                "#,
+                |res| assert_eq!(res, str("hello")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn trim() {
+            test(
+                r#"
+                let str = " hello  "
+                <: str.trim()
+                "#,
+                |res| assert_eq!(res, str("hello")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn replace() {
+            test(
+                r#"
+                let str = "hello"
+                <: str.replace("l", "x")
+                "#,
+                |res| assert_eq!(res, str("hexxo")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn index_of() {
+            test(
+                r#"
+                let str = '0123401234'
+                <: [
+                    str.index_of('3') == 3,
+                    str.index_of('5') == -1,
+                    str.index_of('3', 3) == 3,
+                    str.index_of('3', 4) == 8,
+                    str.index_of('3', -1) == -1,
+                    str.index_of('3', -2) == 8,
+                    str.index_of('3', -7) == 3,
+                    str.index_of('3', 10) == -1,
+                ].map(@(v){if (v) '1' else '0'}).join()
+                "#,
+                |res| assert_eq!(res, str("11111111")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn incl() {
+            test(
+                r#"
+                let str = "hello"
+                <: [str.incl("ll"), str.incl("x")]
+                "#,
+                |res| assert_eq!(res, arr([bool(true), bool(false)])),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn split() {
+            test(
+                r#"
+                let str = "a,b,c"
+                <: str.split(",")
+                "#,
+                |res| assert_eq!(res, arr([str("a"), str("b"), str("c")])),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn pick() {
+            test(
+                r#"
+                let str = "hello"
+                <: str.pick(1)
+                "#,
+                |res| assert_eq!(res, str("e")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn slice() {
+            test(
+                r#"
+                let str = "hello"
+                <: str.slice(1, 3)
+                "#,
+                |res| assert_eq!(res, str("el")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn slice_out_of_range() {
+            test(
+                r#"
+                let str = "hello"
+                <: str.slice(3, 1)
+                "#,
+                |res| assert_eq!(res, str("")),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let str = "hello"
+                <: str.slice(-1, 3)
+                "#,
+                |res| assert_eq!(res, str("hel")),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let str = "hello"
+                <: str.slice(3, -1)
+                "#,
+                |res| assert_eq!(res, str("")),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let str = "hello"
+                <: str.slice(-1, -3)
+                "#,
+                |res| assert_eq!(res, str("")),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let str = "hello"
+                <: str.slice(-3, -1)
+                "#,
+                |res| assert_eq!(res, str("")),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let str = "hello"
+                <: str.slice(11, 13)
+                "#,
+                |res| assert_eq!(res, str("")),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn codepoint_at() {
+            test(
+                r#"
+                let str = "𩸽"
+                <: str.codepoint_at(0)
+                "#,
+                |res| assert_eq!(res, num(171581)),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn to_arr() {
+            test(
+                r#"
+                let str = "𩸽👉🏿👨‍👦"
+                <: str.to_arr()
+                "#,
+                |res| assert_eq!(res, arr([str("𩸽"), str("👉🏿"), str("👨‍👦")])),
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn to_unicode_arr() {
+            test(
+                r#"
+                let str = "𩸽👉🏿👨‍👦"
+                <: str.to_unicode_arr()
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str("𩸽"),
+                            str("👉"),
+                            str("\u{1F3FF}"),
+                            str("👨"),
+                            str("\u{200d}"),
+                            str("👦")
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn to_unicode_codepoint_arr() {
+            test(
+                r#"
+                let str = "𩸽👉🏿👨‍👦"
+                <: str.to_unicode_codepoint_arr()
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(171581),
+                            num(128073),
+                            num(127999),
+                            num(128104),
+                            num(8205),
+                            num(128102)
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn to_char_arr() {
+            test(
+                r#"
+                let str = "abc𩸽👉🏿👨‍👦def"
+                <: str.to_char_arr()
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            97, 98, 99, 55399, 56893, 55357, 56393, 55356, 57343, 55357, 56424,
+                            8205, 55357, 56422, 100, 101, 102
+                        ]
+                        .into_iter()
+                        .map(|u| str(String::from_utf16_lossy(&[u])))
+                        .collect::<Vec<Value>>())
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn to_charcode_arr() {
+            test(
+                r#"
+                let str = "abc𩸽👉🏿👨‍👦def"
+                <: str.to_charcode_arr()
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(97),
+                            num(98),
+                            num(99),
+                            num(55399),
+                            num(56893),
+                            num(55357),
+                            num(56393),
+                            num(55356),
+                            num(57343),
+                            num(55357),
+                            num(56424),
+                            num(8205),
+                            num(55357),
+                            num(56422),
+                            num(100),
+                            num(101),
+                            num(102),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn to_utf8_byte_arr() {
+            test(
+                r#"
+                let str = "abc𩸽👉🏿👨‍👦def"
+                <: str.to_utf8_byte_arr()
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(97),
+                            num(98),
+                            num(99),
+                            num(240),
+                            num(169),
+                            num(184),
+                            num(189),
+                            num(240),
+                            num(159),
+                            num(145),
+                            num(137),
+                            num(240),
+                            num(159),
+                            num(143),
+                            num(191),
+                            num(240),
+                            num(159),
+                            num(145),
+                            num(168),
+                            num(226),
+                            num(128),
+                            num(141),
+                            num(240),
+                            num(159),
+                            num(145),
+                            num(166),
+                            num(100),
+                            num(101),
+                            num(102),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn starts_with_no_index() {
+            test(
+                r#"
+                let str = "hello"
+                let empty = ""
+                <: [
+                    str.starts_with(""), str.starts_with("hello"),
+                    str.starts_with("he"), str.starts_with("ell"),
+                    empty.starts_with(""), empty.starts_with("he"),
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(false),
+                            bool(true),
+                            bool(false),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn starts_with_with_index() {
+            test(
+                r#"
+                let str = "hello"
+                let empty = ""
+                <: [
+                    str.starts_with("", 4), str.starts_with("he", 0),
+                    str.starts_with("ll", 2), str.starts_with("lo", 3),
+                    str.starts_with("lo", -2), str.starts_with("hel", -5),
+                    str.starts_with("he", 2), str.starts_with("loa", 3),
+                    str.starts_with("lo", -6), str.starts_with("", -7),
+                    str.starts_with("lo", 6), str.starts_with("", 7),
+                    empty.starts_with("", 2), empty.starts_with("ll", 2),
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(false),
+                            bool(false),
+                            bool(false),
+                            bool(true),
+                            bool(false),
+                            bool(true),
+                            bool(true),
+                            bool(false),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn ends_with_no_index() {
+            test(
+                r#"
+                let str = "hello"
+                let empty = ""
+                <: [
+                    str.ends_with(""), str.ends_with("hello"),
+                    str.ends_with("lo"), str.ends_with("ell"),
+                    empty.ends_with(""), empty.ends_with("he"),
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(false),
+                            bool(true),
+                            bool(false),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn ends_with_with_index() {
+            test(
+                r#"
+                let str = "hello"
+                let empty = ""
+                <: [
+                    str.ends_with("", 3), str.ends_with("lo", 5),
+                    str.ends_with("ll", 4), str.ends_with("he", 2),
+                    str.ends_with("ll", -1), str.ends_with("he", -3),
+                    str.ends_with("he", 5), str.ends_with("lo", 3),
+                    str.ends_with("lo", -6), str.ends_with("", -7),
+                    str.ends_with("lo", 6), str.ends_with("", 7),
+                    empty.ends_with("", 2), empty.ends_with("ll", 2),
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(true),
+                            bool(false),
+                            bool(false),
+                            bool(false),
+                            bool(true),
+                            bool(false),
+                            bool(true),
+                            bool(true),
+                            bool(false),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn pad_start() {
+            test(
+                r#"
+                let str = "abc"
+                <: [
+                    str.pad_start(0), str.pad_start(1), str.pad_start(2),
+                    str.pad_start(3), str.pad_start(4), str.pad_start(5),
+                    str.pad_start(0, "0"), str.pad_start(1, "0"), str.pad_start(2, "0"),
+                    str.pad_start(3, "0"), str.pad_start(4, "0"), str.pad_start(5, "0"),
+                    str.pad_start(0, "01"), str.pad_start(1, "01"), str.pad_start(2, "01"),
+                    str.pad_start(3, "01"), str.pad_start(4, "01"), str.pad_start(5, "01"),
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str(" abc"),
+                            str("  abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("0abc"),
+                            str("00abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("0abc"),
+                            str("01abc"),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn pad_end() {
+            test(
+                r#"
+                let str = "abc"
+                <: [
+                    str.pad_end(0), str.pad_end(1), str.pad_end(2),
+                    str.pad_end(3), str.pad_end(4), str.pad_end(5),
+                    str.pad_end(0, "0"), str.pad_end(1, "0"), str.pad_end(2, "0"),
+                    str.pad_end(3, "0"), str.pad_end(4, "0"), str.pad_end(5, "0"),
+                    str.pad_end(0, "01"), str.pad_end(1, "01"), str.pad_end(2, "01"),
+                    str.pad_end(3, "01"), str.pad_end(4, "01"), str.pad_end(5, "01"),
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc "),
+                            str("abc  "),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc0"),
+                            str("abc00"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc"),
+                            str("abc0"),
+                            str("abc01"),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
     }
 
-    mod string {
+    mod arr {
         use super::*;
 
-        #[test]
-        fn valid() {
-            let res = get_meta(
+        #[tokio::test]
+        async fn len() {
+            test(
                 r#"
-                ### x "hoge"
+                let arr = [1, 2, 3]
+                <: arr.len
                 "#,
+                |res| assert_eq!(res, num(3)),
             )
+            .await
             .unwrap();
-            assert_eq!(
-                res,
-                IndexMap::<Option<String>, Option<Value>>::from_iter([(
-                    Some("x".to_string()),
-                    Some(str("hoge"))
-                )])
-            );
         }
-    }
 
-    mod number {
-        use super::*;
+        #[tokio::test]
+        async fn push() {
+            test(
+                r#"
+                let arr = [1, 2, 3]
+                arr.push(4)
+                <: arr
+                "#,
+                |res| assert_eq!(res, arr([num(1), num(2), num(3), num(4)])),
+            )
+            .await
+            .unwrap();
+        }
 
-        #[test]
-        fn valid() {
-            let res = get_meta(
+        #[tokio::test]
+        async fn unshift() {
+            test(
                 r#"
-                ### x 42
+                let arr = [1, 2, 3]
+                arr.unshift(4)
+                <: arr
                 "#,
+                |res| assert_eq!(res, arr([num(4), num(1), num(2), num(3)])),
             )
+            .await
             .unwrap();
-            assert_eq!(
-                res,
-                IndexMap::<Option<String>, Option<Value>>::from_iter([(
-                    Some("x".to_string()),
-                    Some(num(42))
-                )])
-            );
         }
-    }
 
-    mod boolean {
-        use super::*;
+        #[tokio::test]
+        async fn pop() {
+            test(
+                r#"
+                let arr = [1, 2, 3]
+                let popped = arr.pop()
+                <: [popped, arr]
+                "#,
+                |res| assert_eq!(res, arr([num(3), arr([num(1), num(2)])])),
+            )
+            .await
+            .unwrap();
+        }
 
-        #[test]
-        fn valid() {
-            let res = get_meta(
+        #[tokio::test]
+        async fn shift() {
+            test(
                 r#"
-                ### x true
+                let arr = [1, 2, 3]
+                let shifted = arr.shift()
+                <: [shifted, arr]
                 "#,
+                |res| assert_eq!(res, arr([num(1), arr([num(2), num(3)])])),
             )
+            .await
             .unwrap();
-            assert_eq!(
-                res,
-                IndexMap::<Option<String>, Option<Value>>::from_iter([(
-                    Some("x".to_string()),
-                    Some(bool(true))
-                )])
-            );
         }
-    }
 
-    mod null {
-        use super::*;
+        #[tokio::test]
+        async fn concat() {
+            test(
+                r#"
+                let arr = [1, 2, 3]
+                let concated = arr.concat([4, 5])
+                <: [concated, arr]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(1), num(2), num(3), num(4), num(5)]),
+                            arr([num(1), num(2), num(3)])
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
+        }
 
-        #[test]
-        fn valid() {
-            let res = get_meta(
+        #[tokio::test]
+        async fn slice() {
+            test(
                 r#"
-                ### x null
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                let sliced = arr.slice(2, 4)
+                <: [sliced, arr]
                 "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([str("camel"), str("duck")]),
+                            arr([
+                                str("ant"),
+                                str("bison"),
+                                str("camel"),
+                                str("duck"),
+                                str("elephant")
+                            ])
+                        ])
+                    )
+                },
             )
+            .await
             .unwrap();
-            assert_eq!(
-                res,
-                IndexMap::<Option<String>, Option<Value>>::from_iter([(
-                    Some("x".to_string()),
-                    Some(null())
-                )])
-            );
         }
-    }
 
-    mod array {
-        use super::*;
+        #[tokio::test]
+        async fn slice_out_of_range() {
+            test(
+                r#"
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                <: arr.slice(4, 2)
+                "#,
+                |res| assert_eq!(res, arr([])),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                <: arr.slice(-2, 4)
+                "#,
+                |res| assert_eq!(res, arr([str("duck")])),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                <: arr.slice(4, -2)
+                "#,
+                |res| assert_eq!(res, arr([])),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                <: arr.slice(-2, -4)
+                "#,
+                |res| assert_eq!(res, arr([])),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                <: arr.slice(-4, -2)
+                "#,
+                |res| assert_eq!(res, arr([str("bison"), str("camel")])),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                <: arr.slice(12, 14)
+                "#,
+                |res| assert_eq!(res, arr([])),
+            )
+            .await
+            .unwrap();
+
+            test(
+                r#"
+                let arr = ["ant", "bison", "camel", "duck", "elephant"]
+                <: arr.slice(-14, -12)
+                "#,
+                |res| assert_eq!(res, arr([])),
+            )
+            .await
+            .unwrap();
+        }
 
-        #[test]
-        fn valid() {
-            let res = get_meta(
+        #[tokio::test]
+        async fn join() {
+            test(
                 r#"
-                ### x [1 2 3]
+                let arr = ["a", "b", "c"]
+                <: arr.join("-")
                 "#,
+                |res| assert_eq!(res, str("a-b-c")),
             )
+            .await
             .unwrap();
-            assert_eq!(
-                res,
-                IndexMap::<Option<String>, Option<Value>>::from_iter([(
-                    Some("x".to_string()),
-                    Some(arr([num(1), num(2), num(3)]))
-                )])
-            );
         }
 
-        #[test]
-        fn invalid() {
-            get_meta(
+        #[tokio::test]
+        async fn map() {
+            test(
                 r#"
-                ### x [1 (2 + 2) 3]
+                let arr = [1, 2, 3]
+                <: arr.map(@(item) { item * 2 })
                 "#,
+                |res| assert_eq!(res, arr([num(2), num(4), num(6)])),
             )
-            .unwrap_err();
+            .await
+            .unwrap();
         }
-    }
-
-    mod object {
-        use super::*;
 
-        #[test]
-        fn valid() {
-            let res = get_meta(
+        #[tokio::test]
+        async fn map_with_index() {
+            test(
                 r#"
-                ### x { a: 1; b: 2; c: 3; }
+                let arr = [1, 2, 3]
+                <: arr.map(@(item, index) { item * index })
                 "#,
+                |res| assert_eq!(res, arr([num(0), num(2), num(6)])),
             )
+            .await
             .unwrap();
-            assert_eq!(
-                res,
-                IndexMap::<Option<String>, Option<Value>>::from_iter([(
-                    Some("x".to_string()),
-                    Some(obj([("a", num(1)), ("b", num(2)), ("c", num(3))]))
-                )])
-            );
         }
 
-        #[test]
-        fn invalid() {
-            get_meta(
+        #[tokio::test]
+        async fn filter() {
+            test(
                 r#"
-                ### x { a: 1; b: (2 + 2); c: 3; }
+                let arr = [1, 2, 3]
+                <: arr.filter(@(item) { item != 2 })
                 "#,
+                |res| assert_eq!(res, arr([num(1), num(3)])),
             )
-            .unwrap_err();
+            .await
+            .unwrap();
         }
-    }
-
-    mod template {
-        use super::*;
 
-        #[test]
-        fn invalid() {
-            get_meta(
+        #[tokio::test]
+        async fn filter_with_index() {
+            test(
                 r#"
-                ### x `foo {bar} baz`
+                let arr = [1, 2, 3, 4]
+                <: arr.filter(@(item, index) { item != 2 && index != 3 })
                 "#,
+                |res| assert_eq!(res, arr([num(1), num(3)])),
             )
-            .unwrap_err();
+            .await
+            .unwrap();
         }
-    }
-
-    mod expression {
-        use super::*;
 
-        #[test]
-        fn invalid() {
-            get_meta(
+        #[tokio::test]
+        async fn reduce() {
+            test(
                 r#"
-                ### x (1 + 1)
+                let arr = [1, 2, 3, 4]
+                <: arr.reduce(@(accumulator, currentValue) { (accumulator + currentValue) })
                 "#,
+                |res| assert_eq!(res, num(10)),
             )
-            .unwrap_err();
+            .await
+            .unwrap();
         }
-    }
-}
-
-mod lang_version {
-    use super::*;
-
-    #[test]
-    fn number() {
-        let res = utils::get_lang_version(
-            r#"
-            /// @2021
-            @f(x) {
-                x
-            }
-            "#,
-        );
-        assert_eq!(res, Some("2021".to_string()));
-    }
-
-    #[test]
-    fn chars() {
-        let res = utils::get_lang_version(
-            r#"
-            /// @ canary
-            const a = 1
-            @f(x) {
-                x
-            }
-            f(a)
-            "#,
-        );
-        assert_eq!(res, Some("canary".to_string()));
-    }
-
-    #[test]
-    fn complex() {
-        let res = utils::get_lang_version(
-            r#"
-            /// @ 2.0-Alpha
-            @f(x) {
-                x
-            }
-            "#,
-        );
-        assert_eq!(res, Some("2.0-Alpha".to_string()));
-    }
-
-    #[test]
-    fn no_specified() {
-        let res = utils::get_lang_version(
-            r#"
-            @f(x) {
-                x
-            }
-            "#,
-        );
-        assert_eq!(res, None);
-    }
-}
-
-mod attribute {
-    use super::*;
 
-    #[test]
-    fn single_attribute_with_function_str() {
-        let nodes = Parser::default()
-            .parse(
+        #[tokio::test]
+        async fn reduce_with_index() {
+            test(
                 r#"
-                #[Event "Received"]
-                @onReceived(data) {
-                    data
-                }
+                let arr = [1, 2, 3, 4]
+                <: arr.reduce(@(accumulator, currentValue, index) { (accumulator + (currentValue * index)) } 0)
                 "#,
+                |res| assert_eq!(res, num(20)),
             )
+            .await
             .unwrap();
-        if let [Node::Statement(Statement::Definition(Definition { name, attr, .. }))] = &nodes[..]
-        {
-            assert_eq!(name, "onReceived");
-            if let Some(attr) = attr {
-                if let [Attribute {
-                    name,
-                    value: Expression::Str(Str { value, .. }),
-                    ..
-                }] = &attr[..]
-                {
-                    assert_eq!(name, "Event");
-                    assert_eq!(value, "Received");
-                    return;
-                }
-            }
         }
-        panic!();
-    }
 
-    #[test]
-    fn multiple_attributes_with_function_obj_str_bool() {
-        let nodes = Parser::default()
-            .parse(
+        #[tokio::test]
+        async fn reduce_of_empty_array_without_initial_value() {
+            let err = test(
                 r#"
-                #[Endpoint { path: "/notes/create"; }]
-                #[Desc "Create a note."]
-                #[Cat true]
-                @createNote(text) {
-                    <: text
-                }
+                let arr = [1, 2, 3, 4]
+                <: [].reduce(@(){})
                 "#,
+                |_| {},
             )
-            .unwrap();
-        if let [Node::Statement(Statement::Definition(Definition { name, attr, .. }))] = &nodes[..]
-        {
-            assert_eq!(name, "createNote");
-            if let Some(attr) = attr {
-                if let [Attribute {
-                    name: name1,
-                    value: Expression::Obj(Obj { value: value1, .. }),
-                    ..
-                }, Attribute {
-                    name: name2,
-                    value: Expression::Str(Str { value: value2, .. }),
-                    ..
-                }, Attribute {
-                    name: name3,
-                    value: Expression::Bool(Bool { value: true, .. }),
-                    ..
-                }] = &attr[..]
-                {
-                    assert_eq!(name1, "Endpoint");
-                    assert_eq!(name2, "Desc");
-                    assert_eq!(value2, "Create a note.");
-                    assert_eq!(name3, "Cat");
-                    if let [(key, Expression::Str(Str { value, .. }))] =
-                        value1.iter().collect::<Vec<(&String, &Expression)>>()[..]
-                    {
-                        assert_eq!(key, "path");
-                        assert_eq!(value, "/notes/create");
-                        return;
-                    }
-                }
-            }
+            .await
+            .unwrap_err();
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Runtime(message))
+                    if &message == "Reduce of empty array without initial value"
+            ));
+        }
+
+        #[tokio::test]
+        async fn find() {
+            test(
+                r#"
+                let arr = ["abc", "def", "ghi"]
+                <: arr.find(@(item) { item.incl("e") })
+                "#,
+                |res| assert_eq!(res, str("def")),
+            )
+            .await
+            .unwrap();
         }
-        panic!();
-    }
 
-    #[test]
-    fn single_attribute_no_value() {
-        let nodes = Parser::default()
-            .parse(
+        #[tokio::test]
+        async fn find_with_index() {
+            test(
                 r#"
-                #[serializable]
-                let data = 1
+                let arr = ["abc1", "def1", "ghi1", "abc2", "def2", "ghi2"]
+                <: arr.find(@(item, index) { item.incl("e") && index > 1 })
                 "#,
+                |res| assert_eq!(res, str("def2")),
             )
+            .await
             .unwrap();
-        if let [Node::Statement(Statement::Definition(Definition { name, attr, .. }))] = &nodes[..]
-        {
-            assert_eq!(name, "data");
-            if let Some(attr) = attr {
-                if let [Attribute {
-                    name,
-                    value: Expression::Bool { .. },
-                    ..
-                }] = &attr[..]
-                {
-                    assert_eq!(name, "serializable");
-                    return;
-                }
-            }
         }
-        panic!();
-    }
-}
 
-mod location {
-    use super::*;
-
-    #[test]
-    fn function() {
-        let nodes = Parser::default()
-            .parse(
+        #[tokio::test]
+        async fn incl() {
+            test(
                 r#"
-		@f(a) { a }
+                let arr = ["abc", "def", "ghi"]
+                <: [arr.incl("def"), arr.incl("jkl")]
                 "#,
+                |res| assert_eq!(res, arr([bool(true), bool(false)])),
             )
+            .await
             .unwrap();
-        if let [Node::Statement(Statement::Definition(Definition {
-            loc: Some(Loc { start, end }),
-            ..
-        }))] = &nodes[..]
-        {
-            assert_eq!(start.clone(), 3);
-            assert_eq!(end.clone(), 13);
-            return;
         }
-        panic!();
-    }
 
-    #[test]
-    fn comment() {
-        let nodes = Parser::default()
-            .parse(
+        #[tokio::test]
+        async fn index_of() {
+            test(
                 r#"
-		/*
-		*/
-		// hoge
-		@f(a) { a }
+                let arr = [0,1,2,3,4,0,1,2,3,4]
+                <: [
+                    arr.index_of(3) == 3,
+                    arr.index_of(5) == -1,
+                    arr.index_of(3, 3) == 3,
+                    arr.index_of(3, 4) == 8,
+                    arr.index_of(3, -1) == -1,
+                    arr.index_of(3, -2) == 8,
+                    arr.index_of(3, -7) == 3,
+                    arr.index_of(3, 10) == -1,
+                ].map(@(v){if (v) '1' else '0'}).join()
                 "#,
+                |res| assert_eq!(res, str("11111111")),
             )
+            .await
             .unwrap();
-        if let [Node::Statement(Statement::Definition(Definition {
-            loc: Some(Loc { start, end }),
-            ..
-        }))] = &nodes[..]
-        {
-            assert_eq!(start.clone(), 23);
-            assert_eq!(end.clone(), 33);
-            return;
         }
-        panic!();
-    }
-}
-
-mod variable_declaration {
-    use super::*;
-
-    #[tokio::test]
-    async fn do_not_assign_to_let_issue_328() {
-        let err = test(
-            r#"
-            let hoge = 33
-            hoge = 4
-            "#,
-            |_| {},
-        )
-        .await
-        .unwrap_err();
-        assert!(matches!(err, AiScriptError::Runtime(_)));
-    }
-}
-
-mod variable_assignment {
-    use super::*;
-
-    #[tokio::test]
-    async fn simple() {
-        test(
-            r#"
-            var hoge = 25
-            hoge = 7
-            <: hoge
-            "#,
-            |res| assert_eq!(res, num(7)),
-        )
-        .await
-        .unwrap();
-    }
-
-    #[tokio::test]
-    async fn destructuring_assingment() {
-        test(
-            r#"
-            var hoge = 'foo'
-            var fuga = { value: 'bar' }
-            [{ value: hoge }, fuga] = [fuga, hoge]
-            <: [hoge, fuga]
-            "#,
-            |res| assert_eq!(res, arr([str("bar"), str("foo")])),
-        )
-        .await
-        .unwrap();
-    }
-}
-
-mod primitive_props {
-    use super::*;
 
-    mod num {
-        use super::*;
+        #[tokio::test]
+        async fn reverse() {
+            test(
+                r#"
+                let arr = [1, 2, 3]
+                arr.reverse()
+                <: arr
+                "#,
+                |res| assert_eq!(res, arr([num(3), num(2), num(1)])),
+            )
+            .await
+            .unwrap();
+        }
 
         #[tokio::test]
-        async fn to_str() {
+        async fn copy() {
             test(
                 r#"
-                let num = 123
-                <: num.to_str()
+                let arr = [1, 2, 3]
+                let copied = arr.copy()
+                copied.reverse()
+                <: [copied, arr]
                 "#,
-                |res| assert_eq!(res, str("123")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([arr([num(3), num(2), num(1)]), arr([num(1), num(2), num(3)])])
+                    )
+                },
             )
             .await
             .unwrap();
         }
-    }
 
-    mod str {
-        use super::*;
+        #[tokio::test]
+        async fn sort_num_array() {
+            test(
+                r#"
+                var arr = [2, 10, 3]
+				let comp = @(a, b) { a - b }
+				arr.sort(comp)
+				<: arr
+                "#,
+                |res| assert_eq!(res, arr([num(2), num(3), num(10)])),
+            )
+            .await
+            .unwrap();
+        }
 
         #[tokio::test]
-        async fn len() {
+        async fn sort_string_array_with_str_lt() {
             test(
                 r#"
-                let str = "hello"
-                <: str.len
+                var arr = ["hoge", "huga", "piyo", "hoge"]
+				arr.sort(Str:lt)
+				<: arr
                 "#,
-                |res| assert_eq!(res, num(5)),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([str("hoge"), str("hoge"), str("huga"), str("piyo")])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn to_num() {
+        async fn sort_string_array_with_str_gt() {
             test(
                 r#"
-                let str = "123"
-                <: str.to_num()
+                var arr = ["hoge", "huga", "piyo", "hoge"]
+				arr.sort(Str:gt)
+				<: arr
                 "#,
-                |res| assert_eq!(res, num(123)),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([str("piyo"), str("huga"), str("hoge"), str("hoge")])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn upper() {
+        async fn sort_object_array() {
             test(
                 r#"
-                let str = "hello"
-                <: str.upper()
+                var arr = [{x: 2}, {x: 10}, {x: 3}]
+				let comp = @(a, b) { a.x - b.x }
+
+				arr.sort(comp)
+				<: arr
                 "#,
-                |res| assert_eq!(res, str("HELLO")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            obj([("x", num(2))]),
+                            obj([("x", num(3))]),
+                            obj([("x", num(10))])
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn lower() {
+        async fn sort_by() {
+            test(
+                r#"
+                var arr = [{x: 2, y: 'a'}, {x: 10, y: 'b'}, {x: 2, y: 'c'}, {x: 1, y: 'd'}]
+                arr.sort_by(@(v) { v.x })
+                <: arr.map(@(v) { v.y })
+                "#,
+                |res| assert_eq!(res, arr([str("d"), str("a"), str("c"), str("b")])),
+            )
+            .await
+            .unwrap();
+
             test(
                 r#"
-                let str = "HELLO"
-                <: str.lower()
+                var arr = ['bb', 'a', 'ab', 'b']
+                arr.sort_by(@(v) { [v.len, v] })
+                <: arr
                 "#,
-                |res| assert_eq!(res, str("hello")),
+                |res| assert_eq!(res, arr([str("a"), str("b"), str("ab"), str("bb")])),
             )
             .await
             .unwrap();
+
+            test("[1, 'a'].sort_by(@(v) { v })", |_| {})
+                .await
+                .unwrap_err();
         }
 
         #[tokio::test]
-        async fn trim() {
+        async fn sort_is_stable() {
             test(
                 r#"
-                let str = " hello  "
-                <: str.trim()
+                var arr = [{k: 1, i: 0}, {k: 0, i: 1}, {k: 1, i: 2}, {k: 0, i: 3}, {k: 1, i: 4}]
+                arr.sort(@(a, b) { a.k - b.k })
+                <: arr.map(@(v) { v.i })
                 "#,
-                |res| assert_eq!(res, str("hello")),
+                |res| assert_eq!(res, arr([num(1), num(3), num(0), num(2), num(4)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn replace() {
+        async fn sort_with_async_comparator() {
             test(
                 r#"
-                let str = "hello"
-                <: str.replace("l", "x")
+                var arr = [5, 3, 4, 1, 2]
+                arr.sort(@(a, b) {
+                    Core:sleep(0)
+                    a - b
+                })
+                <: arr
                 "#,
-                |res| assert_eq!(res, str("hexxo")),
+                |res| assert_eq!(res, arr([num(1), num(2), num(3), num(4), num(5)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn index_of() {
+        async fn sort_propagates_comparator_error() {
             test(
                 r#"
-                let str = '0123401234'
-                <: [
-                    str.index_of('3') == 3,
-                    str.index_of('5') == -1,
-                    str.index_of('3', 3) == 3,
-                    str.index_of('3', 4) == 8,
-                    str.index_of('3', -1) == -1,
-                    str.index_of('3', -2) == 8,
-                    str.index_of('3', -7) == 3,
-                    str.index_of('3', 10) == -1,
-                ].map(@(v){if (v) '1' else '0'}).join()
+                var arr = [2, 1]
+                arr.sort(@(a, b) { a.x - b.x })
                 "#,
-                |res| assert_eq!(res, str("11111111")),
+                |_| {},
             )
             .await
-            .unwrap();
-        }
+            .unwrap_err();
 
-        #[tokio::test]
-        async fn incl() {
             test(
                 r#"
-                let str = "hello"
-                <: [str.incl("ll"), str.incl("x")]
+                var arr = [2, 1]
+                arr.sort(@(a, b) { "1" })
                 "#,
-                |res| assert_eq!(res, arr([bool(true), bool(false)])),
+                |_| {},
             )
             .await
-            .unwrap();
+            .unwrap_err();
         }
 
         #[tokio::test]
-        async fn split() {
+        async fn fill() {
             test(
                 r#"
-                let str = "a,b,c"
-                <: str.split(",")
+                var arr1 = [0, 1, 2]
+				let arr2 = arr1.fill(3)
+				let arr3 = [0, 1, 2].fill(3, 1)
+				let arr4 = [0, 1, 2].fill(3, 1, 2)
+				let arr5 = [0, 1, 2].fill(3, -2, -1)
+				<: [arr1, arr2, arr3, arr4, arr5]
                 "#,
-                |res| assert_eq!(res, arr([str("a"), str("b"), str("c")])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(3), num(3), num(3)]), //target changed
+                            arr([num(3), num(3), num(3)]),
+                            arr([num(0), num(3), num(3)]),
+                            arr([num(0), num(3), num(2)]),
+                            arr([num(0), num(3), num(2)]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn pick() {
+        async fn repeat() {
             test(
                 r#"
-                let str = "hello"
-                <: str.pick(1)
+                var arr1 = [0, 1, 2]
+				let arr2 = arr1.repeat(3)
+				let arr3 = arr1.repeat(0)
+				<: [arr1, arr2, arr3]
                 "#,
-                |res| assert_eq!(res, str("e")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(0), num(1), num(2)]), // target not changed
+                            arr([
+                                num(0),
+                                num(1),
+                                num(2),
+                                num(0),
+                                num(1),
+                                num(2),
+                                num(0),
+                                num(1),
+                                num(2),
+                            ]),
+                            arr([]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn slice() {
+        async fn splice_full() {
             test(
                 r#"
-                let str = "hello"
-                <: str.slice(1, 3)
+                let arr1 = [0, 1, 2, 3]
+				let arr2 = arr1.splice(1, 2, [10])
+				<: [arr1, arr2]
                 "#,
-                |res| assert_eq!(res, str("el")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([arr([num(0), num(10), num(3)]), arr([num(1), num(2)]),])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn slice_out_of_range() {
+        async fn splice_negative_index() {
             test(
                 r#"
-                let str = "hello"
-                <: str.slice(3, 1)
+                let arr1 = [0, 1, 2, 3]
+				let arr2 = arr1.splice(-1, 0, [10, 20])
+				<: [arr1, arr2]
                 "#,
-                |res| assert_eq!(res, str("")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(0), num(1), num(2), num(10), num(20), num(3)]),
+                            arr([]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn splice_larger_index() {
             test(
                 r#"
-                let str = "hello"
-                <: str.slice(-1, 3)
+                let arr1 = [0, 1, 2, 3]
+				let arr2 = arr1.splice(4, 100, [10, 20])
+				<: [arr1, arr2]
                 "#,
-                |res| assert_eq!(res, str("hel")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(0), num(1), num(2), num(3), num(10), num(20)]),
+                            arr([]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn splice_single_argument() {
             test(
                 r#"
-                let str = "hello"
-                <: str.slice(3, -1)
+                let arr1 = [0, 1, 2, 3]
+				let arr2 = arr1.splice(1)
+				<: [arr1, arr2]
                 "#,
-                |res| assert_eq!(res, str("")),
+                |res| assert_eq!(res, arr([arr([num(0)]), arr([num(1), num(2), num(3)]),])),
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn flat() {
             test(
                 r#"
-                let str = "hello"
-                <: str.slice(-1, -3)
+                var arr1 = [0, [1], [2, 3], [4, [5, 6]]]
+				let arr2 = arr1.flat()
+				let arr3 = arr1.flat(2)
+				<: [arr1, arr2, arr3]
                 "#,
-                |res| assert_eq!(res, str("")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([
+                                num(0),
+                                arr([num(1)]),
+                                arr([num(2), num(3)]),
+                                arr([num(4), arr([num(5), num(6)])])
+                            ]), // target not changed
+                            arr([
+                                num(0),
+                                num(1),
+                                num(2),
+                                num(3),
+                                num(4),
+                                arr([num(5), num(6)]),
+                            ]),
+                            arr([num(0), num(1), num(2), num(3), num(4), num(5), num(6),]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn flat_map() {
             test(
                 r#"
-                let str = "hello"
-                <: str.slice(-3, -1)
+                let arr1 = [0, 1, 2]
+				let arr2 = ["a", "b"]
+				let arr3 = arr1.flat_map(@(x){ arr2.map(@(y){ [x, y] }) })
+				<: [arr1, arr3]
                 "#,
-                |res| assert_eq!(res, str("")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(0), num(1), num(2)]), // target not changed
+                            arr([
+                                arr([num(0), str("a")]),
+                                arr([num(0), str("b")]),
+                                arr([num(1), str("a")]),
+                                arr([num(1), str("b")]),
+                                arr([num(2), str("a")]),
+                                arr([num(2), str("b")]),
+                            ]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn every() {
             test(
                 r#"
-                let str = "hello"
-                <: str.slice(11, 13)
+                let arr1 = [0, 1, 2, 3]
+				let res1 = arr1.every(@(v,i){v==0 || i > 0})
+				let res2 = arr1.every(@(v,i){v==0 && i > 0})
+				let res3 = [].every(@(v,i){false})
+				<: [arr1, res1, res2, res3]
                 "#,
-                |res| assert_eq!(res, str("")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(0), num(1), num(2), num(3)]), // target not changed
+                            bool(true),
+                            bool(false),
+                            bool(true),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn codepoint_at() {
+        async fn some() {
             test(
                 r#"
-                let str = "𩸽"
-                <: str.codepoint_at(0)
+                let arr1 = [0, 1, 2, 3]
+				let res1 = arr1.some(@(v,i){v%2==0 && i <= 2})
+				let res2 = arr1.some(@(v,i){v%2==0 && i > 2})
+				<: [arr1, res1, res2]
                 "#,
-                |res| assert_eq!(res, num(171581)),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([num(0), num(1), num(2), num(3)]), // target not changed
+                            bool(true),
+                            bool(false),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn to_arr() {
+        async fn insert() {
             test(
-                r#"
-                let str = "𩸽👉🏿👨‍👦"
-                <: str.to_arr()
+                r#"
+                let arr1 = [0, 1, 2]
+				let res = []
+				res.push(arr1.insert(3, 10)) // [0, 1, 2, 10]
+				res.push(arr1.insert(2, 20)) // [0, 1, 20, 2, 10]
+				res.push(arr1.insert(0, 30)) // [30, 0, 1, 20, 2, 10]
+				res.push(arr1.insert(-1, 40)) // [30, 0, 1, 20, 2, 40, 10]
+				res.push(arr1.insert(-4, 50)) // [30, 0, 1, 50, 20, 2, 40, 10]
+				res.push(arr1.insert(100, 60)) // [30, 0, 1, 50, 20, 2, 40, 10, 60]
+				res.push(arr1)
+				<: res
                 "#,
-                |res| assert_eq!(res, arr([str("𩸽"), str("👉🏿"), str("👨‍👦")])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            null(),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
+                            arr([
+                                num(30),
+                                num(0),
+                                num(1),
+                                num(50),
+                                num(20),
+                                num(2),
+                                num(40),
+                                num(10),
+                                num(60)
+                            ])
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn to_unicode_arr() {
+        async fn remove() {
             test(
                 r#"
-                let str = "𩸽👉🏿👨‍👦"
-                <: str.to_unicode_arr()
+                let arr1 = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+				let res = []
+				res.push(arr1.remove(9)) // 9 [0, 1, 2, 3, 4, 5, 6, 7, 8]
+				res.push(arr1.remove(3)) // 3 [0, 1, 2, 4, 5, 6, 7, 8]
+				res.push(arr1.remove(0)) // 0 [1, 2, 4, 5, 6, 7, 8]
+				res.push(arr1.remove(-1)) // 8 [1, 2, 4, 5, 6, 7]
+				res.push(arr1.remove(-5)) // 2 [1, 4, 5, 6, 7]
+				res.push(arr1.remove(100)) // null [1, 4, 5, 6, 7]
+				res.push(arr1)
+				<: res
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            str("𩸽"),
-                            str("👉"),
-                            str("\u{1F3FF}"),
-                            str("👨"),
-                            str("\u{200d}"),
-                            str("👦")
+                            num(9),
+                            num(3),
+                            num(0),
+                            num(8),
+                            num(2),
+                            null(),
+                            arr([num(1), num(4), num(5), num(6), num(7)])
                         ])
                     )
                 },
@@ -3723,22 +9679,35 @@ mod primitive_props {
         }
 
         #[tokio::test]
-        async fn to_unicode_codepoint_arr() {
+        async fn at_without_default_value() {
             test(
                 r#"
-                let str = "𩸽👉🏿👨‍👦"
-                <: str.to_unicode_codepoint_arr()
+                let arr1 = [10, 20, 30]
+				<: [
+					arr1
+					arr1.at(0), arr1.at(1), arr1.at(2)
+					arr1.at(-3), arr1.at(-2), arr1.at(-1)
+					arr1.at(3), arr1.at(4), arr1.at(5)
+					arr1.at(-6), arr1.at(-5), arr1.at(-4)
+				]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            num(171581),
-                            num(128073),
-                            num(127999),
-                            num(128104),
-                            num(8205),
-                            num(128102)
+                            arr([num(10), num(20), num(30)]),
+                            num(10),
+                            num(20),
+                            num(30),
+                            num(10),
+                            num(20),
+                            num(30),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
                         ])
                     )
                 },
@@ -3748,22 +9717,36 @@ mod primitive_props {
         }
 
         #[tokio::test]
-        async fn to_char_arr() {
+        async fn at_with_default_value() {
             test(
                 r#"
-                let str = "abc𩸽👉🏿👨‍👦def"
-                <: str.to_char_arr()
+                let arr1 = [10, 20, 30]
+				<: [
+					arr1
+					arr1.at(0, 100), arr1.at(1, 100), arr1.at(2, 100)
+					arr1.at(-3, 100), arr1.at(-2, 100), arr1.at(-1, 100)
+					arr1.at(3, 100), arr1.at(4, 100), arr1.at(5, 100)
+					arr1.at(-6, 100), arr1.at(-5, 100), arr1.at(-4, 100)
+				]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            97, 98, 99, 55399, 56893, 55357, 56393, 55356, 57343, 55357, 56424,
-                            8205, 55357, 56422, 100, 101, 102
-                        ]
-                        .into_iter()
-                        .map(|u| str(String::from_utf16_lossy(&[u])))
-                        .collect::<Vec<Value>>())
+                            arr([num(10), num(20), num(30)]),
+                            num(10),
+                            num(20),
+                            num(30),
+                            num(10),
+                            num(20),
+                            num(30),
+                            num(100),
+                            num(100),
+                            num(100),
+                            num(100),
+                            num(100),
+                            num(100),
+                        ])
                     )
                 },
             )
@@ -3772,225 +9755,235 @@ mod primitive_props {
         }
 
         #[tokio::test]
-        async fn to_charcode_arr() {
+        async fn at_fraction() {
             test(
                 r#"
-                let str = "abc𩸽👉🏿👨‍👦def"
-                <: str.to_charcode_arr()
+                let arr1 = [10, 20, 30]
+				<: [
+					arr1
+					arr1.at(0.1), arr1.at(1.4), arr1.at(2.5)
+					arr1.at(-3.1), arr1.at(-2.4), arr1.at(-1.5)
+					arr1.at(3.1), arr1.at(4.4), arr1.at(5.5)
+					arr1.at(-6.1), arr1.at(-5.4), arr1.at(-4.5)
+				]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            num(97),
-                            num(98),
-                            num(99),
-                            num(55399),
-                            num(56893),
-                            num(55357),
-                            num(56393),
-                            num(55356),
-                            num(57343),
-                            num(55357),
-                            num(56424),
-                            num(8205),
-                            num(55357),
-                            num(56422),
-                            num(100),
-                            num(101),
-                            num(102),
+                            arr([num(10), num(20), num(30)]),
+                            num(10),
+                            num(20),
+                            num(30),
+                            num(10),
+                            num(20),
+                            num(30),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
+                            null(),
                         ])
                     )
                 },
             )
             .await
             .unwrap();
-        }
+        }
+    }
+}
+
+mod std {
+    use super::*;
+
+    mod core {
+        use super::*;
+
+        #[tokio::test]
+        async fn range() {
+            test("<: Core:range(1, 10)", |res| {
+                assert_eq!(
+                    res,
+                    arr([
+                        num(1),
+                        num(2),
+                        num(3),
+                        num(4),
+                        num(5),
+                        num(6),
+                        num(7),
+                        num(8),
+                        num(9),
+                        num(10)
+                    ])
+                )
+            })
+            .await
+            .unwrap();
+
+            test("<: Core:range(1, 1)", |res| assert_eq!(res, arr([num(1),])))
+                .await
+                .unwrap();
+
+            test("<: Core:range(9, 7)", |res| {
+                assert_eq!(res, arr([num(9), num(8), num(7),]))
+            })
+            .await
+            .unwrap();
+
+            for src in [
+                "Core:range(0, Math:Infinity)",
+                "Core:range(0 - Math:Infinity, 0)",
+                "Core:range(0, Math:sqrt(-1))",
+            ] {
+                let err = test(src, |_| {}).await.unwrap_err();
+                assert_eq!(err.code(), "runtime", "{src}");
+            }
+        }
+
+        #[tokio::test]
+        async fn to_str() {
+            test(r#"<: Core:to_str("abc")"#, |res| {
+                assert_eq!(res, str("abc"))
+            })
+            .await
+            .unwrap();
+
+            test(r#"<: Core:to_str(123)"#, |res| assert_eq!(res, str("123")))
+                .await
+                .unwrap();
+
+            test(r#"<: Core:to_str(true)"#, |res| {
+                assert_eq!(res, str("true"))
+            })
+            .await
+            .unwrap();
+
+            test(r#"<: Core:to_str(false)"#, |res| {
+                assert_eq!(res, str("false"))
+            })
+            .await
+            .unwrap();
+
+            test(r#"<: Core:to_str(null)"#, |res| {
+                assert_eq!(res, str("null"))
+            })
+            .await
+            .unwrap();
+
+            test(r#"<: Core:to_str({ a: "abc", b: 1234 })"#, |res| {
+                assert_eq!(res, str(r#"{ a: "abc", b: 1234 }"#))
+            })
+            .await
+            .unwrap();
+
+            test(r#"<: Core:to_str([ true, 123, null ])"#, |res| {
+                assert_eq!(res, str("[ true, 123, null ]"))
+            })
+            .await
+            .unwrap();
+
+            test(r#"<: Core:to_str(@( a, b, c ) {})"#, |res| {
+                assert_eq!(res, str("@( a, b, c ) { ... }"))
+            })
+            .await
+            .unwrap();
 
-        #[tokio::test]
-        async fn to_utf8_byte_arr() {
             test(
                 r#"
-                let str = "abc𩸽👉🏿👨‍👦def"
-                <: str.to_utf8_byte_arr()
+                let arr = []
+				arr.push(arr)
+				<: Core:to_str(arr)
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            num(97),
-                            num(98),
-                            num(99),
-                            num(240),
-                            num(169),
-                            num(184),
-                            num(189),
-                            num(240),
-                            num(159),
-                            num(145),
-                            num(137),
-                            num(240),
-                            num(159),
-                            num(143),
-                            num(191),
-                            num(240),
-                            num(159),
-                            num(145),
-                            num(168),
-                            num(226),
-                            num(128),
-                            num(141),
-                            num(240),
-                            num(159),
-                            num(145),
-                            num(166),
-                            num(100),
-                            num(101),
-                            num(102),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, str("[ ... ]")),
             )
             .await
             .unwrap();
-        }
 
-        #[tokio::test]
-        async fn starts_with_no_index() {
             test(
                 r#"
-                let str = "hello"
-                let empty = ""
-                <: [
-                    str.starts_with(""), str.starts_with("hello"),
-                    str.starts_with("he"), str.starts_with("ell"),
-                    empty.starts_with(""), empty.starts_with("he"),
-                ]
+                let arr = []
+				arr.push({ value: arr })
+				<: Core:to_str(arr)
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(false),
-                            bool(true),
-                            bool(false),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, str("[ { value: ... } ]")),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn starts_with_with_index() {
+        async fn inspect() {
+            test(r#"<: Core:inspect("abc")"#, |res| {
+                assert_eq!(res, str(r#""abc""#))
+            })
+            .await
+            .unwrap();
+
             test(
-                r#"
-                let str = "hello"
-                let empty = ""
-                <: [
-                    str.starts_with("", 4), str.starts_with("he", 0),
-                    str.starts_with("ll", 2), str.starts_with("lo", 3),
-                    str.starts_with("lo", -2), str.starts_with("hel", -5),
-                    str.starts_with("he", 2), str.starts_with("loa", 3),
-                    str.starts_with("lo", -6), str.starts_with("", -7),
-                    str.starts_with("lo", 6), str.starts_with("", 7),
-                    empty.starts_with("", 2), empty.starts_with("ll", 2),
-                ]
-                "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(false),
-                            bool(false),
-                            bool(false),
-                            bool(true),
-                            bool(false),
-                            bool(true),
-                            bool(true),
-                            bool(false),
-                        ])
-                    )
-                },
+                r#"<: Core:inspect({ a: [1, [2, [3]]], b: "c" }, { depth: 2 })"#,
+                |res| assert_eq!(res, str(r#"{ a: [ 1, ... ], b: "c" }"#)),
             )
             .await
             .unwrap();
-        }
 
-        #[tokio::test]
-        async fn ends_with_no_index() {
+            test(r#"<: Core:inspect([1, 2, 3, 4], { max_len: 8 })"#, |res| {
+                assert_eq!(res, str("[ 1, 2, ..."))
+            })
+            .await
+            .unwrap();
+
             test(
-                r#"
-                let str = "hello"
-                let empty = ""
-                <: [
-                    str.ends_with(""), str.ends_with("hello"),
-                    str.ends_with("lo"), str.ends_with("ell"),
-                    empty.ends_with(""), empty.ends_with("he"),
-                ]
-                "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(false),
-                            bool(true),
-                            bool(false),
-                        ])
-                    )
-                },
+                r#"<: Core:inspect([1, 2, 3, 4], { depth: 0, max_len: 100 })"#,
+                |res| assert_eq!(res, str("...")),
             )
             .await
             .unwrap();
+
+            let err = test(r#"Core:inspect([], { depth: -1 })"#, |_| {})
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Runtime(_))
+            ));
         }
 
         #[tokio::test]
-        async fn ends_with_with_index() {
+        async fn abort() {
+            let err = test(r#"Core:abort("hoge")"#, |_| {}).await.unwrap_err();
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::User(message))
+                    if message == "hoge"
+            ));
+        }
+
+        #[tokio::test]
+        async fn eval_sandbox() {
             test(
                 r#"
-                let str = "hello"
-                let empty = ""
+                let x = 1
                 <: [
-                    str.ends_with("", 3), str.ends_with("lo", 5),
-                    str.ends_with("ll", 4), str.ends_with("he", 2),
-                    str.ends_with("ll", -1), str.ends_with("he", -3),
-                    str.ends_with("he", 5), str.ends_with("lo", 3),
-                    str.ends_with("lo", -6), str.ends_with("", -7),
-                    str.ends_with("lo", 6), str.ends_with("", 7),
-                    empty.ends_with("", 2), empty.ends_with("ll", 2),
+                    Core:eval_sandbox("1 + 2")
+                    Core:eval_sandbox("Math:abs(x)").name
+                    Core:eval_sandbox("x + 1", { allow: ["x"] })
+                    Core:eval_sandbox("x = 2", { allow: ["x"] }).name
+                    Core:eval_sandbox("loop { 1 }", { max_step: 100 }).name
+                    Core:eval_sandbox("@(").name
                 ]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(true),
-                            bool(false),
-                            bool(false),
-                            bool(false),
-                            bool(true),
-                            bool(false),
-                            bool(true),
-                            bool(true),
-                            bool(false),
+                            num(3),
+                            str("runtime"),
+                            num(2),
+                            str("runtime"),
+                            str("runtime"),
+                            str("syntax"),
                         ])
                     )
                 },
@@ -4000,91 +9993,59 @@ mod primitive_props {
         }
 
         #[tokio::test]
-        async fn pad_start() {
-            test(
+        async fn eval_sandbox_charges_steps() {
+            let err = test(
                 r#"
-                let str = "abc"
-                <: [
-                    str.pad_start(0), str.pad_start(1), str.pad_start(2),
-                    str.pad_start(3), str.pad_start(4), str.pad_start(5),
-                    str.pad_start(0, "0"), str.pad_start(1, "0"), str.pad_start(2, "0"),
-                    str.pad_start(3, "0"), str.pad_start(4, "0"), str.pad_start(5, "0"),
-                    str.pad_start(0, "01"), str.pad_start(1, "01"), str.pad_start(2, "01"),
-                    str.pad_start(3, "01"), str.pad_start(4, "01"), str.pad_start(5, "01"),
-                ]
+                for 100 {
+                    Core:eval_sandbox("for 100 { 1 }")
+                }
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str(" abc"),
-                            str("  abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("0abc"),
-                            str("00abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("0abc"),
-                            str("01abc"),
-                        ])
-                    )
-                },
+                |_| {},
             )
             .await
-            .unwrap();
+            .unwrap_err();
+            assert_eq!(err.code(), "max_step_exceeded");
         }
 
         #[tokio::test]
-        async fn pad_end() {
+        async fn try_() {
             test(
                 r#"
-                let str = "abc"
                 <: [
-                    str.pad_end(0), str.pad_end(1), str.pad_end(2),
-                    str.pad_end(3), str.pad_end(4), str.pad_end(5),
-                    str.pad_end(0, "0"), str.pad_end(1, "0"), str.pad_end(2, "0"),
-                    str.pad_end(3, "0"), str.pad_end(4, "0"), str.pad_end(5, "0"),
-                    str.pad_end(0, "01"), str.pad_end(1, "01"), str.pad_end(2, "01"),
-                    str.pad_end(3, "01"), str.pad_end(4, "01"), str.pad_end(5, "01"),
+                    Core:try(@() { 1 })
+                    Core:try(@() { [1][5] }).name
+                    Core:try(@() { Math:abs("a") }).name
+                    Core:try(@() { Core:abort("oops") }).info.message
+                    Core:try(@() { undefined }, @(e) { `caught {e.name}` })
                 ]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc "),
-                            str("abc  "),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc0"),
-                            str("abc00"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc"),
-                            str("abc0"),
-                            str("abc01"),
+                            num(1),
+                            str("index_out_of_range"),
+                            str("runtime"),
+                            str("oops"),
+                            str("caught runtime"),
                         ])
                     )
                 },
             )
             .await
-            .unwrap();
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn try_does_not_catch_max_step() {
+            test(
+                r#"
+                Core:try(@() { loop { 1 } })
+                "#,
+                |_| {},
+            )
+            .await
+            .unwrap_err();
         }
     }
 
@@ -4092,88 +10053,114 @@ mod primitive_props {
         use super::*;
 
         #[tokio::test]
-        async fn len() {
-            test(
-                r#"
-                let arr = [1, 2, 3]
-                <: arr.len
-                "#,
-                |res| assert_eq!(res, num(3)),
-            )
+        async fn create() {
+            test("<: Arr:create(0)", |res| assert_eq!(res, arr([])))
+                .await
+                .unwrap();
+
+            test("<: Arr:create(3)", |res| {
+                assert_eq!(res, arr([null(), null(), null()]))
+            })
+            .await
+            .unwrap();
+
+            test("<: Arr:create(3, 1)", |res| {
+                assert_eq!(res, arr([num(1), num(1), num(1)]))
+            })
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn push() {
+        async fn parallel_map() {
             test(
                 r#"
-                let arr = [1, 2, 3]
-                arr.push(4)
-                <: arr
+                <: Arr:parallel_map([1, 2, 3], @(x, i) { x * 10 + i }, 2)
                 "#,
-                |res| assert_eq!(res, arr([num(1), num(2), num(3), num(4)])),
+                |res| assert_eq!(res, arr([num(10), num(21), num(32)])),
             )
             .await
             .unwrap();
-        }
 
-        #[tokio::test]
-        async fn unshift() {
             test(
                 r#"
-                let arr = [1, 2, 3]
-                arr.unshift(4)
-                <: arr
+                <: Arr:parallel_map([], @(x) { x })
                 "#,
-                |res| assert_eq!(res, arr([num(4), num(1), num(2), num(3)])),
+                |res| assert_eq!(res, arr([])),
             )
             .await
             .unwrap();
-        }
 
-        #[tokio::test]
-        async fn pop() {
             test(
                 r#"
-                let arr = [1, 2, 3]
-                let popped = arr.pop()
-                <: [popped, arr]
+                <: Arr:parallel_map([1, 2, 3], @(x) { x }, 0)
                 "#,
-                |res| assert_eq!(res, arr([num(3), arr([num(1), num(2)])])),
+                |_| {},
             )
             .await
-            .unwrap();
-        }
+            .unwrap_err();
 
-        #[tokio::test]
-        async fn shift() {
             test(
                 r#"
-                let arr = [1, 2, 3]
-                let shifted = arr.shift()
-                <: [shifted, arr]
+                <: Arr:parallel_map([1, "a", 3], @(x) { x * 2 }, 1)
                 "#,
-                |res| assert_eq!(res, arr([num(1), arr([num(2), num(3)])])),
+                |_| {},
             )
             .await
-            .unwrap();
+            .unwrap_err();
         }
 
         #[tokio::test]
-        async fn concat() {
+        async fn parallel_map_bounded() {
+            use ::std::sync::atomic::{AtomicUsize, Ordering};
+
+            let running = Arc::new(AtomicUsize::new(0));
+            let max = Arc::new(AtomicUsize::new(0));
+            let aiscript = new_interpreter([(
+                "slow".to_string(),
+                Value::fn_native({
+                    let running = running.clone();
+                    let max = max.clone();
+                    move |args, _| {
+                        let running = running.clone();
+                        let max = max.clone();
+                        async move {
+                            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                            max.fetch_max(now, Ordering::SeqCst);
+                            tokio::time::sleep(::std::time::Duration::from_millis(10)).await;
+                            running.fetch_sub(1, Ordering::SeqCst);
+                            Ok(args.into_iter().next().unwrap_or_default())
+                        }
+                        .boxed()
+                    }
+                }),
+            )]);
+            let (_, printed) = aiscript
+                .exec_collect(
+                    Parser::default()
+                        .parse("<: Arr:parallel_map([1, 2, 3, 4, 5], @(x) { slow(x) }, 2)")
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(printed, vec![arr([num(1), num(2), num(3), num(4), num(5)])]);
+            assert_eq!(max.load(Ordering::SeqCst), 2);
+        }
+
+        #[tokio::test]
+        async fn zip() {
             test(
                 r#"
-                let arr = [1, 2, 3]
-                let concated = arr.concat([4, 5])
-                <: [concated, arr]
+                let zipped = Arr:zip([1, 2, 3], ["a", "b"])
+                <: [zipped, Arr:unzip(zipped), Arr:zip()]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            arr([num(1), num(2), num(3), num(4), num(5)]),
-                            arr([num(1), num(2), num(3)])
+                            arr([arr([num(1), str("a")]), arr([num(2), str("b")])]),
+                            arr([arr([num(1), num(2)]), arr([str("a"), str("b")])]),
+                            arr([]),
                         ])
                     )
                 },
@@ -4183,505 +10170,727 @@ mod primitive_props {
         }
 
         #[tokio::test]
-        async fn slice() {
+        async fn range_by() {
             test(
                 r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                let sliced = arr.slice(2, 4)
-                <: [sliced, arr]
+                <: [
+                    Arr:range_by(0, 10, 5)
+                    Arr:range_by(1, 2, 0.5)
+                    Arr:range_by(3, 0, -2)
+                    Arr:range_by(0, -1, 1)
+                ]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            arr([str("camel"), str("duck")]),
-                            arr([
-                                str("ant"),
-                                str("bison"),
-                                str("camel"),
-                                str("duck"),
-                                str("elephant")
-                            ])
+                            arr([num(0), num(5), num(10)]),
+                            arr([num(1), num(1.5), num(2)]),
+                            arr([num(3), num(1)]),
+                            arr([]),
                         ])
                     )
                 },
             )
             .await
             .unwrap();
+
+            test("Arr:range_by(0, 1, 0)", |_| {}).await.unwrap_err();
+            test("Arr:range_by(0, Math:pow(10, 300), 1)", |_| {})
+                .await
+                .unwrap_err();
+            test("Arr:range_by(0, 10, Math:pow(10, -300))", |_| {})
+                .await
+                .unwrap_err();
+            test("Arr:range_by(0, Math:Infinity, 1)", |_| {})
+                .await
+                .unwrap_err();
         }
 
         #[tokio::test]
-        async fn slice_out_of_range() {
-            test(
-                r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                <: arr.slice(4, 2)
-                "#,
-                |res| assert_eq!(res, arr([])),
-            )
+        async fn chunk() {
+            test("<: Arr:chunk([1, 2, 3, 4, 5], 2)", |res| {
+                assert_eq!(
+                    res,
+                    arr([arr([num(1), num(2)]), arr([num(3), num(4)]), arr([num(5)]),])
+                )
+            })
             .await
             .unwrap();
 
+            test("Arr:chunk([1], 0)", |_| {}).await.unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn group_by() {
             test(
                 r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                <: arr.slice(-2, 4)
+                <: Arr:group_by([1, 2, 3, 4, 5], @(x) { if x % 2 == 0 "even" else "odd" })
                 "#,
-                |res| assert_eq!(res, arr([str("duck")])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        obj([
+                            ("odd", arr([num(1), num(3), num(5)])),
+                            ("even", arr([num(2), num(4)])),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn sort_by_keys() {
             test(
                 r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                <: arr.slice(4, -2)
+                let users = [
+                    { name: 'b', age: 20 }
+                    { name: 'a', age: 30 }
+                    { name: 'c', age: 20 }
+                    { name: 'a', age: 20 }
+                ]
+                let sorted = Arr:sort_by_keys(users, [@(u) { u.age }, @(u) { u.name }], ['desc'])
+                <: sorted.map(@(u) { `{u.name}{u.age}` })
                 "#,
-                |res| assert_eq!(res, arr([])),
+                |res| assert_eq!(res, arr([str("a30"), str("a20"), str("b20"), str("c20")])),
             )
             .await
             .unwrap();
 
-            test(
-                r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                <: arr.slice(-2, -4)
-                "#,
-                |res| assert_eq!(res, arr([])),
-            )
+            test("Arr:sort_by_keys([1], [@(v) { v }], ['up'])", |_| {})
+                .await
+                .unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn distinct() {
+            test("<: Arr:distinct([1, 2, 1, [3], [3], '1'])", |res| {
+                assert_eq!(res, arr([num(1), num(2), arr([num(3)]), str("1")]))
+            })
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn stats() {
             test(
                 r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                <: arr.slice(-4, -2)
+                let a = [3, 1, 2]
+                <: [Arr:sum(a), Arr:avg(a), Arr:min(a), Arr:max(a), Arr:sum([]), Arr:avg([]), Arr:min([])]
                 "#,
-                |res| assert_eq!(res, arr([str("bison"), str("camel")])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([num(6), num(2), num(1), num(3), num(0), null(), null()])
+                    )
+                },
             )
             .await
             .unwrap();
 
-            test(
-                r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                <: arr.slice(12, 14)
-                "#,
-                |res| assert_eq!(res, arr([])),
-            )
+            test("Arr:sum([1, '2'])", |_| {}).await.unwrap_err();
+        }
+    }
+
+    mod math {
+        use super::*;
+
+        #[tokio::test]
+        async fn trig() {
+            test("<: Math:sin(Math:PI / 2)", |res| assert_eq!(res, num(1)))
+                .await
+                .unwrap();
+
+            test("<: Math:sin(0 - (Math:PI / 2))", |res| {
+                assert_eq!(res, num(-1))
+            })
             .await
             .unwrap();
 
-            test(
-                r#"
-                let arr = ["ant", "bison", "camel", "duck", "elephant"]
-                <: arr.slice(-14, -12)
-                "#,
-                |res| assert_eq!(res, arr([])),
-            )
+            test("<: Math:sin(Math:PI / 4) * Math:cos(Math:PI / 4)", |res| {
+                assert!((f64::try_from(res).unwrap() - 0.5).abs() <= f64::EPSILON)
+            })
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn join() {
-            test(
-                r#"
-                let arr = ["a", "b", "c"]
-                <: arr.join("-")
-                "#,
-                |res| assert_eq!(res, str("a-b-c")),
-            )
+        async fn abs() {
+            test("<: Math:abs(1 - 6)", |res| assert_eq!(res, num(5)))
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn pow_and_sqrt() {
+            test("<: Math:sqrt(3^2 + 4^2)", |res| assert_eq!(res, num(5)))
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn round() {
+            test("<: Math:round(3.14)", |res| assert_eq!(res, num(3)))
+                .await
+                .unwrap();
+
+            test("<: Math:round(-1.414213)", |res| assert_eq!(res, num(-1)))
+                .await
+                .unwrap();
+        }
+
+        fn assert_num(res: Value, expected: f64) {
+            let res = f64::try_from(res).unwrap();
+            if expected.is_nan() {
+                assert!(res.is_nan(), "{res} is not NaN");
+            } else {
+                assert_eq!(res, expected);
+                assert_eq!(res.is_sign_negative(), expected.is_sign_negative());
+            }
+        }
+
+        #[tokio::test]
+        async fn js_semantics() {
+            for (src, expected) in [
+                ("Math:round(2.5)", 3.0),
+                ("Math:round(-2.5)", -2.0),
+                ("Math:round(-0.5)", -0.0),
+                ("Math:round(0.49999999999999994)", 0.0),
+                ("Math:sign(-3)", -1.0),
+                ("Math:sign(0 - 0)", 0.0),
+                ("Math:sign(-0)", -0.0),
+                ("Math:sign(Math:sqrt(-1))", f64::NAN),
+                ("Math:max(1, Math:sqrt(-1))", f64::NAN),
+                ("Math:max(-0, 0)", 0.0),
+                ("Math:min(0, -0)", -0.0),
+                ("Math:pow(1, Math:Infinity)", f64::NAN),
+                ("Math:pow(1, Math:sqrt(-1))", f64::NAN),
+                ("Math:pow(Math:sqrt(-1), 0)", 1.0),
+                ("Math:hypot([3, 4, 12])", 13.0),
+                ("Math:hypot([10^200, 10^200, 0])", 1e200 * 2f64.sqrt()),
+                (
+                    "Math:hypot([Math:sqrt(-1), Math:Infinity, 1])",
+                    f64::INFINITY,
+                ),
+                ("Math:hypot([Math:sqrt(-1), 1, 1])", f64::NAN),
+                ("Math:imul(4294967295, 5)", -5.0),
+                ("Math:imul(2147483647, 2)", -2.0),
+                ("Math:clz32(4294967295)", 0.0),
+                ("Math:clz32(-1)", 0.0),
+                ("Math:clz32(0)", 32.0),
+                ("Math:trunc(-4.7)", -4.0),
+                ("Math:cbrt(-8)", -2.0),
+                ("Math:log2(8)", 3.0),
+                ("Math:log10(1000)", 3.0),
+                ("Math:log(-1)", f64::NAN),
+                ("Math:atan2(0, -1)", ::std::f64::consts::PI),
+            ] {
+                let res = test(src, |_| {}).await.unwrap();
+                assert_num(res, expected);
+            }
+        }
+
+        #[tokio::test]
+        async fn clamp() {
+            for (src, expected) in [
+                ("Math:clamp(5, 0, 3)", 3.0),
+                ("Math:clamp(-5, 0, 3)", 0.0),
+                ("Math:clamp(1.5, 0, 3)", 1.5),
+                ("Math:clamp(Math:sqrt(-1), 0, 3)", f64::NAN),
+                ("Math:clamp(1, Math:sqrt(-1), 3)", f64::NAN),
+            ] {
+                let res = test(src, |_| {}).await.unwrap();
+                assert_num(res, expected);
+            }
+            test("<: Math:clamp(1, 3, 0)", |_| {}).await.unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn ceil() {
+            test("<: Math:ceil(2.71828)", |res| assert_eq!(res, num(3)))
+                .await
+                .unwrap();
+
+            test("<: Math:ceil(0 - Math:PI)", |res| assert_eq!(res, num(-3)))
+                .await
+                .unwrap();
+
+            test("<: Math:ceil(1 / Math:Infinity)", |res| {
+                assert_eq!(res, num(0))
+            })
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn floor() {
+            test("<: Math:floor(23.14069)", |res| assert_eq!(res, num(23)))
+                .await
+                .unwrap();
+
+            test("<: Math:floor(Math:Infinity / 0)", |res| {
+                assert_eq!(res, num(f64::INFINITY))
+            })
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn map() {
-            test(
-                r#"
-                let arr = [1, 2, 3]
-                <: arr.map(@(item) { item * 2 })
-                "#,
-                |res| assert_eq!(res, arr([num(2), num(4), num(6)])),
-            )
-            .await
-            .unwrap();
+        async fn min() {
+            test("<: Math:min(2, 3)", |res| assert_eq!(res, num(2)))
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn max() {
+            test("<: Math:max(-2, -3)", |res| assert_eq!(res, num(-2)))
+                .await
+                .unwrap();
         }
 
         #[tokio::test]
-        async fn map_with_index() {
-            test(
-                r#"
-                let arr = [1, 2, 3]
-                <: arr.map(@(item, index) { item * index })
-                "#,
-                |res| assert_eq!(res, arr([num(0), num(2), num(6)])),
-            )
-            .await
-            .unwrap();
+        async fn rnd_with_arg() {
+            test("<: Math:rnd(1, 1.5)", |res| assert_eq!(res, num(1)))
+                .await
+                .unwrap();
         }
 
         #[tokio::test]
-        async fn filter() {
+        async fn gen_rng() {
+            // 2つのシード値から1~maxの乱数をn回生成して一致率を見る
             test(
                 r#"
-                let arr = [1, 2, 3]
-                <: arr.filter(@(item) { item != 2 })
+                @test(seed1, seed2) {
+                    let n = 100
+                    let max = 100000
+                    let threshold = 0.05
+                    let random1 = Math:gen_rng(seed1)
+                    let random2 = Math:gen_rng(seed2)
+                    var same = 0
+                    for n {
+                        if random1(1, max) == random2(1, max) {
+                            same += 1
+                        }
+                    }
+                    let rate = same / n
+                    if seed1 == seed2 { rate == 1 }
+                    else { rate < threshold }
+                }
+                let seed1 = `{Util:uuid()}`
+                let seed2 = `{Date:year()}`
+                <: [
+                    test(seed1, seed1)
+                    test(seed1, seed2)
+                ]
                 "#,
-                |res| assert_eq!(res, arr([num(1), num(3)])),
+                |res| assert_eq!(res, arr([bool(true), bool(true)])),
             )
             .await
             .unwrap();
         }
+    }
+
+    mod num {
+        use super::*;
+
+        async fn eval_str(src: &str) -> String {
+            String::try_from(test(src, |_| {}).await.unwrap()).unwrap()
+        }
 
         #[tokio::test]
-        async fn filter_with_index() {
-            test(
-                r#"
-                let arr = [1, 2, 3, 4]
-                <: arr.filter(@(item, index) { item != 2 && index != 3 })
-                "#,
-                |res| assert_eq!(res, arr([num(1), num(3)])),
-            )
-            .await
-            .unwrap();
+        async fn to_fixed() {
+            for (src, expected) in [
+                ("Num:to_fixed(1.005, 2)", "1.00"),
+                ("Num:to_fixed(0.5)", "1"),
+                ("Num:to_fixed(2.5)", "3"),
+                ("Num:to_fixed(-2.5)", "-3"),
+                ("Num:to_fixed(1.45, 1)", "1.4"),
+                ("Num:to_fixed(123.456, 5)", "123.45600"),
+                ("Num:to_fixed(0.000001, 2)", "0.00"),
+                ("Num:to_fixed(-0.0001, 2)", "-0.00"),
+                ("Num:to_fixed(99.99, 1)", "100.0"),
+                ("Num:to_fixed(10^21, 2)", "1e+21"),
+                ("Num:to_fixed(Math:sqrt(-1), 2)", "NaN"),
+            ] {
+                assert_eq!(eval_str(src).await, expected, "{src}");
+            }
+            test("Num:to_fixed(1, 101)", |_| {}).await.unwrap_err();
+            test("Num:to_fixed(1, 1.5)", |_| {}).await.unwrap_err();
         }
 
         #[tokio::test]
-        async fn reduce() {
-            test(
-                r#"
-                let arr = [1, 2, 3, 4]
-                <: arr.reduce(@(accumulator, currentValue) { (accumulator + currentValue) })
-                "#,
-                |res| assert_eq!(res, num(10)),
-            )
-            .await
-            .unwrap();
+        async fn to_precision() {
+            for (src, expected) in [
+                ("Num:to_precision(123.456, 4)", "123.5"),
+                ("Num:to_precision(0.00001234, 2)", "0.000012"),
+                ("Num:to_precision(0.0000001234, 2)", "1.2e-7"),
+                ("Num:to_precision(123456, 2)", "1.2e+5"),
+                ("Num:to_precision(99.99, 3)", "100"),
+                ("Num:to_precision(99.99, 2)", "1.0e+2"),
+                ("Num:to_precision(0, 3)", "0.00"),
+                ("Num:to_precision(-1.5, 1)", "-2"),
+                ("Num:to_precision(Math:Infinity, 2)", "Infinity"),
+            ] {
+                assert_eq!(eval_str(src).await, expected, "{src}");
+            }
+            test("Num:to_precision(1, 0)", |_| {}).await.unwrap_err();
         }
 
         #[tokio::test]
-        async fn reduce_with_index() {
-            test(
-                r#"
-                let arr = [1, 2, 3, 4]
-                <: arr.reduce(@(accumulator, currentValue, index) { (accumulator + (currentValue * index)) } 0)
-                "#,
-                |res| assert_eq!(res, num(20)),
-            )
-            .await
-            .unwrap();
+        async fn to_str_radix() {
+            for (src, expected) in [
+                ("(255).to_str(16)", "ff"),
+                ("(-255).to_str(2)", "-11111111"),
+                ("(0.5).to_str(2)", "0.1"),
+                (
+                    "(0.1).to_str(2)",
+                    "0.0001100110011001100110011001100110011001100110011001101",
+                ),
+                ("(3.75).to_str(16)", "3.c"),
+                ("(35).to_str(36)", "z"),
+                ("(2^60).to_str(16)", "1000000000000000"),
+                ("(0.1).to_str(10)", "0.1"),
+                ("(10^21).to_str(10)", "1e+21"),
+                ("(0.0000001).to_str(10)", "1e-7"),
+                ("Num:to_hex(-255)", "-ff"),
+                ("Num:to_hex(255.5)", "ff.8"),
+            ] {
+                assert_eq!(eval_str(src).await, expected, "{src}");
+            }
+            test("(1).to_str(1)", |_| {}).await.unwrap_err();
+            test("(1).to_str(37)", |_| {}).await.unwrap_err();
         }
 
         #[tokio::test]
-        async fn reduce_of_empty_array_without_initial_value() {
-            let err = test(
-                r#"
-                let arr = [1, 2, 3, 4]
-                <: [].reduce(@(){})
-                "#,
-                |_| {},
-            )
-            .await
-            .unwrap_err();
-            assert!(matches!(
-                err,
-                AiScriptError::Runtime(AiScriptRuntimeError::Runtime(message))
-                    if &message == "Reduce of empty array without initial value"
-            ));
+        async fn parse() {
+            for (src, expected) in [
+                ("Num:parse_int('42px')", 42.0),
+                ("Num:parse_int('  -17')", -17.0),
+                ("Num:parse_int('0x1F')", 31.0),
+                ("Num:parse_int('0x1F', 16)", 31.0),
+                ("Num:parse_int('0x1F', 10)", 0.0),
+                ("Num:parse_int('ff', 16)", 255.0),
+                ("Num:parse_int('z', 36)", 35.0),
+                ("Num:parse_int('101', 2)", 5.0),
+                ("Num:parse_int('12.9')", 12.0),
+                ("Num:parse_float('2.5abc')", 2.5),
+                ("Num:parse_float('  -.5e1x')", -5.0),
+                ("Num:parse_float('1e')", 1.0),
+                ("Num:parse_float('-Infinityx')", f64::NEG_INFINITY),
+            ] {
+                let res = f64::try_from(test(src, |_| {}).await.unwrap()).unwrap();
+                assert_eq!(res, expected, "{src}");
+            }
+            for src in [
+                "Num:parse_int('abc')",
+                "Num:parse_int('-')",
+                "Num:parse_int('1', 1)",
+                "Num:parse_int('1', 37)",
+                "Num:parse_int('1', -1)",
+                "Num:parse_float('.e1')",
+                "Num:parse_float('')",
+            ] {
+                let res = f64::try_from(test(src, |_| {}).await.unwrap()).unwrap();
+                assert!(res.is_nan(), "{src}");
+            }
         }
+    }
+
+    mod obj {
+        use super::*;
 
         #[tokio::test]
-        async fn find() {
+        async fn keys() {
             test(
                 r#"
-                let arr = ["abc", "def", "ghi"]
-                <: arr.find(@(item) { item.incl("e") })
+                let o = { a: 1; b: 2; c: 3; }
+
+                <: Obj:keys(o)
                 "#,
-                |res| assert_eq!(res, str("def")),
+                |res| assert_eq!(res, arr([str("a"), str("b"), str("c")])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn find_with_index() {
+        async fn vals() {
             test(
                 r#"
-                let arr = ["abc1", "def1", "ghi1", "abc2", "def2", "ghi2"]
-                <: arr.find(@(item, index) { item.incl("e") && index > 1 })
+                let o = { _nul: null; _num: 24; _str: 'hoge'; _arr: []; _obj: {}; }
+
+                <: Obj:vals(o)
                 "#,
-                |res| assert_eq!(res, str("def2")),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            null(),
+                            num(24),
+                            str("hoge"),
+                            arr([]),
+                            obj([] as [(String, Value); 0])
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn incl() {
+        async fn kvs() {
             test(
                 r#"
-                let arr = ["abc", "def", "ghi"]
-                <: [arr.incl("def"), arr.incl("jkl")]
+                let o = { a: 1; b: 2; c: 3; }
+
+                <: Obj:kvs(o)
                 "#,
-                |res| assert_eq!(res, arr([bool(true), bool(false)])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([str("a"), num(1)]),
+                            arr([str("b"), num(2)]),
+                            arr([str("c"), num(3)])
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn index_of() {
+        async fn merge() {
             test(
                 r#"
-                let arr = [0,1,2,3,4,0,1,2,3,4]
-                <: [
-                    arr.index_of(3) == 3,
-                    arr.index_of(5) == -1,
-                    arr.index_of(3, 3) == 3,
-                    arr.index_of(3, 4) == 8,
-                    arr.index_of(3, -1) == -1,
-                    arr.index_of(3, -2) == 8,
-                    arr.index_of(3, -7) == 3,
-                    arr.index_of(3, 10) == -1,
-                ].map(@(v){if (v) '1' else '0'}).join()
+                let o1 = { a: 1; b: 2; }
+                let o2 = { b: 3; c: 4; }
+
+                <: Obj:merge(o1, o2)
                 "#,
-                |res| assert_eq!(res, str("11111111")),
+                |res| assert_eq!(res, obj([("a", num(1)), ("b", num(3)), ("c", num(4)),])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn reverse() {
+        async fn insertion_order() {
             test(
                 r#"
-                let arr = [1, 2, 3]
-                arr.reverse()
-                <: arr
+                let o = { b: 1, a: 2, c: 3 }
+                o.a = 4
+                o.d = 5
+                Obj:set(o, 'b', 6)
+                let m = Obj:merge({ z: 1, c: 2 }, o)
+                let j = Json:parse('{"y": 1, "x": 2, "y": 3}')
+                <: [
+                    Obj:keys(o)
+                    Obj:keys(m)
+                    Obj:keys(j)
+                    Obj:keys(Obj:copy(o))
+                    Json:stringify(o)
+                    Core:to_str(o)
+                    Obj:keys(Json:parse(Json:stringify(m)))
+                ]
                 "#,
-                |res| assert_eq!(res, arr([num(3), num(2), num(1)])),
+                |res| {
+                    let strs = |keys: &[&str]| arr(keys.iter().map(|key| str(*key)));
+                    assert_eq!(
+                        res,
+                        arr([
+                            strs(&["b", "a", "c", "d"]),
+                            strs(&["z", "c", "b", "a", "d"]),
+                            strs(&["y", "x"]),
+                            strs(&["b", "a", "c", "d"]),
+                            str(r#"{"b":6,"a":4,"c":3,"d":5}"#),
+                            str("{ b: 6, a: 4, c: 3, d: 5 }"),
+                            strs(&["z", "c", "b", "a", "d"]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn copy() {
+        async fn sort_keys() {
             test(
                 r#"
-                let arr = [1, 2, 3]
-                let copied = arr.copy()
-                copied.reverse()
-                <: [copied, arr]
+                let o = { b: 1, _: 2, a: 3, B: 4 }
+                Obj:set(o, 'あ', 5)
+                Obj:set(o, '10', 6)
+                Obj:set(o, '9', 7)
+                let sorted = Obj:sort_keys(o)
+                <: [Obj:keys(sorted), Obj:keys(o), sorted == o]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
-                        arr([arr([num(3), num(2), num(1)]), arr([num(1), num(2), num(3)])])
+                        arr([
+                            arr(["10", "9", "B", "_", "a", "b", "あ"].map(str)),
+                            arr(["b", "_", "a", "B", "あ", "10", "9"].map(str)),
+                            bool(true),
+                        ])
                     )
                 },
             )
             .await
             .unwrap();
         }
+    }
+
+    mod str {
+        use super::*;
 
         #[tokio::test]
-        async fn sort_num_array() {
+        async fn lf() {
             test(
                 r#"
-                var arr = [2, 10, 3]
-				let comp = @(a, b) { a - b }
-				arr.sort(comp)
-				<: arr
+                <: Str:lf
                 "#,
-                |res| assert_eq!(res, arr([num(2), num(3), num(10)])),
+                |res| assert_eq!(res, str("\n")),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn sort_string_array_with_str_lt() {
+        async fn concat_all() {
             test(
                 r#"
-                var arr = ["hoge", "huga", "piyo", "hoge"]
-				arr.sort(Str:lt)
-				<: arr
+                <: Str:concat_all(["ai", "script", ""])
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([str("hoge"), str("hoge"), str("huga"), str("piyo")])
-                    )
-                },
+                |res| assert_eq!(res, str("aiscript")),
             )
             .await
             .unwrap();
+
+            let err = test(
+                r#"
+                <: Str:concat_all(["ai", 1])
+                "#,
+                |_| {},
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, AiScriptError::Runtime(_)));
         }
 
         #[tokio::test]
-        async fn sort_string_array_with_str_gt() {
+        async fn from_codepoint() {
             test(
                 r#"
-                var arr = ["hoge", "huga", "piyo", "hoge"]
-				arr.sort(Str:gt)
-				<: arr
+                <: Str:from_codepoint(65)
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([str("piyo"), str("huga"), str("hoge"), str("hoge")])
-                    )
-                },
+                |res| assert_eq!(res, str("A")),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn sort_object_array() {
+        async fn from_unicode_codepoints() {
             test(
                 r#"
-                var arr = [{x: 2}, {x: 10}, {x: 3}]
-				let comp = @(a, b) { a.x - b.x }
-
-				arr.sort(comp)
-				<: arr
-                "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            obj([("x", num(2))]),
-                            obj([("x", num(3))]),
-                            obj([("x", num(10))])
-                        ])
-                    )
-                },
+                <: Str:from_unicode_codepoints([171581, 128073, 127999, 128104, 8205, 128102])
+			    "#,
+                |res| assert_eq!(res, str("𩸽👉🏿👨‍👦")),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn fill() {
+        async fn from_utf8_bytes() {
             test(
                 r#"
-                var arr1 = [0, 1, 2]
-				let arr2 = arr1.fill(3)
-				let arr3 = [0, 1, 2].fill(3, 1)
-				let arr4 = [0, 1, 2].fill(3, 1, 2)
-				let arr5 = [0, 1, 2].fill(3, -2, -1)
-				<: [arr1, arr2, arr3, arr4, arr5]
+                <: Str:from_utf8_bytes([240, 169, 184, 189, 240, 159, 145, 137, 240, 159, 143, 191, 240, 159, 145, 168, 226, 128, 141, 240, 159, 145, 166])
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            arr([num(3), num(3), num(3)]), //target changed
-                            arr([num(3), num(3), num(3)]),
-                            arr([num(0), num(3), num(3)]),
-                            arr([num(0), num(3), num(2)]),
-                            arr([num(0), num(3), num(2)]),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, str("𩸽👉🏿👨‍👦")),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn repeat() {
+        async fn normalize() {
             test(
                 r#"
-                var arr1 = [0, 1, 2]
-				let arr2 = arr1.repeat(3)
-				let arr3 = arr1.repeat(0)
-				<: [arr1, arr2, arr3]
+                let s = `Cafe{Str:from_codepoint(769)} ｶﾞ①`
+                <: [
+                    Str:normalize(s),
+                    Str:normalize(s, "NFKC"),
+                    Str:normalize("é", "NFD") == `e{Str:from_codepoint(769)}`,
+                ]
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            arr([num(0), num(1), num(2)]), // target not changed
-                            arr([
-                                num(0),
-                                num(1),
-                                num(2),
-                                num(0),
-                                num(1),
-                                num(2),
-                                num(0),
-                                num(1),
-                                num(2),
-                            ]),
-                            arr([]),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, arr([str("Café ｶﾞ①"), str("Café ガ1"), bool(true)])),
             )
             .await
             .unwrap();
+
+            let err = test(r#"Str:normalize("a", "nfc")"#, |_| {})
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Runtime(_))
+            ));
         }
 
         #[tokio::test]
-        async fn splice_full() {
+        async fn casefold() {
             test(
                 r#"
-                let arr1 = [0, 1, 2, 3]
-				let arr2 = arr1.splice(1, 2, [10])
-				<: [arr1, arr2]
+                <: [Str:casefold("Straße"), Str:casefold("HELLO") == Str:casefold("hello")]
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([arr([num(0), num(10), num(3)]), arr([num(1), num(2)]),])
-                    )
-                },
+                |res| assert_eq!(res, arr([str("strasse"), bool(true)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn splice_negative_index() {
+        async fn levenshtein() {
             test(
                 r#"
-                let arr1 = [0, 1, 2, 3]
-				let arr2 = arr1.splice(-1, 0, [10, 20])
-				<: [arr1, arr2]
+                <: [
+                    Str:levenshtein("kitten", "sitting"),
+                    Str:levenshtein("", "abc"),
+                    Str:levenshtein("abc", "abc"),
+                    Str:levenshtein("👨‍👦a", "a"),
+                ]
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            arr([num(0), num(1), num(2), num(10), num(20), num(3)]),
-                            arr([]),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, arr([num(3), num(3), num(0), num(1)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn splice_larger_index() {
+        async fn charcode_at() {
             test(
                 r#"
-                let arr1 = [0, 1, 2, 3]
-				let arr2 = arr1.splice(4, 100, [10, 20])
-				<: [arr1, arr2]
+                <: "aiscript".split().map(@(x, _) { x.charcode_at(0) })
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            arr([num(0), num(1), num(2), num(3), num(10), num(20)]),
-                            arr([]),
+                            num(97),
+                            num(105),
+                            num(115),
+                            num(99),
+                            num(114),
+                            num(105),
+                            num(112),
+                            num(116),
                         ])
                     )
                 },
@@ -4689,50 +10898,21 @@ mod primitive_props {
             .await
             .unwrap();
         }
+    }
 
-        #[tokio::test]
-        async fn splice_single_argument() {
-            test(
-                r#"
-                let arr1 = [0, 1, 2, 3]
-				let arr2 = arr1.splice(1)
-				<: [arr1, arr2]
-                "#,
-                |res| assert_eq!(res, arr([arr([num(0)]), arr([num(1), num(2), num(3)]),])),
-            )
-            .await
-            .unwrap();
-        }
+    mod uri {
+        use super::*;
 
         #[tokio::test]
-        async fn flat() {
+        async fn encode_full() {
             test(
                 r#"
-                var arr1 = [0, [1], [2, 3], [4, [5, 6]]]
-				let arr2 = arr1.flat()
-				let arr3 = arr1.flat(2)
-				<: [arr1, arr2, arr3]
+                <: Uri:encode_full("https://example.com/?q=あいちゃん")
                 "#,
                 |res| {
                     assert_eq!(
                         res,
-                        arr([
-                            arr([
-                                num(0),
-                                arr([num(1)]),
-                                arr([num(2), num(3)]),
-                                arr([num(4), arr([num(5), num(6)])])
-                            ]), // target not changed
-                            arr([
-                                num(0),
-                                num(1),
-                                num(2),
-                                num(3),
-                                num(4),
-                                arr([num(5), num(6)]),
-                            ]),
-                            arr([num(0), num(1), num(2), num(3), num(4), num(5), num(6),]),
-                        ])
+                        str("https://example.com/?q=%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")
                     )
                 },
             )
@@ -4741,193 +10921,130 @@ mod primitive_props {
         }
 
         #[tokio::test]
-        async fn flat_map() {
+        async fn encode_component() {
             test(
                 r#"
-                let arr1 = [0, 1, 2]
-				let arr2 = ["a", "b"]
-				let arr3 = arr1.flat_map(@(x){ arr2.map(@(y){ [x, y] }) })
-				<: [arr1, arr3]
+                <: Uri:encode_component("https://example.com/?q=あいちゃん")
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            arr([num(0), num(1), num(2)]), // target not changed
-                            arr([
-                                arr([num(0), str("a")]),
-                                arr([num(0), str("b")]),
-                                arr([num(1), str("a")]),
-                                arr([num(1), str("b")]),
-                                arr([num(2), str("a")]),
-                                arr([num(2), str("b")]),
-                            ]),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, str("https%3A%2F%2Fexample.com%2F%3Fq%3D%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn every() {
+        async fn decode_full() {
             test(
                 r#"
-                let arr1 = [0, 1, 2, 3]
-				let res1 = arr1.every(@(v,i){v==0 || i > 0})
-				let res2 = arr1.every(@(v,i){v==0 && i > 0})
-				let res3 = [].every(@(v,i){false})
-				<: [arr1, res1, res2, res3]
+                <: Uri:decode_full("https%3A%2F%2Fexample.com%2F%3Fq%3D%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            arr([num(0), num(1), num(2), num(3)]), // target not changed
-                            bool(true),
-                            bool(false),
-                            bool(true),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, str("https%3A%2F%2Fexample.com%2F%3Fq%3Dあいちゃん")),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn some() {
+        async fn decode_component() {
             test(
                 r#"
-                let arr1 = [0, 1, 2, 3]
-				let res1 = arr1.some(@(v,i){v%2==0 && i <= 2})
-				let res2 = arr1.some(@(v,i){v%2==0 && i > 2})
-				<: [arr1, res1, res2]
+                <: Uri:decode_component("https%3A%2F%2Fexample.com%2F%3Fq%3D%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            arr([num(0), num(1), num(2), num(3)]), // target not changed
-                            bool(true),
-                            bool(false),
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, str("https://example.com/?q=あいちゃん")),
             )
             .await
             .unwrap();
         }
+    }
+
+    mod bin {
+        use super::*;
 
         #[tokio::test]
-        async fn insert() {
+        async fn base64() {
             test(
                 r#"
-                let arr1 = [0, 1, 2]
-				let res = []
-				res.push(arr1.insert(3, 10)) // [0, 1, 2, 10]
-				res.push(arr1.insert(2, 20)) // [0, 1, 20, 2, 10]
-				res.push(arr1.insert(0, 30)) // [30, 0, 1, 20, 2, 10]
-				res.push(arr1.insert(-1, 40)) // [30, 0, 1, 20, 2, 40, 10]
-				res.push(arr1.insert(-4, 50)) // [30, 0, 1, 50, 20, 2, 40, 10]
-				res.push(arr1.insert(100, 60)) // [30, 0, 1, 50, 20, 2, 40, 10, 60]
-				res.push(arr1)
-				<: res
+                let bin = Bin:from_base64("AGFp/w==")
+                <: [bin, Bin:to_base64(bin)]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
-                        arr([
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                            arr([
-                                num(30),
-                                num(0),
-                                num(1),
-                                num(50),
-                                num(20),
-                                num(2),
-                                num(40),
-                                num(10),
-                                num(60)
-                            ])
-                        ])
+                        arr([Value::bin([0, b'a', b'i', 255]), str("AGFp/w==")])
                     )
                 },
             )
             .await
             .unwrap();
+            test("Bin:from_base64('a')", |_| {}).await.unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn hex() {
+            test(
+                r#"
+                let bin = Bin:from_hex("0061FF")
+                <: [bin, Bin:to_hex(bin)]
+                "#,
+                |res| assert_eq!(res, arr([Value::bin([0, b'a', 255]), str("0061ff")])),
+            )
+            .await
+            .unwrap();
+            test("Bin:from_hex('0')", |_| {}).await.unwrap_err();
         }
 
         #[tokio::test]
-        async fn remove() {
+        async fn from_arr() {
             test(
                 r#"
-                let arr1 = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
-				let res = []
-				res.push(arr1.remove(9)) // 9 [0, 1, 2, 3, 4, 5, 6, 7, 8]
-				res.push(arr1.remove(3)) // 3 [0, 1, 2, 4, 5, 6, 7, 8]
-				res.push(arr1.remove(0)) // 0 [1, 2, 4, 5, 6, 7, 8]
-				res.push(arr1.remove(-1)) // 8 [1, 2, 4, 5, 6, 7]
-				res.push(arr1.remove(-5)) // 2 [1, 4, 5, 6, 7]
-				res.push(arr1.remove(100)) // null [1, 4, 5, 6, 7]
-				res.push(arr1)
-				<: res
+                let bin = Bin:from_arr([0, 97, 255])
+                <: [bin.to_arr(), bin.len, bin[1]]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
-                        arr([
-                            num(9),
-                            num(3),
-                            num(0),
-                            num(8),
-                            num(2),
-                            null(),
-                            arr([num(1), num(4), num(5), num(6), num(7)])
-                        ])
+                        super::arr([super::arr([num(0), num(97), num(255)]), num(3), num(97)])
                     )
                 },
             )
             .await
             .unwrap();
+            test("Bin:from_arr([256])", |_| {}).await.unwrap_err();
+            test("Bin:from_arr([0])[1]", |_| {}).await.unwrap_err();
+            test("Bin:from_arr([0])[-1]", |_| {}).await.unwrap_err();
         }
 
         #[tokio::test]
-        async fn at_without_default_value() {
+        async fn utf8() {
             test(
                 r#"
-                let arr1 = [10, 20, 30]
-				<: [
-					arr1
-					arr1.at(0), arr1.at(1), arr1.at(2)
-					arr1.at(-3), arr1.at(-2), arr1.at(-1)
-					arr1.at(3), arr1.at(4), arr1.at(5)
-					arr1.at(-6), arr1.at(-5), arr1.at(-4)
-				]
+                let bin = Str:to_utf8_bin("あい")
+                <: [bin.len, Bin:to_str(bin)]
+                "#,
+                |res| assert_eq!(res, arr([num(6), str("あい")])),
+            )
+            .await
+            .unwrap();
+            test("Bin:to_str(Bin:from_hex('ff'))", |_| {})
+                .await
+                .unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn to_str() {
+            test(
+                r#"
+                let bin = Bin:from_hex("0061")
+                <: [Core:type(bin), Core:to_str(bin), Json:stringify(bin), bin == Bin:from_hex("0061")]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            arr([num(10), num(20), num(30)]),
-                            num(10),
-                            num(20),
-                            num(30),
-                            num(10),
-                            num(20),
-                            num(30),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
+                            str("bin"),
+                            str(r#"Bin:from_hex("0061")"#),
+                            str(r#""AGE=""#),
+                            bool(true)
                         ])
                     )
                 },
@@ -4935,76 +11052,44 @@ mod primitive_props {
             .await
             .unwrap();
         }
+    }
+
+    #[cfg(feature = "crypto")]
+    mod crypto {
+        use super::*;
 
         #[tokio::test]
-        async fn at_with_default_value() {
+        async fn digest() {
             test(
                 r#"
-                let arr1 = [10, 20, 30]
-				<: [
-					arr1
-					arr1.at(0, 100), arr1.at(1, 100), arr1.at(2, 100)
-					arr1.at(-3, 100), arr1.at(-2, 100), arr1.at(-1, 100)
-					arr1.at(3, 100), arr1.at(4, 100), arr1.at(5, 100)
-					arr1.at(-6, 100), arr1.at(-5, 100), arr1.at(-4, 100)
-				]
+                <: [Crypto:sha256("abc"), Crypto:sha1("abc"), Crypto:md5(Str:to_utf8_bin("abc"))]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            arr([num(10), num(20), num(30)]),
-                            num(10),
-                            num(20),
-                            num(30),
-                            num(10),
-                            num(20),
-                            num(30),
-                            num(100),
-                            num(100),
-                            num(100),
-                            num(100),
-                            num(100),
-                            num(100),
+                            str("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+                            str("a9993e364706816aba3e25717850c26c9cd0d89d"),
+                            str("900150983cd24fb0d6963f7d28e17f72"),
                         ])
                     )
                 },
             )
             .await
             .unwrap();
+            test("Crypto:sha256(1)", |_| {}).await.unwrap_err();
         }
 
         #[tokio::test]
-        async fn at_fraction() {
+        async fn hmac_sha256() {
             test(
                 r#"
-                let arr1 = [10, 20, 30]
-				<: [
-					arr1
-					arr1.at(0.1), arr1.at(1.4), arr1.at(2.5)
-					arr1.at(-3.1), arr1.at(-2.4), arr1.at(-1.5)
-					arr1.at(3.1), arr1.at(4.4), arr1.at(5.5)
-					arr1.at(-6.1), arr1.at(-5.4), arr1.at(-4.5)
-				]
+                <: Crypto:hmac_sha256("key", "The quick brown fox jumps over the lazy dog")
                 "#,
                 |res| {
                     assert_eq!(
                         res,
-                        arr([
-                            arr([num(10), num(20), num(30)]),
-                            num(10),
-                            num(20),
-                            num(30),
-                            num(10),
-                            num(20),
-                            num(30),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                            null(),
-                        ])
+                        str("f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8")
                     )
                 },
             )
@@ -5012,319 +11097,697 @@ mod primitive_props {
             .unwrap();
         }
     }
-}
 
-mod std {
-    use super::*;
+    mod storage {
+        use aiscript_v0::{MemoryStorage, Storage};
 
-    mod core {
         use super::*;
 
+        async fn exec(storage: &MemoryStorage, src: &str) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter(storage.clone().functions());
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
+
         #[tokio::test]
-        async fn range() {
-            test("<: Core:range(1, 10)", |res| {
-                assert_eq!(
-                    res,
-                    arr([
-                        num(1),
-                        num(2),
-                        num(3),
-                        num(4),
-                        num(5),
-                        num(6),
-                        num(7),
-                        num(8),
-                        num(9),
-                        num(10)
-                    ])
+        async fn basic() {
+            let storage = MemoryStorage::new();
+            let res = exec(
+                &storage,
+                r#"
+                let settings = { theme: "dark" }
+                Storage:set("settings", settings)
+                Storage:set("count", 1)
+                settings.theme = "light"
+                [Storage:get("settings"), Storage:get("count"), Storage:get("none"), Storage:list()]
+                "#,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                res,
+                arr([
+                    obj([("theme", str("dark"))]),
+                    num(1),
+                    null(),
+                    arr([str("count"), str("settings")]),
+                ])
+            );
+            let res = exec(
+                &storage,
+                r#"
+                Storage:delete("count")
+                Storage:delete("none")
+                Storage:list()
+                "#,
+            )
+            .await
+            .unwrap();
+            assert_eq!(res, arr([str("settings")]));
+            assert_eq!(storage.get("count").await.unwrap(), None);
+        }
+
+        #[tokio::test]
+        async fn cyclic() {
+            let storage = MemoryStorage::new();
+            exec(
+                &storage,
+                r#"
+                let a = {}
+                a.a = a
+                Storage:set("a", a)
+                "#,
+            )
+            .await
+            .unwrap_err();
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    mod fs {
+        use ::std::path::PathBuf;
+
+        use aiscript_v0::FsCapability;
+
+        use super::*;
+
+        fn temp_dir(name: &str) -> PathBuf {
+            let dir =
+                ::std::env::temp_dir().join(format!("aiscript-fs-{}-{name}", ::std::process::id()));
+            let _ = ::std::fs::remove_dir_all(&dir);
+            ::std::fs::create_dir_all(dir.join("data")).unwrap();
+            dir
+        }
+
+        async fn exec(capability: FsCapability, src: &str) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter(capability.functions());
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
+
+        #[tokio::test]
+        async fn read_write() {
+            let dir = temp_dir("read_write");
+            let capability = FsCapability::new(dir.join("data")).writable(true);
+            let res = exec(
+                capability,
+                r#"
+                Fs:write_text("a.txt", "ai")
+                Fs:write_text("./b.txt", "chan")
+                [Fs:read_text("a.txt"), Fs:list_dir(), Fs:read_text("c.txt").name]
+                "#,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                res,
+                arr([
+                    str("ai"),
+                    arr([str("a.txt"), str("b.txt")]),
+                    str("io_error")
+                ])
+            );
+        }
+
+        #[tokio::test]
+        async fn read_only() {
+            let dir = temp_dir("read_only");
+            ::std::fs::write(dir.join("data/a.txt"), "ai").unwrap();
+            let capability = FsCapability::new(dir.join("data"));
+            assert_eq!(
+                exec(capability.clone(), r#"Fs:read_text("a.txt")"#)
+                    .await
+                    .unwrap(),
+                str("ai")
+            );
+            exec(capability, r#"Fs:write_text("a.txt", "chan")"#)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                ::std::fs::read_to_string(dir.join("data/a.txt")).unwrap(),
+                "ai"
+            );
+        }
+
+        #[tokio::test]
+        async fn out_of_root() {
+            let dir = temp_dir("out_of_root");
+            ::std::fs::write(dir.join("secret.txt"), "ai").unwrap();
+            let capability = FsCapability::new(dir.join("data")).writable(true);
+            for src in [
+                r#"Fs:read_text("../secret.txt")"#,
+                r#"Fs:read_text("data/../../secret.txt")"#,
+                r#"Fs:list_dir("..")"#,
+                r#"Fs:write_text("../secret.txt", "")"#,
+            ] {
+                exec(capability.clone(), src).await.unwrap_err();
+            }
+            let absolute = dir.join("secret.txt");
+            exec(
+                capability.clone(),
+                &format!(r#"Fs:read_text("{}")"#, absolute.display()),
+            )
+            .await
+            .unwrap_err();
+            #[cfg(unix)]
+            {
+                ::std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("data/link"))
+                    .unwrap();
+                exec(capability, r#"Fs:read_text("link")"#)
+                    .await
+                    .unwrap_err();
+            }
+        }
+
+        #[cfg(unix)]
+        #[tokio::test]
+        async fn dangling_symlink() {
+            let dir = temp_dir("dangling_symlink");
+            ::std::os::unix::fs::symlink(dir.join("created.txt"), dir.join("data/link")).unwrap();
+            let capability = FsCapability::new(dir.join("data")).writable(true);
+            for src in [
+                r#"Fs:write_text("link", "ai")"#,
+                r#"Fs:write_text("link/a.txt", "ai")"#,
+            ] {
+                exec(capability.clone(), src).await.unwrap_err();
+            }
+            assert!(!dir.join("created.txt").exists());
+        }
+
+        #[tokio::test]
+        async fn missing_root() {
+            let dir = temp_dir("missing_root");
+            let capability = FsCapability::new(dir.join("missing")).writable(true);
+            for src in [
+                r#"Fs:read_text("a.txt")"#,
+                r#"Fs:write_text("a.txt", "ai")"#,
+            ] {
+                exec(capability.clone(), src).await.unwrap_err();
+            }
+        }
+    }
+
+    #[cfg(feature = "http")]
+    mod http {
+        use aiscript_v0::HttpPolicy;
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        use super::*;
+
+        /// Serves HTTP on a random port, responding to each request with `respond(request)`.
+        async fn serve(respond: fn(String) -> String) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    loop {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                        let request = String::from_utf8_lossy(&request).to_lowercase();
+                        if let Some((head, body)) = request.split_once("\r\n\r\n") {
+                            let len = head
+                                .lines()
+                                .find_map(|line| line.strip_prefix("content-length: "))
+                                .map_or(0, |len| len.parse().unwrap());
+                            if body.len() >= len {
+                                break;
+                            }
+                        }
+                    }
+                    let response = respond(String::from_utf8(request).unwrap());
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+            format!("http://{addr}")
+        }
+
+        async fn exec(policy: HttpPolicy, src: &str) -> Result<Value, AiScriptError> {
+            let aiscript = new_interpreter(policy.functions().unwrap());
+            let script = Parser::default().parse(src)?;
+            aiscript.exec(script).await.map(Option::unwrap)
+        }
+
+        fn local() -> HttpPolicy {
+            HttpPolicy::new(|url| url.host_str() == Some("127.0.0.1"))
+        }
+
+        #[tokio::test]
+        async fn get() {
+            let url = serve(|request| {
+                assert!(request.starts_with("GET /path HTTP/1.1"));
+                assert!(request.contains("x-foo: bar"));
+                "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Test: a\r\n\r\nhello".to_string()
+            })
+            .await;
+            let res = exec(
+                local(),
+                &format!(
+                    r#"
+                    let headers = {{}}
+                    headers["X-Foo"] = "bar"
+                    let res = Http:get("{url}/path", {{ headers: headers }})
+                    [res.status, res.body, res.headers["x-test"]]
+                    "#
+                ),
+            )
+            .await
+            .unwrap();
+            assert_eq!(res, arr([num(200), str("hello"), str("a")]));
+        }
+
+        #[tokio::test]
+        async fn post() {
+            let url = serve(|request| {
+                let (_, body) = request.split_once("\r\n\r\n").unwrap();
+                format!(
+                    "HTTP/1.1 201 Created\r\nContent-Length: {}\r\n\r\n{body}",
+                    body.len()
                 )
             })
+            .await;
+            let res = exec(
+                local(),
+                &format!(
+                    r#"
+                    let res = Http:post("{url}", Str:to_utf8_bin("あい"))
+                    [res.status, res.body]
+                    "#
+                ),
+            )
             .await
             .unwrap();
+            assert_eq!(res, arr([num(201), str("あい")]));
+        }
 
-            test("<: Core:range(1, 1)", |res| assert_eq!(res, arr([num(1),])))
+        #[tokio::test]
+        async fn not_allowed() {
+            let url = serve(|_| {
+                "HTTP/1.1 302 Found\r\nLocation: http://example.com/\r\nContent-Length: 0\r\n\r\n"
+                    .to_string()
+            })
+            .await;
+            exec(HttpPolicy::new(|_| false), &format!(r#"Http:get("{url}")"#))
+                .await
+                .unwrap_err();
+            exec(local(), &format!(r#"Http:get("{url}")"#))
+                .await
+                .unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn body_too_large() {
+            let url =
+                serve(|_| "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_string()).await;
+            let res = exec(local().max_body_size(4), &format!(r#"Http:get("{url}")"#))
                 .await
                 .unwrap();
+            assert_eq!(res, error("body_too_large", None));
+        }
 
-            test("<: Core:range(9, 7)", |res| {
-                assert_eq!(res, arr([num(9), num(8), num(7),]))
-            })
+        #[tokio::test]
+        async fn invalid_timeout() {
+            let url =
+                serve(|_| "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_string()).await;
+            let res = exec(
+                local(),
+                &format!(
+                    r#"
+                    [
+                        Http:get("{url}", {{ timeout: Math:Infinity }}).status
+                        Http:get("{url}", {{ timeout: Math:pow(10, 30) }}).status
+                        Http:get("{url}", {{ timeout: Math:sqrt(-1) }}).status
+                    ]
+                    "#
+                ),
+            )
+            .await
+            .unwrap();
+            assert_eq!(res, arr([num(200), num(200), num(200)]));
+        }
+    }
+
+    mod error {
+        use super::*;
+
+        #[tokio::test]
+        async fn create() {
+            test(
+                r#"
+                <: Error:create('ai', {chan: 'kawaii'})
+                "#,
+                |res| assert_eq!(res, error("ai", Some(obj([("chan", str("kawaii"))])))),
+            )
             .await
             .unwrap();
         }
+    }
 
-        #[tokio::test]
-        async fn to_str() {
-            test(r#"<: Core:to_str("abc")"#, |res| {
-                assert_eq!(res, str("abc"))
-            })
-            .await
-            .unwrap();
-
-            test(r#"<: Core:to_str(123)"#, |res| assert_eq!(res, str("123")))
-                .await
-                .unwrap();
+    mod json {
+        use super::*;
 
-            test(r#"<: Core:to_str(true)"#, |res| {
-                assert_eq!(res, str("true"))
-            })
+        #[tokio::test]
+        async fn stringify_fn() {
+            test(
+                r#"
+                <: Json:stringify(@(){})
+                "#,
+                |res| assert_eq!(res, str(r#""<function>""#)),
+            )
             .await
             .unwrap();
+        }
 
-            test(r#"<: Core:to_str(false)"#, |res| {
-                assert_eq!(res, str("false"))
-            })
+        #[tokio::test]
+        async fn stringify_non_finite() {
+            test(
+                r#"
+                <: [
+                    Json:stringify([Math:sqrt(-1), Math:Infinity, 0 - Math:Infinity, Math:pow(10, 21), 0 * -1])
+                    Json:stringify([Math:Infinity], null, { strict: true })
+                    Json:stringify({ f: @(){} }, null, { strict: true })
+                    Json:stringify([Math:sqrt(-1), 1], null, { strict: false })
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str("[null,null,null,1e21,0]"),
+                            error("not_json", None),
+                            error("not_json", None),
+                            str("[null,1]"),
+                        ])
+                    )
+                },
+            )
             .await
             .unwrap();
+        }
 
-            test(r#"<: Core:to_str(null)"#, |res| {
-                assert_eq!(res, str("null"))
-            })
+        #[tokio::test]
+        async fn stringify_replacer() {
+            test(
+                r#"
+                let v = { a: 1, b: [2, 3], c: { a: 4, d: 5 } }
+                <: [
+                    Json:stringify(v, null, {
+                        replacer: @(k, v) { if Core:type(v) == 'num' v * 10 else v }
+                    })
+                    Json:stringify(v, null, { keys: ['a', 'c'] })
+                    Json:stringify(v, null, {
+                        replacer: @(k, v) { if k == '' { x: v.a } else v }
+                    })
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str(r#"{"a":10,"b":[20,30],"c":{"a":40,"d":50}}"#),
+                            str(r#"{"a":1,"c":{"a":4}}"#),
+                            str(r#"{"x":1}"#),
+                        ])
+                    )
+                },
+            )
             .await
             .unwrap();
 
-            test(r#"<: Core:to_str({ a: "abc", b: 1234 })"#, |res| {
-                assert_eq!(res, str(r#"{ a: "abc", b: 1234 }"#))
-            })
+            test(
+                r#"
+                let a = []
+                a.push(a)
+                Json:stringify(a, null, { keys: [] })
+                "#,
+                |_| {},
+            )
             .await
-            .unwrap();
+            .unwrap_err();
+        }
 
-            test(r#"<: Core:to_str([ true, 123, null ])"#, |res| {
-                assert_eq!(res, str("[ true, 123, null ]"))
-            })
+        #[tokio::test]
+        async fn parsable() {
+            test(
+                r#"
+                <: [
+                    Json:parsable('null')
+                    Json:stringify(Json:parse('null'))
+                ]
+                "#,
+                |res| assert_eq!(res, arr([bool(true), str("null")])),
+            )
             .await
             .unwrap();
 
-            test(r#"<: Core:to_str(@( a, b, c ) {})"#, |res| {
-                assert_eq!(res, str("@( a, b, c ) { ... }"))
-            })
+            test(
+                r#"
+                <: [
+                    Json:parsable('"hoge"')
+                    Json:stringify(Json:parse('"hoge"'))
+                ]
+                "#,
+                |res| assert_eq!(res, arr([bool(true), str(r#""hoge""#)])),
+            )
             .await
             .unwrap();
 
             test(
                 r#"
-                let arr = []
-				arr.push(arr)
-				<: Core:to_str(arr)
+                <: [
+                    Json:parsable('[]')
+                    Json:stringify(Json:parse('[]'))
+                ]
                 "#,
-                |res| assert_eq!(res, str("[ ... ]")),
+                |res| assert_eq!(res, arr([bool(true), str("[]")])),
             )
             .await
             .unwrap();
 
             test(
                 r#"
-                let arr = []
-				arr.push({ value: arr })
-				<: Core:to_str(arr)
+                <: [
+                    Json:parsable('{}')
+                    Json:stringify(Json:parse('{}'))
+                ]
                 "#,
-                |res| assert_eq!(res, str("[ { value: ... } ]")),
+                |res| assert_eq!(res, arr([bool(true), str("{}")])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn abort() {
-            let err = test(r#"Core:abort("hoge")"#, |_| {}).await.unwrap_err();
-            assert!(matches!(
-                err,
-                AiScriptError::Runtime(AiScriptRuntimeError::User(message))
-                    if message == "hoge"
-            ));
-        }
-    }
-
-    mod arr {
-        use super::*;
-
-        #[tokio::test]
-        async fn create() {
-            test("<: Arr:create(0)", |res| assert_eq!(res, arr([])))
-                .await
-                .unwrap();
+        async fn unparsable() {
+            test(
+                r#"
+                <: [
+                    Json:parsable('')
+                    Json:stringify(Json:parse(''))
+                ]
+                "#,
+                |res| assert_eq!(res, arr([bool(false), error("not_json", None)])),
+            )
+            .await
+            .unwrap();
 
-            test("<: Arr:create(3)", |res| {
-                assert_eq!(res, arr([null(), null(), null()]))
-            })
+            test(
+                r#"
+                <: [
+                    Json:parsable('hoge')
+                    Json:stringify(Json:parse('hoge'))
+                ]
+                "#,
+                |res| assert_eq!(res, arr([bool(false), error("not_json", None)])),
+            )
             .await
             .unwrap();
 
-            test("<: Arr:create(3, 1)", |res| {
-                assert_eq!(res, arr([num(1), num(1), num(1)]))
-            })
+            test(
+                r#"
+                <: [
+                    Json:parsable('[')
+                    Json:stringify(Json:parse('['))
+                ]
+                "#,
+                |res| assert_eq!(res, arr([bool(false), error("not_json", None)])),
+            )
             .await
             .unwrap();
         }
-    }
-
-    mod math {
-        use super::*;
 
         #[tokio::test]
-        async fn trig() {
-            test("<: Math:sin(Math:PI / 2)", |res| assert_eq!(res, num(1)))
-                .await
-                .unwrap();
-
-            test("<: Math:sin(0 - (Math:PI / 2))", |res| {
-                assert_eq!(res, num(-1))
-            })
+        async fn stringify_indent() {
+            test(
+                r#"
+                let v = { a: [1, []], b: {} }
+                <: [
+                    Json:stringify(v, 2)
+                    Json:stringify(v, Str:from_codepoint(9))
+                    Json:stringify(v, 0)
+                    Json:stringify(v, null)
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str("{\n  \"a\": [\n    1,\n    []\n  ],\n  \"b\": {}\n}"),
+                            str("{\n\t\"a\": [\n\t\t1,\n\t\t[]\n\t],\n\t\"b\": {}\n}"),
+                            str(r#"{"a":[1,[]],"b":{}}"#),
+                            str(r#"{"a":[1,[]],"b":{}}"#),
+                        ])
+                    )
+                },
+            )
             .await
             .unwrap();
 
-            test("<: Math:sin(Math:PI / 4) * Math:cos(Math:PI / 4)", |res| {
-                assert!((f64::try_from(res).unwrap() - 0.5).abs() <= f64::EPSILON)
-            })
+            test(
+                r#"
+                <: Json:stringify(1, true)
+                "#,
+                |_| {},
+            )
             .await
-            .unwrap();
-        }
-
-        #[tokio::test]
-        async fn abs() {
-            test("<: Math:abs(1 - 6)", |res| assert_eq!(res, num(5)))
-                .await
-                .unwrap();
-        }
-
-        #[tokio::test]
-        async fn pow_and_sqrt() {
-            test("<: Math:sqrt(3^2 + 4^2)", |res| assert_eq!(res, num(5)))
-                .await
-                .unwrap();
-        }
-
-        #[tokio::test]
-        async fn round() {
-            test("<: Math:round(3.14)", |res| assert_eq!(res, num(3)))
-                .await
-                .unwrap();
-
-            test("<: Math:round(-1.414213)", |res| assert_eq!(res, num(-1)))
-                .await
-                .unwrap();
+            .unwrap_err();
         }
 
         #[tokio::test]
-        async fn ceil() {
-            test("<: Math:ceil(2.71828)", |res| assert_eq!(res, num(3)))
-                .await
-                .unwrap();
-
-            test("<: Math:ceil(0 - Math:PI)", |res| assert_eq!(res, num(-3)))
-                .await
-                .unwrap();
-
-            test("<: Math:ceil(1 / Math:Infinity)", |res| {
-                assert_eq!(res, num(0))
-            })
+        async fn parse_relaxed() {
+            test(
+                r#"
+                let json = '{
+                    // comment
+                    "a": [1, 2, /* comment */],
+                    "b": "// not a comment,}",
+                }'
+                <: [
+                    Json:parsable(json)
+                    Json:parsable(json, { relaxed: true })
+                    Json:parse(json, { relaxed: true })
+                ]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            bool(false),
+                            bool(true),
+                            obj([
+                                ("a", arr([num(1), num(2)])),
+                                ("b", str("// not a comment,}")),
+                            ]),
+                        ])
+                    )
+                },
+            )
             .await
             .unwrap();
-        }
-
-        #[tokio::test]
-        async fn floor() {
-            test("<: Math:floor(23.14069)", |res| assert_eq!(res, num(23)))
-                .await
-                .unwrap();
 
-            test("<: Math:floor(Math:Infinity / 0)", |res| {
-                assert_eq!(res, num(f64::INFINITY))
-            })
+            test(
+                r#"
+                <: Json:parse('[1,,]', { relaxed: true })
+                "#,
+                |res| assert_eq!(res, error("not_json", None)),
+            )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn min() {
-            test("<: Math:min(2, 3)", |res| assert_eq!(res, num(2)))
-                .await
-                .unwrap();
-        }
-
-        #[tokio::test]
-        async fn max() {
-            test("<: Math:max(-2, -3)", |res| assert_eq!(res, num(-2)))
-                .await
-                .unwrap();
-        }
-
-        #[tokio::test]
-        async fn rnd_with_arg() {
-            test("<: Math:rnd(1, 1.5)", |res| assert_eq!(res, num(1)))
-                .await
-                .unwrap();
-        }
-
-        #[tokio::test]
-        async fn gen_rng() {
-            // 2つのシード値から1~maxの乱数をn回生成して一致率を見る
+        async fn parse_reviver() {
             test(
                 r#"
-                @test(seed1, seed2) {
-                    let n = 100
-                    let max = 100000
-                    let threshold = 0.05
-                    let random1 = Math:gen_rng(seed1)
-                    let random2 = Math:gen_rng(seed2)
-                    var same = 0
-                    for n {
-                        if random1(1, max) == random2(1, max) {
-                            same += 1
-                        }
+                let keys = []
+                let v = Json:parse('{"a": [1, 2], "b": "x"}', {
+                    reviver: @(k, v) {
+                        keys.push(k)
+                        if Core:type(v) == 'num' v * 10 else v
                     }
-                    let rate = same / n
-                    if seed1 == seed2 { rate == 1 }
-                    else { rate < threshold }
-                }
-                let seed1 = `{Util:uuid()}`
-                let seed2 = `{Date:year()}`
-                <: [
-                    test(seed1, seed1)
-                    test(seed1, seed2)
-                ]
+                })
+                <: [v, keys]
                 "#,
-                |res| assert_eq!(res, arr([bool(true), bool(true)])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            obj([("a", arr([num(10), num(20)])), ("b", str("x"))]),
+                            arr([num(0), num(1), str("a"), str("b"), str("")]),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
     }
 
-    mod obj {
+    #[cfg(feature = "toml")]
+    mod toml {
         use super::*;
 
         #[tokio::test]
-        async fn keys() {
+        async fn parse() {
             test(
                 r#"
-                let o = { a: 1; b: 2; c: 3; }
+                let toml = `
+                title = "config"
+                date = 2024-01-02
+
+                [owner]
+                ids = [1, 2.5]
+                `
+                <: [Toml:parsable(toml), Toml:parse(toml)]
+                "#,
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            bool(true),
+                            obj([
+                                ("title", str("config")),
+                                ("date", str("2024-01-02")),
+                                ("owner", obj([("ids", arr([num(1), num(2.5)]))])),
+                            ]),
+                        ])
+                    )
+                },
+            )
+            .await
+            .unwrap();
 
-                <: Obj:keys(o)
+            test(
+                r#"
+                <: [Toml:parsable('a ='), Toml:parse('a =')]
                 "#,
-                |res| assert_eq!(res, arr([str("a"), str("b"), str("c")])),
+                |res| assert_eq!(res, arr([bool(false), error("not_toml", None)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn vals() {
+        async fn stringify() {
             test(
                 r#"
-                let o = { _nul: null; _num: 24; _str: 'hoge'; _arr: []; _obj: {}; }
-
-                <: Obj:vals(o)
+                <: [
+                    Toml:stringify({ a: 1, b: { c: [true, "x"] } })
+                    Toml:stringify(1)
+                    Toml:stringify({ a: null })
+                ]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            null(),
-                            num(24),
-                            str("hoge"),
-                            arr([]),
-                            obj([] as [(String, Value); 0])
+                            str("a = 1\n\n[b]\nc = [true, \"x\"]\n"),
+                            error("not_toml", None),
+                            error("not_toml", None),
                         ])
                     )
                 },
@@ -5332,137 +11795,198 @@ mod std {
             .await
             .unwrap();
         }
+    }
+
+    #[cfg(feature = "yaml")]
+    mod yaml {
+        use super::*;
 
         #[tokio::test]
-        async fn kvs() {
+        async fn parse() {
             test(
                 r#"
-                let o = { a: 1; b: 2; c: 3; }
-
-                <: Obj:kvs(o)
+                let yaml = `
+                name: config
+                ids:
+                  - 1
+                  - 2.5
+                enabled: true
+                empty: null
+                `
+                <: [Yaml:parsable(yaml), Yaml:parse(yaml)]
                 "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            arr([str("a"), num(1)]),
-                            arr([str("b"), num(2)]),
-                            arr([str("c"), num(3)])
+                            bool(true),
+                            obj([
+                                ("name", str("config")),
+                                ("ids", arr([num(1), num(2.5)])),
+                                ("enabled", bool(true)),
+                                ("empty", null()),
+                            ]),
                         ])
                     )
                 },
             )
             .await
             .unwrap();
-        }
 
-        #[tokio::test]
-        async fn merge() {
             test(
                 r#"
-                let o1 = { a: 1; b: 2; }
-                let o2 = { b: 3; c: 4; }
-
-                <: Obj:merge(o1, o2)
+                <: [Yaml:parsable('a: [1'), Yaml:parse('a: [1')]
                 "#,
-                |res| assert_eq!(res, obj([("a", num(1)), ("b", num(3)), ("c", num(4)),])),
+                |res| assert_eq!(res, arr([bool(false), error("not_yaml", None)])),
             )
             .await
             .unwrap();
         }
-    }
-
-    mod str {
-        use super::*;
 
         #[tokio::test]
-        async fn lf() {
+        async fn stringify() {
             test(
                 r#"
-                <: Str:lf
+                <: Yaml:stringify({ a: 1, b: [true, "x"] })
                 "#,
-                |res| assert_eq!(res, str("\n")),
+                |res| assert_eq!(res, str("a: 1\nb:\n- true\n- x\n")),
             )
             .await
             .unwrap();
         }
+    }
+
+    mod date {
+        use chrono::{Datelike, Local, NaiveDate, TimeZone, Timelike};
+
+        use super::*;
 
         #[tokio::test]
-        async fn from_codepoint() {
+        async fn year() {
+            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_milli_opt(3, 4, 5, 6)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .timestamp_millis();
             test(
-                r#"
-                <: Str:from_codepoint(65)
-                "#,
-                |res| assert_eq!(res, str("A")),
+                &format!(
+                    "
+                    <: [Date:year(0), Date:year({example_time})]
+                    "
+                ),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(Local.timestamp_millis_opt(0).unwrap().year()),
+                            num(2024)
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn from_unicode_codepoints() {
+        async fn month() {
+            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_milli_opt(3, 4, 5, 6)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .timestamp_millis();
             test(
-                r#"
-                <: Str:from_unicode_codepoints([171581, 128073, 127999, 128104, 8205, 128102])
-			    "#,
-                |res| assert_eq!(res, str("𩸽👉🏿👨‍👦")),
+                &format!(
+                    "
+                    <: [Date:month(0), Date:month({example_time})]
+                    "
+                ),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([num(Local.timestamp_millis_opt(0).unwrap().month()), num(1)])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn from_utf8_bytes() {
+        async fn day() {
+            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_milli_opt(3, 4, 5, 6)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .timestamp_millis();
             test(
-                r#"
-                <: Str:from_utf8_bytes([240, 169, 184, 189, 240, 159, 145, 137, 240, 159, 143, 191, 240, 159, 145, 168, 226, 128, 141, 240, 159, 145, 166])
-                "#,
-                |res| assert_eq!(res, str("𩸽👉🏿👨‍👦")),
+                &format!(
+                    "
+                    <: [Date:day(0), Date:day({example_time})]
+                    "
+                ),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([num(Local.timestamp_millis_opt(0).unwrap().day()), num(2)])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn charcode_at() {
+        async fn hour() {
+            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_milli_opt(3, 4, 5, 6)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .timestamp_millis();
             test(
-                r#"
-                <: "aiscript".split().map(@(x, _) { x.charcode_at(0) })
-                "#,
+                &format!(
+                    "
+                    <: [Date:hour(0), Date:hour({example_time})]
+                    "
+                ),
                 |res| {
                     assert_eq!(
                         res,
-                        arr([
-                            num(97),
-                            num(105),
-                            num(115),
-                            num(99),
-                            num(114),
-                            num(105),
-                            num(112),
-                            num(116),
-                        ])
+                        arr([num(Local.timestamp_millis_opt(0).unwrap().hour()), num(3)])
                     )
                 },
             )
             .await
             .unwrap();
         }
-    }
-
-    mod uri {
-        use super::*;
 
         #[tokio::test]
-        async fn encode_full() {
+        async fn minute() {
+            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_milli_opt(3, 4, 5, 6)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .timestamp_millis();
             test(
-                r#"
-                <: Uri:encode_full("https://example.com/?q=あいちゃん")
-                "#,
+                &format!(
+                    "
+                    <: [Date:minute(0), Date:minute({example_time})]
+                    "
+                ),
                 |res| {
                     assert_eq!(
                         res,
-                        str("https://example.com/?q=%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")
+                        arr([num(Local.timestamp_millis_opt(0).unwrap().minute()), num(4)])
                     )
                 },
             )
@@ -5471,190 +11995,376 @@ mod std {
         }
 
         #[tokio::test]
-        async fn encode_component() {
+        async fn second() {
+            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_milli_opt(3, 4, 5, 6)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .timestamp_millis();
             test(
-                r#"
-                <: Uri:encode_component("https://example.com/?q=あいちゃん")
-                "#,
-                |res| assert_eq!(res, str("https%3A%2F%2Fexample.com%2F%3Fq%3D%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")),
+                &format!(
+                    "
+                    <: [Date:second(0), Date:second({example_time})]
+                    "
+                ),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([num(Local.timestamp_millis_opt(0).unwrap().second()), num(5)])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn decode_full() {
+        async fn millisecond() {
+            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_milli_opt(3, 4, 5, 6)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .timestamp_millis();
             test(
-                r#"
-                <: Uri:decode_full("https%3A%2F%2Fexample.com%2F%3Fq%3D%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")
-                "#,
-                |res| assert_eq!(res, str("https%3A%2F%2Fexample.com%2F%3Fq%3Dあいちゃん")),
+                &format!(
+                    "
+                    <: [Date:millisecond(0), Date:millisecond({example_time})]
+                    "
+                ),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(
+                                (Local.timestamp_millis_opt(0).unwrap().timestamp_millis() % 1000)
+                                    as f64
+                            ),
+                            num(6)
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn decode_component() {
+        async fn to_iso_str() {
             test(
                 r#"
-                <: Uri:decode_component("https%3A%2F%2Fexample.com%2F%3Fq%3D%E3%81%82%E3%81%84%E3%81%A1%E3%82%83%E3%82%93")
+                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
+				let s1 = Date:to_iso_str(d1)
+				let d2 = Date:parse(s1)
+				<: [d1, d2, s1]
                 "#,
-                |res| assert_eq!(res, str("https://example.com/?q=あいちゃん")),
+                |res| {
+                    let res = <Vec<Value>>::try_from(res).unwrap();
+                    assert_eq!(res[0], res[1]);
+                    let s1 = String::try_from(res[2].clone()).unwrap();
+                    regex::Regex::new(
+                        r"(?x)
+                        ^[0-9]{4,4}-[0-9]{2,2}-[0-9]{2,2}T
+                        [0-9]{2,2}:[0-9]{2,2}:[0-9]{2,2}\.[0-9]{3,3}
+                        (Z|[-+][0-9]{2,2}:[0-9]{2,2})$
+                        ",
+                    )
+                    .unwrap()
+                    .captures(&s1);
+                },
             )
             .await
             .unwrap();
         }
-    }
-
-    mod error {
-        use super::*;
 
         #[tokio::test]
-        async fn create() {
+        async fn to_iso_str_utc() {
             test(
                 r#"
-                <: Error:create('ai', {chan: 'kawaii'})
+                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
+				let s1 = Date:to_iso_str(d1, 0)
+				let d2 = Date:parse(s1)
+				<: [d1, d2, s1]
                 "#,
-                |res| assert_eq!(res, error("ai", Some(obj([("chan", str("kawaii"))])))),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(1712854066021.0),
+                            num(1712854066021.0),
+                            str("2024-04-11T16:47:46.021Z")
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
-    }
-
-    mod json {
-        use super::*;
 
         #[tokio::test]
-        async fn stringify_fn() {
+        async fn to_iso_str_09_00() {
             test(
                 r#"
-                <: Json:stringify(@(){})
+                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
+				let s1 = Date:to_iso_str(d1, 9*60)
+				let d2 = Date:parse(s1)
+				<: [d1, d2, s1]
                 "#,
-                |res| assert_eq!(res, str(r#""<function>""#)),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(1712854066021.0),
+                            num(1712854066021.0),
+                            str("2024-04-12T01:47:46.021+09:00")
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn parsable() {
+        async fn to_iso_str_05_18() {
             test(
                 r#"
-                <: [
-                    Json:parsable('null')
-                    Json:stringify(Json:parse('null'))
-                ]
+                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
+				let s1 = Date:to_iso_str(d1, -5*60-18)
+				let d2 = Date:parse(s1)
+				<: [d1, d2, s1]
                 "#,
-                |res| assert_eq!(res, arr([bool(true), str("null")])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            num(1712854066021.0),
+                            num(1712854066021.0),
+                            str("2024-04-11T11:29:46.021-05:18")
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn format() {
             test(
                 r#"
+                let d = Date:parse("2024-04-12T01:47:46.021+09:00")
                 <: [
-                    Json:parsable('"hoge"')
-                    Json:stringify(Json:parse('"hoge"'))
+                    Date:format(d, "%Y-%m-%d %H:%M:%S", "UTC")
+                    Date:format(d, "%a %b %e %H:%M %:z", 9*60)
+                    Date:format(d, "%Y/%m/%d %H:%M", "-05:30")
                 ]
                 "#,
-                |res| assert_eq!(res, arr([bool(true), str(r#""hoge""#)])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str("2024-04-11 16:47:46"),
+                            str("Fri Apr 12 01:47 +09:00"),
+                            str("2024/04/11 11:17"),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
 
-            test(
-                r#"
-                <: [
-                    Json:parsable('[]')
-                    Json:stringify(Json:parse('[]'))
-                ]
-                "#,
-                |res| assert_eq!(res, arr([bool(true), str("[]")])),
-            )
-            .await
-            .unwrap();
+            let err = test(r#"Date:format(0, "%Q", "UTC")"#, |_| {})
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Runtime(_))
+            ));
+
+            let err = test(r#"Date:format(0, "%Y", "Nowhere/Atlantis")"#, |_| {})
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                AiScriptError::Runtime(AiScriptRuntimeError::Runtime(_))
+            ));
+        }
 
+        #[cfg(feature = "tz")]
+        #[tokio::test]
+        async fn named_timezone() {
             test(
                 r#"
+                let winter = Date:parse("2024-01-15T12:00:00Z")
+                let summer = Date:parse("2024-07-15T12:00:00Z")
                 <: [
-                    Json:parsable('{}')
-                    Json:stringify(Json:parse('{}'))
+                    Date:format(winter, "%H:%M %:z", "America/New_York")
+                    Date:format(summer, "%H:%M %:z", "America/New_York")
+                    Date:to_iso_str(summer, "Asia/Tokyo")
                 ]
                 "#,
-                |res| assert_eq!(res, arr([bool(true), str("{}")])),
+                |res| {
+                    assert_eq!(
+                        res,
+                        arr([
+                            str("07:00 -05:00"),
+                            str("08:00 -04:00"),
+                            str("2024-07-15T21:00:00.000+09:00"),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn unparsable() {
+        async fn invalid_timestamp() {
+            for src in [
+                "Date:year(Math:pow(10, 20))",
+                "Date:second(Math:Infinity)",
+                "Date:to_iso_str(Math:pow(10, 20))",
+                "Date:to_iso_str(Math:pow(10, 20), 0)",
+                "Date:format(Math:pow(10, 20), '%Y')",
+                "Date:format(Math:pow(10, 20), '%Y', 0)",
+                "Date:format(Math:sqrt(-1), '%Y')",
+                "Date:year(Math:sqrt(-1))",
+                "Date:to_iso_str(Math:sqrt(-1))",
+            ] {
+                let err = test(src, |_| {}).await.unwrap_err();
+                assert_eq!(err.code(), "runtime", "{src}");
+            }
+            let err = test("Date:format(Math:pow(10, 20), '%Y')", |_| {})
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Runtime: invalid timestamp: 100000000000000000000"
+            );
+        }
+
+        #[cfg(feature = "locale")]
+        #[tokio::test]
+        async fn format_locale() {
             test(
                 r#"
+                let d = Date:parse("2024-04-12T01:47:46.021+09:00")
                 <: [
-                    Json:parsable('')
-                    Json:stringify(Json:parse(''))
+                    Date:format(d, "%A %e %B %Y", "+09:00", "fr_FR")
+                    Date:format(d, "%B", "+09:00", "de-DE")
                 ]
                 "#,
-                |res| assert_eq!(res, arr([bool(false), error("not_json", None)])),
+                |res| assert_eq!(res, arr([str("vendredi 12 avril 2024"), str("April")])),
             )
             .await
             .unwrap();
+        }
+    }
+
+    mod schema {
+        use super::*;
 
+        #[tokio::test]
+        async fn validate() {
             test(
                 r#"
+                let schema = {
+                    type: 'obj'
+                    props: {
+                        name: { type: 'str', min_len: 1 }
+                        age: { type: 'int', min: 0, max: 150 }
+                        tags: { type: 'arr', items: { type: 'str', pattern: '^[a-z]+$' } }
+                        role: { enum: ['admin', 'user'] }
+                    }
+                    required: ['name', 'age']
+                    additional: false
+                }
                 <: [
-                    Json:parsable('hoge')
-                    Json:stringify(Json:parse('hoge'))
+                    Schema:validate(schema, { name: 'ai', age: 16, tags: ['chan'], role: 'user' })
+                    Schema:validate(schema, { name: '', age: 1.5, tags: ['ok', 'NG', 1] })
+                    Schema:validate(schema, { age: -1, role: 'guest', extra: null })
+                    Schema:is_valid(schema, { name: 'ai', age: 16 })
+                    Schema:is_valid({ type: ['num', 'null'] }, 'str')
                 ]
                 "#,
-                |res| assert_eq!(res, arr([bool(false), error("not_json", None)])),
+                |res| {
+                    let error = |path: Vec<Value>, message: &str| {
+                        obj([("path", arr(path)), ("message", str(message))])
+                    };
+                    assert_eq!(
+                        res,
+                        arr([
+                            arr([]),
+                            arr([
+                                error(vec![str("name")], "expected length at least 1, but got 0"),
+                                error(vec![str("age")], "expected int, but got num"),
+                                error(vec![str("tags"), num(1)], "expected to match /^[a-z]+$/"),
+                                error(vec![str("tags"), num(2)], "expected str, but got num"),
+                            ]),
+                            arr([
+                                error(vec![], "missing required key 'name'"),
+                                error(vec![str("age")], "expected at least 0, but got -1"),
+                                error(vec![str("role")], "expected one of admin, user"),
+                                error(vec![], "unexpected key 'extra'"),
+                            ]),
+                            bool(true),
+                            bool(false),
+                        ])
+                    )
+                },
             )
             .await
             .unwrap();
+        }
 
+        #[tokio::test]
+        async fn nullable() {
             test(
                 r#"
-                <: [
-                    Json:parsable('[')
-                    Json:stringify(Json:parse('['))
-                ]
+                let schema = { type: 'str', nullable: true }
+                <: [Schema:is_valid(schema, null), Schema:is_valid(schema, 0)]
                 "#,
-                |res| assert_eq!(res, arr([bool(false), error("not_json", None)])),
+                |res| assert_eq!(res, arr([bool(true), bool(false)])),
             )
             .await
             .unwrap();
         }
-    }
-
-    mod date {
-        use chrono::{Datelike, Local, NaiveDate, TimeZone, Timelike};
-
-        use super::*;
 
         #[tokio::test]
-        async fn year() {
-            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
-                .unwrap()
-                .and_hms_milli_opt(3, 4, 5, 6)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp_millis();
-            test(
-                &format!(
-                    "
-                    <: [Date:year(0), Date:year({example_time})]
-                    "
-                ),
+        async fn invalid_schema() {
+            test("<: Schema:validate({ typ: 'str' }, '')", |_| {})
+                .await
+                .unwrap_err();
+            test("<: Schema:validate({ pattern: '(' }, '')", |_| {})
+                .await
+                .unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn cyclic() {
+            test(
+                r#"
+                let schema = { type: 'arr' }
+                schema.items = schema
+                let value = []
+                value.push(value)
+                let valid = Schema:is_valid(schema, value)
+                value.push('x')
+                <: [valid, Schema:validate(schema, value)]
+                "#,
                 |res| {
                     assert_eq!(
                         res,
                         arr([
-                            num(Local.timestamp_millis_opt(0).unwrap().year()),
-                            num(2024)
+                            bool(true),
+                            arr([obj([
+                                ("path", arr([num(1)])),
+                                ("message", str("expected arr, but got str")),
+                            ])])
                         ])
                     )
                 },
@@ -5662,52 +12372,53 @@ mod std {
             .await
             .unwrap();
         }
+    }
+
+    mod bit {
+        use super::*;
 
         #[tokio::test]
-        async fn month() {
-            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
-                .unwrap()
-                .and_hms_milli_opt(3, 4, 5, 6)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp_millis();
+        async fn logical() {
             test(
-                &format!(
-                    "
-                    <: [Date:month(0), Date:month({example_time})]
-                    "
-                ),
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([num(Local.timestamp_millis_opt(0).unwrap().month()), num(1)])
-                    )
-                },
+                r#"
+                <: [
+                    Bit:and(0b1100, 0b1010)
+                    Bit:or(0b1100, 0b1010)
+                    Bit:xor(0b1100, 0b1010)
+                    Bit:not(0)
+                    Bit:and(-1, 0xffffffff)
+                ]
+                "#,
+                |res| assert_eq!(res, arr([num(8), num(14), num(6), num(-1), num(-1)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn day() {
-            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
-                .unwrap()
-                .and_hms_milli_opt(3, 4, 5, 6)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp_millis();
+        async fn shift() {
             test(
-                &format!(
-                    "
-                    <: [Date:day(0), Date:day({example_time})]
-                    "
-                ),
+                r#"
+                <: [
+                    Bit:shl(1, 4)
+                    Bit:shl(1, 31)
+                    Bit:shl(1, 32)
+                    Bit:shr(-16, 2)
+                    Bit:ushr(-16, 28)
+                    Bit:ushr(-1, 0)
+                ]
+                "#,
                 |res| {
                     assert_eq!(
                         res,
-                        arr([num(Local.timestamp_millis_opt(0).unwrap().day()), num(2)])
+                        arr([
+                            num(16),
+                            num(-2147483648),
+                            num(1),
+                            num(-4),
+                            num(15),
+                            num(4294967295u32),
+                        ])
                     )
                 },
             )
@@ -5716,24 +12427,22 @@ mod std {
         }
 
         #[tokio::test]
-        async fn hour() {
-            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
-                .unwrap()
-                .and_hms_milli_opt(3, 4, 5, 6)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp_millis();
+        async fn conversion() {
             test(
-                &format!(
-                    "
-                    <: [Date:hour(0), Date:hour({example_time})]
-                    "
-                ),
+                r#"
+                <: [
+                    Bit:or(2.9, 0)
+                    Bit:or(-2.9, 0)
+                    Bit:or(0x100000001, 0)
+                    Bit:or(0x80000000, 0)
+                    Bit:or(Math:Infinity, 0)
+                    Bit:or(Math:sqrt(-1), 0)
+                ]
+                "#,
                 |res| {
                     assert_eq!(
                         res,
-                        arr([num(Local.timestamp_millis_opt(0).unwrap().hour()), num(3)])
+                        arr([num(2), num(-2), num(1), num(-2147483648), num(0), num(0),])
                     )
                 },
             )
@@ -5742,188 +12451,212 @@ mod std {
         }
 
         #[tokio::test]
-        async fn minute() {
-            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
-                .unwrap()
-                .and_hms_milli_opt(3, 4, 5, 6)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp_millis();
+        async fn not_a_number() {
+            let err = test("Bit:and(1, '1')", |_| ()).await.unwrap_err();
+            assert_eq!(err.code(), "type_mismatch");
+        }
+    }
+
+    mod cell {
+        use super::*;
+
+        #[tokio::test]
+        async fn namespace_state() {
             test(
-                &format!(
-                    "
-                    <: [Date:minute(0), Date:minute({example_time})]
-                    "
-                ),
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([num(Local.timestamp_millis_opt(0).unwrap().minute()), num(4)])
-                    )
-                },
+                r#"
+                :: Counter {
+                    let count = Cell:create(0)
+                    @inc() {
+                        Cell:set(count, Cell:get(count) + 1)
+                        Cell:get(count)
+                    }
+                }
+                Counter:inc()
+                Counter:inc()
+                <: [Counter:inc(), Cell:get(Counter:count), Counter:count.value]
+                "#,
+                |res| assert_eq!(res, arr([num(3), num(3), num(3)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn second() {
-            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
-                .unwrap()
-                .and_hms_milli_opt(3, 4, 5, 6)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp_millis();
+        async fn value_prop() {
             test(
-                &format!(
-                    "
-                    <: [Date:second(0), Date:second({example_time})]
-                    "
-                ),
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([num(Local.timestamp_millis_opt(0).unwrap().second()), num(5)])
-                    )
-                },
+                r#"
+                let c = Cell:create()
+                let before = Cell:get(c)
+                c.value = 'a'
+                <: [before, Cell:get(c), Core:type(c)]
+                "#,
+                |res| assert_eq!(res, arr([null(), str("a"), str("cell")])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn millisecond() {
-            let example_time = NaiveDate::from_ymd_opt(2024, 1, 2)
-                .unwrap()
-                .and_hms_milli_opt(3, 4, 5, 6)
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-                .timestamp_millis();
+        async fn not_cell() {
+            test("Cell:get({ value: 1 })", |_| {}).await.unwrap_err();
             test(
-                &format!(
-                    "
-                    <: [Date:millisecond(0), Date:millisecond({example_time})]
-                    "
-                ),
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            num(
-                                (Local.timestamp_millis_opt(0).unwrap().timestamp_millis() % 1000)
-                                    as f64
-                            ),
-                            num(6)
-                        ])
-                    )
-                },
+                "Cell:set(Cell:create(), 1, 2)\nCell:create().other = 1",
+                |_| {},
             )
             .await
-            .unwrap();
+            .unwrap_err();
+        }
+    }
+
+    mod chan {
+        use aiscript_v0::Channel;
+
+        use super::*;
+
+        fn interpreter(channel: &Channel) -> Interpreter {
+            new_interpreter([("events".to_string(), channel.to_value())])
         }
 
         #[tokio::test]
-        async fn to_iso_str() {
+        async fn send_and_recv() {
             test(
                 r#"
-                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
-				let s1 = Date:to_iso_str(d1)
-				let d2 = Date:parse(s1)
-				<: [d1, d2, s1]
+                let ch = Chan:create(2)
+                Chan:send(ch, 1)
+                Chan:send(ch, 2)
+                <: [Chan:recv(ch), Chan:recv(ch)]
                 "#,
-                |res| {
-                    let res = <Vec<Value>>::try_from(res).unwrap();
-                    assert_eq!(res[0], res[1]);
-                    let s1 = String::try_from(res[2].clone()).unwrap();
-                    regex::Regex::new(
-                        r"(?x)
-                        ^[0-9]{4,4}-[0-9]{2,2}-[0-9]{2,2}T
-                        [0-9]{2,2}:[0-9]{2,2}:[0-9]{2,2}\.[0-9]{3,3}
-                        (Z|[-+][0-9]{2,2}:[0-9]{2,2})$
-                        ",
-                    )
-                    .unwrap()
-                    .captures(&s1);
-                },
+                |res| assert_eq!(res, arr([num(1), num(2)])),
             )
             .await
             .unwrap();
         }
 
         #[tokio::test]
-        async fn to_iso_str_utc() {
+        async fn close() {
             test(
                 r#"
-                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
-				let s1 = Date:to_iso_str(d1, 0)
-				let d2 = Date:parse(s1)
-				<: [d1, d2, s1]
+                let ch = Chan:create(1)
+                Chan:send(ch, 1)
+                Chan:close(ch)
+                <: [Chan:recv(ch), Chan:recv(ch)]
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            num(1712854066021.0),
-                            num(1712854066021.0),
-                            str("2024-04-11T16:47:46.021Z")
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, arr([num(1), null()])),
             )
             .await
             .unwrap();
-        }
 
-        #[tokio::test]
-        async fn to_iso_str_09_00() {
             test(
                 r#"
-                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
-				let s1 = Date:to_iso_str(d1, 9*60)
-				let d2 = Date:parse(s1)
-				<: [d1, d2, s1]
+                let ch = Chan:create(1)
+                Chan:close(ch)
+                Chan:send(ch, 1)
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            num(1712854066021.0),
-                            num(1712854066021.0),
-                            str("2024-04-12T01:47:46.021+09:00")
-                        ])
-                    )
-                },
+                |_| {},
             )
             .await
-            .unwrap();
+            .unwrap_err();
         }
 
         #[tokio::test]
-        async fn to_iso_str_05_18() {
+        async fn not_channel() {
+            test("Chan:recv({})", |_| {}).await.unwrap_err();
+            test("Chan:create(0)", |_| {}).await.unwrap_err();
+            test("Chan:create(Math:Infinity)", |_| {})
+                .await
+                .unwrap_err();
+            test("Chan:create(Math:pow(10, 20))", |_| {})
+                .await
+                .unwrap_err();
+            test("Chan:recv({ recv: @() { 1 } })", |_| {})
+                .await
+                .unwrap_err();
+        }
+
+        #[tokio::test]
+        async fn methods() {
             test(
                 r#"
-                let d1 = Date:parse("2024-04-12T01:47:46.021+09:00")
-				let s1 = Date:to_iso_str(d1, -5*60-18)
-				let d2 = Date:parse(s1)
-				<: [d1, d2, s1]
+                let ch = Chan:create(1)
+                ch.send(1)
+                <: [Core:type(ch), ch.recv()]
                 "#,
-                |res| {
-                    assert_eq!(
-                        res,
-                        arr([
-                            num(1712854066021.0),
-                            num(1712854066021.0),
-                            str("2024-04-11T11:29:46.021-05:18")
-                        ])
-                    )
-                },
+                |res| assert_eq!(res, arr([str("channel"), num(1)])),
             )
             .await
             .unwrap();
         }
+
+        #[tokio::test]
+        async fn from_host() {
+            let events = Channel::bounded(1);
+            let aiscript = interpreter(&events);
+            let handle = tokio::spawn(async move {
+                aiscript
+                    .exec_collect(
+                        Parser::default()
+                            .parse(
+                                r#"
+                                loop {
+                                    let e = Chan:recv(events)
+                                    if (e == null) break
+                                    <: e
+                                }
+                                "#,
+                            )
+                            .unwrap(),
+                    )
+                    .await
+            });
+            for i in 0..3 {
+                events.send(num(i)).await.unwrap();
+            }
+            events.close();
+            let (_, printed) = handle.await.unwrap().unwrap();
+            assert_eq!(printed, vec![num(0), num(1), num(2)]);
+        }
+
+        #[tokio::test]
+        async fn to_host() {
+            let results = Channel::bounded(4);
+            let aiscript = interpreter(&results);
+            aiscript
+                .exec(
+                    Parser::default()
+                        .parse("Chan:send(events, 'a')\nChan:send(events, 'b')")
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(results.recv().await, Some(str("a")));
+            assert_eq!(results.recv().await, Some(str("b")));
+        }
+
+        #[tokio::test]
+        async fn abort() {
+            let events = Channel::bounded(1);
+            let aiscript = interpreter(&events);
+            let handle = tokio::spawn({
+                let aiscript = aiscript.clone();
+                async move {
+                    aiscript
+                        .exec_collect(
+                            Parser::default()
+                                .parse("let e = Chan:recv(events)\n<: 'unreachable'")
+                                .unwrap(),
+                        )
+                        .await
+                }
+            });
+            tokio::time::sleep(::std::time::Duration::from_millis(10)).await;
+            aiscript.abort();
+            let (_, printed) = tokio::time::timeout(::std::time::Duration::from_secs(1), handle)
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert_eq!(printed, vec![]);
+        }
     }
 }
 
@@ -0,0 +1,130 @@
+use futures::{future::BoxFuture, FutureExt};
+use indexmap::IndexMap;
+use thiserror::Error;
+
+use crate::v0;
+
+/// Operations shared by all AiScript engines.
+pub trait Runtime {
+    type Script;
+    type Value;
+    type Error;
+
+    /// Parses a source into a script.
+    fn parse(&self, src: &str) -> Result<Self::Script, Self::Error>;
+
+    /// Executes a script and returns the value of the last statement.
+    fn exec(&self, script: Self::Script)
+        -> BoxFuture<'_, Result<Option<Self::Value>, Self::Error>>;
+
+    /// Collects the values of `### name { ... }` metadata blocks of a script.
+    fn collect_metadata(
+        &self,
+        script: &Self::Script,
+    ) -> IndexMap<Option<String>, Option<Self::Value>>;
+}
+
+/// Language versions supported by this crate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Version {
+    V0,
+}
+
+impl Version {
+    /// Detects the version from the `/// @ version` header of a source.
+    ///
+    /// A source without the header is treated as the oldest supported version.
+    pub fn detect(src: &str) -> Result<Self, Error> {
+        match v0::utils::get_lang_version(src) {
+            None => Ok(Version::V0),
+            Some(version) if version == "0" || version.starts_with("0.") => Ok(Version::V0),
+            Some(version) => Err(Error::UnsupportedVersion(version)),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum Error {
+    #[error("Unsupported language version: {0}")]
+    UnsupportedVersion(String),
+    #[error(transparent)]
+    V0(#[from] v0::errors::AiScriptError),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Script {
+    V0(Vec<v0::ast::Node>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    V0(v0::values::Value),
+}
+
+/// An engine of the language version which a source is written in.
+///
+/// Use [`Engine::detect`] to select the engine from the source, or construct a variant directly
+/// to configure the parser and the interpreter.
+pub enum Engine {
+    V0 {
+        parser: v0::Parser,
+        interpreter: v0::Interpreter,
+    },
+}
+
+impl Engine {
+    /// Returns an engine for the version of `src` with the default parser and an interpreter which has
+    /// the standard library but no I/O callbacks.
+    pub fn detect(src: &str) -> Result<Self, Error> {
+        match Version::detect(src)? {
+            Version::V0 => Ok(Engine::V0 {
+                parser: v0::Parser::default(),
+                interpreter: v0::Interpreter::new(
+                    [],
+                    None::<fn(_) -> _>,
+                    None::<fn(_) -> _>,
+                    None::<fn(_) -> _>,
+                    None,
+                ),
+            }),
+        }
+    }
+
+    pub fn version(&self) -> Version {
+        match self {
+            Engine::V0 { .. } => Version::V0,
+        }
+    }
+}
+
+impl Runtime for Engine {
+    type Script = Script;
+    type Value = Value;
+    type Error = Error;
+
+    fn parse(&self, src: &str) -> Result<Script, Error> {
+        match self {
+            Engine::V0 { parser, .. } => Ok(Script::V0(parser.parse(src)?)),
+        }
+    }
+
+    fn exec(&self, script: Script) -> BoxFuture<'_, Result<Option<Value>, Error>> {
+        async move {
+            match (self, script) {
+                (Engine::V0 { interpreter, .. }, Script::V0(script)) => {
+                    Ok(interpreter.exec(script).await?.map(Value::V0))
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn collect_metadata(&self, script: &Script) -> IndexMap<Option<String>, Option<Value>> {
+        match script {
+            Script::V0(script) => v0::Interpreter::collect_metadata(script.clone())
+                .into_iter()
+                .map(|(key, value)| (key, value.map(Value::V0)))
+                .collect(),
+        }
+    }
+}
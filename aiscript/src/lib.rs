@@ -1,3 +1,32 @@
+//! Rust implementation of [AiScript](https://github.com/aiscript-dev/aiscript).
+//!
+//! [`Engine`] selects the engine from the version header of a source.
+//!
+//! Each engine is enabled by the cargo feature of the same name (`v0`), which is enabled by default.
+//!
+//! # Example
+//!
+//! ```
+//! use aiscript::{Engine, Runtime, Script, Value};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), aiscript::Error> {
+//! let src = "/// @ 0.19.0\n1 + 1";
+//! let engine = Engine::detect(src)?;
+//! let script = engine.parse(src)?;
+//! let result = engine.exec(script).await?;
+//! assert_eq!(result, Some(Value::V0(aiscript::v0::values::Value::num(2))));
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "v0")]
+mod engine;
+
+#[cfg(feature = "v0")]
 pub mod v0 {
     pub use aiscript_v0::*;
 }
+
+#[cfg(feature = "v0")]
+pub use engine::{Engine, Error, Runtime, Script, Value, Version};